@@ -1,19 +1,72 @@
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
 use crossterm::event::{self, Event, KeyCode, KeyModifiers, MouseButton, MouseEventKind};
 use git2::Repository;
 use ratatui::layout::Rect;
-use ratatui::text::Line;
+use std::collections::{BTreeSet, HashMap};
 use std::io;
-use std::path::PathBuf;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::sync::mpsc::Receiver;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use crate::blame::{self, FileBlame};
+use crate::bulk;
+use crate::context::{self, ExpandedContext};
 use crate::diff;
-use crate::editor;
-use crate::highlight::Highlighter;
+use crate::editor::{self, EditorLaunch, SplitLayout};
+use crate::feedback;
+use crate::file_tree::{self, FileTreeRow};
+use crate::filter::{self, PathFilter};
+use crate::git;
+use crate::highlight::{self, HighlightCache, Highlighter};
+use crate::journal::{self, Journal};
+use crate::jumps::{JumpList, JumpPosition};
+use crate::messages::{Message, MessageHistory, MessageLevel};
+use crate::multiplexer::{self, Multiplexer, MultiplexerKind};
+use crate::notes;
+use crate::range::RangeCommit;
+use crate::recovery::{self, EditorSession};
+use crate::reminders::{self, PreviousComment};
+use crate::review;
+use crate::risk::{self, HunkRisk, RiskScores};
+use crate::session::Session;
 use crate::staging;
-use crate::types::{AppMode, FileDiff, FocusPanel, Hunk, HunkFeedback, HunkStatus};
+use crate::timing::{ReviewTimer, ReviewTiming};
+use crate::tmux;
+use crate::types::{
+    AppMode, DeltaStatus, DiffLine, FeedbackFormat, FeedbackKind, FileDiff, FocusPanel, Hunk,
+    HunkFeedback, HunkStatus, LineKind, OutputStyle, ReviewOrder, ReviewVerdict, StartPosition,
+    Verdict, ViewMode,
+};
 use crate::ui;
+use crate::ui::theme;
+
+/// Editor open on the review summary after a verdict was given.
+pub struct SummaryEditor {
+    pub tmpfile: tempfile::NamedTempFile,
+    pub rx: Receiver<()>,
+}
+
+/// What a comment not tied to a hunk is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoteTarget {
+    /// The whole review.
+    Review,
+    /// The whole of a file, by path.
+    File(String),
+    /// A commit of a `--range` review, by its label (see
+    /// [`RangeCommit::label`]).
+    Commit(String),
+}
+
+/// Editor open on a comment on a file, a commit or the whole review.
+pub struct NoteEditor {
+    pub tmpfile: tempfile::NamedTempFile,
+    pub rx: Receiver<()>,
+    pub target: NoteTarget,
+}
 
 /// Pending editor state while waiting for the user to close a tmux split pane.
 pub struct EditorState {
@@ -21,6 +74,9 @@ pub struct EditorState {
     pub rx: Receiver<()>,
     pub is_comment: bool,
     pub original_content: String,
+    /// Sidecar recording the hunk, for recovery if stagent dies before the
+    /// editor closes.
+    pub recovery: EditorSession,
 }
 
 /// Application state for the TUI.
@@ -32,18 +88,273 @@ pub struct App {
     pub feedback: Vec<HunkFeedback>,
     pub mode: AppMode,
     pub focus: FocusPanel,
-    pub message: Option<String>,
+    /// The status bar message, or the question of a prompt mode.
+    pub message: Option<Message>,
+    /// Every message shown, for the `M` overlay.
+    pub message_history: MessageHistory,
+    /// The hunk that last failed to stage and why, shown in `StageError`
+    /// mode.
+    pub stage_failure: Option<(JumpPosition, staging::StageFailure)>,
     pub no_stage: bool,
     /// Cached file list area for mouse click mapping.
     pub file_list_area: Rect,
+    /// First file list row on screen, moved to keep the cursor visible.
+    pub file_list_offset: usize,
     /// Cached diff view area for page scroll calculations.
     pub diff_view_area: Rect,
     /// Whether the UI needs to be redrawn.
     pub dirty: bool,
-    /// Cached highlighted lines: (file_index, per-hunk lines).
-    pub highlight_cache: Option<(usize, Vec<Vec<Line<'static>>>)>,
+    /// Highlighted lines of the files viewed last.
+    pub highlight_cache: HighlightCache,
     /// Pending key for multi-key sequences (e.g. `gg`).
     pub pending_key: Option<char>,
+    /// Previous session offered for resumption while in `ResumePrompt` mode.
+    pub resume_offer: Option<Session>,
+    /// Orphaned editor tempfiles offered for recovery in `RecoveryPrompt`
+    /// mode (after any resume prompt).
+    pub recovery_offer: Vec<EditorSession>,
+    /// Working directory of the repository under review, recorded with
+    /// editor tempfiles. `None` in patch mode.
+    pub workdir: Option<PathBuf>,
+    /// Unified or side-by-side diff layout.
+    pub view_mode: ViewMode,
+    /// Draw tabs and spaces as `→` and `·` in the diff view.
+    pub show_whitespace: bool,
+    /// Columns between tab stops in the diff view (`--tab-width`).
+    pub tab_width: usize,
+    /// Wrap lines too long for the diff view onto more rows (`w`).
+    pub wrap_lines: bool,
+    /// Columns of line content scrolled off to the left of the diff view,
+    /// when lines don't wrap.
+    pub h_scroll: usize,
+    /// Extra context shown around hunks, keyed by (file_index, hunk_index):
+    /// requested line count and the lines read from the file.
+    pub expanded_context: HashMap<(usize, usize), (usize, ExpandedContext)>,
+    /// Review notes from earlier sessions on the same content, keyed by path.
+    pub review_notes: HashMap<PathBuf, String>,
+    /// Text typed after `:` while in `Command` mode.
+    pub command_input: String,
+    /// First line of the help overlay shown, clamped when it is drawn.
+    pub help_scroll: usize,
+    /// Search typed after `/` in the help overlay; only matching keys are
+    /// listed.
+    pub help_query: String,
+    /// Whether keys in the help overlay go to `help_query`.
+    pub help_searching: bool,
+    /// Output format for feedback (`:format`).
+    pub feedback_format: FeedbackFormat,
+    /// How comments sit in diff-format feedback (`--output-style`).
+    pub output_style: OutputStyle,
+    /// Context lines around comments in diff-format feedback.
+    pub context_lines: usize,
+    /// Files hidden by `:files <glob>`, restored by `:files` or on quit.
+    pub hidden_files: Vec<FileDiff>,
+    /// Current directory relative to the repo root; `:files` globs are
+    /// resolved against it.
+    pub cwd_prefix: PathBuf,
+    /// Hunks marked for batch staging, keyed by (file_index, hunk_index).
+    pub marked: BTreeSet<(usize, usize)>,
+    /// Text typed into the inline comment box.
+    pub comment_input: String,
+    /// Line of the current hunk (index into `lines`) an inline comment
+    /// being typed will follow.
+    pub comment_anchor: Option<usize>,
+    /// Line mode: `j`/`k` move a cursor over the lines of the diff instead
+    /// of scrolling.
+    pub line_mode: bool,
+    /// Line of the current hunk (index into `lines`) under the cursor in
+    /// line mode.
+    pub cursor_line: usize,
+    /// Other end of a line selection dragged with the mouse or started
+    /// with `V`, as (file, hunk, line); the selection runs from here to the
+    /// line cursor.
+    pub line_anchor: Option<(usize, usize, usize)>,
+    /// Comments from the previous review round, badged on hunks where
+    /// they are still unaddressed.
+    pub previous_comments: Vec<PreviousComment>,
+    /// Whether the statistics panel is shown.
+    pub show_stats: bool,
+    /// Whether the blame panel is shown for the selected hunk.
+    pub show_blame: bool,
+    /// Blame of the files shown in the blame panel so far, or why it
+    /// failed. Cleared when the diff is refreshed.
+    pub blame: HashMap<PathBuf, Result<FileBlame, String>>,
+    /// Working tree file hashes at load time; staging is refused for files
+    /// that changed since. Also covers changed files outside the review,
+    /// so `--watch` only refreshes on new edits.
+    pub worktree_snapshot: staging::WorktreeSnapshot,
+    /// `--files` / `--exclude` selection, re-applied when the diff is
+    /// refreshed.
+    pub path_filter: PathFilter,
+    /// Order of the file list.
+    pub order: ReviewOrder,
+    /// `--auto-split` threshold, re-applied when the diff is refreshed.
+    pub auto_split: Option<usize>,
+    /// `--ignore-whitespace` / `--ignore-blank-lines`, re-applied when the
+    /// diff is refreshed.
+    pub diff_settings: git::DiffSettings,
+    /// Risk scores from git history, shown as hunk badges. Empty unless
+    /// ordering by risk.
+    pub risk: RiskScores,
+    /// Whether the file list is shown as a directory tree.
+    pub tree_view: bool,
+    /// Directories collapsed in the tree view, by repo-relative path.
+    pub collapsed_dirs: BTreeSet<PathBuf>,
+    /// Directory row under the file list cursor in the tree view; `None`
+    /// when the cursor is on the selected file.
+    pub selected_dir: Option<PathBuf>,
+    /// Fuzzy filter typed after `f`; the file list only shows matching
+    /// files. File indices elsewhere still refer to `files`.
+    pub file_filter: String,
+    /// Whether the diff came from a patch; see [`RunOptions::patch`].
+    pub reviewing_patch: bool,
+    /// Row of the file overview under the cursor.
+    pub overview_cursor: usize,
+    /// Feedback item (index into `feedback`) under the cursor in the
+    /// feedback panel.
+    pub feedback_cursor: usize,
+    /// Commits of a `--range` review, oldest first, for commit comments.
+    pub commits: Vec<RangeCommit>,
+    /// Commit (index into `commits`) under the cursor in the commits
+    /// panel.
+    pub commit_cursor: usize,
+    /// Files (indices into `files`) left out of the review in the
+    /// overview.
+    pub overview_excluded: BTreeSet<usize>,
+    /// Untracked files added with intent-to-add by refreshes during the
+    /// review, to undo on exit if nothing of them was staged.
+    pub intent_added: Vec<String>,
+    /// Refreshes leave untracked files out of the index (`--no-ita`,
+    /// `--no-untracked`).
+    pub no_intent_to_add: bool,
+    /// Hunks found by `:stage-matching` awaiting confirmation in
+    /// `BulkStagePrompt` mode, as (file, hunk) indices.
+    pub bulk_stage: Vec<(usize, usize)>,
+    /// Hunks jumped away from, for `Ctrl+o` / `Ctrl+i`. Cleared when the
+    /// file list changes.
+    pub jumps: JumpList,
+    /// Edit of the current hunk awaiting the choice in `StageEditPrompt`
+    /// mode: the feedback it would be, and the edited new side.
+    pub pending_edit: Option<(HunkFeedback, String)>,
+    /// Ask for a review verdict when quitting with feedback (`--verdict`).
+    pub ask_verdict: bool,
+    /// Ask for the verdict even without feedback (`--hook`), so that the
+    /// commit can be rejected.
+    pub require_verdict: bool,
+    /// The verdict given at the end of the review, output before the
+    /// feedback.
+    pub verdict: Option<ReviewVerdict>,
+    /// Time spent per hunk, if tracked (`--track-time`).
+    pub timer: Option<ReviewTimer>,
+    /// Journal each piece of feedback is appended to as it is captured,
+    /// so it survives a crash. `None` if it could not be created.
+    pub journal: Option<Journal>,
+    /// Where editors open: a tmux split, a zellij pane or this terminal.
+    pub multiplexer: Arc<dyn Multiplexer>,
+    /// Command run instead of `$EDITOR` and waited on, for editors that
+    /// return at once (`stagent.editorWaitCommand`).
+    pub editor_wait_command: Option<String>,
+}
+
+/// Settings for [`run`].
+#[derive(Debug, Default)]
+pub struct RunOptions {
+    /// Don't actually stage hunks (review-only mode).
+    pub no_stage: bool,
+    /// Initial selection (--start-file / --start-hunk).
+    pub start: Option<StartPosition>,
+    /// Previous session to restore.
+    pub resume: Option<Session>,
+    /// Ask before restoring `resume` instead of applying it directly.
+    pub confirm_resume: bool,
+    /// Initial diff layout.
+    pub view_mode: ViewMode,
+    /// Draw tabs and spaces in the diff view (`--show-whitespace`).
+    pub show_whitespace: bool,
+    /// Columns between tab stops in the diff view (`--tab-width`).
+    pub tab_width: usize,
+    /// Initial feedback output format.
+    pub feedback_format: FeedbackFormat,
+    /// How comments sit in diff-format feedback.
+    pub output_style: OutputStyle,
+    /// Context lines around comments in diff-format feedback.
+    pub context_lines: usize,
+    /// Current directory relative to the repo root (see [`git::cwd_prefix`]).
+    pub cwd_prefix: PathBuf,
+    /// Feedback file from the previous review round, for unaddressed
+    /// comment reminders. Defaults to the latest archived feedback.
+    pub previous_feedback: Option<PathBuf>,
+    /// Refresh the diff when the working tree changes (`--watch`).
+    pub watch: bool,
+    /// `--files` / `--exclude` selection the initial diff was filtered with.
+    pub path_filter: PathFilter,
+    /// Order of the file list (`--order`).
+    pub order: ReviewOrder,
+    /// Split hunks longer than this many lines at load (`--auto-split`).
+    pub auto_split: Option<usize>,
+    /// Changes the diff was computed without (`--ignore-whitespace`,
+    /// `--ignore-blank-lines`).
+    pub diff_settings: git::DiffSettings,
+    /// Don't add untracked files with intent-to-add when refreshing
+    /// (`--no-ita`, `--no-untracked`).
+    pub no_intent_to_add: bool,
+    /// Start with the file overview (`--overview`).
+    pub overview: bool,
+    /// Ask for a review verdict and summary when quitting with feedback
+    /// (`--verdict`).
+    pub verdict: bool,
+    /// Ask for the verdict even without feedback (`--hook`).
+    pub require_verdict: bool,
+    /// Time the review per hunk and file (`--track-time`).
+    pub track_time: bool,
+    /// The diff was read from a patch (`--patch`, `--patch-file`) rather
+    /// than computed from the working tree, so `r` can't refresh it.
+    pub patch: bool,
+    /// Directory for the feedback journal (see [`journal`]), normally
+    /// `.git/stagent`. Interrupted journals found there are archived.
+    pub journal_dir: Option<PathBuf>,
+    /// Where editors open (`--multiplexer`, resolved).
+    pub multiplexer: MultiplexerKind,
+    /// How tmux lays out editor panes (`--split`, `--split-size`).
+    pub split: SplitLayout,
+    /// See [`App::editor_wait_command`].
+    pub editor_wait_command: Option<String>,
+    /// Commits of the range under review (`--range`).
+    pub commits: Vec<RangeCommit>,
+}
+
+/// How often `--watch` checks the working tree for changes.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Rows kept above a hunk scrolled to that is too tall to center.
+const HUNK_SCROLL_MARGIN: u32 = 2;
+
+/// Columns `Shift+←` / `Shift+→` scroll long lines sideways by.
+const H_SCROLL_STEP: isize = 8;
+
+/// The `:` commands by name, with their argument, for completion.
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("context", "<lines>"),
+    ("files", "[glob]"),
+    ("format", "diff|json|gerrit"),
+    ("patch", "<file>"),
+    ("q", ""),
+    ("q!", ""),
+    ("quit", ""),
+    ("skip-file", ""),
+    ("sort", "path|pending|lines|risk"),
+    ("stage-file", ""),
+    ("stage-matching", "<regex>"),
+    ("theme", "dark|light|auto|<name>"),
+    ("w", "<file>"),
+    ("write", "<file>"),
+];
+
+/// What the event loop should do after a `:` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandOutcome {
+    Continue,
+    Quit,
 }
 
 /// Return the path to the help-shown marker file (`~/.config/stagent/help_shown`).
@@ -88,13 +399,491 @@ impl App {
             mode: initial_mode,
             focus: FocusPanel::DiffView,
             message: None,
+            message_history: MessageHistory::default(),
+            stage_failure: None,
             no_stage,
             file_list_area: Rect::default(),
+            file_list_offset: 0,
             diff_view_area: Rect::default(),
             dirty: true,
-            highlight_cache: None,
+            highlight_cache: HighlightCache::default(),
             pending_key: None,
+            resume_offer: None,
+            recovery_offer: Vec::new(),
+            workdir: None,
+            view_mode: ViewMode::Unified,
+            show_whitespace: false,
+            tab_width: ui::diff_view::DEFAULT_TAB_WIDTH,
+            wrap_lines: false,
+            h_scroll: 0,
+            expanded_context: HashMap::new(),
+            review_notes: HashMap::new(),
+            command_input: String::new(),
+            help_scroll: 0,
+            help_query: String::new(),
+            help_searching: false,
+            feedback_format: FeedbackFormat::Diff,
+            output_style: OutputStyle::Annotated,
+            context_lines: feedback::DEFAULT_CONTEXT_LINES,
+            hidden_files: Vec::new(),
+            cwd_prefix: PathBuf::new(),
+            comment_input: String::new(),
+            comment_anchor: None,
+            line_mode: false,
+            line_anchor: None,
+            cursor_line: 0,
+            previous_comments: Vec::new(),
+            show_stats: false,
+            show_blame: false,
+            blame: HashMap::new(),
+            worktree_snapshot: HashMap::new(),
+            path_filter: PathFilter::default(),
+            order: ReviewOrder::Path,
+            auto_split: None,
+            diff_settings: git::DiffSettings::default(),
+            risk: RiskScores::default(),
+            tree_view: false,
+            collapsed_dirs: BTreeSet::new(),
+            selected_dir: None,
+            file_filter: String::new(),
+            reviewing_patch: false,
+            overview_cursor: 0,
+            feedback_cursor: 0,
+            commits: Vec::new(),
+            commit_cursor: 0,
+            overview_excluded: BTreeSet::new(),
+            intent_added: Vec::new(),
+            no_intent_to_add: false,
+            bulk_stage: Vec::new(),
+            jumps: JumpList::default(),
+            pending_edit: None,
+            ask_verdict: false,
+            require_verdict: false,
+            verdict: None,
+            timer: None,
+            journal: None,
+            marked: BTreeSet::new(),
+            multiplexer: multiplexer::system(MultiplexerKind::Tmux, SplitLayout::default()),
+            editor_wait_command: None,
+        }
+    }
+
+    /// Offer to continue a previous session. Switches to `ResumePrompt` mode
+    /// until the user accepts or declines.
+    pub fn offer_resume(&mut self, session: Session) {
+        self.resume_offer = Some(session);
+        self.mode = AppMode::ResumePrompt;
+        self.dirty = true;
+    }
+
+    /// Show the file overview, where files can be left out before the
+    /// hunk-by-hunk review starts.
+    pub fn start_overview(&mut self) {
+        if self.files.is_empty() {
+            return;
+        }
+        self.overview_cursor = 0;
+        self.mode = AppMode::Overview;
+        self.dirty = true;
+    }
+
+    /// Handle a key in the file overview: `j`/`k` move, space toggles
+    /// whether the file is reviewed, `a` includes every file and Enter
+    /// starts the review.
+    pub fn handle_overview_key(&mut self, code: KeyCode) {
+        self.dirty = true;
+        match code {
+            KeyCode::Char('j') | KeyCode::Down if self.overview_cursor + 1 < self.files.len() => {
+                self.overview_cursor += 1;
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.overview_cursor = self.overview_cursor.saturating_sub(1);
+            }
+            KeyCode::Char(' ') | KeyCode::Char('x') => {
+                let idx = self.overview_cursor;
+                if !self.overview_excluded.remove(&idx) {
+                    self.overview_excluded.insert(idx);
+                }
+            }
+            KeyCode::Char('a') => self.overview_excluded.clear(),
+            KeyCode::Enter => self.finish_overview(),
+            _ => {}
+        }
+    }
+
+    /// Leave the overview: files left out are hidden like `:files` hides
+    /// them (`:files` brings them back) and the review starts.
+    fn finish_overview(&mut self) {
+        if self.overview_excluded.len() >= self.files.len() {
+            self.warn("Every file is left out; include at least one");
+            return;
+        }
+        let excluded = std::mem::take(&mut self.overview_excluded);
+        if !excluded.is_empty() {
+            let total = self.files.len();
+            let (hidden, shown): (Vec<_>, Vec<_>) = std::mem::take(&mut self.files)
+                .into_iter()
+                .enumerate()
+                .partition(|(i, _)| excluded.contains(i));
+            self.files = shown.into_iter().map(|(_, f)| f).collect();
+            self.hidden_files.extend(hidden.into_iter().map(|(_, f)| f));
+            self.selected_file = 0;
+            self.selected_hunk = 0;
+            self.scroll_offset = 0;
+            self.highlight_cache.clear();
+            self.expanded_context.clear();
+            self.marked.clear();
+            self.jumps.clear();
+            self.info(format!(
+                "Reviewing {} of {} files (:files shows all)",
+                self.files.len(),
+                total
+            ));
+        }
+        self.mode = AppMode::Browsing;
+    }
+
+    /// Hunks not yet staged or skipped, hidden files included.
+    pub fn pending_hunk_count(&self) -> usize {
+        self.files
+            .iter()
+            .chain(&self.hidden_files)
+            .flat_map(|f| &f.hunks)
+            .filter(|h| h.status == HunkStatus::Pending)
+            .count()
+    }
+
+    /// Handle `q`: returns whether to quit right away, i.e. when nothing
+    /// is pending and there is no feedback. Otherwise asks first (see
+    /// [`handle_confirm_quit_key`](Self::handle_confirm_quit_key)).
+    pub fn request_quit(&mut self) -> bool {
+        if self.pending_hunk_count() == 0 && self.feedback.is_empty() {
+            return true;
+        }
+        self.mode = AppMode::ConfirmQuit;
+        self.dirty = true;
+        false
+    }
+
+    /// Handle a key in the quit confirmation: `y`/`q`/Enter quit, writing
+    /// the feedback as usual (after asking for a verdict, see
+    /// [`finish_review`](Self::finish_review)), `d` quits discarding the
+    /// feedback, `n`/Esc go back to reviewing. Returns whether to quit.
+    pub fn handle_confirm_quit_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('y') | KeyCode::Char('q') | KeyCode::Enter => self.finish_review(),
+            KeyCode::Char('d') => {
+                self.feedback.clear();
+                true
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                self.mode = AppMode::Browsing;
+                self.dirty = true;
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the review is over and stagent can quit. With `--verdict`,
+    /// asks for a verdict in `VerdictPrompt` mode first if there is
+    /// feedback, or always with `--hook`.
+    pub fn finish_review(&mut self) -> bool {
+        let nothing_to_judge = self.feedback.is_empty() && !self.require_verdict;
+        if !self.ask_verdict || nothing_to_judge || self.verdict.is_some() {
+            return true;
+        }
+        self.mode = AppMode::VerdictPrompt;
+        self.dirty = true;
+        false
+    }
+
+    /// Handle a key in the verdict prompt: `a` approves, `r` requests
+    /// changes and `c` just comments, `s` skips the verdict, Esc goes back
+    /// to reviewing. Returns `true` once the choice is made; a summary is
+    /// then asked for if a verdict was given.
+    pub fn handle_verdict_key(&mut self, code: KeyCode) -> bool {
+        let verdict = match code {
+            KeyCode::Char('a') => Verdict::Approve,
+            KeyCode::Char('r') => Verdict::RequestChanges,
+            KeyCode::Char('c') => Verdict::Comment,
+            KeyCode::Char('s') => return true,
+            KeyCode::Esc => {
+                self.mode = AppMode::Browsing;
+                self.dirty = true;
+                return false;
+            }
+            _ => return false,
+        };
+        self.verdict = Some(ReviewVerdict {
+            verdict,
+            summary: None,
+        });
+        true
+    }
+
+    /// Open the editor on the summary for the verdict given.
+    pub fn start_summary_editor(&mut self) -> Result<SummaryEditor> {
+        let verdict = self
+            .verdict
+            .as_ref()
+            .map_or(Verdict::Comment, |v| v.verdict);
+        let tmpfile = editor::prepare_summary_tempfile(verdict)?;
+        let rx = self.open_editor(&tmpfile.path().to_string_lossy(), None)?;
+        self.mode = AppMode::WaitingForEditor;
+        self.dirty = true;
+        Ok(SummaryEditor { tmpfile, rx })
+    }
+
+    /// Take the summary from the closed summary editor.
+    pub fn finish_summary(&mut self, tmpfile_path: &Path) {
+        let content = std::fs::read_to_string(tmpfile_path).unwrap_or_default();
+        if let Some(verdict) = self.verdict.as_mut() {
+            verdict.summary = editor::parse_summary(&content);
+        }
+    }
+
+    /// Open the editor on a comment on the current file, pre-filled with
+    /// its existing file comment. `None` if no file is selected.
+    pub fn start_file_comment(&mut self) -> Result<Option<NoteEditor>> {
+        let Some(file) = self.current_file() else {
+            return Ok(None);
+        };
+        let file_path = file.path.to_string_lossy().to_string();
+        let target = NoteTarget::File(file_path.clone());
+        let existing = self.note_index(&target).map(|i| &self.feedback[i]);
+        let tmpfile = editor::prepare_file_comment_tempfile(&file_path, existing)?;
+        self.open_note_editor(tmpfile, target).map(Some)
+    }
+
+    /// Open the editor on a comment on the review as a whole, pre-filled
+    /// with the existing one and listing the files reviewed.
+    pub fn start_review_comment(&mut self) -> Result<NoteEditor> {
+        let existing = self
+            .note_index(&NoteTarget::Review)
+            .map(|i| &self.feedback[i]);
+        let tmpfile =
+            editor::prepare_review_comment_tempfile(&self.reviewed_files_summary(), existing)?;
+        self.open_note_editor(tmpfile, NoteTarget::Review)
+    }
+
+    /// Open the editor on a comment on the commit under the cursor in the
+    /// commits panel, pre-filled with its existing comment. `None` without
+    /// commits.
+    pub fn start_commit_comment(&mut self) -> Result<Option<NoteEditor>> {
+        let Some(commit) = self.commits.get(self.commit_cursor) else {
+            return Ok(None);
+        };
+        let label = commit.label();
+        let target = NoteTarget::Commit(label.clone());
+        let existing = self.note_index(&target).map(|i| &self.feedback[i]);
+        let tmpfile = editor::prepare_commit_comment_tempfile(&label, existing)?;
+        self.open_note_editor(tmpfile, target).map(Some)
+    }
+
+    fn open_note_editor(
+        &mut self,
+        tmpfile: tempfile::NamedTempFile,
+        target: NoteTarget,
+    ) -> Result<NoteEditor> {
+        let rx = self.open_editor(&tmpfile.path().to_string_lossy(), None)?;
+        self.mode = AppMode::WaitingForEditor;
+        self.dirty = true;
+        Ok(NoteEditor {
+            tmpfile,
+            rx,
+            target,
+        })
+    }
+
+    /// One line per file for the review comment editor: its path and how
+    /// many of its hunks are in each status.
+    fn reviewed_files_summary(&self) -> Vec<String> {
+        const STATUSES: [(HunkStatus, &str); 5] = [
+            (HunkStatus::Staged, "staged"),
+            (HunkStatus::Skipped, "skipped"),
+            (HunkStatus::Edited, "edited"),
+            (HunkStatus::Commented, "commented"),
+            (HunkStatus::Pending, "pending"),
+        ];
+        self.files
+            .iter()
+            .map(|file| {
+                let counts: Vec<String> = STATUSES
+                    .iter()
+                    .filter_map(|&(status, label)| {
+                        let n = file.hunks.iter().filter(|h| h.status == status).count();
+                        (n > 0).then(|| format!("{} {}", n, label))
+                    })
+                    .collect();
+                format!("{}: {}", file.path.display(), counts.join(", "))
+            })
+            .collect()
+    }
+
+    /// Take the comment from a closed file, commit or review comment
+    /// editor, replacing the previous one. Returns whether a comment was
+    /// captured.
+    pub fn finish_note(&mut self, tmpfile_path: &Path, target: &NoteTarget) -> bool {
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+        let content = std::fs::read_to_string(tmpfile_path).unwrap_or_default();
+        let parsed = match target {
+            NoteTarget::Review => editor::parse_review_comment(&content),
+            NoteTarget::File(path) => editor::parse_file_comment(&content, path),
+            NoteTarget::Commit(label) => editor::parse_commit_comment(&content, label),
+        };
+        let Some(fb) = parsed else {
+            return false;
+        };
+        let index = match self.note_index(target) {
+            Some(i) => {
+                self.feedback[i] = fb;
+                i
+            }
+            None => {
+                self.feedback.push(fb);
+                self.feedback.len() - 1
+            }
+        };
+        self.journal_feedback(index..index + 1);
+        true
+    }
+
+    /// Index in the feedback of the comment on `target`.
+    fn note_index(&self, target: &NoteTarget) -> Option<usize> {
+        self.feedback.iter().position(|fb| match target {
+            NoteTarget::Review => fb.kind == FeedbackKind::ReviewComment,
+            NoteTarget::File(path) => fb.kind == FeedbackKind::FileComment && fb.file_path == *path,
+            NoteTarget::Commit(label) => {
+                fb.kind == FeedbackKind::CommitComment && fb.hunk_header == *label
+            }
+        })
+    }
+
+    /// Show the commits panel listing the commits of a `--range` review.
+    pub fn open_commits_panel(&mut self) {
+        self.dirty = true;
+        if self.commits.is_empty() {
+            self.warn("Commit comments need a --range review");
+            return;
+        }
+        self.commit_cursor = self.commit_cursor.min(self.commits.len() - 1);
+        self.mode = AppMode::CommitsPanel;
+    }
+
+    /// Handle a key in the commits panel other than the ones commenting:
+    /// move or close the panel.
+    pub fn handle_commits_panel_key(&mut self, code: KeyCode) {
+        self.dirty = true;
+        match code {
+            KeyCode::Char('j') | KeyCode::Down if self.commit_cursor + 1 < self.commits.len() => {
+                self.commit_cursor += 1;
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.commit_cursor = self.commit_cursor.saturating_sub(1);
+            }
+            KeyCode::Esc | KeyCode::Char('O') | KeyCode::Char('q') => {
+                self.mode = AppMode::Browsing;
+            }
+            _ => {}
+        }
+    }
+
+    /// Accept the pending resume offer, restoring its hunk statuses.
+    pub fn accept_resume(&mut self) {
+        if let Some(session) = self.resume_offer.take() {
+            let before = self.feedback.len();
+            let restored = session.apply(self);
+            self.journal_feedback(before..self.feedback.len());
+            self.scroll_to_selected_hunk();
+            self.info(format!("Resumed previous review ({} hunks)", restored));
+        }
+        self.mode = AppMode::Browsing;
+        self.show_recovery_prompt();
+        self.dirty = true;
+    }
+
+    /// Decline the pending resume offer and start from scratch.
+    pub fn decline_resume(&mut self) {
+        self.resume_offer = None;
+        self.mode = AppMode::Browsing;
+        self.info("Starting a fresh review");
+        self.show_recovery_prompt();
+        self.dirty = true;
+    }
+
+    /// Offer to recover orphaned editor tempfiles (see [`recovery`]).
+    /// Waits for a pending resume prompt to be answered first.
+    pub fn offer_recovery(&mut self, sessions: Vec<EditorSession>) {
+        self.recovery_offer = sessions;
+        if self.mode != AppMode::ResumePrompt {
+            self.show_recovery_prompt();
+        }
+    }
+
+    /// Switch to `RecoveryPrompt` mode if tempfiles are waiting.
+    fn show_recovery_prompt(&mut self) {
+        if self.recovery_offer.is_empty() {
+            return;
+        }
+        let (comments, edits): (Vec<_>, Vec<_>) =
+            self.recovery_offer.iter().partition(|s| s.is_comment);
+        self.prompt(format!(
+            "A stagent session exited with {} comment(s) and {} edit(s) open in the editor. \
+             Recover them as feedback? (y/n)",
+            comments.len(),
+            edits.len()
+        ));
+        self.mode = AppMode::RecoveryPrompt;
+        self.dirty = true;
+    }
+
+    /// Add the content of the offered tempfiles as feedback, marking the
+    /// hunks they belong to, and delete the tempfiles.
+    pub fn accept_recovery(&mut self) {
+        let mut recovered = 0;
+        for session in std::mem::take(&mut self.recovery_offer) {
+            if let Some(fb) = session.recover() {
+                let status = if session.is_comment {
+                    HunkStatus::Commented
+                } else {
+                    HunkStatus::Edited
+                };
+                if let Some(hunk) = self
+                    .files
+                    .iter_mut()
+                    .filter(|f| f.path.to_string_lossy() == session.file_path)
+                    .flat_map(|f| &mut f.hunks)
+                    .find(|h| {
+                        h.header == session.hunk_header
+                            || h.lines
+                                .iter()
+                                .map(|l| &l.content)
+                                .eq(session.hunk_lines.iter().map(|l| &l.content))
+                    })
+                {
+                    hunk.status = status;
+                }
+                self.feedback.push(fb);
+                self.journal_feedback(self.feedback.len() - 1..self.feedback.len());
+                recovered += 1;
+            }
+            session.discard();
         }
+        self.info(format!("Recovered {} feedback item(s)", recovered));
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+    }
+
+    /// Delete the offered tempfiles without recovering them.
+    pub fn decline_recovery(&mut self) {
+        for session in std::mem::take(&mut self.recovery_offer) {
+            session.discard();
+        }
+        self.info("Discarded unsaved editor content");
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
     }
 
     /// Get the currently selected file, if any.
@@ -108,32 +897,43 @@ impl App {
             .and_then(|f| f.hunks.get(self.selected_hunk))
     }
 
-    /// Select the next file (wraps around).
+    /// What the current hunk stands for if it is a placeholder that can
+    /// only be staged or skipped (see [`FileDiff::placeholder_noun`]).
+    fn current_placeholder(&self) -> Option<&'static str> {
+        self.current_file()?.placeholder_noun(self.selected_hunk)
+    }
+
+    /// Select the next file shown in the file list (wraps around).
     pub fn select_next_file(&mut self) {
-        if self.files.is_empty() {
+        let shown = self.shown_files();
+        let Some(&first) = shown.first() else {
             return;
-        }
-        if self.selected_file + 1 < self.files.len() {
-            self.selected_file += 1;
-        } else {
-            self.selected_file = 0;
-        }
+        };
+        self.selected_file = shown
+            .into_iter()
+            .find(|&i| i > self.selected_file)
+            .unwrap_or(first);
+        self.selected_dir = None;
         self.selected_hunk = 0;
+        self.cursor_line = 0;
         self.scroll_offset = 0;
         self.dirty = true;
     }
 
-    /// Select the previous file (wraps around).
+    /// Select the previous file shown in the file list (wraps around).
     pub fn select_prev_file(&mut self) {
-        if self.files.is_empty() {
+        let shown = self.shown_files();
+        let Some(&last) = shown.last() else {
             return;
-        }
-        if self.selected_file > 0 {
-            self.selected_file -= 1;
-        } else {
-            self.selected_file = self.files.len() - 1;
-        }
+        };
+        self.selected_file = shown
+            .into_iter()
+            .rev()
+            .find(|&i| i < self.selected_file)
+            .unwrap_or(last);
+        self.selected_dir = None;
         self.selected_hunk = 0;
+        self.cursor_line = 0;
         self.scroll_offset = 0;
         self.dirty = true;
     }
@@ -152,6 +952,8 @@ impl App {
                 self.selected_hunk = 0;
             }
         }
+        self.selected_dir = None;
+        self.cursor_line = 0;
         self.scroll_to_selected_hunk();
         self.dirty = true;
     }
@@ -166,13 +968,122 @@ impl App {
                 self.selected_hunk = file.hunks.len().saturating_sub(1);
             }
         }
+        self.selected_dir = None;
+        self.cursor_line = 0;
+        self.scroll_to_selected_hunk();
+        self.dirty = true;
+    }
+
+    /// Jump to the next pending hunk in the files shown, wrapping around
+    /// after the last one.
+    pub fn select_next_pending_hunk(&mut self) {
+        let positions = self.pending_positions();
+        let current = (self.selected_file, self.selected_hunk);
+        let next = positions
+            .iter()
+            .copied()
+            .find(|&p| p > current)
+            .or_else(|| positions.first().copied());
+        self.jump_to_pending(next);
+    }
+
+    /// Jump to the previous pending hunk in the files shown, wrapping
+    /// around before the first one.
+    pub fn select_prev_pending_hunk(&mut self) {
+        let positions = self.pending_positions();
+        let current = (self.selected_file, self.selected_hunk);
+        let prev = positions
+            .iter()
+            .rev()
+            .copied()
+            .find(|&p| p < current)
+            .or_else(|| positions.last().copied());
+        self.jump_to_pending(prev);
+    }
+
+    /// `(file, hunk)` of every pending hunk in the files shown, in order.
+    fn pending_positions(&self) -> Vec<(usize, usize)> {
+        self.shown_files()
+            .into_iter()
+            .flat_map(|fi| {
+                self.files[fi]
+                    .hunks
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, h)| h.status == HunkStatus::Pending)
+                    .map(move |(hi, _)| (fi, hi))
+            })
+            .collect()
+    }
+
+    fn jump_to_pending(&mut self, target: Option<(usize, usize)>) {
+        self.dirty = true;
+        let Some(target) = target else {
+            self.warn("No pending hunks");
+            return;
+        };
+        self.record_jump(target);
+        self.go_to(target);
+    }
+
+    /// The selected (file, hunk).
+    fn position(&self) -> JumpPosition {
+        (self.selected_file, self.selected_hunk)
+    }
+
+    /// Select hunk `hunk_idx` of file `file_idx`, scrolled into view.
+    fn go_to(&mut self, (file_idx, hunk_idx): JumpPosition) {
+        if file_idx != self.selected_file {
+            self.scroll_offset = 0;
+        }
+        self.selected_file = file_idx;
+        self.selected_hunk = hunk_idx;
+        self.selected_dir = None;
+        self.cursor_line = 0;
         self.scroll_to_selected_hunk();
         self.dirty = true;
     }
 
+    /// Remember the current position in the jump list before jumping to
+    /// `target`, unless that is where the selection already is.
+    fn record_jump(&mut self, target: JumpPosition) {
+        if target != self.position() {
+            self.jumps.record(self.position());
+        }
+    }
+
+    /// Go back to the hunk the last jump left (`Ctrl+o`).
+    pub fn jump_back(&mut self) {
+        match self.jumps.back(self.position()) {
+            Some(target) => self.go_to_jump(target),
+            None => self.warn("No older jumps"),
+        }
+        self.dirty = true;
+    }
+
+    /// Undo [`jump_back`](Self::jump_back) (`Ctrl+i`).
+    pub fn jump_forward(&mut self) {
+        match self.jumps.forward(self.position()) {
+            Some(target) => self.go_to_jump(target),
+            None => self.warn("No newer jumps"),
+        }
+        self.dirty = true;
+    }
+
+    /// [`go_to`](Self::go_to) a jump list position, clamped to the hunks
+    /// there now: splitting hunks doesn't clear the list.
+    fn go_to_jump(&mut self, (file_idx, hunk_idx): JumpPosition) {
+        let Some(file) = self.files.get(file_idx) else {
+            return;
+        };
+        let hunk_idx = hunk_idx.min(file.hunks.len().saturating_sub(1));
+        self.go_to((file_idx, hunk_idx));
+    }
+
     /// Scroll the diff view down.
     pub fn scroll_down(&mut self) {
         self.scroll_offset = self.scroll_offset.saturating_add(1);
+        self.clamp_scroll();
         self.dirty = true;
     }
 
@@ -188,15 +1099,54 @@ impl App {
         self.dirty = true;
     }
 
+    /// The hunks of the selected file with a row among `rows` of the diff
+    /// view, each with the number of its body rows down to the end of
+    /// `rows`.
+    pub fn visible_hunks(&self, rows: Range<usize>) -> Vec<(usize, usize)> {
+        let Some(file) = self.files.get(self.selected_file) else {
+            return Vec::new();
+        };
+        let mut visible = Vec::new();
+        let mut top = 0;
+        for (idx, hunk) in file.hunks.iter().enumerate() {
+            let body = self.hunk_body_rows(self.selected_file, idx, hunk) as usize;
+            // Header, body and separator
+            let bottom = top + body + 2;
+            if top >= rows.end {
+                break;
+            }
+            if bottom > rows.start {
+                visible.push((idx, (rows.end - top - 1).min(body)));
+            }
+            top = bottom;
+        }
+        visible
+    }
+
+    /// Pack the lines of the large files (see [`highlight::is_large`]) not
+    /// viewed recently, those neither selected nor highlighted in the
+    /// cache, so a big review doesn't hold every one of their lines. They
+    /// are unpacked when next read.
+    pub fn pack_unviewed_files(&mut self) {
+        for (idx, file) in self.files.iter_mut().enumerate() {
+            let viewed = idx == self.selected_file || self.highlight_cache.get(idx).is_some();
+            if !viewed && highlight::is_large(&file.hunks) {
+                for hunk in &mut file.hunks {
+                    hunk.lines.pack();
+                }
+            }
+        }
+    }
+
     /// Compute the total number of rendered lines for the current file's diff.
     /// Each hunk has: 1 header + N lines + 1 separator (except last hunk has no separator).
     pub fn total_content_lines(&self) -> u32 {
         match self.files.get(self.selected_file) {
             Some(file) if !file.hunks.is_empty() => {
                 let mut total: u32 = 0;
-                for hunk in &file.hunks {
+                for (idx, hunk) in file.hunks.iter().enumerate() {
                     total += 1; // header
-                    total += hunk.lines.len() as u32;
+                    total += self.hunk_body_rows(self.selected_file, idx, hunk);
                     total += 1; // separator
                 }
                 total - 1 // last hunk has no separator
@@ -208,17 +1158,82 @@ impl App {
     /// Scroll to the bottom of the diff view.
     /// Positions the view so the last content line is at the bottom of the viewport.
     pub fn scroll_to_bottom(&mut self) {
-        let total = self.total_content_lines();
+        self.scroll_offset = self.max_scroll();
+        self.dirty = true;
+    }
+
+    /// Largest useful scroll offset: the last content line at the bottom
+    /// of the viewport.
+    pub fn max_scroll(&self) -> u32 {
         // Inner height = area height minus 2 for block borders
         let visible = self.diff_view_area.height.saturating_sub(2) as u32;
-        self.scroll_offset = total.saturating_sub(visible);
-        self.dirty = true;
+        self.total_content_lines().saturating_sub(visible)
+    }
+
+    /// Run the review timer for the selected hunk at `now`, or pause it
+    /// while the help or the overview is shown.
+    pub fn track_time(&mut self, now: Instant) {
+        let Some(timer) = &mut self.timer else {
+            return;
+        };
+        let hunk = match self.mode {
+            AppMode::Help | AppMode::Overview => None,
+            _ => self.files.get(self.selected_file).and_then(|file| {
+                file.hunks
+                    .get(self.selected_hunk)
+                    .map(|hunk| (file.path.to_str().unwrap_or_default(), hunk.header.as_str()))
+            }),
+        };
+        timer.track(hunk, now);
+    }
+
+    /// The time spent so far, if tracked (`--track-time`).
+    pub fn review_timing(&self) -> Option<ReviewTiming> {
+        self.timer
+            .as_ref()
+            .map(|timer| timer.timing(Instant::now()))
+    }
+
+    /// Keep the diff view from scrolling past the end of the content.
+    pub fn clamp_scroll(&mut self) {
+        self.scroll_offset = self.scroll_offset.min(self.max_scroll());
+    }
+
+    /// Row of the file list cursor and the number of rows in the list. The
+    /// cursor is `None` when the selected file is filtered out.
+    pub fn file_list_position(&self) -> (Option<usize>, usize) {
+        if self.tree_view {
+            let rows = self.file_tree_rows();
+            (Some(self.file_tree_cursor(&rows)), rows.len())
+        } else {
+            let shown = self.shown_files();
+            let cursor = shown.iter().position(|&i| i == self.selected_file);
+            (cursor, shown.len())
+        }
+    }
+
+    /// Scroll the file list just enough to keep the cursor on screen, and
+    /// never past the last row.
+    pub fn sync_file_list_offset(&mut self) {
+        // Inner height = area height minus 2 for block borders
+        let visible = (self.file_list_area.height.saturating_sub(2) as usize).max(1);
+        let (cursor, rows) = self.file_list_position();
+        let mut offset = self.file_list_offset;
+        if let Some(cursor) = cursor {
+            if cursor < offset {
+                offset = cursor;
+            } else if cursor >= offset + visible {
+                offset = cursor + 1 - visible;
+            }
+        }
+        self.file_list_offset = offset.min(rows.saturating_sub(visible));
     }
 
     /// Scroll half a page down in the diff view.
     pub fn scroll_half_page_down(&mut self) {
         let amount = (self.diff_view_area.height / 2).max(1) as u32;
         self.scroll_offset = self.scroll_offset.saturating_add(amount);
+        self.clamp_scroll();
         self.dirty = true;
     }
 
@@ -233,6 +1248,7 @@ impl App {
     pub fn scroll_full_page_down(&mut self) {
         let amount = self.diff_view_area.height.max(1) as u32;
         self.scroll_offset = self.scroll_offset.saturating_add(amount);
+        self.clamp_scroll();
         self.dirty = true;
     }
 
@@ -243,70 +1259,403 @@ impl App {
         self.dirty = true;
     }
 
-    /// Toggle focus between file list and diff view.
-    pub fn toggle_focus(&mut self) {
-        self.focus = match self.focus {
-            FocusPanel::FileList => FocusPanel::DiffView,
-            FocusPanel::DiffView => FocusPanel::FileList,
+    /// Number of rendered rows for a hunk's lines (including expanded
+    /// context) in the current view mode.
+    pub fn hunk_body_rows(&self, file_idx: usize, hunk_idx: usize, hunk: &Hunk) -> u32 {
+        let body = match self.view_mode {
+            _ if self.wrap_lines => self.body_row_heights(file_idx, hunk).iter().sum(),
+            ViewMode::Unified => hunk.lines.len(),
+            ViewMode::SideBySide => ui::diff_view::side_by_side_rows(hunk).len(),
         };
-        self.dirty = true;
+        let extra = self
+            .expanded_context
+            .get(&(file_idx, hunk_idx))
+            .map_or(0, |(_, ctx)| ctx.len());
+        (body + extra) as u32
     }
 
-    /// Compute the line offset for the current hunk caused by previously staged
-    /// hunks in the same file. Each staged hunk that appears before this one
-    /// shifts line numbers by (new_lines - old_lines).
-    fn compute_line_offset(&self, file_idx: usize, hunk_idx: usize) -> i32 {
-        let mut offset: i32 = 0;
-        if let Some(file) = self.files.get(file_idx) {
-            for (idx, h) in file.hunks.iter().enumerate() {
-                if idx == hunk_idx {
-                    break;
-                }
-                if h.status == HunkStatus::Staged {
-                    offset += h.new_lines as i32 - h.old_lines as i32;
-                }
+    /// Rows taken by each line of `hunk` of file `file_idx` (each row pair
+    /// side by side) in the diff view as it is now.
+    fn body_row_heights(&self, file_idx: usize, hunk: &Hunk) -> Vec<usize> {
+        match self.files.get(file_idx) {
+            Some(file) => {
+                ui::diff_view::body_row_heights(self, file, hunk, self.diff_view_area.width)
             }
+            None => Vec::new(),
         }
-        offset
     }
 
-    /// Access the current pending hunk mutably and execute a closure on it.
-    /// Returns `true` if the closure was executed (hunk exists and is Pending).
-    fn with_current_pending_hunk<F>(&mut self, repo: Option<&Repository>, f: F) -> Result<bool>
-    where
-        F: FnOnce(&mut Self, usize, usize, Option<&Repository>) -> Result<()>,
-    {
-        let file_idx = self.selected_file;
-        let hunk_idx = self.selected_hunk;
-
-        let is_pending = self
-            .files
-            .get(file_idx)
-            .and_then(|file| file.hunks.get(hunk_idx))
-            .is_some_and(|hunk| hunk.status == HunkStatus::Pending);
-
-        if is_pending {
-            f(self, file_idx, hunk_idx, repo)?;
-            Ok(true)
+    /// Wrap long lines onto more rows, or stop wrapping them (`w`).
+    pub fn toggle_wrap(&mut self) {
+        self.wrap_lines = !self.wrap_lines;
+        self.h_scroll = 0;
+        self.scroll_to_selected_hunk();
+        self.clamp_scroll();
+        self.info(if self.wrap_lines {
+            "Wrapping long lines"
         } else {
-            Ok(false)
+            "Not wrapping long lines"
+        });
+    }
+
+    /// Scroll the diff view's line content `columns` to the right, or to
+    /// the left if negative, up to the end of the current file's longest
+    /// line. Long lines wrap instead while wrapping is on.
+    pub fn scroll_horizontally(&mut self, columns: isize) {
+        if self.wrap_lines {
+            self.warn("Long lines wrap: w to scroll them instead");
+            return;
         }
+        let longest = self.current_file().map_or(0, |file| {
+            file.hunks
+                .iter()
+                .flat_map(|h| &h.lines)
+                .map(|l| ui::diff_view::display_width(&l.content, self.tab_width))
+                .max()
+                .unwrap_or(0)
+        });
+        self.h_scroll = self
+            .h_scroll
+            .saturating_add_signed(columns)
+            .min(longest.saturating_sub(1));
+        self.dirty = true;
     }
 
-    /// Stage the current hunk.
-    pub fn stage_current_hunk(&mut self, repo: &Repository) -> Result<()> {
-        self.with_current_pending_hunk(Some(repo), |app, fi, hi, repo| {
-            if !app.no_stage {
-                let offset = app.compute_line_offset(fi, hi);
-                staging::stage_hunk(
-                    repo.unwrap(),
-                    &app.files[fi],
-                    &app.files[fi].hunks[hi],
-                    offset,
-                )?;
+    /// Show more (`grow`) or fewer lines of file context around the current
+    /// hunk, reading the file from the working tree (or index blob).
+    pub fn expand_current_context(&mut self, repo: Option<&Repository>, grow: bool) {
+        let key = (self.selected_file, self.selected_hunk);
+        let Some(file) = self.files.get(key.0) else {
+            return;
+        };
+        if key.1 >= file.hunks.len() {
+            return;
+        }
+        if let Some(noun) = file.placeholder_noun(key.1) {
+            self.warn(format!("No context for a {}", noun));
+            self.dirty = true;
+            return;
+        }
+
+        let current = self.expanded_context.get(&key).map_or(0, |(n, _)| *n);
+        let requested = if grow {
+            current + context::CONTEXT_STEP
+        } else {
+            current.saturating_sub(context::CONTEXT_STEP)
+        };
+
+        if requested == 0 {
+            self.expanded_context.remove(&key);
+            self.info("Context collapsed");
+        } else {
+            let Some((source, side)) = context::load_file_lines(repo, &file.path) else {
+                self.error(format!("Cannot read {}", file.path.display()));
+                self.dirty = true;
+                return;
+            };
+            let hunk = &file.hunks[key.1];
+            let prev = key.1.checked_sub(1).and_then(|i| file.hunks.get(i));
+            let next = file.hunks.get(key.1 + 1);
+            let ctx = context::expand(&source, side, hunk, prev, next, requested);
+            self.info(format!("Showing {} extra context lines", ctx.len()));
+            self.expanded_context.insert(key, (requested, ctx));
+        }
+        self.dirty = true;
+    }
+
+    /// Switch between the unified and side-by-side diff layouts, keeping the
+    /// selected hunk in view.
+    pub fn toggle_view_mode(&mut self) {
+        self.view_mode = match self.view_mode {
+            ViewMode::Unified => ViewMode::SideBySide,
+            ViewMode::SideBySide => ViewMode::Unified,
+        };
+        self.scroll_to_selected_hunk();
+        self.dirty = true;
+    }
+
+    /// Show or hide the statistics panel.
+    pub fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+        self.dirty = true;
+    }
+
+    /// Toggle the blame panel. Blame needs the diff from a repository, not
+    /// a patch.
+    pub fn toggle_blame(&mut self, repo: Option<&Repository>) {
+        let Some(repo) = repo.filter(|_| !self.reviewing_patch) else {
+            self.warn("Blame needs a diff from a git repository");
+            return;
+        };
+        self.show_blame = !self.show_blame;
+        self.load_blame(repo);
+        self.dirty = true;
+    }
+
+    /// Blame the current file for the blame panel, unless that was done
+    /// already or the current hunk has no old lines.
+    pub fn load_blame(&mut self, repo: &Repository) {
+        if self.show_blame || self.mode == AppMode::HunkDetails {
+            self.blame_current_file(repo);
+        }
+    }
+
+    fn blame_current_file(&mut self, repo: &Repository) {
+        let Some(file) = self.files.get(self.selected_file) else {
+            return;
+        };
+        let has_old_lines = file
+            .hunks
+            .get(self.selected_hunk)
+            .is_some_and(|h| h.lines.iter().any(|l| l.old_lineno.is_some()));
+        if !has_old_lines || self.blame.contains_key(&file.path) {
+            return;
+        }
+        let blame = blame::blame_file(repo, &file.path).map_err(|e| format!("{:#}", e));
+        self.blame.insert(file.path.clone(), blame);
+        self.dirty = true;
+    }
+
+    /// Show the commits that last changed the current hunk's old lines.
+    pub fn open_hunk_details(&mut self, repo: Option<&Repository>) {
+        let Some(repo) = repo.filter(|_| !self.reviewing_patch) else {
+            self.warn("Hunk details need a diff from a git repository");
+            return;
+        };
+        if self.current_hunk().is_none() {
+            return;
+        }
+        self.mode = AppMode::HunkDetails;
+        self.blame_current_file(repo);
+        self.dirty = true;
+    }
+
+    /// Toggle between the flat file list and the directory tree.
+    pub fn toggle_tree_view(&mut self) {
+        self.tree_view = !self.tree_view;
+        self.selected_dir = None;
+        self.dirty = true;
+    }
+
+    /// Visible rows of the tree view.
+    pub fn file_tree_rows(&self) -> Vec<FileTreeRow> {
+        let reveal = self.selected_dir.is_none().then_some(self.selected_file);
+        file_tree::build(
+            &self.files,
+            &self.shown_files(),
+            &self.collapsed_dirs,
+            reveal,
+        )
+    }
+
+    /// Row of `rows` under the file list cursor.
+    pub fn file_tree_cursor(&self, rows: &[FileTreeRow]) -> usize {
+        self.selected_dir
+            .as_deref()
+            .and_then(|dir| file_tree::dir_row(rows, dir))
+            .or_else(|| file_tree::file_row(rows, self.selected_file))
+            .unwrap_or(0)
+    }
+
+    /// Move the tree view cursor onto `row`: a file row selects the file,
+    /// a directory row only moves the cursor.
+    fn select_tree_row(&mut self, row: &FileTreeRow) {
+        match row {
+            FileTreeRow::Dir { path, .. } => self.selected_dir = Some(path.clone()),
+            FileTreeRow::File { index, .. } => {
+                self.selected_dir = None;
+                self.selected_file = *index;
+                self.selected_hunk = 0;
+                self.cursor_line = 0;
+                self.scroll_offset = 0;
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Move the tree view cursor down a row (wraps around).
+    pub fn select_next_row(&mut self) {
+        let rows = self.file_tree_rows();
+        if rows.is_empty() {
+            return;
+        }
+        let next = (self.file_tree_cursor(&rows) + 1) % rows.len();
+        self.select_tree_row(&rows[next]);
+    }
+
+    /// Move the tree view cursor up a row (wraps around).
+    pub fn select_prev_row(&mut self) {
+        let rows = self.file_tree_rows();
+        if rows.is_empty() {
+            return;
+        }
+        let prev = self
+            .file_tree_cursor(&rows)
+            .checked_sub(1)
+            .unwrap_or(rows.len() - 1);
+        self.select_tree_row(&rows[prev]);
+    }
+
+    /// Collapse the directory under the cursor. On a file or an already
+    /// collapsed directory, collapse the enclosing directory instead and
+    /// move the cursor onto it.
+    pub fn collapse_dir(&mut self) {
+        let rows = self.file_tree_rows();
+        let cursor = self.file_tree_cursor(&rows);
+        let target = match rows.get(cursor) {
+            Some(FileTreeRow::Dir {
+                collapsed: false, ..
+            }) => Some(cursor),
+            Some(_) => file_tree::parent_row(&rows, cursor),
+            None => None,
+        };
+        if let Some(FileTreeRow::Dir { path, .. }) = target.and_then(|t| rows.get(t)) {
+            self.collapsed_dirs.insert(path.clone());
+            self.selected_dir = Some(path.clone());
+            self.dirty = true;
+        }
+    }
+
+    /// Expand the directory under the cursor.
+    pub fn expand_dir(&mut self) {
+        if let Some(dir) = self.selected_dir.take() {
+            self.collapsed_dirs.remove(&dir);
+            self.selected_dir = Some(dir);
+            self.dirty = true;
+        }
+    }
+
+    /// Expand the directory under the cursor if collapsed, otherwise
+    /// collapse it (or the file's directory).
+    pub fn toggle_dir(&mut self) {
+        match &self.selected_dir {
+            Some(dir) if self.collapsed_dirs.contains(dir) => self.expand_dir(),
+            _ => self.collapse_dir(),
+        }
+    }
+
+    /// Toggle focus between file list and diff view.
+    pub fn toggle_focus(&mut self) {
+        self.focus = match self.focus {
+            FocusPanel::FileList => FocusPanel::DiffView,
+            FocusPanel::DiffView => FocusPanel::FileList,
+        };
+        self.dirty = true;
+    }
+
+    /// Compute the line offset for the current hunk caused by previously staged
+    /// hunks in the same file (see [`review::line_offset`]).
+    fn compute_line_offset(&self, file_idx: usize, hunk_idx: usize) -> i32 {
+        self.files
+            .get(file_idx)
+            .map_or(0, |file| review::line_offset(file, hunk_idx))
+    }
+
+    /// Access the current pending hunk mutably and execute a closure on it.
+    /// Returns `true` if the closure was executed (hunk exists and is Pending).
+    fn with_current_pending_hunk<F>(&mut self, repo: Option<&Repository>, f: F) -> Result<bool>
+    where
+        F: FnOnce(&mut Self, usize, usize, Option<&Repository>) -> Result<()>,
+    {
+        let file_idx = self.selected_file;
+        let hunk_idx = self.selected_hunk;
+
+        let is_pending = self
+            .files
+            .get(file_idx)
+            .and_then(|file| file.hunks.get(hunk_idx))
+            .is_some_and(|hunk| hunk.status == HunkStatus::Pending);
+
+        if is_pending {
+            f(self, file_idx, hunk_idx, repo)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Stage `hunk` of file `file_idx`, provided the working tree file still
+    /// matches the diff. Fails without staging if it changed before; if it
+    /// changed while staging, the hunk is staged and a warning is returned.
+    fn stage_verified(
+        &self,
+        repo: &Repository,
+        file_idx: usize,
+        hunk: &Hunk,
+        offset: i32,
+    ) -> Result<Option<String>> {
+        let file = &self.files[file_idx];
+        staging::check_unchanged(repo, &self.worktree_snapshot, &file.path)?;
+        staging::stage_hunk(repo, file, hunk, offset)?;
+        Ok(
+            staging::check_unchanged(repo, &self.worktree_snapshot, &file.path)
+                .err()
+                .map(|e| format!("Staged, but {:#}", e)),
+        )
+    }
+
+    /// Remember why hunk `at` could not be staged if `result` is an error,
+    /// for [`report_stage_error`](Self::report_stage_error) to show.
+    fn note_stage_failure<T>(&mut self, (fi, hi): JumpPosition, result: Result<T>) -> Result<T> {
+        if let Err(e) = &result {
+            let file = &self.files[fi];
+            let header = file.hunks.get(hi).map_or("", |h| h.header.as_str());
+            self.stage_failure =
+                Some(((fi, hi), staging::StageFailure::new(&file.path, header, e)));
+        }
+        result
+    }
+
+    /// Report a failure to stage: a one-line error in the status bar, and
+    /// the full error with remedies in the error panel. The hunk stays
+    /// pending, so staging it can be retried.
+    pub fn report_stage_error(&mut self, e: &anyhow::Error) {
+        self.error(format!("Stage error: {:#}", e));
+        self.open_stage_error();
+    }
+
+    /// Show the error panel for the failure noted last, if any.
+    fn open_stage_error(&mut self) {
+        if self.stage_failure.is_some() {
+            self.mode = AppMode::StageError;
+            self.dirty = true;
+        }
+    }
+
+    /// Handle a key in the error panel: Enter goes to the hunk that
+    /// failed, Esc or q just closes the panel.
+    pub fn handle_stage_error_key(&mut self, code: KeyCode) {
+        let go_to_hunk = match code {
+            KeyCode::Enter => true,
+            KeyCode::Esc | KeyCode::Char('q') => false,
+            _ => return,
+        };
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+        if let Some((at, _)) = self.stage_failure.take()
+            && go_to_hunk
+            && self.files.get(at.0).is_some_and(|f| at.1 < f.hunks.len())
+        {
+            self.record_jump(at);
+            self.go_to(at);
+        }
+    }
+
+    /// Stage the current hunk.
+    pub fn stage_current_hunk(&mut self, repo: &Repository) -> Result<()> {
+        self.with_current_pending_hunk(Some(repo), |app, fi, hi, repo| {
+            let mut warning = None;
+            if !app.no_stage {
+                let offset = app.compute_line_offset(fi, hi);
+                let result =
+                    app.stage_verified(repo.unwrap(), fi, &app.files[fi].hunks[hi], offset);
+                warning = app.note_stage_failure((fi, hi), result)?;
             }
             app.files[fi].hunks[hi].status = HunkStatus::Staged;
-            app.message = Some("Hunk staged".to_string());
+            match warning {
+                Some(warning) => app.warn(warning),
+                None => app.info("Hunk staged"),
+            }
             app.select_next_hunk();
             Ok(())
         })?;
@@ -317,1150 +1666,4603 @@ impl App {
     pub fn skip_current_hunk(&mut self) {
         let _ = self.with_current_pending_hunk(None, |app, fi, hi, _| {
             app.files[fi].hunks[hi].status = HunkStatus::Skipped;
-            app.message = Some("Hunk skipped".to_string());
+            app.info("Hunk skipped");
             app.select_next_hunk();
             Ok(())
         });
     }
 
+    /// Skip every pending hunk of the current file (`:skip-file`).
+    fn skip_current_file(&mut self) -> Result<String, String> {
+        let file = self
+            .files
+            .get_mut(self.selected_file)
+            .ok_or("No file selected")?;
+        let mut skipped = 0;
+        for hunk in file.hunks.iter_mut() {
+            if hunk.status == HunkStatus::Pending {
+                hunk.status = HunkStatus::Skipped;
+                skipped += 1;
+            }
+        }
+        Ok(format!(
+            "Skipped {} hunk(s) of {}",
+            skipped,
+            file.path.display()
+        ))
+    }
+
+    /// Stage every pending hunk of the current file (`:stage-file`).
+    fn stage_current_file(&mut self, repo: Option<&Repository>) -> Result<String, String> {
+        let fi = self.selected_file;
+        let file = self.files.get(fi).ok_or("No file selected")?;
+        let path = file.path.clone();
+        let targets = (0..file.hunks.len()).map(|hi| (fi, hi)).collect();
+        match self.stage_hunks(repo, targets) {
+            Ok((staged, warning)) => Ok(warning
+                .unwrap_or_else(|| format!("Staged {} hunk(s) of {}", staged, path.display()))),
+            Err(e) => {
+                self.open_stage_error();
+                Err(format!("Stage error: {:#}", e))
+            }
+        }
+    }
+
+    /// Skip every pending hunk that only changes whitespace, in all files.
+    pub fn skip_whitespace_only_hunks(&mut self) {
+        let mut skipped = 0;
+        for hunk in self.files.iter_mut().flat_map(|f| f.hunks.iter_mut()) {
+            if hunk.status == HunkStatus::Pending && diff::is_whitespace_only(hunk) {
+                hunk.status = HunkStatus::Skipped;
+                skipped += 1;
+            }
+        }
+        if skipped == 0 {
+            self.warn("No pending whitespace-only hunks");
+        } else {
+            self.info(format!("Skipped {} whitespace-only hunk(s)", skipped));
+        }
+        self.dirty = true;
+    }
+
     /// Accept the current hunk (marks as Staged without actually staging via git).
     /// Used in patch mode where there's no git repo.
     pub fn accept_current_hunk(&mut self) {
         let _ = self.with_current_pending_hunk(None, |app, fi, hi, _| {
             app.files[fi].hunks[hi].status = HunkStatus::Staged;
-            app.message = Some("Hunk accepted".to_string());
+            app.info("Hunk accepted");
             app.select_next_hunk();
             Ok(())
         });
     }
 
-    /// Split the current hunk into sub-hunks.
+    /// Whether a hunk is marked for batch staging.
+    pub fn is_marked(&self, file_idx: usize, hunk_idx: usize) -> bool {
+        self.marked.contains(&(file_idx, hunk_idx))
+    }
+
+    /// Mark or unmark the current hunk, then move to the next one.
+    pub fn toggle_mark_current_hunk(&mut self) {
+        let key = (self.selected_file, self.selected_hunk);
+        if self.current_hunk().is_none() {
+            return;
+        }
+        if !self.marked.remove(&key) {
+            self.marked.insert(key);
+        }
+        self.info(format!("{} hunk(s) marked", self.marked.len()));
+        self.select_next_hunk();
+        self.dirty = true;
+    }
+
+    /// Stage every marked pending hunk, file by file in hunk order so each
+    /// hunk's line offset accounts for the ones staged before it. Marks are
+    /// cleared; on error the hunks not yet staged stay marked. In
+    /// no-stage/patch mode hunks are only marked as accepted.
+    pub fn stage_marked_hunks(&mut self, repo: Option<&Repository>) -> Result<usize> {
+        let marked: Vec<(usize, usize)> = self.marked.iter().copied().collect();
+        let (staged, warning) = self.stage_hunks(repo, marked)?;
+        self.marked.clear();
+        match warning {
+            Some(warning) => self.warn(warning),
+            None => self.info(format!("Staged {} marked hunk(s)", staged)),
+        }
+        self.dirty = true;
+        Ok(staged)
+    }
+
+    /// Stage the pending hunks among `targets` ((file, hunk) indices, in
+    /// order). Returns how many were staged and the last staging warning.
+    fn stage_hunks(
+        &mut self,
+        repo: Option<&Repository>,
+        targets: Vec<(usize, usize)>,
+    ) -> Result<(usize, Option<String>)> {
+        let mut staged = 0;
+        let mut warning = None;
+        for (fi, hi) in targets {
+            let Some(hunk) = self.files.get(fi).and_then(|f| f.hunks.get(hi)) else {
+                continue;
+            };
+            if hunk.status != HunkStatus::Pending {
+                continue;
+            }
+            if !self.no_stage
+                && let Some(repo) = repo
+            {
+                let offset = self.compute_line_offset(fi, hi);
+                let result = self.stage_verified(repo, fi, hunk, offset);
+                if let Some(w) = self.note_stage_failure((fi, hi), result)? {
+                    warning = Some(w);
+                }
+            }
+            self.files[fi].hunks[hi].status = HunkStatus::Staged;
+            self.marked.remove(&(fi, hi));
+            staged += 1;
+        }
+        Ok((staged, warning))
+    }
+
+    /// `:stage-matching`: find the pending hunks whose changed lines match
+    /// `pattern` and ask whether to stage them.
+    fn start_stage_matching(&mut self, pattern: &str) -> Result<String, String> {
+        let matcher = bulk::HunkMatcher::new(pattern).map_err(|e| format!("{:#}", e))?;
+        let matches = bulk::matching_hunks(&self.files, &matcher);
+        if matches.is_empty() {
+            return Err(format!("No pending hunk matches /{}/", matcher.pattern()));
+        }
+        let files: BTreeSet<usize> = matches.iter().map(|&(fi, _)| fi).collect();
+        let prompt = format!(
+            "Stage {} hunk(s) in {} file(s) matching /{}/?",
+            matches.len(),
+            files.len(),
+            matcher.pattern()
+        );
+        self.bulk_stage = matches;
+        self.mode = AppMode::BulkStagePrompt;
+        Ok(prompt)
+    }
+
+    /// Handle a key in `BulkStagePrompt` mode: `y` stages the matched
+    /// hunks, `n`/Esc leaves them.
+    pub fn handle_bulk_stage_key(&mut self, code: KeyCode, repo: Option<&Repository>) {
+        let stage = match code {
+            KeyCode::Char('y') => true,
+            KeyCode::Char('n') | KeyCode::Esc => false,
+            _ => return,
+        };
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+        let targets = std::mem::take(&mut self.bulk_stage);
+        if !stage {
+            self.info("Nothing staged");
+            return;
+        }
+        match self.stage_hunks(repo, targets) {
+            Ok((_, Some(warning))) => self.warn(warning),
+            Ok((staged, None)) => self.info(format!("Staged {} matching hunk(s)", staged)),
+            Err(e) => self.report_stage_error(&e),
+        }
+    }
+
+    /// Keep marks pointing at the right hunks after the hunk at `hunk_idx`
+    /// of `file_idx` is replaced by `parts` sub-hunks. A marked hunk marks
+    /// all of its parts.
+    fn remap_marks_after_split(&mut self, file_idx: usize, hunk_idx: usize, parts: usize) {
+        let was_marked = self.marked.remove(&(file_idx, hunk_idx));
+        let shifted: Vec<(usize, usize)> = self
+            .marked
+            .iter()
+            .filter(|(fi, hi)| *fi == file_idx && *hi > hunk_idx)
+            .copied()
+            .collect();
+        for key in &shifted {
+            self.marked.remove(key);
+        }
+        self.marked
+            .extend(shifted.into_iter().map(|(fi, hi)| (fi, hi + parts - 1)));
+        if was_marked {
+            self.marked
+                .extend((hunk_idx..hunk_idx + parts).map(|hi| (file_idx, hi)));
+        }
+    }
+
+    /// Split the current hunk into sub-hunks. In line mode the hunk is
+    /// split in two before the cursor line, and the cursor moves to the
+    /// start of the second part.
     pub fn split_current_hunk(&mut self) {
         let file_idx = self.selected_file;
         let hunk_idx = self.selected_hunk;
 
-        if let Some(file) = self.files.get(file_idx)
-            && let Some(hunk) = file.hunks.get(hunk_idx)
+        if let Some(noun) = self
+            .files
+            .get(file_idx)
+            .and_then(|f| f.placeholder_noun(hunk_idx))
         {
+            self.warn(format!("Cannot split a {}", noun));
+        } else if let Some(cursor) = self.line_cursor() {
+            let parts = self
+                .current_hunk()
+                .and_then(|hunk| diff::split_hunk_at(hunk, cursor));
+            if let Some((first, second)) = parts {
+                self.replace_current_hunk(vec![first, second]);
+                self.selected_hunk = hunk_idx + 1;
+                self.cursor_line = 0;
+                self.line_anchor = None;
+                self.scroll_to_cursor();
+                self.info("Hunk split at cursor");
+            } else {
+                self.warn("Cannot split here: both parts need a change");
+            }
+        } else if let Some(hunk) = self.current_hunk() {
             let sub_hunks = diff::split_hunk(hunk);
             if sub_hunks.len() > 1 {
-                let file = &mut self.files[file_idx];
-                file.hunks.splice(hunk_idx..=hunk_idx, sub_hunks);
-                self.message = Some("Hunk split".to_string());
-                self.highlight_cache = None;
+                self.replace_current_hunk(sub_hunks);
+                self.info("Hunk split");
             } else {
-                self.message = Some("Cannot split hunk further".to_string());
+                self.warn("Cannot split hunk further");
             }
         }
         self.dirty = true;
     }
 
+    /// Replace the current hunk with the parts it was split into, keeping
+    /// marks and risk scores.
+    fn replace_current_hunk(&mut self, parts: Vec<Hunk>) {
+        let (file_idx, hunk_idx) = (self.selected_file, self.selected_hunk);
+        let file = &self.files[file_idx];
+        let risk = self.risk.get(&file.path, &file.hunks[hunk_idx]).copied();
+        let count = parts.len();
+        self.remap_marks_after_split(file_idx, hunk_idx, count);
+        self.files[file_idx]
+            .hunks
+            .splice(hunk_idx..=hunk_idx, parts);
+        self.inherit_risk(file_idx, hunk_idx..hunk_idx + count, risk);
+        self.highlight_cache.clear();
+        // Hunk indices in this file shifted
+        self.expanded_context.retain(|(fi, _), _| *fi != file_idx);
+    }
+
+    /// Give the hunks in `range` of file `fi` the risk score of the hunk
+    /// they were split from.
+    fn inherit_risk(&mut self, fi: usize, range: Range<usize>, risk: Option<HunkRisk>) {
+        let Some(risk) = risk else {
+            return;
+        };
+        let file = &self.files[fi];
+        for hunk in &file.hunks[range] {
+            self.risk.insert(&file.path, hunk, risk);
+        }
+    }
+
     /// Start the editor flow for the current hunk (edit or comment).
     fn start_editor_flow(
         &mut self,
         prepare_fn: fn(&Hunk) -> Result<tempfile::NamedTempFile>,
         is_comment: bool,
     ) -> Result<Option<EditorState>> {
+        if let Some(noun) = self.current_placeholder() {
+            bail!("A {} can only be staged or skipped", noun);
+        }
         if let Some(hunk) = self.current_hunk() {
             let tmpfile = prepare_fn(hunk)?;
-            let original_content = std::fs::read_to_string(tmpfile.path())?;
-            let tmp_path = tmpfile.path().to_string_lossy().to_string();
-            let pane_id = editor::open_editor(&tmp_path)?;
-            let rx = editor::wait_for_pane_close(pane_id);
-            self.mode = AppMode::WaitingForEditor;
-            self.dirty = true;
-            Ok(Some(EditorState {
-                tmpfile,
-                rx,
-                is_comment,
-                original_content,
-            }))
+            // A commented hunk's comment is opened again to be revised
+            let draft = is_comment
+                .then(|| self.current_comment_index())
+                .flatten()
+                .map(|i| self.feedback[i].clone());
+            self.open_editor_flow(tmpfile, None, is_comment, draft.as_ref())
+                .map(Some)
         } else {
             Ok(None)
         }
     }
 
-    /// Start the edit flow for the current hunk.
-    pub fn start_edit(&mut self) -> Result<Option<EditorState>> {
-        self.start_editor_flow(editor::prepare_edit_tempfile, false)
+    /// Open a prepared tempfile in the editor pane (at `line`, if given)
+    /// and wait for it to close. A `draft` comment is written into the
+    /// tempfile, while the original content stays the bare template.
+    fn open_editor_flow(
+        &mut self,
+        tmpfile: tempfile::NamedTempFile,
+        line: Option<u32>,
+        is_comment: bool,
+        draft: Option<&HunkFeedback>,
+    ) -> Result<EditorState> {
+        let original_content = std::fs::read_to_string(tmpfile.path())?;
+        if let Some(fb) = draft {
+            std::fs::write(tmpfile.path(), editor::comment_draft(&original_content, fb))?;
+        }
+        let recovery = EditorSession {
+            tempfile: tmpfile.path().to_path_buf(),
+            workdir: self.workdir.clone(),
+            pid: std::process::id(),
+            is_comment,
+            file_path: self
+                .current_file()
+                .map(|f| f.path.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            hunk_header: self
+                .current_hunk()
+                .map(|h| h.header.clone())
+                .unwrap_or_default(),
+            hunk_lines: self
+                .current_hunk()
+                .map(|h| h.lines.to_vec())
+                .unwrap_or_default(),
+            original_content: original_content.clone(),
+        };
+        // Without the sidecar the edit still works; it just can't be
+        // recovered after a crash
+        let _ = recovery.save();
+        let rx = self
+            .open_editor(&tmpfile.path().to_string_lossy(), line)
+            .inspect_err(|_| recovery.clear())?;
+        self.mode = AppMode::WaitingForEditor;
+        self.dirty = true;
+        Ok(EditorState {
+            tmpfile,
+            rx,
+            is_comment,
+            original_content,
+            recovery,
+        })
     }
 
-    /// Start the comment flow for the current hunk.
-    pub fn start_comment(&mut self) -> Result<Option<EditorState>> {
-        self.start_editor_flow(editor::prepare_comment_tempfile, true)
+    /// Open `$EDITOR` on `path` (at `line`, if given) through the
+    /// multiplexer, or as a process of its own for GUI editors (see
+    /// [`editor::plan_editor_launch`]). The receiver signals when it closes.
+    fn open_editor(&self, path: &str, line: Option<u32>) -> Result<Receiver<()>> {
+        let editor = editor::get_editor();
+        let launch =
+            editor::plan_editor_launch(&editor, self.editor_wait_command.as_deref(), path, line);
+        let command = match launch {
+            EditorLaunch::Wait(command) => return editor::spawn_waiting_editor(&command),
+            EditorLaunch::Pane(command) => command,
+        };
+        let pane = self.multiplexer.open_pane(&command)?;
+        let (_, clock) = tmux::system();
+        Ok(multiplexer::wait_for_close(
+            self.multiplexer.clone(),
+            clock,
+            pane,
+        ))
     }
 
-    /// Handle a mouse click at the given coordinates.
-    pub fn handle_mouse_click(&mut self, column: u16, row: u16) {
-        // Check if click is within file list area
-        let area = self.file_list_area;
-        if column >= area.x
-            && column < area.x + area.width
-            && row >= area.y
-            && row < area.y + area.height
+    /// Start the edit flow for the current hunk.
+    ///
+    /// Large hunks are edited within a copy of the working-tree file (see
+    /// [`editor::prepare_region_edit_tempfile`]); others, and files that
+    /// can't be read or no longer match the diff, as a fragment.
+    pub fn start_edit(&mut self, repo: Option<&Repository>) -> Result<Option<EditorState>> {
+        if self.current_placeholder().is_none()
+            && let Some((tmpfile, line)) = self.prepare_region_edit(repo)?
         {
-            // +1 for the border, row within the list content
-            let list_row = row.saturating_sub(area.y + 1);
-            let idx = list_row as usize;
-            if idx < self.files.len() {
-                self.selected_file = idx;
-                self.selected_hunk = 0;
-                self.scroll_offset = 0;
-                self.focus = FocusPanel::FileList;
-                self.dirty = true;
-            }
+            return self
+                .open_editor_flow(tmpfile, Some(line), false, None)
+                .map(Some);
         }
+        self.start_editor_flow(editor::prepare_edit_tempfile, false)
     }
 
-    /// Flush a pending editor result by reading the tempfile and processing it.
-    ///
-    /// This handles the race condition where the user presses `q` immediately
-    /// after the editor closes, before the background pane-polling thread has
-    /// detected the close. Since vim has already written the file, we can read
-    /// it directly.
-    ///
-    /// Returns `true` if feedback was actually captured, `false` otherwise.
-    pub fn flush_pending_editor_state(
-        &mut self,
-        tmpfile_path: &std::path::Path,
-        is_comment: bool,
-        original_content: &str,
-    ) -> bool {
-        let edited = std::fs::read_to_string(tmpfile_path).unwrap_or_default();
-        let mut captured = false;
-
-        if let Some(file) = self.current_file() {
-            let file_path = file.path.to_string_lossy().to_string();
-            if let Some(hunk) = self.current_hunk() {
-                let hunk_header = hunk.header.clone();
-                let hunk_lines = hunk.lines.clone();
-
-                if is_comment {
-                    if let Some(fb) = editor::parse_comment_result(
-                        original_content,
-                        &edited,
-                        &file_path,
-                        &hunk_header,
-                        &hunk_lines,
-                    ) {
-                        self.feedback.push(fb);
-                        let fi = self.selected_file;
-                        let hi = self.selected_hunk;
-                        self.files[fi].hunks[hi].status = HunkStatus::Commented;
-                        captured = true;
-                    }
-                } else {
-                    let original = editor::extract_new_side_content(&hunk_lines);
-                    if let Some(fb) = editor::parse_edit_result(
-                        &original,
-                        &edited,
-                        &file_path,
-                        &hunk_header,
-                        &hunk_lines,
-                    ) {
-                        self.feedback.push(fb);
-                        let fi = self.selected_file;
-                        let hi = self.selected_hunk;
-                        self.files[fi].hunks[hi].status = HunkStatus::Edited;
-                        captured = true;
-                    }
-                }
-            }
+    /// Whole-file edit tempfile for the current hunk, if it exceeds the
+    /// fragment size limit. Returns the tempfile and the line to open it at.
+    pub fn prepare_region_edit(
+        &self,
+        repo: Option<&Repository>,
+    ) -> Result<Option<(tempfile::NamedTempFile, u32)>> {
+        let Some(hunk) = self.current_hunk() else {
+            return Ok(None);
+        };
+        if !editor::exceeds_fragment_limit(hunk) {
+            return Ok(None);
         }
-        self.mode = AppMode::Browsing;
-        self.dirty = true;
-        captured
+        let Some((path, _)) = self.current_file_location(repo) else {
+            return Ok(None);
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        let extension = path.extension().map(|e| e.to_string_lossy().to_string());
+        editor::prepare_region_edit_tempfile(&content, hunk, extension.as_deref())
     }
 
-    /// Estimate scroll position for the currently selected hunk.
-    fn scroll_to_selected_hunk(&mut self) {
-        let mut line_count: u32 = 0;
-        if let Some(file) = self.files.get(self.selected_file) {
-            for (idx, hunk) in file.hunks.iter().enumerate() {
-                if idx == self.selected_hunk {
-                    self.scroll_offset = line_count;
-                    return;
-                }
-                line_count += 1; // header
-                line_count += hunk.lines.len() as u32;
-                line_count += 1; // separator
-            }
-        }
+    /// Start the comment flow for the current hunk, with its comment
+    /// pre-filled if it has one.
+    pub fn start_comment(&mut self) -> Result<Option<EditorState>> {
+        self.start_editor_flow(editor::prepare_comment_tempfile, true)
     }
-}
 
-/// Guard that restores terminal state on drop (including panics).
-struct TerminalGuard;
+    /// Working tree path and first line of the current hunk (or the line
+    /// under the cursor in line mode), for opening the file in an editor.
+    /// `None` if nothing is selected.
+    pub fn current_file_location(&self, repo: Option<&Repository>) -> Option<(PathBuf, u32)> {
+        let file = self.current_file()?;
+        let hunk = self.current_hunk()?;
+        let path = match repo.and_then(|r| r.workdir()) {
+            Some(workdir) => workdir.join(&file.path),
+            None => file.path.clone(),
+        };
+        let line = match self.line_cursor() {
+            // A removed line has no new line number: use the next line's
+            Some(cursor) => hunk.lines[cursor..]
+                .iter()
+                .find_map(|l| l.new_lineno)
+                .unwrap_or(hunk.new_start + hunk.new_lines),
+            None => hunk.new_start,
+        };
+        Some((path, line.max(1)))
+    }
 
-impl Drop for TerminalGuard {
-    fn drop(&mut self) {
-        let _ = crossterm::terminal::disable_raw_mode();
-        let _ = crossterm::execute!(
-            io::stdout(),
-            crossterm::terminal::LeaveAlternateScreen,
-            crossterm::event::DisableMouseCapture,
-        );
+    /// Open the current file in `$EDITOR` (tmux split) at the current hunk.
+    /// The pane is independent of the review: nothing is captured from it.
+    pub fn open_current_file(&mut self, repo: Option<&Repository>) {
+        self.dirty = true;
+        let Some((path, line)) = self.current_file_location(repo) else {
+            self.warn("No hunk selected");
+            return;
+        };
+        if !path.is_file() {
+            self.warn(format!("{} does not exist", path.display()));
+            return;
+        }
+        match self.open_editor(&path.to_string_lossy(), Some(line)) {
+            Ok(_) => self.info(format!("Opened {}:{}", path.display(), line)),
+            Err(e) => self.error(format!("Open error: {}", e)),
+        }
     }
-}
 
-/// Run the TUI application. Returns collected feedback on exit.
-pub fn run(
-    files: Vec<FileDiff>,
-    repo: Option<&Repository>,
-    no_stage: bool,
-) -> Result<Vec<HunkFeedback>> {
-    // Set up terminal
-    crossterm::terminal::enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    crossterm::execute!(
-        stdout,
-        crossterm::terminal::EnterAlternateScreen,
-        crossterm::event::EnableMouseCapture,
-    )?;
+    /// Enter or leave line mode. Entering puts the cursor on the first
+    /// changed line of the current hunk.
+    pub fn toggle_line_mode(&mut self) {
+        self.dirty = true;
+        if self.line_mode {
+            self.line_mode = false;
+            self.line_anchor = None;
+            self.info("Line mode off");
+            return;
+        }
+        let Some(hunk) = self.current_hunk() else {
+            self.warn("No hunk selected");
+            return;
+        };
+        if let Some(noun) = self.current_placeholder() {
+            self.warn(format!("A {} has no lines", noun));
+            return;
+        }
+        self.cursor_line = hunk
+            .lines
+            .iter()
+            .position(|l| l.kind != LineKind::Context)
+            .unwrap_or(0);
+        self.line_mode = true;
+        self.focus = FocusPanel::DiffView;
+        self.scroll_to_cursor();
+        self.info(
+            "Line mode: j/k move, V select, y stage line, s split, C comment, o open, v/Esc exit"
+                .to_string(),
+        );
+    }
 
-    // Guard ensures terminal is restored even on panic
-    let _guard = TerminalGuard;
+    /// The line cursor, clamped to the current hunk. `None` outside line
+    /// mode or when the hunk has no lines.
+    pub fn line_cursor(&self) -> Option<usize> {
+        if !self.line_mode {
+            return None;
+        }
+        let len = self.current_hunk()?.lines.len();
+        len.checked_sub(1).map(|last| self.cursor_line.min(last))
+    }
 
-    let backend = ratatui::backend::CrosstermBackend::new(stdout);
-    let mut terminal = ratatui::Terminal::new(backend)?;
+    /// Lines of the current hunk selected in line mode: the line cursor,
+    /// or the range dragged with the mouse.
+    pub fn selected_lines(&self) -> Option<RangeInclusive<usize>> {
+        let cursor = self.line_cursor()?;
+        let anchor = match self.line_anchor {
+            Some((fi, hi, line)) if (fi, hi) == (self.selected_file, self.selected_hunk) => {
+                line.min(self.current_hunk()?.lines.len() - 1)
+            }
+            _ => cursor,
+        };
+        Some(anchor.min(cursor)..=anchor.max(cursor))
+    }
 
-    let mut app = App::new(files, no_stage);
-    let highlighter = Highlighter::new();
+    /// Start a line selection at the cursor, or drop the current one:
+    /// moving the cursor then extends it, like dragging with the mouse.
+    pub fn toggle_line_selection(&mut self) {
+        let Some(cursor) = self.line_cursor() else {
+            return;
+        };
+        let here = (self.selected_file, self.selected_hunk);
+        self.dirty = true;
+        if self.line_anchor.is_some_and(|(fi, hi, _)| (fi, hi) == here) {
+            self.line_anchor = None;
+            self.info("Selection cleared");
+        } else {
+            self.line_anchor = Some((here.0, here.1, cursor));
+            self.info("Selecting: j/k extend, y stage, V clear");
+        }
+    }
 
-    let mut editor_state: Option<EditorState> = None;
+    /// Move the line cursor down, continuing into the next hunk.
+    pub fn cursor_down(&mut self) {
+        let Some(cursor) = self.line_cursor() else {
+            return;
+        };
+        let len = self.current_hunk().map_or(0, |h| h.lines.len());
+        if cursor + 1 < len {
+            self.cursor_line = cursor + 1;
+        } else {
+            self.line_anchor = None;
+            self.select_next_hunk();
+        }
+        self.scroll_to_cursor();
+        self.dirty = true;
+    }
 
-    loop {
-        // Draw only when state has changed
-        if app.dirty {
-            terminal.draw(|frame| {
-                ui::render(frame, &mut app, &highlighter);
-            })?;
-            app.dirty = false;
+    /// Move the line cursor up, continuing into the previous hunk.
+    pub fn cursor_up(&mut self) {
+        let Some(cursor) = self.line_cursor() else {
+            return;
+        };
+        if cursor > 0 {
+            self.cursor_line = cursor - 1;
+        } else {
+            self.line_anchor = None;
+            let before = (self.selected_file, self.selected_hunk);
+            self.select_prev_hunk();
+            if (self.selected_file, self.selected_hunk) != before {
+                self.cursor_line = self
+                    .current_hunk()
+                    .map_or(0, |h| h.lines.len().saturating_sub(1));
+            }
         }
+        self.scroll_to_cursor();
+        self.dirty = true;
+    }
 
-        // Check if editor has closed
-        if let Some(ref state) = editor_state
-            && state.rx.try_recv().is_ok()
-        {
-            // Take ownership to process
-            let state = editor_state.take().unwrap();
-            let captured = app.flush_pending_editor_state(
-                state.tmpfile.path(),
-                state.is_comment,
-                &state.original_content,
-            );
-            app.message = Some(if captured {
-                if state.is_comment {
-                    "Comment captured".to_string()
-                } else {
-                    "Edit captured".to_string()
+    /// Stage only the changed lines selected in line mode (see
+    /// [`selected_lines`](Self::selected_lines)). The hunk is replaced by
+    /// the staged lines and the pending rest, and the cursor moves to the
+    /// next change. Without a repo (or in no-stage mode) the lines are only
+    /// marked as accepted.
+    pub fn stage_current_line(&mut self, repo: Option<&Repository>) -> Result<()> {
+        let Some(selected) = self.selected_lines() else {
+            return Ok(());
+        };
+        let cursor = *selected.start();
+        let (fi, hi) = (self.selected_file, self.selected_hunk);
+        let hunk = &self.files[fi].hunks[hi];
+        if hunk.status != HunkStatus::Pending {
+            self.warn("Hunk already reviewed");
+            self.dirty = true;
+            return Ok(());
+        }
+        let changed = hunk.lines[selected.clone()]
+            .iter()
+            .filter(|l| l.kind != LineKind::Context)
+            .count();
+        if changed == 0 {
+            self.warn("Not a changed line");
+            self.dirty = true;
+            return Ok(());
+        }
+        self.line_anchor = None;
+        let removed = hunk.lines[selected.clone()]
+            .iter()
+            .filter(|l| l.kind == LineKind::Removed)
+            .count();
+        let Some((above, mut line_hunk, rest)) = diff::split_off_lines(hunk, selected) else {
+            // The only change: stage the whole hunk
+            return match repo {
+                Some(repo) => self.stage_current_hunk(repo),
+                None => {
+                    self.accept_current_hunk();
+                    Ok(())
                 }
-            } else {
-                "No changes detected".to_string()
-            });
-            app.dirty = true;
+            };
+        };
+        let risk = self.risk.get(&self.files[fi].path, hunk).copied();
+        // Lines above the cursor that the rest no longer starts with
+        let dropped = rest
+            .as_ref()
+            .map_or(0, |rest| hunk.lines.len() - removed - rest.lines.len());
+
+        let mut warning = None;
+        if !self.no_stage
+            && let Some(repo) = repo
+        {
+            let offset = self.compute_line_offset(fi, hi);
+            let result = self.stage_verified(repo, fi, &line_hunk, offset);
+            warning = self.note_stage_failure((fi, hi), result)?;
+        }
+        line_hunk.status = HunkStatus::Staged;
+        // The cursor goes on to the rest, or back up when nothing is left
+        // below
+        let rest_idx = if rest.is_some() {
+            hi + usize::from(above.is_some()) + 1
+        } else {
+            hi
+        };
+        let pieces: Vec<Hunk> = above.into_iter().chain([line_hunk]).chain(rest).collect();
+        let count = pieces.len();
+        self.remap_marks_after_split(fi, hi, count);
+        self.files[fi].hunks.splice(hi..=hi, pieces);
+        self.inherit_risk(fi, hi..hi + count, risk);
+        self.highlight_cache.clear();
+        self.expanded_context.retain(|(f, _), _| *f != fi);
+
+        self.selected_hunk = rest_idx;
+        let lines = &self.files[fi].hunks[rest_idx].lines;
+        let start = cursor.saturating_sub(dropped).min(lines.len() - 1);
+        self.cursor_line = lines[start..]
+            .iter()
+            .position(|l| l.kind != LineKind::Context)
+            .map_or(start, |p| start + p);
+        self.scroll_to_cursor();
+        let staged = match changed {
+            1 => "Line staged".to_string(),
+            n => format!("{} lines staged", n),
+        };
+        match warning {
+            Some(warning) => self.warn(warning),
+            None => self.info(staged),
         }
+        self.dirty = true;
+        Ok(())
+    }
 
-        // Handle events
-        if event::poll(Duration::from_millis(50))? {
-            match event::read()? {
-                Event::Key(key) => {
-                    if app.mode == AppMode::WaitingForEditor {
-                        // Only allow quit while waiting for editor
-                        if key.code == KeyCode::Char('q') {
-                            if let Some(state) = editor_state.take() {
-                                app.flush_pending_editor_state(
-                                    state.tmpfile.path(),
-                                    state.is_comment,
-                                    &state.original_content,
-                                );
-                            }
-                            break Ok(app.feedback);
-                        }
-                        continue;
-                    }
+    /// Line of the current hunk to highlight in the diff view: the inline
+    /// comment anchor while typing a comment, or the line cursor.
+    pub fn highlighted_line(&self) -> Option<usize> {
+        match self.mode {
+            AppMode::InlineComment => self.comment_anchor,
+            _ => self.line_cursor(),
+        }
+    }
 
-                    // Help mode: any key dismisses the overlay
-                    if app.mode == AppMode::Help {
-                        app.mode = AppMode::Browsing;
-                        app.dirty = true;
-                        mark_help_shown();
-                        continue;
-                    }
+    /// Lines of the current hunk to highlight in the diff view: like
+    /// [`highlighted_line`](Self::highlighted_line), but the whole
+    /// selection in line mode.
+    pub fn highlighted_lines(&self) -> Option<RangeInclusive<usize>> {
+        match self.mode {
+            AppMode::InlineComment => self.comment_anchor.map(|a| a..=a),
+            _ => self.selected_lines(),
+        }
+    }
 
-                    // Handle pending key sequences (gg)
-                    if app.pending_key == Some('g') {
-                        app.pending_key = None;
-                        app.message = None;
-                        if key.code == KeyCode::Char('g') {
-                            app.scroll_to_top();
-                            continue;
-                        }
-                        // Fall through to process the key normally
-                    }
+    /// The diff line an inline comment being typed will follow.
+    pub fn comment_anchor_line(&self) -> Option<&DiffLine> {
+        self.current_hunk()?.lines.get(self.comment_anchor?)
+    }
 
-                    // Handle Ctrl modifier keys
-                    if key.modifiers.contains(KeyModifiers::CONTROL) {
-                        match key.code {
-                            KeyCode::Char('d') => app.scroll_half_page_down(),
-                            KeyCode::Char('u') => app.scroll_half_page_up(),
-                            KeyCode::Char('f') => app.scroll_full_page_down(),
-                            KeyCode::Char('b') => app.scroll_full_page_up(),
-                            _ => {}
-                        }
-                        continue;
-                    }
+    /// Default line for an inline comment: the end of the line selection
+    /// in line mode, otherwise the last changed line of the current hunk,
+    /// so the comment reads right after the change.
+    fn default_comment_anchor(&self) -> Option<usize> {
+        if let Some(selected) = self.selected_lines() {
+            return Some(*selected.end());
+        }
+        let lines = &self.current_hunk()?.lines;
+        lines
+            .iter()
+            .rposition(|l| l.kind != LineKind::Context)
+            .or(lines.len().checked_sub(1))
+    }
 
-                    match key.code {
-                        KeyCode::Char('q') => {
-                            break Ok(app.feedback);
-                        }
-                        KeyCode::Char('j') => {
-                            if app.focus == FocusPanel::FileList {
-                                app.select_next_file();
-                            } else {
-                                app.scroll_down();
-                            }
-                        }
-                        KeyCode::Char('k') => {
-                            if app.focus == FocusPanel::FileList {
-                                app.select_prev_file();
-                            } else {
-                                app.scroll_up();
-                            }
-                        }
-                        KeyCode::Char('J') | KeyCode::Char('}') => app.select_next_hunk(),
-                        KeyCode::Char('K') | KeyCode::Char('{') => app.select_prev_hunk(),
-                        KeyCode::Char('H') => app.select_prev_file(),
-                        KeyCode::Char('L') => app.select_next_file(),
-                        KeyCode::Char('h') => {
-                            app.focus = FocusPanel::FileList;
-                            app.dirty = true;
-                        }
-                        KeyCode::Char('l') => {
-                            app.focus = FocusPanel::DiffView;
-                            app.dirty = true;
-                        }
-                        KeyCode::Char('G') => app.scroll_to_bottom(),
-                        KeyCode::Char('g') => {
-                            app.pending_key = Some('g');
-                            app.message = Some("g...".to_string());
-                            app.dirty = true;
-                        }
-                        KeyCode::Char('?') => {
-                            app.mode = AppMode::Help;
-                            app.dirty = true;
-                        }
-                        KeyCode::Down => {
-                            if app.focus == FocusPanel::FileList {
-                                app.select_next_file();
-                            } else {
-                                app.select_next_hunk();
-                            }
-                        }
-                        KeyCode::Up => {
-                            if app.focus == FocusPanel::FileList {
-                                app.select_prev_file();
-                            } else {
-                                app.select_prev_hunk();
-                            }
-                        }
-                        KeyCode::Tab => app.toggle_focus(),
-                        KeyCode::Char('y') => match repo {
-                            Some(r) => {
-                                if let Err(e) = app.stage_current_hunk(r) {
-                                    app.message = Some(format!("Stage error: {}", e));
-                                }
-                            }
-                            None => app.accept_current_hunk(),
-                        },
-                        KeyCode::Char('n') => app.skip_current_hunk(),
-                        KeyCode::Char('s') => app.split_current_hunk(),
-                        KeyCode::Char('e') => match app.start_edit() {
-                            Ok(Some(state)) => {
-                                editor_state = Some(state);
-                            }
-                            Ok(None) => {
-                                app.message = Some("No hunk selected".to_string());
-                            }
-                            Err(e) => {
-                                app.message = Some(format!("Edit error: {}", e));
-                            }
-                        },
-                        KeyCode::Char('c') => match app.start_comment() {
-                            Ok(Some(state)) => {
-                                editor_state = Some(state);
-                            }
-                            Ok(None) => {
-                                app.message = Some("No hunk selected".to_string());
-                            }
-                            Err(e) => {
-                                app.message = Some(format!("Comment error: {}", e));
-                            }
-                        },
-                        _ => {}
-                    }
-                }
-                Event::Mouse(mouse) => match mouse.kind {
-                    MouseEventKind::ScrollDown => app.scroll_down(),
-                    MouseEventKind::ScrollUp => app.scroll_up(),
-                    MouseEventKind::Down(MouseButton::Left) => {
-                        app.handle_mouse_click(mouse.column, mouse.row);
-                    }
-                    _ => {}
-                },
-                Event::Resize(_, _) => {
-                    app.dirty = true;
-                }
-                _ => {}
-            }
+    /// Open the inline comment box for the current hunk.
+    pub fn start_inline_comment(&mut self) {
+        self.dirty = true;
+        if let Some(noun) = self.current_placeholder() {
+            self.warn(format!("A {} can only be staged or skipped", noun));
+            return;
         }
+        let Some(anchor) = self.default_comment_anchor() else {
+            self.warn("No hunk selected");
+            return;
+        };
+        self.comment_anchor = Some(anchor);
+        self.comment_input.clear();
+        self.mode = AppMode::InlineComment;
+        self.message = None;
     }
-    // _guard will restore terminal on drop
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::types::{DeltaStatus, DiffLine, HunkStatus, LineKind};
+    /// Close the inline comment box without saving.
+    pub fn cancel_inline_comment(&mut self) {
+        self.comment_input.clear();
+        self.comment_anchor = None;
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+    }
 
-    fn make_test_files() -> Vec<FileDiff> {
-        vec![
-            FileDiff {
-                path: "src/a.rs".into(),
-                hunks: vec![
-                    Hunk {
-                        header: "@@ -1,3 +1,4 @@".to_string(),
-                        lines: vec![
-                            DiffLine {
-                                kind: LineKind::Context,
-                                content: "line1\n".to_string(),
-                                old_lineno: Some(1),
-                                new_lineno: Some(1),
-                            },
-                            DiffLine {
-                                kind: LineKind::Removed,
-                                content: "old\n".to_string(),
-                                old_lineno: Some(2),
-                                new_lineno: None,
-                            },
-                            DiffLine {
-                                kind: LineKind::Added,
-                                content: "new\n".to_string(),
-                                old_lineno: None,
-                                new_lineno: Some(2),
-                            },
-                            DiffLine {
-                                kind: LineKind::Context,
-                                content: "line3\n".to_string(),
-                                old_lineno: Some(3),
-                                new_lineno: Some(3),
-                            },
-                        ],
-                        status: HunkStatus::Pending,
-                        old_start: 1,
-                        old_lines: 3,
-                        new_start: 1,
-                        new_lines: 3,
-                    },
-                    Hunk {
-                        header: "@@ -20,3 +21,4 @@".to_string(),
-                        lines: vec![DiffLine {
-                            kind: LineKind::Added,
-                            content: "added line\n".to_string(),
-                            old_lineno: None,
-                            new_lineno: Some(22),
-                        }],
-                        status: HunkStatus::Pending,
-                        old_start: 20,
-                        old_lines: 3,
-                        new_start: 21,
-                        new_lines: 4,
-                    },
-                ],
-                status: DeltaStatus::Modified,
-                is_binary: false,
+    /// Save the typed inline comment and close the box. Empty input is
+    /// discarded.
+    pub fn submit_inline_comment(&mut self) {
+        let text = std::mem::take(&mut self.comment_input);
+        let anchor = self.comment_anchor.take();
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+        match anchor {
+            Some(anchor) if !text.trim().is_empty() => {
+                self.add_line_comment(anchor, text.trim());
+            }
+            _ => self.warn("Empty comment discarded"),
+        }
+    }
+
+    /// Attach `text` as a comment after line `anchor` of the current hunk
+    /// (see [`review::add_comment`]).
+    pub fn add_line_comment(&mut self, anchor: usize, text: &str) {
+        let (fi, hi) = (self.selected_file, self.selected_hunk);
+        let Some(file) = self.files.get(fi).filter(|f| hi < f.hunks.len()) else {
+            return;
+        };
+        self.dirty = true;
+        let Some((index, severity)) =
+            review::add_comment(&mut self.feedback, file, hi, anchor, text)
+        else {
+            self.warn("Empty comment discarded");
+            return;
+        };
+        self.journal_feedback(index..index + 1);
+
+        self.files[fi].hunks[hi].status = HunkStatus::Commented;
+        self.info(match severity {
+            Some(severity) => format!("Comment added ({})", severity.label()),
+            None => "Comment added".to_string(),
+        });
+    }
+
+    /// Previous-round comments still unaddressed in a hunk.
+    pub fn unaddressed_comments(&self, file_idx: usize, hunk_idx: usize) -> Vec<&PreviousComment> {
+        let Some(file) = self.files.get(file_idx) else {
+            return Vec::new();
+        };
+        let Some(hunk) = file.hunks.get(hunk_idx) else {
+            return Vec::new();
+        };
+        self.previous_comments
+            .iter()
+            .filter(|c| c.path == file.path && c.is_unaddressed(hunk))
+            .collect()
+    }
+
+    /// Show the unaddressed previous comments on the current hunk in the
+    /// status bar.
+    pub fn show_previous_comments(&mut self) {
+        let comments = self.unaddressed_comments(self.selected_file, self.selected_hunk);
+        if comments.is_empty() {
+            self.warn("No unaddressed previous comments on this hunk");
+        } else {
+            let texts: Vec<String> = comments.iter().map(|c| c.text.replace('\n', " ")).collect();
+            self.info(format!("Previous comment: {}", texts.join(" | ")));
+        }
+        self.dirty = true;
+    }
+
+    /// Append feedback items `indices` to the journal. A failing journal
+    /// is reported and dropped; feedback is still written on quit.
+    fn journal_feedback(&mut self, mut indices: Range<usize>) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        let result = indices.try_for_each(|i| journal.record(i, &self.feedback[i]));
+        if let Err(e) = result {
+            self.journal = None;
+            self.error(format!("Feedback journal disabled: {:#}", e));
+        }
+    }
+
+    /// Show the feedback panel listing the captured feedback.
+    pub fn open_feedback_panel(&mut self) {
+        self.dirty = true;
+        if self.feedback.is_empty() {
+            self.warn("No feedback captured");
+            return;
+        }
+        self.feedback_cursor = self.feedback_cursor.min(self.feedback.len() - 1);
+        self.mode = AppMode::FeedbackPanel;
+    }
+
+    /// Handle a key in the feedback panel: move, delete the item under the
+    /// cursor, go to its hunk, or close the panel.
+    pub fn handle_feedback_panel_key(&mut self, code: KeyCode) {
+        self.dirty = true;
+        match code {
+            KeyCode::Char('j') | KeyCode::Down
+                if self.feedback_cursor + 1 < self.feedback.len() =>
+            {
+                self.feedback_cursor += 1;
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.feedback_cursor = self.feedback_cursor.saturating_sub(1);
+            }
+            KeyCode::Char('d') | KeyCode::Char('x') | KeyCode::Delete => {
+                self.delete_feedback(self.feedback_cursor);
+                if self.feedback.is_empty() {
+                    self.mode = AppMode::Browsing;
+                }
+            }
+            KeyCode::Enter => {
+                self.mode = AppMode::Browsing;
+                self.jump_to_feedback(self.feedback_cursor);
+            }
+            KeyCode::Esc | KeyCode::Char('F') | KeyCode::Char('q') => {
+                self.mode = AppMode::Browsing;
+            }
+            _ => {}
+        }
+    }
+
+    /// Delete feedback item `index`. A hunk left without feedback goes
+    /// back to pending if it was commented or edited.
+    pub fn delete_feedback(&mut self, index: usize) {
+        if index >= self.feedback.len() {
+            return;
+        }
+        let fb = self.feedback.remove(index);
+        if let Some(journal) = &mut self.journal
+            && let Err(e) = journal.record_removal(index)
+        {
+            self.journal = None;
+            self.error(format!("Feedback journal disabled: {:#}", e));
+        }
+        self.feedback_cursor = self
+            .feedback_cursor
+            .min(self.feedback.len().saturating_sub(1));
+
+        let orphaned = !self
+            .feedback
+            .iter()
+            .any(|f| f.file_path == fb.file_path && f.hunk_header == fb.hunk_header);
+        if orphaned
+            && let Some(hunk) = self
+                .files
+                .iter_mut()
+                .filter(|f| f.path.to_string_lossy() == fb.file_path)
+                .flat_map(|f| f.hunks.iter_mut())
+                .find(|h| h.header == fb.hunk_header)
+            && matches!(hunk.status, HunkStatus::Commented | HunkStatus::Edited)
+        {
+            hunk.status = HunkStatus::Pending;
+        }
+    }
+
+    /// Select the file (and hunk, if it has one) of feedback item `index`.
+    fn jump_to_feedback(&mut self, index: usize) {
+        let Some(fb) = self.feedback.get(index) else {
+            return;
+        };
+        let Some(fi) = self
+            .files
+            .iter()
+            .position(|f| f.path.to_string_lossy() == fb.file_path)
+        else {
+            return;
+        };
+        let hunk = self.files[fi]
+            .hunks
+            .iter()
+            .position(|h| h.header == fb.hunk_header)
+            .unwrap_or(0);
+        self.record_jump((fi, hunk));
+        self.selected_file = fi;
+        self.selected_hunk = hunk;
+        self.scroll_to_selected_hunk();
+    }
+
+    /// Show the outcome of an action in the status bar until it expires
+    /// (see [`crate::messages`]), and keep it in the history.
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.show_message(MessageLevel::Info, text);
+    }
+
+    /// [`info`](Self::info) for an action that did nothing.
+    pub fn warn(&mut self, text: impl Into<String>) {
+        self.show_message(MessageLevel::Warn, text);
+    }
+
+    /// [`info`](Self::info) for an action that failed.
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.show_message(MessageLevel::Error, text);
+    }
+
+    fn show_message(&mut self, level: MessageLevel, text: impl Into<String>) {
+        let message = Message::new(level, text);
+        self.message_history.push(message.clone());
+        self.message = Some(message);
+        self.dirty = true;
+    }
+
+    /// Show a question or hint for the current mode: it stays while the
+    /// mode lasts and isn't kept in the history.
+    pub fn prompt(&mut self, text: impl Into<String>) {
+        self.message = Some(Message::new(MessageLevel::Info, text));
+        self.dirty = true;
+    }
+
+    /// Clear the status bar message once its time is up at `now`. Prompts
+    /// of other modes stay.
+    pub fn expire_message(&mut self, now: Instant) {
+        if self.mode == AppMode::Browsing
+            && self.pending_key.is_none()
+            && self.message.as_ref().is_some_and(|m| m.is_expired(now))
+        {
+            self.message = None;
+            self.dirty = true;
+        }
+    }
+
+    /// Open or close the message history overlay (`M`).
+    pub fn toggle_message_history(&mut self) {
+        self.mode = match self.mode {
+            AppMode::MessageHistory => AppMode::Browsing,
+            _ => AppMode::MessageHistory,
+        };
+        self.dirty = true;
+    }
+
+    /// Enter `:` command mode.
+    pub fn start_command(&mut self) {
+        self.command_input.clear();
+        self.mode = AppMode::Command;
+        self.message = None;
+        self.dirty = true;
+    }
+
+    /// Complete the command name typed so far (Tab): a single match is
+    /// completed, several are completed to their common prefix and listed
+    /// in `message`.
+    pub fn complete_command(&mut self) {
+        self.dirty = true;
+        if self.command_input.contains(char::is_whitespace) {
+            return;
+        }
+        let matches: Vec<&(&str, &str)> = COMMANDS
+            .iter()
+            .filter(|(name, _)| name.starts_with(self.command_input.as_str()))
+            .collect();
+        match matches.as_slice() {
+            [] => self.prompt("No such command"),
+            [(name, args)] => {
+                self.command_input = name.to_string();
+                if !args.is_empty() {
+                    self.command_input.push(' ');
+                }
+                self.message = (!args.is_empty()).then(|| Message::new(MessageLevel::Info, *args));
+            }
+            [(first, _), rest @ ..] => {
+                let common = rest.iter().fold(first.len(), |len, (name, _)| {
+                    first
+                        .bytes()
+                        .zip(name.bytes())
+                        .take(len)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                });
+                self.command_input = first[..common].to_string();
+                let names: Vec<&str> = matches.iter().map(|(name, _)| *name).collect();
+                self.prompt(names.join(" "));
+            }
+        }
+    }
+
+    /// Leave command mode without running anything.
+    pub fn cancel_command(&mut self) {
+        self.command_input.clear();
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+    }
+
+    /// Run a `:` command (without the leading colon). The result is reported
+    /// through `message`.
+    ///
+    /// Commands:
+    /// - `w <file>` / `write <file>`: write the feedback collected so far
+    /// - `format diff|json`: feedback output format
+    /// - `theme dark|light|auto|<syntax theme>`: switch the color theme
+    /// - `files [glob]`: only show matching files (no glob shows all)
+    /// - `sort path|pending|lines|risk`: re-sort the file list
+    /// - `context <lines>`: context lines around comments in diff output
+    /// - `stage-file` / `skip-file`: stage or skip the current file's
+    ///   pending hunks
+    /// - `stage-matching <regex>`: stage the pending hunks matching a regex
+    /// - `q` / `quit`: quit
+    ///
+    /// The commands call the same methods as the keys; see [`COMMANDS`].
+    pub fn execute_command(&mut self, input: &str, repo: Option<&Repository>) -> CommandOutcome {
+        let mut parts = input.split_whitespace();
+        let Some(cmd) = parts.next() else {
+            return CommandOutcome::Continue;
+        };
+        let arg = parts.next();
+        self.dirty = true;
+
+        let result = match (cmd, arg) {
+            ("q" | "quit", _) => {
+                return if self.request_quit() {
+                    CommandOutcome::Quit
+                } else {
+                    CommandOutcome::Continue
+                };
+            }
+            ("q!" | "quit!", _) => return CommandOutcome::Quit,
+            ("w" | "write", Some(path)) => self.write_feedback_to(Path::new(path)),
+            ("w" | "write", None) => Err("Usage: :w <file>".to_string()),
+            ("patch", Some(path)) => self.write_edits_patch_to(Path::new(path)),
+            ("patch", None) => Err("Usage: :patch <file>".to_string()),
+            ("format", None) => Ok(format!("Feedback format: {:?}", self.feedback_format)),
+            ("format", Some(name)) => match FeedbackFormat::from_str(name, true) {
+                Ok(format) => {
+                    self.feedback_format = format;
+                    Ok(format!("Feedback format: {:?}", format))
+                }
+                Err(_) => Err(format!("Unknown format '{}' (diff, json, gerrit)", name)),
             },
-            FileDiff {
-                path: "src/b.rs".into(),
-                hunks: vec![Hunk {
-                    header: "@@ -5,3 +5,3 @@".to_string(),
-                    lines: vec![
-                        DiffLine {
-                            kind: LineKind::Removed,
-                            content: "foo\n".to_string(),
-                            old_lineno: Some(6),
-                            new_lineno: None,
-                        },
-                        DiffLine {
-                            kind: LineKind::Added,
-                            content: "bar\n".to_string(),
-                            old_lineno: None,
-                            new_lineno: Some(6),
-                        },
-                    ],
-                    status: HunkStatus::Pending,
-                    old_start: 5,
-                    old_lines: 3,
-                    new_start: 5,
-                    new_lines: 3,
-                }],
-                status: DeltaStatus::Modified,
-                is_binary: false,
+            ("theme", Some(name)) => match theme::select(name) {
+                Some(variant) => {
+                    self.highlight_cache.clear();
+                    Ok(format!("Theme: {:?} ({})", variant, theme::syntect_theme()))
+                }
+                None => Err(format!(
+                    "Unknown theme '{}' (dark, light, auto, or a syntax theme)",
+                    name
+                )),
             },
-        ]
+            ("theme", None) => Err("Usage: :theme dark|light|auto|<syntax theme>".to_string()),
+            ("sort", None) => Ok(format!("Sorted by {:?}", self.order)),
+            ("sort", Some(name)) => match ReviewOrder::from_str(name, true) {
+                Ok(ReviewOrder::Risk) if self.risk.is_empty() => {
+                    Err("Risk scores are only computed with --order risk".to_string())
+                }
+                Ok(order) => {
+                    self.set_order(order);
+                    Ok(format!("Sorted by {:?}", order))
+                }
+                Err(_) => Err(format!(
+                    "Unknown sort order '{}' (path, pending, lines, risk)",
+                    name
+                )),
+            },
+            ("files", pattern) => self
+                .filter_files(pattern)
+                .map(|n| format!("Showing {} file(s)", n)),
+            ("context", None) => Ok(format!("Context lines: {}", self.context_lines)),
+            ("context", Some(n)) => match n.parse() {
+                Ok(lines) => {
+                    self.context_lines = lines;
+                    Ok(format!("Context lines: {}", lines))
+                }
+                Err(_) => Err(format!("Invalid number of lines '{}'", n)),
+            },
+            ("stage-file", None) => self.stage_current_file(repo),
+            ("skip-file", None) => self.skip_current_file(),
+            // The pattern is the rest of the line, spaces included
+            ("stage-matching", None) => Err("Usage: :stage-matching <regex>".to_string()),
+            ("stage-matching", Some(_)) => {
+                let (_, pattern) = input
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .unwrap_or_default();
+                self.start_stage_matching(pattern.trim())
+            }
+            _ => Err(format!("Unknown command: {}", cmd)),
+        };
+
+        match result {
+            // The question of a prompt the command opened
+            Ok(msg) if self.mode != AppMode::Browsing => self.prompt(msg),
+            Ok(msg) => self.info(msg),
+            Err(msg) => self.error(msg),
+        }
+        CommandOutcome::Continue
+    }
+
+    /// Write the feedback collected so far to `path` in the current format.
+    fn write_feedback_to(&self, path: &Path) -> Result<String, String> {
+        if self.feedback.is_empty() {
+            return Err("No feedback to write".to_string());
+        }
+        feedback::format_feedback_as(
+            &self.feedback,
+            self.verdict.as_ref(),
+            self.review_timing().as_ref(),
+            self.context_lines,
+            self.feedback_format,
+            self.output_style,
+        )
+        .and_then(|output| feedback::write_feedback(&output, Some(path)))
+        .map(|()| {
+            format!(
+                "Wrote {} feedback item(s) to {}",
+                self.feedback.len(),
+                path.display()
+            )
+        })
+        .map_err(|e| format!("Write error: {:#}", e))
+    }
+
+    /// Write the edits as a patch against the working tree for `:patch`.
+    fn write_edits_patch_to(&self, path: &Path) -> Result<String, String> {
+        let edits = |skipped: &[String]| {
+            self.feedback
+                .iter()
+                .filter(|fb| fb.kind == FeedbackKind::Edit && !skipped.contains(&fb.file_path))
+                .count()
+        };
+        if edits(&[]) == 0 {
+            return Err("No edits to write".to_string());
+        }
+        let root = self.workdir.clone().unwrap_or_else(|| PathBuf::from("."));
+        crate::export::edits::edits_patch(&root, &self.feedback)
+            .and_then(|edits_patch| {
+                std::fs::write(path, &edits_patch.patch)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                Ok(edits_patch.skipped)
+            })
+            .map(|skipped| {
+                let mut message =
+                    format!("Wrote {} edit(s) to {}", edits(&skipped), path.display());
+                if !skipped.is_empty() {
+                    message.push_str(&format!(
+                        ", left out {}: not UTF-8 text",
+                        skipped.join(", ")
+                    ));
+                }
+                message
+            })
+            .map_err(|e| format!("Patch error: {:#}", e))
+    }
+
+    /// Show only files whose path matches `pattern`, or every file with
+    /// `None`. The pattern is relative to the current directory, like
+    /// `--files`, and `!` negates it. Hunk statuses of hidden files are
+    /// kept. Returns the number of files shown.
+    pub fn filter_files(&mut self, pattern: Option<&str>) -> Result<usize, String> {
+        let matcher = pattern
+            .map(|p| PathFilter::new(&self.cwd_prefix, &[p.to_string()], &[]))
+            .transpose()
+            .map_err(|e| format!("{:#}", e))?;
+
+        let mut all = std::mem::take(&mut self.files);
+        all.append(&mut self.hidden_files);
+        self.sort_files(&mut all);
+
+        let (shown, hidden): (Vec<FileDiff>, Vec<FileDiff>) = match matcher {
+            Some(ref m) => all.into_iter().partition(|f| m.matches(&f.path)),
+            None => (all, Vec::new()),
+        };
+        if shown.is_empty() {
+            // Nothing matches: keep showing everything
+            self.files = hidden;
+            return Err(format!("No files match '{}'", pattern.unwrap_or("")));
+        }
+
+        self.files = shown;
+        self.hidden_files = hidden;
+        self.selected_file = 0;
+        self.selected_hunk = 0;
+        self.scroll_offset = 0;
+        self.highlight_cache.clear();
+        self.expanded_context.clear();
+        self.marked.clear();
+        self.jumps.clear();
+        self.dirty = true;
+        Ok(self.files.len())
+    }
+
+    /// Sort `files` into the review order.
+    fn sort_files(&self, files: &mut [FileDiff]) {
+        files.sort_by(|a, b| self.compare_files(a, b));
+    }
+
+    /// Comparison of two files in the review order; ties go by path.
+    fn compare_files(&self, a: &FileDiff, b: &FileDiff) -> std::cmp::Ordering {
+        let by_path = || a.path.cmp(&b.path);
+        match self.order {
+            ReviewOrder::Path => by_path(),
+            ReviewOrder::Risk => risk::compare_by_risk(a, b, &self.risk),
+            ReviewOrder::Pending => {
+                let pending = |f: &FileDiff| {
+                    f.hunks
+                        .iter()
+                        .filter(|h| h.status == HunkStatus::Pending)
+                        .count()
+                };
+                pending(b).cmp(&pending(a)).then_with(by_path)
+            }
+            ReviewOrder::Lines => {
+                let changed = |f: &FileDiff| {
+                    f.hunks
+                        .iter()
+                        .flat_map(|h| &h.lines)
+                        .filter(|l| l.kind != LineKind::Context)
+                        .count()
+                };
+                changed(b).cmp(&changed(a)).then_with(by_path)
+            }
+        }
+    }
+
+    /// Re-sort the file list in `order`. Selection, marks, jumps and
+    /// expanded context follow their files to their new positions.
+    pub fn set_order(&mut self, order: ReviewOrder) {
+        self.order = order;
+        let mut perm: Vec<usize> = (0..self.files.len()).collect();
+        perm.sort_by(|&a, &b| self.compare_files(&self.files[a], &self.files[b]));
+        let mut new_index = vec![0; perm.len()];
+        for (new, &old) in perm.iter().enumerate() {
+            new_index[old] = new;
+        }
+
+        let mut old_files: Vec<Option<FileDiff>> = std::mem::take(&mut self.files)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.files = perm.iter().filter_map(|&i| old_files[i].take()).collect();
+        let mut hidden = std::mem::take(&mut self.hidden_files);
+        self.sort_files(&mut hidden);
+        self.hidden_files = hidden;
+
+        if let Some(&new) = new_index.get(self.selected_file) {
+            self.selected_file = new;
+        }
+        self.marked = self
+            .marked
+            .iter()
+            .map(|&(fi, hi)| (new_index[fi], hi))
+            .collect();
+        self.jumps.remap_files(&new_index);
+        self.expanded_context = std::mem::take(&mut self.expanded_context)
+            .into_iter()
+            .map(|((fi, hi), v)| ((new_index[fi], hi), v))
+            .collect();
+        self.highlight_cache.remap(&new_index);
+        self.dirty = true;
+    }
+
+    /// Indices of the files listed in the file list: those matching the
+    /// `f` filter.
+    pub fn shown_files(&self) -> Vec<usize> {
+        (0..self.files.len())
+            .filter(|&i| filter::fuzzy_match(&self.file_filter, &self.files[i].path))
+            .collect()
+    }
+
+    /// Handle a key while the help overlay is shown: `j`/`k` scroll, `/`
+    /// searches, any other key closes it. Returns whether it was closed.
+    pub fn handle_help_key(&mut self, code: KeyCode) -> bool {
+        self.dirty = true;
+        if self.help_searching {
+            match code {
+                KeyCode::Enter => self.help_searching = false,
+                KeyCode::Esc => {
+                    self.help_searching = false;
+                    self.help_query.clear();
+                }
+                KeyCode::Backspace => {
+                    self.help_query.pop();
+                }
+                KeyCode::Char(c) => self.help_query.push(c),
+                _ => {}
+            }
+            self.help_scroll = 0;
+            return false;
+        }
+        match code {
+            KeyCode::Char('j') | KeyCode::Down => self.help_scroll += 1,
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.help_scroll = self.help_scroll.saturating_sub(1)
+            }
+            KeyCode::Char('/') => self.help_searching = true,
+            _ => {
+                self.mode = AppMode::Browsing;
+                self.help_scroll = 0;
+                self.help_query.clear();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Start typing a file list filter.
+    pub fn start_file_filter(&mut self) {
+        self.mode = AppMode::FileFilter;
+        self.focus = FocusPanel::FileList;
+        self.message = None;
+        self.dirty = true;
+    }
+
+    /// Apply the filter being typed: if the selected file no longer
+    /// matches, select the first file that does.
+    pub fn update_file_filter(&mut self) {
+        let shown = self.shown_files();
+        if !shown.contains(&self.selected_file)
+            && let Some(&first) = shown.first()
+        {
+            self.selected_file = first;
+            self.selected_hunk = 0;
+            self.cursor_line = 0;
+            self.scroll_offset = 0;
+        }
+        self.selected_dir = None;
+        self.dirty = true;
+    }
+
+    /// Stop typing and keep the filter.
+    pub fn accept_file_filter(&mut self) {
+        self.mode = AppMode::Browsing;
+        if !self.file_filter.is_empty() {
+            self.info(format!(
+                "Showing {} of {} file(s)",
+                self.shown_files().len(),
+                self.files.len()
+            ));
+        }
+        self.dirty = true;
+    }
+
+    /// Stop typing and show every file again.
+    pub fn clear_file_filter(&mut self) {
+        self.file_filter.clear();
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+    }
+
+    /// Record the working tree state the current diff was computed from:
+    /// the reviewed files plus any other changed files.
+    pub fn snapshot_worktree(&mut self, repo: &Repository) {
+        self.worktree_snapshot = staging::snapshot_worktree(repo, &self.files);
+        self.worktree_snapshot
+            .extend(staging::snapshot_worktree(repo, &self.hidden_files));
+        if let Ok(changed) = git::worktree_changes(repo) {
+            staging::extend_snapshot(repo, &mut self.worktree_snapshot, &changed);
+        }
+    }
+
+    /// Recompute the diff from the working tree. Hunks that make the same
+    /// change as before keep their status (see
+    /// [`diff::carry_over_statuses`]), new hunks are pending and hunks
+    /// staged in the meantime drop out. The selected file stays selected;
+    /// a `:files` filter is cleared.
+    pub fn refresh(&mut self, repo: &Repository) -> Result<()> {
+        if !self.no_intent_to_add {
+            let added = git::intent_to_add_untracked(repo)?;
+            self.intent_added.extend(added);
+        }
+        let mut files = git::get_unstaged_diff_with(repo, &self.diff_settings)?;
+        files.retain(|f| self.path_filter.matches(&f.path));
+        if let Some(max_lines) = self.auto_split {
+            diff::auto_split(&mut files, max_lines);
+        }
+        if self.order == ReviewOrder::Risk {
+            self.risk = risk::score_files(repo, &files);
+        }
+        self.sort_files(&mut files);
+
+        let selected_path = self.current_file().map(|f| f.path.clone());
+        let selected_hunk = self.selected_hunk;
+        // Hidden files' statuses must be carried over too
+        self.filter_files(None).ok();
+        diff::carry_over_statuses(&self.files, &mut files);
+        self.files = files;
+
+        self.selected_file = selected_path
+            .and_then(|path| self.files.iter().position(|f| f.path == path))
+            .unwrap_or(0);
+        self.selected_hunk = self
+            .current_file()
+            .map_or(0, |f| selected_hunk.min(f.hunks.len().saturating_sub(1)));
+        self.scroll_offset = 0;
+        self.cursor_line = 0;
+        self.marked.clear();
+        self.jumps.clear();
+        self.expanded_context.clear();
+        self.blame.clear();
+        self.highlight_cache.clear();
+        self.snapshot_worktree(repo);
+        self.info("Diff refreshed");
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// [`refresh`](Self::refresh) if the working tree changed since the
+    /// diff was loaded. Returns whether it did.
+    pub fn refresh_if_changed(&mut self, repo: &Repository) -> Result<bool> {
+        let changed = git::worktree_changes(repo)?;
+        if !staging::worktree_changed(repo, &self.worktree_snapshot, &changed) {
+            return Ok(false);
+        }
+        self.refresh(repo)?;
+        Ok(true)
+    }
+
+    /// Handle a mouse click at the given coordinates. Clicking another
+    /// file or hunk is a jump.
+    pub fn handle_mouse_click(&mut self, column: u16, row: u16) {
+        let from = self.position();
+        self.click(column, row);
+        if self.position() != from {
+            self.jumps.record(from);
+        }
+    }
+
+    fn click(&mut self, column: u16, row: u16) {
+        // Check if click is within file list area
+        let area = self.file_list_area;
+        if column >= area.x
+            && column < area.x + area.width
+            && row >= area.y
+            && row < area.y + area.height
+        {
+            // +1 for the border, row within the list content, shifted by
+            // the rows scrolled off the top
+            let list_row = row.saturating_sub(area.y + 1);
+            let idx = self.file_list_offset + list_row as usize;
+            if self.tree_view {
+                let rows = self.file_tree_rows();
+                if let Some(clicked) = rows.get(idx) {
+                    self.select_tree_row(clicked);
+                    if let FileTreeRow::Dir { path, .. } = clicked
+                        && !self.collapsed_dirs.remove(path)
+                    {
+                        self.collapsed_dirs.insert(path.clone());
+                    }
+                    self.focus = FocusPanel::FileList;
+                }
+            } else if let Some(&file_idx) = self.shown_files().get(idx) {
+                self.selected_file = file_idx;
+                self.selected_hunk = 0;
+                self.scroll_offset = 0;
+                self.focus = FocusPanel::FileList;
+                self.dirty = true;
+            }
+            return;
+        }
+
+        // In the diff view, a hunk header selects the hunk and a line puts
+        // the line cursor on it
+        match self.diff_target_at(column, row) {
+            Some((hunk_idx, None)) => {
+                self.selected_hunk = hunk_idx;
+                self.line_mode = false;
+                self.line_anchor = None;
+            }
+            Some((hunk_idx, Some(line_idx))) => {
+                self.selected_hunk = hunk_idx;
+                self.line_mode = true;
+                self.line_anchor = None;
+                self.cursor_line = line_idx;
+            }
+            None => return,
+        }
+        self.focus = FocusPanel::DiffView;
+        self.dirty = true;
+    }
+
+    /// Handle a mouse drag to the given coordinates: in line mode, extend
+    /// the line selection of the current hunk from the clicked line.
+    pub fn handle_mouse_drag(&mut self, column: u16, row: u16) {
+        let Some(cursor) = self.line_cursor() else {
+            return;
+        };
+        let Some((hunk_idx, Some(line_idx))) = self.diff_target_at(column, row) else {
+            return;
+        };
+        if hunk_idx != self.selected_hunk {
+            return;
+        }
+        let here = (self.selected_file, self.selected_hunk);
+        if self.line_anchor.is_none_or(|(fi, hi, _)| (fi, hi) != here) {
+            self.line_anchor = Some((here.0, here.1, cursor));
+        }
+        self.cursor_line = line_idx;
+        self.dirty = true;
+    }
+
+    /// The hunk of the current file drawn at screen position (`column`,
+    /// `row`) of the diff view, with the index of the hunk line there; the
+    /// line is `None` on the header and on expanded context.
+    fn diff_target_at(&self, column: u16, row: u16) -> Option<(usize, Option<usize>)> {
+        let area = self.diff_view_area;
+        // Inside the borders
+        if column <= area.x
+            || column + 1 >= area.x + area.width
+            || row <= area.y
+            || row + 1 >= area.y + area.height
+        {
+            return None;
+        }
+        let mut target = self.scroll_offset + u32::from(row - area.y - 1);
+        let left_column = column - area.x - 1 < area.width.saturating_sub(2) / 2;
+        let file = self.current_file()?;
+        for (hunk_idx, hunk) in file.hunks.iter().enumerate() {
+            let (before, after) = self
+                .expanded_context
+                .get(&(self.selected_file, hunk_idx))
+                .map_or((0, 0), |(_, ctx)| (ctx.before.len(), ctx.after.len()));
+            let body = self.hunk_body_rows(self.selected_file, hunk_idx, hunk) as usize;
+            let rows = 1 + body as u32 + 1;
+            if target >= rows {
+                target -= rows;
+                continue;
+            }
+            if target == 0 {
+                return Some((hunk_idx, None));
+            }
+            // Expanded context and the separator select just the hunk
+            let body_row = target as usize - 1;
+            if body_row < before || body_row >= body - after {
+                return Some((hunk_idx, None));
+            }
+            // Long lines can take more than one row
+            let mut item = body_row - before;
+            if self.wrap_lines {
+                let heights = self.body_row_heights(self.selected_file, hunk);
+                let mut rows = item;
+                item = heights
+                    .iter()
+                    .position(|&height| {
+                        let here = rows < height;
+                        rows = rows.saturating_sub(height);
+                        here
+                    })
+                    .unwrap_or(heights.len().saturating_sub(1));
+            }
+            let line = match self.view_mode {
+                ViewMode::Unified => Some(item),
+                ViewMode::SideBySide => {
+                    let (old, new) = ui::diff_view::side_by_side_rows(hunk)[item];
+                    if left_column {
+                        old.or(new)
+                    } else {
+                        new.or(old)
+                    }
+                }
+            };
+            return Some((hunk_idx, line));
+        }
+        None
+    }
+
+    /// Flush a pending editor result by reading the tempfile and processing it.
+    ///
+    /// Used for comments once the editor has closed, and when quitting with
+    /// the editor still open: whatever was saved so far is kept. (tmux wakes
+    /// stagent through `wait-for` the moment the editor exits, so `q` no
+    /// longer races a pane poll.)
+    ///
+    /// Returns `true` if feedback was actually captured, `false` otherwise.
+    pub fn flush_pending_editor_state(
+        &mut self,
+        tmpfile_path: &std::path::Path,
+        is_comment: bool,
+        original_content: &str,
+    ) -> bool {
+        let edited = std::fs::read_to_string(tmpfile_path).unwrap_or_default();
+        let captured = match self.parse_editor_result(original_content, &edited, is_comment) {
+            Some(fb) => {
+                self.record_editor_feedback(fb, is_comment);
+                true
+            }
+            None => false,
+        };
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+        captured
+    }
+
+    /// Capture an edit of the current hunk once the editor has closed.
+    /// When the edit could be staged, ask in `StageEditPrompt` mode
+    /// whether to apply and stage it or keep it as feedback; otherwise
+    /// record it as feedback like [`Self::flush_pending_editor_state`].
+    ///
+    /// Returns `true` if an edit was captured.
+    pub fn capture_edit(
+        &mut self,
+        repo: Option<&Repository>,
+        tmpfile_path: &std::path::Path,
+        original_content: &str,
+    ) -> bool {
+        let edited = std::fs::read_to_string(tmpfile_path).unwrap_or_default();
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+        let Some(fb) = self.parse_editor_result(original_content, &edited, false) else {
+            return false;
+        };
+        match editor::edited_new_side(original_content, &edited) {
+            Some(new_side) if repo.is_some() && self.can_stage_edit() => {
+                self.pending_edit = Some((fb, new_side));
+                self.mode = AppMode::StageEditPrompt;
+            }
+            _ => self.record_editor_feedback(fb, false),
+        }
+        true
+    }
+
+    /// Whether an edit of the current hunk could be applied to the working
+    /// tree and staged.
+    fn can_stage_edit(&self) -> bool {
+        !self.no_stage
+            && !self.reviewing_patch
+            && self.current_placeholder().is_none()
+            && self.current_hunk().is_some()
+    }
+
+    /// Feedback for the current hunk from editor tempfile content, or
+    /// `None` if nothing was changed.
+    fn parse_editor_result(
+        &self,
+        original_content: &str,
+        edited: &str,
+        is_comment: bool,
+    ) -> Option<HunkFeedback> {
+        let file = self.current_file()?;
+        let hunk = self.current_hunk()?;
+        editor::parse_editor_result(
+            original_content,
+            edited,
+            is_comment,
+            &file.path.to_string_lossy(),
+            &hunk.header,
+            &hunk.lines,
+        )
+    }
+
+    /// Index in the feedback of the current hunk's comment, if it has one.
+    fn current_comment_index(&self) -> Option<usize> {
+        let path = self.current_file()?.path.to_string_lossy();
+        let header = &self.current_hunk()?.header;
+        self.feedback.iter().position(|fb| {
+            fb.kind == FeedbackKind::Comment && fb.file_path == path && &fb.hunk_header == header
+        })
+    }
+
+    /// Record editor feedback for the current hunk and mark the hunk
+    /// commented or edited. A comment replaces the hunk's previous one,
+    /// which the editor was pre-filled with.
+    fn record_editor_feedback(&mut self, fb: HunkFeedback, is_comment: bool) {
+        let index = match is_comment.then(|| self.current_comment_index()).flatten() {
+            Some(i) => {
+                self.feedback[i] = fb;
+                i
+            }
+            None => {
+                self.feedback.push(fb);
+                self.feedback.len() - 1
+            }
+        };
+        self.journal_feedback(index..index + 1);
+        let fi = self.selected_file;
+        let hi = self.selected_hunk;
+        self.files[fi].hunks[hi].status = if is_comment {
+            HunkStatus::Commented
+        } else {
+            HunkStatus::Edited
+        };
+    }
+
+    /// Handle a key in the stage-edit prompt: `a`/`y` apply the edit to the
+    /// working tree and stage it, `f`/`n`/Esc keep it as feedback. If the
+    /// edit can't be staged it is kept as feedback too.
+    pub fn handle_stage_edit_key(&mut self, code: KeyCode, repo: Option<&Repository>) {
+        let stage = match code {
+            KeyCode::Char('a') | KeyCode::Char('y') => true,
+            KeyCode::Char('f') | KeyCode::Char('n') | KeyCode::Esc => false,
+            _ => return,
+        };
+        self.mode = AppMode::Browsing;
+        self.dirty = true;
+        let Some((fb, new_side)) = self.pending_edit.take() else {
+            return;
+        };
+        let result = match repo {
+            Some(repo) if stage => self.stage_edit(repo, &new_side),
+            _ => {
+                self.record_editor_feedback(fb, false);
+                self.info("Edit captured");
+                return;
+            }
+        };
+        match result {
+            Ok(()) => self.info("Edit applied and staged"),
+            Err(e) => {
+                self.record_editor_feedback(fb, false);
+                self.warn(format!("Kept as feedback: {:#}", e));
+            }
+        }
+    }
+
+    /// Whether staging the current hunk (or the lines selected in line
+    /// mode) removes its file from the index: the last of a deleted file.
+    pub fn stages_deletion(&self) -> bool {
+        let Some(file) = self.current_file() else {
+            return false;
+        };
+        let Some(hunk) = self.current_hunk() else {
+            return false;
+        };
+        let rest_staged = file
+            .hunks
+            .iter()
+            .enumerate()
+            .all(|(i, h)| i == self.selected_hunk || h.status == HunkStatus::Staged);
+        let whole_hunk = self.selected_lines().is_none_or(|selected| {
+            hunk.lines
+                .iter()
+                .enumerate()
+                .all(|(i, l)| l.kind == LineKind::Context || selected.contains(&i))
+        });
+        !self.no_stage
+            && file.status == DeltaStatus::Deleted
+            && hunk.status == HunkStatus::Pending
+            && rest_staged
+            && whole_hunk
+    }
+
+    /// Ask before staging the deletion of the current file (see
+    /// [`Self::stages_deletion`]).
+    pub fn confirm_deletion(&mut self) {
+        self.message = self
+            .current_file()
+            .map(|f| Message::new(MessageLevel::Info, f.path.display().to_string()));
+        self.mode = AppMode::DeletionPrompt;
+        self.dirty = true;
+    }
+
+    /// Handle a key in `DeletionPrompt` mode: `y` stages the deletion,
+    /// `n` or `Esc` leaves the file in the index.
+    pub fn handle_deletion_key(&mut self, code: KeyCode, repo: Option<&Repository>) {
+        let stage = match code {
+            KeyCode::Char('y') => true,
+            KeyCode::Char('n') | KeyCode::Esc => false,
+            _ => return,
+        };
+        self.mode = AppMode::Browsing;
+        self.message = None;
+        self.dirty = true;
+        let Some(repo) = repo.filter(|_| stage) else {
+            self.info("Deletion not staged");
+            return;
+        };
+        let result = if self.line_mode {
+            self.stage_current_line(Some(repo))
+        } else {
+            self.stage_current_hunk(repo)
+        };
+        match result {
+            Ok(()) => self.info("Deletion staged"),
+            Err(e) => self.report_stage_error(&e),
+        }
+    }
+
+    /// Apply `new_side` as the new side of the current hunk: write it to
+    /// the working tree and stage the resulting change. The hunk is
+    /// replaced by the staged one, and later hunks of the file move with
+    /// the lines the edit added or removed.
+    pub fn stage_edit(&mut self, repo: &Repository, new_side: &str) -> Result<()> {
+        let (fi, hi) = (self.selected_file, self.selected_hunk);
+        let file = &self.files[fi];
+        let hunk = &file.hunks[hi];
+        staging::check_unchanged(repo, &self.worktree_snapshot, &file.path)?;
+        let edited = diff::with_new_side(hunk, new_side);
+        let offset = self.compute_line_offset(fi, hi);
+        staging::stage_hunk(repo, file, &edited, offset)?;
+        staging::apply_to_worktree(repo, &file.path, hunk, new_side)?;
+
+        let path = file.path.clone();
+        let shift = edited.new_lines as i64 - hunk.new_lines as i64;
+        self.worktree_snapshot
+            .insert(path.clone(), staging::worktree_oid(repo, &path));
+        let hunks = &mut self.files[fi].hunks;
+        for later in &mut hunks[hi + 1..] {
+            later.new_start = (later.new_start as i64 + shift) as u32;
+            for line in &mut later.lines {
+                line.new_lineno = line.new_lineno.map(|n| (n as i64 + shift) as u32);
+            }
+        }
+        hunks[hi] = Hunk {
+            status: HunkStatus::Staged,
+            ..edited
+        };
+        self.marked.remove(&(fi, hi));
+        self.highlight_cache.clear();
+        self.expanded_context.retain(|(f, _), _| *f != fi);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Move the selection to a start position given on the command line.
+    ///
+    /// The file is matched by exact path or by path suffix so that paths
+    /// relative to a subdirectory still resolve. When a line is given, the
+    /// hunk whose new-side range contains it is selected (or the next hunk
+    /// after it). Returns `false` and sets a status message if the file or
+    /// hunk could not be found.
+    pub fn jump_to_position(&mut self, pos: &StartPosition) -> bool {
+        let file_idx = match &pos.path {
+            Some(path) => {
+                let path = path.strip_prefix("./").unwrap_or(path);
+                match self
+                    .files
+                    .iter()
+                    .position(|f| f.path == path || f.path.ends_with(path))
+                {
+                    Some(idx) => idx,
+                    None => {
+                        self.warn(format!("File not in diff: {}", path.display()));
+                        self.dirty = true;
+                        return false;
+                    }
+                }
+            }
+            None => self.selected_file,
+        };
+
+        let Some(file) = self.files.get(file_idx) else {
+            return false;
+        };
+
+        let hunk_idx = if let Some(line) = pos.line {
+            file.hunks
+                .iter()
+                .position(|h| line >= h.new_start && line < h.new_start + h.new_lines.max(1))
+                .or_else(|| file.hunks.iter().position(|h| h.new_start > line))
+                .unwrap_or(file.hunks.len().saturating_sub(1))
+        } else if let Some(n) = pos.hunk {
+            if n == 0 || n > file.hunks.len() {
+                self.warn(format!(
+                    "Hunk {} out of range (file has {})",
+                    n,
+                    file.hunks.len()
+                ));
+                self.dirty = true;
+                return false;
+            }
+            n - 1
+        } else {
+            0
+        };
+
+        self.record_jump((file_idx, hunk_idx));
+        self.selected_file = file_idx;
+        self.selected_hunk = hunk_idx;
+        self.scroll_to_selected_hunk();
+        self.dirty = true;
+        true
+    }
+
+    /// Scroll the diff view to the selected hunk: centered if it fits,
+    /// otherwise with its header [`HUNK_SCROLL_MARGIN`] rows from the top.
+    /// Rows are counted as drawn, wrapped lines and expanded context
+    /// included.
+    fn scroll_to_selected_hunk(&mut self) {
+        let Some(header) = self.selected_hunk_row() else {
+            return;
+        };
+        let rows = match self.current_hunk() {
+            Some(hunk) => 1 + self.hunk_body_rows(self.selected_file, self.selected_hunk, hunk),
+            None => 1,
+        };
+        let visible = self.diff_view_area.height.saturating_sub(2) as u32;
+        self.scroll_offset = if visible == 0 {
+            header
+        } else if rows + 2 * HUNK_SCROLL_MARGIN <= visible {
+            header.saturating_sub((visible - rows) / 2)
+        } else {
+            header.saturating_sub(HUNK_SCROLL_MARGIN)
+        };
+        self.clamp_scroll();
+    }
+
+    /// Row of the selected hunk's header in the current file's diff view.
+    fn selected_hunk_row(&self) -> Option<u32> {
+        let mut line_count: u32 = 0;
+        let file = self.files.get(self.selected_file)?;
+        for (idx, hunk) in file.hunks.iter().enumerate() {
+            if idx == self.selected_hunk {
+                return Some(line_count);
+            }
+            line_count += 1; // header
+            line_count += self.hunk_body_rows(self.selected_file, idx, hunk);
+            line_count += 1; // separator
+        }
+        None
+    }
+
+    /// Row of the line cursor in the current file's diff view.
+    fn cursor_row(&self) -> Option<u32> {
+        let hunk = self.current_hunk()?;
+        let before = self
+            .expanded_context
+            .get(&(self.selected_file, self.selected_hunk))
+            .map_or(0, |(_, ctx)| ctx.before.len());
+        let mut within = match self.view_mode {
+            ViewMode::Unified => self.cursor_line,
+            ViewMode::SideBySide => {
+                ui::diff_view::side_by_side_rows(hunk)
+                    .iter()
+                    .position(|(old, new)| {
+                        *old == Some(self.cursor_line) || *new == Some(self.cursor_line)
+                    })?
+            }
+        };
+        if self.wrap_lines {
+            within = self.body_row_heights(self.selected_file, hunk)[..within]
+                .iter()
+                .sum();
+        }
+        Some(self.selected_hunk_row()? + 1 + (before + within) as u32)
+    }
+
+    /// Scroll just enough to keep the line cursor in view.
+    fn scroll_to_cursor(&mut self) {
+        let visible = self.diff_view_area.height.saturating_sub(2) as u32;
+        let Some(row) = self.cursor_row() else {
+            return;
+        };
+        if row < self.scroll_offset {
+            self.scroll_offset = row;
+        } else if visible > 0 && row >= self.scroll_offset + visible {
+            self.scroll_offset = row + 1 - visible;
+        }
+    }
+}
+
+/// Guard that restores terminal state on drop (including panics).
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = crossterm::terminal::disable_raw_mode();
+        let _ = crossterm::execute!(
+            io::stdout(),
+            crossterm::terminal::LeaveAlternateScreen,
+            crossterm::event::DisableMouseCapture,
+        );
+    }
+}
+
+/// Set up the app for a review from the run options: load the diff state
+/// (risk, notes, previous comments), open the feedback journal and apply
+/// the resumed session. Shared by the TUI ([`run`]) and scripted reviews
+/// ([`crate::script`]).
+pub fn prepare(files: Vec<FileDiff>, repo: Option<&Repository>, opts: RunOptions) -> App {
+    let mut app = App::new(files, opts.no_stage);
+    app.view_mode = opts.view_mode;
+    app.show_whitespace = opts.show_whitespace;
+    app.tab_width = opts.tab_width;
+    app.feedback_format = opts.feedback_format;
+    app.output_style = opts.output_style;
+    app.context_lines = opts.context_lines;
+    app.cwd_prefix = opts.cwd_prefix;
+    app.path_filter = opts.path_filter;
+    app.auto_split = opts.auto_split;
+    app.ask_verdict = opts.verdict;
+    app.require_verdict = opts.require_verdict;
+    app.timer = opts.track_time.then(ReviewTimer::default);
+    app.diff_settings = opts.diff_settings;
+    app.no_intent_to_add = opts.no_intent_to_add;
+    app.reviewing_patch = opts.patch;
+    app.multiplexer = multiplexer::system(opts.multiplexer, opts.split);
+    app.editor_wait_command = opts.editor_wait_command;
+    app.commits = opts.commits;
+    if let Some(max_lines) = app.auto_split {
+        let split = diff::auto_split(&mut app.files, max_lines);
+        if split > 0 {
+            app.info(format!("Auto-split {} large hunk(s)", split));
+        }
+    }
+    if let Some(repo) = repo
+        && opts.order == ReviewOrder::Risk
+    {
+        app.risk = risk::score_files(repo, &app.files);
+    }
+    if opts.order != ReviewOrder::Path {
+        app.order = opts.order;
+        let mut files = std::mem::take(&mut app.files);
+        app.sort_files(&mut files);
+        app.files = files;
+    }
+    if let Some(repo) = repo {
+        app.snapshot_worktree(repo);
+        app.review_notes = notes::load_notes(repo, &app.files);
+        app.previous_comments =
+            reminders::load(repo, opts.previous_feedback.as_deref(), &app.review_notes);
+    }
+    if let Some(ref pos) = opts.start {
+        app.jump_to_position(pos);
+    }
+    if let Some(dir) = &opts.journal_dir {
+        // The archive lives next to the journals (see `archive::archive_dir`)
+        match journal::archive_interrupted(dir, &dir.join("feedback")) {
+            Ok(archived) if !archived.is_empty() => {
+                let items: usize = archived.iter().map(|(_, n)| n).sum();
+                app.info(format!(
+                    "Archived {} feedback item(s) from an interrupted session (stagent feedback list)",
+                    items
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => app.error(format!("Journal recovery failed: {:#}", e)),
+        }
+        match Journal::create(dir) {
+            Ok(journal) => app.journal = Some(journal),
+            Err(e) => app.error(format!("Feedback journal disabled: {:#}", e)),
+        }
+    }
+    if let Some(session) = opts.resume {
+        app.offer_resume(session);
+        if !opts.confirm_resume {
+            app.accept_resume();
+        }
+    }
+    app.workdir = repo.and_then(|r| r.workdir()).map(Path::to_path_buf);
+    app
+}
+
+/// Run the TUI application. Returns the final app state (including
+/// collected feedback) on exit.
+pub fn run(files: Vec<FileDiff>, repo: Option<&Repository>, opts: RunOptions) -> Result<App> {
+    // Set up terminal
+    crossterm::terminal::enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    crossterm::execute!(
+        stdout,
+        crossterm::terminal::EnterAlternateScreen,
+        crossterm::event::EnableMouseCapture,
+    )?;
+
+    // Guard ensures terminal is restored even on panic
+    let _guard = TerminalGuard;
+
+    let backend = ratatui::backend::CrosstermBackend::new(stdout);
+    let mut terminal = ratatui::Terminal::new(backend)?;
+
+    let watch = opts.watch && repo.is_some();
+    let overview = opts.overview;
+    let mut app = prepare(files, repo, opts);
+    let orphans = recovery::find_orphans(&std::env::temp_dir(), app.workdir.as_deref());
+    if !orphans.is_empty() {
+        app.offer_recovery(orphans);
+    }
+    // After the prompts above, which need answering first
+    if overview && matches!(app.mode, AppMode::Browsing | AppMode::Help) {
+        app.start_overview();
+    }
+    let highlighter = Highlighter::new();
+
+    let mut editor_state: Option<EditorState> = None;
+    let mut summary_editor: Option<SummaryEditor> = None;
+    let mut note_editor: Option<NoteEditor> = None;
+    let mut last_watch = Instant::now();
+
+    let mut app = loop {
+        // Back from an editor run in this terminal: the screen is blank
+        if multiplexer::take_resumed() {
+            terminal.clear()?;
+            app.dirty = true;
+        }
+
+        app.expire_message(Instant::now());
+        app.track_time(Instant::now());
+        if let Some(repo) = repo
+            && !app.reviewing_patch
+        {
+            app.load_blame(repo);
+        }
+
+        // Draw only when state has changed
+        if app.dirty {
+            terminal.draw(|frame| {
+                ui::render(frame, &mut app, &highlighter);
+            })?;
+            app.dirty = false;
+        }
+
+        // Check if editor has closed
+        if let Some(ref state) = editor_state
+            && state.rx.try_recv().is_ok()
+        {
+            // Take ownership to process
+            let state = editor_state.take().unwrap();
+            let captured = if state.is_comment {
+                app.flush_pending_editor_state(state.tmpfile.path(), true, &state.original_content)
+            } else {
+                app.capture_edit(repo, state.tmpfile.path(), &state.original_content)
+            };
+            state.recovery.clear();
+            match (captured, state.is_comment) {
+                (true, true) => app.info("Comment captured"),
+                (true, false) => app.info("Edit captured"),
+                (false, _) => app.warn("No changes detected"),
+            }
+            app.dirty = true;
+        }
+
+        if let Some(ref note) = note_editor
+            && note.rx.try_recv().is_ok()
+        {
+            let note = note_editor.take().unwrap();
+            if app.finish_note(note.tmpfile.path(), &note.target) {
+                app.info(match note.target {
+                    NoteTarget::Review => "Review comment captured".to_string(),
+                    NoteTarget::File(path) => format!("File comment captured for {}", path),
+                    NoteTarget::Commit(label) => format!("Commit comment captured for {}", label),
+                });
+            } else {
+                app.warn("No changes detected");
+            }
+        }
+
+        // The summary editor closed: the review is done
+        if let Some(ref summary) = summary_editor
+            && summary.rx.try_recv().is_ok()
+        {
+            app.finish_summary(summary.tmpfile.path());
+            break app;
+        }
+
+        // --watch: re-diff when files change, but not in the middle of an
+        // edit, comment or prompt
+        if watch
+            && let Some(repo) = repo
+            && app.mode == AppMode::Browsing
+            && last_watch.elapsed() >= WATCH_INTERVAL
+        {
+            last_watch = Instant::now();
+            if let Err(e) = app.refresh_if_changed(repo) {
+                app.error(format!("Refresh failed: {:#}", e));
+                app.dirty = true;
+            }
+        }
+
+        // Handle events
+        if event::poll(Duration::from_millis(50))? {
+            match event::read()? {
+                Event::Key(key) => {
+                    if app.mode == AppMode::WaitingForEditor {
+                        // Only allow quit while waiting for editor
+                        if key.code == KeyCode::Char('q') {
+                            if let Some(state) = editor_state.take() {
+                                app.flush_pending_editor_state(
+                                    state.tmpfile.path(),
+                                    state.is_comment,
+                                    &state.original_content,
+                                );
+                                state.recovery.clear();
+                            }
+                            if let Some(note) = note_editor.take() {
+                                app.finish_note(note.tmpfile.path(), &note.target);
+                            }
+                            if let Some(summary) = summary_editor.take() {
+                                app.finish_summary(summary.tmpfile.path());
+                            }
+                            break app;
+                        }
+                        continue;
+                    }
+
+                    // Verdict: give one (then write a summary) or skip it
+                    if app.mode == AppMode::VerdictPrompt {
+                        if app.handle_verdict_key(key.code) {
+                            if app.verdict.is_none() {
+                                break app;
+                            }
+                            match app.start_summary_editor() {
+                                Ok(summary) => summary_editor = Some(summary),
+                                // No editor pane: the verdict goes without
+                                // a summary
+                                Err(_) => break app,
+                            }
+                        }
+                        continue;
+                    }
+
+                    // File overview: pick the files to review, q quits
+                    if app.mode == AppMode::Overview {
+                        if key.code == KeyCode::Char('q') {
+                            break app;
+                        }
+                        app.handle_overview_key(key.code);
+                        continue;
+                    }
+
+                    // :stage-matching found hunks: y stages them
+                    if app.mode == AppMode::BulkStagePrompt {
+                        app.handle_bulk_stage_key(key.code, repo);
+                        continue;
+                    }
+
+                    // Staging error details: Enter goes to the hunk
+                    if app.mode == AppMode::StageError {
+                        app.handle_stage_error_key(key.code);
+                        continue;
+                    }
+
+                    // Hunk details: D, q or Esc closes them
+                    if app.mode == AppMode::HunkDetails {
+                        if matches!(
+                            key.code,
+                            KeyCode::Char('D') | KeyCode::Char('q') | KeyCode::Esc
+                        ) {
+                            app.mode = AppMode::Browsing;
+                            app.dirty = true;
+                        }
+                        continue;
+                    }
+
+                    // Message history: M, q or Esc closes it
+                    if app.mode == AppMode::MessageHistory {
+                        if matches!(
+                            key.code,
+                            KeyCode::Char('M') | KeyCode::Char('q') | KeyCode::Esc
+                        ) {
+                            app.toggle_message_history();
+                        }
+                        continue;
+                    }
+
+                    // Feedback panel: move, delete or go to feedback
+                    if app.mode == AppMode::FeedbackPanel {
+                        app.handle_feedback_panel_key(key.code);
+                        continue;
+                    }
+
+                    // Commits panel: move, or comment on a commit
+                    if app.mode == AppMode::CommitsPanel {
+                        if matches!(key.code, KeyCode::Char('c') | KeyCode::Enter) {
+                            match app.start_commit_comment() {
+                                Ok(note) => note_editor = note,
+                                Err(e) => app.error(format!("Comment error: {}", e)),
+                            }
+                        } else {
+                            app.handle_commits_panel_key(key.code);
+                        }
+                        continue;
+                    }
+
+                    // Edit captured: apply and stage it, or keep it as
+                    // feedback
+                    if app.mode == AppMode::StageEditPrompt {
+                        app.handle_stage_edit_key(key.code, repo);
+                        continue;
+                    }
+
+                    // Deletion of a file about to be staged: y goes ahead
+                    if app.mode == AppMode::DeletionPrompt {
+                        app.handle_deletion_key(key.code, repo);
+                        continue;
+                    }
+
+                    // Quit confirmation: quit (writing or discarding the
+                    // feedback) or keep reviewing
+                    if app.mode == AppMode::ConfirmQuit {
+                        if app.handle_confirm_quit_key(key.code) {
+                            break app;
+                        }
+                        continue;
+                    }
+
+                    // Resume prompt: y continues the previous session, n starts fresh
+                    if app.mode == AppMode::ResumePrompt {
+                        match key.code {
+                            KeyCode::Char('y') => app.accept_resume(),
+                            KeyCode::Char('n') | KeyCode::Esc => app.decline_resume(),
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    // Recovery prompt: y adds orphaned editor content as
+                    // feedback, n deletes it
+                    if app.mode == AppMode::RecoveryPrompt {
+                        match key.code {
+                            KeyCode::Char('y') => app.accept_recovery(),
+                            KeyCode::Char('n') | KeyCode::Esc => app.decline_recovery(),
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    // Command line: edit the input, Enter runs it, Esc cancels
+                    if app.mode == AppMode::Command {
+                        match key.code {
+                            KeyCode::Enter => {
+                                let input = std::mem::take(&mut app.command_input);
+                                app.mode = AppMode::Browsing;
+                                if app.execute_command(&input, repo) == CommandOutcome::Quit {
+                                    break app;
+                                }
+                            }
+                            KeyCode::Esc => app.cancel_command(),
+                            KeyCode::Tab => app.complete_command(),
+                            // Backspace on an empty line leaves command mode, as in vim
+                            KeyCode::Backspace if app.command_input.pop().is_none() => {
+                                app.cancel_command()
+                            }
+                            KeyCode::Char(c) => app.command_input.push(c),
+                            _ => {}
+                        }
+                        app.dirty = true;
+                        continue;
+                    }
+
+                    // File list filter: typing narrows the list, Enter keeps
+                    // the filter, Esc clears it
+                    if app.mode == AppMode::FileFilter {
+                        match key.code {
+                            KeyCode::Enter => app.accept_file_filter(),
+                            KeyCode::Esc => app.clear_file_filter(),
+                            KeyCode::Backspace => {
+                                app.file_filter.pop();
+                                app.update_file_filter();
+                            }
+                            KeyCode::Char(c) => {
+                                app.file_filter.push(c);
+                                app.update_file_filter();
+                            }
+                            _ => {}
+                        }
+                        app.dirty = true;
+                        continue;
+                    }
+
+                    // Inline comment box: edit the text, Enter saves, Esc cancels
+                    if app.mode == AppMode::InlineComment {
+                        match key.code {
+                            KeyCode::Enter => app.submit_inline_comment(),
+                            KeyCode::Esc => app.cancel_inline_comment(),
+                            KeyCode::Backspace => {
+                                app.comment_input.pop();
+                            }
+                            KeyCode::Char(c) => app.comment_input.push(c),
+                            _ => {}
+                        }
+                        app.dirty = true;
+                        continue;
+                    }
+
+                    // Help mode: scroll, search or dismiss the overlay
+                    if app.mode == AppMode::Help {
+                        if app.handle_help_key(key.code) {
+                            mark_help_shown();
+                        }
+                        continue;
+                    }
+
+                    // Handle pending key sequences (gg)
+                    if app.pending_key == Some('g') {
+                        app.pending_key = None;
+                        app.message = None;
+                        if key.code == KeyCode::Char('g') {
+                            app.scroll_to_top();
+                            continue;
+                        }
+                        // Fall through to process the key normally
+                    }
+
+                    // Handle Ctrl modifier keys
+                    if key.modifiers.contains(KeyModifiers::CONTROL) {
+                        match key.code {
+                            KeyCode::Char('d') => app.scroll_half_page_down(),
+                            KeyCode::Char('u') => app.scroll_half_page_up(),
+                            KeyCode::Char('f') => app.scroll_full_page_down(),
+                            KeyCode::Char('b') => app.scroll_full_page_up(),
+                            KeyCode::Char('o') => app.jump_back(),
+                            // Most terminals send Ctrl+i as Tab
+                            KeyCode::Char('i') | KeyCode::Char('n') => app.jump_forward(),
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    match key.code {
+                        KeyCode::Char('q') if app.request_quit() => break app,
+                        KeyCode::Char('j') | KeyCode::Down
+                            if app.line_mode && app.focus == FocusPanel::DiffView =>
+                        {
+                            app.cursor_down()
+                        }
+                        KeyCode::Char('k') | KeyCode::Up
+                            if app.line_mode && app.focus == FocusPanel::DiffView =>
+                        {
+                            app.cursor_up()
+                        }
+                        KeyCode::Char('j') | KeyCode::Down
+                            if app.tree_view && app.focus == FocusPanel::FileList =>
+                        {
+                            app.select_next_row()
+                        }
+                        KeyCode::Char('k') | KeyCode::Up
+                            if app.tree_view && app.focus == FocusPanel::FileList =>
+                        {
+                            app.select_prev_row()
+                        }
+                        KeyCode::Enter if app.tree_view && app.focus == FocusPanel::FileList => {
+                            app.toggle_dir()
+                        }
+                        KeyCode::Left if app.tree_view && app.focus == FocusPanel::FileList => {
+                            app.collapse_dir()
+                        }
+                        KeyCode::Right if app.tree_view && app.focus == FocusPanel::FileList => {
+                            app.expand_dir()
+                        }
+                        KeyCode::Char('t') => app.toggle_tree_view(),
+                        KeyCode::Char('f') => app.start_file_filter(),
+                        KeyCode::Char('j') => {
+                            if app.focus == FocusPanel::FileList {
+                                app.select_next_file();
+                            } else {
+                                app.scroll_down();
+                            }
+                        }
+                        KeyCode::Char('k') => {
+                            if app.focus == FocusPanel::FileList {
+                                app.select_prev_file();
+                            } else {
+                                app.scroll_up();
+                            }
+                        }
+                        KeyCode::Char('J') | KeyCode::Char('}') => app.select_next_hunk(),
+                        KeyCode::Char('K') | KeyCode::Char('{') => app.select_prev_hunk(),
+                        KeyCode::Char(']') => app.select_next_pending_hunk(),
+                        KeyCode::Char('[') => app.select_prev_pending_hunk(),
+                        KeyCode::Char('H') => app.select_prev_file(),
+                        KeyCode::Char('L') => app.select_next_file(),
+                        KeyCode::Char('h') => {
+                            app.focus = FocusPanel::FileList;
+                            app.dirty = true;
+                        }
+                        KeyCode::Char('l') => {
+                            app.focus = FocusPanel::DiffView;
+                            app.dirty = true;
+                        }
+                        KeyCode::Char('G') | KeyCode::End => app.scroll_to_bottom(),
+                        KeyCode::Home => app.scroll_to_top(),
+                        KeyCode::PageDown => app.scroll_full_page_down(),
+                        KeyCode::PageUp => app.scroll_full_page_up(),
+                        KeyCode::Char('g') => {
+                            app.pending_key = Some('g');
+                            app.prompt("g...");
+                            app.dirty = true;
+                        }
+                        KeyCode::Char('?') => {
+                            app.mode = AppMode::Help;
+                            app.dirty = true;
+                        }
+                        KeyCode::Down => {
+                            if app.focus == FocusPanel::FileList {
+                                app.select_next_file();
+                            } else {
+                                app.select_next_hunk();
+                            }
+                        }
+                        KeyCode::Up => {
+                            if app.focus == FocusPanel::FileList {
+                                app.select_prev_file();
+                            } else {
+                                app.select_prev_hunk();
+                            }
+                        }
+                        KeyCode::Tab => app.toggle_focus(),
+                        KeyCode::Char('v') => app.toggle_line_mode(),
+                        KeyCode::Esc if app.line_mode => app.toggle_line_mode(),
+                        KeyCode::Char('V') if app.line_mode => app.toggle_line_selection(),
+                        KeyCode::Char('y') if repo.is_some() && app.stages_deletion() => {
+                            app.confirm_deletion()
+                        }
+                        KeyCode::Char('y') if app.line_mode => {
+                            if let Err(e) = app.stage_current_line(repo) {
+                                app.report_stage_error(&e);
+                            }
+                        }
+                        KeyCode::Char('y') => match repo {
+                            Some(r) => {
+                                if let Err(e) = app.stage_current_hunk(r) {
+                                    app.report_stage_error(&e);
+                                }
+                            }
+                            None => app.accept_current_hunk(),
+                        },
+                        KeyCode::Char('n') => app.skip_current_hunk(),
+                        KeyCode::Char('W') => app.skip_whitespace_only_hunks(),
+                        KeyCode::Char('s') => app.split_current_hunk(),
+                        KeyCode::Char('S') => app.toggle_view_mode(),
+                        KeyCode::Char('w') => app.toggle_wrap(),
+                        KeyCode::Left if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                            app.scroll_horizontally(-H_SCROLL_STEP)
+                        }
+                        KeyCode::Right if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                            app.scroll_horizontally(H_SCROLL_STEP)
+                        }
+                        KeyCode::Char('+') => app.expand_current_context(repo, true),
+                        KeyCode::Char('-') => app.expand_current_context(repo, false),
+                        KeyCode::Char('o') => app.open_current_file(repo),
+                        KeyCode::Char(':') => app.start_command(),
+                        KeyCode::Char('C') => app.start_inline_comment(),
+                        KeyCode::Char('p') => app.show_previous_comments(),
+                        KeyCode::Char('i') => app.toggle_stats(),
+                        KeyCode::Char('b') => app.toggle_blame(repo),
+                        KeyCode::Char('D') => app.open_hunk_details(repo),
+                        KeyCode::Char('r') => match repo {
+                            Some(r) if !app.reviewing_patch => {
+                                if let Err(e) = app.refresh(r) {
+                                    app.error(format!("Refresh error: {:#}", e));
+                                }
+                            }
+                            _ => app.warn("Nothing to refresh: reviewing a patch"),
+                        },
+                        KeyCode::Char(' ') => app.toggle_mark_current_hunk(),
+                        KeyCode::Char('Y') => {
+                            if let Err(e) = app.stage_marked_hunks(repo) {
+                                app.report_stage_error(&e);
+                            }
+                        }
+                        KeyCode::Char('e') => match app.start_edit(repo) {
+                            Ok(Some(state)) => {
+                                editor_state = Some(state);
+                            }
+                            Ok(None) => {
+                                app.warn("No hunk selected");
+                            }
+                            Err(e) => {
+                                app.error(format!("Edit error: {}", e));
+                            }
+                        },
+                        KeyCode::Char('c') if app.focus == FocusPanel::FileList => {
+                            match app.start_file_comment() {
+                                Ok(Some(note)) => note_editor = Some(note),
+                                Ok(None) => app.warn("No file selected"),
+                                Err(e) => app.error(format!("Comment error: {}", e)),
+                            }
+                        }
+                        KeyCode::Char('F') => app.open_feedback_panel(),
+                        KeyCode::Char('O') => app.open_commits_panel(),
+                        KeyCode::Char('M') => app.toggle_message_history(),
+                        KeyCode::Char('R') => match app.start_review_comment() {
+                            Ok(note) => note_editor = Some(note),
+                            Err(e) => app.error(format!("Comment error: {}", e)),
+                        },
+                        KeyCode::Char('c') => match app.start_comment() {
+                            Ok(Some(state)) => {
+                                editor_state = Some(state);
+                            }
+                            Ok(None) => {
+                                app.warn("No hunk selected");
+                            }
+                            Err(e) => {
+                                app.error(format!("Comment error: {}", e));
+                            }
+                        },
+                        _ => {}
+                    }
+                }
+                Event::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::ScrollDown => app.scroll_down(),
+                    MouseEventKind::ScrollUp => app.scroll_up(),
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        app.handle_mouse_click(mouse.column, mouse.row);
+                    }
+                    MouseEventKind::Drag(MouseButton::Left) => {
+                        app.handle_mouse_drag(mouse.column, mouse.row);
+                    }
+                    _ => {}
+                },
+                Event::Resize(_, _) => {
+                    app.dirty = true;
+                }
+                _ => {}
+            }
+        }
+    };
+
+    // Files hidden by `:files` still count for the session's output
+    app.filter_files(None).ok();
+    Ok(app)
+    // _guard will restore terminal on drop
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DeltaStatus, DiffLine, HunkStatus, LineKind, Severity};
+
+    fn make_test_files() -> Vec<FileDiff> {
+        vec![
+            FileDiff {
+                path: "src/a.rs".into(),
+                hunks: vec![
+                    Hunk {
+                        header: "@@ -1,3 +1,4 @@".to_string(),
+                        lines: vec![
+                            DiffLine {
+                                kind: LineKind::Context,
+                                content: "line1\n".to_string(),
+                                old_lineno: Some(1),
+                                new_lineno: Some(1),
+                                raw: None,
+                                no_newline: false,
+                            },
+                            DiffLine {
+                                kind: LineKind::Removed,
+                                content: "old\n".to_string(),
+                                old_lineno: Some(2),
+                                new_lineno: None,
+                                raw: None,
+                                no_newline: false,
+                            },
+                            DiffLine {
+                                kind: LineKind::Added,
+                                content: "new\n".to_string(),
+                                old_lineno: None,
+                                new_lineno: Some(2),
+                                raw: None,
+                                no_newline: false,
+                            },
+                            DiffLine {
+                                kind: LineKind::Context,
+                                content: "line3\n".to_string(),
+                                old_lineno: Some(3),
+                                new_lineno: Some(3),
+                                raw: None,
+                                no_newline: false,
+                            },
+                        ]
+                        .into(),
+                        status: HunkStatus::Pending,
+                        old_start: 1,
+                        old_lines: 3,
+                        new_start: 1,
+                        new_lines: 3,
+                    },
+                    Hunk {
+                        header: "@@ -20,3 +21,4 @@".to_string(),
+                        lines: vec![DiffLine {
+                            kind: LineKind::Added,
+                            content: "added line\n".to_string(),
+                            old_lineno: None,
+                            new_lineno: Some(22),
+                            raw: None,
+                            no_newline: false,
+                        }]
+                        .into(),
+                        status: HunkStatus::Pending,
+                        old_start: 20,
+                        old_lines: 3,
+                        new_start: 21,
+                        new_lines: 4,
+                    },
+                ],
+                status: DeltaStatus::Modified,
+                is_binary: false,
+                is_submodule: false,
+                mode_change: None,
+            },
+            FileDiff {
+                path: "src/b.rs".into(),
+                hunks: vec![Hunk {
+                    header: "@@ -5,3 +5,3 @@".to_string(),
+                    lines: vec![
+                        DiffLine {
+                            kind: LineKind::Removed,
+                            content: "foo\n".to_string(),
+                            old_lineno: Some(6),
+                            new_lineno: None,
+                            raw: None,
+                            no_newline: false,
+                        },
+                        DiffLine {
+                            kind: LineKind::Added,
+                            content: "bar\n".to_string(),
+                            old_lineno: None,
+                            new_lineno: Some(6),
+                            raw: None,
+                            no_newline: false,
+                        },
+                    ]
+                    .into(),
+                    status: HunkStatus::Pending,
+                    old_start: 5,
+                    old_lines: 3,
+                    new_start: 5,
+                    new_lines: 3,
+                }],
+                status: DeltaStatus::Modified,
+                is_binary: false,
+                is_submodule: false,
+                mode_change: None,
+            },
+        ]
+    }
+
+    /// The first test file with `extra` added lines appended to its first
+    /// hunk, for scrolling.
+    fn make_long_files(extra: usize) -> Vec<FileDiff> {
+        let mut files = make_test_files();
+        files[0].hunks[0].lines.extend((0..extra).map(|i| DiffLine {
+            kind: LineKind::Added,
+            content: format!("more {}\n", i),
+            old_lineno: None,
+            new_lineno: Some(4 + i as u32),
+            raw: None,
+            no_newline: false,
+        }));
+        files
+    }
+
+    /// One file with a hunk of `lines` added lines per entry of `sizes`.
+    fn make_hunks(sizes: &[usize]) -> Vec<FileDiff> {
+        let mut files = make_test_files();
+        files.truncate(1);
+        let template = files[0].hunks[0].clone();
+        files[0].hunks = sizes
+            .iter()
+            .map(|&lines| Hunk {
+                lines: (0..lines)
+                    .map(|i| DiffLine {
+                        kind: LineKind::Added,
+                        content: format!("line {}\n", i),
+                        old_lineno: None,
+                        new_lineno: Some(i as u32 + 1),
+                        raw: None,
+                        no_newline: false,
+                    })
+                    .collect(),
+                ..template.clone()
+            })
+            .collect();
+        files
+    }
+
+    #[test]
+    fn test_scroll_to_hunk_centers_it() {
+        // Short hunks take 5 rows with their separator; 10 rows are visible
+        let mut app = App::new_with_help(make_hunks(&[3, 3, 20, 3, 3, 3]), false, false);
+        app.diff_view_area = Rect::new(0, 0, 80, 12);
+        let scroll_to = |app: &mut App, hunk: usize| {
+            app.selected_hunk = hunk;
+            app.scroll_to_selected_hunk();
+            app.scroll_offset
+        };
+
+        // Header at row 32, the 4-row hunk in the middle of the view
+        assert_eq!(scroll_to(&mut app, 3), 32 - 3);
+        assert_eq!(scroll_to(&mut app, 0), 0, "nothing above the first");
+        // Too tall to center: the header a margin below the top
+        assert_eq!(scroll_to(&mut app, 2), 10 - HUNK_SCROLL_MARGIN);
+        // Nothing past the end below the last
+        assert_eq!(scroll_to(&mut app, 5), app.max_scroll());
+
+        // Wrapped lines above push the hunk down
+        for line in &mut app.files[0].hunks[0].lines {
+            line.content = format!("{}\n", "x".repeat(100));
+        }
+        app.wrap_lines = true;
+        let offset = scroll_to(&mut app, 1);
+        let header = app.selected_hunk_row().unwrap();
+        assert!(header > 5, "{}", header);
+        assert_eq!(offset, header - 3);
+    }
+
+    #[test]
+    fn test_app_initial_state() {
+        let app = App::new_with_help(make_test_files(), false, false);
+        assert_eq!(app.selected_file, 0);
+        assert_eq!(app.selected_hunk, 0);
+        assert_eq!(app.mode, AppMode::Browsing);
+        assert_eq!(app.focus, FocusPanel::DiffView);
+    }
+
+    #[test]
+    fn test_select_next_file() {
+        let mut app = App::new(make_test_files(), false);
+        app.select_next_file();
+        assert_eq!(app.selected_file, 1);
+    }
+
+    #[test]
+    fn test_select_prev_file() {
+        let mut app = App::new(make_test_files(), false);
+        app.selected_file = 1;
+        app.select_prev_file();
+        assert_eq!(app.selected_file, 0);
+    }
+
+    #[test]
+    fn test_select_file_wraps() {
+        let mut app = App::new(make_test_files(), false);
+        app.selected_file = 1; // last file
+        app.select_next_file();
+        assert_eq!(app.selected_file, 0); // wrapped to first
+    }
+
+    #[test]
+    fn test_select_next_hunk() {
+        let mut app = App::new(make_test_files(), false);
+        app.select_next_hunk();
+        assert_eq!(app.selected_hunk, 1);
+        assert_eq!(app.selected_file, 0);
+    }
+
+    #[test]
+    fn test_next_hunk_advances_file() {
+        let mut app = App::new(make_test_files(), false);
+        app.selected_hunk = 1; // last hunk of first file
+        app.select_next_hunk();
+        assert_eq!(app.selected_file, 1);
+        assert_eq!(app.selected_hunk, 0);
+    }
+
+    #[test]
+    fn test_next_pending_hunk_skips_reviewed_across_files() {
+        let mut app = App::new(make_test_files(), false);
+        app.files[0].hunks[1].status = HunkStatus::Staged;
+        app.select_next_pending_hunk();
+        assert_eq!((app.selected_file, app.selected_hunk), (1, 0));
+        // Wraps around to the first pending hunk
+        app.select_next_pending_hunk();
+        assert_eq!((app.selected_file, app.selected_hunk), (0, 0));
+        app.select_prev_pending_hunk();
+        assert_eq!((app.selected_file, app.selected_hunk), (1, 0));
+
+        app.files[0].hunks[0].status = HunkStatus::Skipped;
+        app.files[1].hunks[0].status = HunkStatus::Commented;
+        app.select_next_pending_hunk();
+        assert_eq!((app.selected_file, app.selected_hunk), (1, 0));
+        assert_eq!(app.message.as_deref(), Some("No pending hunks"));
+    }
+
+    #[test]
+    fn test_jump_list_goes_back_and_forward() {
+        let mut app = App::new(make_test_files(), false);
+        app.files[0].hunks[1].status = HunkStatus::Staged;
+        app.select_next_pending_hunk();
+        assert_eq!(app.position(), (1, 0));
+        // Stepping through hunks isn't a jump
+        app.select_prev_hunk();
+        assert_eq!(app.position(), (0, 1));
+
+        app.jump_back();
+        assert_eq!(app.position(), (0, 0));
+        app.jump_back();
+        assert_eq!(app.position(), (0, 0));
+        assert_eq!(app.message.as_deref(), Some("No older jumps"));
+        app.jump_forward();
+        assert_eq!(app.position(), (0, 1));
+        app.jump_forward();
+        assert_eq!(app.message.as_deref(), Some("No newer jumps"));
+
+        // Changing the file list forgets the jumps
+        app.message = None;
+        app.filter_files(None).unwrap();
+        app.jump_back();
+        assert_eq!(app.message.as_deref(), Some("No older jumps"));
+    }
+
+    #[test]
+    fn test_messages_have_levels_expire_and_are_kept() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.files[0].hunks[0].status = HunkStatus::Skipped;
+        app.files[0].hunks[1].status = HunkStatus::Skipped;
+        app.files[1].hunks[0].status = HunkStatus::Skipped;
+        app.select_next_pending_hunk();
+        let message = app.message.clone().unwrap();
+        assert_eq!(message.level, MessageLevel::Warn);
+        assert_eq!(&*message, "No pending hunks");
+
+        // Up until its timeout, gone after it
+        app.expire_message(message.shown_at + Duration::from_secs(1));
+        assert!(app.message.is_some());
+        app.expire_message(message.shown_at + MessageLevel::Warn.timeout());
+        assert!(app.message.is_none());
+
+        // Prompts of other modes don't expire and aren't history
+        app.execute_command("nosuchcommand", None);
+        assert_eq!(app.message.as_ref().unwrap().level, MessageLevel::Error);
+        app.start_command();
+        app.command_input = "stage-".to_string();
+        app.complete_command();
+        app.expire_message(Instant::now() + Duration::from_secs(3600));
+        assert!(app.message.is_some());
+
+        let history: Vec<_> = app
+            .message_history
+            .newest_first()
+            .map(|m| (m.level, m.text.as_str()))
+            .collect();
+        assert_eq!(
+            history,
+            vec![
+                (MessageLevel::Error, "Unknown command: nosuchcommand"),
+                (MessageLevel::Warn, "No pending hunks"),
+            ]
+        );
+
+        app.cancel_command();
+        app.toggle_message_history();
+        assert_eq!(app.mode, AppMode::MessageHistory);
+        app.toggle_message_history();
+        assert_eq!(app.mode, AppMode::Browsing);
+    }
+
+    #[test]
+    fn test_next_hunk_wraps_at_end() {
+        let mut app = App::new(make_test_files(), false);
+        // Navigate to last hunk of last file
+        app.selected_file = 1;
+        app.selected_hunk = 0; // only one hunk
+        app.select_next_hunk();
+        // Should wrap to first hunk of first file
+        assert_eq!(app.selected_file, 0);
+        assert_eq!(app.selected_hunk, 0);
+    }
+
+    #[test]
+    fn test_scroll_down() {
+        let mut app = App::new(make_test_files(), false);
+        app.scroll_down();
+        assert_eq!(app.scroll_offset, 1);
+    }
+
+    #[test]
+    fn test_scroll_clamps_to_content() {
+        let mut app = App::new(make_test_files(), false);
+        app.scroll_up(); // at 0, should stay at 0
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_current_file() {
+        let app = App::new(make_test_files(), false);
+        let file = app.current_file().unwrap();
+        assert_eq!(file.path.to_string_lossy(), "src/a.rs");
+    }
+
+    #[test]
+    fn test_current_hunk() {
+        let app = App::new(make_test_files(), false);
+        let hunk = app.current_hunk().unwrap();
+        assert_eq!(hunk.header, "@@ -1,3 +1,4 @@");
+    }
+
+    #[test]
+    fn test_empty_diff_state() {
+        let mut app = App::new(vec![], false);
+        assert!(app.current_file().is_none());
+        assert!(app.current_hunk().is_none());
+        // These should be no-ops without panic
+        app.select_next_file();
+        app.select_prev_file();
+        app.select_next_hunk();
+        app.select_prev_hunk();
+        app.scroll_down();
+        app.scroll_up();
+    }
+
+    #[test]
+    fn test_skip_updates_hunk_status() {
+        let mut app = App::new(make_test_files(), false);
+        app.skip_current_hunk();
+        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Skipped);
+    }
+
+    #[test]
+    fn test_skip_whitespace_only_hunks() {
+        let mut files = make_test_files();
+        files[1].hunks[0].lines[1].content = "\tfoo  \n".to_string();
+        let mut app = App::new(files, false);
+        app.skip_whitespace_only_hunks();
+        assert_eq!(app.files[1].hunks[0].status, HunkStatus::Skipped);
+        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
+        assert_eq!(app.files[0].hunks[1].status, HunkStatus::Pending);
+        assert_eq!(
+            app.message.as_deref(),
+            Some("Skipped 1 whitespace-only hunk(s)")
+        );
+
+        app.skip_whitespace_only_hunks();
+        assert_eq!(
+            app.message.as_deref(),
+            Some("No pending whitespace-only hunks")
+        );
+    }
+
+    #[test]
+    fn test_accept_current_hunk() {
+        let mut app = App::new(make_test_files(), true);
+        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
+        app.accept_current_hunk();
+        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Staged);
+        assert_eq!(
+            app.message.as_deref(),
+            Some("Hunk accepted"),
+            "Should show 'Hunk accepted' message"
+        );
+        // Should advance to next hunk
+        assert_eq!(app.selected_hunk, 1);
+    }
+
+    #[test]
+    fn test_accept_current_hunk_skips_non_pending() {
+        let mut app = App::new(make_test_files(), true);
+        app.files[0].hunks[0].status = HunkStatus::Skipped;
+        app.accept_current_hunk();
+        // Should not change status of already-skipped hunk
+        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Skipped);
+    }
+
+    #[test]
+    fn test_toggle_focus() {
+        let mut app = App::new(make_test_files(), false);
+        assert_eq!(app.focus, FocusPanel::DiffView);
+        app.toggle_focus();
+        assert_eq!(app.focus, FocusPanel::FileList);
+        app.toggle_focus();
+        assert_eq!(app.focus, FocusPanel::DiffView);
+    }
+
+    #[test]
+    fn test_all_hunks_staged_marks_file() {
+        let mut app = App::new(make_test_files(), true);
+        // Stage first file's hunks via skip (since no_stage=true)
+        app.selected_file = 1;
+        app.skip_current_hunk();
+        let file = &app.files[1];
+        assert!(file.hunks.iter().all(|h| h.status != HunkStatus::Pending));
+    }
+
+    #[test]
+    fn test_handle_mouse_click_selects_file() {
+        let mut app = App::new(make_test_files(), false);
+        // Simulate file list area: x=0, y=0, width=20, height=10
+        app.file_list_area = Rect::new(0, 0, 20, 10);
+        // Click on second file (row 2 = border row 0 + item index 1)
+        app.handle_mouse_click(5, 2);
+        assert_eq!(app.selected_file, 1);
+        assert_eq!(app.focus, FocusPanel::FileList);
+    }
+
+    #[test]
+    fn test_visible_hunks() {
+        let app = App::new_with_help(make_test_files(), false, false);
+        let rows: Vec<u32> = app.files[0]
+            .hunks
+            .iter()
+            .enumerate()
+            .map(|(i, h)| app.hunk_body_rows(0, i, h))
+            .collect();
+        assert!(rows.len() >= 2, "the test needs two hunks");
+        let second_top = rows[0] as usize + 2;
+
+        // The first hunk's header and two body rows
+        assert_eq!(app.visible_hunks(0..3), vec![(0, 2)]);
+        // The second hunk's header only
+        assert_eq!(app.visible_hunks(second_top..second_top + 1), vec![(1, 0)]);
+        assert_eq!(app.visible_hunks(0..second_top + 2)[1], (1, 1));
+    }
+
+    #[test]
+    fn test_pack_unviewed_files() {
+        let mut files = make_long_files(highlight::LARGE_FILE_LINES);
+        let last = files[0].hunks[0].lines.last_mut().unwrap();
+        last.raw = Some(b"caf\xe9".to_vec());
+        last.mark_no_newline();
+        files[1] = files[0].clone();
+        files.truncate(2);
+        let mut app = App::new_with_help(files, false, false);
+        let stats = crate::stats::compute(&app.files);
+        let lines = format!("{:?}", app.files[1].hunks[0].lines);
+
+        app.pack_unviewed_files();
+        assert!(!app.files[0].hunks[0].lines.is_packed(), "selected");
+        assert!(app.files[1].hunks[0].lines.is_packed());
+        assert_eq!(crate::stats::compute(&app.files), stats);
+        assert!(app.files[1].hunks[0].lines.is_packed(), "counted packed");
+
+        // Read back as they were, and packed again for free
+        assert_eq!(format!("{:?}", app.files[1].hunks[0].lines), lines);
+        app.pack_unviewed_files();
+        assert!(app.files[1].hunks[0].lines.is_packed());
+
+        // Changed lines are packed anew
+        app.files[1].hunks[0].lines[0].content = "changed\n".to_string();
+        app.pack_unviewed_files();
+        assert_eq!(app.files[1].hunks[0].lines[0].content, "changed\n");
+
+        // Viewed files stay unpacked
+        app.select_next_file();
+        app.pack_unviewed_files();
+        assert!(!app.files[1].hunks[0].lines.is_packed());
+    }
+
+    #[test]
+    fn test_file_list_offset_follows_selection_and_clicks() {
+        let template = make_test_files().remove(0);
+        let files = (0..20)
+            .map(|i| FileDiff {
+                path: format!("f{:02}.rs", i).into(),
+                ..template.clone()
+            })
+            .collect();
+        let mut app = App::new(files, false);
+        // 8 rows inside the borders
+        app.file_list_area = Rect::new(0, 0, 20, 10);
+
+        app.selected_file = 12;
+        app.sync_file_list_offset();
+        assert_eq!(app.file_list_offset, 5);
+        assert_eq!(app.file_list_position(), (Some(12), 20));
+
+        // Moving within the visible rows keeps the offset
+        app.selected_file = 6;
+        app.sync_file_list_offset();
+        assert_eq!(app.file_list_offset, 5);
+
+        // The first list row is the first file on screen
+        app.handle_mouse_click(5, 1);
+        assert_eq!(app.selected_file, 5);
+
+        app.selected_file = 2;
+        app.sync_file_list_offset();
+        assert_eq!(app.file_list_offset, 2);
+
+        // A taller pane never scrolls past the last row
+        app.file_list_area = Rect::new(0, 0, 20, 30);
+        app.sync_file_list_offset();
+        assert_eq!(app.file_list_offset, 0);
+    }
+
+    #[test]
+    fn test_tree_view_navigation_and_folding() {
+        let mut app = App::new(make_test_files(), false);
+        app.toggle_tree_view();
+        // Rows: src/, a.rs, b.rs
+        assert_eq!(app.file_tree_rows().len(), 3);
+        assert_eq!(app.file_tree_cursor(&app.file_tree_rows()), 1);
+
+        app.select_next_row();
+        assert_eq!(app.selected_file, 1);
+        app.select_next_row();
+        assert_eq!(app.selected_dir.as_deref(), Some(Path::new("src")));
+        assert_eq!(app.selected_file, 1, "the diff view keeps its file");
+
+        app.collapse_dir();
+        assert_eq!(app.file_tree_rows().len(), 1);
+        app.toggle_dir();
+        assert_eq!(app.file_tree_rows().len(), 3);
+
+        // Collapsing on a file folds its directory and moves onto it
+        app.select_next_row();
+        assert_eq!(app.selected_file, 0);
+        app.collapse_dir();
+        assert_eq!(app.selected_dir.as_deref(), Some(Path::new("src")));
+        assert_eq!(app.file_tree_rows().len(), 1);
+
+        // Moving to a file in a collapsed directory reveals it
+        app.select_next_file();
+        assert_eq!(app.selected_file, 1);
+        assert_eq!(app.file_tree_rows().len(), 3);
+    }
+
+    #[test]
+    fn test_handle_mouse_click_in_tree_view() {
+        let mut app = App::new(make_test_files(), false);
+        app.toggle_tree_view();
+        app.file_list_area = Rect::new(0, 0, 20, 10);
+
+        // Row 3 = border + src/ + a.rs
+        app.handle_mouse_click(5, 3);
+        assert_eq!(app.selected_file, 1);
+        assert_eq!(app.focus, FocusPanel::FileList);
+
+        // Clicking the directory toggles it
+        app.handle_mouse_click(5, 1);
+        assert_eq!(app.selected_dir.as_deref(), Some(Path::new("src")));
+        assert_eq!(app.file_tree_rows().len(), 1);
+        app.handle_mouse_click(5, 1);
+        assert_eq!(app.file_tree_rows().len(), 3);
+    }
+
+    #[test]
+    fn test_sort_remaps_selection_and_marks() {
+        let mut app = App::new(make_test_files(), true);
+        for hunk in &mut app.files[0].hunks {
+            hunk.status = HunkStatus::Skipped;
+        }
+        app.marked.insert((1, 0));
+
+        app.execute_command("sort pending", None);
+        assert_eq!(app.message.as_deref(), Some("Sorted by Pending"));
+        assert_eq!(app.files[0].path, Path::new("src/b.rs"));
+        assert_eq!(app.selected_file, 1, "selection follows src/a.rs");
+        assert!(app.is_marked(0, 0), "mark follows src/b.rs");
+
+        app.execute_command("sort path", None);
+        assert_eq!(app.files[0].path, Path::new("src/a.rs"));
+        assert_eq!(app.selected_file, 0);
+        assert!(app.is_marked(1, 0));
+
+        app.execute_command("sort risk", None);
+        assert_eq!(app.order, ReviewOrder::Path, "no risk scores to sort by");
+    }
+
+    #[test]
+    fn test_file_filter_remaps_list_rows() {
+        let mut app = App::new(make_test_files(), false);
+        app.start_file_filter();
+        app.file_filter.push('b');
+        app.update_file_filter();
+        assert_eq!(app.shown_files(), vec![1]);
+        assert_eq!(app.selected_file, 1, "filtered-out selection moves");
+
+        app.accept_file_filter();
+        assert_eq!(app.mode, AppMode::Browsing);
+        app.select_next_file();
+        assert_eq!(app.selected_file, 1, "only src/b.rs is listed");
+
+        app.selected_file = 0;
+        app.file_list_area = Rect::new(0, 0, 20, 10);
+        app.handle_mouse_click(5, 1);
+        assert_eq!(app.selected_file, 1, "first row is src/b.rs");
+
+        app.clear_file_filter();
+        assert_eq!(app.shown_files(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_feedback_is_journaled_as_captured() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = App::new(make_test_files(), false);
+        app.journal = Some(Journal::create(dir.path()).unwrap());
+
+        app.add_line_comment(0, "first");
+        app.add_line_comment(2, "second");
+        app.selected_file = 1;
+        app.add_line_comment(0, "other file");
+
+        let path = app.journal.as_ref().unwrap().path().to_path_buf();
+        let replayed = journal::replay(&path).unwrap();
+        assert_eq!(replayed.len(), 2);
+        assert_eq!(replayed[0].content, "first\nsecond");
+        assert_eq!(replayed[1].file_path, "src/b.rs");
+    }
+
+    #[test]
+    fn test_handle_mouse_click_outside_file_list() {
+        let mut app = App::new(make_test_files(), false);
+        app.file_list_area = Rect::new(0, 0, 20, 10);
+        // Click outside the file list area
+        app.handle_mouse_click(25, 2);
+        assert_eq!(app.selected_file, 0); // unchanged
+    }
+
+    #[test]
+    fn test_handle_mouse_click_in_diff_view() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.diff_view_area = Rect::new(20, 0, 60, 20);
+        // Row 0 is the border; hunk 0 takes rows 1-5, then its separator
+        app.handle_mouse_click(30, 7);
+        assert_eq!(app.selected_hunk, 1);
+        assert!(!app.line_mode);
+
+        app.handle_mouse_click(30, 3);
+        assert_eq!(app.selected_hunk, 0);
+        assert_eq!(app.line_cursor(), Some(1));
+        assert_eq!(app.focus, FocusPanel::DiffView);
+
+        // The separator selects the hunk above and leaves line mode
+        app.handle_mouse_click(30, 6);
+        assert_eq!(app.selected_hunk, 0);
+        assert!(!app.line_mode);
+
+        // Borders
+        app.handle_mouse_click(20, 7);
+        app.handle_mouse_click(30, 19);
+        assert_eq!(app.selected_hunk, 0);
+    }
+
+    #[test]
+    fn test_mouse_drag_selects_lines_to_stage() {
+        let mut app = App::new_with_help(make_test_files(), true, false);
+        app.diff_view_area = Rect::new(20, 0, 60, 20);
+        app.handle_mouse_click(30, 2);
+        app.handle_mouse_drag(30, 3);
+        assert_eq!(app.selected_lines(), Some(0..=1));
+        // Dragging into another hunk doesn't move the selection
+        app.handle_mouse_drag(30, 8);
+        assert_eq!(app.selected_lines(), Some(0..=1));
+
+        app.start_inline_comment();
+        assert_eq!(app.comment_anchor, Some(1));
+        app.cancel_inline_comment();
+
+        // Only the removal is selected
+        app.stage_current_line(None).unwrap();
+        assert_eq!(app.files[0].hunks.len(), 3);
+        assert_eq!(app.files[0].hunks[0].new_lines, 2);
+        assert_eq!(app.line_anchor, None);
+
+        // Selecting every change of the rest (rows 7-9) accepts it whole
+        app.handle_mouse_click(30, 7);
+        app.handle_mouse_drag(30, 9);
+        assert_eq!(app.selected_lines(), Some(0..=2));
+        app.stage_current_line(None).unwrap();
+        assert_eq!(app.files[0].hunks[1].status, HunkStatus::Staged);
+    }
+
+    #[test]
+    fn test_dirty_flag_set_on_navigation() {
+        let mut app = App::new(make_test_files(), false);
+        assert!(app.dirty, "dirty should start true");
+        app.dirty = false;
+
+        app.select_next_file();
+        assert!(app.dirty, "dirty should be true after select_next_file");
+        app.dirty = false;
+
+        app.select_prev_file();
+        assert!(app.dirty, "dirty should be true after select_prev_file");
+        app.dirty = false;
+
+        app.select_next_hunk();
+        assert!(app.dirty, "dirty should be true after select_next_hunk");
+        app.dirty = false;
+
+        app.select_prev_hunk();
+        assert!(app.dirty, "dirty should be true after select_prev_hunk");
+        app.dirty = false;
+
+        app.scroll_down();
+        assert!(app.dirty, "dirty should be true after scroll_down");
+        app.dirty = false;
+
+        app.scroll_up();
+        assert!(app.dirty, "dirty should be true after scroll_up");
+        app.dirty = false;
+
+        app.toggle_focus();
+        assert!(app.dirty, "dirty should be true after toggle_focus");
+        app.dirty = false;
+
+        app.skip_current_hunk();
+        assert!(app.dirty, "dirty should be true after skip_current_hunk");
+        app.dirty = false;
+
+        app.split_current_hunk();
+        assert!(app.dirty, "dirty should be true after split_current_hunk");
+    }
+
+    #[test]
+    fn test_compute_line_offset_no_staged() {
+        let app = App::new(make_test_files(), false);
+        assert_eq!(app.compute_line_offset(0, 1), 0);
+    }
+
+    #[test]
+    fn test_compute_line_offset_with_staged() {
+        let mut app = App::new(make_test_files(), false);
+        // First hunk: old_lines=3, new_lines=3 → offset 0
+        app.files[0].hunks[0].status = HunkStatus::Staged;
+        assert_eq!(app.compute_line_offset(0, 1), 0);
+
+        // Change first hunk to have different new_lines
+        app.files[0].hunks[0].new_lines = 5;
+        // offset = 5 - 3 = 2
+        assert_eq!(app.compute_line_offset(0, 1), 2);
+    }
+
+    // --- scroll_to_top tests ---
+
+    #[test]
+    fn test_scroll_to_top() {
+        let mut app = App::new(make_test_files(), false);
+        app.scroll_offset = 42;
+        app.scroll_to_top();
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_scroll_to_top_already_at_top() {
+        let mut app = App::new(make_test_files(), false);
+        app.scroll_offset = 0;
+        app.scroll_to_top();
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_scroll_to_top_empty() {
+        let mut app = App::new(vec![], false);
+        app.scroll_to_top(); // should not panic
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    // --- scroll_to_bottom tests ---
+
+    #[test]
+    fn test_scroll_to_bottom() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        // Simulate a diff view area: 80 wide, 10 tall (inner height = 8)
+        app.diff_view_area = Rect::new(0, 0, 80, 10);
+        app.scroll_to_bottom();
+        // File 0 has 2 hunks:
+        //   hunk0: 1 header + 4 lines + 1 sep = 6
+        //   hunk1: 1 header + 1 line (no trailing sep) = 2
+        // Total content = 8 lines, visible inner height = 8
+        // scroll_offset = 8 - 8 = 0
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_scroll_to_bottom_with_small_viewport() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        // Small viewport: inner height = 3
+        app.diff_view_area = Rect::new(0, 0, 80, 5);
+        app.scroll_to_bottom();
+        // Total content = 8, visible = 3, offset = 8 - 3 = 5
+        assert_eq!(app.scroll_offset, 5);
+    }
+
+    #[test]
+    fn test_scroll_to_bottom_no_overscroll() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        // Viewport larger than content: inner height = 50
+        app.diff_view_area = Rect::new(0, 0, 80, 52);
+        app.scroll_to_bottom();
+        // Total content = 8, visible = 50, saturating_sub → 0
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_scroll_to_bottom_empty() {
+        let mut app = App::new_with_help(vec![], false, false);
+        app.scroll_to_bottom(); // should not panic
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    // --- half-page scroll tests ---
+
+    #[test]
+    fn test_scroll_half_page_down() {
+        let mut app = App::new(make_long_files(100), false);
+        app.diff_view_area = Rect::new(0, 0, 80, 20);
+        app.scroll_offset = 0;
+        app.scroll_half_page_down();
+        assert_eq!(app.scroll_offset, 10); // 20/2
+    }
+
+    #[test]
+    fn test_scroll_half_page_up() {
+        let mut app = App::new(make_test_files(), false);
+        app.diff_view_area = Rect::new(0, 0, 80, 20);
+        app.scroll_offset = 15;
+        app.scroll_half_page_up();
+        assert_eq!(app.scroll_offset, 5); // 15 - 10
+    }
+
+    #[test]
+    fn test_scroll_half_page_up_clamps_to_zero() {
+        let mut app = App::new(make_test_files(), false);
+        app.diff_view_area = Rect::new(0, 0, 80, 20);
+        app.scroll_offset = 3; // less than half page (10)
+        app.scroll_half_page_up();
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    // --- full-page scroll tests ---
+
+    #[test]
+    fn test_scroll_full_page_down() {
+        let mut app = App::new(make_long_files(100), false);
+        app.diff_view_area = Rect::new(0, 0, 80, 20);
+        app.scroll_offset = 0;
+        app.scroll_full_page_down();
+        assert_eq!(app.scroll_offset, 20);
+    }
+
+    #[test]
+    fn test_scroll_down_stops_at_end_of_content() {
+        // 105 rows for hunk 0, a separator and 2 for hunk 1, 18 visible
+        let mut app = App::new(make_long_files(100), false);
+        app.diff_view_area = Rect::new(0, 0, 80, 20);
+        assert_eq!(app.max_scroll(), 108 - 18);
+        for _ in 0..10 {
+            app.scroll_full_page_down();
+        }
+        assert_eq!(app.scroll_offset, 90);
+        app.scroll_down();
+        assert_eq!(app.scroll_offset, 90);
+
+        // Content shorter than the view doesn't scroll
+        let mut app = App::new(make_test_files(), false);
+        app.diff_view_area = Rect::new(0, 0, 80, 20);
+        app.scroll_down();
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_scroll_full_page_up() {
+        let mut app = App::new(make_test_files(), false);
+        app.diff_view_area = Rect::new(0, 0, 80, 20);
+        app.scroll_offset = 30;
+        app.scroll_full_page_up();
+        assert_eq!(app.scroll_offset, 10); // 30 - 20
+    }
+
+    #[test]
+    fn test_scroll_full_page_up_clamps_to_zero() {
+        let mut app = App::new(make_test_files(), false);
+        app.diff_view_area = Rect::new(0, 0, 80, 20);
+        app.scroll_offset = 5; // less than full page (20)
+        app.scroll_full_page_up();
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_scroll_page_zero_height() {
+        let mut app = App::new(make_test_files(), false);
+        app.diff_view_area = Rect::new(0, 0, 80, 0);
+        app.scroll_offset = 0;
+        app.scroll_half_page_down();
+        assert_eq!(app.scroll_offset, 1); // .max(1) ensures scroll by 1
+        app.scroll_offset = 0;
+        app.scroll_full_page_down();
+        assert_eq!(app.scroll_offset, 1);
     }
 
+    // --- context-sensitive j/k tests ---
+
     #[test]
-    fn test_app_initial_state() {
-        let app = App::new_with_help(make_test_files(), false, false);
-        assert_eq!(app.selected_file, 0);
-        assert_eq!(app.selected_hunk, 0);
-        assert_eq!(app.mode, AppMode::Browsing);
-        assert_eq!(app.focus, FocusPanel::DiffView);
+    fn test_j_scrolls_diff_when_diffview_focused() {
+        let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        app.focus = FocusPanel::DiffView;
+        app.scroll_offset = 0;
+        // Simulate j: when DiffView focused, scroll_down
+        app.scroll_down();
+        assert_eq!(app.scroll_offset, 1);
     }
 
     #[test]
-    fn test_select_next_file() {
+    fn test_k_scrolls_diff_when_diffview_focused() {
+        let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        app.focus = FocusPanel::DiffView;
+        app.scroll_offset = 5;
+        app.scroll_up();
+        assert_eq!(app.scroll_offset, 4);
+    }
+
+    #[test]
+    fn test_j_navigates_file_when_filelist_focused() {
         let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        app.focus = FocusPanel::FileList;
+        assert_eq!(app.selected_file, 0);
         app.select_next_file();
         assert_eq!(app.selected_file, 1);
     }
 
     #[test]
-    fn test_select_prev_file() {
+    fn test_k_navigates_file_when_filelist_focused() {
         let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        app.focus = FocusPanel::FileList;
         app.selected_file = 1;
         app.select_prev_file();
         assert_eq!(app.selected_file, 0);
     }
 
+    // --- new hunk/file navigation key tests ---
+
     #[test]
-    fn test_select_file_wraps() {
+    fn test_curly_brace_next_hunk() {
         let mut app = App::new(make_test_files(), false);
-        app.selected_file = 1; // last file
-        app.select_next_file();
-        assert_eq!(app.selected_file, 0); // wrapped to first
+        app.mode = AppMode::Browsing;
+        assert_eq!(app.selected_hunk, 0);
+        app.select_next_hunk();
+        assert_eq!(app.selected_hunk, 1);
     }
 
     #[test]
-    fn test_select_next_hunk() {
+    fn test_curly_brace_prev_hunk() {
+        let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        app.selected_hunk = 1;
+        app.select_prev_hunk();
+        assert_eq!(app.selected_hunk, 0);
+    }
+
+    #[test]
+    fn test_shift_j_next_hunk() {
         let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        assert_eq!(app.selected_hunk, 0);
+        // J calls select_next_hunk (synonym for })
         app.select_next_hunk();
         assert_eq!(app.selected_hunk, 1);
-        assert_eq!(app.selected_file, 0);
     }
 
     #[test]
-    fn test_next_hunk_advances_file() {
+    fn test_shift_k_prev_hunk() {
         let mut app = App::new(make_test_files(), false);
-        app.selected_hunk = 1; // last hunk of first file
-        app.select_next_hunk();
-        assert_eq!(app.selected_file, 1);
+        app.mode = AppMode::Browsing;
+        app.selected_hunk = 1;
+        // K calls select_prev_hunk (synonym for {)
+        app.select_prev_hunk();
         assert_eq!(app.selected_hunk, 0);
     }
 
     #[test]
-    fn test_next_hunk_wraps_at_end() {
+    fn test_shift_l_next_file() {
         let mut app = App::new(make_test_files(), false);
-        // Navigate to last hunk of last file
+        app.mode = AppMode::Browsing;
+        assert_eq!(app.selected_file, 0);
+        app.select_next_file();
+        assert_eq!(app.selected_file, 1);
+    }
+
+    #[test]
+    fn test_shift_h_prev_file() {
+        let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
         app.selected_file = 1;
-        app.selected_hunk = 0; // only one hunk
-        app.select_next_hunk();
-        // Should wrap to first hunk of first file
+        app.select_prev_file();
         assert_eq!(app.selected_file, 0);
-        assert_eq!(app.selected_hunk, 0);
     }
 
+    // --- directional panel focus tests ---
+
     #[test]
-    fn test_scroll_down() {
+    fn test_h_focuses_filelist() {
         let mut app = App::new(make_test_files(), false);
-        app.scroll_down();
-        assert_eq!(app.scroll_offset, 1);
+        app.mode = AppMode::Browsing;
+        app.focus = FocusPanel::DiffView;
+        app.focus = FocusPanel::FileList;
+        assert_eq!(app.focus, FocusPanel::FileList);
     }
 
     #[test]
-    fn test_scroll_clamps_to_content() {
+    fn test_l_focuses_diffview() {
         let mut app = App::new(make_test_files(), false);
-        app.scroll_up(); // at 0, should stay at 0
-        assert_eq!(app.scroll_offset, 0);
+        app.mode = AppMode::Browsing;
+        app.focus = FocusPanel::FileList;
+        app.focus = FocusPanel::DiffView;
+        assert_eq!(app.focus, FocusPanel::DiffView);
     }
 
     #[test]
-    fn test_current_file() {
-        let app = App::new(make_test_files(), false);
-        let file = app.current_file().unwrap();
-        assert_eq!(file.path.to_string_lossy(), "src/a.rs");
+    fn test_h_when_already_filelist() {
+        let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        app.focus = FocusPanel::FileList;
+        // Setting again is idempotent
+        app.focus = FocusPanel::FileList;
+        assert_eq!(app.focus, FocusPanel::FileList);
     }
 
     #[test]
-    fn test_current_hunk() {
-        let app = App::new(make_test_files(), false);
-        let hunk = app.current_hunk().unwrap();
-        assert_eq!(hunk.header, "@@ -1,3 +1,4 @@");
+    fn test_l_when_already_diffview() {
+        let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        app.focus = FocusPanel::DiffView;
+        // Setting again is idempotent
+        app.focus = FocusPanel::DiffView;
+        assert_eq!(app.focus, FocusPanel::DiffView);
     }
 
+    // --- pending key / gg sequence tests ---
+
     #[test]
-    fn test_empty_diff_state() {
-        let mut app = App::new(vec![], false);
-        assert!(app.current_file().is_none());
-        assert!(app.current_hunk().is_none());
-        // These should be no-ops without panic
-        app.select_next_file();
-        app.select_prev_file();
-        app.select_next_hunk();
-        app.select_prev_hunk();
-        app.scroll_down();
-        app.scroll_up();
+    fn test_g_sets_pending_key() {
+        let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        app.pending_key = Some('g');
+        app.prompt("g...");
+        assert_eq!(app.pending_key, Some('g'));
+        assert_eq!(app.message.as_deref(), Some("g..."));
     }
 
     #[test]
-    fn test_skip_updates_hunk_status() {
+    fn test_gg_scrolls_to_top() {
         let mut app = App::new(make_test_files(), false);
-        app.skip_current_hunk();
-        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Skipped);
+        app.mode = AppMode::Browsing;
+        app.scroll_offset = 42;
+        // Simulate: first g sets pending, second g triggers scroll_to_top
+        app.pending_key = Some('g');
+        // When event loop sees pending_key == Some('g') and next key is 'g':
+        app.pending_key = None;
+        app.scroll_to_top();
+        assert_eq!(app.scroll_offset, 0);
+        assert_eq!(app.pending_key, None);
     }
 
     #[test]
-    fn test_accept_current_hunk() {
-        let mut app = App::new(make_test_files(), true);
+    fn test_g_then_other_key_clears_pending() {
+        let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        app.pending_key = Some('g');
+        // Non-g key should clear pending
+        app.pending_key = None;
+        app.message = None;
+        assert_eq!(app.pending_key, None);
+    }
+
+    #[test]
+    fn test_g_then_capital_g_clears_pending_and_scrolls_bottom() {
+        let mut app = App::new(make_test_files(), false);
+        app.mode = AppMode::Browsing;
+        app.pending_key = Some('g');
+        // When event loop sees pending_key == Some('g') and next key is 'G':
+        // it clears pending and falls through to match G → scroll_to_bottom
+        app.pending_key = None;
+        app.message = None;
+        app.scroll_to_bottom();
+        assert!(app.scroll_offset > 0);
+    }
+
+    // --- help overlay mode tests ---
+
+    #[test]
+    fn test_initial_mode_is_help_on_first_run() {
+        let app = App::new_with_help(make_test_files(), false, true);
+        assert_eq!(app.mode, AppMode::Help);
+    }
+
+    #[test]
+    fn test_initial_mode_is_browsing_on_subsequent_run() {
+        let app = App::new_with_help(make_test_files(), false, false);
+        assert_eq!(app.mode, AppMode::Browsing);
+    }
+
+    #[test]
+    fn test_help_mode_any_key_dismisses() {
+        let mut app = App::new_with_help(make_test_files(), false, true);
+        assert_eq!(app.mode, AppMode::Help);
+        // Simulate: any key in Help mode switches to Browsing
+        app.mode = AppMode::Browsing;
+        assert_eq!(app.mode, AppMode::Browsing);
+    }
+
+    #[test]
+    fn test_help_mode_key_not_processed_as_action() {
+        let mut app = App::new_with_help(make_test_files(), false, true);
+        assert_eq!(app.mode, AppMode::Help);
+        // Pressing 'y' in Help mode should dismiss help, NOT stage a hunk
+        app.mode = AppMode::Browsing; // This is what the event loop does
+        // Hunk status should remain Pending (not Staged)
         assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
-        app.accept_current_hunk();
-        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Staged);
-        assert_eq!(
-            app.message.as_deref(),
-            Some("Hunk accepted"),
-            "Should show 'Hunk accepted' message"
-        );
-        // Should advance to next hunk
-        assert_eq!(app.selected_hunk, 1);
     }
 
     #[test]
-    fn test_accept_current_hunk_skips_non_pending() {
-        let mut app = App::new(make_test_files(), true);
-        app.files[0].hunks[0].status = HunkStatus::Skipped;
-        app.accept_current_hunk();
-        // Should not change status of already-skipped hunk
-        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Skipped);
+    fn test_help_keys_scroll_search_and_close() {
+        let mut app = App::new_with_help(make_test_files(), false, true);
+        assert!(!app.handle_help_key(KeyCode::Char('j')));
+        assert!(!app.handle_help_key(KeyCode::Down));
+        assert!(!app.handle_help_key(KeyCode::Char('k')));
+        assert_eq!(app.help_scroll, 1);
+
+        // While searching, j and q are typed rather than acted on
+        assert!(!app.handle_help_key(KeyCode::Char('/')));
+        for c in "jq".chars() {
+            assert!(!app.handle_help_key(KeyCode::Char(c)));
+        }
+        assert_eq!(app.help_query, "jq");
+        assert_eq!(app.help_scroll, 0);
+        app.handle_help_key(KeyCode::Backspace);
+        app.handle_help_key(KeyCode::Enter);
+        assert_eq!(app.help_query, "j");
+        assert_eq!(app.mode, AppMode::Help);
+
+        // Any other key closes and resets the overlay
+        assert!(app.handle_help_key(KeyCode::Char('y')));
+        assert_eq!(app.mode, AppMode::Browsing);
+        assert!(app.help_query.is_empty());
+        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
     }
 
     #[test]
-    fn test_toggle_focus() {
-        let mut app = App::new(make_test_files(), false);
-        assert_eq!(app.focus, FocusPanel::DiffView);
-        app.toggle_focus();
-        assert_eq!(app.focus, FocusPanel::FileList);
-        app.toggle_focus();
-        assert_eq!(app.focus, FocusPanel::DiffView);
+    fn test_overview_leaves_out_excluded_files() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.start_overview();
+        assert_eq!(app.mode, AppMode::Overview);
+        app.handle_overview_key(KeyCode::Char(' '));
+        app.handle_overview_key(KeyCode::Char('j'));
+        app.handle_overview_key(KeyCode::Char('j'));
+        assert_eq!(app.overview_cursor, 1);
+        app.handle_overview_key(KeyCode::Char(' '));
+
+        // Not with every file left out
+        app.handle_overview_key(KeyCode::Enter);
+        assert_eq!(app.mode, AppMode::Overview);
+        app.handle_overview_key(KeyCode::Char(' '));
+
+        app.handle_overview_key(KeyCode::Enter);
+        assert_eq!(app.mode, AppMode::Browsing);
+        assert_eq!(app.files.len(), 1);
+        assert_eq!(app.files[0].path, PathBuf::from("src/b.rs"));
+        assert_eq!(app.hidden_files[0].path, PathBuf::from("src/a.rs"));
+
+        // `:files` brings it back
+        app.execute_command("files", None);
+        assert_eq!(app.files.len(), 2);
+    }
+
+    #[test]
+    fn test_quit_asks_while_hunks_are_pending() {
+        let mut app = App::new_with_help(make_test_files(), true, false);
+        assert_eq!(app.pending_hunk_count(), 3);
+        assert!(!app.request_quit());
+        assert_eq!(app.mode, AppMode::ConfirmQuit);
+        // Other keys do nothing; n goes back to reviewing
+        assert!(!app.handle_confirm_quit_key(KeyCode::Char('x')));
+        assert_eq!(app.mode, AppMode::ConfirmQuit);
+        assert!(!app.handle_confirm_quit_key(KeyCode::Esc));
+        assert_eq!(app.mode, AppMode::Browsing);
+
+        app.request_quit();
+        assert!(app.handle_confirm_quit_key(KeyCode::Char('y')));
+
+        // Nothing pending and no feedback: quit right away
+        for file in &mut app.files {
+            for hunk in &mut file.hunks {
+                hunk.status = HunkStatus::Skipped;
+            }
+        }
+        app.mode = AppMode::Browsing;
+        assert!(app.request_quit());
     }
 
     #[test]
-    fn test_all_hunks_staged_marks_file() {
-        let mut app = App::new(make_test_files(), true);
-        // Stage first file's hunks via skip (since no_stage=true)
-        app.selected_file = 1;
-        app.skip_current_hunk();
-        let file = &app.files[1];
-        assert!(file.hunks.iter().all(|h| h.status != HunkStatus::Pending));
+    fn test_quit_discarding_feedback() {
+        let mut app = App::new_with_help(make_test_files(), true, false);
+        app.add_line_comment(1, "why?");
+        app.request_quit();
+        assert!(app.handle_confirm_quit_key(KeyCode::Char('d')));
+        assert!(app.feedback.is_empty());
     }
 
     #[test]
-    fn test_handle_mouse_click_selects_file() {
-        let mut app = App::new(make_test_files(), false);
-        // Simulate file list area: x=0, y=0, width=20, height=10
-        app.file_list_area = Rect::new(0, 0, 20, 10);
-        // Click on second file (row 2 = border row 0 + item index 1)
-        app.handle_mouse_click(5, 2);
-        assert_eq!(app.selected_file, 1);
-        assert_eq!(app.focus, FocusPanel::FileList);
+    fn test_question_mark_toggles_help() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        assert_eq!(app.mode, AppMode::Browsing);
+        // Pressing '?' in Browsing mode switches to Help
+        app.mode = AppMode::Help;
+        assert_eq!(app.mode, AppMode::Help);
     }
 
     #[test]
-    fn test_handle_mouse_click_outside_file_list() {
-        let mut app = App::new(make_test_files(), false);
-        app.file_list_area = Rect::new(0, 0, 20, 10);
-        // Click outside the file list area
-        app.handle_mouse_click(25, 2);
-        assert_eq!(app.selected_file, 0); // unchanged
+    fn test_question_mark_from_help_dismisses() {
+        let mut app = App::new_with_help(make_test_files(), false, true);
+        app.mode = AppMode::Help;
+        // Pressing '?' in Help mode switches back to Browsing
+        app.mode = AppMode::Browsing;
+        assert_eq!(app.mode, AppMode::Browsing);
     }
 
+    // --- dirty flag for new methods ---
+
     #[test]
-    fn test_dirty_flag_set_on_navigation() {
+    fn test_dirty_flag_new_methods() {
         let mut app = App::new(make_test_files(), false);
-        assert!(app.dirty, "dirty should start true");
-        app.dirty = false;
-
-        app.select_next_file();
-        assert!(app.dirty, "dirty should be true after select_next_file");
-        app.dirty = false;
-
-        app.select_prev_file();
-        assert!(app.dirty, "dirty should be true after select_prev_file");
-        app.dirty = false;
+        app.diff_view_area = Rect::new(0, 0, 80, 20);
 
-        app.select_next_hunk();
-        assert!(app.dirty, "dirty should be true after select_next_hunk");
         app.dirty = false;
+        app.scroll_to_top();
+        assert!(app.dirty, "dirty should be true after scroll_to_top");
 
-        app.select_prev_hunk();
-        assert!(app.dirty, "dirty should be true after select_prev_hunk");
         app.dirty = false;
+        app.scroll_to_bottom();
+        assert!(app.dirty, "dirty should be true after scroll_to_bottom");
 
-        app.scroll_down();
-        assert!(app.dirty, "dirty should be true after scroll_down");
         app.dirty = false;
+        app.scroll_half_page_down();
+        assert!(
+            app.dirty,
+            "dirty should be true after scroll_half_page_down"
+        );
 
-        app.scroll_up();
-        assert!(app.dirty, "dirty should be true after scroll_up");
         app.dirty = false;
+        app.scroll_half_page_up();
+        assert!(app.dirty, "dirty should be true after scroll_half_page_up");
 
-        app.toggle_focus();
-        assert!(app.dirty, "dirty should be true after toggle_focus");
         app.dirty = false;
+        app.scroll_full_page_down();
+        assert!(
+            app.dirty,
+            "dirty should be true after scroll_full_page_down"
+        );
 
-        app.skip_current_hunk();
-        assert!(app.dirty, "dirty should be true after skip_current_hunk");
         app.dirty = false;
-
-        app.split_current_hunk();
-        assert!(app.dirty, "dirty should be true after split_current_hunk");
-    }
-
-    #[test]
-    fn test_compute_line_offset_no_staged() {
-        let app = App::new(make_test_files(), false);
-        assert_eq!(app.compute_line_offset(0, 1), 0);
-    }
-
-    #[test]
-    fn test_compute_line_offset_with_staged() {
-        let mut app = App::new(make_test_files(), false);
-        // First hunk: old_lines=3, new_lines=3 → offset 0
-        app.files[0].hunks[0].status = HunkStatus::Staged;
-        assert_eq!(app.compute_line_offset(0, 1), 0);
-
-        // Change first hunk to have different new_lines
-        app.files[0].hunks[0].new_lines = 5;
-        // offset = 5 - 3 = 2
-        assert_eq!(app.compute_line_offset(0, 1), 2);
+        app.scroll_full_page_up();
+        assert!(app.dirty, "dirty should be true after scroll_full_page_up");
     }
 
-    // --- scroll_to_top tests ---
+    // --- start position tests ---
 
     #[test]
-    fn test_scroll_to_top() {
-        let mut app = App::new(make_test_files(), false);
-        app.scroll_offset = 42;
-        app.scroll_to_top();
-        assert_eq!(app.scroll_offset, 0);
+    fn test_jump_to_position_by_file_and_hunk() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        let pos = StartPosition::from_args(Some("src/a.rs"), Some(2));
+        assert!(app.jump_to_position(&pos));
+        assert_eq!(app.selected_file, 0);
+        assert_eq!(app.selected_hunk, 1);
+        assert_eq!(app.scroll_offset, 6);
     }
 
     #[test]
-    fn test_scroll_to_top_already_at_top() {
-        let mut app = App::new(make_test_files(), false);
-        app.scroll_offset = 0;
-        app.scroll_to_top();
-        assert_eq!(app.scroll_offset, 0);
-    }
+    fn test_jump_to_position_by_line() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        // Line 22 falls inside the second hunk (+21,4)
+        let pos = StartPosition::from_args(Some("src/a.rs:22"), None);
+        assert!(app.jump_to_position(&pos));
+        assert_eq!(app.selected_hunk, 1);
 
-    #[test]
-    fn test_scroll_to_top_empty() {
-        let mut app = App::new(vec![], false);
-        app.scroll_to_top(); // should not panic
-        assert_eq!(app.scroll_offset, 0);
+        // Line between hunks selects the next one
+        let pos = StartPosition::from_args(Some("src/a.rs:10"), None);
+        assert!(app.jump_to_position(&pos));
+        assert_eq!(app.selected_hunk, 1);
     }
 
-    // --- scroll_to_bottom tests ---
-
     #[test]
-    fn test_scroll_to_bottom() {
+    fn test_jump_to_position_path_suffix() {
         let mut app = App::new_with_help(make_test_files(), false, false);
-        // Simulate a diff view area: 80 wide, 10 tall (inner height = 8)
-        app.diff_view_area = Rect::new(0, 0, 80, 10);
-        app.scroll_to_bottom();
-        // File 0 has 2 hunks:
-        //   hunk0: 1 header + 4 lines + 1 sep = 6
-        //   hunk1: 1 header + 1 line (no trailing sep) = 2
-        // Total content = 8 lines, visible inner height = 8
-        // scroll_offset = 8 - 8 = 0
-        assert_eq!(app.scroll_offset, 0);
+        let pos = StartPosition::from_args(Some("./b.rs"), None);
+        assert!(app.jump_to_position(&pos));
+        assert_eq!(app.selected_file, 1);
+        assert_eq!(app.selected_hunk, 0);
     }
 
     #[test]
-    fn test_scroll_to_bottom_with_small_viewport() {
+    fn test_jump_to_position_unknown_file() {
         let mut app = App::new_with_help(make_test_files(), false, false);
-        // Small viewport: inner height = 3
-        app.diff_view_area = Rect::new(0, 0, 80, 5);
-        app.scroll_to_bottom();
-        // Total content = 8, visible = 3, offset = 8 - 3 = 5
-        assert_eq!(app.scroll_offset, 5);
+        let pos = StartPosition::from_args(Some("src/missing.rs"), Some(1));
+        assert!(!app.jump_to_position(&pos));
+        assert_eq!(app.selected_file, 0);
+        assert!(app.message.as_deref().unwrap().contains("src/missing.rs"));
     }
 
     #[test]
-    fn test_scroll_to_bottom_no_overscroll() {
+    fn test_jump_to_position_hunk_out_of_range() {
         let mut app = App::new_with_help(make_test_files(), false, false);
-        // Viewport larger than content: inner height = 50
-        app.diff_view_area = Rect::new(0, 0, 80, 52);
-        app.scroll_to_bottom();
-        // Total content = 8, visible = 50, saturating_sub → 0
-        assert_eq!(app.scroll_offset, 0);
+        let pos = StartPosition::from_args(Some("src/b.rs"), Some(5));
+        assert!(!app.jump_to_position(&pos));
+        assert_eq!(app.selected_file, 0);
+        assert_eq!(app.selected_hunk, 0);
     }
 
     #[test]
-    fn test_scroll_to_bottom_empty() {
-        let mut app = App::new_with_help(vec![], false, false);
-        app.scroll_to_bottom(); // should not panic
-        assert_eq!(app.scroll_offset, 0);
+    fn test_start_position_from_args() {
+        let pos = StartPosition::from_args(Some("src/a.rs:12"), None);
+        assert_eq!(pos.path, Some(PathBuf::from("src/a.rs")));
+        assert_eq!(pos.line, Some(12));
+
+        // Non-numeric suffix is treated as part of the path
+        let pos = StartPosition::from_args(Some("weird:name.rs"), Some(2));
+        assert_eq!(pos.path, Some(PathBuf::from("weird:name.rs")));
+        assert_eq!(pos.line, None);
+        assert_eq!(pos.hunk, Some(2));
     }
 
-    // --- half-page scroll tests ---
-
-    #[test]
-    fn test_scroll_half_page_down() {
-        let mut app = App::new(make_test_files(), false);
-        app.diff_view_area = Rect::new(0, 0, 80, 20);
-        app.scroll_offset = 0;
-        app.scroll_half_page_down();
-        assert_eq!(app.scroll_offset, 10); // 20/2
-    }
+    // --- view mode tests ---
 
     #[test]
-    fn test_scroll_half_page_up() {
-        let mut app = App::new(make_test_files(), false);
-        app.diff_view_area = Rect::new(0, 0, 80, 20);
-        app.scroll_offset = 15;
-        app.scroll_half_page_up();
-        assert_eq!(app.scroll_offset, 5); // 15 - 10
-    }
+    fn test_current_file_location() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.select_next_hunk();
+        assert_eq!(
+            app.current_file_location(None),
+            Some((PathBuf::from("src/a.rs"), 21))
+        );
 
-    #[test]
-    fn test_scroll_half_page_up_clamps_to_zero() {
-        let mut app = App::new(make_test_files(), false);
-        app.diff_view_area = Rect::new(0, 0, 80, 20);
-        app.scroll_offset = 3; // less than half page (10)
-        app.scroll_half_page_up();
-        assert_eq!(app.scroll_offset, 0);
+        let empty = App::new_with_help(vec![], false, false);
+        assert_eq!(empty.current_file_location(None), None);
     }
 
-    // --- full-page scroll tests ---
-
     #[test]
-    fn test_scroll_full_page_down() {
-        let mut app = App::new(make_test_files(), false);
-        app.diff_view_area = Rect::new(0, 0, 80, 20);
-        app.scroll_offset = 0;
-        app.scroll_full_page_down();
-        assert_eq!(app.scroll_offset, 20);
+    fn test_line_mode_cursor_crosses_hunks() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        assert_eq!(app.highlighted_line(), None);
+        app.toggle_line_mode();
+        assert!(app.line_mode);
+        // Starts on the first change
+        assert_eq!(app.line_cursor(), Some(1));
+        assert_eq!(app.highlighted_line(), Some(1));
+
+        app.cursor_down();
+        app.cursor_down();
+        app.cursor_down();
+        assert_eq!((app.selected_hunk, app.line_cursor()), (1, Some(0)));
+        app.cursor_up();
+        assert_eq!((app.selected_hunk, app.line_cursor()), (0, Some(3)));
+
+        app.toggle_line_mode();
+        assert!(!app.line_mode);
+        assert_eq!(app.highlighted_line(), None);
     }
 
     #[test]
-    fn test_scroll_full_page_up() {
-        let mut app = App::new(make_test_files(), false);
-        app.diff_view_area = Rect::new(0, 0, 80, 20);
-        app.scroll_offset = 30;
-        app.scroll_full_page_up();
-        assert_eq!(app.scroll_offset, 10); // 30 - 20
-    }
+    fn test_line_cursor_anchors_comment_and_location() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.toggle_line_mode();
+        // On the removed line: the editor opens at the line replacing it
+        assert_eq!(
+            app.current_file_location(None),
+            Some((PathBuf::from("src/a.rs"), 2))
+        );
+        app.cursor_down();
+        app.cursor_down();
+        assert_eq!(
+            app.current_file_location(None),
+            Some((PathBuf::from("src/a.rs"), 3))
+        );
 
-    #[test]
-    fn test_scroll_full_page_up_clamps_to_zero() {
-        let mut app = App::new(make_test_files(), false);
-        app.diff_view_area = Rect::new(0, 0, 80, 20);
-        app.scroll_offset = 5; // less than full page (20)
-        app.scroll_full_page_up();
-        assert_eq!(app.scroll_offset, 0);
+        app.start_inline_comment();
+        assert_eq!(app.comment_anchor, Some(3));
+        app.comment_input.push_str("after line3");
+        app.submit_inline_comment();
+        assert_eq!(
+            app.feedback[0].comment_positions,
+            vec![(4, "after line3".to_string())]
+        );
     }
 
     #[test]
-    fn test_scroll_page_zero_height() {
-        let mut app = App::new(make_test_files(), false);
-        app.diff_view_area = Rect::new(0, 0, 80, 0);
-        app.scroll_offset = 0;
-        app.scroll_half_page_down();
-        assert_eq!(app.scroll_offset, 1); // .max(1) ensures scroll by 1
-        app.scroll_offset = 0;
-        app.scroll_full_page_down();
-        assert_eq!(app.scroll_offset, 1);
+    fn test_accept_line_without_repo() {
+        let mut app = App::new_with_help(make_test_files(), true, false);
+        app.toggle_line_mode();
+        app.stage_current_line(None).unwrap();
+        let hunks = &app.files[0].hunks;
+        assert_eq!(hunks.len(), 3);
+        assert_eq!(hunks[0].status, HunkStatus::Staged);
+        assert_eq!(hunks[0].old_lines, 3);
+        assert_eq!(hunks[0].new_lines, 2);
+        // The rest keeps the added line, on top of the staged removal
+        assert_eq!(hunks[1].status, HunkStatus::Pending);
+        assert_eq!(hunks[1].old_lines, 2);
+        assert_eq!(hunks[1].new_lines, 3);
+        assert_eq!(app.selected_hunk, 1);
+        assert_eq!(app.line_cursor(), Some(1));
     }
 
-    // --- context-sensitive j/k tests ---
-
     #[test]
-    fn test_j_scrolls_diff_when_diffview_focused() {
-        let mut app = App::new(make_test_files(), false);
+    fn test_command_quit_and_unknown() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        // Hunks are pending, so `:q` asks first and `:q!` doesn't
+        assert_eq!(app.execute_command("q", None), CommandOutcome::Continue);
+        assert_eq!(app.mode, AppMode::ConfirmQuit);
         app.mode = AppMode::Browsing;
-        app.focus = FocusPanel::DiffView;
-        app.scroll_offset = 0;
-        // Simulate j: when DiffView focused, scroll_down
-        app.scroll_down();
-        assert_eq!(app.scroll_offset, 1);
+        assert_eq!(app.execute_command("q!", None), CommandOutcome::Quit);
+        assert_eq!(app.execute_command("  ", None), CommandOutcome::Continue);
+        assert_eq!(
+            app.execute_command("frobnicate", None),
+            CommandOutcome::Continue
+        );
+        assert_eq!(app.message.as_deref(), Some("Unknown command: frobnicate"));
+        app.execute_command("theme neon", None);
+        assert!(app.message.as_deref().unwrap().contains("Unknown theme"));
     }
 
     #[test]
-    fn test_k_scrolls_diff_when_diffview_focused() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.focus = FocusPanel::DiffView;
-        app.scroll_offset = 5;
-        app.scroll_up();
-        assert_eq!(app.scroll_offset, 4);
+    fn test_command_stage_matching() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.execute_command("stage-matching (", None);
+        assert!(app.message.as_deref().unwrap().starts_with("Invalid regex"));
+        app.execute_command("stage-matching no such line", None);
+        assert_eq!(
+            app.message.as_deref(),
+            Some("No pending hunk matches /no such line/")
+        );
+        assert_eq!(app.mode, AppMode::Browsing);
+
+        app.execute_command("stage-matching ^ba[rz]$", None);
+        assert_eq!(app.mode, AppMode::BulkStagePrompt);
+        assert_eq!(
+            app.message.as_deref(),
+            Some("Stage 1 hunk(s) in 1 file(s) matching /^ba[rz]$/?")
+        );
+        app.handle_bulk_stage_key(KeyCode::Char('n'), None);
+        assert_eq!(app.files[1].hunks[0].status, HunkStatus::Pending);
+
+        app.execute_command("stage-matching ^ba[rz]$", None);
+        app.handle_bulk_stage_key(KeyCode::Char('y'), None);
+        assert_eq!(app.mode, AppMode::Browsing);
+        assert_eq!(app.files[1].hunks[0].status, HunkStatus::Staged);
+        assert!(
+            app.files[0]
+                .hunks
+                .iter()
+                .all(|h| h.status == HunkStatus::Pending)
+        );
     }
 
     #[test]
-    fn test_j_navigates_file_when_filelist_focused() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.focus = FocusPanel::FileList;
-        assert_eq!(app.selected_file, 0);
-        app.select_next_file();
-        assert_eq!(app.selected_file, 1);
+    fn test_command_completion() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.start_command();
+        app.command_input = "st".to_string();
+        app.complete_command();
+        assert_eq!(app.command_input, "stage-");
+        assert_eq!(app.message.as_deref(), Some("stage-file stage-matching"));
+        app.command_input.push('m');
+        app.complete_command();
+        assert_eq!(app.command_input, "stage-matching ");
+        assert_eq!(app.message.as_deref(), Some("<regex>"));
+        app.command_input = "zz".to_string();
+        app.complete_command();
+        assert_eq!(app.message.as_deref(), Some("No such command"));
     }
 
     #[test]
-    fn test_k_navigates_file_when_filelist_focused() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.focus = FocusPanel::FileList;
+    fn test_command_file_actions_and_context() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.execute_command("skip-file", None);
+        assert!(
+            app.files[0]
+                .hunks
+                .iter()
+                .all(|h| h.status == HunkStatus::Skipped)
+        );
         app.selected_file = 1;
-        app.select_prev_file();
-        assert_eq!(app.selected_file, 0);
+        app.execute_command("stage-file", None);
+        assert_eq!(app.files[1].hunks[0].status, HunkStatus::Staged);
+        assert_eq!(app.message.as_deref(), Some("Staged 1 hunk(s) of src/b.rs"));
+
+        app.execute_command("context 2", None);
+        assert_eq!(app.context_lines, 2);
+        app.execute_command("context many", None);
+        assert_eq!(app.context_lines, 2);
+        assert_eq!(
+            app.message.as_deref(),
+            Some("Invalid number of lines 'many'")
+        );
     }
 
-    // --- new hunk/file navigation key tests ---
-
     #[test]
-    fn test_curly_brace_next_hunk() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        assert_eq!(app.selected_hunk, 0);
-        app.select_next_hunk();
-        assert_eq!(app.selected_hunk, 1);
+    fn test_command_format() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.execute_command("format json", None);
+        assert_eq!(app.feedback_format, FeedbackFormat::Json);
+        app.execute_command("format yaml", None);
+        assert_eq!(app.feedback_format, FeedbackFormat::Json);
+        assert!(app.message.as_deref().unwrap().contains("Unknown format"));
+        app.execute_command("format gerrit", None);
+        assert_eq!(app.feedback_format, FeedbackFormat::Gerrit);
     }
 
     #[test]
-    fn test_curly_brace_prev_hunk() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.selected_hunk = 1;
-        app.select_prev_hunk();
-        assert_eq!(app.selected_hunk, 0);
+    fn test_command_files_filter_and_restore() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.files[1].hunks[0].status = HunkStatus::Skipped;
+
+        app.execute_command("files src/b*", None);
+        assert_eq!(app.files.len(), 1);
+        assert_eq!(app.files[0].path, PathBuf::from("src/b.rs"));
+        assert_eq!(app.hidden_files.len(), 1);
+
+        // No match keeps everything visible
+        app.execute_command("files *.py", None);
+        assert_eq!(app.files.len(), 2);
+        assert!(app.message.as_deref().unwrap().contains("No files match"));
+
+        app.execute_command("files src/b*", None);
+        app.execute_command("files", None);
+        assert_eq!(app.files.len(), 2);
+        assert!(app.hidden_files.is_empty());
+        assert_eq!(app.files[1].hunks[0].status, HunkStatus::Skipped);
     }
 
     #[test]
-    fn test_shift_j_next_hunk() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        assert_eq!(app.selected_hunk, 0);
-        // J calls select_next_hunk (synonym for })
-        app.select_next_hunk();
-        assert_eq!(app.selected_hunk, 1);
+    fn test_command_files_relative_to_cwd() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.cwd_prefix = PathBuf::from("src");
+
+        app.execute_command("files a*", None);
+        assert_eq!(app.files.len(), 1);
+        assert_eq!(app.files[0].path, PathBuf::from("src/a.rs"));
     }
 
     #[test]
-    fn test_shift_k_prev_hunk() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.selected_hunk = 1;
-        // K calls select_prev_hunk (synonym for {)
-        app.select_prev_hunk();
-        assert_eq!(app.selected_hunk, 0);
+    fn test_inline_comment_after_last_change() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.start_inline_comment();
+        assert_eq!(app.mode, AppMode::InlineComment);
+        // Anchored on the added line, the last change in the hunk
+        assert_eq!(app.comment_anchor, Some(2));
+        assert_eq!(app.highlighted_line(), Some(2));
+
+        app.comment_input.push_str("  rename this  ");
+        app.submit_inline_comment();
+        assert_eq!(app.mode, AppMode::Browsing);
+        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Commented);
+        assert_eq!(app.feedback.len(), 1);
+        assert_eq!(
+            app.feedback[0].comment_positions,
+            vec![(3, "rename this".to_string())]
+        );
+
+        let output = feedback::format_feedback(&app.feedback, 5);
+        assert!(output.contains("+new\n# REVIEW COMMENT: rename this\n line3"));
     }
 
     #[test]
-    fn test_shift_l_next_file() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        assert_eq!(app.selected_file, 0);
-        app.select_next_file();
-        assert_eq!(app.selected_file, 1);
+    fn test_inline_comments_merge_in_line_order() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.add_line_comment(2, "second");
+        app.add_line_comment(0, "first");
+        assert_eq!(app.feedback.len(), 1);
+        let fb = &app.feedback[0];
+        assert_eq!(
+            fb.comment_positions,
+            vec![(1, "first".to_string()), (3, "second".to_string())]
+        );
+        assert_eq!(fb.content, "first\nsecond");
     }
 
     #[test]
-    fn test_shift_h_prev_file() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.selected_file = 1;
-        app.select_prev_file();
-        assert_eq!(app.selected_file, 0);
+    fn test_inline_comment_severity_keeps_the_highest() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.add_line_comment(2, "!issue leaks the handle");
+        assert_eq!(app.message.as_deref(), Some("Comment added (issue)"));
+        app.add_line_comment(0, "!NIT spacing");
+        app.add_line_comment(1, "!blocker");
+        assert_eq!(app.message.as_deref(), Some("Empty comment discarded"));
+
+        let fb = &app.feedback[0];
+        assert_eq!(fb.content, "spacing\nleaks the handle");
+        assert_eq!(fb.severity, Some(Severity::Issue));
     }
 
-    // --- directional panel focus tests ---
-
     #[test]
-    fn test_h_focuses_filelist() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.focus = FocusPanel::DiffView;
-        app.focus = FocusPanel::FileList;
-        assert_eq!(app.focus, FocusPanel::FileList);
+    fn test_inline_comment_cancel_and_empty() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.start_inline_comment();
+        app.comment_input.push_str("never mind");
+        app.cancel_inline_comment();
+        assert_eq!(app.mode, AppMode::Browsing);
+        assert!(app.comment_input.is_empty());
+
+        app.start_inline_comment();
+        app.comment_input.push_str("   ");
+        app.submit_inline_comment();
+        assert!(app.feedback.is_empty());
+        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
     }
 
     #[test]
-    fn test_l_focuses_diffview() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.focus = FocusPanel::FileList;
-        app.focus = FocusPanel::DiffView;
-        assert_eq!(app.focus, FocusPanel::DiffView);
+    fn test_command_write_feedback() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("feedback.md");
+        let mut app = App::new_with_help(make_test_files(), false, false);
+
+        app.execute_command(&format!("w {}", path.display()), None);
+        assert_eq!(app.message.as_deref(), Some("No feedback to write"));
+        assert!(!path.exists());
+
+        app.feedback.push(HunkFeedback {
+            file_path: "src/a.rs".to_string(),
+            hunk_header: "@@ -1,3 +1,4 @@".to_string(),
+            kind: crate::types::FeedbackKind::Edit,
+            content: "-a\n+b\n".to_string(),
+            context_lines: vec![],
+            comment_positions: vec![],
+            suggestion: None,
+            severity: None,
+        });
+        app.execute_command(&format!("w {}", path.display()), None);
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("--- a/src/a.rs")
+        );
     }
 
     #[test]
-    fn test_h_when_already_filelist() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.focus = FocusPanel::FileList;
-        // Setting again is idempotent
-        app.focus = FocusPanel::FileList;
-        assert_eq!(app.focus, FocusPanel::FileList);
+    fn test_command_patch_writes_edits() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a\nc\n").unwrap();
+        let path = dir.path().join("edits.patch");
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.workdir = Some(dir.path().to_path_buf());
+
+        app.execute_command(&format!("patch {}", path.display()), None);
+        assert_eq!(app.message.as_deref(), Some("No edits to write"));
+
+        app.feedback.push(HunkFeedback {
+            file_path: "a.txt".to_string(),
+            hunk_header: "@@ -1,1 +1,2 @@".to_string(),
+            kind: crate::types::FeedbackKind::Edit,
+            content: "@@ -1,1 +1,2 @@\n a\n+b\n".to_string(),
+            context_lines: vec![],
+            comment_positions: vec![],
+            suggestion: None,
+            severity: None,
+        });
+        app.execute_command(&format!("patch {}", path.display()), None);
+        assert!(
+            app.message
+                .as_deref()
+                .unwrap()
+                .starts_with("Wrote 1 edit(s)")
+        );
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("@@ -1,2 +1,3 @@\n a\n+b\n c\n")
+        );
     }
 
     #[test]
-    fn test_l_when_already_diffview() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.focus = FocusPanel::DiffView;
-        // Setting again is idempotent
-        app.focus = FocusPanel::DiffView;
-        assert_eq!(app.focus, FocusPanel::DiffView);
-    }
+    fn test_toggle_mark_advances_and_unmarks() {
+        let mut app = App::new_with_help(make_test_files(), true, false);
+        app.toggle_mark_current_hunk();
+        assert!(app.is_marked(0, 0));
+        assert_eq!(app.selected_hunk, 1);
 
-    // --- pending key / gg sequence tests ---
+        app.select_prev_hunk();
+        app.toggle_mark_current_hunk();
+        assert!(app.marked.is_empty());
+    }
 
     #[test]
-    fn test_g_sets_pending_key() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.pending_key = Some('g');
-        app.message = Some("g...".to_string());
-        assert_eq!(app.pending_key, Some('g'));
-        assert_eq!(app.message, Some("g...".to_string()));
+    fn test_stage_marked_hunks_no_stage_mode() {
+        let mut app = App::new_with_help(make_test_files(), true, false);
+        app.marked.insert((0, 1));
+        app.marked.insert((1, 0));
+        // Already reviewed hunks are left alone
+        app.files[1].hunks[0].status = HunkStatus::Skipped;
+
+        assert_eq!(app.stage_marked_hunks(None).unwrap(), 1);
+        assert_eq!(app.files[0].hunks[1].status, HunkStatus::Staged);
+        assert_eq!(app.files[1].hunks[0].status, HunkStatus::Skipped);
+        assert!(app.marked.is_empty());
     }
 
     #[test]
-    fn test_gg_scrolls_to_top() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.scroll_offset = 42;
-        // Simulate: first g sets pending, second g triggers scroll_to_top
-        app.pending_key = Some('g');
-        // When event loop sees pending_key == Some('g') and next key is 'g':
-        app.pending_key = None;
-        app.scroll_to_top();
-        assert_eq!(app.scroll_offset, 0);
-        assert_eq!(app.pending_key, None);
+    fn test_marks_follow_split() {
+        let mut app = App::new_with_help(make_test_files(), true, false);
+        app.marked.extend([(0, 0), (0, 1), (1, 0)]);
+        // Hunk 0 of file 0 became three sub-hunks
+        app.remap_marks_after_split(0, 0, 3);
+        let expected: BTreeSet<_> = [(0, 0), (0, 1), (0, 2), (0, 3), (1, 0)].into();
+        assert_eq!(app.marked, expected);
+
+        // Unmarked hunk split: later marks only shift
+        app.marked = [(0, 3)].into();
+        app.remap_marks_after_split(0, 1, 2);
+        assert_eq!(app.marked, [(0, 4)].into());
     }
 
     #[test]
-    fn test_g_then_other_key_clears_pending() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.pending_key = Some('g');
-        // Non-g key should clear pending
-        app.pending_key = None;
-        app.message = None;
-        assert_eq!(app.pending_key, None);
+    fn test_split_at_cursor_in_line_mode() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.marked.insert((0, 0));
+        app.toggle_line_mode();
+        // The cursor is on the removed line: nothing changes above it
+        app.split_current_hunk();
+        assert_eq!(app.files[0].hunks.len(), 2);
+        assert!(
+            app.message
+                .as_ref()
+                .unwrap()
+                .starts_with("Cannot split here")
+        );
+
+        app.cursor_line = 2;
+        app.split_current_hunk();
+        let hunks = &app.files[0].hunks;
+        assert_eq!(hunks.len(), 3);
+        assert_eq!((hunks[0].old_start, hunks[0].old_lines), (1, 2));
+        assert_eq!((hunks[0].new_start, hunks[0].new_lines), (1, 1));
+        assert_eq!((hunks[1].old_start, hunks[1].old_lines), (3, 1));
+        assert_eq!((hunks[1].new_start, hunks[1].new_lines), (2, 2));
+        assert_eq!((app.selected_hunk, app.line_cursor()), (1, Some(0)));
+        assert_eq!(app.marked, [(0, 0), (0, 1)].into());
     }
 
     #[test]
-    fn test_g_then_capital_g_clears_pending_and_scrolls_bottom() {
-        let mut app = App::new(make_test_files(), false);
-        app.mode = AppMode::Browsing;
-        app.pending_key = Some('g');
-        // When event loop sees pending_key == Some('g') and next key is 'G':
-        // it clears pending and falls through to match G → scroll_to_bottom
-        app.pending_key = None;
-        app.message = None;
-        app.scroll_to_bottom();
-        assert!(app.scroll_offset > 0);
-    }
+    fn test_verdict_asked_before_quitting_with_feedback() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.ask_verdict = true;
+        // Nothing to give a verdict on
+        assert!(app.finish_review());
+
+        app.add_line_comment(0, "why?");
+        assert!(!app.request_quit());
+        assert!(!app.handle_confirm_quit_key(KeyCode::Char('y')));
+        assert_eq!(app.mode, AppMode::VerdictPrompt);
+        assert!(!app.handle_verdict_key(KeyCode::Char('x')));
+        assert!(!app.handle_verdict_key(KeyCode::Esc));
+        assert_eq!(app.mode, AppMode::Browsing);
 
-    // --- help overlay mode tests ---
+        assert!(!app.finish_review());
+        assert!(app.handle_verdict_key(KeyCode::Char('r')));
+        let verdict = app.verdict.clone().unwrap();
+        assert_eq!(verdict.verdict, Verdict::RequestChanges);
+
+        let summary = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(summary.path(), "# Verdict\n\nPlease add tests.\n\n").unwrap();
+        app.finish_summary(summary.path());
+        assert_eq!(
+            app.verdict.as_ref().unwrap().summary.as_deref(),
+            Some("Please add tests.")
+        );
+        // Asked once
+        assert!(app.finish_review());
+    }
 
     #[test]
-    fn test_initial_mode_is_help_on_first_run() {
-        let app = App::new_with_help(make_test_files(), false, true);
-        assert_eq!(app.mode, AppMode::Help);
+    fn test_hook_verdict_asked_without_feedback() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.ask_verdict = true;
+        app.require_verdict = true;
+        assert!(app.feedback.is_empty());
+        assert!(!app.finish_review());
+        assert_eq!(app.mode, AppMode::VerdictPrompt);
+        assert!(app.handle_verdict_key(KeyCode::Char('r')));
+        assert_eq!(
+            app.verdict.as_ref().map(|v| v.verdict),
+            Some(Verdict::RequestChanges)
+        );
+        assert!(app.finish_review());
     }
 
     #[test]
-    fn test_initial_mode_is_browsing_on_subsequent_run() {
-        let app = App::new_with_help(make_test_files(), false, false);
+    fn test_commit_comments_from_the_commits_panel() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.open_commits_panel();
+        assert_eq!(app.mode, AppMode::Browsing, "no commits outside --range");
+
+        let commit = |id: &str, summary: &str| RangeCommit {
+            id: git2::Oid::from_str(id).unwrap(),
+            summary: summary.to_string(),
+        };
+        app.commits = vec![
+            commit("1a2b3c4d5e6f", "Add the parser"),
+            commit("5d6e7f8a9b0c", "Fix typo"),
+        ];
+        app.open_commits_panel();
+        assert_eq!(app.mode, AppMode::CommitsPanel);
+        app.handle_commits_panel_key(KeyCode::Char('j'));
+        app.handle_commits_panel_key(KeyCode::Char('j'));
+        assert_eq!(app.commit_cursor, 1);
+        app.handle_commits_panel_key(KeyCode::Esc);
         assert_eq!(app.mode, AppMode::Browsing);
+
+        let target = NoteTarget::Commit(app.commits[1].label());
+        let note = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(note.path(), "!nit Squash it\n").unwrap();
+        assert!(app.finish_note(note.path(), &target));
+        std::fs::write(note.path(), "Squash it into the parser\n").unwrap();
+        assert!(app.finish_note(note.path(), &target));
+        std::fs::write(note.path(), "# only instructions\n").unwrap();
+        assert!(!app.finish_note(note.path(), &target));
+
+        assert_eq!(app.feedback.len(), 1, "replaces the commit's comment");
+        assert_eq!(app.feedback[0].kind, FeedbackKind::CommitComment);
+        assert_eq!(app.feedback[0].hunk_header, "5d6e7f8 Fix typo");
+        assert_eq!(app.feedback[0].content, "Squash it into the parser");
     }
 
     #[test]
-    fn test_help_mode_any_key_dismisses() {
-        let mut app = App::new_with_help(make_test_files(), false, true);
-        assert_eq!(app.mode, AppMode::Help);
-        // Simulate: any key in Help mode switches to Browsing
-        app.mode = AppMode::Browsing;
+    fn test_file_comment_replaces_previous() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        let note = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(note.path(), "# Comment on src/b.rs\n\nRename it\n").unwrap();
+        assert!(app.finish_note(note.path(), &NoteTarget::File("src/b.rs".to_string())));
+        std::fs::write(note.path(), "!nit Rename it to c.rs\n").unwrap();
+        assert!(app.finish_note(note.path(), &NoteTarget::File("src/b.rs".to_string())));
+        std::fs::write(note.path(), "# only instructions\n").unwrap();
+        assert!(!app.finish_note(note.path(), &NoteTarget::File("src/b.rs".to_string())));
+
+        assert_eq!(app.feedback.len(), 1);
+        assert_eq!(app.feedback[0].kind, FeedbackKind::FileComment);
+        assert_eq!(app.feedback[0].content, "Rename it to c.rs");
         assert_eq!(app.mode, AppMode::Browsing);
     }
 
     #[test]
-    fn test_help_mode_key_not_processed_as_action() {
-        let mut app = App::new_with_help(make_test_files(), false, true);
-        assert_eq!(app.mode, AppMode::Help);
-        // Pressing 'y' in Help mode should dismiss help, NOT stage a hunk
-        app.mode = AppMode::Browsing; // This is what the event loop does
-        // Hunk status should remain Pending (not Staged)
-        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
+    fn test_review_comment_is_kept_apart_from_file_comments() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.files[0].hunks[0].status = HunkStatus::Staged;
+        assert_eq!(
+            app.reviewed_files_summary(),
+            vec!["src/a.rs: 1 staged, 1 pending", "src/b.rs: 1 pending"]
+        );
+
+        let note = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(note.path(), "Looks good overall\n").unwrap();
+        assert!(app.finish_note(note.path(), &NoteTarget::File("src/b.rs".to_string())));
+        assert!(app.finish_note(note.path(), &NoteTarget::Review));
+        std::fs::write(note.path(), "Looks good, one nit\n").unwrap();
+        assert!(app.finish_note(note.path(), &NoteTarget::Review));
+
+        assert_eq!(app.feedback.len(), 2);
+        assert_eq!(app.feedback[1].kind, FeedbackKind::ReviewComment);
+        assert_eq!(app.feedback[1].content, "Looks good, one nit");
+        assert!(app.feedback[1].file_path.is_empty());
     }
 
     #[test]
-    fn test_question_mark_toggles_help() {
+    fn test_toggle_view_mode() {
         let mut app = App::new_with_help(make_test_files(), false, false);
-        assert_eq!(app.mode, AppMode::Browsing);
-        // Pressing '?' in Browsing mode switches to Help
-        app.mode = AppMode::Help;
-        assert_eq!(app.mode, AppMode::Help);
+        assert_eq!(app.view_mode, ViewMode::Unified);
+        app.dirty = false;
+        app.toggle_view_mode();
+        assert_eq!(app.view_mode, ViewMode::SideBySide);
+        assert!(app.dirty);
+        app.toggle_view_mode();
+        assert_eq!(app.view_mode, ViewMode::Unified);
     }
 
     #[test]
-    fn test_question_mark_from_help_dismisses() {
-        let mut app = App::new_with_help(make_test_files(), false, true);
-        app.mode = AppMode::Help;
-        // Pressing '?' in Help mode switches back to Browsing
-        app.mode = AppMode::Browsing;
-        assert_eq!(app.mode, AppMode::Browsing);
+    fn test_side_by_side_content_lines() {
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        app.view_mode = ViewMode::SideBySide;
+        // hunk0: header + 3 rows (removed/added paired) + sep = 5
+        // hunk1: header + 1 row = 2
+        assert_eq!(app.total_content_lines(), 7);
+
+        app.select_next_hunk();
+        assert_eq!(app.scroll_offset, 5);
     }
 
-    // --- dirty flag for new methods ---
+    fn orphaned_comment(dir: &Path, hunk: &Hunk, comment: &str) -> EditorSession {
+        let original =
+            std::fs::read_to_string(editor::prepare_comment_tempfile(hunk).unwrap().path())
+                .unwrap();
+        let tempfile = dir.join("stagent-comment-orphan.tmp");
+        std::fs::write(&tempfile, format!("{}{}\n", original, comment)).unwrap();
+        let session = EditorSession {
+            tempfile,
+            workdir: None,
+            pid: 999_999_999,
+            is_comment: true,
+            file_path: "src/a.rs".to_string(),
+            hunk_header: hunk.header.clone(),
+            hunk_lines: hunk.lines.to_vec(),
+            original_content: original,
+        };
+        session.save().unwrap();
+        session
+    }
 
     #[test]
-    fn test_dirty_flag_new_methods() {
-        let mut app = App::new(make_test_files(), false);
-        app.diff_view_area = Rect::new(0, 0, 80, 20);
-
-        app.dirty = false;
-        app.scroll_to_top();
-        assert!(app.dirty, "dirty should be true after scroll_to_top");
+    fn test_accept_recovery_adds_feedback() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        let hunk = app.files[0].hunks[1].clone();
+        let session = orphaned_comment(dir.path(), &hunk, "lost comment");
 
-        app.dirty = false;
-        app.scroll_to_bottom();
-        assert!(app.dirty, "dirty should be true after scroll_to_bottom");
+        app.offer_recovery(vec![session.clone()]);
+        assert_eq!(app.mode, AppMode::RecoveryPrompt);
+        assert!(app.message.as_ref().unwrap().contains("1 comment(s)"));
 
-        app.dirty = false;
-        app.scroll_half_page_down();
-        assert!(
-            app.dirty,
-            "dirty should be true after scroll_half_page_down"
-        );
+        app.accept_recovery();
+        assert_eq!(app.mode, AppMode::Browsing);
+        assert_eq!(app.feedback.len(), 1);
+        assert_eq!(app.feedback[0].content, "lost comment");
+        assert_eq!(app.files[0].hunks[1].status, HunkStatus::Commented);
+        assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
+        assert!(!session.tempfile.exists());
+        assert!(!session.sidecar_path().exists());
+    }
 
-        app.dirty = false;
-        app.scroll_half_page_up();
-        assert!(app.dirty, "dirty should be true after scroll_half_page_up");
+    #[test]
+    fn test_recovery_prompt_waits_for_resume_prompt() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = App::new_with_help(make_test_files(), false, false);
+        let hunk = app.files[0].hunks[0].clone();
+        let session = orphaned_comment(dir.path(), &hunk, "lost comment");
 
-        app.dirty = false;
-        app.scroll_full_page_down();
-        assert!(
-            app.dirty,
-            "dirty should be true after scroll_full_page_down"
-        );
+        app.offer_resume(Session::capture(&app, String::new()));
+        app.offer_recovery(vec![session.clone()]);
+        assert_eq!(app.mode, AppMode::ResumePrompt);
 
-        app.dirty = false;
-        app.scroll_full_page_up();
-        assert!(app.dirty, "dirty should be true after scroll_full_page_up");
+        app.decline_resume();
+        assert_eq!(app.mode, AppMode::RecoveryPrompt);
+        app.decline_recovery();
+        assert_eq!(app.mode, AppMode::Browsing);
+        assert!(app.feedback.is_empty());
+        assert!(!session.tempfile.exists(), "declined content is deleted");
     }
 }
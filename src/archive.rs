@@ -0,0 +1,166 @@
+//! Archive of feedback from past sessions under `.git/stagent/feedback/`.
+//!
+//! Feedback written only to stdout is lost once the pane closes (notably in
+//! spawn mode), so sessions without `--output` also keep a timestamped copy
+//! here. `stagent feedback list` / `show` browse the archive.
+
+use anyhow::{Context, Result, bail};
+use git2::Repository;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::notes::format_timestamp;
+
+/// Extension of archived feedback files.
+const ARCHIVE_EXT: &str = "diff";
+
+/// A feedback file in the archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedFeedback {
+    /// Identifier used by `stagent feedback show` (the file stem).
+    pub id: String,
+    pub path: PathBuf,
+    /// Number of files the feedback covers.
+    pub files: usize,
+    /// Number of hunks with feedback.
+    pub hunks: usize,
+}
+
+/// Archive directory for a repository (`.git/stagent/feedback`).
+pub fn archive_dir(repo: &Repository) -> PathBuf {
+    repo.path().join("stagent").join("feedback")
+}
+
+/// Archive id for a Unix timestamp: compact ISO 8601, e.g. `20261018T093000Z`.
+/// Ids sort chronologically.
+pub fn archive_id(secs: u64) -> String {
+    format_timestamp(secs).replace(['-', ':'], "")
+}
+
+/// Write `output` to a new timestamped file in `dir`. Returns its path.
+pub fn archive_feedback(dir: &Path, output: &str) -> Result<PathBuf> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    archive_feedback_at(dir, output, secs)
+}
+
+/// Like [`archive_feedback`], with an explicit timestamp. Sessions ending in
+/// the same second get a numeric suffix instead of overwriting each other.
+pub fn archive_feedback_at(dir: &Path, output: &str, secs: u64) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let base = archive_id(secs);
+    let mut path = dir.join(format!("{}.{}", base, ARCHIVE_EXT));
+    let mut n = 1;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.{}", base, n, ARCHIVE_EXT));
+        n += 1;
+    }
+
+    std::fs::write(&path, output)
+        .with_context(|| format!("Failed to write archived feedback: {}", path.display()))?;
+    Ok(path)
+}
+
+/// List archived feedback, newest first. A missing directory is an empty list.
+pub fn list_archived(dir: &Path) -> Result<Vec<ArchivedFeedback>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+    };
+
+    let mut archived: Vec<ArchivedFeedback> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == ARCHIVE_EXT))
+        .filter_map(|path| {
+            let id = path.file_stem()?.to_string_lossy().to_string();
+            let content = std::fs::read_to_string(&path).unwrap_or_default();
+            Some(ArchivedFeedback {
+                id,
+                files: content.lines().filter(|l| l.starts_with("+++ b/")).count(),
+                hunks: content.lines().filter(|l| l.starts_with("@@")).count(),
+                path,
+            })
+        })
+        .collect();
+    archived.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(archived)
+}
+
+/// Find an archived feedback file by id, unique id prefix, or `latest`.
+pub fn find_archived(dir: &Path, query: &str) -> Result<ArchivedFeedback> {
+    let archived = list_archived(dir)?;
+    if query == "latest" {
+        return archived
+            .into_iter()
+            .next()
+            .context("No archived feedback found");
+    }
+    if let Some(exact) = archived.iter().find(|a| a.id == query) {
+        return Ok(exact.clone());
+    }
+
+    let mut matches = archived.into_iter().filter(|a| a.id.starts_with(query));
+    match (matches.next(), matches.next()) {
+        (Some(found), None) => Ok(found),
+        (Some(_), Some(_)) => bail!("Ambiguous feedback id '{}'", query),
+        (None, _) => bail!("No archived feedback matching '{}'", query),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1 +1 @@\n-x\n+y\n@@ -9 +9 @@\n-p\n+q\n--- a/b.rs\n+++ b/b.rs\n@@ -1 +1 @@\n-a\n+b\n";
+
+    #[test]
+    fn test_archive_id_sorts_chronologically() {
+        assert_eq!(archive_id(0), "19700101T000000Z");
+        assert!(archive_id(1_000_000_000) > archive_id(999_999_999));
+    }
+
+    #[test]
+    fn test_archive_and_list() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let archive = dir.path().join("feedback");
+        assert!(list_archived(&archive).unwrap().is_empty());
+
+        archive_feedback_at(&archive, SAMPLE, 100).unwrap();
+        archive_feedback_at(&archive, "--- a/x\n+++ b/x\n@@ -1 +1 @@\n", 200).unwrap();
+
+        let listed = list_archived(&archive).unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].id, archive_id(200), "newest first");
+        assert_eq!((listed[1].files, listed[1].hunks), (2, 3));
+    }
+
+    #[test]
+    fn test_archive_same_second_does_not_overwrite() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let first = archive_feedback_at(dir.path(), "one", 100).unwrap();
+        let second = archive_feedback_at(dir.path(), "two", 100).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read_to_string(first).unwrap(), "one");
+        assert_eq!(std::fs::read_to_string(second).unwrap(), "two");
+    }
+
+    #[test]
+    fn test_find_archived() {
+        let dir = tempfile::TempDir::new().unwrap();
+        archive_feedback_at(dir.path(), SAMPLE, 100).unwrap();
+        archive_feedback_at(dir.path(), SAMPLE, 86_400 * 400).unwrap();
+
+        let latest = find_archived(dir.path(), "latest").unwrap();
+        assert_eq!(latest.id, archive_id(86_400 * 400));
+        assert_eq!(
+            find_archived(dir.path(), "1970").unwrap().id,
+            archive_id(100)
+        );
+        assert!(find_archived(dir.path(), "19").is_err(), "ambiguous prefix");
+        assert!(find_archived(dir.path(), "2099").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+//! `git blame` for the old side of hunks, shown by the `b` panel.
+//!
+//! Files are blamed as a whole on first use and cached by the app. The
+//! blame is taken at `HEAD` and then moved onto the index version of the
+//! file, which is the old side of the unstaged diff, so lines changed in
+//! the index but not committed show as uncommitted.
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::types::{Hunk, LineKind};
+
+/// The commit that last changed a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlameCommit {
+    /// Abbreviated commit id, empty for lines not committed yet.
+    pub id: String,
+    pub author: String,
+    /// Commit time, in seconds since the epoch.
+    pub time: i64,
+    /// First line of the commit message.
+    pub summary: String,
+}
+
+impl BlameCommit {
+    fn uncommitted() -> Self {
+        Self {
+            id: String::new(),
+            author: String::new(),
+            time: 0,
+            summary: "Not committed yet".to_string(),
+        }
+    }
+
+    pub fn is_committed(&self) -> bool {
+        !self.id.is_empty()
+    }
+}
+
+/// Blame of every line of a file's index version.
+#[derive(Debug, Clone, Default)]
+pub struct FileBlame {
+    commits: Vec<BlameCommit>,
+    /// Index into `commits` per line, from line 1.
+    lines: Vec<usize>,
+}
+
+impl FileBlame {
+    /// The commit that last changed line `lineno` (1-based).
+    pub fn line(&self, lineno: u32) -> Option<&BlameCommit> {
+        let idx = *self.lines.get((lineno as usize).checked_sub(1)?)?;
+        self.commits.get(idx)
+    }
+
+    /// The old lines of `hunk` grouped into runs of consecutive lines
+    /// last changed by the same commit.
+    pub fn hunk_ranges(&self, hunk: &Hunk) -> Vec<(RangeInclusive<u32>, &BlameCommit)> {
+        let mut ranges: Vec<(RangeInclusive<u32>, &BlameCommit)> = Vec::new();
+        let old_lines = hunk
+            .lines
+            .iter()
+            .filter(|l| l.kind != LineKind::Added)
+            .filter_map(|l| l.old_lineno);
+        for lineno in old_lines {
+            let Some(commit) = self.line(lineno) else {
+                continue;
+            };
+            if let Some((range, last)) = ranges.last_mut()
+                && *range.end() + 1 == lineno
+                && std::ptr::eq(*last, commit)
+            {
+                *range = *range.start()..=lineno;
+                continue;
+            }
+            ranges.push((lineno..=lineno, commit));
+        }
+        ranges
+    }
+
+    /// The distinct commits that last changed `hunk`'s old lines, newest
+    /// first, with lines not committed yet leading.
+    pub fn hunk_commits(&self, hunk: &Hunk) -> Vec<HunkCommit<'_>> {
+        let mut commits: Vec<HunkCommit> = Vec::new();
+        for (range, commit) in self.hunk_ranges(hunk) {
+            match commits.iter_mut().find(|c| std::ptr::eq(c.commit, commit)) {
+                Some(c) => c.lines.push(range),
+                None => commits.push(HunkCommit {
+                    commit,
+                    lines: vec![range],
+                }),
+            }
+        }
+        commits.sort_by_key(|c| (c.commit.is_committed(), std::cmp::Reverse(c.commit.time)));
+        commits
+    }
+}
+
+/// Commits younger than this are highlighted as recent work in the hunk
+/// details.
+pub const RECENT_SECS: i64 = 14 * 24 * 3600;
+
+/// A commit that last changed some of a hunk's old lines, and which.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HunkCommit<'a> {
+    pub commit: &'a BlameCommit,
+    pub lines: Vec<RangeInclusive<u32>>,
+}
+
+/// Line ranges for people: `3`, `10-14, 20`.
+pub fn format_ranges(ranges: &[RangeInclusive<u32>]) -> String {
+    ranges
+        .iter()
+        .map(|range| {
+            if range.start() == range.end() {
+                range.start().to_string()
+            } else {
+                format!("{}-{}", range.start(), range.end())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Blame the index version of `path`, or its `HEAD` version if it is not
+/// in the index.
+pub fn blame_file(repo: &Repository, path: &Path) -> Result<FileBlame> {
+    let blame = repo
+        .blame_file(path, None)
+        .with_context(|| format!("Failed to blame {}", path.display()))?;
+    let index = repo.index().context("Failed to read the index")?;
+    let staged = index
+        .get_path(path, 0)
+        .and_then(|entry| repo.find_blob(entry.id).ok());
+    let blame = match staged {
+        Some(blob) => blame
+            .blame_buffer(blob.content())
+            .with_context(|| format!("Failed to blame the index version of {}", path.display()))?,
+        None => blame,
+    };
+
+    let mut file = FileBlame::default();
+    let mut by_commit: HashMap<Oid, usize> = HashMap::new();
+    for hunk in blame.iter() {
+        let oid = hunk.final_commit_id();
+        let idx = *by_commit.entry(oid).or_insert_with(|| {
+            file.commits.push(describe(repo, oid));
+            file.commits.len() - 1
+        });
+        let start = hunk.final_start_line();
+        // Blame hunks are in line order; fill any gap as uncommitted
+        while file.lines.len() + 1 < start {
+            let uncommitted = *by_commit.entry(Oid::zero()).or_insert_with(|| {
+                file.commits.push(BlameCommit::uncommitted());
+                file.commits.len() - 1
+            });
+            file.lines.push(uncommitted);
+        }
+        file.lines
+            .extend(std::iter::repeat_n(idx, hunk.lines_in_hunk()));
+    }
+    Ok(file)
+}
+
+fn describe(repo: &Repository, oid: Oid) -> BlameCommit {
+    if oid.is_zero() {
+        return BlameCommit::uncommitted();
+    }
+    match repo.find_commit(oid) {
+        Ok(commit) => BlameCommit {
+            id: short_id(oid),
+            author: commit.author().name().unwrap_or("unknown").to_string(),
+            time: commit.time().seconds(),
+            summary: commit.summary().unwrap_or_default().to_string(),
+        },
+        Err(_) => BlameCommit {
+            id: short_id(oid),
+            author: String::new(),
+            time: 0,
+            summary: String::new(),
+        },
+    }
+}
+
+fn short_id(oid: Oid) -> String {
+    oid.to_string()[..7].to_string()
+}
+
+/// How long ago `time` was at `now` (both in seconds since the epoch), in
+/// the largest whole unit: `5 minutes ago`, `3 days ago`, `2 years ago`.
+pub fn format_age(time: i64, now: i64) -> String {
+    const UNITS: &[(i64, &str)] = &[
+        (365 * 24 * 3600, "year"),
+        (30 * 24 * 3600, "month"),
+        (7 * 24 * 3600, "week"),
+        (24 * 3600, "day"),
+        (3600, "hour"),
+        (60, "minute"),
+    ];
+    let elapsed = (now - time).max(0);
+    for &(secs, unit) in UNITS {
+        let n = elapsed / secs;
+        if n > 0 {
+            return format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" });
+        }
+    }
+    "just now".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_age() {
+        let now = 1_000_000_000;
+        assert_eq!(format_age(now - 30, now), "just now");
+        assert_eq!(format_age(now - 60, now), "1 minute ago");
+        assert_eq!(format_age(now - 3 * 24 * 3600, now), "3 days ago");
+        assert_eq!(format_age(now - 800 * 24 * 3600, now), "2 years ago");
+        // Clock skew: a commit from the future
+        assert_eq!(format_age(now + 100, now), "just now");
+    }
+
+    #[test]
+    fn test_format_ranges() {
+        assert_eq!(format_ranges(&[3..=3]), "3");
+        assert_eq!(format_ranges(&[10..=14, 20..=20]), "10-14, 20");
+    }
+}
@@ -0,0 +1,115 @@
+//! Bulk selection of hunks by content, for `:stage-matching`.
+//!
+//! Mechanical changes such as renaming a function across many files are
+//! quicker to stage by pattern than hunk by hunk. A hunk matches when any
+//! of its added or removed lines matches the regex; context lines don't
+//! count, so a hunk that merely sits next to the pattern is left alone.
+
+use anyhow::{Context, Result};
+use regex_automata::meta::Regex;
+
+use crate::types::{FileDiff, Hunk, HunkStatus, LineKind};
+
+/// A regex matched against the changed lines of hunks.
+#[derive(Debug, Clone)]
+pub struct HunkMatcher {
+    pattern: String,
+    regex: Regex,
+}
+
+impl HunkMatcher {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).with_context(|| format!("Invalid regex '{}'", pattern))?;
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether any added or removed line of `hunk` matches.
+    ///
+    /// ```
+    /// use stagent::bulk::HunkMatcher;
+    /// use stagent::patch::parse_unified_diff;
+    ///
+    /// let files = parse_unified_diff(
+    ///     "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n\
+    ///      @@ -1,2 +1,2 @@\n fn main() {\n-    old_name();\n+    new_name();\n",
+    /// )?;
+    /// let hunk = &files[0].hunks[0];
+    /// assert!(HunkMatcher::new(r"new_\w+\(")?.matches(hunk));
+    /// assert!(!HunkMatcher::new("fn main")?.matches(hunk), "context lines don't count");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn matches(&self, hunk: &Hunk) -> bool {
+        hunk.lines
+            .iter()
+            .filter(|l| l.kind != LineKind::Context)
+            .any(|l| self.regex.is_match(l.content.trim_end_matches('\n')))
+    }
+}
+
+/// Pending hunks of `files` that `matcher` matches, as (file, hunk)
+/// indices in order.
+pub fn matching_hunks(files: &[FileDiff], matcher: &HunkMatcher) -> Vec<(usize, usize)> {
+    files
+        .iter()
+        .enumerate()
+        .flat_map(|(fi, file)| {
+            file.hunks
+                .iter()
+                .enumerate()
+                .filter(|(_, h)| h.status == HunkStatus::Pending && matcher.matches(h))
+                .map(move |(hi, _)| (fi, hi))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::parse_unified_diff;
+
+    #[test]
+    fn test_matching_hunks_skips_reviewed_and_unrelated() {
+        let mut files = parse_unified_diff(
+            "\
+diff --git a/a.rs b/a.rs
+--- a/a.rs
++++ b/a.rs
+@@ -1,1 +1,1 @@
+-old_name();
++new_name();
+@@ -10,1 +10,1 @@
+-let x = 1;
++let x = 2;
+diff --git a/b.rs b/b.rs
+--- a/b.rs
++++ b/b.rs
+@@ -3,1 +3,1 @@
+-use old_name;
++use new_name;
+",
+        )
+        .unwrap();
+        let matcher = HunkMatcher::new("new_name").unwrap();
+        assert_eq!(matching_hunks(&files, &matcher), vec![(0, 0), (1, 0)]);
+
+        files[1].hunks[0].status = HunkStatus::Skipped;
+        assert_eq!(matching_hunks(&files, &matcher), vec![(0, 0)]);
+
+        // Anchors apply to the line without its newline
+        let matcher = HunkMatcher::new(r"^let x = \d;$").unwrap();
+        assert_eq!(matching_hunks(&files, &matcher), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_invalid_regex_is_an_error() {
+        let err = HunkMatcher::new("foo(").unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid regex 'foo('"));
+    }
+}
@@ -0,0 +1,205 @@
+//! Extra context lines around a hunk, read on demand from the file itself.
+//!
+//! Diffs only carry a few lines of context. When reviewing, it is often
+//! necessary to see more of the surrounding code; this module reads the file
+//! (working tree first, falling back to the index blob for deleted files)
+//! and slices out the lines just before and after a hunk.
+
+use git2::Repository;
+use std::path::Path;
+
+use crate::types::{DiffLine, Hunk, LineKind};
+
+/// Number of lines added or removed by one expand/collapse step.
+pub const CONTEXT_STEP: usize = 5;
+
+/// Which version of the file the source lines come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// Working tree content (numbered by `new_lineno`).
+    New,
+    /// Index content, used when the working tree file is gone.
+    Old,
+}
+
+/// Context lines shown before and after a hunk, rendered as context.
+#[derive(Debug, Clone, Default)]
+pub struct ExpandedContext {
+    pub before: Vec<DiffLine>,
+    pub after: Vec<DiffLine>,
+}
+
+impl ExpandedContext {
+    /// Total number of extra lines.
+    pub fn len(&self) -> usize {
+        self.before.len() + self.after.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Read the lines of `path` for context expansion.
+///
+/// With a repository, `path` is relative to its working directory and the
+/// index blob is used as a fallback. Without one (patch mode), `path` is read
+/// relative to the current directory.
+pub fn load_file_lines(repo: Option<&Repository>, path: &Path) -> Option<(Vec<String>, Side)> {
+    let full_path = match repo.and_then(|r| r.workdir()) {
+        Some(workdir) => workdir.join(path),
+        None => path.to_path_buf(),
+    };
+    // Context is only displayed, so text that isn't UTF-8 is shown lossily
+    if let Ok(content) = std::fs::read(&full_path) {
+        return Some((split_lines(&String::from_utf8_lossy(&content)), Side::New));
+    }
+
+    let repo = repo?;
+    let index = repo.index().ok()?;
+    let entry = index.get_path(path, 0)?;
+    let blob = repo.find_blob(entry.id).ok()?;
+    let content = String::from_utf8_lossy(blob.content());
+    Some((split_lines(&content), Side::Old))
+}
+
+fn split_lines(content: &str) -> Vec<String> {
+    content.lines().map(|l| format!("{}\n", l)).collect()
+}
+
+/// Slice up to `extra` lines before and after `hunk` out of `source`.
+///
+/// Expansion stops at the file boundaries and at the neighbouring hunks
+/// (`prev` / `next`) so no line is shown twice.
+pub fn expand(
+    source: &[String],
+    side: Side,
+    hunk: &Hunk,
+    prev: Option<&Hunk>,
+    next: Option<&Hunk>,
+    extra: usize,
+) -> ExpandedContext {
+    // Work in the numbering of the side we read, converting to the other
+    // side with the hunk's start offset.
+    let (start, count) = match side {
+        Side::New => (hunk.new_start, hunk.new_lines),
+        Side::Old => (hunk.old_start, hunk.old_lines),
+    };
+    // An empty range ("+5,0") sits *after* its start line
+    let start = if count == 0 { start + 1 } else { start };
+    let span_end = |h: &Hunk| match side {
+        Side::New => h.new_start + h.new_lines,
+        Side::Old => h.old_start + h.old_lines,
+    };
+    let span_start = |h: &Hunk| match side {
+        Side::New => h.new_start,
+        Side::Old => h.old_start,
+    };
+    let delta = hunk.old_start as i64 - hunk.new_start as i64;
+
+    let make_line = |lineno: u32| -> DiffLine {
+        let (old, new) = match side {
+            Side::New => (lineno as i64 + delta, lineno as i64),
+            Side::Old => (lineno as i64, lineno as i64 - delta),
+        };
+        DiffLine {
+            kind: LineKind::Context,
+            content: source[lineno as usize - 1].clone(),
+            old_lineno: (old > 0).then_some(old as u32),
+            new_lineno: (new > 0).then_some(new as u32),
+            raw: None,
+            no_newline: false,
+        }
+    };
+
+    // Lines are 1-based; `start` is the first line of the hunk.
+    let lower = prev.map(span_end).unwrap_or(1).max(1);
+    let first = (start as usize).saturating_sub(extra).max(lower as usize);
+    let before = (first..start as usize)
+        .filter(|n| *n >= 1 && *n <= source.len())
+        .map(|n| make_line(n as u32))
+        .collect();
+
+    let after_start = start + count;
+    let upper = next
+        .map(|h| span_start(h) as usize)
+        .unwrap_or(source.len() + 1)
+        .min(source.len() + 1);
+    let last = (after_start as usize + extra).min(upper);
+    let after = (after_start as usize..last)
+        .filter(|n| *n >= 1)
+        .map(|n| make_line(n as u32))
+        .collect();
+
+    ExpandedContext { before, after }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::HunkStatus;
+
+    fn source(n: usize) -> Vec<String> {
+        (1..=n).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    fn hunk(old_start: u32, old_lines: u32, new_start: u32, new_lines: u32) -> Hunk {
+        Hunk {
+            header: format!(
+                "@@ -{},{} +{},{} @@",
+                old_start, old_lines, new_start, new_lines
+            ),
+            lines: vec![].into(),
+            status: HunkStatus::Pending,
+            old_start,
+            old_lines,
+            new_start,
+            new_lines,
+        }
+    }
+
+    #[test]
+    fn test_expand_before_and_after() {
+        let src = source(30);
+        // New side lines 10..=12, old side shifted by 2
+        let h = hunk(12, 3, 10, 3);
+        let ctx = expand(&src, Side::New, &h, None, None, 3);
+
+        let before: Vec<_> = ctx.before.iter().map(|l| l.new_lineno).collect();
+        assert_eq!(before, vec![Some(7), Some(8), Some(9)]);
+        assert_eq!(ctx.before[0].old_lineno, Some(9));
+        assert_eq!(ctx.before[0].content, "line 7\n");
+
+        let after: Vec<_> = ctx.after.iter().map(|l| l.new_lineno).collect();
+        assert_eq!(after, vec![Some(13), Some(14), Some(15)]);
+        assert!(ctx.after.iter().all(|l| l.kind == LineKind::Context));
+    }
+
+    #[test]
+    fn test_expand_clamps_to_file_bounds() {
+        let src = source(5);
+        let h = hunk(2, 2, 2, 2);
+        let ctx = expand(&src, Side::New, &h, None, None, 10);
+        assert_eq!(ctx.before.len(), 1);
+        assert_eq!(ctx.after.len(), 2);
+        assert_eq!(ctx.after.last().unwrap().content, "line 5\n");
+    }
+
+    #[test]
+    fn test_expand_stops_at_neighbouring_hunks() {
+        let src = source(40);
+        let prev = hunk(1, 5, 1, 5);
+        let h = hunk(10, 3, 10, 3);
+        let next = hunk(16, 3, 16, 3);
+        let ctx = expand(&src, Side::New, &h, Some(&prev), Some(&next), 10);
+        assert_eq!(ctx.before.first().unwrap().new_lineno, Some(6));
+        assert_eq!(ctx.after.last().unwrap().new_lineno, Some(15));
+    }
+
+    #[test]
+    fn test_expand_zero_is_empty() {
+        let src = source(20);
+        let h = hunk(10, 3, 10, 3);
+        assert!(expand(&src, Side::New, &h, None, None, 0).is_empty());
+    }
+}
@@ -1,7 +1,14 @@
 use anyhow::Result;
-use git2::Diff;
+use git2::{Diff, FileMode, Oid};
+use similar::{ChangeTag, TextDiff};
+use std::ops::RangeInclusive;
 
-use crate::types::{DeltaStatus, DiffLine, FileDiff, Hunk, HunkStatus, LineKind};
+use crate::types::{DeltaStatus, DiffLine, FileDiff, Hunk, HunkLines, HunkStatus, LineKind};
+
+/// Whether `id` is the empty blob, which `git add -N` puts in the index.
+fn is_empty_blob(id: Oid) -> bool {
+    Oid::hash_object(git2::ObjectType::Blob, b"").is_ok_and(|empty| empty == id)
+}
 
 /// Parse a git2 Diff into our structured FileDiff types.
 ///
@@ -9,8 +16,9 @@ use crate::types::{DeltaStatus, DiffLine, FileDiff, Hunk, HunkStatus, LineKind};
 /// which avoids the multiple mutable borrow issues of `diff.foreach()`.
 pub fn parse_diff(diff: &Diff) -> Result<Vec<FileDiff>> {
     let mut files: Vec<FileDiff> = Vec::new();
+    let mut patches = Vec::new();
 
-    for delta in diff.deltas() {
+    for (delta_idx, delta) in diff.deltas().enumerate() {
         let path = delta
             .new_file()
             .path()
@@ -23,26 +31,75 @@ pub fn parse_diff(diff: &Diff) -> Result<Vec<FileDiff>> {
             git2::Delta::Deleted => DeltaStatus::Deleted,
             git2::Delta::Renamed => DeltaStatus::Renamed,
             git2::Delta::Untracked => DeltaStatus::Untracked,
+            // Added with intent-to-add: the index holds an empty blob (so
+            // does an empty file gaining content, which reviews the same)
+            git2::Delta::Modified if is_empty_blob(delta.old_file().id()) => DeltaStatus::Added,
             _ => DeltaStatus::Modified,
         };
 
-        let is_binary = delta.flags().contains(git2::DiffFlags::BINARY);
+        let is_commit = |file: git2::DiffFile| file.mode() == FileMode::Commit;
+        if is_commit(delta.old_file()) || is_commit(delta.new_file()) {
+            let commit = |file: git2::DiffFile| Some(file.id()).filter(|id| !id.is_zero());
+            let (old, new) = (commit(delta.old_file()), commit(delta.new_file()));
+            // Changes inside the submodule's working tree leave its commit
+            // alone and can't be staged from here
+            if old != new {
+                files.push(FileDiff {
+                    path,
+                    hunks: vec![submodule_hunk(old, new)],
+                    status,
+                    is_binary: false,
+                    is_submodule: true,
+                    mode_change: None,
+                });
+                patches.push(None);
+            }
+            continue;
+        }
+
+        // The BINARY flag is only set once content has been examined, which
+        // loading the patch does
+        let patch = git2::Patch::from_diff(diff, delta_idx).ok().flatten();
+        let flags = patch.as_ref().map_or(delta.flags(), |p| p.delta().flags());
+        let is_binary = flags.contains(git2::DiffFlags::BINARY);
+        // Binary files are staged whole, mode included
+        let mode_change = Some((
+            u32::from(delta.old_file().mode()),
+            u32::from(delta.new_file().mode()),
+        ))
+        .filter(|&(old, new)| !is_binary && old != new && is_blob_mode(old) && is_blob_mode(new));
+        let hunks = if is_binary {
+            let (old, new) = patch
+                .as_ref()
+                .map_or((delta.old_file(), delta.new_file()), |p| {
+                    (p.delta().old_file(), p.delta().new_file())
+                });
+            vec![binary_hunk(Some((old.size(), new.size())))]
+        } else {
+            mode_change
+                .map(|(old, new)| mode_hunk(old, new))
+                .into_iter()
+                .collect()
+        };
 
         files.push(FileDiff {
             path,
-            hunks: Vec::new(),
+            hunks,
             status,
             is_binary,
+            is_submodule: false,
+            mode_change,
         });
+        patches.push(patch);
     }
 
     // Now parse hunks and lines using the patch API
-    for (file_idx, file) in files.iter_mut().enumerate() {
-        if file.is_binary {
+    for (file, patch) in files.iter_mut().zip(&patches) {
+        if file.is_whole_file() {
             continue;
         }
 
-        if let Ok(Some(patch)) = git2::Patch::from_diff(diff, file_idx) {
+        if let Some(patch) = patch {
             let num_hunks = patch.num_hunks();
 
             for hunk_idx in 0..num_hunks {
@@ -51,24 +108,43 @@ pub fn parse_diff(diff: &Diff) -> Result<Vec<FileDiff>> {
                     .trim_end()
                     .to_string();
 
-                let mut lines = Vec::new();
+                let mut lines: Vec<DiffLine> = Vec::new();
 
                 for line_idx in 0..num_lines {
                     match patch.line_in_hunk(hunk_idx, line_idx) {
                         Ok(line) => {
+                            // "\ No newline at end of file" after the line
+                            // before: for the old side ('>'), the new one
+                            // ('<') or both ('=')
+                            if matches!(line.origin(), '=' | '>' | '<') {
+                                if let Some(last) = lines.last_mut() {
+                                    last.mark_no_newline();
+                                }
+                                continue;
+                            }
                             let kind = match line.origin() {
                                 '+' => LineKind::Added,
                                 '-' => LineKind::Removed,
                                 _ => LineKind::Context,
                             };
 
-                            let content = String::from_utf8_lossy(line.content()).to_string();
+                            // Non-UTF8 lines keep their bytes for staging
+                            let bytes = line.content();
+                            let (content, raw) = match std::str::from_utf8(bytes) {
+                                Ok(text) => (text.to_string(), None),
+                                Err(_) => (
+                                    String::from_utf8_lossy(bytes).to_string(),
+                                    Some(bytes.to_vec()),
+                                ),
+                            };
 
                             lines.push(DiffLine {
                                 kind,
                                 content,
                                 old_lineno: line.old_lineno(),
                                 new_lineno: line.new_lineno(),
+                                raw,
+                                no_newline: false,
                             });
                         }
                         Err(e) => {
@@ -85,7 +161,7 @@ pub fn parse_diff(diff: &Diff) -> Result<Vec<FileDiff>> {
 
                 file.hunks.push(Hunk {
                     header,
-                    lines,
+                    lines: lines.into(),
                     status: HunkStatus::Pending,
                     old_start: hunk_header.old_start(),
                     old_lines: hunk_header.old_lines(),
@@ -99,9 +175,149 @@ pub fn parse_diff(diff: &Diff) -> Result<Vec<FileDiff>> {
     Ok(files)
 }
 
+/// Placeholder hunk standing in for a whole binary file, which has no lines
+/// and is accepted or skipped as a unit. `sizes` are the old and new sizes in
+/// bytes, when known.
+pub fn binary_hunk(sizes: Option<(u64, u64)>) -> Hunk {
+    let header = match sizes {
+        Some((old, new)) => format!("Binary file ({} → {} bytes)", old, new),
+        None => "Binary file".to_string(),
+    };
+    Hunk {
+        header,
+        lines: HunkLines::default(),
+        status: HunkStatus::Pending,
+        old_start: 0,
+        old_lines: 0,
+        new_start: 0,
+        new_lines: 0,
+    }
+}
+
+/// Start of the lines showing a submodule's commits, as in `git diff`.
+pub const SUBPROJECT_PREFIX: &str = "Subproject commit ";
+
+/// Placeholder hunk standing in for a submodule whose commit changed: a
+/// `Subproject commit <sha>` line for the old and for the new commit, as
+/// `git diff` shows them. `None` for the side of an added or removed
+/// submodule. The hunk is staged or skipped as a unit.
+pub fn submodule_hunk(old: Option<Oid>, new: Option<Oid>) -> Hunk {
+    let short = |id: Option<Oid>| match id {
+        Some(id) => id.to_string()[..7].to_string(),
+        None => "none".to_string(),
+    };
+    let line = |kind, id: Oid| DiffLine {
+        kind,
+        content: format!("{}{}\n", SUBPROJECT_PREFIX, id),
+        old_lineno: (kind == LineKind::Removed).then_some(1),
+        new_lineno: (kind == LineKind::Added).then_some(1),
+        raw: None,
+        no_newline: false,
+    };
+    Hunk {
+        header: format!("Submodule commit {} → {}", short(old), short(new)),
+        lines: old
+            .map(|id| line(LineKind::Removed, id))
+            .into_iter()
+            .chain(new.map(|id| line(LineKind::Added, id)))
+            .collect(),
+        status: HunkStatus::Pending,
+        old_start: old.map_or(0, |_| 1),
+        old_lines: old.map_or(0, |_| 1),
+        new_start: new.map_or(0, |_| 1),
+        new_lines: new.map_or(0, |_| 1),
+    }
+}
+
+/// The old and new commits shown by a submodule hunk.
+pub fn submodule_commits(hunk: &Hunk) -> (Option<Oid>, Option<Oid>) {
+    let commit = |kind| {
+        hunk.lines
+            .iter()
+            .filter(|l| l.kind == kind)
+            .find_map(|l| l.content.trim_end().strip_prefix(SUBPROJECT_PREFIX))
+            .and_then(|sha| Oid::from_str(sha).ok())
+    };
+    (commit(LineKind::Removed), commit(LineKind::Added))
+}
+
+/// Start of the header of a [`mode_hunk`].
+pub const MODE_HEADER_PREFIX: &str = "Mode change ";
+
+/// Whether `mode` is a regular or executable file.
+fn is_blob_mode(mode: u32) -> bool {
+    mode == u32::from(FileMode::Blob) || mode == u32::from(FileMode::BlobExecutable)
+}
+
+/// Placeholder hunk for a file whose executable bit flipped: `old mode` and
+/// `new mode` lines, as in `git diff`'s extended header. It is staged or
+/// skipped on its own, independently of the file's content hunks.
+pub fn mode_hunk(old: u32, new: u32) -> Hunk {
+    let line = |kind, content: String| DiffLine {
+        kind,
+        content,
+        old_lineno: None,
+        new_lineno: None,
+        raw: None,
+        no_newline: false,
+    };
+    Hunk {
+        header: format!("{}{:o} → {:o}", MODE_HEADER_PREFIX, old, new),
+        lines: vec![
+            line(LineKind::Removed, format!("old mode {:o}\n", old)),
+            line(LineKind::Added, format!("new mode {:o}\n", new)),
+        ]
+        .into(),
+        status: HunkStatus::Pending,
+        old_start: 0,
+        old_lines: 0,
+        new_start: 0,
+        new_lines: 0,
+    }
+}
+
+/// Whether `hunk` is a [`mode_hunk`].
+pub fn is_mode_hunk(hunk: &Hunk) -> bool {
+    hunk.header.starts_with(MODE_HEADER_PREFIX)
+}
+
+/// Whether `hunk` only changes whitespace: its removed and added lines are
+/// the same once all whitespace is dropped (reindenting, rewrapping, blank
+/// lines).
+///
+/// ```
+/// let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1 +1,2 @@\n-a b\n+a  b\n+\n";
+/// let hunk = &stagent::patch::parse_unified_diff(diff)?[0].hunks[0];
+/// assert!(stagent::diff::is_whitespace_only(hunk));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn is_whitespace_only(hunk: &Hunk) -> bool {
+    // Read packed, as the status bar checks every hunk on each draw
+    let text = |kind| {
+        hunk.lines
+            .contents()
+            .filter(move |&(k, _)| k == kind)
+            .flat_map(|(_, content)| content.chars())
+            .filter(|c| !c.is_whitespace())
+    };
+    hunk.lines
+        .contents()
+        .any(|(kind, _)| kind != LineKind::Context)
+        && text(LineKind::Removed).eq(text(LineKind::Added))
+}
+
 /// Split a hunk into smaller sub-hunks at context-only boundaries.
 /// Each sub-hunk must contain at least one added or removed line.
 /// If the hunk cannot be split (all changes are contiguous), returns a vec with the original hunk.
+///
+/// ```
+/// let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1,9 +1,9 @@\n-a\n+A\n 2\n 3\n 4\n 5\n 6\n 7\n 8\n-i\n+I\n";
+/// let files = stagent::patch::parse_unified_diff(diff)?;
+/// let parts = stagent::diff::split_hunk(&files[0].hunks[0]);
+/// assert_eq!(parts.len(), 2);
+/// assert!(parts[1].header.starts_with("@@ -6,4 +6,4 @@"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
 pub fn split_hunk(hunk: &Hunk) -> Vec<Hunk> {
     // Find regions of changes separated by context lines.
     // A "region" is a contiguous group of Added/Removed lines.
@@ -181,15 +397,21 @@ pub fn split_hunk(hunk: &Hunk) -> Vec<Hunk> {
             }
         }
 
-        // Calculate old_start from the first line's old_lineno, or estimate
-        let old_start = lines
+        // Start positions follow from the lines before the sub-hunk. A side
+        // with no lines starts at the line before, as git numbers pure
+        // insertions and deletions, so sub-hunks without context still
+        // stage at the right place.
+        let preceding = &hunk.lines[..clamped_before];
+        let old_before = preceding
             .iter()
-            .find_map(|l| l.old_lineno)
-            .unwrap_or(hunk.old_start);
-        let new_start = lines
+            .filter(|l| l.kind != LineKind::Added)
+            .count() as u32;
+        let new_before = preceding
             .iter()
-            .find_map(|l| l.new_lineno)
-            .unwrap_or(hunk.new_start);
+            .filter(|l| l.kind != LineKind::Removed)
+            .count() as u32;
+        let old_start = start_line(hunk.old_start, hunk.old_lines, old_before, old_count);
+        let new_start = start_line(hunk.new_start, hunk.new_lines, new_before, new_count);
 
         let header = format!(
             "@@ -{},{} +{},{} @@ split {}/{}",
@@ -203,7 +425,7 @@ pub fn split_hunk(hunk: &Hunk) -> Vec<Hunk> {
 
         sub_hunks.push(Hunk {
             header,
-            lines,
+            lines: lines.into(),
             status: HunkStatus::Pending,
             old_start,
             old_lines: old_count,
@@ -214,3 +436,521 @@ pub fn split_hunk(hunk: &Hunk) -> Vec<Hunk> {
 
     sub_hunks
 }
+
+/// Split a hunk in two before line `at`, for dividing it where
+/// [`split_hunk`] cannot, such as inside a run of changes. Both parts keep
+/// the lines they get, context included, and can be split again. Returns
+/// `None` unless both parts hold a change.
+///
+/// ```
+/// let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n-a\n-b\n+A\n+B\n";
+/// let hunk = &stagent::patch::parse_unified_diff(diff)?[0].hunks[0];
+/// let (first, second) = stagent::diff::split_hunk_at(hunk, 1).unwrap();
+/// assert!(first.header.starts_with("@@ -1,1 +0,0 @@"));
+/// assert!(second.header.starts_with("@@ -2,1 +1,2 @@"));
+/// assert!(stagent::diff::split_hunk_at(hunk, 4).is_none());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn split_hunk_at(hunk: &Hunk, at: usize) -> Option<(Hunk, Hunk)> {
+    let has_change = |lines: &[DiffLine]| lines.iter().any(|l| l.kind != LineKind::Context);
+    if at > hunk.lines.len() || !has_change(&hunk.lines[..at]) || !has_change(&hunk.lines[at..]) {
+        return None;
+    }
+
+    let (before, after) = hunk.lines.split_at(at);
+    let part = |lines: &[DiffLine], preceding: &[DiffLine], n: usize| {
+        let old_side =
+            |lines: &[DiffLine]| lines.iter().filter(|l| l.kind != LineKind::Added).count() as u32;
+        let new_side = |lines: &[DiffLine]| {
+            lines.iter().filter(|l| l.kind != LineKind::Removed).count() as u32
+        };
+        let (old_lines, new_lines) = (old_side(lines), new_side(lines));
+        let old_start = start_line(
+            hunk.old_start,
+            hunk.old_lines,
+            old_side(preceding),
+            old_lines,
+        );
+        let new_start = start_line(
+            hunk.new_start,
+            hunk.new_lines,
+            new_side(preceding),
+            new_lines,
+        );
+        Hunk {
+            header: format!(
+                "@@ -{},{} +{},{} @@ split {}/2",
+                old_start, old_lines, new_start, new_lines, n
+            ),
+            lines: lines.to_vec().into(),
+            status: HunkStatus::Pending,
+            old_start,
+            old_lines,
+            new_start,
+            new_lines,
+        }
+    };
+    Some((part(before, &[], 1), part(after, before, 2)))
+}
+
+/// Start line of a sub-hunk side with `count` lines, `before` lines into a
+/// hunk side that starts at `start` and has `total` lines.
+fn start_line(start: u32, total: u32, before: u32, count: u32) -> u32 {
+    // An empty hunk side names the line before it, a non-empty one its
+    // first line
+    let first = if total == 0 { start + 1 } else { start } + before;
+    if count == 0 {
+        first.saturating_sub(1)
+    } else {
+        first
+    }
+}
+
+/// Default hunk size threshold for `--auto-split`, in diff lines.
+pub const DEFAULT_AUTO_SPLIT_LINES: usize = 20;
+
+/// Split every hunk with more than `max_lines` lines (context included)
+/// with [`split_hunk`], so a review starts with reasonably sized units.
+/// Binary files are left alone. Returns the number of hunks that were
+/// split.
+pub fn auto_split(files: &mut [FileDiff], max_lines: usize) -> usize {
+    let mut split = 0;
+    for file in files.iter_mut().filter(|f| !f.is_whole_file()) {
+        let mut hunks = Vec::with_capacity(file.hunks.len());
+        for hunk in file.hunks.drain(..) {
+            if hunk.lines.len() <= max_lines || is_mode_hunk(&hunk) {
+                hunks.push(hunk);
+                continue;
+            }
+            let parts = split_hunk(&hunk);
+            if parts.len() > 1 {
+                split += 1;
+            }
+            hunks.extend(parts);
+        }
+        file.hunks = hunks;
+    }
+    split
+}
+
+/// Split the changes on lines `selected` out of a hunk, for staging part
+/// of it.
+///
+/// Returns `(above, line_hunk, rest)`: `line_hunk` applies only those
+/// changes (other removed lines become context, other added lines are
+/// dropped), and `rest` applies the remaining changes on top of it.
+/// `rest.old_start` is given relative to the index *before* `line_hunk`
+/// is staged, minus its line delta, so it lines up once the staged
+/// `line_hunk` is counted in the line offset of later hunks. Near the top
+/// of a file, as when staging part of a new file, that can come before
+/// the first line: the rest then drops the staged lines it starts with,
+/// and changes above them go to `above`, a hunk of their own ahead of
+/// `line_hunk` (`rest` is `None` if no change is left below). Returns
+/// `None` if `selected` holds no change or every change in the hunk.
+///
+/// ```
+/// let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1,2 +1,3 @@\n-a\n+A\n b\n+c\n";
+/// let hunk = &stagent::patch::parse_unified_diff(diff)?[0].hunks[0];
+/// let (above, line_hunk, rest) = stagent::diff::split_off_lines(hunk, 0..=1).unwrap();
+/// assert!(above.is_none());
+/// assert_eq!((line_hunk.old_lines, line_hunk.new_lines), (2, 2));
+/// let rest = rest.unwrap();
+/// assert_eq!((rest.old_lines, rest.new_lines), (2, 3));
+/// assert!(stagent::diff::split_off_lines(hunk, 0..=3).is_none());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn split_off_lines(
+    hunk: &Hunk,
+    selected: RangeInclusive<usize>,
+) -> Option<(Option<Hunk>, Hunk, Option<Hunk>)> {
+    let is_change = |(_, l): &(usize, &DiffLine)| l.kind != LineKind::Context;
+    let changes = hunk.lines.iter().enumerate().filter(is_change);
+    let chosen = changes
+        .clone()
+        .filter(|(i, _)| selected.contains(i))
+        .count();
+    if chosen == 0 || chosen == changes.count() {
+        return None;
+    }
+
+    let (line_hunk, rest) = line_and_rest(hunk, &selected);
+    if rest.is_some() {
+        return Some((None, line_hunk, rest));
+    }
+    // Changes above the selection keep the rest from starting at it
+    let first = *selected.start();
+    let (above, below) = split_hunk_at(hunk, first)?;
+    let below_selected = 0..=selected.end() - first;
+    let below_rest = below
+        .lines
+        .iter()
+        .enumerate()
+        .any(|(i, l)| l.kind != LineKind::Context && !below_selected.contains(&i));
+    if !below_rest {
+        return Some((Some(above), below, None));
+    }
+    let (line_hunk, rest) = line_and_rest(&below, &below_selected);
+    Some((Some(above), line_hunk, Some(rest?)))
+}
+
+/// The two hunks of [`split_off_lines`]; the rest is `None` if it can't
+/// start at or after the first line of the file.
+fn line_and_rest(hunk: &Hunk, selected: &RangeInclusive<usize>) -> (Hunk, Option<Hunk>) {
+    let as_context = |line: &DiffLine| DiffLine {
+        kind: LineKind::Context,
+        ..line.clone()
+    };
+    let mut line_lines = Vec::new();
+    let mut rest_lines = Vec::new();
+    for (i, line) in hunk.lines.iter().enumerate() {
+        match (line.kind, selected.contains(&i)) {
+            (LineKind::Context, _) => {
+                line_lines.push(line.clone());
+                rest_lines.push(line.clone());
+            }
+            (LineKind::Added, true) => {
+                line_lines.push(line.clone());
+                rest_lines.push(as_context(line));
+            }
+            (LineKind::Removed, true) => line_lines.push(line.clone()),
+            (LineKind::Added, false) => rest_lines.push(line.clone()),
+            (LineKind::Removed, false) => {
+                line_lines.push(as_context(line));
+                rest_lines.push(line.clone());
+            }
+        }
+    }
+
+    let count = |lines: &[DiffLine]| {
+        lines
+            .iter()
+            .fold((0u32, 0u32), |(old, new), l| match l.kind {
+                LineKind::Context => (old + 1, new + 1),
+                LineKind::Removed => (old + 1, new),
+                LineKind::Added => (old, new + 1),
+            })
+    };
+    let (line_old, line_new) = count(&line_lines);
+    let delta = line_new as i64 - line_old as i64;
+
+    let line_hunk = Hunk {
+        header: format!(
+            "@@ -{},{} +{},{} @@ line",
+            hunk.old_start, line_old, hunk.old_start, line_new
+        ),
+        lines: line_lines.into(),
+        status: HunkStatus::Pending,
+        old_start: hunk.old_start,
+        old_lines: line_old,
+        new_start: hunk.old_start,
+        new_lines: line_new,
+    };
+
+    // Leading context is dropped while the rest would start above the
+    // first line
+    let mut skip = 0;
+    let rest_start = loop {
+        let (rest_old, _) = count(&rest_lines[skip..]);
+        let start = start_line(hunk.old_start, hunk.old_lines, skip as u32, rest_old) as i64;
+        if start - delta >= 0 {
+            break start;
+        }
+        if rest_lines[skip].kind != LineKind::Context {
+            return (line_hunk, None);
+        }
+        skip += 1;
+    };
+    let rest_lines = rest_lines.split_off(skip);
+    let (rest_old, rest_new) = count(&rest_lines);
+    let new_start = start_line(hunk.new_start, hunk.new_lines, skip as u32, rest_new);
+    let rest = Hunk {
+        header: format!(
+            "@@ -{},{} +{},{} @@ rest",
+            rest_start, rest_old, new_start, rest_new
+        ),
+        lines: rest_lines.into(),
+        status: HunkStatus::Pending,
+        old_start: (rest_start - delta) as u32,
+        old_lines: rest_old,
+        new_start,
+        new_lines: rest_new,
+    };
+    (line_hunk, Some(rest))
+}
+
+/// The hunk that turns the old side of `hunk` into `new_side` instead of
+/// its own new side, e.g. after the new side was edited in the editor.
+/// Lines the two sides share become context.
+///
+/// ```
+/// let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n-b\n+B\n";
+/// let hunk = &stagent::patch::parse_unified_diff(diff)?[0].hunks[0];
+/// let edited = stagent::diff::with_new_side(hunk, "a\nb2\nc\n");
+/// assert_eq!((edited.old_lines, edited.new_lines), (2, 3));
+/// assert_eq!(edited.lines[1].content, "b\n");
+/// assert_eq!(edited.lines[3].new_lineno, Some(3));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn with_new_side(hunk: &Hunk, new_side: &str) -> Hunk {
+    let old_lines: Vec<&DiffLine> = hunk
+        .lines
+        .iter()
+        .filter(|l| l.kind != LineKind::Added)
+        .collect();
+    let old: Vec<&str> = old_lines.iter().map(|l| l.content.as_str()).collect();
+    let new: Vec<&str> = new_side.split_inclusive('\n').collect();
+    let first_new = if hunk.new_lines == 0 {
+        hunk.new_start + 1
+    } else {
+        hunk.new_start
+    };
+
+    let lines: Vec<DiffLine> = TextDiff::from_slices(&old, &new)
+        .iter_all_changes()
+        .map(|change| {
+            let new_lineno = change.new_index().map(|i| first_new + i as u32);
+            match (change.tag(), change.old_index()) {
+                (ChangeTag::Insert, _) | (_, None) => DiffLine {
+                    kind: LineKind::Added,
+                    content: change.value().to_string(),
+                    old_lineno: None,
+                    new_lineno,
+                    raw: None,
+                    no_newline: false,
+                },
+                (tag, Some(i)) => DiffLine {
+                    kind: if tag == ChangeTag::Equal {
+                        LineKind::Context
+                    } else {
+                        LineKind::Removed
+                    },
+                    new_lineno,
+                    ..old_lines[i].clone()
+                },
+            }
+        })
+        .collect();
+
+    let new_count = new.len() as u32;
+    let new_start = start_line(hunk.new_start, hunk.new_lines, 0, new_count);
+    Hunk {
+        header: format!(
+            "@@ -{},{} +{},{} @@ edited",
+            hunk.old_start, hunk.old_lines, new_start, new_count
+        ),
+        lines: lines.into(),
+        status: HunkStatus::Pending,
+        old_start: hunk.old_start,
+        old_lines: hunk.old_lines,
+        new_start,
+        new_lines: new_count,
+    }
+}
+
+/// Whether two hunks make the same change: identical lines (kind and
+/// content), wherever they are in the file. Headers and line numbers are
+/// ignored, since staging an earlier hunk shifts them.
+pub fn same_change(a: &Hunk, b: &Hunk) -> bool {
+    a.lines.len() == b.lines.len()
+        && a.lines
+            .iter()
+            .zip(&b.lines)
+            .all(|(x, y)| x.kind == y.kind && x.bytes() == y.bytes())
+}
+
+/// Carry review statuses from `old` over to a freshly computed diff `new`:
+/// each hunk in `new` that makes the [`same_change`] as a reviewed hunk of
+/// the same file takes that hunk's status. Everything else stays
+/// `Pending`. Each old hunk is used at most once. Returns the number of
+/// hunks whose status was carried over.
+pub fn carry_over_statuses(old: &[FileDiff], new: &mut [FileDiff]) -> usize {
+    let mut carried = 0;
+    for file in new.iter_mut() {
+        let Some(old_file) = old.iter().find(|f| f.path == file.path) else {
+            continue;
+        };
+        let mut used = vec![false; old_file.hunks.len()];
+        for hunk in &mut file.hunks {
+            let found = old_file.hunks.iter().enumerate().position(|(i, h)| {
+                !used[i] && h.status != HunkStatus::Pending && same_change(h, hunk)
+            });
+            if let Some(i) = found {
+                used[i] = true;
+                hunk.status = old_file.hunks[i].status;
+                carried += 1;
+            }
+        }
+    }
+    carried
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(old_start: u32, lines: &[(LineKind, &str)], status: HunkStatus) -> Hunk {
+        Hunk {
+            header: format!("@@ -{},1 +{},1 @@", old_start, old_start),
+            lines: lines
+                .iter()
+                .map(|&(kind, content)| DiffLine {
+                    kind,
+                    content: format!("{}\n", content),
+                    old_lineno: None,
+                    new_lineno: None,
+                    raw: None,
+                    no_newline: false,
+                })
+                .collect(),
+            status,
+            old_start,
+            old_lines: 1,
+            new_start: old_start,
+            new_lines: 1,
+        }
+    }
+
+    fn file(path: &str, hunks: Vec<Hunk>) -> FileDiff {
+        FileDiff {
+            path: path.into(),
+            hunks,
+            status: DeltaStatus::Modified,
+            is_binary: false,
+            is_submodule: false,
+            mode_change: None,
+        }
+    }
+
+    const CHANGE_A: &[(LineKind, &str)] = &[(LineKind::Removed, "a"), (LineKind::Added, "A")];
+    const CHANGE_B: &[(LineKind, &str)] = &[(LineKind::Removed, "b"), (LineKind::Added, "B")];
+
+    #[test]
+    fn test_same_change_ignores_position() {
+        let a = hunk(1, CHANGE_A, HunkStatus::Pending);
+        assert!(same_change(&a, &hunk(40, CHANGE_A, HunkStatus::Skipped)));
+        assert!(!same_change(&a, &hunk(1, CHANGE_B, HunkStatus::Pending)));
+        assert!(!same_change(
+            &a,
+            &hunk(1, &[(LineKind::Removed, "a")], HunkStatus::Pending)
+        ));
+    }
+
+    #[test]
+    fn test_carry_over_statuses() {
+        let old = vec![
+            file(
+                "x.rs",
+                vec![
+                    hunk(1, CHANGE_A, HunkStatus::Skipped),
+                    hunk(10, CHANGE_B, HunkStatus::Commented),
+                ],
+            ),
+            file("y.rs", vec![hunk(1, CHANGE_A, HunkStatus::Skipped)]),
+        ];
+        let mut new = vec![
+            file(
+                "x.rs",
+                vec![
+                    // Moved down by an edit above it
+                    hunk(3, CHANGE_A, HunkStatus::Pending),
+                    hunk(
+                        12,
+                        &[(LineKind::Removed, "b"), (LineKind::Added, "B2")],
+                        HunkStatus::Pending,
+                    ),
+                ],
+            ),
+            // Same change, different file
+            file("z.rs", vec![hunk(1, CHANGE_A, HunkStatus::Pending)]),
+        ];
+
+        assert_eq!(carry_over_statuses(&old, &mut new), 1);
+        assert_eq!(new[0].hunks[0].status, HunkStatus::Skipped);
+        assert_eq!(new[0].hunks[1].status, HunkStatus::Pending);
+        assert_eq!(new[1].hunks[0].status, HunkStatus::Pending);
+    }
+
+    #[test]
+    fn test_auto_split_only_large_hunks() {
+        use LineKind::*;
+        let mut lines = vec![(Removed, "a"), (Added, "A")];
+        lines.extend([(Context, "ctx"); 8]);
+        lines.extend([(Removed, "b"), (Added, "B")]);
+        let large = hunk(1, &lines, HunkStatus::Pending);
+        let small = hunk(30, CHANGE_A, HunkStatus::Pending);
+        let mut files = vec![file("x.rs", vec![large, small])];
+
+        assert_eq!(auto_split(&mut files, 100), 0);
+        assert_eq!(files[0].hunks.len(), 2);
+        assert_eq!(auto_split(&mut files, 5), 1);
+        assert_eq!(files[0].hunks.len(), 3);
+        assert_eq!(files[0].hunks[2].old_start, 30);
+    }
+
+    #[test]
+    fn test_split_piece_without_context_starts_after_preceding_lines() {
+        use LineKind::*;
+        let mut lines = vec![(Removed, "a"), (Added, "A")];
+        lines.extend([(Context, "ctx"); 3]);
+        lines.push((Added, "new"));
+        lines.extend([(Context, "ctx"); 3]);
+        lines.extend([(Removed, "b"), (Added, "B")]);
+        let parts = split_hunk(&hunk(10, &lines, HunkStatus::Pending));
+
+        assert_eq!(parts.len(), 3);
+        // The insertion follows old lines 10-13 and new lines 10-13
+        assert_eq!(parts[1].lines.len(), 1);
+        assert_eq!((parts[1].old_start, parts[1].old_lines), (13, 0));
+        assert_eq!((parts[1].new_start, parts[1].new_lines), (14, 1));
+        assert_eq!((parts[2].old_start, parts[2].new_start), (17, 18));
+    }
+
+    #[test]
+    fn test_split_off_lines_of_insertion_without_context() {
+        let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -5,0 +6,2 @@\n+x\n+y\n";
+        let hunk = &crate::patch::parse_unified_diff(diff).unwrap()[0].hunks[0];
+        let (above, line_hunk, rest) = split_off_lines(hunk, 0..=0).unwrap();
+        assert!(above.is_none());
+        assert_eq!((line_hunk.old_start, line_hunk.old_lines), (5, 0));
+        // Once "x" is staged after line 5, the rest starts at it: line 6,
+        // or 5 before the staged line is counted
+        let rest = rest.unwrap();
+        assert_eq!((rest.old_start, rest.old_lines), (5, 1));
+
+        // At the top of a new file the rest can't start at the staged line
+        let diff = "diff --git a/f b/f\n--- /dev/null\n+++ b/f\n@@ -0,0 +1,3 @@\n+x\n+y\n+z\n";
+        let hunk = &crate::patch::parse_unified_diff(diff).unwrap()[0].hunks[0];
+        let (above, _, rest) = split_off_lines(hunk, 1..=1).unwrap();
+        assert!(above.is_none());
+        // "x" is inserted above "y" once "y" is staged
+        assert_eq!(rest.unwrap().old_start, 0);
+
+        let (above, line_hunk, rest) = split_off_lines(hunk, 1..=2).unwrap();
+        let above = above.unwrap();
+        assert_eq!(above.lines.len(), 1);
+        assert_eq!((above.old_start, above.old_lines), (0, 0));
+        assert_eq!(line_hunk.lines.len(), 2);
+        assert!(rest.is_none());
+    }
+
+    #[test]
+    fn test_carry_over_uses_each_hunk_once() {
+        let old = vec![file(
+            "x.rs",
+            vec![
+                hunk(1, CHANGE_A, HunkStatus::Skipped),
+                hunk(5, CHANGE_A, HunkStatus::Pending),
+            ],
+        )];
+        let mut new = vec![file(
+            "x.rs",
+            vec![
+                hunk(1, CHANGE_A, HunkStatus::Pending),
+                hunk(5, CHANGE_A, HunkStatus::Pending),
+            ],
+        )];
+        assert_eq!(carry_over_statuses(&old, &mut new), 1);
+        assert_eq!(new[0].hunks[0].status, HunkStatus::Skipped);
+        assert_eq!(new[0].hunks[1].status, HunkStatus::Pending);
+    }
+}
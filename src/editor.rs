@@ -1,30 +1,312 @@
 use anyhow::{Context, Result, bail};
 use similar::TextDiff;
 use std::io::Write;
-use std::sync::mpsc;
+use std::sync::{Arc, mpsc};
 use std::thread;
 use std::time::Duration;
 
-use crate::types::{DiffLine, FeedbackKind, Hunk, HunkFeedback, LineKind};
+use crate::tmux::{self, Clock, SystemTmux, TmuxRunner};
+use crate::types::{DiffLine, FeedbackKind, Hunk, HunkFeedback, LineKind, Severity, Verdict};
 
 /// Build the tmux split-window command arguments.
 ///
 /// The editor and file path are passed as separate shell-quoted arguments
 /// to avoid command injection via `$EDITOR` or paths with special characters.
 pub fn build_tmux_split_command(editor: &str, file_path: &str) -> Vec<String> {
-    vec![
-        "tmux".to_string(),
-        "split-window".to_string(),
-        "-h".to_string(),
-        "-p".to_string(),
-        "50".to_string(),
-        "-P".to_string(),
-        "-F".to_string(),
-        "#{pane_id}".to_string(),
-        "--".to_string(),
-        editor.to_string(),
-        file_path.to_string(),
-    ]
+    build_tmux_split_command_for(vec![editor.to_string(), file_path.to_string()])
+}
+
+/// Build the tmux split-window command arguments for an arbitrary editor
+/// invocation (`editor_args[0]` is the editor, the rest its arguments).
+pub fn build_tmux_split_command_for(editor_args: Vec<String>) -> Vec<String> {
+    build_tmux_pane_command(&SplitLayout::default(), editor_args)
+}
+
+/// Build the tmux command opening `args` where `layout` says: a split of
+/// stagent's pane, or a new window. Prints the new pane's ID either way.
+pub fn build_tmux_pane_command(layout: &SplitLayout, args: Vec<String>) -> Vec<String> {
+    let mut cmd = vec!["tmux".to_string()];
+    match layout.direction {
+        SplitDirection::Window => cmd.push("new-window".to_string()),
+        direction => {
+            cmd.push("split-window".to_string());
+            cmd.push(
+                if direction == SplitDirection::Vertical {
+                    "-v"
+                } else {
+                    "-h"
+                }
+                .to_string(),
+            );
+            match layout.size {
+                SplitSize::Percent(percent) => {
+                    cmd.push("-p".to_string());
+                    cmd.push(percent.to_string());
+                }
+                SplitSize::Cells(cells) => {
+                    cmd.push("-l".to_string());
+                    cmd.push(cells.to_string());
+                }
+            }
+        }
+    }
+    cmd.extend(["-P", "-F", "#{pane_id}", "--"].map(String::from));
+    cmd.extend(args);
+    cmd
+}
+
+/// How a tmux split is laid out (`--split`, `stagent.split`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum SplitDirection {
+    /// Side by side with stagent
+    #[default]
+    Horizontal,
+    /// Below stagent
+    Vertical,
+    /// A new tmux window instead of a split
+    Window,
+}
+
+impl SplitDirection {
+    /// The command-line and git config value of the direction.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Horizontal => "horizontal",
+            Self::Vertical => "vertical",
+            Self::Window => "window",
+        }
+    }
+}
+
+impl std::str::FromStr for SplitDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "horizontal" => Ok(Self::Horizontal),
+            "vertical" => Ok(Self::Vertical),
+            "window" => Ok(Self::Window),
+            _ => Err(format!(
+                "invalid split '{}' (horizontal, vertical or window)",
+                s
+            )),
+        }
+    }
+}
+
+/// The size of a new split (`--split-size`, `stagent.splitSize`): a
+/// percentage of stagent's pane (`40%`) or a number of columns or rows
+/// (`80`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitSize {
+    Percent(u8),
+    Cells(u16),
+}
+
+impl Default for SplitSize {
+    fn default() -> Self {
+        Self::Percent(50)
+    }
+}
+
+impl std::fmt::Display for SplitSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Percent(percent) => write!(f, "{}%", percent),
+            Self::Cells(cells) => write!(f, "{}", cells),
+        }
+    }
+}
+
+impl std::str::FromStr for SplitSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid split size '{}' (a percentage like 40% or a number of cells)",
+                s
+            )
+        };
+        match s.trim().strip_suffix('%') {
+            Some(percent) => match percent.parse() {
+                Ok(percent @ 1..=99) => Ok(Self::Percent(percent)),
+                _ => Err(invalid()),
+            },
+            None => match s.trim().parse() {
+                Ok(cells) if cells > 0 => Ok(Self::Cells(cells)),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+/// Where tmux opens editors and spawned reviews. The default is a
+/// horizontal split at half the width.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SplitLayout {
+    pub direction: SplitDirection,
+    /// Ignored for [`SplitDirection::Window`].
+    pub size: SplitSize,
+}
+
+impl SplitLayout {
+    /// The layout set in git config: `stagent.split` (horizontal, vertical
+    /// or window) and `stagent.splitSize`, the default for what is unset.
+    pub fn from_git_config(config: &git2::Config) -> Result<Self> {
+        let mut layout = Self::default();
+        if let Ok(direction) = config.get_string("stagent.split") {
+            layout.direction = direction
+                .parse()
+                .map_err(|e: String| anyhow::anyhow!("stagent.split: {}", e))?;
+        }
+        if let Ok(size) = config.get_string("stagent.splitSize") {
+            layout.size = size
+                .parse()
+                .map_err(|e: String| anyhow::anyhow!("stagent.splitSize: {}", e))?;
+        }
+        Ok(layout)
+    }
+}
+
+/// Build the editor invocation that opens `file_path` at `line`.
+///
+/// Editors disagree on how to take a line number, so the syntax is picked by
+/// the editor's executable name:
+/// - `+<line> <path>`: vi, vim, nvim, nano, emacs, kak, micro and friends
+/// - `<path>:<line>`: helix, sublime, zed
+/// - `--goto <path>:<line>`: VS Code
+///
+/// Unknown editors just get the path. `editor` may carry arguments
+/// (`nvim -u NONE`), separated by whitespace.
+pub fn build_editor_args_at_line(editor: &str, file_path: &str, line: u32) -> Vec<String> {
+    let name = editor_name(editor);
+    let located = format!("{}:{}", file_path, line);
+
+    let args: Vec<String> = match name.as_str() {
+        "vi" | "vim" | "nvim" | "gvim" | "view" | "nano" | "pico" | "emacs" | "emacsclient"
+        | "kak" | "micro" | "joe" | "ne" => {
+            vec![format!("+{}", line), file_path.to_string()]
+        }
+        "hx" | "helix" | "subl" | "sublime_text" | "zed" => vec![located],
+        "code" | "code-insiders" | "codium" | "cursor" => vec!["--goto".to_string(), located],
+        _ => vec![file_path.to_string()],
+    };
+
+    let mut cmd = editor_words(editor);
+    cmd.extend(args);
+    cmd
+}
+
+/// Build the editor invocation that opens `file_path`.
+pub fn build_editor_args(editor: &str, file_path: &str) -> Vec<String> {
+    let mut cmd = editor_words(editor);
+    cmd.push(file_path.to_string());
+    cmd
+}
+
+/// `$EDITOR` split into the program and its arguments.
+fn editor_words(editor: &str) -> Vec<String> {
+    editor.split_whitespace().map(String::from).collect()
+}
+
+/// The executable name of `editor`'s program (`code` for
+/// `/usr/bin/code -w`).
+fn editor_name(editor: &str) -> String {
+    editor
+        .split_whitespace()
+        .next()
+        .and_then(|program| std::path::Path::new(program).file_name())
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// GUI editors, which return at once (leaving the file to an editor
+/// window) unless given a flag to wait until it is closed: the flags they
+/// take for it, the first being the one stagent adds.
+const GUI_EDITORS: &[(&[&str], &[&str])] = &[
+    (
+        &["code", "code-insiders", "codium", "cursor", "windsurf"],
+        &["--wait", "-w"],
+    ),
+    (
+        &["subl", "sublime_text", "atom", "mate", "zed"],
+        &["--wait", "-w"],
+    ),
+    (&["gvim", "mvim", "gview"], &["--nofork", "-f"]),
+    (&["gedit", "gnome-text-editor"], &["--wait", "-w"]),
+    (&["kate"], &["--block", "-b"]),
+];
+
+/// How an editor is started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditorLaunch {
+    /// A terminal editor, run in a pane of the multiplexer.
+    Pane(Vec<String>),
+    /// A GUI editor (or `stagent.editorWaitCommand`), run directly: it
+    /// opens its own window, and the file is done once the process exits.
+    Wait(Vec<String>),
+}
+
+/// How to open `file_path` (at `line`, if given) with `editor`.
+///
+/// `wait_command`, set with `git config stagent.editorWaitCommand`, is a
+/// command that edits a file and exits once it is done with it; it takes
+/// the place of `editor` and is waited on like a GUI editor. Otherwise GUI
+/// editors (VS Code, Sublime Text, gvim, ...) are run with their wait flag,
+/// unless it is already in `editor`, and other editors open in a pane.
+pub fn plan_editor_launch(
+    editor: &str,
+    wait_command: Option<&str>,
+    file_path: &str,
+    line: Option<u32>,
+) -> EditorLaunch {
+    let args = |editor: &str| match line {
+        Some(line) => build_editor_args_at_line(editor, file_path, line),
+        None => build_editor_args(editor, file_path),
+    };
+    if let Some(command) = wait_command.filter(|c| !c.trim().is_empty()) {
+        return EditorLaunch::Wait(args(command));
+    }
+
+    let name = editor_name(editor);
+    let Some((_, flags)) = GUI_EDITORS
+        .iter()
+        .find(|(names, _)| names.contains(&name.as_str()))
+    else {
+        return EditorLaunch::Pane(args(editor));
+    };
+    let mut cmd = args(editor);
+    let words = editor_words(editor);
+    if !words[1..].iter().any(|w| flags.contains(&w.as_str())) {
+        cmd.insert(words.len(), flags[0].to_string());
+    }
+    EditorLaunch::Wait(cmd)
+}
+
+/// Run `command` and signal the returned receiver once it exits, for a
+/// [`EditorLaunch::Wait`] editor. Fails if it can't be started.
+pub fn spawn_waiting_editor(command: &[String]) -> Result<mpsc::Receiver<()>> {
+    let mut child = std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", command[0]))?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = child.wait();
+        let _ = tx.send(());
+    });
+    Ok(rx)
+}
+
+/// The `stagent.editorWaitCommand` git config (see
+/// [`plan_editor_launch`]).
+pub fn editor_wait_command_from_git_config(config: &git2::Config) -> Option<String> {
+    config.get_string("stagent.editorWaitCommand").ok()
 }
 
 /// Build a command to check if a tmux pane still exists.
@@ -56,38 +338,58 @@ pub fn get_editor() -> String {
 /// Open the editor in a tmux split pane. Returns the pane ID.
 pub fn open_editor(file_path: &str) -> Result<String> {
     let editor = get_editor();
-    let cmd = build_tmux_split_command(&editor, file_path);
+    let args = build_editor_args(&editor, file_path);
+    run_tmux_split(&SystemTmux, &build_tmux_split_command_for(args))
+}
 
-    let output = std::process::Command::new(&cmd[0])
-        .args(&cmd[1..])
-        .output()
-        .context("Failed to run tmux split-window")?;
+/// Open the editor in a tmux split pane positioned at `line` of `file_path`.
+/// Returns the pane ID.
+pub fn open_editor_at_line(file_path: &str, line: u32) -> Result<String> {
+    let editor = get_editor();
+    let args = build_editor_args_at_line(&editor, file_path, line);
+    run_tmux_split(&SystemTmux, &build_tmux_split_command_for(args))
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("tmux split-window failed: {}", stderr);
+/// Run a tmux split-window command (as built by [`build_tmux_split_command`])
+/// and return the new pane ID.
+pub fn run_tmux_split(tmux: &dyn TmuxRunner, cmd: &[String]) -> Result<String> {
+    let stdout = tmux.run(&cmd[1..])?;
+    let pane_id = stdout.trim().to_string();
+    if pane_id.is_empty() {
+        bail!("tmux split-window did not return a pane ID");
     }
-
-    let pane_id = String::from_utf8_lossy(&output.stdout).trim().to_string();
     Ok(pane_id)
 }
 
+/// Interval between pane-existence polls.
+pub const PANE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 /// Maximum number of poll iterations before giving up on pane close detection.
 /// At 500ms per poll, this is ~5 minutes.
-const MAX_PANE_POLL_ITERATIONS: u32 = 600;
+pub const MAX_PANE_POLL_ITERATIONS: u32 = 600;
 
 /// Wait for a tmux pane to close by polling whether the pane still exists.
 /// Returns a receiver that signals when the pane closes.
 pub fn wait_for_pane_close(pane_id: String) -> mpsc::Receiver<()> {
+    let (tmux, clock) = tmux::system();
+    wait_for_pane_close_with(tmux, clock, pane_id)
+}
+
+/// [`wait_for_pane_close`] with an explicit tmux runner and clock.
+pub fn wait_for_pane_close_with(
+    tmux: Arc<dyn TmuxRunner>,
+    clock: Arc<dyn Clock>,
+    pane_id: String,
+) -> mpsc::Receiver<()> {
     let (tx, rx) = mpsc::channel();
 
     thread::spawn(move || {
         for _ in 0..MAX_PANE_POLL_ITERATIONS {
-            if !pane_exists(&pane_id) {
+            if !pane_exists_with(tmux.as_ref(), &pane_id) {
                 let _ = tx.send(());
                 return;
             }
-            thread::sleep(Duration::from_millis(500));
+            clock.sleep(PANE_POLL_INTERVAL);
         }
         // Timeout: send signal anyway so the UI doesn't hang forever
         let _ = tx.send(());
@@ -99,12 +401,14 @@ pub fn wait_for_pane_close(pane_id: String) -> mpsc::Receiver<()> {
 /// Check if a tmux pane still exists by listing all panes and searching for
 /// the given pane ID.
 pub fn pane_exists(pane_id: &str) -> bool {
+    pane_exists_with(&SystemTmux, pane_id)
+}
+
+/// [`pane_exists`] with an explicit tmux runner.
+pub fn pane_exists_with(tmux: &dyn TmuxRunner, pane_id: &str) -> bool {
     let cmd = build_pane_exists_check_command();
-    match std::process::Command::new(&cmd[0]).args(&cmd[1..]).output() {
-        Ok(output) => {
-            let pane_list = String::from_utf8_lossy(&output.stdout);
-            pane_list.lines().any(|line| line.trim() == pane_id)
-        }
+    match tmux.run(&cmd[1..]) {
+        Ok(pane_list) => pane_list.lines().any(|line| line.trim() == pane_id),
         Err(_) => false, // tmux command failed, assume pane is gone
     }
 }
@@ -142,6 +446,164 @@ pub fn prepare_edit_tempfile(hunk: &Hunk) -> Result<tempfile::NamedTempFile> {
     Ok(tmpfile)
 }
 
+/// Hunks whose new side has more lines than this are edited within a copy of
+/// the whole working-tree file instead of as a detached fragment, so the
+/// resulting diff carries real file line numbers.
+pub const MAX_FRAGMENT_EDIT_LINES: usize = 40;
+
+/// Marker line opening the editable region in a whole-file edit tempfile.
+pub const EDIT_REGION_START: &str = ">>>>>>> stagent: edit the hunk below this line";
+
+/// Marker line closing the editable region in a whole-file edit tempfile.
+pub const EDIT_REGION_END: &str = "<<<<<<< stagent: end of hunk";
+
+/// Whether the hunk is too large to edit as a fragment.
+pub fn exceeds_fragment_limit(hunk: &Hunk) -> bool {
+    hunk.lines
+        .iter()
+        .filter(|l| l.kind != LineKind::Removed)
+        .count()
+        > MAX_FRAGMENT_EDIT_LINES
+}
+
+/// Prepare a tempfile for editing a hunk in place: a copy of the working-tree
+/// file `file_content` with the hunk's new-side region wrapped in
+/// [`EDIT_REGION_START`] / [`EDIT_REGION_END`] marker lines.
+///
+/// `extension` is kept on the tempfile so editors pick the right syntax.
+/// Returns the tempfile and the 1-based line of the first region line, or
+/// `None` if the file no longer matches the hunk at its position.
+pub fn prepare_region_edit_tempfile(
+    file_content: &str,
+    hunk: &Hunk,
+    extension: Option<&str>,
+) -> Result<Option<(tempfile::NamedTempFile, u32)>> {
+    let lines: Vec<&str> = file_content.split_inclusive('\n').collect();
+    let region_len = hunk
+        .lines
+        .iter()
+        .filter(|l| l.kind != LineKind::Removed)
+        .count();
+    let start = (hunk.new_start as usize).saturating_sub(1);
+    let Some(region) = lines.get(start..start + region_len) else {
+        return Ok(None);
+    };
+    let region: String = region
+        .iter()
+        .map(|l| {
+            if l.ends_with('\n') {
+                l.to_string()
+            } else {
+                format!("{}\n", l)
+            }
+        })
+        .collect();
+    if region != extract_new_side_content(&hunk.lines) {
+        return Ok(None);
+    }
+
+    let suffix = extension.map_or(".tmp".to_string(), |ext| format!(".{}", ext));
+    let mut tmpfile = tempfile::Builder::new()
+        .prefix("stagent-edit-")
+        .suffix(&suffix)
+        .tempfile()
+        .context("Failed to create temp file")?;
+
+    for line in &lines[..start] {
+        write!(tmpfile, "{}", line)?;
+    }
+    writeln!(tmpfile, "{}", EDIT_REGION_START)?;
+    write!(tmpfile, "{}", region)?;
+    writeln!(tmpfile, "{}", EDIT_REGION_END)?;
+    for line in &lines[start + region_len..] {
+        write!(tmpfile, "{}", line)?;
+    }
+    tmpfile.flush()?;
+    Ok(Some((tmpfile, start as u32 + 1)))
+}
+
+/// Whether tempfile content was prepared by [`prepare_region_edit_tempfile`].
+pub fn is_region_edit(content: &str) -> bool {
+    content.lines().any(|l| l == EDIT_REGION_START)
+}
+
+/// Extract the region between the edit markers. Returns the 1-based file
+/// line of the region's first line and the region content, or `None` if
+/// either marker was removed.
+pub fn extract_edit_region(content: &str) -> Option<(u32, String)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let start = lines.iter().position(|l| *l == EDIT_REGION_START)?;
+    let end = start
+        + 1
+        + lines[start + 1..]
+            .iter()
+            .position(|l| *l == EDIT_REGION_END)?;
+    let region = lines[start + 1..end]
+        .iter()
+        .map(|l| format!("{}\n", l))
+        .collect();
+    Some((start as u32 + 1, region))
+}
+
+/// Parse the result of an in-place region edit. The diff is computed between
+/// the original and edited regions only (changes outside the markers are
+/// ignored), with hunk headers shifted to working-tree file line numbers.
+pub fn parse_region_edit_result(
+    original: &str,
+    edited: &str,
+    file_path: &str,
+    hunk_header: &str,
+    hunk_lines: &[crate::types::DiffLine],
+) -> Option<HunkFeedback> {
+    let (start_line, original_region) = extract_edit_region(original)?;
+    let (_, edited_region) = extract_edit_region(edited)?;
+    let mut feedback = parse_edit_result(
+        &original_region,
+        &edited_region,
+        file_path,
+        hunk_header,
+        hunk_lines,
+    )?;
+    feedback.content = offset_hunk_headers(&feedback.content, start_line - 1);
+    Some(feedback)
+}
+
+/// Shift the line numbers of every `@@ -a,b +c,d @@` header in a unified
+/// diff by `offset`.
+fn offset_hunk_headers(unified: &str, offset: u32) -> String {
+    let shift = |range: &str| -> String {
+        let (start, count) = match range.split_once(',') {
+            Some((start, count)) => (start, Some(count)),
+            None => (range, None),
+        };
+        let start = start.parse::<u32>().map_or(0, |n| n + offset);
+        match count {
+            Some(count) => format!("{},{}", start, count),
+            None => start.to_string(),
+        }
+    };
+
+    unified
+        .split_inclusive('\n')
+        .map(|line| {
+            let Some(rest) = line.strip_prefix("@@ -") else {
+                return line.to_string();
+            };
+            let mut parts = rest.splitn(3, ' ');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(old), Some(new), Some(tail)) if new.starts_with('+') => {
+                    format!("@@ -{} +{} {}", shift(old), shift(&new[1..]), tail)
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Opening line of a suggestion block in a comment tempfile. The lines up to
+/// the closing fence are replacement code, not comment text.
+pub const SUGGESTION_FENCE: &str = "```suggestion";
+
 /// Prepare a tempfile for commenting on a hunk.
 /// Contains the full hunk with `# COMMENT:` instruction markers.
 pub fn prepare_comment_tempfile(hunk: &Hunk) -> Result<tempfile::NamedTempFile> {
@@ -156,6 +618,15 @@ pub fn prepare_comment_tempfile(hunk: &Hunk) -> Result<tempfile::NamedTempFile>
         tmpfile,
         "# Any new lines you add will be captured as comments."
     )?;
+    writeln!(
+        tmpfile,
+        "# Put replacement code between {} and ``` to suggest a change.",
+        SUGGESTION_FENCE
+    )?;
+    writeln!(
+        tmpfile,
+        "# Start a comment with !nit, !suggestion, !issue or !blocker to rate it."
+    )?;
     writeln!(tmpfile, "# {}", hunk)?;
     writeln!(tmpfile)?;
 
@@ -170,6 +641,223 @@ pub fn prepare_comment_tempfile(hunk: &Hunk) -> Result<tempfile::NamedTempFile>
     Ok(tmpfile)
 }
 
+/// The content of a [`prepare_comment_tempfile`] `template` with the
+/// comments of `existing` feedback put back where they were written, so
+/// [`parse_comment_result`] against the template reads them again.
+pub fn comment_draft(template: &str, existing: &HunkFeedback) -> String {
+    let mut lines: Vec<String> = template.lines().map(str::to_string).collect();
+    let body_start = lines.iter().position(|l| l.is_empty()).map_or(0, |i| i + 1);
+    let body_len = lines.len() - body_start;
+
+    let mut comments: Vec<(usize, String)> = if existing.comment_positions.is_empty() {
+        existing
+            .content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| (body_len, l.to_string()))
+            .collect()
+    } else {
+        existing.comment_positions.clone()
+    };
+    if let (Some(severity), Some((_, first))) = (existing.severity, comments.first_mut()) {
+        *first = format!("!{} {}", severity.label(), first);
+    }
+    // Insert from the end so earlier positions stay valid
+    for (position, text) in comments.into_iter().rev() {
+        lines.insert(body_start + position.min(body_len), text);
+    }
+    if let Some(suggestion) = &existing.suggestion {
+        lines.push(SUGGESTION_FENCE.to_string());
+        lines.extend(suggestion.lines().map(str::to_string));
+        lines.push("```".to_string());
+    }
+
+    let mut draft = lines.join("\n");
+    draft.push('\n');
+    draft
+}
+
+/// Prepare a tempfile for writing the summary that goes with a review
+/// verdict. Lines starting with `#` are instructions.
+pub fn prepare_summary_tempfile(verdict: Verdict) -> Result<tempfile::NamedTempFile> {
+    let mut tmpfile = tempfile::Builder::new()
+        .prefix("stagent-summary-")
+        .suffix(".md")
+        .tempfile()
+        .context("Failed to create temp file")?;
+
+    writeln!(tmpfile, "# Verdict: {}", verdict.label())?;
+    writeln!(
+        tmpfile,
+        "# Write an optional summary of the review below, then close the editor."
+    )?;
+    writeln!(tmpfile, "# Lines starting with # are ignored.")?;
+    writeln!(tmpfile)?;
+    tmpfile.flush()?;
+    Ok(tmpfile)
+}
+
+/// The summary written in a [`prepare_summary_tempfile`] tempfile, without
+/// instruction lines and surrounding blank lines. `None` if it is empty.
+pub fn parse_summary(content: &str) -> Option<String> {
+    let summary = content
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let summary = summary.trim();
+    (!summary.is_empty()).then(|| summary.to_string())
+}
+
+/// Prepare a tempfile for a comment on a whole file, pre-filled with the
+/// file's `existing` comment so it can be revised. Lines starting with `#`
+/// are instructions.
+pub fn prepare_file_comment_tempfile(
+    file_path: &str,
+    existing: Option<&HunkFeedback>,
+) -> Result<tempfile::NamedTempFile> {
+    let header = [
+        format!("# Comment on {}", file_path),
+        "# Write a comment on the whole file below, then close the editor.".to_string(),
+    ];
+    prepare_note_tempfile("stagent-file-comment-", &header, existing)
+}
+
+/// Prepare a tempfile for a comment on the review as a whole, listing the
+/// `files` reviewed (one summary line each) in the instructions.
+pub fn prepare_review_comment_tempfile(
+    files: &[String],
+    existing: Option<&HunkFeedback>,
+) -> Result<tempfile::NamedTempFile> {
+    let mut header = vec![
+        "# Write your overall impressions of the review below, then close the editor.".to_string(),
+    ];
+    header.push("# Files reviewed:".to_string());
+    header.extend(files.iter().map(|f| format!("#   {}", f)));
+    prepare_note_tempfile("stagent-review-comment-", &header, existing)
+}
+
+/// Prepare a tempfile for a comment on the commit labeled `commit` (short
+/// SHA and subject) of a `--range` review.
+pub fn prepare_commit_comment_tempfile(
+    commit: &str,
+    existing: Option<&HunkFeedback>,
+) -> Result<tempfile::NamedTempFile> {
+    let header = [
+        format!("# Comment on commit {}", commit),
+        "# Write a comment on the whole commit below, then close the editor.".to_string(),
+    ];
+    prepare_note_tempfile("stagent-commit-comment-", &header, existing)
+}
+
+/// A tempfile for a comment not tied to a hunk: the `header` lines, the
+/// common instructions, then the `existing` comment if there is one.
+fn prepare_note_tempfile(
+    prefix: &str,
+    header: &[String],
+    existing: Option<&HunkFeedback>,
+) -> Result<tempfile::NamedTempFile> {
+    let mut tmpfile = tempfile::Builder::new()
+        .prefix(prefix)
+        .suffix(".md")
+        .tempfile()
+        .context("Failed to create temp file")?;
+
+    for line in header {
+        writeln!(tmpfile, "{}", line)?;
+    }
+    writeln!(
+        tmpfile,
+        "# Start it with !nit, !suggestion, !issue or !blocker to rate it."
+    )?;
+    writeln!(tmpfile, "# Lines starting with # are ignored.")?;
+    writeln!(tmpfile)?;
+    if let Some(fb) = existing {
+        if let Some(severity) = fb.severity {
+            write!(tmpfile, "!{} ", severity.label())?;
+        }
+        writeln!(tmpfile, "{}", fb.content)?;
+    }
+    tmpfile.flush()?;
+    Ok(tmpfile)
+}
+
+/// The file comment written in a [`prepare_file_comment_tempfile`]
+/// tempfile. `None` if it is empty.
+pub fn parse_file_comment(content: &str, file_path: &str) -> Option<HunkFeedback> {
+    parse_note(content, file_path, FeedbackKind::FileComment)
+}
+
+/// The comment written in a [`prepare_review_comment_tempfile`] tempfile.
+/// `None` if it is empty.
+pub fn parse_review_comment(content: &str) -> Option<HunkFeedback> {
+    parse_note(content, "", FeedbackKind::ReviewComment)
+}
+
+/// The comment written in a [`prepare_commit_comment_tempfile`] tempfile
+/// for `commit`. `None` if it is empty.
+pub fn parse_commit_comment(content: &str, commit: &str) -> Option<HunkFeedback> {
+    let fb = parse_note(content, "", FeedbackKind::CommitComment)?;
+    Some(HunkFeedback {
+        hunk_header: commit.to_string(),
+        ..fb
+    })
+}
+
+fn parse_note(content: &str, file_path: &str, kind: FeedbackKind) -> Option<HunkFeedback> {
+    let text = parse_summary(content)?;
+    let (severity, text) = match Severity::strip_prefix(&text) {
+        Some((level, rest)) => (Some(level), rest.to_string()),
+        None => (None, text),
+    };
+    if text.is_empty() {
+        return None;
+    }
+    Some(HunkFeedback {
+        file_path: file_path.to_string(),
+        hunk_header: String::new(),
+        kind,
+        content: text,
+        context_lines: vec![],
+        comment_positions: vec![],
+        suggestion: None,
+        severity,
+    })
+}
+
+/// Turn the content of an editor tempfile into feedback for the hunk it was
+/// prepared for. `original_content` is the tempfile content before editing;
+/// `is_comment` selects [`parse_comment_result`] over the edit parsers.
+/// Returns `None` if nothing was changed.
+pub fn parse_editor_result(
+    original_content: &str,
+    edited: &str,
+    is_comment: bool,
+    file_path: &str,
+    hunk_header: &str,
+    hunk_lines: &[DiffLine],
+) -> Option<HunkFeedback> {
+    if is_comment {
+        parse_comment_result(original_content, edited, file_path, hunk_header, hunk_lines)
+    } else if is_region_edit(original_content) {
+        parse_region_edit_result(original_content, edited, file_path, hunk_header, hunk_lines)
+    } else {
+        let original = extract_new_side_content(hunk_lines);
+        parse_edit_result(&original, edited, file_path, hunk_header, hunk_lines)
+    }
+}
+
+/// The new side of the hunk after an edit: the whole fragment, or the
+/// region between the markers of a whole-file edit. `None` if the markers
+/// were removed.
+pub fn edited_new_side(original_content: &str, edited: &str) -> Option<String> {
+    if is_region_edit(original_content) {
+        extract_edit_region(edited).map(|(_, region)| region)
+    } else {
+        Some(edited.to_string())
+    }
+}
+
 /// Parse the result of an edit operation by diffing original vs edited content.
 pub fn parse_edit_result(
     original: &str,
@@ -200,6 +888,8 @@ pub fn parse_edit_result(
         content: unified,
         context_lines: hunk_lines.to_vec(),
         comment_positions: vec![],
+        suggestion: None,
+        severity: None,
     })
 }
 
@@ -214,7 +904,8 @@ fn lines_match(edited: &str, original: &str) -> bool {
 /// Detects user comments by comparing the original template with the edited
 /// version. Any new line that wasn't in the original template is treated as
 /// a comment. Lines with `# COMMENT:` prefix have the prefix stripped for
-/// backward compatibility.
+/// backward compatibility. Lines inside a [`SUGGESTION_FENCE`] block are kept
+/// verbatim as the suggestion; an unclosed block runs to the end of the file.
 pub fn parse_comment_result(
     original: &str,
     edited: &str,
@@ -248,8 +939,25 @@ pub fn parse_comment_result(
     let mut orig_idx = 0;
     let mut positioned_comments: Vec<(usize, String)> = Vec::new();
     let mut all_comment_text = Vec::new();
+    let mut suggestion: Option<Vec<&str>> = None;
+    let mut in_suggestion = false;
+    let mut severity = None;
 
     for edited_line in &edited_body {
+        if in_suggestion {
+            if edited_line.trim() == "```" {
+                in_suggestion = false;
+            } else if let Some(lines) = suggestion.as_mut() {
+                lines.push(edited_line);
+            }
+            continue;
+        }
+        if edited_line.trim() == SUGGESTION_FENCE {
+            in_suggestion = true;
+            suggestion.get_or_insert_with(Vec::new);
+            continue;
+        }
+
         // Try to match at the current position first
         if orig_idx < original_body.len() && lines_match(edited_line, original_body[orig_idx]) {
             orig_idx += 1;
@@ -274,11 +982,15 @@ pub fn parse_comment_result(
 
         if !matched_ahead && !edited_line.trim().is_empty() {
             // This is a user comment at position orig_idx (after orig_idx-1)
-            let text = if let Some(stripped) = edited_line.strip_prefix("# COMMENT:") {
+            let mut text = if let Some(stripped) = edited_line.strip_prefix("# COMMENT:") {
                 stripped.trim()
             } else {
                 edited_line.trim()
             };
+            if let Some((level, rest)) = Severity::strip_prefix(text) {
+                severity = severity.max(Some(level));
+                text = rest;
+            }
             if !text.is_empty() {
                 // Map orig_idx back to hunk line index.
                 // orig_idx is the count of body lines matched so far,
@@ -290,7 +1002,7 @@ pub fn parse_comment_result(
         }
     }
 
-    if positioned_comments.is_empty() {
+    if positioned_comments.is_empty() && suggestion.is_none() {
         return None;
     }
 
@@ -301,5 +1013,7 @@ pub fn parse_comment_result(
         content: all_comment_text.join("\n"),
         context_lines: hunk_lines.to_vec(),
         comment_positions: positioned_comments,
+        suggestion: suggestion.map(|lines| lines.join("\n")),
+        severity,
     })
 }
@@ -0,0 +1,270 @@
+//! Edit feedback as a patch against the working tree (`--edits-patch`,
+//! `:patch`), for `git apply` instead of typing the edits in again.
+//!
+//! The `@@` headers of an edit count lines from the start of its hunk's new
+//! side, or from the start of the file for an edit of the whole file. Each
+//! edit is placed where its old lines match the file in the working tree,
+//! so the patch applies to the working tree as it is now, or not at all.
+//! Edits of files that aren't UTF-8 text are left out of the patch.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use similar::TextDiff;
+
+use crate::patch::parse_hunk_header;
+use crate::types::{FeedbackKind, HunkFeedback, LineKind};
+
+/// A hunk of an edit, placed in the working tree file.
+struct PlacedHunk<'a> {
+    /// First old line (1-based), or the line inserted after when
+    /// `old_count` is 0.
+    old_start: u32,
+    old_count: u32,
+    body: Vec<&'a str>,
+}
+
+/// A patch of edits, see [`edits_patch`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct EditsPatch {
+    /// The unified diff, empty without edits.
+    pub patch: String,
+    /// Files whose edits were left out because they aren't UTF-8.
+    pub skipped: Vec<String>,
+}
+
+/// The edits in `feedbacks` as a unified diff against the files under
+/// `root`. Fails if an edit no longer matches its file or two edits
+/// overlap.
+pub fn edits_patch(root: &Path, feedbacks: &[HunkFeedback]) -> Result<EditsPatch> {
+    let mut by_file: BTreeMap<&str, Vec<&HunkFeedback>> = BTreeMap::new();
+    for fb in feedbacks.iter().filter(|fb| fb.kind == FeedbackKind::Edit) {
+        by_file.entry(&fb.file_path).or_default().push(fb);
+    }
+
+    let mut output = EditsPatch::default();
+    for (path, edits) in by_file {
+        let bytes =
+            std::fs::read(root.join(path)).with_context(|| format!("Failed to read {}", path))?;
+        let Ok(content) = String::from_utf8(bytes) else {
+            output.skipped.push(path.to_string());
+            continue;
+        };
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+
+        let mut hunks = Vec::new();
+        for fb in edits {
+            hunks.extend(place_edit(fb, &lines)?);
+        }
+        hunks.sort_by_key(|h| h.old_start);
+        for pair in hunks.windows(2) {
+            if pair[1].old_start < pair[0].old_start + pair[0].old_count {
+                bail!(
+                    "Edits of {} overlap around line {}",
+                    path,
+                    pair[1].old_start
+                );
+            }
+        }
+
+        // Diffed again rather than copied, so that hunks have the usual
+        // context on both sides, which `git apply` needs to place them
+        let edited = apply_hunks(&lines, &hunks);
+        let diff = TextDiff::from_lines(content.as_str(), edited.as_str());
+        let hunks: String = diff
+            .unified_diff()
+            .iter_hunks()
+            .map(|hunk| hunk.to_string())
+            .collect();
+        if !hunks.is_empty() {
+            output.patch.push_str(&format!(
+                "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n{1}",
+                path, hunks
+            ));
+        }
+    }
+    Ok(output)
+}
+
+/// The file's `lines` with the placed `hunks`, in line order, applied.
+fn apply_hunks(lines: &[&str], hunks: &[PlacedHunk]) -> String {
+    let mut edited = String::new();
+    let mut next = 0;
+    let mut last_added = false;
+    for hunk in hunks {
+        let first = match hunk.old_count {
+            0 => hunk.old_start as usize,
+            _ => hunk.old_start as usize - 1,
+        };
+        edited.extend(lines[next..first].iter().copied());
+        next = first;
+        for line in &hunk.body {
+            match line.as_bytes().first() {
+                Some(b' ') => {
+                    edited.push_str(lines[next]);
+                    next += 1;
+                }
+                Some(b'-') => next += 1,
+                Some(b'+') => edited.push_str(&line[1..]),
+                // `\ No newline at end of file` after an added line
+                Some(b'\\') if last_added && edited.ends_with('\n') => {
+                    edited.pop();
+                }
+                _ => {}
+            }
+            last_added = line.starts_with('+');
+        }
+    }
+    edited.extend(lines[next..].iter().copied());
+    edited
+}
+
+/// The hunks of edit `fb`, placed in the file's `lines`: relative to its
+/// hunk's new side, or to the file.
+fn place_edit<'a>(fb: &'a HunkFeedback, lines: &[&str]) -> Result<Vec<PlacedHunk<'a>>> {
+    let hunks = split_hunks(&fb.content)?;
+    let fragment_start = fb
+        .context_lines
+        .iter()
+        .filter(|l| l.kind != LineKind::Removed)
+        .find_map(|l| l.new_lineno);
+    let offsets = fragment_start.map(|start| start - 1).into_iter().chain([0]);
+    for offset in offsets {
+        let placed: Vec<PlacedHunk> = hunks
+            .iter()
+            .map(|(old_start, old_count, body)| PlacedHunk {
+                old_start: old_start + offset,
+                old_count: *old_count,
+                body: body.clone(),
+            })
+            .collect();
+        if placed.iter().all(|hunk| matches_file(hunk, lines)) {
+            return Ok(placed);
+        }
+    }
+    bail!(
+        "The edit of {} at {} no longer matches the working tree",
+        fb.file_path,
+        fb.hunk_header
+    )
+}
+
+/// A hunk of a unified diff fragment: old start, old count and the body
+/// lines.
+type FragmentHunk<'a> = (u32, u32, Vec<&'a str>);
+
+fn split_hunks(content: &str) -> Result<Vec<FragmentHunk<'_>>> {
+    let mut hunks: Vec<FragmentHunk> = Vec::new();
+    for line in content.split_inclusive('\n') {
+        if line.starts_with("@@") {
+            let (old_start, old_count, _, _, _) = parse_hunk_header(line)?;
+            hunks.push((old_start, old_count, Vec::new()));
+        } else if let Some((_, _, body)) = hunks.last_mut() {
+            body.push(line);
+        }
+    }
+    Ok(hunks)
+}
+
+/// Whether the context and removed lines of `hunk` are the file's lines
+/// where it is placed.
+fn matches_file(hunk: &PlacedHunk, lines: &[&str]) -> bool {
+    let old_lines = hunk
+        .body
+        .iter()
+        .filter(|l| l.starts_with([' ', '-']))
+        .map(|l| l[1..].trim_end_matches('\n'));
+    let first = (hunk.old_start as usize).saturating_sub(1);
+    old_lines.enumerate().all(|(i, old)| {
+        lines
+            .get(first + i)
+            .is_some_and(|line| line.trim_end_matches('\n') == old)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DiffLine;
+
+    fn line(kind: LineKind, content: &str, old: Option<u32>, new: Option<u32>) -> DiffLine {
+        DiffLine {
+            kind,
+            content: format!("{}\n", content),
+            old_lineno: old,
+            new_lineno: new,
+            raw: None,
+            no_newline: false,
+        }
+    }
+
+    fn edit(content: &str, context_lines: Vec<DiffLine>) -> HunkFeedback {
+        HunkFeedback {
+            file_path: "a.txt".to_string(),
+            hunk_header: "@@ -4,2 +4,3 @@".to_string(),
+            kind: FeedbackKind::Edit,
+            content: content.to_string(),
+            context_lines,
+            comment_positions: vec![],
+            suggestion: None,
+            severity: None,
+        }
+    }
+
+    #[test]
+    fn test_edits_are_placed_in_the_working_tree_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "1\n2\n3\n4\nfive\n6\n7\n8\n").unwrap();
+
+        // An edit of the hunk's new side, which starts at line 4
+        let hunk_edit = edit(
+            "@@ -1,3 +1,3 @@\n 4\n-five\n+5\n 6\n",
+            vec![
+                line(LineKind::Context, "4", Some(4), Some(4)),
+                line(LineKind::Added, "five", None, Some(5)),
+                line(LineKind::Context, "6", Some(5), Some(6)),
+            ],
+        );
+        // An edit of the whole file, numbered from its start
+        let file_edit = edit("@@ -1,1 +1,2 @@\n 1\n+1.5\n", vec![]);
+
+        let patch = edits_patch(dir.path(), &[hunk_edit.clone(), file_edit]).unwrap();
+        assert!(patch.skipped.is_empty());
+        assert_eq!(
+            patch.patch,
+            "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n\
+             @@ -1,8 +1,9 @@\n 1\n+1.5\n 2\n 3\n 4\n-five\n+5\n 6\n 7\n 8\n"
+        );
+
+        // The working tree moved on: the edit no longer applies
+        std::fs::write(dir.path().join("a.txt"), "1\n2\n3\n4\n5\n6\n").unwrap();
+        let err = edits_patch(dir.path(), &[hunk_edit]).unwrap_err();
+        assert!(err.to_string().contains("no longer matches"), "{}", err);
+    }
+
+    #[test]
+    fn test_no_edits_no_patch() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(edits_patch(dir.path(), &[]).unwrap(), EditsPatch::default());
+    }
+
+    #[test]
+    fn test_files_that_are_not_utf8_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), b"caf\xe9\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "1\n").unwrap();
+
+        let mut latin1 = edit("@@ -1,1 +1,1 @@\n-caf\u{fffd}\n+cafe\n", vec![]);
+        latin1.file_path = "a.txt".to_string();
+        let mut utf8 = edit("@@ -1,1 +1,1 @@\n-1\n+one\n", vec![]);
+        utf8.file_path = "b.txt".to_string();
+
+        let patch = edits_patch(dir.path(), &[latin1, utf8]).unwrap();
+        assert_eq!(patch.skipped, vec!["a.txt"]);
+        assert_eq!(
+            patch.patch,
+            "diff --git a/b.txt b/b.txt\n--- a/b.txt\n+++ b/b.txt\n@@ -1 +1 @@\n-1\n+one\n"
+        );
+    }
+}
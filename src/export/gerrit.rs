@@ -0,0 +1,225 @@
+//! Feedback as a Gerrit `ReviewInput` (`--format gerrit`).
+//!
+//! The output can be posted to Gerrit's set-review endpoint as is:
+//! comments are grouped by file, each with the new-side line it refers to
+//! (see [`new_side_line`]). Suggestions and edits are attached to the last
+//! line of their hunk, and file comments have no line. Comments on the
+//! review as a whole and on commits go in the review message. A review
+//! verdict becomes a `Code-Review` vote and its summary heads the review
+//! message.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use super::{hunk_end_line, labeled, new_side_line};
+use crate::types::{FeedbackKind, HunkFeedback, ReviewVerdict, Verdict};
+
+/// A comment on one line of a file, or on the whole file without a line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommentInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+/// Gerrit's review input: comments keyed by file path, plus the review
+/// message and label votes.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReviewInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, i8>,
+    pub comments: BTreeMap<String, Vec<CommentInput>>,
+}
+
+/// Map feedback to Gerrit comments. Feedback whose lines can't be
+/// determined is left out.
+pub fn review_input(feedbacks: &[HunkFeedback]) -> ReviewInput {
+    let mut input = ReviewInput {
+        message: super::overall_message(feedbacks),
+        ..ReviewInput::default()
+    };
+    for fb in feedbacks.iter().filter(|fb| fb.kind.has_file()) {
+        let comments = input.comments.entry(fb.file_path.clone()).or_default();
+        let mut push = |line: Option<u32>, message: String| {
+            if line.is_some() {
+                comments.push(CommentInput { line, message });
+            }
+        };
+        match fb.kind {
+            FeedbackKind::ReviewComment | FeedbackKind::CommitComment => {}
+            FeedbackKind::FileComment => comments.push(CommentInput {
+                line: None,
+                message: labeled(fb, &fb.content),
+            }),
+            FeedbackKind::Comment => {
+                if fb.comment_positions.is_empty() && !fb.content.is_empty() {
+                    push(hunk_end_line(fb), labeled(fb, &fb.content));
+                }
+                for (position, text) in &fb.comment_positions {
+                    push(new_side_line(fb, *position), labeled(fb, text));
+                }
+                if let Some(ref suggestion) = fb.suggestion {
+                    push(
+                        hunk_end_line(fb),
+                        format!("Suggested replacement:\n```\n{}\n```", suggestion),
+                    );
+                }
+            }
+            FeedbackKind::Edit => push(
+                hunk_end_line(fb),
+                format!(
+                    "Suggested edit:\n```\n{}\n```",
+                    fb.content.trim_end_matches('\n')
+                ),
+            ),
+        }
+    }
+    input.comments.retain(|_, comments| !comments.is_empty());
+    input
+}
+
+/// Add a review verdict to review input: approving votes `Code-Review`
+/// +1, requesting changes -1, and the summary heads the review message.
+pub fn apply_verdict(input: &mut ReviewInput, verdict: &ReviewVerdict) {
+    let vote = match verdict.verdict {
+        Verdict::Approve => Some(1),
+        Verdict::RequestChanges => Some(-1),
+        Verdict::Comment => None,
+    };
+    if let Some(vote) = vote {
+        input.labels.insert("Code-Review".to_string(), vote);
+    }
+    input.message = match (verdict.summary.clone(), input.message.take()) {
+        (Some(summary), Some(overall)) => Some(format!("{}\n\n{}", summary, overall)),
+        (summary, overall) => summary.or(overall),
+    };
+}
+
+/// Format feedback, and the review `verdict` if given, as pretty-printed
+/// Gerrit review input JSON.
+pub fn format_feedback_gerrit(
+    feedbacks: &[HunkFeedback],
+    verdict: Option<&ReviewVerdict>,
+) -> Result<String> {
+    if feedbacks.is_empty() {
+        return Ok(String::new());
+    }
+    let mut input = review_input(feedbacks);
+    if let Some(verdict) = verdict {
+        apply_verdict(&mut input, verdict);
+    }
+    let mut json = serde_json::to_string_pretty(&input).context("Failed to serialize feedback")?;
+    json.push('\n');
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::line;
+    use crate::types::LineKind;
+
+    #[test]
+    fn test_comments_grouped_by_file_on_new_side_lines() {
+        let feedbacks = vec![
+            HunkFeedback {
+                file_path: "src/b.rs".to_string(),
+                hunk_header: "@@ -10,2 +10,2 @@".to_string(),
+                kind: FeedbackKind::Comment,
+                content: "why remove?\nok".to_string(),
+                context_lines: vec![
+                    line(LineKind::Context, Some(10), Some(10)),
+                    line(LineKind::Removed, Some(11), None),
+                    line(LineKind::Added, None, Some(11)),
+                ],
+                comment_positions: vec![(2, "why remove?".to_string()), (3, "ok".to_string())],
+                suggestion: Some("y".to_string()),
+                severity: None,
+            },
+            HunkFeedback {
+                file_path: "src/a.rs".to_string(),
+                hunk_header: "@@ -1,2 +1,3 @@".to_string(),
+                kind: FeedbackKind::Edit,
+                content: "-a\n+b\n".to_string(),
+                context_lines: vec![],
+                comment_positions: vec![],
+                suggestion: None,
+                severity: None,
+            },
+            HunkFeedback {
+                file_path: "src/a.rs".to_string(),
+                hunk_header: String::new(),
+                kind: FeedbackKind::FileComment,
+                content: "Rename this module".to_string(),
+                context_lines: vec![],
+                comment_positions: vec![],
+                suggestion: None,
+                severity: None,
+            },
+        ];
+
+        let json: serde_json::Value =
+            serde_json::from_str(&format_feedback_gerrit(&feedbacks, None).unwrap()).unwrap();
+        let comments = json["comments"].as_object().unwrap();
+        assert_eq!(
+            comments.keys().collect::<Vec<_>>(),
+            vec!["src/a.rs", "src/b.rs"]
+        );
+
+        let b = &comments["src/b.rs"];
+        assert_eq!(b[0]["line"], 10);
+        assert_eq!(b[0]["message"], "why remove?");
+        assert_eq!(b[1]["line"], 11);
+        assert_eq!(b[2]["message"], "Suggested replacement:\n```\ny\n```");
+
+        let a = &comments["src/a.rs"];
+        assert_eq!(a[0]["line"], 3);
+        assert!(a[1].get("line").is_none());
+        assert_eq!(a[1]["message"], "Rename this module");
+        assert_eq!(a[0]["message"], "Suggested edit:\n```\n-a\n+b\n```");
+
+        assert!(json.get("labels").is_none());
+        assert_eq!(format_feedback_gerrit(&[], None).unwrap(), "");
+
+        let verdict = ReviewVerdict {
+            verdict: Verdict::RequestChanges,
+            summary: Some("Needs error handling".to_string()),
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&format_feedback_gerrit(&feedbacks, Some(&verdict)).unwrap())
+                .unwrap();
+        assert_eq!(json["labels"]["Code-Review"], -1);
+        assert_eq!(json["message"], "Needs error handling");
+    }
+
+    #[test]
+    fn test_review_comment_is_the_message() {
+        let feedbacks = vec![HunkFeedback {
+            file_path: String::new(),
+            hunk_header: String::new(),
+            kind: FeedbackKind::ReviewComment,
+            content: "Good direction".to_string(),
+            context_lines: vec![],
+            comment_positions: vec![],
+            suggestion: None,
+            severity: None,
+        }];
+        let input = review_input(&feedbacks);
+        assert_eq!(input.message.as_deref(), Some("Good direction"));
+        assert!(input.comments.is_empty());
+
+        let mut input = review_input(&feedbacks);
+        apply_verdict(
+            &mut input,
+            &ReviewVerdict {
+                verdict: Verdict::Approve,
+                summary: Some("LGTM".to_string()),
+            },
+        );
+        assert_eq!(input.message.as_deref(), Some("LGTM\n\nGood direction"));
+    }
+}
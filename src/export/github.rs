@@ -0,0 +1,367 @@
+//! Publishing feedback as a GitHub pull request review (`--publish`).
+//!
+//! Each comment becomes a review comment on the line it was placed after.
+//! Suggestions become ```` ```suggestion ```` comments on the lines the hunk
+//! added, and edits are attached to the end of their hunk as a `diff` block.
+//! Comments on the review as a whole, on commits and on whole files go in
+//! the review's body, since a review comment must be on a line. The review
+//! is created pending, so it can be looked over on GitHub before it is
+//! submitted.
+//!
+//! Requests go through `curl`. The token is read from `GITHUB_TOKEN` or
+//! `GH_TOKEN`, and `GITHUB_API_URL` points at a GitHub Enterprise server.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+
+use super::{Side, added_range, comment_line, hunk_end_line, labeled};
+use crate::types::{FeedbackKind, HunkFeedback};
+
+/// Prefix of a `--publish` target for GitHub.
+pub const TARGET_PREFIX: &str = "github:";
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// A pull request, parsed from `github:<owner>/<repo>#<number>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullRequest {
+    pub owner: String,
+    pub repo: String,
+    pub number: u64,
+}
+
+impl PullRequest {
+    pub fn parse(target: &str) -> Result<Self> {
+        let usage = || {
+            format!(
+                "Invalid publish target '{}' (expected github:<owner>/<repo>#<pr>)",
+                target
+            )
+        };
+        let rest = target.strip_prefix(TARGET_PREFIX).with_context(usage)?;
+        let (name, number) = rest.split_once('#').with_context(usage)?;
+        let (owner, repo) = name.split_once('/').with_context(usage)?;
+        let number = number.parse().ok().with_context(usage)?;
+        if owner.is_empty() || repo.is_empty() || repo.contains('/') {
+            bail!(usage());
+        }
+        Ok(Self {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            number,
+        })
+    }
+
+    /// Endpoint for creating a review on the pull request.
+    pub fn reviews_url(&self, api_url: &str) -> String {
+        format!(
+            "{}/repos/{}/{}/pulls/{}/reviews",
+            api_url.trim_end_matches('/'),
+            self.owner,
+            self.repo,
+            self.number
+        )
+    }
+}
+
+/// Side of the diff in GitHub's terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ReviewSide {
+    Left,
+    Right,
+}
+
+impl From<Side> for ReviewSide {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Old => ReviewSide::Left,
+            Side::New => ReviewSide::Right,
+        }
+    }
+}
+
+/// One comment of a review. `start_line` is set for comments spanning
+/// several lines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReviewComment {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_side: Option<ReviewSide>,
+    pub line: u32,
+    pub side: ReviewSide,
+    pub body: String,
+}
+
+/// Body of the create-review request. Without an `event` the review is
+/// left pending. `body` carries the comments on the review as a whole, on
+/// commits and on whole files.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    pub comments: Vec<ReviewComment>,
+}
+
+/// Map feedback to review comments. Review, commit and file comments,
+/// which go in the review's body, and feedback whose lines can't be
+/// determined are left out.
+pub fn review_comments(feedbacks: &[HunkFeedback]) -> Vec<ReviewComment> {
+    let mut comments = Vec::new();
+    for fb in feedbacks {
+        let comment = |line: u32, side: Side, body: String| ReviewComment {
+            path: fb.file_path.clone(),
+            start_line: None,
+            start_side: None,
+            line,
+            side: side.into(),
+            body,
+        };
+        match fb.kind {
+            FeedbackKind::ReviewComment
+            | FeedbackKind::CommitComment
+            | FeedbackKind::FileComment => {}
+            FeedbackKind::Comment => {
+                if fb.comment_positions.is_empty()
+                    && !fb.content.is_empty()
+                    && let Some(line) = hunk_end_line(fb)
+                {
+                    comments.push(comment(line, Side::New, labeled(fb, &fb.content)));
+                }
+                for (position, text) in &fb.comment_positions {
+                    if let Some((side, line)) = comment_line(fb, *position) {
+                        comments.push(comment(line, side, labeled(fb, text)));
+                    }
+                }
+                if let Some(ref suggestion) = fb.suggestion {
+                    let body = format!("```suggestion\n{}\n```", suggestion);
+                    match added_range(fb) {
+                        Some((first, last)) => comments.push(ReviewComment {
+                            start_line: (first < last).then_some(first),
+                            start_side: (first < last).then_some(ReviewSide::Right),
+                            ..comment(last, Side::New, body)
+                        }),
+                        None => {
+                            if let Some(line) = hunk_end_line(fb) {
+                                comments.push(comment(line, Side::New, body));
+                            }
+                        }
+                    }
+                }
+            }
+            FeedbackKind::Edit => {
+                if let Some(line) = hunk_end_line(fb) {
+                    let body = format!(
+                        "Suggested edit:\n```diff\n{}\n```",
+                        fb.content.trim_end_matches('\n')
+                    );
+                    comments.push(comment(line, Side::New, body));
+                }
+            }
+        }
+    }
+    comments
+}
+
+/// JSON payload creating a pending review with the feedback.
+pub fn review_payload(feedbacks: &[HunkFeedback]) -> Result<String> {
+    let files = feedbacks
+        .iter()
+        .filter(|fb| fb.kind == FeedbackKind::FileComment)
+        .map(|fb| format!("`{}`: {}", fb.file_path, labeled(fb, &fb.content)));
+    let body: Vec<String> = super::overall_message(feedbacks)
+        .into_iter()
+        .chain(files)
+        .collect();
+    let request = ReviewRequest {
+        body: (!body.is_empty()).then(|| body.join("\n\n")),
+        comments: review_comments(feedbacks),
+    };
+    serde_json::to_string_pretty(&request).context("Failed to serialize review")
+}
+
+/// API base URL, from `GITHUB_API_URL` or the public API.
+pub fn api_url() -> String {
+    std::env::var("GITHUB_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string())
+}
+
+/// Token from `GITHUB_TOKEN` or `GH_TOKEN`.
+pub fn token() -> Result<String> {
+    std::env::var("GITHUB_TOKEN")
+        .or_else(|_| std::env::var("GH_TOKEN"))
+        .ok()
+        .filter(|t| !t.is_empty())
+        .context("Publishing to GitHub needs a token in GITHUB_TOKEN or GH_TOKEN")
+}
+
+/// Create a pending review on `pr` with the feedback. Returns the review's
+/// URL.
+pub fn publish(pr: &PullRequest, feedbacks: &[HunkFeedback]) -> Result<String> {
+    let token = token()?;
+    let payload = review_payload(feedbacks)?;
+    let mut body = tempfile::NamedTempFile::new().context("Failed to create temp file")?;
+    body.write_all(payload.as_bytes())?;
+    body.flush()?;
+
+    // The token goes in through a config on stdin, not the command line
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail-with-body"])
+        .args(["--config", "-", "--data-binary"])
+        .arg(format!("@{}", body.path().display()))
+        .arg(pr.reviews_url(&api_url()))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        write!(
+            stdin,
+            "header = \"Authorization: Bearer {}\"\n\
+             header = \"Accept: application/vnd.github+json\"\n\
+             header = \"X-GitHub-Api-Version: 2022-11-28\"\n",
+            token
+        )?;
+    }
+    let output = child.wait_with_output().context("Failed to run curl")?;
+    let response = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        bail!(
+            "GitHub rejected the review: {}{}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+            response.trim()
+        );
+    }
+
+    let review: serde_json::Value =
+        serde_json::from_str(&response).context("Unexpected response from GitHub")?;
+    Ok(review["html_url"].as_str().unwrap_or_default().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::line;
+    use crate::types::{DiffLine, LineKind, Severity};
+
+    fn hunk_lines() -> Vec<DiffLine> {
+        vec![
+            line(LineKind::Context, Some(10), Some(10)),
+            line(LineKind::Removed, Some(11), None),
+            line(LineKind::Added, None, Some(11)),
+            line(LineKind::Added, None, Some(12)),
+        ]
+    }
+
+    #[test]
+    fn test_parse_target() {
+        let pr = PullRequest::parse("github:kimpers/stagent#42").unwrap();
+        assert_eq!(
+            pr,
+            PullRequest {
+                owner: "kimpers".to_string(),
+                repo: "stagent".to_string(),
+                number: 42,
+            }
+        );
+        assert_eq!(
+            pr.reviews_url("https://api.github.com/"),
+            "https://api.github.com/repos/kimpers/stagent/pulls/42/reviews"
+        );
+        for bad in [
+            "kimpers/stagent#42",
+            "github:kimpers#42",
+            "github:kimpers/stagent",
+            "github:kimpers/stagent#pr",
+            "github:/stagent#1",
+        ] {
+            assert!(PullRequest::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_review_comments() {
+        let feedbacks = vec![
+            HunkFeedback {
+                file_path: "src/a.rs".to_string(),
+                hunk_header: "@@ -10,2 +10,3 @@".to_string(),
+                kind: FeedbackKind::Comment,
+                content: "why remove?\nnice".to_string(),
+                context_lines: hunk_lines(),
+                comment_positions: vec![(2, "why remove?".to_string()), (4, "nice".to_string())],
+                suggestion: Some("    better();".to_string()),
+                severity: Some(Severity::Issue),
+            },
+            HunkFeedback {
+                file_path: "src/b.rs".to_string(),
+                hunk_header: "@@ -1,2 +1,2 @@".to_string(),
+                kind: FeedbackKind::Edit,
+                content: "-a\n+b\n".to_string(),
+                context_lines: vec![],
+                comment_positions: vec![],
+                suggestion: None,
+                severity: None,
+            },
+            HunkFeedback {
+                file_path: "src/b.rs".to_string(),
+                hunk_header: String::new(),
+                kind: FeedbackKind::FileComment,
+                content: "Split this file".to_string(),
+                context_lines: vec![],
+                comment_positions: vec![],
+                suggestion: None,
+                severity: None,
+            },
+        ];
+
+        let comments = review_comments(&feedbacks);
+        let summary: Vec<_> = comments
+            .iter()
+            .map(|c| (c.path.as_str(), c.start_line, c.line, c.side))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("src/a.rs", None, 11, ReviewSide::Left),
+                ("src/a.rs", None, 12, ReviewSide::Right),
+                ("src/a.rs", Some(11), 12, ReviewSide::Right),
+                ("src/b.rs", None, 2, ReviewSide::Right),
+            ]
+        );
+        assert_eq!(comments[0].body, "[issue] why remove?");
+        assert_eq!(comments[2].body, "```suggestion\n    better();\n```");
+        assert_eq!(comments[3].body, "Suggested edit:\n```diff\n-a\n+b\n```");
+
+        let payload: serde_json::Value =
+            serde_json::from_str(&review_payload(&feedbacks).unwrap()).unwrap();
+        assert!(payload.get("event").is_none(), "the review stays pending");
+        assert_eq!(payload["comments"][0]["side"], "LEFT");
+        assert!(payload["comments"][0].get("start_line").is_none());
+        assert_eq!(payload["comments"][2]["start_side"], "RIGHT");
+        // The file comment has no line to go on
+        assert_eq!(payload["body"], "`src/b.rs`: Split this file");
+    }
+
+    #[test]
+    fn test_review_comment_is_the_body() {
+        let feedbacks = vec![HunkFeedback {
+            file_path: String::new(),
+            hunk_header: String::new(),
+            kind: FeedbackKind::ReviewComment,
+            content: "Good direction".to_string(),
+            context_lines: vec![],
+            comment_positions: vec![],
+            suggestion: None,
+            severity: Some(Severity::Suggestion),
+        }];
+        assert!(review_comments(&feedbacks).is_empty());
+        let payload: serde_json::Value =
+            serde_json::from_str(&review_payload(&feedbacks).unwrap()).unwrap();
+        assert_eq!(payload["body"], "[suggestion] Good direction");
+    }
+}
@@ -0,0 +1,157 @@
+//! Exporting feedback to code review services.
+//!
+//! Comments in [`HunkFeedback`] are positioned by index into the hunk's diff
+//! lines; review services want file line numbers. The helpers here do that
+//! mapping for the individual exporters. [`edits`] turns edits into a patch
+//! for the working tree instead.
+
+pub mod edits;
+pub mod gerrit;
+pub mod github;
+
+use crate::patch::parse_hunk_header;
+use crate::types::{DiffLine, FeedbackKind, HunkFeedback, LineKind};
+
+/// Which version of the file a line number refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The file before the change (removed lines).
+    Old,
+    /// The file after the change (added and context lines).
+    New,
+}
+
+/// The diff line a comment at `position` was placed after; the first line
+/// for a comment at the top of the hunk.
+fn anchor(fb: &HunkFeedback, position: usize) -> Option<&DiffLine> {
+    fb.context_lines.get(position.saturating_sub(1))
+}
+
+/// File line a comment at `position` refers to: the line it was placed
+/// after, on the old side for a removed line. Falls back to
+/// [`hunk_end_line`] when the hunk lines are unknown.
+pub fn comment_line(fb: &HunkFeedback, position: usize) -> Option<(Side, u32)> {
+    match anchor(fb, position) {
+        Some(line) if line.kind == LineKind::Removed => line.old_lineno.map(|n| (Side::Old, n)),
+        Some(line) => line.new_lineno.map(|n| (Side::New, n)),
+        None => hunk_end_line(fb).map(|n| (Side::New, n)),
+    }
+}
+
+/// New-file line a comment at `position` refers to. A comment after a
+/// removed line goes to the closest new-side line before it, or after it at
+/// the top of a hunk.
+pub fn new_side_line(fb: &HunkFeedback, position: usize) -> Option<u32> {
+    let split = position.min(fb.context_lines.len());
+    let (before, after) = fb.context_lines.split_at(split);
+    before
+        .iter()
+        .rev()
+        .find_map(|l| l.new_lineno)
+        .or_else(|| after.iter().find_map(|l| l.new_lineno))
+        .or_else(|| hunk_end_line(fb))
+}
+
+/// Last line of the hunk in the new file, for feedback on the hunk as a
+/// whole. Taken from the hunk lines, or the `@@` header without them.
+pub fn hunk_end_line(fb: &HunkFeedback) -> Option<u32> {
+    fb.context_lines
+        .iter()
+        .filter_map(|l| l.new_lineno)
+        .max()
+        .or_else(|| {
+            let (_, _, start, count, _) = parse_hunk_header(&fb.hunk_header).ok()?;
+            Some((start + count).saturating_sub(1).max(start))
+        })
+}
+
+/// Comment `text` labeled with the feedback's severity, e.g.
+/// `[blocker] text`, so it stands out in the review service.
+pub fn labeled(fb: &HunkFeedback, text: &str) -> String {
+    match fb.severity {
+        Some(severity) => format!("[{}] {}", severity.label(), text),
+        None => text.to_string(),
+    }
+}
+
+/// The review's message from the comments on the review as a whole, then
+/// on commits, each headed by its commit. `None` without either.
+pub fn overall_message(feedbacks: &[HunkFeedback]) -> Option<String> {
+    let overall = feedbacks
+        .iter()
+        .filter(|fb| fb.kind == FeedbackKind::ReviewComment)
+        .map(|fb| labeled(fb, &fb.content));
+    let commits = feedbacks
+        .iter()
+        .filter(|fb| fb.kind == FeedbackKind::CommitComment)
+        .map(|fb| format!("Commit {}:\n{}", fb.hunk_header, labeled(fb, &fb.content)));
+    let message: Vec<String> = overall.chain(commits).collect();
+    (!message.is_empty()).then(|| message.join("\n\n"))
+}
+
+/// First and last line the hunk added to the new file, if it added any.
+pub fn added_range(fb: &HunkFeedback) -> Option<(u32, u32)> {
+    let mut added = fb
+        .context_lines
+        .iter()
+        .filter(|l| l.kind == LineKind::Added)
+        .filter_map(|l| l.new_lineno);
+    let first = added.next()?;
+    Some((first, added.next_back().unwrap_or(first)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FeedbackKind;
+
+    /// A diff line with the given line numbers, shared by the exporters' tests.
+    pub(super) fn line(kind: LineKind, old: Option<u32>, new: Option<u32>) -> DiffLine {
+        DiffLine {
+            kind,
+            content: "x\n".to_string(),
+            old_lineno: old,
+            new_lineno: new,
+            raw: None,
+            no_newline: false,
+        }
+    }
+
+    fn feedback(context_lines: Vec<DiffLine>) -> HunkFeedback {
+        HunkFeedback {
+            file_path: "src/a.rs".to_string(),
+            hunk_header: "@@ -10,3 +10,4 @@".to_string(),
+            kind: FeedbackKind::Comment,
+            content: String::new(),
+            context_lines,
+            comment_positions: vec![],
+            suggestion: None,
+            severity: None,
+        }
+    }
+
+    #[test]
+    fn test_line_mapping() {
+        let fb = feedback(vec![
+            line(LineKind::Context, Some(10), Some(10)),
+            line(LineKind::Removed, Some(11), None),
+            line(LineKind::Added, None, Some(11)),
+            line(LineKind::Added, None, Some(12)),
+            line(LineKind::Context, Some(12), Some(13)),
+        ]);
+        assert_eq!(comment_line(&fb, 0), Some((Side::New, 10)));
+        assert_eq!(comment_line(&fb, 2), Some((Side::Old, 11)));
+        assert_eq!(comment_line(&fb, 4), Some((Side::New, 12)));
+        assert_eq!(new_side_line(&fb, 0), Some(10));
+        assert_eq!(new_side_line(&fb, 2), Some(10));
+        assert_eq!(new_side_line(&fb, 4), Some(12));
+        assert_eq!(hunk_end_line(&fb), Some(13));
+        assert_eq!(added_range(&fb), Some((11, 12)));
+
+        // Without hunk lines, the header gives the hunk's extent
+        let fb = feedback(vec![]);
+        assert_eq!(comment_line(&fb, 1), Some((Side::New, 13)));
+        assert_eq!(new_side_line(&fb, 1), Some(13));
+        assert_eq!(added_range(&fb), None);
+    }
+}
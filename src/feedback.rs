@@ -1,9 +1,15 @@
 use anyhow::{Context, Result};
+use serde::Serialize;
 use std::collections::BTreeMap;
 use std::io::Write;
 use std::path::Path;
 
-use crate::types::{FeedbackKind, HunkFeedback};
+use crate::export::{self, Side};
+use crate::patch::parse_hunk_header;
+use crate::timing::{self, ReviewTiming};
+use crate::types::{
+    FeedbackFormat, FeedbackKind, HunkFeedback, LineKind, OutputStyle, ReviewVerdict,
+};
 
 /// Default number of context lines to show around changes in comment feedback.
 pub const DEFAULT_CONTEXT_LINES: usize = 5;
@@ -11,26 +17,96 @@ pub const DEFAULT_CONTEXT_LINES: usize = 5;
 /// Format all feedback as a unified diff string.
 /// `context_count` controls how many surrounding lines to show around
 /// changed lines in comment feedback output.
+///
+/// ```
+/// use stagent::types::{DiffLine, FeedbackKind, HunkFeedback, LineKind};
+///
+/// let line = |kind, content: &str| DiffLine {
+///     kind,
+///     content: format!("{}\n", content),
+///     old_lineno: None,
+///     new_lineno: None,
+///     raw: None,
+///     no_newline: false,
+/// };
+/// let feedback = HunkFeedback {
+///     file_path: "src/lib.rs".to_string(),
+///     hunk_header: "@@ -1 +1 @@".to_string(),
+///     kind: FeedbackKind::Comment,
+///     content: "why?".to_string(),
+///     context_lines: vec![line(LineKind::Removed, "old"), line(LineKind::Added, "new")],
+///     comment_positions: vec![(2, "why?".to_string())],
+///     suggestion: None,
+///     severity: None,
+/// };
+/// assert_eq!(
+///     stagent::feedback::format_feedback(&[feedback], 5),
+///     "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-old\n+new\n# REVIEW COMMENT: why?\n"
+/// );
+/// ```
 pub fn format_feedback(feedbacks: &[HunkFeedback], context_count: usize) -> String {
     if feedbacks.is_empty() {
         return String::new();
     }
 
+    let mut output = String::new();
+
+    // Comments on the review as a whole come first
+    for fb in feedbacks
+        .iter()
+        .filter(|fb| fb.kind == FeedbackKind::ReviewComment)
+    {
+        if let Some(severity) = fb.severity {
+            output.push_str(&format!("# SEVERITY: {}\n", severity.label()));
+        }
+        for line in fb.content.lines() {
+            output.push_str(&format!("# OVERALL COMMENT: {}\n", line));
+        }
+    }
+
+    // Then comments on commits of a `--range` review, under their commit
+    for fb in feedbacks
+        .iter()
+        .filter(|fb| fb.kind == FeedbackKind::CommitComment)
+    {
+        output.push_str(&format!("# COMMIT: {}\n", fb.hunk_header));
+        if let Some(severity) = fb.severity {
+            output.push_str(&format!("# SEVERITY: {}\n", severity.label()));
+        }
+        for line in fb.content.lines() {
+            output.push_str(&format!("# COMMIT COMMENT: {}\n", line));
+        }
+    }
+
     // Group feedback by file path
     let mut by_file: BTreeMap<&str, Vec<&HunkFeedback>> = BTreeMap::new();
-    for fb in feedbacks {
+    for fb in feedbacks.iter().filter(|fb| fb.kind.has_file()) {
         by_file.entry(&fb.file_path).or_default().push(fb);
     }
 
-    let mut output = String::new();
-
     for (file_path, file_feedbacks) in &by_file {
         // File header
         output.push_str(&format!("--- a/{}\n", file_path));
         output.push_str(&format!("+++ b/{}\n", file_path));
 
+        // Comments on the whole file come before its hunks
+        for fb in file_feedbacks
+            .iter()
+            .filter(|fb| fb.kind == FeedbackKind::FileComment)
+        {
+            if let Some(severity) = fb.severity {
+                output.push_str(&format!("# SEVERITY: {}\n", severity.label()));
+            }
+            for line in fb.content.lines() {
+                output.push_str(&format!("# FILE COMMENT: {}\n", line));
+            }
+        }
+
         for fb in file_feedbacks {
             match fb.kind {
+                FeedbackKind::FileComment
+                | FeedbackKind::ReviewComment
+                | FeedbackKind::CommitComment => {}
                 FeedbackKind::Edit => {
                     output.push_str(&format!("{}\n", fb.hunk_header));
                     output.push_str(&fb.content);
@@ -40,9 +116,15 @@ pub fn format_feedback(feedbacks: &[HunkFeedback], context_count: usize) -> Stri
                 }
                 FeedbackKind::Comment => {
                     output.push_str(&format!("{}\n", fb.hunk_header));
+                    if let Some(severity) = fb.severity {
+                        output.push_str(&format!("# SEVERITY: {}\n", severity.label()));
+                    }
                     // Show up to 5 context lines before and after each
                     // changed line so the comment has surrounding diff context.
                     format_comment_with_context(&mut output, fb, context_count);
+                    if let Some(suggestion) = &fb.suggestion {
+                        format_suggestion(&mut output, suggestion);
+                    }
                 }
             }
         }
@@ -51,6 +133,266 @@ pub fn format_feedback(feedbacks: &[HunkFeedback], context_count: usize) -> Stri
     output
 }
 
+/// Format all feedback as a diff that parses and applies as one
+/// (`--output-style strict-diff`): the hunks with feedback, whole, and
+/// after each file's hunks its comments and edits in blocks of `#` lines.
+/// Comments on the review as a whole, then on commits, come first.
+///
+/// ```text
+/// diff --git a/src/lib.rs b/src/lib.rs
+/// --- a/src/lib.rs
+/// +++ b/src/lib.rs
+/// @@ -1 +1 @@
+/// -old
+/// +new
+/// #=== REVIEW COMMENT src/lib.rs line 1 ===
+/// # why?
+/// #=== END ===
+/// ```
+pub fn format_feedback_strict(feedbacks: &[HunkFeedback]) -> String {
+    let mut output = String::new();
+    for fb in feedbacks
+        .iter()
+        .filter(|fb| fb.kind == FeedbackKind::ReviewComment)
+    {
+        push_block(&mut output, "OVERALL COMMENT", fb, &fb.content);
+    }
+    for fb in feedbacks
+        .iter()
+        .filter(|fb| fb.kind == FeedbackKind::CommitComment)
+    {
+        let title = format!("COMMIT COMMENT {}", fb.hunk_header);
+        push_block(&mut output, &title, fb, &fb.content);
+    }
+
+    let mut by_file: BTreeMap<&str, Vec<&HunkFeedback>> = BTreeMap::new();
+    for fb in feedbacks.iter().filter(|fb| fb.kind.has_file()) {
+        by_file.entry(&fb.file_path).or_default().push(fb);
+    }
+
+    for (file_path, mut file_feedbacks) in by_file {
+        // In line order, as a diff has its hunks; file comments first
+        file_feedbacks.sort_by_key(|fb| {
+            parse_hunk_header(&fb.hunk_header)
+                .ok()
+                .filter(|_| fb.kind != FeedbackKind::FileComment)
+                .map(|(old_start, ..)| old_start)
+        });
+
+        let mut hunks = String::new();
+        let mut headers: Vec<&str> = Vec::new();
+        for fb in &file_feedbacks {
+            if fb.kind != FeedbackKind::FileComment && !headers.contains(&fb.hunk_header.as_str()) {
+                headers.push(&fb.hunk_header);
+                push_hunk(&mut hunks, fb);
+            }
+        }
+        if !hunks.is_empty() {
+            output.push_str(&format!(
+                "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n{1}",
+                file_path, hunks
+            ));
+        }
+
+        for fb in file_feedbacks {
+            match fb.kind {
+                FeedbackKind::ReviewComment | FeedbackKind::CommitComment => {}
+                FeedbackKind::FileComment => push_block(
+                    &mut output,
+                    &format!("FILE COMMENT {}", file_path),
+                    fb,
+                    &fb.content,
+                ),
+                FeedbackKind::Edit => push_block(
+                    &mut output,
+                    &format!("EDIT {} {}", file_path, fb.hunk_header),
+                    fb,
+                    &fb.content,
+                ),
+                FeedbackKind::Comment if fb.comment_positions.is_empty() => push_block(
+                    &mut output,
+                    &format!("REVIEW COMMENT {} {}", file_path, fb.hunk_header),
+                    fb,
+                    &fb.content,
+                ),
+                FeedbackKind::Comment => {
+                    for (position, text) in &fb.comment_positions {
+                        let at = match export::comment_line(fb, *position) {
+                            Some((Side::New, line)) => format!(" line {}", line),
+                            Some((Side::Old, line)) => format!(" old line {}", line),
+                            None => String::new(),
+                        };
+                        push_block(
+                            &mut output,
+                            &format!("REVIEW COMMENT {}{}", file_path, at),
+                            fb,
+                            text,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    output
+}
+
+/// Push the hunk `fb` is on, from its diff lines, with the counts of its
+/// `@@` header taken from those lines. Feedback without diff lines or on a
+/// hunk that is not a text change (a mode change, a binary file) has no
+/// hunk to push.
+fn push_hunk(output: &mut String, fb: &HunkFeedback) {
+    let Ok((old_start, _, new_start, _, header)) = parse_hunk_header(&fb.hunk_header) else {
+        return;
+    };
+    if fb.context_lines.is_empty() {
+        return;
+    }
+    // The function name git puts after the ranges, if any
+    let section = header[2..]
+        .split_once("@@")
+        .map(|(_, section)| section)
+        .unwrap_or_default();
+    let count = |kind| fb.context_lines.iter().filter(|l| l.kind != kind).count();
+    output.push_str(&format!(
+        "@@ -{},{} +{},{} @@{}\n",
+        old_start,
+        count(LineKind::Added),
+        new_start,
+        count(LineKind::Removed),
+        section
+    ));
+    for line in &fb.context_lines {
+        output.push_str(&format!(
+            "{}{}\n",
+            line.kind.prefix(),
+            line.content.trim_end_matches('\n')
+        ));
+        if line.no_newline {
+            output.push_str("\\ No newline at end of file\n");
+        }
+    }
+}
+
+/// Push a block of feedback `text` for strict-diff output, with the
+/// severity of `fb` if it has one:
+///
+/// ```text
+/// #=== REVIEW COMMENT src/lib.rs line 12 ===
+/// # SEVERITY: blocker
+/// # text
+/// #=== END ===
+/// ```
+fn push_block(output: &mut String, title: &str, fb: &HunkFeedback, text: &str) {
+    output.push_str(&format!("#=== {} ===\n", title));
+    if let Some(severity) = fb.severity {
+        output.push_str(&format!("# SEVERITY: {}\n", severity.label()));
+    }
+    let mut body = text.to_string();
+    if let Some(suggestion) = &fb.suggestion {
+        format_suggestion(&mut body, suggestion);
+    }
+    for line in body.lines() {
+        match line {
+            "" => output.push_str("#\n"),
+            line => output.push_str(&format!("# {}\n", line)),
+        }
+    }
+    output.push_str("#=== END ===\n");
+}
+
+/// Format all feedback as a pretty-printed JSON array, comments on the
+/// review as a whole and on commits first.
+pub fn format_feedback_json(feedbacks: &[HunkFeedback]) -> Result<String> {
+    if feedbacks.is_empty() {
+        return Ok(String::new());
+    }
+    let mut json = serde_json::to_string_pretty(&overall_first(feedbacks))
+        .context("Failed to serialize feedback")?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// Format all feedback in the given output format, headed by the review
+/// `verdict` and the `--track-time` `timing` if given. Gerrit output has
+/// no place for the timing and leaves it out. `style` only applies to diff
+/// output.
+pub fn format_feedback_as(
+    feedbacks: &[HunkFeedback],
+    verdict: Option<&ReviewVerdict>,
+    timing: Option<&ReviewTiming>,
+    context_count: usize,
+    format: FeedbackFormat,
+    style: OutputStyle,
+) -> Result<String> {
+    match (format, verdict, timing) {
+        (FeedbackFormat::Diff, _, _) => Ok(format!(
+            "{}{}{}",
+            verdict.map(format_verdict).unwrap_or_default(),
+            timing.map(timing::format_timing).unwrap_or_default(),
+            match style {
+                OutputStyle::Annotated => format_feedback(feedbacks, context_count),
+                OutputStyle::StrictDiff => format_feedback_strict(feedbacks),
+            }
+        )),
+        (FeedbackFormat::Json, None, None) => format_feedback_json(feedbacks),
+        (FeedbackFormat::Json, _, _) => format_review_json(feedbacks, verdict, timing),
+        (FeedbackFormat::Gerrit, _, _) => {
+            crate::export::gerrit::format_feedback_gerrit(feedbacks, verdict)
+        }
+    }
+}
+
+/// The verdict header of diff output:
+///
+/// ```text
+/// # REVIEW VERDICT: request changes
+/// # REVIEW SUMMARY: first line of the summary
+/// # REVIEW SUMMARY: second line
+/// ```
+pub fn format_verdict(verdict: &ReviewVerdict) -> String {
+    let mut output = format!("# REVIEW VERDICT: {}\n", verdict.verdict.label());
+    for line in verdict.summary.iter().flat_map(|s| s.lines()) {
+        output.push_str(&format!("# REVIEW SUMMARY: {}\n", line));
+    }
+    output
+}
+
+/// JSON output with a verdict or timing: an object with the verdict, the
+/// summary, the timing and the feedback array.
+#[derive(Serialize)]
+struct JsonReview<'a> {
+    #[serde(flatten)]
+    verdict: Option<&'a ReviewVerdict>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timing: Option<&'a ReviewTiming>,
+    feedback: &'a [&'a HunkFeedback],
+}
+
+/// Format a review with a verdict or timing as a pretty-printed JSON
+/// object.
+fn format_review_json(
+    feedbacks: &[HunkFeedback],
+    verdict: Option<&ReviewVerdict>,
+    timing: Option<&ReviewTiming>,
+) -> Result<String> {
+    let feedback = overall_first(feedbacks);
+    let review = JsonReview {
+        verdict,
+        timing,
+        feedback: &feedback,
+    };
+    let mut json = serde_json::to_string_pretty(&review).context("Failed to serialize feedback")?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// The feedback with comments on the review as a whole and on commits
+/// moved to the front.
+fn overall_first(feedbacks: &[HunkFeedback]) -> Vec<&HunkFeedback> {
+    let (overall, rest): (Vec<_>, Vec<_>) = feedbacks.iter().partition(|fb| !fb.kind.has_file());
+    overall.into_iter().chain(rest).collect()
+}
+
 /// Format a comment with surrounding diff context from the hunk.
 ///
 /// Each comment is placed at its original position within the hunk,
@@ -143,6 +485,17 @@ fn format_comment_with_context(output: &mut String, fb: &HunkFeedback, context_c
     }
 }
 
+/// Format a suggestion as a GitHub ```` ```suggestion ```` block, ready to
+/// paste into a pull request review comment.
+fn format_suggestion(output: &mut String, suggestion: &str) {
+    output.push_str("```suggestion\n");
+    for line in suggestion.lines() {
+        output.push_str(line);
+        output.push('\n');
+    }
+    output.push_str("```\n");
+}
+
 /// Write feedback to a file or stdout.
 pub fn write_feedback(output: &str, file_path: Option<&Path>) -> Result<()> {
     if output.is_empty() {
@@ -168,7 +521,7 @@ pub fn write_feedback(output: &str, file_path: Option<&Path>) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::LineKind;
+    use crate::types::{Severity, Verdict};
 
     #[test]
     fn test_empty_feedback() {
@@ -176,6 +529,313 @@ mod tests {
         assert_eq!(result, "");
     }
 
+    #[test]
+    fn test_json_feedback() {
+        assert_eq!(format_feedback_json(&[]).unwrap(), "");
+
+        let feedback = vec![HunkFeedback {
+            file_path: "src/main.rs".to_string(),
+            hunk_header: "@@ -1,3 +1,4 @@".to_string(),
+            kind: FeedbackKind::Comment,
+            context_lines: vec![],
+            comment_positions: vec![(0, "looks odd".to_string())],
+            suggestion: None,
+            severity: None,
+            content: "looks odd".to_string(),
+        }];
+        let json = format_feedback_as(
+            &feedback,
+            None,
+            None,
+            DEFAULT_CONTEXT_LINES,
+            FeedbackFormat::Json,
+            OutputStyle::Annotated,
+        )
+        .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0]["file_path"], "src/main.rs");
+        assert_eq!(parsed[0]["kind"], "comment");
+        assert_eq!(parsed[0]["comment_positions"][0][1], "looks odd");
+    }
+
+    #[test]
+    fn test_comment_severity_in_output() {
+        let mut feedback = vec![HunkFeedback {
+            file_path: "src/main.rs".to_string(),
+            hunk_header: "@@ -1,3 +1,4 @@".to_string(),
+            kind: FeedbackKind::Comment,
+            context_lines: vec![],
+            comment_positions: vec![],
+            suggestion: None,
+            severity: None,
+            content: "unwrap can panic".to_string(),
+        }];
+        let plain = format_feedback(&feedback, DEFAULT_CONTEXT_LINES);
+        assert!(!plain.contains("SEVERITY"));
+        let json = format_feedback_json(&feedback).unwrap();
+        assert!(!json.contains("severity"));
+
+        feedback[0].severity = Some(Severity::Blocker);
+        assert_eq!(
+            format_feedback(&feedback, DEFAULT_CONTEXT_LINES),
+            "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,3 +1,4 @@\n\
+             # SEVERITY: blocker\n# REVIEW COMMENT: unwrap can panic\n"
+        );
+        let json = format_feedback_json(&feedback).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0]["severity"], "blocker");
+    }
+
+    #[test]
+    fn test_strict_diff_keeps_edits_out_of_the_diff() {
+        let line = |kind, content: &str, old, new| crate::types::DiffLine {
+            kind,
+            content: format!("{}\n", content),
+            old_lineno: old,
+            new_lineno: new,
+            raw: None,
+            no_newline: false,
+        };
+        let feedback = vec![HunkFeedback {
+            file_path: "src/a.rs".to_string(),
+            hunk_header: "@@ -3,2 +3,2 @@ fn main()".to_string(),
+            kind: FeedbackKind::Edit,
+            content: "@@ -1 +1 @@\n-new\n+newer\n".to_string(),
+            context_lines: vec![
+                line(LineKind::Removed, "old", Some(3), None),
+                line(LineKind::Added, "new", None, Some(3)),
+                line(LineKind::Context, "same", Some(4), Some(4)),
+            ],
+            comment_positions: vec![],
+            suggestion: None,
+            severity: None,
+        }];
+        assert_eq!(
+            format_feedback_strict(&feedback),
+            "diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n\
+             @@ -3,2 +3,2 @@ fn main()\n-old\n+new\n same\n\
+             #=== EDIT src/a.rs @@ -3,2 +3,2 @@ fn main() ===\n\
+             # @@ -1 +1 @@\n# -new\n# +newer\n#=== END ===\n"
+        );
+    }
+
+    #[test]
+    fn test_verdict_heads_the_output() {
+        let feedback = vec![HunkFeedback {
+            file_path: "src/main.rs".to_string(),
+            hunk_header: "@@ -1,3 +1,4 @@".to_string(),
+            kind: FeedbackKind::Comment,
+            context_lines: vec![],
+            comment_positions: vec![],
+            suggestion: None,
+            severity: None,
+            content: "typo".to_string(),
+        }];
+        let verdict = ReviewVerdict {
+            verdict: Verdict::RequestChanges,
+            summary: Some("Close, but:\nfix the typo".to_string()),
+        };
+
+        let diff = format_feedback_as(
+            &feedback,
+            Some(&verdict),
+            None,
+            DEFAULT_CONTEXT_LINES,
+            FeedbackFormat::Diff,
+            OutputStyle::Annotated,
+        )
+        .unwrap();
+        assert!(diff.starts_with(
+            "# REVIEW VERDICT: request changes\n\
+             # REVIEW SUMMARY: Close, but:\n\
+             # REVIEW SUMMARY: fix the typo\n\
+             --- a/src/main.rs\n"
+        ));
+
+        let json = format_feedback_as(
+            &feedback,
+            Some(&verdict),
+            None,
+            DEFAULT_CONTEXT_LINES,
+            FeedbackFormat::Json,
+            OutputStyle::Annotated,
+        )
+        .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["verdict"], "request_changes");
+        assert_eq!(parsed["summary"], "Close, but:\nfix the typo");
+        assert_eq!(parsed["feedback"][0]["content"], "typo");
+    }
+
+    #[test]
+    fn test_timing_follows_the_verdict() {
+        let feedback = vec![HunkFeedback {
+            file_path: "src/main.rs".to_string(),
+            hunk_header: "@@ -1,3 +1,4 @@".to_string(),
+            kind: FeedbackKind::Comment,
+            context_lines: vec![],
+            comment_positions: vec![],
+            suggestion: None,
+            severity: None,
+            content: "typo".to_string(),
+        }];
+        let verdict = ReviewVerdict {
+            verdict: Verdict::Approve,
+            summary: None,
+        };
+        let timing = ReviewTiming {
+            total_seconds: 42,
+            files: vec![crate::timing::FileTiming {
+                file_path: "src/main.rs".to_string(),
+                seconds: 42,
+                hunks: vec![crate::timing::HunkTiming {
+                    hunk_header: "@@ -1,3 +1,4 @@".to_string(),
+                    seconds: 42,
+                }],
+            }],
+        };
+
+        let diff = format_feedback_as(
+            &feedback,
+            Some(&verdict),
+            Some(&timing),
+            DEFAULT_CONTEXT_LINES,
+            FeedbackFormat::Diff,
+            OutputStyle::Annotated,
+        )
+        .unwrap();
+        assert!(diff.starts_with(
+            "# REVIEW VERDICT: approve\n\
+             # REVIEW TIME: 42s total\n\
+             # REVIEW TIME: 42s src/main.rs\n\
+             # REVIEW TIME: 42s src/main.rs @@ -1,3 +1,4 @@\n\
+             --- a/src/main.rs\n"
+        ));
+
+        // Without a verdict the JSON output still becomes an object
+        let json = format_feedback_as(
+            &feedback,
+            None,
+            Some(&timing),
+            DEFAULT_CONTEXT_LINES,
+            FeedbackFormat::Json,
+            OutputStyle::Annotated,
+        )
+        .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(parsed.get("verdict").is_none());
+        assert_eq!(parsed["timing"]["total_seconds"], 42);
+        assert_eq!(parsed["timing"]["files"][0]["hunks"][0]["seconds"], 42);
+        assert_eq!(parsed["feedback"][0]["content"], "typo");
+        assert_eq!(crate::reminders::parse_feedback(&json).len(), 1);
+    }
+
+    #[test]
+    fn test_commit_comments_are_grouped_under_their_commit() {
+        let commit = |label: &str, content: &str, severity| HunkFeedback {
+            file_path: String::new(),
+            hunk_header: label.to_string(),
+            kind: FeedbackKind::CommitComment,
+            context_lines: vec![],
+            comment_positions: vec![],
+            suggestion: None,
+            severity,
+            content: content.to_string(),
+        };
+        let feedback = vec![
+            commit("1a2b3c4 Add the parser", "Split this commit", None),
+            commit(
+                "5d6e7f8 Fix typo",
+                "Squash into the parser",
+                Some(Severity::Nit),
+            ),
+        ];
+        assert_eq!(
+            format_feedback(&feedback, DEFAULT_CONTEXT_LINES),
+            "# COMMIT: 1a2b3c4 Add the parser\n# COMMIT COMMENT: Split this commit\n\
+             # COMMIT: 5d6e7f8 Fix typo\n# SEVERITY: nit\n# COMMIT COMMENT: Squash into the parser\n"
+        );
+        assert_eq!(
+            format_feedback_strict(&feedback[..1]),
+            "#=== COMMIT COMMENT 1a2b3c4 Add the parser ===\n# Split this commit\n#=== END ===\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&format_feedback_json(&feedback).unwrap()).unwrap();
+        assert_eq!(json[0]["kind"], "commit_comment");
+        assert_eq!(json[0]["hunk_header"], "1a2b3c4 Add the parser");
+    }
+
+    #[test]
+    fn test_file_comments_head_their_file() {
+        let feedback = vec![
+            HunkFeedback {
+                file_path: "src/old.rs".to_string(),
+                hunk_header: "@@ -1 +1 @@".to_string(),
+                kind: FeedbackKind::Edit,
+                context_lines: vec![],
+                comment_positions: vec![],
+                suggestion: None,
+                severity: None,
+                content: "-a\n+b\n".to_string(),
+            },
+            HunkFeedback {
+                file_path: "src/old.rs".to_string(),
+                hunk_header: String::new(),
+                kind: FeedbackKind::FileComment,
+                context_lines: vec![],
+                comment_positions: vec![],
+                suggestion: None,
+                severity: Some(Severity::Issue),
+                content: "Delete this file\nIt is unused".to_string(),
+            },
+        ];
+        assert_eq!(
+            format_feedback(&feedback, DEFAULT_CONTEXT_LINES),
+            "--- a/src/old.rs\n+++ b/src/old.rs\n# SEVERITY: issue\n\
+             # FILE COMMENT: Delete this file\n# FILE COMMENT: It is unused\n\
+             @@ -1 +1 @@\n-a\n+b\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&format_feedback_json(&feedback).unwrap()).unwrap();
+        assert_eq!(json[1]["kind"], "file_comment");
+        assert_eq!(json[1]["hunk_header"], "");
+    }
+
+    #[test]
+    fn test_review_comments_head_the_output() {
+        let feedback = vec![
+            HunkFeedback {
+                file_path: "src/a.rs".to_string(),
+                hunk_header: "@@ -1 +1 @@".to_string(),
+                kind: FeedbackKind::Edit,
+                context_lines: vec![],
+                comment_positions: vec![],
+                suggestion: None,
+                severity: None,
+                content: "-a\n+b\n".to_string(),
+            },
+            HunkFeedback {
+                file_path: String::new(),
+                hunk_header: String::new(),
+                kind: FeedbackKind::ReviewComment,
+                context_lines: vec![],
+                comment_positions: vec![],
+                suggestion: None,
+                severity: None,
+                content: "Nice cleanup\nNeeds tests".to_string(),
+            },
+        ];
+        assert_eq!(
+            format_feedback(&feedback, DEFAULT_CONTEXT_LINES),
+            "# OVERALL COMMENT: Nice cleanup\n# OVERALL COMMENT: Needs tests\n\
+             --- a/src/a.rs\n+++ b/src/a.rs\n@@ -1 +1 @@\n-a\n+b\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&format_feedback_json(&feedback).unwrap()).unwrap();
+        assert_eq!(json[0]["kind"], "review_comment");
+        assert_eq!(json[1]["kind"], "edit");
+    }
+
     #[test]
     fn test_single_edit_feedback() {
         let feedback = vec![HunkFeedback {
@@ -184,6 +844,8 @@ mod tests {
             kind: FeedbackKind::Edit,
             context_lines: vec![],
             comment_positions: vec![],
+            suggestion: None,
+            severity: None,
             content: "-old line\n+new line\n".to_string(),
         }];
         let result = format_feedback(&feedback, DEFAULT_CONTEXT_LINES);
@@ -203,6 +865,8 @@ mod tests {
                 kind: FeedbackKind::Edit,
                 context_lines: vec![],
                 comment_positions: vec![],
+                suggestion: None,
+                severity: None,
                 content: "-old\n+new\n".to_string(),
             },
             HunkFeedback {
@@ -211,6 +875,8 @@ mod tests {
                 kind: FeedbackKind::Edit,
                 context_lines: vec![],
                 comment_positions: vec![],
+                suggestion: None,
+                severity: None,
                 content: "-another old\n+another new\n".to_string(),
             },
         ];
@@ -232,6 +898,8 @@ mod tests {
                 kind: FeedbackKind::Edit,
                 context_lines: vec![],
                 comment_positions: vec![],
+                suggestion: None,
+                severity: None,
                 content: "-old\n+new\n".to_string(),
             },
             HunkFeedback {
@@ -240,6 +908,8 @@ mod tests {
                 kind: FeedbackKind::Edit,
                 context_lines: vec![],
                 comment_positions: vec![],
+                suggestion: None,
+                severity: None,
                 content: "-foo\n+bar\n".to_string(),
             },
         ];
@@ -263,28 +933,38 @@ mod tests {
                     content: "fn main() {\n".into(),
                     old_lineno: Some(1),
                     new_lineno: Some(1),
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Removed,
                     content: "    old_code();\n".into(),
                     old_lineno: Some(2),
                     new_lineno: None,
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Added,
                     content: "    new_code();\n".into(),
                     old_lineno: None,
                     new_lineno: Some(2),
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Context,
                     content: "}\n".into(),
                     old_lineno: Some(3),
                     new_lineno: Some(3),
+                    raw: None,
+                    no_newline: false,
                 },
             ],
             // Comment placed after the added line (index 3 = after context_lines[2])
             comment_positions: vec![(3, "This function needs better error handling".to_string())],
+            suggestion: None,
+            severity: None,
         }];
         let result = format_feedback(&feedback, DEFAULT_CONTEXT_LINES);
         assert!(result.contains("# REVIEW COMMENT: This function needs better error handling"));
@@ -330,6 +1010,8 @@ mod tests {
                 kind: FeedbackKind::Edit,
                 context_lines: vec![],
                 comment_positions: vec![],
+                suggestion: None,
+                severity: None,
                 content: "-old\n+new\n".to_string(),
             },
             HunkFeedback {
@@ -342,15 +1024,21 @@ mod tests {
                         content: "old\n".into(),
                         old_lineno: Some(10),
                         new_lineno: None,
+                        raw: None,
+                        no_newline: false,
                     },
                     DiffLine {
                         kind: LineKind::Added,
                         content: "new\n".into(),
                         old_lineno: None,
                         new_lineno: Some(10),
+                        raw: None,
+                        no_newline: false,
                     },
                 ],
                 comment_positions: vec![(2, "Consider refactoring this".to_string())],
+                suggestion: None,
+                severity: None,
                 content: "Consider refactoring this".to_string(),
             },
         ];
@@ -376,54 +1064,72 @@ mod tests {
                     content: "line1\n".into(),
                     old_lineno: Some(1),
                     new_lineno: Some(1),
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Removed,
                     content: "old_a\n".into(),
                     old_lineno: Some(2),
                     new_lineno: None,
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Added,
                     content: "new_a\n".into(),
                     old_lineno: None,
                     new_lineno: Some(2),
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Context,
                     content: "line3\n".into(),
                     old_lineno: Some(3),
                     new_lineno: Some(3),
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Context,
                     content: "line4\n".into(),
                     old_lineno: Some(4),
                     new_lineno: Some(4),
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Context,
                     content: "line5\n".into(),
                     old_lineno: Some(5),
                     new_lineno: Some(5),
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Removed,
                     content: "old_b\n".into(),
                     old_lineno: Some(6),
                     new_lineno: None,
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Added,
                     content: "new_b\n".into(),
                     old_lineno: None,
                     new_lineno: Some(6),
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Context,
                     content: "line7\n".into(),
                     old_lineno: Some(7),
                     new_lineno: Some(7),
+                    raw: None,
+                    no_newline: false,
                 },
             ],
             // Comment after first change (pos 3) and after second change (pos 8)
@@ -431,6 +1137,8 @@ mod tests {
                 (3, "First comment".to_string()),
                 (8, "Second comment".to_string()),
             ],
+            suggestion: None,
+            severity: None,
         }];
 
         let result = format_feedback(&feedback, 2);
@@ -464,6 +1172,26 @@ mod tests {
         // two comment regions since they're far apart
     }
 
+    #[test]
+    fn test_format_comment_with_suggestion() {
+        let feedback = vec![HunkFeedback {
+            file_path: "src/main.rs".to_string(),
+            hunk_header: "@@ -1 +1 @@".to_string(),
+            kind: FeedbackKind::Comment,
+            content: "Propagate the error".to_string(),
+            context_lines: vec![],
+            comment_positions: vec![],
+            suggestion: Some("    code()?;".to_string()),
+            severity: None,
+        }];
+        assert_eq!(
+            format_feedback(&feedback, DEFAULT_CONTEXT_LINES),
+            "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1 +1 @@\n\
+             # REVIEW COMMENT: Propagate the error\n\
+             ```suggestion\n    code()?;\n```\n"
+        );
+    }
+
     #[test]
     fn test_feedback_is_valid_patch() {
         let feedback = vec![HunkFeedback {
@@ -472,6 +1200,8 @@ mod tests {
             kind: FeedbackKind::Edit,
             context_lines: vec![],
             comment_positions: vec![],
+            suggestion: None,
+            severity: None,
             content: " context\n-old line\n+new line\n context2\n".to_string(),
         }];
         let result = format_feedback(&feedback, DEFAULT_CONTEXT_LINES);
@@ -0,0 +1,275 @@
+//! Directory tree of the file list, for the tree view (`t`).
+//!
+//! Files are grouped under their directories in the order the file list
+//! has them: a directory appears where its first file would. A directory
+//! whose only content is one subdirectory shares its row (`src/ui/`), so
+//! deep paths don't cost a line per level.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::types::FileDiff;
+
+/// A visible row of the tree view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileTreeRow {
+    Dir {
+        /// Repo-relative path, the key for collapsing.
+        path: PathBuf,
+        /// Path relative to the parent row, e.g. `src/ui`.
+        label: String,
+        depth: usize,
+        /// Indices of all files under the directory.
+        files: Vec<usize>,
+        collapsed: bool,
+    },
+    File {
+        index: usize,
+        depth: usize,
+    },
+}
+
+impl FileTreeRow {
+    pub fn depth(&self) -> usize {
+        match self {
+            FileTreeRow::Dir { depth, .. } | FileTreeRow::File { depth, .. } => *depth,
+        }
+    }
+}
+
+enum Node {
+    Dir(DirNode),
+    File(usize),
+}
+
+struct DirNode {
+    path: PathBuf,
+    label: String,
+    children: Vec<Node>,
+}
+
+impl DirNode {
+    fn child_dir(&mut self, name: &str) -> &mut DirNode {
+        let pos = self
+            .children
+            .iter()
+            .position(|c| matches!(c, Node::Dir(d) if d.label == name));
+        let pos = pos.unwrap_or_else(|| {
+            self.children.push(Node::Dir(DirNode {
+                path: self.path.join(name),
+                label: name.to_string(),
+                children: Vec::new(),
+            }));
+            self.children.len() - 1
+        });
+        match &mut self.children[pos] {
+            Node::Dir(d) => d,
+            Node::File(_) => unreachable!(),
+        }
+    }
+
+    /// Merge chains of single-subdirectory directories into one node.
+    fn compress(&mut self) {
+        for child in &mut self.children {
+            if let Node::Dir(dir) = child {
+                while let [Node::Dir(_)] = dir.children.as_slice() {
+                    let Some(Node::Dir(only)) = dir.children.pop() else {
+                        unreachable!()
+                    };
+                    dir.label = format!("{}/{}", dir.label, only.label);
+                    dir.path = only.path;
+                    dir.children = only.children;
+                }
+                dir.compress();
+            }
+        }
+    }
+
+    fn file_indices(&self, out: &mut Vec<usize>) {
+        for child in &self.children {
+            match child {
+                Node::Dir(d) => d.file_indices(out),
+                Node::File(i) => out.push(*i),
+            }
+        }
+    }
+}
+
+/// Visible rows of the tree for the `shown` files (indices into `files`).
+/// Directories in `collapsed` hide their contents, except those containing
+/// the `reveal` file, which is the selected file when the cursor is on a
+/// file row.
+pub fn build(
+    files: &[FileDiff],
+    shown: &[usize],
+    collapsed: &BTreeSet<PathBuf>,
+    reveal: Option<usize>,
+) -> Vec<FileTreeRow> {
+    let mut root = DirNode {
+        path: PathBuf::new(),
+        label: String::new(),
+        children: Vec::new(),
+    };
+    for &index in shown {
+        let file = &files[index];
+        let mut dir = &mut root;
+        if let Some(parent) = file.path.parent() {
+            for component in parent.components() {
+                dir = dir.child_dir(&component.as_os_str().to_string_lossy());
+            }
+        }
+        dir.children.push(Node::File(index));
+    }
+    root.compress();
+
+    let mut rows = Vec::new();
+    flatten(&root.children, 0, collapsed, reveal, &mut rows);
+    rows
+}
+
+fn flatten(
+    nodes: &[Node],
+    depth: usize,
+    collapsed: &BTreeSet<PathBuf>,
+    reveal: Option<usize>,
+    rows: &mut Vec<FileTreeRow>,
+) {
+    for node in nodes {
+        match node {
+            Node::Dir(dir) => {
+                let mut files = Vec::new();
+                dir.file_indices(&mut files);
+                let is_collapsed =
+                    collapsed.contains(&dir.path) && !reveal.is_some_and(|r| files.contains(&r));
+                rows.push(FileTreeRow::Dir {
+                    path: dir.path.clone(),
+                    label: dir.label.clone(),
+                    depth,
+                    files,
+                    collapsed: is_collapsed,
+                });
+                if !is_collapsed {
+                    flatten(&dir.children, depth + 1, collapsed, reveal, rows);
+                }
+            }
+            Node::File(index) => rows.push(FileTreeRow::File {
+                index: *index,
+                depth,
+            }),
+        }
+    }
+}
+
+/// Position of the directory row for `path` in `rows`.
+pub fn dir_row(rows: &[FileTreeRow], path: &Path) -> Option<usize> {
+    rows.iter()
+        .position(|r| matches!(r, FileTreeRow::Dir { path: p, .. } if p == path))
+}
+
+/// Position of the row for file `index` in `rows`.
+pub fn file_row(rows: &[FileTreeRow], index: usize) -> Option<usize> {
+    rows.iter()
+        .position(|r| matches!(r, FileTreeRow::File { index: i, .. } if *i == index))
+}
+
+/// Position of the directory row containing the row at `row`.
+pub fn parent_row(rows: &[FileTreeRow], row: usize) -> Option<usize> {
+    let depth = rows.get(row)?.depth();
+    rows[..row].iter().rposition(|r| r.depth() < depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DeltaStatus;
+
+    fn files(paths: &[&str]) -> Vec<FileDiff> {
+        paths
+            .iter()
+            .map(|p| FileDiff {
+                path: p.into(),
+                hunks: Vec::new(),
+                status: DeltaStatus::Modified,
+                is_binary: false,
+                is_submodule: false,
+                mode_change: None,
+            })
+            .collect()
+    }
+
+    fn all(files: &[FileDiff]) -> Vec<usize> {
+        (0..files.len()).collect()
+    }
+
+    fn labels(rows: &[FileTreeRow], files: &[FileDiff]) -> Vec<String> {
+        rows.iter()
+            .map(|r| match r {
+                FileTreeRow::Dir {
+                    label,
+                    depth,
+                    files,
+                    collapsed,
+                    ..
+                } => format!(
+                    "{}{}/ ({}){}",
+                    "  ".repeat(*depth),
+                    label,
+                    files.len(),
+                    if *collapsed { " +" } else { "" }
+                ),
+                FileTreeRow::File { index, depth } => format!(
+                    "{}{}",
+                    "  ".repeat(*depth),
+                    files[*index].path.file_name().unwrap().to_string_lossy()
+                ),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_groups_by_directory_and_compresses_chains() {
+        let f = files(&[
+            "Cargo.toml",
+            "crates/core/src/lib.rs",
+            "crates/core/src/parse.rs",
+            "src/main.rs",
+            "src/ui/list.rs",
+            "src/ui/view.rs",
+        ]);
+        let rows = build(&f, &all(&f), &BTreeSet::new(), None);
+        assert_eq!(
+            labels(&rows, &f),
+            vec![
+                "Cargo.toml",
+                "crates/core/src/ (2)",
+                "  lib.rs",
+                "  parse.rs",
+                "src/ (3)",
+                "  main.rs",
+                "  ui/ (2)",
+                "    list.rs",
+                "    view.rs",
+            ]
+        );
+        assert_eq!(parent_row(&rows, 7), Some(6));
+        assert_eq!(parent_row(&rows, 6), Some(4));
+        assert_eq!(parent_row(&rows, 0), None);
+    }
+
+    #[test]
+    fn test_collapsed_directories_hide_files_unless_revealed() {
+        let f = files(&["src/main.rs", "src/ui/list.rs", "src/ui/view.rs"]);
+        let collapsed = BTreeSet::from([PathBuf::from("src/ui")]);
+
+        let rows = build(&f, &all(&f), &collapsed, None);
+        assert_eq!(
+            labels(&rows, &f),
+            vec!["src/ (3)", "  main.rs", "  ui/ (2) +"]
+        );
+        assert_eq!(dir_row(&rows, Path::new("src/ui")), Some(2));
+        assert_eq!(file_row(&rows, 1), None);
+
+        let rows = build(&f, &all(&f), &collapsed, Some(2));
+        assert_eq!(file_row(&rows, 2), Some(4));
+    }
+}
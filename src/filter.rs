@@ -0,0 +1,149 @@
+//! File selection for `--files`, `--exclude` and `:files`, and the fuzzy
+//! file list filter (`f`).
+//!
+//! Patterns are globs with `**`, `{a,b}` and character classes, given
+//! relative to the current directory (see [`git::to_repo_path`]) and
+//! matched against whole repo-relative paths. As in `.gitignore`, a pattern
+//! starting with `!` excludes instead of includes. `*` also matches `/`, so
+//! `*.rs` selects Rust files at any depth.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::git;
+
+/// Include and exclude globs for selecting files to review.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    /// `None` includes every file.
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    /// Build a filter from `--files` patterns (`!pattern` excludes) and
+    /// `--exclude` patterns, resolved against `cwd_prefix`. A file is
+    /// selected if it matches any include pattern (or there are none) and
+    /// no exclude pattern.
+    pub fn new(cwd_prefix: &Path, files: &[String], exclude: &[String]) -> Result<Self> {
+        let (negated, included): (Vec<&String>, Vec<&String>) =
+            files.iter().partition(|p| p.starts_with('!'));
+        let excluded = negated
+            .into_iter()
+            .map(|p| &p[1..])
+            .chain(exclude.iter().map(String::as_str));
+
+        Ok(Self {
+            include: build_set(cwd_prefix, included.into_iter().map(String::as_str))?,
+            exclude: build_set(cwd_prefix, excluded)?,
+        })
+    }
+
+    /// Whether the filter selects every file.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+
+    /// Whether the repo-relative `path` is selected.
+    pub fn matches(&self, path: &Path) -> bool {
+        self.include.as_ref().is_none_or(|set| set.is_match(path))
+            && !self.exclude.as_ref().is_some_and(|set| set.is_match(path))
+    }
+}
+
+/// Whether `query` matches `path` fuzzily: its characters appear in the
+/// path in order, ignoring case. An empty query matches every path.
+pub fn fuzzy_match(query: &str, path: &Path) -> bool {
+    let path = path.to_string_lossy().to_lowercase();
+    let mut chars = path.chars();
+    query
+        .to_lowercase()
+        .chars()
+        .all(|q| chars.by_ref().any(|c| c == q))
+}
+
+/// Compile `patterns` into one set, or `None` if there are none.
+fn build_set<'a>(
+    cwd_prefix: &Path,
+    patterns: impl Iterator<Item = &'a str>,
+) -> Result<Option<GlobSet>> {
+    let mut builder = GlobSetBuilder::new();
+    let mut any = false;
+    for pattern in patterns {
+        let glob = Glob::new(&git::to_repo_path(cwd_prefix, pattern))
+            .with_context(|| format!("Invalid glob pattern '{}'", pattern))?;
+        builder.add(glob);
+        any = true;
+    }
+    if !any {
+        return Ok(None);
+    }
+    Ok(Some(builder.build().context("Failed to compile globs")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(files: &[&str], exclude: &[&str]) -> PathFilter {
+        let owned = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        PathFilter::new(Path::new(""), &owned(files), &owned(exclude)).unwrap()
+    }
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let f = filter(&[], &[]);
+        assert!(f.is_empty());
+        assert!(f.matches(Path::new("any/file.txt")));
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let f = filter(&["src/**/*.rs", "*.toml"], &["**/generated/**"]);
+        assert!(f.matches(Path::new("src/main.rs")));
+        assert!(f.matches(Path::new("src/ui/view.rs")));
+        assert!(f.matches(Path::new("Cargo.toml")));
+        assert!(!f.matches(Path::new("src/generated/api.rs")));
+        assert!(!f.matches(Path::new("README.md")));
+    }
+
+    #[test]
+    fn test_negated_files_pattern_and_braces() {
+        let f = filter(&["*.{rs,md}", "!tests/**"], &[]);
+        assert!(f.matches(Path::new("src/lib.rs")));
+        assert!(f.matches(Path::new("README.md")));
+        assert!(!f.matches(Path::new("tests/ui_test.rs")));
+        assert!(!f.matches(Path::new("Cargo.toml")));
+
+        // Only negative patterns: everything else is included
+        let f = filter(&["!*.lock"], &[]);
+        assert!(f.matches(Path::new("src/lib.rs")));
+        assert!(!f.matches(Path::new("Cargo.lock")));
+    }
+
+    #[test]
+    fn test_patterns_relative_to_cwd() {
+        let f = PathFilter::new(Path::new("src"), &["ui/*.rs".to_string()], &[]).unwrap();
+        assert!(f.matches(Path::new("src/ui/mod.rs")));
+        assert!(!f.matches(Path::new("ui/mod.rs")));
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        let path = Path::new("src/ui/file_list.rs");
+        assert!(fuzzy_match("", path));
+        assert!(fuzzy_match("list", path));
+        assert!(fuzzy_match("uifl", path));
+        assert!(fuzzy_match("FILE", path));
+        assert!(!fuzzy_match("lf", Path::new("src/flag.rs")));
+        assert!(!fuzzy_match("view", path));
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        let err = PathFilter::new(Path::new(""), &["src/[".to_string()], &[]).unwrap_err();
+        assert!(err.to_string().contains("src/["));
+    }
+}
@@ -1,21 +1,75 @@
 use anyhow::{Context, Result};
 use git2::{DiffOptions, Repository};
 use std::os::unix::fs::PermissionsExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::diff;
-use crate::types::FileDiff;
+use crate::staging;
+use crate::types::{DeltaStatus, FileDiff};
 
-/// Open a git repository at the given path.
+/// Open the git repository containing the given path.
+///
+/// The repository is discovered by walking up from `path`, so this works
+/// from any subdirectory and inside linked worktrees. `FileDiff` paths are
+/// always relative to the working directory root, never to `path`.
 pub fn open_repo(path: impl AsRef<Path>) -> Result<Repository> {
     Repository::discover(path.as_ref())
         .context("Failed to open git repository. Are you in a git repo?")
 }
 
+/// Location of `cwd` inside the repository's working directory, e.g.
+/// `src/ui` when run from `<repo>/src/ui`. Empty at the root, for bare
+/// repositories, and when `cwd` is outside the working tree.
+pub fn cwd_prefix(repo: &Repository, cwd: &Path) -> PathBuf {
+    let Some(workdir) = repo.workdir() else {
+        return PathBuf::new();
+    };
+    // Canonicalize both sides so symlinked temp dirs etc. compare equal
+    let workdir = workdir
+        .canonicalize()
+        .unwrap_or_else(|_| workdir.to_path_buf());
+    let cwd = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+    cwd.strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+/// Translate a path or glob given relative to the current directory into one
+/// relative to the repository root, where `prefix` is the [`cwd_prefix`].
+///
+/// `.` and `..` components are resolved lexically, so `../lib.rs` from `src/ui`
+/// becomes `src/lib.rs`. Paths that climb above the root keep their leading
+/// `..` and simply match nothing.
+pub fn to_repo_path(prefix: &Path, path: &str) -> String {
+    let mut parts: Vec<String> = prefix
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    let mut escaped = 0;
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() {
+                    escaped += 1;
+                }
+            }
+            _ => parts.push(part.to_string()),
+        }
+    }
+    let mut out = vec!["..".to_string(); escaped];
+    out.extend(parts);
+    out.join("/")
+}
+
+/// Index flag of intent-to-add entries (`GIT_INDEX_ENTRY_INTENT_TO_ADD`).
+const INTENT_TO_ADD: u16 = 1 << 13;
+
 /// Add all untracked files to the index with intent-to-add (`git add -N`).
 /// This creates an empty blob entry for each untracked file so its full
-/// content appears as unstaged changes in the diff.
-pub fn intent_to_add_untracked(repo: &Repository) -> Result<()> {
+/// content appears as unstaged changes in the diff. Returns the paths
+/// added, for [`remove_intent_to_add`].
+pub fn intent_to_add_untracked(repo: &Repository) -> Result<Vec<String>> {
     let statuses = repo.statuses(None).context("Failed to get repo status")?;
 
     let untracked: Vec<String> = statuses
@@ -25,7 +79,7 @@ pub fn intent_to_add_untracked(repo: &Repository) -> Result<()> {
         .collect();
 
     if untracked.is_empty() {
-        return Ok(());
+        return Ok(untracked);
     }
 
     let mut index = repo.index().context("Failed to open index")?;
@@ -54,8 +108,7 @@ pub fn intent_to_add_untracked(repo: &Repository) -> Result<()> {
             flags_extended: 0,
             path: path.as_bytes().to_vec(),
         };
-        const GIT_IDXENTRY_INTENT_TO_ADD: u16 = 1 << 13;
-        entry.flags_extended |= GIT_IDXENTRY_INTENT_TO_ADD;
+        entry.flags_extended |= INTENT_TO_ADD;
 
         index
             .add(&entry)
@@ -69,21 +122,278 @@ pub fn intent_to_add_untracked(repo: &Repository) -> Result<()> {
     repo.set_index(&mut repo.index().context("Failed to reload index")?)
         .context("Failed to refresh repo index")?;
 
-    Ok(())
+    Ok(untracked)
+}
+
+/// Undo [`intent_to_add_untracked`] for `paths` that are still only
+/// intended to be added: nothing of them was staged, so they go back to
+/// being untracked. Staging clears the flag, so staged files are kept.
+/// Returns the paths removed from the index.
+pub fn remove_intent_to_add(repo: &Repository, paths: &[String]) -> Result<Vec<String>> {
+    let mut index = repo.index().context("Failed to open index")?;
+    let mut removed = Vec::new();
+    for path in paths {
+        let still_intended = index
+            .get_path(Path::new(path), 0)
+            .is_some_and(|entry| entry.flags_extended & INTENT_TO_ADD != 0);
+        if still_intended {
+            index
+                .remove_path(Path::new(path))
+                .with_context(|| format!("Failed to remove intent-to-add for {}", path))?;
+            removed.push(path.clone());
+        }
+    }
+    if !removed.is_empty() {
+        staging::write_index(&mut index)?;
+    }
+    Ok(removed)
+}
+
+/// Untracked files added with intent-to-add for a review, undone with
+/// [`remove_intent_to_add`] when dropped, so that a review ending in an
+/// error leaves them untracked too. [`finish`](Self::finish) undoes them
+/// and reports failures.
+pub struct IntentToAdd<'r> {
+    repo: &'r Repository,
+    paths: Vec<String>,
+}
+
+impl<'r> IntentToAdd<'r> {
+    /// Add the untracked files of `repo` with intent-to-add, see
+    /// [`intent_to_add_untracked`].
+    pub fn add_untracked(repo: &'r Repository) -> Result<Self> {
+        let paths = intent_to_add_untracked(repo)?;
+        Ok(Self { repo, paths })
+    }
+
+    /// No files added yet.
+    pub fn none(repo: &'r Repository) -> Self {
+        Self {
+            repo,
+            paths: Vec::new(),
+        }
+    }
+
+    /// Also undo `paths`, added with intent-to-add later on.
+    pub fn extend(&mut self, paths: impl IntoIterator<Item = String>) {
+        self.paths.extend(paths);
+    }
+
+    /// Undo the intent-to-add of the files nothing was staged from.
+    /// Returns the paths removed from the index.
+    pub fn finish(mut self) -> Result<Vec<String>> {
+        let paths = std::mem::take(&mut self.paths);
+        remove_intent_to_add(self.repo, &paths)
+    }
+}
+
+impl Drop for IntentToAdd<'_> {
+    fn drop(&mut self) {
+        if !self.paths.is_empty() {
+            let _ = remove_intent_to_add(self.repo, &self.paths);
+        }
+    }
+}
+
+/// Paths with unstaged changes in the working tree (modified, deleted or
+/// untracked), relative to the repository root. Cheaper than a full diff;
+/// used by `--watch` to notice edits.
+pub fn worktree_changes(repo: &Repository) -> Result<Vec<PathBuf>> {
+    let mut opts = git2::StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    let statuses = repo
+        .statuses(Some(&mut opts))
+        .context("Failed to get repo status")?;
+
+    let worktree = git2::Status::WT_NEW
+        | git2::Status::WT_MODIFIED
+        | git2::Status::WT_DELETED
+        | git2::Status::WT_TYPECHANGE
+        | git2::Status::WT_RENAMED;
+    Ok(statuses
+        .iter()
+        .filter(|e| e.status().intersects(worktree))
+        .filter_map(|e| e.path().map(PathBuf::from))
+        .collect())
+}
+
+/// Which changes the diff leaves out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffSettings {
+    /// Treat lines that differ only in whitespace as unchanged
+    /// (`-w` / `--ignore-whitespace`).
+    pub ignore_whitespace: bool,
+    /// Leave out changes that only add or remove blank lines
+    /// (`--ignore-blank-lines`).
+    pub ignore_blank_lines: bool,
+    /// Unchanged lines around each change (`-U` / `--unified`); git's
+    /// default of 3 when `None`. Changes closer than twice this are
+    /// merged into one hunk.
+    pub context_lines: Option<u32>,
+    /// Leave out untracked files (`--no-untracked`). Files already added
+    /// with intent-to-add are tracked and still shown.
+    pub exclude_untracked: bool,
+    /// How lines are matched (`--diff-algorithm`); the repository's
+    /// `diff.algorithm` when `None`.
+    pub algorithm: Option<DiffAlgorithm>,
+}
+
+/// How lines are matched up when diffing (`--diff-algorithm`, git's
+/// `diff.algorithm`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum DiffAlgorithm {
+    /// The basic greedy algorithm (git's `default`).
+    #[default]
+    Myers,
+    /// Myers, spending extra time to find the smallest diff.
+    Minimal,
+    /// Matches unique lines first, which keeps reformatted code readable.
+    Patience,
+    /// git's extension of patience. libgit2 doesn't implement it, so this
+    /// diffs with patience.
+    Histogram,
+}
+
+impl DiffAlgorithm {
+    /// The command-line and git config value of the algorithm.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Myers => "myers",
+            Self::Minimal => "minimal",
+            Self::Patience => "patience",
+            Self::Histogram => "histogram",
+        }
+    }
+
+    /// The configured `diff.algorithm`, if any.
+    pub fn from_git_config(config: &git2::Config) -> Result<Option<Self>> {
+        match config.get_string("diff.algorithm") {
+            Ok(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|e: String| anyhow::anyhow!("diff.algorithm: {}", e)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn apply(self, opts: &mut DiffOptions) {
+        opts.minimal(self == Self::Minimal);
+        opts.patience(matches!(self, Self::Patience | Self::Histogram));
+    }
+}
+
+impl std::str::FromStr for DiffAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "myers" | "default" => Ok(Self::Myers),
+            "minimal" => Ok(Self::Minimal),
+            "patience" => Ok(Self::Patience),
+            "histogram" => Ok(Self::Histogram),
+            _ => Err(format!(
+                "unknown diff algorithm '{}' (expected myers, minimal, patience or histogram)",
+                s
+            )),
+        }
+    }
 }
 
 /// Get all unstaged changes as a list of FileDiff.
 pub fn get_unstaged_diff(repo: &Repository) -> Result<Vec<FileDiff>> {
+    get_unstaged_diff_with(repo, &DiffSettings::default())
+}
+
+/// [`get_unstaged_diff`] computed as `settings` asks: with whitespace and
+/// blank-line changes left out, or with other context around changes.
+/// Unless `settings` picks an algorithm, lines are matched with the
+/// repository's `diff.algorithm`, so hunks come out as `git diff` shows
+/// them; untracked files ignored by `.gitignore` or `core.excludesFile`
+/// are left out.
+///
+/// With `ignore_whitespace`, context lines show the working tree's
+/// version; staging takes them from the index instead (see
+/// [`crate::staging::reconstruct_blob`]).
+pub fn get_unstaged_diff_with(repo: &Repository, settings: &DiffSettings) -> Result<Vec<FileDiff>> {
     let index = repo.index().context("Failed to open index")?;
 
-    let mut opts = DiffOptions::new();
-    opts.include_untracked(true);
-    opts.recurse_untracked_dirs(true);
-    opts.show_untracked_content(true);
+    let mut opts = diff_options(repo, settings)?;
+    if !settings.exclude_untracked {
+        opts.include_untracked(true);
+        opts.recurse_untracked_dirs(true);
+        opts.show_untracked_content(true);
+    }
 
     let diff = repo
         .diff_index_to_workdir(Some(&index), Some(&mut opts))
         .context("Failed to compute diff")?;
+    files_from_diff(&diff)
+}
+
+/// The staged changes (`HEAD` to the index, as `git diff --cached` shows
+/// them) computed as `settings` asks, like [`get_unstaged_diff_with`].
+/// With an unborn `HEAD` every staged file is new.
+pub fn get_staged_diff_with(repo: &Repository, settings: &DiffSettings) -> Result<Vec<FileDiff>> {
+    let index = repo.index().context("Failed to open index")?;
+    let head = match repo.head() {
+        Ok(head) => Some(
+            head.peel_to_tree()
+                .context("Failed to read the HEAD tree")?,
+        ),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+        Err(e) => return Err(e).context("Failed to read HEAD"),
+    };
+
+    let mut opts = diff_options(repo, settings)?;
+    let diff = repo
+        .diff_tree_to_index(head.as_ref(), Some(&index), Some(&mut opts))
+        .context("Failed to compute the staged diff")?;
+    files_from_diff(&diff)
+}
+
+/// The changes from tree `from` to tree `to` (as `git diff A B` shows
+/// them), computed as `settings` asks, like [`get_unstaged_diff_with`].
+pub fn get_tree_diff_with(
+    repo: &Repository,
+    from: &git2::Tree,
+    to: &git2::Tree,
+    settings: &DiffSettings,
+) -> Result<Vec<FileDiff>> {
+    let mut opts = diff_options(repo, settings)?;
+    let diff = repo
+        .diff_tree_to_tree(Some(from), Some(to), Some(&mut opts))
+        .context("Failed to compute the diff")?;
+    files_from_diff(&diff)
+}
+
+/// Diff options for `settings`, matching lines with the repository's
+/// `diff.algorithm` unless `settings` picks one.
+fn diff_options(repo: &Repository, settings: &DiffSettings) -> Result<DiffOptions> {
+    let mut opts = DiffOptions::new();
+    opts.ignore_whitespace(settings.ignore_whitespace);
+    opts.ignore_blank_lines(settings.ignore_blank_lines);
+    if let Some(lines) = settings.context_lines {
+        opts.context_lines(lines);
+    }
+    let algorithm = match settings.algorithm {
+        Some(algorithm) => Some(algorithm),
+        None => {
+            let config = repo.config().context("Failed to read git config")?;
+            DiffAlgorithm::from_git_config(&config)?
+        }
+    };
+    if let Some(algorithm) = algorithm {
+        algorithm.apply(&mut opts);
+    }
+    Ok(opts)
+}
 
-    diff::parse_diff(&diff)
+fn files_from_diff(diff: &git2::Diff) -> Result<Vec<FileDiff>> {
+    let mut files = diff::parse_diff(diff)?;
+    // Files whose every change was ignored still show up as modified
+    files.retain(|f| !(f.status == DeltaStatus::Modified && f.hunks.is_empty()));
+    Ok(files)
 }
@@ -1,23 +1,111 @@
+//! Syntax highlighting of diff lines with syntect.
+//!
+//! The syntax and theme sets are loaded once and shared. Besides syntect's
+//! defaults they include `*.sublime-syntax` files from
+//! `~/.config/stagent/syntaxes` and `*.tmTheme` files from
+//! `~/.config/stagent/themes` (a theme is named after its file stem).
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
 use syntect::easy::HighlightLines;
-use syntect::highlighting::{FontStyle, ThemeSet};
-use syntect::parsing::SyntaxSet;
+use syntect::highlighting::{FontStyle, HighlightState, ThemeSet};
+use syntect::parsing::{ParseState, SyntaxSet};
+
+use crate::types::{DiffLine, Hunk, LineKind};
+use crate::ui::theme::{self, ThemeVariant};
 
-use crate::types::{Hunk, LineKind};
-use crate::ui::theme;
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(|| load_syntax_set(config_dir().as_deref()));
+static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(|| load_theme_set(config_dir().as_deref()));
+
+/// `~/.config/stagent`, where user syntaxes and themes live.
+fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/stagent"))
+}
+
+/// syntect's default syntaxes plus those in `<dir>/syntaxes`. A folder that
+/// fails to load is reported and left out.
+pub fn load_syntax_set(dir: Option<&Path>) -> SyntaxSet {
+    let defaults = SyntaxSet::load_defaults_newlines();
+    let Some(folder) = dir.map(|d| d.join("syntaxes")).filter(|d| d.is_dir()) else {
+        return defaults;
+    };
+    let mut builder = defaults.clone().into_builder();
+    match builder.add_from_folder(&folder, true) {
+        Ok(()) => builder.build(),
+        Err(e) => {
+            eprintln!(
+                "Warning: failed to load syntaxes from {}: {}",
+                folder.display(),
+                e
+            );
+            defaults
+        }
+    }
+}
+
+/// syntect's default themes plus the `*.tmTheme` files in `<dir>/themes`.
+/// Themes that fail to load are reported and skipped.
+pub fn load_theme_set(dir: Option<&Path>) -> ThemeSet {
+    let mut set = ThemeSet::load_defaults();
+    let Some(Ok(entries)) = dir.map(|d| std::fs::read_dir(d.join("themes"))) else {
+        return set;
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "tmTheme"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let Some(name) = path.file_stem().map(|s| s.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        match ThemeSet::get_theme(&path) {
+            Ok(theme) => {
+                set.themes.insert(name, theme);
+            }
+            Err(e) => eprintln!("Warning: failed to load theme {}: {}", path.display(), e),
+        }
+    }
+    set
+}
+
+/// Load the shared syntax and theme sets now, so problems with user files
+/// are reported before the TUI takes over the terminal.
+pub fn load() {
+    LazyLock::force(&SYNTAX_SET);
+    LazyLock::force(&THEME_SET);
+}
+
+/// Palette that goes with the syntect theme `name`: light for themes with a
+/// light background. `None` if there is no such theme.
+pub fn theme_variant(name: &str) -> Option<ThemeVariant> {
+    let theme = THEME_SET.themes.get(name)?;
+    let light = theme
+        .settings
+        .background
+        .is_some_and(|bg| 0.299 * bg.r as f32 + 0.587 * bg.g as f32 + 0.114 * bg.b as f32 > 128.0);
+    Some(if light {
+        ThemeVariant::Light
+    } else {
+        ThemeVariant::Dark
+    })
+}
 
 /// Highlighter wraps syntect for syntax highlighting of diff lines.
 pub struct Highlighter {
-    syntax_set: SyntaxSet,
-    theme_set: ThemeSet,
+    syntax_set: &'static SyntaxSet,
+    theme_set: &'static ThemeSet,
 }
 
 impl Highlighter {
     pub fn new() -> Self {
         Self {
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
+            syntax_set: &SYNTAX_SET,
+            theme_set: &THEME_SET,
         }
     }
 
@@ -45,7 +133,7 @@ impl Highlighter {
         // For removed lines, use simple dimmed red without syntax highlighting
         if kind == LineKind::Removed {
             let style = Style::default()
-                .fg(Color::Red)
+                .fg(theme::removed_dim_fg())
                 .add_modifier(Modifier::DIM)
                 .bg(theme::removed_dim_bg());
             return Line::from(Span::styled(content.to_string(), style));
@@ -59,7 +147,7 @@ impl Highlighter {
             .flatten()
             .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
 
-        let theme = match self.theme_set.themes.get(theme::syntect_theme()) {
+        let theme = match self.theme_set.themes.get(&theme::syntect_theme()) {
             Some(t) => t,
             None => {
                 // Fallback to plain text if theme not found
@@ -78,7 +166,7 @@ impl Highlighter {
             format!("{}\n", content)
         };
 
-        match h.highlight_line(&line_with_newline, &self.syntax_set) {
+        match h.highlight_line(&line_with_newline, self.syntax_set) {
             Ok(ranges) => {
                 let spans: Vec<Span> = ranges
                     .iter()
@@ -118,6 +206,24 @@ impl Highlighter {
     /// instance across context/added lines for correct multi-line syntax state.
     /// Returns `Vec<Vec<Line>>` — outer = per hunk, inner = per `DiffLine`.
     pub fn highlight_file_lines(&self, path: &str, hunks: &[Hunk]) -> Vec<Vec<Line<'static>>> {
+        let mut file = HighlightedFile::default();
+        self.highlight_more(path, hunks, &mut file, usize::MAX);
+        file.hunks
+    }
+
+    /// Continue highlighting `file` until its first `upto` diff lines (counted
+    /// across hunks) are done. The syntax state is kept between calls, so a
+    /// file is highlighted as it is scrolled rather than all up front.
+    pub fn highlight_more(
+        &self,
+        path: &str,
+        hunks: &[Hunk],
+        file: &mut HighlightedFile,
+        upto: usize,
+    ) {
+        if file.done >= upto {
+            return;
+        }
         let syntax = self
             .syntax_set
             .find_syntax_for_file(path)
@@ -125,93 +231,275 @@ impl Highlighter {
             .flatten()
             .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
 
-        let theme = match self.theme_set.themes.get(theme::syntect_theme()) {
-            Some(t) => t,
-            None => {
-                // Fallback: return plain lines
-                return hunks
+        // Without the theme, lines stay plain
+        let mut h = self
+            .theme_set
+            .themes
+            .get(&theme::syntect_theme())
+            .map(|theme| match file.state.take() {
+                Some((highlight_state, parse_state)) => {
+                    HighlightLines::from_state(theme, highlight_state, parse_state)
+                }
+                None => HighlightLines::new(syntax, theme),
+            });
+
+        'hunks: for (hunk_idx, hunk) in hunks.iter().enumerate() {
+            if file.hunks.len() == hunk_idx {
+                file.hunks.push(Vec::with_capacity(hunk.lines.len()));
+            }
+            let hunk_lines = &mut file.hunks[hunk_idx];
+            for diff_line in &hunk.lines[hunk_lines.len()..] {
+                if file.done >= upto {
+                    break 'hunks;
+                }
+                hunk_lines.push(match h.as_mut() {
+                    Some(h) => self.highlight_diff_line(h, diff_line),
+                    None => Line::from(diff_line.content.clone()),
+                });
+                file.done += 1;
+            }
+        }
+
+        file.state = h.map(HighlightLines::state);
+    }
+
+    /// Highlight the hunks of a large file (see [`is_large`]) on screen:
+    /// for each `(hunk index, lines)` in `visible`, its first `lines`
+    /// lines. Each hunk starts from a fresh syntax state rather than the
+    /// state after the hunk before, so that no hunk above the view is
+    /// needed; constructs that span hunks, like long block comments, may
+    /// come out wrong.
+    pub fn highlight_hunks(
+        &self,
+        path: &str,
+        hunks: &[Hunk],
+        file: &mut HighlightedFile,
+        visible: &[(usize, usize)],
+    ) {
+        let syntax = self
+            .syntax_set
+            .find_syntax_for_file(path)
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+        let theme = self.theme_set.themes.get(&theme::syntect_theme());
+
+        for &(hunk_idx, upto) in visible {
+            let Some(hunk) = hunks.get(hunk_idx) else {
+                continue;
+            };
+            if file.hunks.len() <= hunk_idx {
+                file.hunks.resize_with(hunk_idx + 1, Vec::new);
+                file.hunk_states.resize_with(hunk_idx + 1, || None);
+            }
+            let done = file.hunks[hunk_idx].len();
+            let upto = upto.min(hunk.lines.len());
+            if done >= upto {
+                continue;
+            }
+            let mut h = theme.map(|theme| match file.hunk_states[hunk_idx].take() {
+                Some((highlight_state, parse_state)) => {
+                    HighlightLines::from_state(theme, highlight_state, parse_state)
+                }
+                None => HighlightLines::new(syntax, theme),
+            });
+            for diff_line in &hunk.lines[done..upto] {
+                file.hunks[hunk_idx].push(match h.as_mut() {
+                    Some(h) => self.highlight_diff_line(h, diff_line),
+                    None => Line::from(diff_line.content.clone()),
+                });
+            }
+            file.hunk_states[hunk_idx] = h.map(HighlightLines::state);
+        }
+    }
+
+    /// Highlight one line of a file with the shared state `h`.
+    fn highlight_diff_line(&self, h: &mut HighlightLines, diff_line: &DiffLine) -> Line<'static> {
+        if diff_line.kind == LineKind::Removed {
+            // Removed lines: dimmed red, no syntax highlighting
+            let style = Style::default()
+                .fg(theme::removed_dim_fg())
+                .add_modifier(Modifier::DIM)
+                .bg(theme::removed_dim_bg());
+            return Line::from(Span::styled(diff_line.content.clone(), style));
+        }
+
+        // Context and Added lines: syntax highlight with shared state
+        let bg = match diff_line.kind {
+            LineKind::Added => Some(theme::added_bg()),
+            LineKind::Context => None,
+            LineKind::Removed => unreachable!(),
+        };
+
+        let line_with_newline = if diff_line.content.ends_with('\n') {
+            diff_line.content.clone()
+        } else {
+            format!("{}\n", diff_line.content)
+        };
+
+        match h.highlight_line(&line_with_newline, self.syntax_set) {
+            Ok(ranges) => {
+                let spans: Vec<Span> = ranges
                     .iter()
-                    .map(|hunk| {
-                        hunk.lines
-                            .iter()
-                            .map(|dl| Line::from(dl.content.clone()))
-                            .collect()
+                    .map(|(style, text)| {
+                        let fg =
+                            Color::Rgb(style.foreground.r, style.foreground.g, style.foreground.b);
+                        let mut ratatui_style = Style::default().fg(fg);
+
+                        if style.font_style.contains(FontStyle::BOLD) {
+                            ratatui_style = ratatui_style.add_modifier(Modifier::BOLD);
+                        }
+                        if style.font_style.contains(FontStyle::ITALIC) {
+                            ratatui_style = ratatui_style.add_modifier(Modifier::ITALIC);
+                        }
+
+                        if let Some(bg_color) = bg {
+                            ratatui_style = ratatui_style.bg(bg_color);
+                        }
+
+                        Span::styled(text.to_string(), ratatui_style)
                     })
                     .collect();
+                Line::from(spans)
             }
+            Err(_) => {
+                let style = match bg {
+                    Some(bg_color) => Style::default().bg(bg_color),
+                    None => Style::default(),
+                };
+                Line::from(Span::styled(diff_line.content.clone(), style))
+            }
+        }
+    }
+}
+
+/// Syntax highlighting of one file, done as far as it has been needed.
+#[derive(Default)]
+pub struct HighlightedFile {
+    /// Per hunk, the highlighted lines so far. A hunk's lines past the end
+    /// (or a missing hunk) aren't highlighted yet.
+    pub hunks: Vec<Vec<Line<'static>>>,
+    /// Syntax state after the last highlighted line.
+    state: Option<(HighlightState, ParseState)>,
+    /// For a large file highlighted a hunk at a time, the syntax state
+    /// after the last highlighted line of each hunk.
+    hunk_states: Vec<Option<(HighlightState, ParseState)>>,
+    /// Number of lines highlighted, across hunks.
+    done: usize,
+}
+
+impl HighlightedFile {
+    /// Number of lines highlighted, across hunks.
+    pub fn len(&self) -> usize {
+        self.hunks.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the highlighting of the hunks not in `keep`, of a large file
+    /// highlighted a hunk at a time.
+    fn keep_hunks(&mut self, keep: &[(usize, usize)]) {
+        let kept = |idx| keep.iter().any(|&(k, _)| k == idx);
+        for (idx, hunk) in self.hunks.iter_mut().enumerate() {
+            if !kept(idx) {
+                *hunk = Vec::new();
+            }
+        }
+        for (idx, state) in self.hunk_states.iter_mut().enumerate() {
+            if !kept(idx) {
+                *state = None;
+            }
+        }
+    }
+}
+
+/// Files with more diff lines than this are highlighted a hunk at a time
+/// with [`Highlighter::highlight_hunks`], as they are scrolled to, and
+/// have their lines packed while off screen (see
+/// [`App::pack_unviewed_files`](crate::app::App::pack_unviewed_files)).
+pub const LARGE_FILE_LINES: usize = 5_000;
+
+/// Whether a file with `hunks` has more than [`LARGE_FILE_LINES`] lines.
+pub fn is_large(hunks: &[Hunk]) -> bool {
+    hunks.iter().map(|h| h.lines.len()).sum::<usize>() > LARGE_FILE_LINES
+}
+
+/// Most files kept highlighted by a [`HighlightCache`].
+pub const CACHED_FILES: usize = 8;
+
+/// Most highlighted lines kept by a [`HighlightCache`], across files.
+pub const CACHED_LINES: usize = 20_000;
+
+/// The highlighting of the files viewed last, by file index, so going
+/// back to a file doesn't highlight it again. Files not viewed recently
+/// are dropped to keep at most [`CACHED_FILES`] files and
+/// [`CACHED_LINES`] lines.
+#[derive(Default)]
+pub struct HighlightCache {
+    /// Most recently viewed first.
+    files: VecDeque<(usize, HighlightedFile)>,
+}
+
+impl HighlightCache {
+    /// The highlighting of file `file_idx`, if it is cached.
+    pub fn get(&self, file_idx: usize) -> Option<&HighlightedFile> {
+        self.files
+            .iter()
+            .find(|(idx, _)| *idx == file_idx)
+            .map(|(_, file)| file)
+    }
+
+    /// The highlighting of file `file_idx`, now the most recently viewed,
+    /// with nothing highlighted if it wasn't cached.
+    pub fn viewed(&mut self, file_idx: usize) -> &mut HighlightedFile {
+        let entry = match self.files.iter().position(|(idx, _)| *idx == file_idx) {
+            Some(pos) => self.files.remove(pos).unwrap_or_default(),
+            None => (file_idx, HighlightedFile::default()),
         };
+        self.files.push_front(entry);
+        &mut self.files[0].1
+    }
 
-        let mut h = HighlightLines::new(syntax, theme);
-        let mut result = Vec::with_capacity(hunks.len());
-
-        for hunk in hunks {
-            let mut hunk_lines = Vec::with_capacity(hunk.lines.len());
-            for diff_line in &hunk.lines {
-                if diff_line.kind == LineKind::Removed {
-                    // Removed lines: dimmed red, no syntax highlighting
-                    let style = Style::default()
-                        .fg(Color::Red)
-                        .add_modifier(Modifier::DIM)
-                        .bg(theme::removed_dim_bg());
-                    hunk_lines.push(Line::from(Span::styled(diff_line.content.clone(), style)));
-                } else {
-                    // Context and Added lines: syntax highlight with shared state
-                    let bg = match diff_line.kind {
-                        LineKind::Added => Some(theme::added_bg()),
-                        LineKind::Context => None,
-                        LineKind::Removed => unreachable!(),
-                    };
-
-                    let line_with_newline = if diff_line.content.ends_with('\n') {
-                        diff_line.content.clone()
-                    } else {
-                        format!("{}\n", diff_line.content)
-                    };
-
-                    match h.highlight_line(&line_with_newline, &self.syntax_set) {
-                        Ok(ranges) => {
-                            let spans: Vec<Span> = ranges
-                                .iter()
-                                .map(|(style, text)| {
-                                    let fg = Color::Rgb(
-                                        style.foreground.r,
-                                        style.foreground.g,
-                                        style.foreground.b,
-                                    );
-                                    let mut ratatui_style = Style::default().fg(fg);
-
-                                    if style.font_style.contains(FontStyle::BOLD) {
-                                        ratatui_style = ratatui_style.add_modifier(Modifier::BOLD);
-                                    }
-                                    if style.font_style.contains(FontStyle::ITALIC) {
-                                        ratatui_style =
-                                            ratatui_style.add_modifier(Modifier::ITALIC);
-                                    }
-
-                                    if let Some(bg_color) = bg {
-                                        ratatui_style = ratatui_style.bg(bg_color);
-                                    }
-
-                                    Span::styled(text.to_string(), ratatui_style)
-                                })
-                                .collect();
-                            hunk_lines.push(Line::from(spans));
-                        }
-                        Err(_) => {
-                            let style = match bg {
-                                Some(bg_color) => Style::default().bg(bg_color),
-                                None => Style::default(),
-                            };
-                            hunk_lines
-                                .push(Line::from(Span::styled(diff_line.content.clone(), style)));
-                        }
-                    }
-                }
+    /// Drop the files viewed least recently while there are more than
+    /// [`CACHED_FILES`] or [`CACHED_LINES`] lines. When the most recent
+    /// file alone has more lines, which only a large file highlighted a
+    /// hunk at a time can, its hunks not in `visible` are dropped too.
+    pub fn trim(&mut self, visible: &[(usize, usize)]) {
+        let mut lines: usize = self.files.iter().map(|(_, file)| file.len()).sum();
+        while self.files.len() > CACHED_FILES || (self.files.len() > 1 && lines > CACHED_LINES) {
+            if let Some((_, file)) = self.files.pop_back() {
+                lines -= file.len();
             }
-            result.push(hunk_lines);
         }
+        if lines > CACHED_LINES
+            && let Some((_, file)) = self.files.front_mut()
+        {
+            file.keep_hunks(visible);
+        }
+    }
+
+    /// Follow the files to their new indices after a reorder:
+    /// `new_index[old]` is where file `old` went.
+    pub fn remap(&mut self, new_index: &[usize]) {
+        for (idx, _) in &mut self.files {
+            *idx = new_index[*idx];
+        }
+    }
+
+    /// Drop all highlighting, for a new diff or theme.
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+
+    /// Number of files cached.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
 
-        result
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
     }
 }
 
@@ -267,7 +555,7 @@ mod tests {
         let line = h.highlight_line("foo.rs", "let x = 42;", LineKind::Removed);
         // Should be dimmed red
         assert!(!line.spans.is_empty());
-        assert_eq!(line.spans[0].style.fg, Some(Color::Red));
+        assert_eq!(line.spans[0].style.fg, Some(theme::removed_dim_fg()));
     }
 
     #[test]
@@ -300,20 +588,27 @@ mod tests {
                         content: "use std::io;\n".to_string(),
                         old_lineno: Some(1),
                         new_lineno: Some(1),
+                        raw: None,
+                        no_newline: false,
                     },
                     DiffLine {
                         kind: LineKind::Removed,
                         content: "let x = 1;\n".to_string(),
                         old_lineno: Some(2),
                         new_lineno: None,
+                        raw: None,
+                        no_newline: false,
                     },
                     DiffLine {
                         kind: LineKind::Added,
                         content: "let x = 42;\n".to_string(),
                         old_lineno: None,
                         new_lineno: Some(2),
+                        raw: None,
+                        no_newline: false,
                     },
-                ],
+                ]
+                .into(),
                 status: HunkStatus::Pending,
                 old_start: 1,
                 old_lines: 3,
@@ -327,7 +622,10 @@ mod tests {
                     content: "fn main() {}\n".to_string(),
                     old_lineno: Some(10),
                     new_lineno: Some(11),
-                }],
+                    raw: None,
+                    no_newline: false,
+                }]
+                .into(),
                 status: HunkStatus::Pending,
                 old_start: 10,
                 old_lines: 3,
@@ -348,7 +646,10 @@ mod tests {
         // Removed line (index 1 of first hunk) should be red
         let removed_line = &result[0][1];
         assert!(!removed_line.spans.is_empty());
-        assert_eq!(removed_line.spans[0].style.fg, Some(Color::Red));
+        assert_eq!(
+            removed_line.spans[0].style.fg,
+            Some(theme::removed_dim_fg())
+        );
 
         // Added line (index 2 of first hunk) should have ADDED_BG
         let added_line = &result[0][2];
@@ -361,4 +662,186 @@ mod tests {
         let context_line = &result[0][0];
         assert!(!context_line.spans.is_empty());
     }
+
+    #[test]
+    fn test_load_user_themes_and_syntaxes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("themes")).unwrap();
+        std::fs::create_dir_all(dir.path().join("syntaxes")).unwrap();
+        std::fs::write(
+            dir.path().join("themes/paper.tmTheme"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+    <key>name</key><string>Paper</string>
+    <key>settings</key>
+    <array>
+        <dict>
+            <key>settings</key>
+            <dict>
+                <key>background</key><string>#FAFAFA</string>
+                <key>foreground</key><string>#202020</string>
+            </dict>
+        </dict>
+    </array>
+</dict>
+</plist>
+"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("themes/broken.tmTheme"), "not a plist").unwrap();
+        std::fs::write(
+            dir.path().join("syntaxes/stagentconf.sublime-syntax"),
+            "%YAML 1.2\n---\nname: Stagent Config\nfile_extensions: [stagentconf]\nscope: source.stagentconf\ncontexts:\n  main:\n    - match: '#.*$'\n      scope: comment.line\n",
+        )
+        .unwrap();
+
+        let themes = load_theme_set(Some(dir.path()));
+        let paper = &themes.themes["paper"];
+        assert_eq!(paper.name.as_deref(), Some("Paper"));
+        assert!(!themes.themes.contains_key("broken"));
+        assert!(themes.themes.contains_key("base16-ocean.dark"));
+
+        let syntaxes = load_syntax_set(Some(dir.path()));
+        assert!(syntaxes.find_syntax_by_extension("stagentconf").is_some());
+        assert!(syntaxes.find_syntax_by_extension("rs").is_some());
+
+        // Without a config directory only the defaults are there
+        assert!(
+            load_syntax_set(None)
+                .find_syntax_by_extension("stagentconf")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_theme_variant_from_background() {
+        assert_eq!(theme_variant("InspiredGitHub"), Some(ThemeVariant::Light));
+        assert_eq!(theme_variant("base16-ocean.dark"), Some(ThemeVariant::Dark));
+        assert_eq!(theme_variant("no-such-theme"), None);
+    }
+
+    #[test]
+    fn test_highlight_more_continues_where_it_stopped() {
+        use crate::types::{DiffLine, HunkStatus};
+
+        // A string spanning lines needs the state carried between calls
+        let lines = ["let s = \"a\n", "b\";\n", "let x = 1;\n"];
+        let hunks = vec![Hunk {
+            header: "@@ -1,3 +1,3 @@".to_string(),
+            lines: lines
+                .iter()
+                .map(|content| DiffLine {
+                    kind: LineKind::Context,
+                    content: content.to_string(),
+                    old_lineno: None,
+                    new_lineno: None,
+                    raw: None,
+                    no_newline: false,
+                })
+                .collect(),
+            status: HunkStatus::Pending,
+            old_start: 1,
+            old_lines: 3,
+            new_start: 1,
+            new_lines: 3,
+        }];
+
+        let h = Highlighter::new();
+        let mut file = HighlightedFile::default();
+        h.highlight_more("foo.rs", &hunks, &mut file, 1);
+        assert_eq!(file.hunks[0].len(), 1);
+        h.highlight_more("foo.rs", &hunks, &mut file, 10);
+        assert_eq!(file.hunks, h.highlight_file_lines("foo.rs", &hunks));
+    }
+
+    fn hunks(sizes: &[usize]) -> Vec<Hunk> {
+        use crate::types::HunkStatus;
+
+        sizes
+            .iter()
+            .map(|&size| Hunk {
+                header: "@@ -1 +1 @@".to_string(),
+                lines: (0..size)
+                    .map(|i| DiffLine {
+                        kind: LineKind::Context,
+                        content: format!("let x{} = {};\n", i, i),
+                        old_lineno: None,
+                        new_lineno: None,
+                        raw: None,
+                        no_newline: false,
+                    })
+                    .collect(),
+                status: HunkStatus::Pending,
+                old_start: 1,
+                old_lines: 1,
+                new_start: 1,
+                new_lines: 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_highlight_hunks_only_on_screen() {
+        let hunks = hunks(&[10, 10, 10]);
+        let h = Highlighter::new();
+        let mut file = HighlightedFile::default();
+
+        h.highlight_hunks("foo.rs", &hunks, &mut file, &[(1, 4)]);
+        assert_eq!(file.hunks.len(), 2);
+        assert!(file.hunks[0].is_empty());
+        assert_eq!(file.hunks[1].len(), 4);
+
+        // Scrolling on continues the hunk; nothing past its end
+        h.highlight_hunks("foo.rs", &hunks, &mut file, &[(1, 20), (2, 3)]);
+        assert_eq!(file.hunks[1].len(), 10);
+        assert_eq!(file.hunks[2].len(), 3);
+        assert_eq!(file.len(), 13);
+    }
+
+    #[test]
+    fn test_highlight_cache_drops_files_not_viewed_recently() {
+        let h = Highlighter::new();
+        let small = hunks(&[10]);
+        let mut cache = HighlightCache::default();
+        for file_idx in 0..CACHED_FILES + 2 {
+            h.highlight_more("foo.rs", &small, cache.viewed(file_idx), usize::MAX);
+            cache.trim(&[(0, 10)]);
+        }
+        assert_eq!(cache.len(), CACHED_FILES);
+        assert!(cache.get(0).is_none() && cache.get(1).is_none());
+
+        // Viewing file 2 again makes file 3 the least recently viewed
+        assert_eq!(cache.viewed(2).len(), 10);
+        h.highlight_more("foo.rs", &small, cache.viewed(100), usize::MAX);
+        cache.trim(&[(0, 10)]);
+        assert!(cache.get(2).is_some());
+        assert!(cache.get(3).is_none());
+
+        // Files are followed to their new indices
+        let mut new_index: Vec<usize> = (0..=100).collect();
+        new_index[100] = 0;
+        cache.remap(&new_index);
+        assert!(cache.get(0).is_some());
+        assert!(cache.get(100).is_none());
+    }
+
+    #[test]
+    fn test_highlight_cache_keeps_to_its_line_budget() {
+        let h = Highlighter::new();
+        let mut cache = HighlightCache::default();
+        let small = hunks(&[10]);
+        h.highlight_more("foo.rs", &small, cache.viewed(0), usize::MAX);
+
+        // A large file on screen pushes out the other files, then its
+        // own hunks that aren't on screen
+        cache.viewed(1).hunks = [CACHED_LINES / 2, CACHED_LINES / 2, 10]
+            .map(|size| vec![Line::default(); size])
+            .to_vec();
+        cache.trim(&[(2, 10)]);
+        assert_eq!(cache.len(), 1);
+        let file = cache.get(1).unwrap();
+        assert_eq!(file.len(), 10);
+        assert!(file.hunks[0].is_empty());
+    }
 }
@@ -0,0 +1,55 @@
+//! `--hook`: reviews run from a git hook, whose outcome decides whether git
+//! goes ahead.
+//!
+//! With `--hook pre-commit` the staged changes are reviewed read-only, and
+//! the commit is blocked when the reviewer left a `!blocker` comment or
+//! requested changes in the verdict. Installed as `.git/hooks/pre-commit`:
+//!
+//! ```sh
+//! #!/bin/sh
+//! exec stagent --hook pre-commit
+//! ```
+
+use crate::types::{HunkFeedback, ReviewVerdict, Severity, Verdict};
+
+/// The git hook stagent was run from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum Hook {
+    /// Review the staged changes before they are committed.
+    PreCommit,
+}
+
+/// Why the review blocks the commit: blocker feedback or a verdict
+/// requesting changes. `None` lets the commit go ahead.
+///
+/// ```
+/// use stagent::hook::commit_blockers;
+/// use stagent::types::{ReviewVerdict, Verdict};
+///
+/// assert_eq!(commit_blockers(&[], None), None);
+/// let verdict = ReviewVerdict { verdict: Verdict::RequestChanges, summary: None };
+/// assert_eq!(
+///     commit_blockers(&[], Some(&verdict)).as_deref(),
+///     Some("the review requested changes")
+/// );
+/// ```
+pub fn commit_blockers(
+    feedback: &[HunkFeedback],
+    verdict: Option<&ReviewVerdict>,
+) -> Option<String> {
+    let mut reasons = Vec::new();
+    if verdict.is_some_and(|v| v.verdict == Verdict::RequestChanges) {
+        reasons.push("the review requested changes".to_string());
+    }
+    let blockers = feedback
+        .iter()
+        .filter(|fb| fb.severity == Some(Severity::Blocker))
+        .count();
+    match blockers {
+        0 => {}
+        1 => reasons.push("1 blocker comment".to_string()),
+        n => reasons.push(format!("{} blocker comments", n)),
+    }
+    (!reasons.is_empty()).then(|| reasons.join(" and "))
+}
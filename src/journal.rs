@@ -0,0 +1,240 @@
+//! Feedback journal: every piece of feedback is appended to a file as soon
+//! as it is captured, so a crash or a killed tmux session loses nothing.
+//!
+//! Feedback is otherwise only written on a clean exit. Each journal line is
+//! a JSON object holding a feedback item's index and its new value, or none
+//! once deleted; replaying the lines in order rebuilds the feedback list,
+//! including comments that were later extended or deleted. The journal is
+//! removed once the final output has been written. Journals whose stagent
+//! process is gone are recovered into the feedback archive on the next run
+//! (see [`archive_interrupted`]).
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::archive;
+use crate::feedback;
+use crate::recovery;
+use crate::types::HunkFeedback;
+
+const PREFIX: &str = "stagent-journal-";
+const EXT: &str = ".jsonl";
+
+/// One journal line: feedback item `index` is now `feedback`, or was
+/// deleted if there is none.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    feedback: Option<HunkFeedback>,
+}
+
+/// An open journal file of this process.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+/// Journal directory for a repository (`.git/stagent`).
+pub fn journal_dir(repo: &git2::Repository) -> PathBuf {
+    repo.path().join("stagent")
+}
+
+impl Journal {
+    /// Create this process's journal in `dir`, replacing any stale one.
+    pub fn create(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(format!("{}{}{}", PREFIX, std::process::id(), EXT));
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create journal {}", path.display()))?;
+        Ok(Self { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append feedback item `index` and flush it to disk.
+    pub fn record(&mut self, index: usize, feedback: &HunkFeedback) -> Result<()> {
+        self.append(Entry {
+            index,
+            feedback: Some(feedback.clone()),
+        })
+    }
+
+    /// Append the deletion of feedback item `index`; later items move
+    /// down one index.
+    pub fn record_removal(&mut self, index: usize) -> Result<()> {
+        self.append(Entry {
+            index,
+            feedback: None,
+        })
+    }
+
+    fn append(&mut self, entry: Entry) -> Result<()> {
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|()| self.file.sync_data())
+            .context("Failed to append to the feedback journal")
+    }
+
+    /// Delete the journal once the final output has been written.
+    pub fn remove(self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Rebuild the feedback list from a journal. A truncated last line (from a
+/// crash mid-write) is ignored.
+pub fn replay(path: &Path) -> Result<Vec<HunkFeedback>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read journal {}", path.display()))?;
+    let mut items: Vec<HunkFeedback> = Vec::new();
+    for entry in content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Entry>(line).ok())
+    {
+        match (entry.feedback, entry.index < items.len()) {
+            (Some(feedback), true) => items[entry.index] = feedback,
+            (Some(feedback), false) => items.push(feedback),
+            (None, true) => {
+                items.remove(entry.index);
+            }
+            (None, false) => {}
+        }
+    }
+    Ok(items)
+}
+
+/// Journals in `dir` left by stagent processes that are no longer running.
+pub fn find_interrupted(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut found: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix(PREFIX)?.strip_suffix(EXT))
+                .and_then(|pid| pid.parse::<u32>().ok())
+                .is_some_and(|pid| !recovery::process_alive(pid))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// Move the feedback of interrupted journals in `dir` into the archive at
+/// `archive_dir`, and delete the journals. Returns the archived files with
+/// their number of feedback items.
+pub fn archive_interrupted(dir: &Path, archive_dir: &Path) -> Result<Vec<(PathBuf, usize)>> {
+    let mut archived = Vec::new();
+    for path in find_interrupted(dir) {
+        let items = replay(&path)?;
+        if !items.is_empty() {
+            let output = feedback::format_feedback(&items, feedback::DEFAULT_CONTEXT_LINES);
+            archived.push((
+                archive::archive_feedback(archive_dir, &output)?,
+                items.len(),
+            ));
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+    Ok(archived)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FeedbackKind;
+    use std::fs::OpenOptions;
+
+    fn comment(content: &str) -> HunkFeedback {
+        HunkFeedback {
+            file_path: "src/a.rs".to_string(),
+            hunk_header: "@@ -1 +1 @@".to_string(),
+            kind: FeedbackKind::Comment,
+            content: content.to_string(),
+            context_lines: Vec::new(),
+            comment_positions: Vec::new(),
+            suggestion: None,
+            severity: None,
+        }
+    }
+
+    #[test]
+    fn test_replay_applies_updates_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = Journal::create(dir.path()).unwrap();
+        journal.record(0, &comment("first")).unwrap();
+        journal.record(1, &comment("second")).unwrap();
+        journal.record(0, &comment("first\nmore")).unwrap();
+
+        // A write cut off by a crash
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(journal.path())
+            .unwrap();
+        file.write_all(b"{\"index\":2,\"feedb").unwrap();
+
+        let items = replay(journal.path()).unwrap();
+        let contents: Vec<&str> = items.iter().map(|f| f.content.as_str()).collect();
+        assert_eq!(contents, vec!["first\nmore", "second"]);
+
+        let path = journal.path().to_path_buf();
+        journal.remove();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_replay_applies_removals() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = Journal::create(dir.path()).unwrap();
+        journal.record(0, &comment("first")).unwrap();
+        journal.record(1, &comment("second")).unwrap();
+        journal.record(2, &comment("third")).unwrap();
+        journal.record_removal(0).unwrap();
+        journal.record(0, &comment("second, revised")).unwrap();
+        journal.record(2, &comment("fourth")).unwrap();
+
+        let items = replay(journal.path()).unwrap();
+        let contents: Vec<&str> = items.iter().map(|f| f.content.as_str()).collect();
+        assert_eq!(contents, vec!["second, revised", "third", "fourth"]);
+    }
+
+    #[test]
+    fn test_interrupted_journals_are_archived() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_dir = dir.path().join("feedback");
+
+        // Our own journal is live and left alone
+        let mut live = Journal::create(dir.path()).unwrap();
+        live.record(0, &comment("live")).unwrap();
+
+        let dead = dir.path().join(format!("{}999999999{}", PREFIX, EXT));
+        let entry = Entry {
+            index: 0,
+            feedback: Some(comment("lost?")),
+        };
+        std::fs::write(&dead, serde_json::to_string(&entry).unwrap() + "\n").unwrap();
+
+        assert_eq!(find_interrupted(dir.path()), vec![dead.clone()]);
+        let archived = archive_interrupted(dir.path(), &archive_dir).unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].1, 1);
+        let output = std::fs::read_to_string(&archived[0].0).unwrap();
+        assert!(output.contains("# REVIEW COMMENT: lost?"));
+        assert!(!dead.exists());
+        assert!(live.path().exists());
+    }
+}
@@ -0,0 +1,137 @@
+//! The jump list: hunks visited by jumping, for going back and forth
+//! between them like vim's `Ctrl+o` / `Ctrl+i`.
+//!
+//! Jumps are moves that can land far from where they started: to the next
+//! pending hunk, a mouse click, a feedback item or `--start-file`. Moving
+//! hunk by hunk isn't a jump and leaves the list alone.
+
+/// A (file, hunk) index pair.
+pub type JumpPosition = (usize, usize);
+
+/// At most this many positions are remembered; the oldest go first.
+pub const MAX_JUMPS: usize = 100;
+
+/// Positions jumped away from, oldest first, and where in them
+/// `Ctrl+o` / `Ctrl+i` have moved to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JumpList {
+    entries: Vec<JumpPosition>,
+    /// Index into `entries` of the position going back and forth has
+    /// reached; `entries.len()` when not going back and forth.
+    index: usize,
+}
+
+impl JumpList {
+    /// Record a jump away from `from`. Positions gone forward from are
+    /// dropped, as a new jump starts a new history from here.
+    pub fn record(&mut self, from: JumpPosition) {
+        self.entries.truncate(self.index);
+        if self.entries.last() != Some(&from) {
+            self.entries.push(from);
+        }
+        if self.entries.len() > MAX_JUMPS {
+            self.entries.remove(0);
+        }
+        self.index = self.entries.len();
+    }
+
+    /// The position to go back to from `current`, if any. The first step
+    /// back remembers `current`, so going forward again returns to it.
+    pub fn back(&mut self, current: JumpPosition) -> Option<JumpPosition> {
+        if self.index == self.entries.len() {
+            if self.entries.last() != Some(&current) {
+                self.entries.push(current);
+            }
+            self.index = self.entries.len() - 1;
+        }
+        let target = (0..self.index)
+            .rev()
+            .find(|&i| self.entries[i] != current)?;
+        self.index = target;
+        Some(self.entries[target])
+    }
+
+    /// The position to go forward to from `current` after going back, if
+    /// any.
+    pub fn forward(&mut self, current: JumpPosition) -> Option<JumpPosition> {
+        let target = (self.index + 1..self.entries.len()).find(|&i| self.entries[i] != current)?;
+        self.index = target;
+        Some(self.entries[target])
+    }
+
+    /// Follow files to their new indices after the file list is re-sorted:
+    /// the file at index `i` moved to `new_index[i]`.
+    pub fn remap_files(&mut self, new_index: &[usize]) {
+        for (file, _) in &mut self.entries {
+            if let Some(&new) = new_index.get(*file) {
+                *file = new;
+            }
+        }
+    }
+
+    /// Forget all positions, e.g. when the hunks they point to change.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_back_and_forward_retrace_jumps() {
+        let mut jumps = JumpList::default();
+        assert_eq!(jumps.back((0, 0)), None);
+
+        // (0, 0) -> (1, 2) -> (3, 0)
+        jumps.record((0, 0));
+        jumps.record((1, 2));
+        assert_eq!(jumps.back((3, 0)), Some((1, 2)));
+        assert_eq!(jumps.back((1, 2)), Some((0, 0)));
+        assert_eq!(jumps.back((0, 0)), None, "nothing older");
+        assert_eq!(jumps.forward((0, 0)), Some((1, 2)));
+        assert_eq!(jumps.forward((1, 2)), Some((3, 0)), "back to the start");
+        assert_eq!(jumps.forward((3, 0)), None);
+    }
+
+    #[test]
+    fn test_new_jump_drops_forward_history() {
+        let mut jumps = JumpList::default();
+        jumps.record((0, 0));
+        jumps.record((1, 0));
+        assert_eq!(jumps.back((2, 0)), Some((1, 0)));
+
+        // Jumping from (1, 0) to (5, 5) forgets (2, 0)
+        jumps.record((1, 0));
+        assert_eq!(jumps.forward((5, 5)), None);
+        assert_eq!(jumps.back((5, 5)), Some((1, 0)));
+        assert_eq!(jumps.back((1, 0)), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_repeated_positions_are_skipped() {
+        let mut jumps = JumpList::default();
+        jumps.record((0, 0));
+        jumps.record((0, 0));
+        jumps.record((2, 1));
+        // Going back from where the last jump started skips over it
+        assert_eq!(jumps.back((2, 1)), Some((0, 0)));
+        assert_eq!(jumps.back((0, 0)), None);
+    }
+
+    #[test]
+    fn test_oldest_positions_are_dropped() {
+        let mut jumps = JumpList::default();
+        for i in 0..MAX_JUMPS + 10 {
+            jumps.record((i, 0));
+        }
+        let mut oldest = None;
+        let mut current = (MAX_JUMPS + 10, 0);
+        while let Some(position) = jumps.back(current) {
+            oldest = Some(position);
+            current = position;
+        }
+        assert_eq!(oldest, Some((10, 0)));
+    }
+}
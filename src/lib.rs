@@ -1,11 +1,90 @@
+//! stagent: interactive hunk-by-hunk review and staging of git changes.
+//!
+//! Besides the `stagent` binary, the crate exposes the diff handling it is
+//! built on, for tools that want to parse, split or stage hunks and format
+//! review feedback without the TUI:
+//!
+//! - [`patch::parse_unified_diff`]: parse `git diff` output into [`types::FileDiff`]s
+//! - [`diff::parse_diff`]: the same from a `git2::Diff`
+//! - [`diff::split_hunk`]: split a hunk at unchanged lines
+//! - [`staging::reconstruct_blob`] / [`staging::stage_hunk`]: apply a single
+//!   hunk to file content / to the index
+//! - [`feedback::format_feedback`]: render review feedback as a unified diff
+//! - [`review::ReviewSession`]: the review itself (stage, skip and comment
+//!   on hunks, collect the feedback), to drive from another interface
+//!
+//! These functions and the [`types`] they use follow semver. Other public
+//! modules exist for the binary and its tests and may change in any release.
+//!
+//! # Features
+//!
+//! - `tui` (default): the interactive reviewer ([`app`], [`ui`], syntax
+//!   highlighting, `stagent print`) and the binary. Pulls in ratatui,
+//!   crossterm, syntect and clap. Disable it to depend on the library layer
+//!   alone:
+//!
+//! ```toml
+//! stagent = { version = "0.1", default-features = false }
+//! ```
+//!
+//! # Example
+//!
+//! ```
+//! use stagent::{patch, staging};
+//!
+//! let diff = "\
+//! diff --git a/greeting.txt b/greeting.txt
+//! --- a/greeting.txt
+//! +++ b/greeting.txt
+//! @@ -1,2 +1,2 @@
+//!  hello
+//! -world
+//! +there
+//! ";
+//! let files = patch::parse_unified_diff(diff)?;
+//! let hunk = &files[0].hunks[0];
+//! let staged = staging::reconstruct_blob(b"hello\nworld\n", hunk, 0)?;
+//! assert_eq!(staged, b"hello\nthere\n");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+#[cfg(feature = "tui")]
 pub mod app;
+pub mod archive;
+pub mod blame;
+pub mod bulk;
+pub mod context;
 pub mod diff;
 pub mod editor;
+pub mod export;
 pub mod feedback;
+pub mod file_tree;
+pub mod filter;
 pub mod git;
+#[cfg(feature = "tui")]
 pub mod highlight;
+pub mod hook;
+pub mod journal;
+pub mod jumps;
+pub mod messages;
+pub mod multiplexer;
+pub mod notes;
 pub mod patch;
+#[cfg(feature = "tui")]
+pub mod print;
+pub mod range;
+pub mod recovery;
+pub mod reminders;
+pub mod review;
+pub mod risk;
+#[cfg(feature = "tui")]
+pub mod script;
+pub mod session;
 pub mod spawn;
 pub mod staging;
+pub mod stats;
+pub mod timing;
+pub mod tmux;
 pub mod types;
+#[cfg(feature = "tui")]
 pub mod ui;
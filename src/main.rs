@@ -1,9 +1,25 @@
-use anyhow::{Result, bail};
-use clap::Parser;
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand};
 use git2::Repository;
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 
-use stagent::types::FileDiff;
+use stagent::app::{App, RunOptions};
+use stagent::editor::{SplitDirection, SplitLayout, SplitSize};
+use stagent::filter::PathFilter;
+use stagent::git::{DiffAlgorithm, DiffSettings, IntentToAdd};
+use stagent::hook::Hook;
+use stagent::multiplexer::MultiplexerKind;
+use stagent::print::ColorMode;
+use stagent::range::{RangeCommit, RevRange};
+use stagent::session::{self, Session};
+use stagent::stats::ReviewStats;
+use stagent::types::{
+    FeedbackFormat, FileDiff, HunkFeedback, OutputStyle, ReviewOrder, StartPosition, ViewMode,
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -11,22 +27,46 @@ use stagent::types::FileDiff;
     about = "Interactive TUI code review tool for staged diffs"
 )]
 pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Write feedback output to a file instead of stdout
     #[arg(long, value_name = "FILE")]
     output: Option<PathBuf>,
 
+    /// Also write the edits made during the review as a patch against the
+    /// working tree, for `git apply`
+    #[arg(long, value_name = "FILE")]
+    edits_patch: Option<PathBuf>,
+
     /// Don't actually stage hunks (review-only mode)
     #[arg(long)]
     no_stage: bool,
 
-    /// Only show files matching this glob pattern
-    #[arg(long, value_name = "GLOB")]
-    files: Option<String>,
+    /// Only show files matching this glob (relative to the current directory;
+    /// repeatable, supports `**` and `{a,b}`, `!GLOB` excludes)
+    #[arg(long, value_name = "GLOB", global = true)]
+    files: Vec<String>,
+
+    /// Hide files matching this glob (repeatable)
+    #[arg(long, value_name = "GLOB", global = true)]
+    exclude: Vec<String>,
 
-    /// Color theme name
-    #[arg(long, default_value = "default")]
+    /// Color theme: dark, light, auto, or a syntax highlighting theme
+    /// (built in or a .tmTheme in ~/.config/stagent/themes)
+    #[arg(long, default_value = "default", global = true)]
     theme: String,
 
+    /// Feedback output format (change it during the review with `:format`)
+    #[arg(long, value_enum, default_value_t = FeedbackFormat::Diff)]
+    format: FeedbackFormat,
+
+    /// How comments sit in diff-format feedback: `annotated` lines among
+    /// the diff lines, or `strict-diff`, whole hunks that parse and apply
+    /// as a diff with the comments in blocks between files
+    #[arg(long, value_enum, default_value_t = OutputStyle::Annotated)]
+    output_style: OutputStyle,
+
     /// Number of context lines to show around changes in comment feedback
     #[arg(short = 'C', long = "context-lines", default_value_t = stagent::feedback::DEFAULT_CONTEXT_LINES)]
     context_lines: usize,
@@ -35,54 +75,531 @@ pub struct Cli {
     #[arg(long)]
     spawn: bool,
 
+    /// With --spawn: close the review's pane if it is still open after this
+    /// many seconds (0 waits for as long as it takes) [default: 1800]
+    #[arg(long, value_name = "SECS", requires = "spawn")]
+    spawn_timeout: Option<u64>,
+
+    /// Where editors (and --spawn) open: a tmux split, a zellij pane, or
+    /// this terminal with the review suspended; auto picks tmux or zellij
+    /// from the session stagent runs in
+    #[arg(long, value_enum, default_value_t = MultiplexerKind::Auto)]
+    multiplexer: MultiplexerKind,
+
+    /// How tmux opens editors and --spawn: beside stagent (horizontal),
+    /// below it (vertical), or in a new window [default: horizontal, or
+    /// git config stagent.split]
+    #[arg(long, value_enum)]
+    split: Option<SplitDirection>,
+
+    /// Size of the split: a percentage (40%) or a number of columns or rows
+    /// [default: 50%, or git config stagent.splitSize]
+    #[arg(long, value_name = "SIZE")]
+    split_size: Option<SplitSize>,
+
+    /// Run the review from a script of actions (next, stage, skip, comment
+    /// "text", ...; `-` reads stdin) instead of the TUI; needs no terminal
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["spawn", "patch"])]
+    script: Option<PathBuf>,
+
+    /// Print the diff (files, hunks and lines with their kinds and line
+    /// numbers) as JSON to stdout instead of starting the TUI
+    #[arg(long, conflicts_with_all = ["spawn", "script"])]
+    dump_json: bool,
+
     /// Read a unified diff from stdin instead of computing one from git
-    #[arg(short = 'p', long = "patch")]
+    #[arg(short = 'p', long = "patch", global = true)]
     patch: bool,
+
+    /// Read a unified diff from a file instead of computing one from git
+    /// (works with --spawn, unlike --patch)
+    #[arg(long, value_name = "PATH", global = true, conflicts_with = "patch")]
+    patch_file: Option<PathBuf>,
+
+    /// With --patch or --patch-file: stage accepted hunks into the index of
+    /// the repository in the current directory (the patch must apply to it)
+    #[arg(long)]
+    apply: bool,
+
+    /// Run from a git hook. `pre-commit` reviews the staged changes without
+    /// staging and fails, blocking the commit, on a `!blocker` comment or a
+    /// request-changes verdict
+    #[arg(
+        long,
+        value_enum,
+        value_name = "HOOK",
+        conflicts_with_all = ["spawn", "patch", "patch_file", "resume", "watch"]
+    )]
+    hook: Option<Hook>,
+
+    /// Review the changes of a range of commits (`A..B`, `A...B`, or `A`
+    /// for `A..HEAD`) without staging; `O` lists its commits to comment on
+    /// them
+    #[arg(
+        long,
+        value_name = "RANGE",
+        conflicts_with_all = ["patch", "patch_file", "apply", "hook", "resume", "watch"]
+    )]
+    range: Option<String>,
+
+    /// Start with this file selected (use `path:line` to select the hunk containing a line)
+    #[arg(long, value_name = "PATH[:LINE]")]
+    start_file: Option<String>,
+
+    /// Start with the n-th hunk (1-based) of the start file selected
+    #[arg(long, value_name = "N")]
+    start_hunk: Option<usize>,
+
+    /// Diff layout: unified or side-by-side (toggle with `S` in the TUI)
+    #[arg(long, value_enum, default_value_t = ViewMode::Unified, global = true)]
+    view: ViewMode,
+
+    /// Draw tabs and spaces in the diff view as → and ·
+    #[arg(long, global = true)]
+    show_whitespace: bool,
+
+    /// Columns between tab stops in the diff view
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = stagent::ui::diff_view::DEFAULT_TAB_WIDTH,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=32),
+        global = true
+    )]
+    tab_width: usize,
+
+    /// Hide changes that only alter whitespace
+    #[arg(short = 'w', long, global = true)]
+    ignore_whitespace: bool,
+
+    /// Hide changes that only add or remove blank lines
+    #[arg(long, global = true)]
+    ignore_blank_lines: bool,
+
+    /// How lines are matched when computing the diff (default: git's
+    /// diff.algorithm, else myers); patience keeps reformatted code
+    /// readable. Ignored in patch mode
+    #[arg(long, value_enum, value_name = "ALGORITHM", global = true)]
+    diff_algorithm: Option<DiffAlgorithm>,
+
+    /// Leave untracked files out of the review
+    #[arg(long, global = true)]
+    no_untracked: bool,
+
+    /// Show untracked files without adding them to the index with
+    /// intent-to-add; staging a hunk of one adds the file
+    #[arg(long, global = true, conflicts_with = "no_untracked")]
+    no_ita: bool,
+
+    /// Unchanged lines around each change when computing the diff (default
+    /// 3); fewer lines give smaller hunks. Ignored in patch mode
+    #[arg(short = 'U', long, value_name = "N", global = true)]
+    unified: Option<u32>,
+
+    /// Start with an overview of the changed files, where files can be left
+    /// out of the review
+    #[arg(long)]
+    overview: bool,
+
+    /// When quitting with feedback, ask for an overall verdict (approve,
+    /// request changes, comment) and a summary, output before the feedback
+    #[arg(long)]
+    verdict: bool,
+
+    /// Time the review: record how long each hunk was selected and output
+    /// the time per hunk and file before the feedback
+    #[arg(long)]
+    track_time: bool,
+
+    /// Record review outcomes as git notes (refs/notes/stagent)
+    #[arg(long)]
+    notes: bool,
+
+    /// Resume the review saved when stagent last quit with pending hunks
+    #[arg(long)]
+    resume: bool,
+
+    /// Refresh the diff when files in the working tree change
+    #[arg(long)]
+    watch: bool,
+
+    /// Split hunks longer than MAX_LINES diff lines at unchanged lines on load
+    /// (default 20)
+    // The missing value matches stagent::diff::DEFAULT_AUTO_SPLIT_LINES
+    #[arg(
+        long,
+        value_name = "MAX_LINES",
+        num_args = 0..=1,
+        default_missing_value = "20"
+    )]
+    auto_split: Option<usize>,
+
+    /// File order: by path, riskiest first (churn and bug-fix history), most
+    /// pending hunks first, or most changed lines first
+    #[arg(long, value_enum, default_value_t = ReviewOrder::Path)]
+    order: ReviewOrder,
+
+    /// After quitting, print a machine-readable `STAGENT_RESULT: {...}` line
+    /// with hunk counts per status and the number of feedback items to stderr
+    #[arg(long)]
+    summary: bool,
+
+    /// After quitting, publish the feedback as a pending review on a pull
+    /// request (`github:<owner>/<repo>#<pr>`; token from GITHUB_TOKEN)
+    #[arg(long, value_name = "TARGET")]
+    publish: Option<String>,
+
+    /// With --publish: print the request payload to stderr instead of
+    /// sending it
+    #[arg(long, requires = "publish")]
+    dry_run: bool,
+
+    /// Set by --spawn --summary on the child process: write the summary line
+    /// to this file for the parent to print
+    #[arg(long, value_name = "FILE", hide = true)]
+    summary_file: Option<PathBuf>,
+
+    /// Set by --spawn on the child process; enables the review handoff
+    #[arg(long, hide = true)]
+    spawned: bool,
+
+    /// Set by --spawn on the child process: write the exit status (0 or 1)
+    /// to this file for the parent to report
+    #[arg(long, value_name = "FILE", hide = true)]
+    status_file: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Whether the diff comes from `--patch` or `--patch-file` rather than
+    /// the working tree.
+    fn is_patch_mode(&self) -> bool {
+        self.patch || self.patch_file.is_some()
+    }
+
+    /// How the diff is computed: `--ignore-whitespace`,
+    /// `--ignore-blank-lines`, `--unified`, `--no-untracked` and
+    /// `--diff-algorithm`.
+    fn diff_settings(&self) -> DiffSettings {
+        DiffSettings {
+            ignore_whitespace: self.ignore_whitespace,
+            ignore_blank_lines: self.ignore_blank_lines,
+            context_lines: self.unified,
+            exclude_untracked: self.no_untracked,
+            algorithm: self.diff_algorithm,
+        }
+    }
+
+    /// Whether untracked files are added to the index with intent-to-add
+    /// so they can be staged hunk by hunk. Off with `--no-ita`, and
+    /// pointless with `--no-untracked`.
+    fn intent_to_add(&self) -> bool {
+        !self.no_ita && !self.no_untracked
+    }
+
+    /// The tmux split layout: `--split` and `--split-size`, falling back to
+    /// the `stagent.split` and `stagent.splitSize` git config.
+    fn split_layout(&self) -> Result<SplitLayout> {
+        let mut layout = SplitLayout::from_git_config(&git_config()?)?;
+        if let Some(direction) = self.split {
+            layout.direction = direction;
+        }
+        if let Some(size) = self.split_size {
+            layout.size = size;
+        }
+        Ok(layout)
+    }
+}
+
+/// The git config of the repository in the current directory, or the
+/// global one outside a repository.
+fn git_config() -> Result<git2::Config> {
+    match Repository::discover(".") {
+        Ok(repo) => repo.config(),
+        Err(_) => git2::Config::open_default(),
+    }
+    .context("Failed to read git config")
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Browse feedback archived from earlier sessions
+    Feedback {
+        #[command(subcommand)]
+        action: FeedbackAction,
+    },
+    /// Print the diff with syntax highlighting and review statuses, without
+    /// the TUI
+    Print {
+        /// When to use colors
+        #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+        color: ColorMode,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum FeedbackAction {
+    /// List archived feedback, newest first
+    List,
+    /// Print archived feedback (defaults to the latest)
+    Show {
+        /// Feedback id (or a unique prefix of one), or `latest`
+        #[arg(default_value = "latest")]
+        id: String,
+    },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    let result = run(&cli);
+    if let Some(ref path) = cli.status_file {
+        let status = if result.is_ok() { 0 } else { 1 };
+        // The error itself is still reported below
+        let _ = std::fs::write(path, format!("{}\n", status));
+    }
+    result
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    // Subcommands don't start the TUI, so they work outside tmux
+    if let Some(Command::Feedback { ref action }) = cli.command {
+        return run_feedback_command(action);
+    }
 
     // Initialise color theme before anything renders
+    stagent::highlight::load();
     stagent::ui::theme::init(&cli.theme);
 
-    // Check tmux
-    if std::env::var("TMUX").is_err() {
-        bail!("stagent requires tmux. Please run inside a tmux session.");
+    if let Some(Command::Print { color }) = cli.command {
+        return run_print_command(cli, color);
+    }
+
+    if cli.dump_json {
+        return run_dump_json(cli);
+    }
+
+    // Catch a bad target before the review rather than after it
+    if let Some(ref target) = cli.publish {
+        stagent::export::github::PullRequest::parse(target)?;
+        if !cli.dry_run {
+            stagent::export::github::token()?;
+        }
+    }
+    if cli.dry_run && cli.spawn {
+        bail!(
+            "--dry-run cannot be used with --spawn (the payload would be printed in the closed pane)"
+        );
+    }
+
+    // Without tmux or zellij the review runs in this terminal, so there
+    // must be one; a scripted review never opens the TUI
+    let multiplexer = cli.multiplexer.resolve();
+    if cli.spawn && multiplexer == MultiplexerKind::Terminal && cli.spawn_timeout.is_some() {
+        eprintln!(
+            "Warning: no tmux or zellij to spawn into, so the review runs here and --spawn-timeout is ignored"
+        );
+    }
+    if cli.script.is_none() && multiplexer == MultiplexerKind::Terminal && !has_terminal(cli) {
+        bail!(
+            "stagent needs tmux, zellij or an interactive terminal. Please run it inside one, or use --script or --dump-json."
+        );
     }
 
     // --patch + --spawn is not supported (stdin can't be forwarded through tmux split)
     if cli.patch && cli.spawn {
         bail!(
-            "--patch and --spawn cannot be used together (stdin cannot be forwarded through a tmux split)"
+            "--patch and --spawn cannot be used together (stdin cannot be forwarded through a tmux split); use --patch-file"
         );
     }
 
-    // Handle --spawn mode: spawn stagent in a split and wait for completion
-    if cli.spawn {
-        let opts = stagent::spawn::SpawnOptions {
-            output: cli.output.clone(),
-            files: cli.files.clone(),
-            theme: cli.theme.clone(),
-            context_lines: cli.context_lines,
-            no_stage: cli.no_stage,
-        };
-        return stagent::spawn::spawn_in_split(&opts);
+    if cli.apply && !cli.is_patch_mode() {
+        bail!("--apply requires --patch or --patch-file");
+    }
+    if cli.apply && cli.no_stage {
+        bail!("--apply and --no-stage cannot be used together");
+    }
+
+    // Sessions are stored in the repository and keyed by the working tree diff
+    if cli.is_patch_mode() && cli.resume {
+        bail!("--resume is not supported with --patch or --patch-file");
+    }
+
+    // A patch has no working tree to watch
+    if cli.is_patch_mode() && cli.watch {
+        bail!("--watch is not supported with --patch or --patch-file");
+    }
+
+    // Handle --spawn mode: spawn stagent in a split and wait for completion.
+    // Without a split to spawn into, the review runs right here.
+    if cli.spawn && multiplexer != MultiplexerKind::Terminal {
+        return run_spawn(cli);
+    }
+
+    if let Some(hook) = cli.hook {
+        return run_hook_mode(cli, hook);
+    }
+
+    if let Some(ref spec) = cli.range {
+        return run_range_mode(cli, spec);
     }
 
-    if cli.patch {
-        return run_patch_mode(&cli);
+    if cli.is_patch_mode() {
+        return run_patch_mode(cli);
     }
 
-    run_git_mode(&cli)
+    run_git_mode(cli)
+}
+
+/// Whether the review can run in this terminal. Git runs hooks with stdin
+/// from /dev/null and stdout on stderr, so a hook's review reads keys from
+/// the controlling terminal instead.
+fn has_terminal(cli: &Cli) -> bool {
+    if cli.hook.is_some() {
+        std::io::stdout().is_terminal() && std::fs::File::open("/dev/tty").is_ok()
+    } else {
+        std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+    }
+}
+
+/// Handle `--hook`: review the staged changes without staging, then fail if
+/// the review blocks the commit.
+fn run_hook_mode(cli: &Cli, hook: Hook) -> Result<()> {
+    let repo = stagent::git::open_repo(".")?;
+    let cwd_prefix = stagent::git::cwd_prefix(&repo, &std::env::current_dir()?);
+    let files = match hook {
+        Hook::PreCommit => stagent::git::get_staged_diff_with(&repo, &cli.diff_settings())?,
+    };
+
+    // Like a patch: the diff is not the working tree's, so there is
+    // nothing to stage, refresh or blame against the index
+    let app = run_review_pipeline(
+        files,
+        None,
+        &cwd_prefix,
+        true,
+        "No staged changes to review.",
+        cli,
+        None,
+    )?;
+    if let Some(app) = app
+        && let Some(reason) = stagent::hook::commit_blockers(&app.feedback, app.verdict.as_ref())
+    {
+        bail!("Commit blocked: {}", reason);
+    }
+    Ok(())
+}
+
+/// Handle `--spawn`: run the review in a new pane, wait for it to close,
+/// then report where the feedback went and how the review ended.
+fn run_spawn(cli: &Cli) -> Result<()> {
+    let output = match cli.output {
+        // The pane may start in another directory
+        Some(ref output) => std::path::absolute(output)?,
+        // The child's stdout is gone with its pane, so the feedback goes to
+        // a file the caller can read afterwards
+        None => tempfile::Builder::new()
+            .prefix("stagent-feedback-")
+            .suffix(match cli.format {
+                FeedbackFormat::Diff => ".diff",
+                FeedbackFormat::Json | FeedbackFormat::Gerrit => ".json",
+            })
+            .tempfile()?
+            .into_temp_path()
+            .keep()?,
+    };
+    // The child's stderr goes to its pane; it leaves its exit status and
+    // summary in files instead
+    let temp_path = |prefix: &str| -> Result<_> {
+        Ok(tempfile::Builder::new()
+            .prefix(prefix)
+            .tempfile()?
+            .into_temp_path())
+    };
+    let status_file = temp_path("stagent-status-")?;
+    let summary_file = cli
+        .summary
+        .then(|| temp_path("stagent-summary-"))
+        .transpose()?;
+
+    let opts = stagent::spawn::SpawnOptions {
+        output: Some(output.clone()),
+        // Relative to this directory, not the child's
+        edits_patch: cli
+            .edits_patch
+            .as_deref()
+            .map(std::path::absolute)
+            .transpose()?,
+        files: cli.files.clone(),
+        exclude: cli.exclude.clone(),
+        theme: cli.theme.clone(),
+        context_lines: cli.context_lines,
+        format: cli.format,
+        no_stage: cli.no_stage,
+        start_file: cli.start_file.clone(),
+        start_hunk: cli.start_hunk,
+        view: cli.view,
+        show_whitespace: cli.show_whitespace,
+        tab_width: Some(cli.tab_width)
+            .filter(|&width| width != stagent::ui::diff_view::DEFAULT_TAB_WIDTH),
+        ignore_whitespace: cli.ignore_whitespace,
+        ignore_blank_lines: cli.ignore_blank_lines,
+        no_untracked: cli.no_untracked,
+        no_ita: cli.no_ita,
+        diff_algorithm: cli.diff_algorithm,
+        overview: cli.overview,
+        unified: cli.unified,
+        verdict: cli.verdict,
+        track_time: cli.track_time,
+        output_style: cli.output_style,
+        notes: cli.notes,
+        resume: cli.resume,
+        watch: cli.watch,
+        order: cli.order,
+        auto_split: cli.auto_split,
+        summary_file: summary_file.as_ref().map(|p| p.to_path_buf()),
+        status_file: Some(status_file.to_path_buf()),
+        apply: cli.apply,
+        publish: cli.publish.clone(),
+        range: cli.range.clone(),
+        multiplexer: cli.multiplexer,
+        split: cli.split_layout()?,
+        timeout: match cli.spawn_timeout {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => Some(stagent::spawn::DEFAULT_SPAWN_TIMEOUT),
+        },
+        patch_file: cli
+            .patch_file
+            .as_deref()
+            .map(std::path::absolute)
+            .transpose()?,
+    };
+    // Ctrl+C closes the review's pane instead of leaving it behind
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, interrupted.clone())
+        .context("Failed to handle Ctrl+C")?;
+    stagent::spawn::spawn_in_split(&opts, &interrupted)?;
+
+    let summary = match summary_file {
+        Some(ref path) => Some(std::fs::read_to_string(path)?),
+        None => None,
+    };
+    if let Some(ref summary) = summary {
+        eprint!("{}", summary);
+    }
+    let exit_status = stagent::spawn::read_exit_status(&status_file);
+    eprintln!("{}", stagent::spawn::report_line(&output, exit_status));
+    if summary.is_some_and(|s| s.is_empty()) {
+        bail!("The spawned review ended without a summary");
+    }
+    Ok(())
 }
 
 /// Maximum patch input size (100 MB). Prevents OOM from unbounded stdin.
 const MAX_PATCH_SIZE: u64 = 100 * 1024 * 1024;
 
-/// Run in patch mode: read a unified diff from stdin and review it.
-fn run_patch_mode(cli: &Cli) -> Result<()> {
-    use std::io::{IsTerminal, Read};
+/// Read and parse a unified diff from stdin (`--patch`).
+fn read_patch_stdin() -> Result<Vec<FileDiff>> {
+    use std::io::Read;
 
     if std::io::stdin().is_terminal() {
         bail!("--patch requires piped input. Usage: git diff | stagent -p");
@@ -98,72 +615,496 @@ fn run_patch_mode(cli: &Cli) -> Result<()> {
             MAX_PATCH_SIZE / (1024 * 1024)
         );
     }
-    let files = stagent::patch::parse_unified_diff(&input)?;
+    stagent::patch::parse_unified_diff(&input)
+}
+
+/// Read and parse a unified diff from a file (`--patch-file`).
+fn read_patch_file(path: &Path) -> Result<Vec<FileDiff>> {
+    let size = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read patch file {}", path.display()))?
+        .len();
+    if size > MAX_PATCH_SIZE {
+        bail!(
+            "Patch input exceeds maximum size ({} MB)",
+            MAX_PATCH_SIZE / (1024 * 1024)
+        );
+    }
+    let input = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read patch file {}", path.display()))?;
+    stagent::patch::parse_unified_diff(&input)
+}
+
+/// Read the diff to review in patch mode, from `--patch-file` or stdin.
+fn read_patch(cli: &Cli) -> Result<Vec<FileDiff>> {
+    let mut files = match cli.patch_file {
+        Some(ref path) => read_patch_file(path)?,
+        None => read_patch_stdin()?,
+    };
+    stagent::patch::apply_diff_settings(&mut files, &cli.diff_settings());
+    Ok(files)
+}
+
+/// Run in patch mode: read a unified diff from stdin or a file and review it.
+fn run_patch_mode(cli: &Cli) -> Result<()> {
+    let files = read_patch(cli)?;
+
+    if cli.apply {
+        // Patch paths are relative to the repository root, as in `git diff`
+        let repo = stagent::git::open_repo(".")?;
+        let cwd_prefix = stagent::git::cwd_prefix(&repo, &std::env::current_dir()?);
+        stagent::staging::verify_patch_applies(&repo, &files)
+            .context("Cannot --apply this patch")?;
+        run_review_pipeline(
+            files,
+            Some(&repo),
+            &cwd_prefix,
+            false,
+            "No changes to review.",
+            cli,
+            None,
+        )?;
+        return Ok(());
+    }
 
-    // Staging is disabled in patch mode — no git repo context
-    run_review_pipeline(files, None, true, "No changes to review.", cli)
+    // Staging is disabled in patch mode — no git repo context, and paths
+    // are taken as they appear in the patch
+    run_review_pipeline(
+        files,
+        None,
+        Path::new(""),
+        true,
+        "No changes to review.",
+        cli,
+        None,
+    )?;
+    Ok(())
+}
+
+/// Handle `--range`: review the changes of a range of commits, without
+/// staging.
+fn run_range_mode(cli: &Cli, spec: &str) -> Result<()> {
+    let repo = stagent::git::open_repo(".")?;
+    let cwd_prefix = stagent::git::cwd_prefix(&repo, &std::env::current_dir()?);
+    let files = RevRange::resolve(&repo, spec)?.diff(&repo, &cli.diff_settings())?;
+
+    // Like a patch: the diff is not the working tree's, so there is
+    // nothing to stage, refresh or blame against the index
+    run_review_pipeline(
+        files,
+        None,
+        &cwd_prefix,
+        true,
+        &format!("No changes in {}.", spec),
+        cli,
+        None,
+    )?;
+    Ok(())
+}
+
+/// The commits of the `--range` under review, if any.
+fn range_commits(cli: &Cli) -> Result<Vec<RangeCommit>> {
+    let Some(ref spec) = cli.range else {
+        return Ok(Vec::new());
+    };
+    let repo = stagent::git::open_repo(".")?;
+    RevRange::resolve(&repo, spec)?.commits(&repo)
 }
 
 /// Run in normal git mode: compute diff from working tree and review/stage.
 fn run_git_mode(cli: &Cli) -> Result<()> {
     let repo = stagent::git::open_repo(".")?;
+    // Diff paths are relative to the repo root; user-supplied paths are
+    // relative to wherever stagent was started
+    let cwd_prefix = stagent::git::cwd_prefix(&repo, &std::env::current_dir()?);
 
     // Add untracked files with intent-to-add so they appear in the diff
-    // and can be staged hunk-by-hunk.
-    stagent::git::intent_to_add_untracked(&repo)?;
+    // and can be staged hunk-by-hunk. Files nothing was staged from are
+    // untracked again on exit, errors included.
+    let mut intent_added = if cli.intent_to_add() {
+        IntentToAdd::add_untracked(&repo)?
+    } else {
+        IntentToAdd::none(&repo)
+    };
 
-    let files = stagent::git::get_unstaged_diff(&repo)?;
+    let files = stagent::git::get_unstaged_diff_with(&repo, &cli.diff_settings())?;
 
-    run_review_pipeline(
+    // --resume restores the session saved on the last quit; spawned
+    // sessions pick up the handoff left by the previous --spawn run
+    let fingerprint = session::fingerprint(&files);
+    let session_path = session::session_path(&repo);
+    let handoff_path = session::handoff_path(&repo);
+    let resume = if cli.resume {
+        match session::load(&session_path) {
+            Some(saved) if saved.fingerprint == fingerprint => Some(saved),
+            Some(_) => {
+                eprintln!("Saved session is stale (the diff has changed); starting fresh.");
+                None
+            }
+            None => {
+                eprintln!("No saved session found; starting fresh.");
+                None
+            }
+        }
+    } else if cli.spawned {
+        session::load_matching(&handoff_path, &fingerprint)
+    } else {
+        None
+    };
+
+    let app = run_review_pipeline(
         files,
         Some(&repo),
+        &cwd_prefix,
         cli.no_stage,
         "No unstaged changes to review.",
         cli,
-    )
+        resume,
+    )?;
+
+    let Some(app) = app else {
+        intent_added.finish()?;
+        return Ok(());
+    };
+
+    if cli.notes {
+        stagent::notes::write_notes(&repo, &app.files, &app.feedback)?;
+    }
+
+    if session::has_pending(&app.files) {
+        // Fingerprint the diff as the next run will see it, i.e. without
+        // the hunks staged in this session.
+        let remaining = stagent::git::get_unstaged_diff_with(&repo, &cli.diff_settings())?;
+        let saved = Session::capture(&app, session::fingerprint(&remaining));
+        session::save(&session_path, &saved)?;
+
+        if cli.spawned {
+            // The feedback was already delivered through this run's output
+            let handoff = Session {
+                feedback: Vec::new(),
+                ..saved
+            };
+            session::save(&handoff_path, &handoff)?;
+        }
+    } else {
+        session::clear(&session_path);
+        if cli.spawned {
+            session::clear(&handoff_path);
+        }
+    }
+
+    // After the session fingerprint, which is of the diff the next run
+    // sees once it has added the files again
+    intent_added.extend(app.intent_added);
+    intent_added.finish()?;
+
+    Ok(())
+}
+
+/// The `--files` / `--exclude` selection, resolved relative to `cwd_prefix`.
+fn path_filter(cli: &Cli, cwd_prefix: &Path) -> Result<PathFilter> {
+    PathFilter::new(cwd_prefix, &cli.files, &cli.exclude)
+}
+
+/// Handle `stagent print`: render the diff to stdout and exit.
+///
+/// Read-only: unlike the TUI, untracked files are not added with
+/// intent-to-add. Statuses from a saved session are shown when it matches
+/// the current diff.
+fn run_print_command(cli: &Cli, color: ColorMode) -> Result<()> {
+    use std::io::Write;
+
+    let (mut files, saved, cwd_prefix) = if cli.is_patch_mode() {
+        (read_patch(cli)?, None, PathBuf::new())
+    } else {
+        let repo = stagent::git::open_repo(".")?;
+        let cwd_prefix = stagent::git::cwd_prefix(&repo, &std::env::current_dir()?);
+        let files = stagent::git::get_unstaged_diff_with(&repo, &cli.diff_settings())?;
+        let saved =
+            session::load_matching(&session::session_path(&repo), &session::fingerprint(&files));
+        (files, saved, cwd_prefix)
+    };
+    let filter = path_filter(cli, &cwd_prefix)?;
+    files.retain(|f| filter.matches(&f.path));
+
+    let mut app = App::new_with_help(files, true, false);
+    app.view_mode = cli.view;
+    app.show_whitespace = cli.show_whitespace;
+    app.tab_width = cli.tab_width;
+    if let Some(saved) = saved {
+        saved.apply(&mut app);
+    }
+
+    let stdout = std::io::stdout();
+    let is_terminal = stdout.is_terminal();
+    let color = match color {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => is_terminal,
+    };
+    let width = if is_terminal {
+        crossterm::terminal::size().map_or(stagent::print::DEFAULT_WIDTH, |(w, _)| w)
+    } else {
+        stagent::print::DEFAULT_WIDTH
+    };
+
+    let highlighter = stagent::highlight::Highlighter::new();
+    let output = stagent::print::render(&mut app, &highlighter, width, color);
+    // A closed pipe (e.g. `| head`) is not an error
+    let _ = stdout.lock().write_all(output.as_bytes());
+    Ok(())
+}
+
+/// Handle `--dump-json`: print the filtered diff as JSON and exit.
+///
+/// Read-only like `stagent print`: untracked files are listed but not
+/// added with intent-to-add.
+fn run_dump_json(cli: &Cli) -> Result<()> {
+    use std::io::Write;
+
+    let (mut files, cwd_prefix) = if cli.is_patch_mode() {
+        (read_patch(cli)?, PathBuf::new())
+    } else {
+        let repo = stagent::git::open_repo(".")?;
+        let cwd_prefix = stagent::git::cwd_prefix(&repo, &std::env::current_dir()?);
+        let files = stagent::git::get_unstaged_diff_with(&repo, &cli.diff_settings())?;
+        (files, cwd_prefix)
+    };
+    let filter = path_filter(cli, &cwd_prefix)?;
+    files.retain(|f| filter.matches(&f.path));
+
+    let json = serde_json::to_string_pretty(&files).context("Failed to serialize the diff")?;
+    // A closed pipe (e.g. `| head`) is not an error
+    let _ = writeln!(std::io::stdout().lock(), "{}", json);
+    Ok(())
 }
 
 /// Shared pipeline: filter files, run TUI, write feedback.
+/// `cwd_prefix` locates the current directory inside the repo, for resolving
+/// `--files` and `--start-file`.
+/// Returns the final app state, or `None` if there was nothing to review.
 fn run_review_pipeline(
     mut files: Vec<FileDiff>,
     repo: Option<&Repository>,
+    cwd_prefix: &Path,
     no_stage: bool,
     empty_message: &str,
     cli: &Cli,
-) -> Result<()> {
-    // Filter by glob if specified
-    if let Some(ref glob_pattern) = cli.files {
-        match glob::Pattern::new(glob_pattern) {
-            Ok(pattern) => {
-                files.retain(|f| pattern.matches_path(&f.path));
-            }
-            Err(e) => {
-                eprintln!("Warning: invalid glob pattern '{}': {}", glob_pattern, e);
+    resume: Option<Session>,
+) -> Result<Option<App>> {
+    let path_filter = path_filter(cli, cwd_prefix)?;
+    files.retain(|f| path_filter.matches(&f.path));
+
+    if files.is_empty() {
+        println!("{}", empty_message);
+        emit_summary(cli, &ReviewStats::default(), 0)?;
+        return Ok(None);
+    }
+
+    let start = (cli.start_file.is_some() || cli.start_hunk.is_some())
+        .then(|| StartPosition::from_args(cli.start_file.as_deref(), cli.start_hunk))
+        .map(|mut pos| {
+            pos.path = pos.path.map(|p| {
+                PathBuf::from(stagent::git::to_repo_path(cwd_prefix, &p.to_string_lossy()))
+            });
+            pos
+        });
+
+    let opts = RunOptions {
+        no_stage,
+        start,
+        // An explicit --resume needs no confirmation; a handoff asks first
+        confirm_resume: !cli.resume,
+        resume,
+        view_mode: cli.view,
+        show_whitespace: cli.show_whitespace,
+        tab_width: cli.tab_width,
+        feedback_format: cli.format,
+        output_style: cli.output_style,
+        context_lines: cli.context_lines,
+        cwd_prefix: cwd_prefix.to_path_buf(),
+        // The previous round's --output, before this run overwrites it
+        // (an empty one, like a fresh --spawn output file, has nothing)
+        previous_feedback: cli
+            .output
+            .clone()
+            .filter(|p| p.metadata().is_ok_and(|m| m.len() > 0)),
+        watch: cli.watch,
+        path_filter,
+        order: cli.order,
+        auto_split: cli.auto_split,
+        diff_settings: cli.diff_settings(),
+        no_intent_to_add: !cli.intent_to_add(),
+        overview: cli.overview,
+        // A hook's review can always be rejected
+        verdict: cli.verdict || cli.hook.is_some(),
+        require_verdict: cli.hook.is_some(),
+        track_time: cli.track_time,
+        patch: cli.is_patch_mode() || cli.hook.is_some() || cli.range.is_some(),
+        journal_dir: match repo {
+            Some(r) => Some(stagent::journal::journal_dir(r)),
+            // Patch mode: journal into the surrounding repo, if any
+            None => stagent::git::open_repo(".")
+                .ok()
+                .map(|r| stagent::journal::journal_dir(&r)),
+        },
+        multiplexer: cli.multiplexer.resolve(),
+        split: cli.split_layout()?,
+        editor_wait_command: stagent::editor::editor_wait_command_from_git_config(&git_config()?),
+        commits: range_commits(cli)?,
+    };
+    let mut app = match cli.script {
+        Some(ref path) => stagent::script::run(files, repo, opts, &read_script(path)?)?,
+        None => stagent::app::run(files, repo, opts)?,
+    };
+
+    // With --track-time the timing is output even without feedback
+    let timing = app.review_timing();
+    if !app.feedback.is_empty() || timing.is_some() {
+        // The format may have been changed with `:format` during the session
+        let output = stagent::feedback::format_feedback_as(
+            &app.feedback,
+            app.verdict.as_ref(),
+            timing.as_ref(),
+            app.context_lines,
+            app.feedback_format,
+            app.output_style,
+        )?;
+        stagent::feedback::write_feedback(&output, cli.output.as_deref())?;
+
+        // Stdout-only feedback is gone once the pane closes, and a spawned
+        // review's output file may be a temporary one; keep a copy
+        if cli.output.is_none() || cli.spawned {
+            let archive_dir = match repo {
+                Some(r) => Some(stagent::archive::archive_dir(r)),
+                // Patch mode: archive into the surrounding repo, if any
+                None => stagent::git::open_repo(".")
+                    .ok()
+                    .map(|r| stagent::archive::archive_dir(&r)),
+            };
+            if let Some(dir) = archive_dir
+                && let Err(e) = stagent::archive::archive_feedback(&dir, &output)
+            {
+                eprintln!("Warning: failed to archive feedback: {:#}", e);
             }
         }
     }
 
-    // Filter out binary files
-    files.retain(|f| {
-        if f.is_binary {
-            eprintln!("Skipping binary file: {}", f.path.display());
-            false
-        } else {
-            true
-        }
-    });
+    // The output is written; the journal is no longer needed, even if
+    // writing the edits or publishing fails below
+    if let Some(journal) = app.journal.take() {
+        journal.remove();
+    }
 
-    if files.is_empty() {
-        println!("{}", empty_message);
+    let exported = cli
+        .edits_patch
+        .as_ref()
+        .map_or(Ok(()), |path| write_edits_patch(path, &app, repo))
+        .and_then(|()| match cli.publish {
+            Some(ref target) => publish_feedback(target, &app.feedback, cli.dry_run),
+            None => Ok(()),
+        });
+
+    emit_summary(
+        cli,
+        &stagent::stats::compute(&app.files),
+        app.feedback.len(),
+    )?;
+    exported?;
+
+    Ok(Some(app))
+}
+
+/// Handle `--edits-patch`: write the review's edits as a patch against
+/// the working tree, if there are any.
+fn write_edits_patch(path: &Path, app: &App, repo: Option<&Repository>) -> Result<()> {
+    let root = match repo.and_then(|r| r.workdir()) {
+        Some(workdir) => workdir.to_path_buf(),
+        // Patch mode: the patch's paths are relative to the surrounding
+        // repo, if any
+        None => stagent::git::open_repo(".")
+            .ok()
+            .and_then(|r| r.workdir().map(Path::to_path_buf))
+            .unwrap_or_else(|| PathBuf::from(".")),
+    };
+    let edits = stagent::export::edits::edits_patch(&root, &app.feedback)?;
+    for skipped in &edits.skipped {
+        eprintln!(
+            "Warning: edits of {} left out of the patch: not UTF-8 text",
+            skipped
+        );
+    }
+    if edits.patch.is_empty() {
         return Ok(());
     }
+    std::fs::write(path, edits.patch).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Read and parse the `--script` file, or stdin for `-`.
+fn read_script(path: &Path) -> Result<Vec<(usize, stagent::script::Action)>> {
+    let script = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin()).context("Failed to read script from stdin")?
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read script {}", path.display()))?
+    };
+    stagent::script::parse(&script)
+}
 
-    let feedback = stagent::app::run(files, repo, no_stage)?;
+/// Handle `--publish`: create a pending review with the feedback, or print
+/// the request with `--dry-run`.
+fn publish_feedback(target: &str, feedback: &[HunkFeedback], dry_run: bool) -> Result<()> {
+    use stagent::export::github;
 
-    if !feedback.is_empty() {
-        let output = stagent::feedback::format_feedback(&feedback, cli.context_lines);
-        stagent::feedback::write_feedback(&output, cli.output.as_deref())?;
+    let pr = github::PullRequest::parse(target)?;
+    if feedback.is_empty() {
+        eprintln!("No feedback to publish.");
+        return Ok(());
+    }
+    if dry_run {
+        eprintln!("POST {}", pr.reviews_url(&github::api_url()));
+        eprintln!("{}", github::review_payload(feedback)?);
+        return Ok(());
     }
+    let url = github::publish(&pr, feedback).context("Failed to publish the review")?;
+    eprintln!("Created a pending review: {}", url);
+    Ok(())
+}
 
+/// Print the `--summary` line to stderr, or write it to `--summary-file`.
+fn emit_summary(cli: &Cli, stats: &ReviewStats, feedback: usize) -> Result<()> {
+    let line = stagent::stats::summary_line(stats, feedback);
+    if let Some(ref path) = cli.summary_file {
+        std::fs::write(path, format!("{}\n", line))
+            .with_context(|| format!("Failed to write summary to {}", path.display()))?;
+    } else if cli.summary {
+        eprintln!("{}", line);
+    }
+    Ok(())
+}
+
+/// Handle `stagent feedback list|show`.
+fn run_feedback_command(action: &FeedbackAction) -> Result<()> {
+    let repo = stagent::git::open_repo(".")?;
+    let dir = stagent::archive::archive_dir(&repo);
+
+    match action {
+        FeedbackAction::List => {
+            let archived = stagent::archive::list_archived(&dir)?;
+            if archived.is_empty() {
+                println!("No archived feedback.");
+            }
+            for entry in archived {
+                println!(
+                    "{}  {} file(s), {} hunk(s)",
+                    entry.id, entry.files, entry.hunks
+                );
+            }
+        }
+        FeedbackAction::Show { id } => {
+            let entry = stagent::archive::find_archived(&dir, id)?;
+            let content = std::fs::read_to_string(&entry.path)?;
+            print!("{}", content);
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,136 @@
+//! Status bar messages: what the last action did, how serious it is, and
+//! for how long it stays up.
+//!
+//! Each message has a [`MessageLevel`]. The status bar colors it by level
+//! and clears it once [`MessageLevel::timeout`] has passed, so a stale
+//! "Hunk staged" doesn't linger. Every message is also kept in a
+//! [`MessageHistory`], which `M` shows, for errors that were replaced
+//! before they could be read.
+
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::time::{Duration, Instant};
+
+/// How serious a message is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessageLevel {
+    /// The outcome of an action: "Hunk staged".
+    Info,
+    /// An action that did nothing: "No pending hunks".
+    Warn,
+    /// An action that failed: "Stage error: ...".
+    Error,
+}
+
+impl MessageLevel {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+
+    /// How long a message of this level stays in the status bar. Errors
+    /// stay longest, to leave time to read them.
+    pub fn timeout(self) -> Duration {
+        match self {
+            Self::Info => Duration::from_secs(4),
+            Self::Warn => Duration::from_secs(8),
+            Self::Error => Duration::from_secs(15),
+        }
+    }
+}
+
+/// A message and when it was shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub level: MessageLevel,
+    pub text: String,
+    pub shown_at: Instant,
+}
+
+impl Message {
+    pub fn new(level: MessageLevel, text: impl Into<String>) -> Self {
+        Self {
+            level,
+            text: text.into(),
+            shown_at: Instant::now(),
+        }
+    }
+
+    /// Whether the message has been up for its level's timeout at `now`.
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.shown_at) >= self.level.timeout()
+    }
+}
+
+/// The message text, so `app.message.as_deref()` reads as a `&str`.
+impl Deref for Message {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+/// At most this many messages are kept in the history.
+pub const MAX_HISTORY: usize = 200;
+
+/// Messages shown during the review, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct MessageHistory {
+    messages: VecDeque<Message>,
+}
+
+impl MessageHistory {
+    pub fn push(&mut self, message: Message) {
+        if self.messages.len() == MAX_HISTORY {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+    }
+
+    /// The messages, newest first.
+    pub fn newest_first(&self) -> impl Iterator<Item = &Message> {
+        self.messages.iter().rev()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_expire_by_level() {
+        let info = Message::new(MessageLevel::Info, "Hunk staged");
+        let error = Message::new(MessageLevel::Error, "Stage error: index locked");
+        let later = info.shown_at + Duration::from_secs(5);
+        assert!(!info.is_expired(info.shown_at));
+        assert!(info.is_expired(later));
+        assert!(!error.is_expired(later), "errors stay up longer");
+        assert!(error.is_expired(error.shown_at + MessageLevel::Error.timeout()));
+        assert_eq!(&*error, "Stage error: index locked");
+    }
+
+    #[test]
+    fn test_history_keeps_the_newest() {
+        let mut history = MessageHistory::default();
+        assert!(history.is_empty());
+        for i in 0..MAX_HISTORY + 5 {
+            history.push(Message::new(MessageLevel::Info, i.to_string()));
+        }
+        assert_eq!(history.len(), MAX_HISTORY);
+        let texts: Vec<&str> = history.newest_first().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts[0], (MAX_HISTORY + 4).to_string());
+        assert_eq!(texts[MAX_HISTORY - 1], "5");
+    }
+}
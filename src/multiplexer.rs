@@ -0,0 +1,541 @@
+//! Where editors and spawned reviews open: a tmux split, a zellij pane, or
+//! (without a multiplexer) the terminal stagent runs in, with the TUI
+//! suspended until the editor exits.
+//!
+//! The editor flows in [`crate::app`] and `--spawn` ([`crate::spawn`]) go
+//! through [`Multiplexer`]; `--multiplexer` picks one, and by default it is
+//! detected from the environment ([`MultiplexerKind::resolve`]), so stagent
+//! also runs over SSH or wherever neither tmux nor zellij is available.
+
+use anyhow::{Context, Result, bail};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use crate::editor::{
+    MAX_PANE_POLL_ITERATIONS, PANE_POLL_INTERVAL, SplitLayout, build_tmux_pane_command,
+    pane_exists_with, run_tmux_split,
+};
+use crate::tmux::{self, Clock, TmuxRunner};
+
+/// Which multiplexer opens editor panes (`--multiplexer`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum MultiplexerKind {
+    /// tmux inside a tmux session, zellij inside a zellij session
+    #[default]
+    Auto,
+    Tmux,
+    Zellij,
+    /// No split: suspend the TUI and run the editor in its terminal
+    Terminal,
+}
+
+impl MultiplexerKind {
+    /// The multiplexer of the session stagent runs in, from `$TMUX` and
+    /// `$ZELLIJ`.
+    pub fn detect() -> Option<Self> {
+        if std::env::var_os("TMUX").is_some() {
+            Some(Self::Tmux)
+        } else if std::env::var_os("ZELLIJ").is_some() {
+            Some(Self::Zellij)
+        } else {
+            None
+        }
+    }
+
+    /// The multiplexer to use: the one asked for, or for `Auto` the
+    /// detected one, falling back to the terminal without one.
+    pub fn resolve(self) -> Self {
+        match self {
+            Self::Auto => Self::detect().unwrap_or(Self::Terminal),
+            kind => kind,
+        }
+    }
+
+    /// The command-line value of the multiplexer, for forwarding it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Tmux => "tmux",
+            Self::Zellij => "zellij",
+            Self::Terminal => "terminal",
+        }
+    }
+}
+
+/// A pane opened by [`Multiplexer::open_pane`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pane {
+    /// tmux pane ID (`%5`), or what identifies the pane to its multiplexer.
+    pub id: String,
+    /// File removed when the pane's command exits, for multiplexers that
+    /// can't be asked whether a pane is still open.
+    pub marker: Option<PathBuf>,
+    /// tmux `wait-for` channel signalled when the pane's command exits.
+    pub channel: Option<String>,
+}
+
+/// Opens commands beside stagent's pane and tells when they are done.
+pub trait Multiplexer: Send + Sync {
+    /// Run `command` (program and arguments) in a new pane.
+    fn open_pane(&self, command: &[String]) -> Result<Pane>;
+
+    /// Whether `pane` is still open.
+    fn is_open(&self, pane: &Pane) -> bool;
+
+    /// Close `pane`, ending its command.
+    fn close_pane(&self, pane: &Pane) -> Result<()>;
+
+    /// Block until `pane` closes. Returns `false` if it is still open
+    /// after `max_polls` checks of [`is_open`](Self::is_open), one every
+    /// [`PANE_POLL_INTERVAL`].
+    fn wait_closed(&self, pane: &Pane, clock: &dyn Clock, max_polls: u32) -> bool {
+        poll_until_closed(|| self.is_open(pane), clock, max_polls)
+    }
+}
+
+/// Shell script run around a tmux pane's command: `"$@"` is the command,
+/// `$0` the `wait-for` channel, signalled however the shell ends (the
+/// command exiting, or the pane being killed).
+pub const TMUX_WAIT_FOR_WRAPPER: &str =
+    r#"trap exit HUP INT TERM; trap 'tmux wait-for -S "$0"' EXIT; "$@""#;
+
+/// How often a pane waited on through `wait-for` is checked for having
+/// been killed (see [`Tmux`]'s `wait_closed`).
+pub const PANE_WATCHDOG_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A `wait-for` channel name unique to this process and pane.
+fn next_channel() -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::SeqCst);
+    format!("stagent-{}-{}", std::process::id(), n)
+}
+
+/// tmux: a split of stagent's pane, or a new window, as its
+/// [`SplitLayout`] says (by default a horizontal split at half the width).
+pub struct Tmux {
+    runner: Arc<dyn TmuxRunner>,
+    layout: SplitLayout,
+}
+
+impl Tmux {
+    pub fn new(runner: Arc<dyn TmuxRunner>) -> Self {
+        Self {
+            runner,
+            layout: SplitLayout::default(),
+        }
+    }
+
+    /// Open panes with `layout` instead of the default split.
+    pub fn with_layout(mut self, layout: SplitLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+}
+
+impl Multiplexer for Tmux {
+    fn open_pane(&self, command: &[String]) -> Result<Pane> {
+        let channel = next_channel();
+        let mut wrapped = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            TMUX_WAIT_FOR_WRAPPER.to_string(),
+            channel.clone(),
+        ];
+        wrapped.extend(command.iter().cloned());
+        let cmd = build_tmux_pane_command(&self.layout, wrapped);
+        Ok(Pane {
+            id: run_tmux_split(self.runner.as_ref(), &cmd)?,
+            marker: None,
+            channel: Some(channel),
+        })
+    }
+
+    fn is_open(&self, pane: &Pane) -> bool {
+        pane_exists_with(self.runner.as_ref(), &pane.id)
+    }
+
+    /// `kill-pane`; the hangup makes the wrapper signal the pane's
+    /// channel.
+    fn close_pane(&self, pane: &Pane) -> Result<()> {
+        self.runner
+            .run(&["kill-pane".to_string(), "-t".to_string(), pane.id.clone()])
+            .map(|_| ())
+    }
+
+    /// Block on the pane's `wait-for` channel, which tmux wakes as soon as
+    /// the command exits (or already has). Polls instead if the tmux is
+    /// too old to have `wait-for`.
+    ///
+    /// A killed pane's shell may not get to signal (its command can
+    /// outlive the hangup), so a watchdog checks every
+    /// [`PANE_WATCHDOG_INTERVAL`] that the pane still exists and wakes the
+    /// channel itself once it is gone.
+    fn wait_closed(&self, pane: &Pane, clock: &dyn Clock, max_polls: u32) -> bool {
+        if let Some(ref channel) = pane.channel {
+            let done = Arc::new(AtomicBool::new(false));
+            let watchdog = {
+                let (runner, done) = (self.runner.clone(), done.clone());
+                let (id, channel) = (pane.id.clone(), channel.clone());
+                move || {
+                    loop {
+                        thread::sleep(PANE_WATCHDOG_INTERVAL);
+                        if done.load(Ordering::SeqCst) {
+                            return;
+                        }
+                        if !pane_exists_with(runner.as_ref(), &id) {
+                            let _ =
+                                runner.run(&["wait-for".to_string(), "-S".to_string(), channel]);
+                            return;
+                        }
+                    }
+                }
+            };
+            thread::spawn(watchdog);
+            let woken = self
+                .runner
+                .run(&["wait-for".to_string(), channel.clone()])
+                .is_ok();
+            done.store(true, Ordering::SeqCst);
+            if woken {
+                return true;
+            }
+        }
+        poll_until_closed(|| self.is_open(pane), clock, max_polls)
+    }
+}
+
+/// zellij: a pane to the right that closes when its command exits.
+///
+/// zellij has no command to ask whether a pane is still open, so the
+/// command runs in a shell that removes a marker file once it exits. The
+/// shell writes its PID into the marker, for [`Multiplexer::close_pane`]
+/// to end it.
+pub struct Zellij;
+
+/// Build the `zellij action new-pane` command running `command` in `cwd`
+/// and removing `marker` when it exits.
+pub fn build_zellij_pane_command(command: &[String], marker: &str, cwd: &str) -> Vec<String> {
+    let mut cmd: Vec<String> = [
+        "zellij",
+        "action",
+        "new-pane",
+        "--direction",
+        "right",
+        "--close-on-exit",
+        "--cwd",
+        cwd,
+        "--",
+        "sh",
+        "-c",
+        // Arguments are passed through "$@", never interpolated
+        r#"echo $$ > "$0"; "$@"; rm -f "$0""#,
+        marker,
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    cmd.extend(command.iter().cloned());
+    cmd
+}
+
+impl Multiplexer for Zellij {
+    fn open_pane(&self, command: &[String]) -> Result<Pane> {
+        let marker = tempfile::Builder::new()
+            .prefix("stagent-pane-")
+            .tempfile()
+            .context("Failed to create pane marker")?
+            .into_temp_path()
+            .keep()
+            .context("Failed to create pane marker")?;
+        let cwd = std::env::current_dir()?;
+        let cmd =
+            build_zellij_pane_command(command, &marker.to_string_lossy(), &cwd.to_string_lossy());
+        let output = std::process::Command::new(&cmd[0])
+            .args(&cmd[1..])
+            .output()
+            .context("Failed to run zellij")?;
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&marker);
+            bail!(
+                "zellij action new-pane failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(Pane {
+            id: marker.to_string_lossy().to_string(),
+            marker: Some(marker),
+            channel: None,
+        })
+    }
+
+    fn is_open(&self, pane: &Pane) -> bool {
+        pane.marker.as_ref().is_some_and(|m| m.exists())
+    }
+
+    /// Hang up the pane's shell, which closes the pane. It can't remove
+    /// the marker any more, so that is done here.
+    fn close_pane(&self, pane: &Pane) -> Result<()> {
+        let Some(ref marker) = pane.marker else {
+            return Ok(());
+        };
+        let pid = std::fs::read_to_string(marker).unwrap_or_default();
+        let pid = pid.trim();
+        if !pid.is_empty() {
+            std::process::Command::new("kill")
+                .args(["-HUP", pid])
+                .status()
+                .context("Failed to run kill")?;
+        }
+        let _ = std::fs::remove_file(marker);
+        Ok(())
+    }
+}
+
+/// No multiplexer: leave the TUI's alternate screen, run the command in
+/// the terminal and come back once it exits. The pane is closed by the
+/// time [`Multiplexer::open_pane`] returns.
+#[cfg(feature = "tui")]
+pub struct Terminal;
+
+/// Set when [`Terminal`] gave the screen back, for the TUI to redraw all
+/// of it (see [`take_resumed`]).
+#[cfg(feature = "tui")]
+static RESUMED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the TUI was suspended for an editor since the last call; the
+/// screen then needs a full redraw.
+#[cfg(feature = "tui")]
+pub fn take_resumed() -> bool {
+    RESUMED.swap(false, Ordering::SeqCst)
+}
+
+#[cfg(feature = "tui")]
+impl Multiplexer for Terminal {
+    fn open_pane(&self, command: &[String]) -> Result<Pane> {
+        use crossterm::{event, execute, terminal};
+
+        let mut stdout = std::io::stdout();
+        terminal::disable_raw_mode()?;
+        execute!(
+            stdout,
+            terminal::LeaveAlternateScreen,
+            event::DisableMouseCapture
+        )?;
+        let status = std::process::Command::new(&command[0])
+            .args(&command[1..])
+            .status();
+        // Restore the TUI whatever the editor did
+        execute!(
+            stdout,
+            terminal::EnterAlternateScreen,
+            event::EnableMouseCapture
+        )?;
+        terminal::enable_raw_mode()?;
+        RESUMED.store(true, Ordering::SeqCst);
+
+        let status = status.with_context(|| format!("Failed to run {}", command[0]))?;
+        if !status.success() {
+            bail!("{} exited with {}", command[0], status);
+        }
+        Ok(Pane {
+            id: "terminal".to_string(),
+            marker: None,
+            channel: None,
+        })
+    }
+
+    fn is_open(&self, _pane: &Pane) -> bool {
+        false
+    }
+
+    fn close_pane(&self, _pane: &Pane) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The multiplexer for `kind` (already [resolved](MultiplexerKind::resolve)),
+/// running real commands. tmux lays its panes out with `layout`.
+pub fn system(kind: MultiplexerKind, layout: SplitLayout) -> Arc<dyn Multiplexer> {
+    match kind {
+        MultiplexerKind::Zellij => Arc::new(Zellij),
+        #[cfg(feature = "tui")]
+        MultiplexerKind::Terminal => Arc::new(Terminal),
+        _ => Arc::new(Tmux::new(tmux::system().0).with_layout(layout)),
+    }
+}
+
+/// Wait for `pane` to close (see [`Multiplexer::wait_closed`]). Returns a
+/// receiver that signals when it closes, or when polling gives up after
+/// [`MAX_PANE_POLL_ITERATIONS`] polls so the UI doesn't hang forever.
+pub fn wait_for_close(
+    mux: Arc<dyn Multiplexer>,
+    clock: Arc<dyn Clock>,
+    pane: Pane,
+) -> mpsc::Receiver<()> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        wait_until_closed(
+            mux.as_ref(),
+            clock.as_ref(),
+            &pane,
+            MAX_PANE_POLL_ITERATIONS,
+        );
+        let _ = tx.send(());
+    });
+    rx
+}
+
+/// Block until `pane` closes (see [`Multiplexer::wait_closed`]). Returns
+/// `false` on timeout.
+pub fn wait_until_closed(
+    mux: &dyn Multiplexer,
+    clock: &dyn Clock,
+    pane: &Pane,
+    max_polls: u32,
+) -> bool {
+    mux.wait_closed(pane, clock, max_polls)
+}
+
+/// Check `is_open` every [`PANE_POLL_INTERVAL`], at most `max_polls`
+/// times. Returns `false` if it never turned false.
+fn poll_until_closed(is_open: impl Fn() -> bool, clock: &dyn Clock, max_polls: u32) -> bool {
+    for _ in 0..max_polls {
+        if !is_open() {
+            return true;
+        }
+        clock.sleep(PANE_POLL_INTERVAL);
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmux::{FakeClock, FakeTmux};
+
+    #[test]
+    fn test_tmux_waits_on_the_pane_channel() {
+        let tmux = Arc::new(FakeTmux::new(|args| match args[0].as_str() {
+            "split-window" => Ok("%7\n".to_string()),
+            "wait-for" => Ok(String::new()),
+            _ => Ok("%0\n%7\n".to_string()),
+        }));
+        let mux = Tmux::new(tmux.clone());
+        let pane = mux
+            .open_pane(&["vi".to_string(), "/tmp/f".to_string()])
+            .unwrap();
+        assert_eq!(pane.id, "%7");
+        let channel = pane.channel.clone().unwrap();
+        let clock = FakeClock::new();
+        assert!(wait_until_closed(&mux, &clock, &pane, 3));
+        assert_eq!(clock.sleeps(), 0);
+
+        let calls = tmux.calls();
+        assert_eq!(calls[0][0], "split-window");
+        let dash = calls[0].iter().position(|a| a == "--").unwrap();
+        assert_eq!(
+            calls[0][dash + 1..],
+            ["sh", "-c", TMUX_WAIT_FOR_WRAPPER, &channel, "vi", "/tmp/f"]
+        );
+        assert_eq!(calls[1], ["wait-for", channel.as_str()]);
+        assert_ne!(next_channel(), channel);
+    }
+
+    #[test]
+    fn test_tmux_opens_panes_with_its_layout() {
+        use crate::editor::{SplitDirection, SplitSize};
+
+        let tmux = Arc::new(FakeTmux::new(|_| Ok("%8\n".to_string())));
+        let mux = Tmux::new(tmux.clone()).with_layout(SplitLayout {
+            direction: SplitDirection::Window,
+            size: SplitSize::Cells(80),
+        });
+        mux.open_pane(&["vi".to_string()]).unwrap();
+        let calls = tmux.calls();
+        assert_eq!(calls[0][..4], ["new-window", "-P", "-F", "#{pane_id}"]);
+    }
+
+    #[test]
+    fn test_tmux_without_wait_for_polls() {
+        let tmux = Arc::new(FakeTmux::new(|args| match args[0].as_str() {
+            "wait-for" => bail!("unknown command: wait-for"),
+            _ => Ok("%7\n".to_string()),
+        }));
+        let mux = Tmux::new(tmux);
+        let clock = FakeClock::new();
+        let pane = Pane {
+            id: "%7".to_string(),
+            marker: None,
+            channel: Some("stagent-test".to_string()),
+        };
+        assert!(!wait_until_closed(&mux, &clock, &pane, 3));
+        assert_eq!(clock.sleeps(), 3);
+    }
+
+    #[test]
+    fn test_zellij_command_passes_arguments_through() {
+        let cmd = build_zellij_pane_command(
+            &["vi".to_string(), "my file; rm -rf".to_string()],
+            "/tmp/marker",
+            "/repo",
+        );
+        let dash = cmd.iter().position(|a| a == "--").unwrap();
+        assert_eq!(cmd[..3], ["zellij", "action", "new-pane"]);
+        assert!(cmd[..dash].contains(&"--close-on-exit".to_string()));
+        assert_eq!(
+            cmd[dash + 1..],
+            [
+                "sh",
+                "-c",
+                r#"echo $$ > "$0"; "$@"; rm -f "$0""#,
+                "/tmp/marker",
+                "vi",
+                "my file; rm -rf"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zellij_pane_is_open_until_the_marker_goes() {
+        let marker = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let pane = Pane {
+            id: "zellij".to_string(),
+            marker: Some(marker.to_path_buf()),
+            channel: None,
+        };
+        assert!(Zellij.is_open(&pane));
+        marker.close().unwrap();
+        assert!(!Zellij.is_open(&pane));
+    }
+
+    #[test]
+    fn test_zellij_close_hangs_up_the_shell() {
+        let mut shell = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let marker = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        std::fs::write(&marker, format!("{}\n", shell.id())).unwrap();
+        let pane = Pane {
+            id: "zellij".to_string(),
+            marker: Some(marker.to_path_buf()),
+            channel: None,
+        };
+        Zellij.close_pane(&pane).unwrap();
+        assert!(!shell.wait().unwrap().success());
+        assert!(!Zellij.is_open(&pane));
+    }
+
+    #[test]
+    fn test_explicit_kinds_resolve_to_themselves() {
+        assert_eq!(MultiplexerKind::Zellij.resolve(), MultiplexerKind::Zellij);
+        assert_eq!(
+            MultiplexerKind::Terminal.resolve(),
+            MultiplexerKind::Terminal
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Record review outcomes as git notes under `refs/notes/stagent`.
+//!
+//! Notes are attached per file to the blob of the reviewed content (the
+//! working tree version, or the index version for deleted files). Reviewing
+//! the same content again finds the note, so earlier decisions are shown
+//! without any service outside the repository.
+
+use anyhow::{Context, Result};
+use git2::{ObjectType, Oid, Repository, Signature};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::types::{FeedbackKind, FileDiff, HunkFeedback, HunkStatus};
+
+/// Notes ref used for review records.
+pub const NOTES_REF: &str = "refs/notes/stagent";
+
+/// Object id of the reviewed content of `path`.
+///
+/// Hashes the working tree file; falls back to the index entry when the file
+/// was deleted. With `write`, the blob is also stored in the object database
+/// so the note has an object to attach to.
+pub fn content_oid(repo: &Repository, path: &Path, write: bool) -> Option<Oid> {
+    let full_path = repo.workdir()?.join(path);
+    if full_path.is_file() {
+        return if write {
+            repo.blob_path(&full_path).ok()
+        } else {
+            Oid::hash_file(ObjectType::Blob, &full_path).ok()
+        };
+    }
+    let index = repo.index().ok()?;
+    index.get_path(path, 0).map(|entry| entry.id)
+}
+
+/// Build the note text for one reviewed file.
+pub fn build_note(
+    file: &FileDiff,
+    feedback: &[HunkFeedback],
+    reviewer: &str,
+    date: &str,
+) -> String {
+    let count = |status: HunkStatus| file.hunks.iter().filter(|h| h.status == status).count();
+    let path = file.path.to_string_lossy();
+    let comments: Vec<&HunkFeedback> = feedback.iter().filter(|fb| fb.file_path == path).collect();
+
+    let mut note = format!(
+        "stagent review: {}\nReviewer: {}\nDate: {}\nHunks: {} staged, {} skipped, {} pending\n",
+        path,
+        reviewer,
+        date,
+        count(HunkStatus::Staged),
+        count(HunkStatus::Skipped),
+        count(HunkStatus::Pending),
+    );
+    if !comments.is_empty() {
+        note.push_str(&format!("Feedback: {}\n", comments.len()));
+        for fb in comments {
+            let kind = match fb.kind {
+                FeedbackKind::Comment => "comment",
+                FeedbackKind::Edit => "edit",
+                FeedbackKind::FileComment => "file comment",
+                FeedbackKind::ReviewComment => "review comment",
+                FeedbackKind::CommitComment => "commit comment",
+            };
+            let first_line = fb.content.lines().next().unwrap_or("");
+            let target = [fb.hunk_header.as_str(), kind]
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            note.push_str(&format!("- {}: {}\n", target, first_line));
+        }
+    }
+    note
+}
+
+/// One-line summary of a note for display: reviewer, date and hunk counts.
+pub fn summarize(note: &str) -> String {
+    let field = |name: &str| {
+        note.lines()
+            .find_map(|l| l.strip_prefix(name))
+            .map(str::trim)
+            .unwrap_or("?")
+    };
+    format!(
+        "Reviewed {} by {}: {}",
+        field("Date:"),
+        field("Reviewer:"),
+        field("Hunks:")
+    )
+}
+
+/// Read the stagent note attached to `oid`, if any.
+pub fn read_note(repo: &Repository, oid: Oid) -> Option<String> {
+    let note = repo.find_note(Some(NOTES_REF), oid).ok()?;
+    note.message().map(str::to_string)
+}
+
+/// Existing notes for the reviewed content of each file, keyed by path.
+pub fn load_notes(repo: &Repository, files: &[FileDiff]) -> HashMap<PathBuf, String> {
+    files
+        .iter()
+        .filter_map(|f| {
+            let oid = content_oid(repo, &f.path, false)?;
+            read_note(repo, oid).map(|note| (f.path.clone(), note))
+        })
+        .collect()
+}
+
+/// Attach a review note to every file that was reviewed (has a decided hunk
+/// or feedback). Existing notes on the same content are replaced. Returns the
+/// number of notes written.
+pub fn write_notes(
+    repo: &Repository,
+    files: &[FileDiff],
+    feedback: &[HunkFeedback],
+) -> Result<usize> {
+    let sig = repo
+        .signature()
+        .or_else(|_| Signature::now("stagent", "stagent@localhost"))
+        .context("Failed to create signature for review notes")?;
+    let reviewer = match sig.email() {
+        Some(email) => format!("{} <{}>", sig.name().unwrap_or("unknown"), email),
+        None => sig.name().unwrap_or("unknown").to_string(),
+    };
+    let date = format_timestamp(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    );
+
+    let mut written = 0;
+    for file in files {
+        let path = file.path.to_string_lossy();
+        let reviewed = file.hunks.iter().any(|h| h.status != HunkStatus::Pending)
+            || feedback.iter().any(|fb| fb.file_path == path);
+        if !reviewed {
+            continue;
+        }
+        let Some(oid) = content_oid(repo, &file.path, true) else {
+            continue;
+        };
+        let note = build_note(file, feedback, &reviewer, &date);
+        repo.note(&sig, &sig, Some(NOTES_REF), oid, &note, true)
+            .with_context(|| format!("Failed to write review note for {}", path))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Format seconds since the Unix epoch as an ISO 8601 UTC timestamp.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil-from-days (proleptic Gregorian), see H. Hinnant's date algorithms
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
@@ -1,10 +1,25 @@
 use anyhow::{Result, bail};
 
+use crate::diff;
+use crate::git::DiffSettings;
 use crate::types::{DeltaStatus, DiffLine, FileDiff, Hunk, HunkStatus, LineKind};
 
 /// Parse a unified diff (as produced by `git diff`) into our structured `FileDiff` types.
 ///
 /// This is the stdin-based counterpart to `diff::parse_diff()` which uses git2.
+///
+/// ```
+/// use stagent::types::{DeltaStatus, LineKind};
+///
+/// let files = stagent::patch::parse_unified_diff(
+///     "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-old\n+new\n",
+/// )?;
+/// assert_eq!(files[0].path.to_str(), Some("src/lib.rs"));
+/// assert_eq!(files[0].status, DeltaStatus::Modified);
+/// let kinds: Vec<LineKind> = files[0].hunks[0].lines.iter().map(|l| l.kind).collect();
+/// assert_eq!(kinds, [LineKind::Removed, LineKind::Added]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
 pub fn parse_unified_diff(input: &str) -> Result<Vec<FileDiff>> {
     if input.trim().is_empty() {
         return Ok(Vec::new());
@@ -30,19 +45,77 @@ pub fn parse_unified_diff(input: &str) -> Result<Vec<FileDiff>> {
     Ok(files)
 }
 
+/// Drop the hunks of a parsed patch that `settings` ignores, the
+/// `--patch` counterpart of [`crate::git::get_unstaged_diff_with`]. A
+/// patch can't be re-diffed, so whole hunks are dropped: with
+/// `ignore_whitespace` those whose removed and added lines match line for
+/// line once whitespace is dropped, with `ignore_blank_lines` those that
+/// only add or remove blank lines. Files
+/// left without hunks are dropped too.
+///
+/// ```
+/// use stagent::git::DiffSettings;
+///
+/// let mut files = stagent::patch::parse_unified_diff(
+///     "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a  b\n+a b\n",
+/// )?;
+/// let settings = DiffSettings { ignore_whitespace: true, ..Default::default() };
+/// stagent::patch::apply_diff_settings(&mut files, &settings);
+/// assert!(files.is_empty());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn apply_diff_settings(files: &mut Vec<FileDiff>, settings: &DiffSettings) {
+    let ignored = |hunk: &Hunk| {
+        !diff::is_mode_hunk(hunk)
+            && ((settings.ignore_whitespace && is_whitespace_change_only(hunk))
+                || (settings.ignore_blank_lines && is_blank_lines_only(hunk)))
+    };
+    files.retain_mut(|file| {
+        if file.hunks.is_empty() {
+            return true;
+        }
+        file.hunks.retain(|h| !ignored(h));
+        !file.hunks.is_empty()
+    });
+}
+
+/// Whether `hunk` only changes whitespace within lines, as `git diff -w`
+/// sees it. Unlike [`diff::is_whitespace_only`], added or removed blank
+/// lines count as changes.
+fn is_whitespace_change_only(hunk: &Hunk) -> bool {
+    let lines = |kind| {
+        hunk.lines
+            .iter()
+            .filter(move |l| l.kind == kind)
+            .map(|l| l.content.split_whitespace().collect::<String>())
+    };
+    diff::is_whitespace_only(hunk) && lines(LineKind::Removed).eq(lines(LineKind::Added))
+}
+
+/// Whether every added and removed line of `hunk` is blank.
+fn is_blank_lines_only(hunk: &Hunk) -> bool {
+    let changed = || hunk.lines.iter().filter(|l| l.kind != LineKind::Context);
+    changed().next().is_some() && changed().all(|l| l.content.trim().is_empty())
+}
+
 /// Parse a single file's diff starting from the "diff --git" line.
 /// Returns the FileDiff and the index of the next line to process.
-fn parse_file_diff(
+fn parse_file_diff<'a>(
     git_header_rest: &str,
-    lines: &[&str],
+    lines: &[&'a str],
     start: usize,
 ) -> Result<(FileDiff, usize)> {
-    // Extract path from "a/path b/path"
-    let path = parse_git_header_path(git_header_rest);
+    // Extract path from "a/path b/path", or "path path" with diff.noprefix
+    let (path, prefixed) = parse_git_header_path(git_header_rest);
+    let strip = |p: &'a str| if prefixed { strip_ab_prefix(p) } else { p };
 
     let mut i = start + 1; // skip "diff --git" line
     let mut status = DeltaStatus::Modified;
     let mut is_binary = false;
+    // Submodules have mode 160000 ("index abc..def 160000")
+    let mut is_submodule = false;
+    let mut old_mode = None;
+    let mut new_mode = None;
     let mut actual_path = path.clone();
 
     // Parse extended headers
@@ -52,7 +125,15 @@ fn parse_file_diff(
             break;
         }
 
-        if line.starts_with("new file mode") {
+        if (line.starts_with("index ") || line.contains(" file mode ")) && line.ends_with(" 160000")
+        {
+            is_submodule = true;
+        }
+        if let Some(mode) = line.strip_prefix("old mode ") {
+            old_mode = u32::from_str_radix(mode.trim(), 8).ok();
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            new_mode = u32::from_str_radix(mode.trim(), 8).ok();
+        } else if line.starts_with("new file mode") {
             status = DeltaStatus::Added;
         } else if line.starts_with("deleted file mode") {
             status = DeltaStatus::Deleted;
@@ -63,13 +144,13 @@ fn parse_file_diff(
             is_binary = true;
         } else if let Some(rest) = line.strip_prefix("+++ ") {
             // "+++ b/path" — use this as the definitive path
-            let p = strip_ab_prefix(rest);
+            let p = strip(rest);
             if p != "/dev/null" {
                 actual_path = p.to_string();
             }
         } else if let Some(rest) = line.strip_prefix("--- ") {
             // For deleted files, "--- a/path" is the only real path
-            let p = strip_ab_prefix(rest);
+            let p = strip(rest);
             if rest != "/dev/null" && status == DeltaStatus::Deleted {
                 actual_path = p.to_string();
             }
@@ -96,30 +177,60 @@ fn parse_file_diff(
         }
     }
 
+    // Binary patches carry no hunks; review them as a whole file
+    if is_binary && hunks.is_empty() {
+        hunks.push(crate::diff::binary_hunk(None));
+    }
+    let mode_change = old_mode
+        .zip(new_mode)
+        .filter(|_| !is_binary && !is_submodule);
+    if let Some((old, new)) = mode_change {
+        hunks.insert(0, crate::diff::mode_hunk(old, new));
+    }
+    if is_submodule && let Some(hunk) = hunks.first() {
+        let (old, new) = crate::diff::submodule_commits(hunk);
+        hunks = vec![crate::diff::submodule_hunk(old, new)];
+    }
+
     Ok((
         FileDiff {
             path: actual_path.into(),
             hunks,
             status,
             is_binary,
+            is_submodule,
+            mode_change,
         },
         i,
     ))
 }
 
-/// Parse the path from the git diff header "a/path b/path".
-/// Handles paths with spaces by splitting on " b/".
-fn parse_git_header_path(header: &str) -> String {
+/// Parse the path from the git diff header "a/path b/path", and whether
+/// the diff's paths carry the `a/` and `b/` prefixes. Handles paths with
+/// spaces by splitting on " b/".
+///
+/// Diffs made with `diff.noprefix` have "path path" headers instead. Both
+/// halves are the same path unless the file was renamed, which tells them
+/// apart from prefixed headers even for paths under an `a/` directory.
+fn parse_git_header_path(header: &str) -> (String, bool) {
+    let mid = header.len() / 2;
+    if header.len() % 2 == 1
+        && header.is_char_boundary(mid)
+        && header[mid..].starts_with(' ')
+        && header[..mid] == header[mid + 1..]
+    {
+        return (header[..mid].to_string(), false);
+    }
     // Format: "a/path b/path" — find the " b/" separator
-    if let Some(pos) = header.find(" b/") {
-        header[pos + 3..].to_string()
+    if header.starts_with("a/")
+        && let Some(pos) = header.find(" b/")
+    {
+        (header[pos + 3..].to_string(), true)
     } else {
-        // Fallback: try splitting on space
-        header
-            .split_whitespace()
-            .last()
-            .map(|s| strip_ab_prefix(s).to_string())
-            .unwrap_or_else(|| header.to_string())
+        // A renamed file without prefixes: "old new", resolved by the
+        // "rename to" and "+++" lines that follow
+        let path = header.split_whitespace().last().unwrap_or(header);
+        (path.to_string(), false)
     }
 }
 
@@ -140,7 +251,7 @@ fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize)> {
     let header_line = lines[start];
     let (old_start, old_lines, new_start, new_lines, header) = parse_hunk_header(header_line)?;
 
-    let mut diff_lines = Vec::new();
+    let mut diff_lines: Vec<DiffLine> = Vec::new();
     let mut old_lineno = old_start;
     let mut new_lineno = new_start;
     let mut i = start + 1;
@@ -152,9 +263,19 @@ fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize)> {
         if line.starts_with("@@ ") || line.starts_with("diff --git ") {
             break;
         }
+        // Once the header's lines are all there, anything but a diff line
+        // is text between hunks, like the comment blocks of strict-diff
+        // feedback
+        let complete = old_lineno - old_start >= old_lines && new_lineno - new_start >= new_lines;
+        if complete && !line.starts_with(['+', '-', ' ', '\\']) {
+            break;
+        }
 
-        // "\ No newline at end of file" — skip
+        // "\ No newline at end of file" for the line before
         if line.starts_with("\\ ") {
+            if let Some(last) = diff_lines.last_mut() {
+                last.mark_no_newline();
+            }
             i += 1;
             continue;
         }
@@ -165,6 +286,8 @@ fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize)> {
                 content: format!("{content}\n"),
                 old_lineno: None,
                 new_lineno: Some(new_lineno),
+                raw: None,
+                no_newline: false,
             });
             new_lineno += 1;
         } else if let Some(content) = line.strip_prefix('-') {
@@ -173,6 +296,8 @@ fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize)> {
                 content: format!("{content}\n"),
                 old_lineno: Some(old_lineno),
                 new_lineno: None,
+                raw: None,
+                no_newline: false,
             });
             old_lineno += 1;
         } else if let Some(content) = line.strip_prefix(' ') {
@@ -181,6 +306,8 @@ fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize)> {
                 content: format!("{content}\n"),
                 old_lineno: Some(old_lineno),
                 new_lineno: Some(new_lineno),
+                raw: None,
+                no_newline: false,
             });
             old_lineno += 1;
             new_lineno += 1;
@@ -191,6 +318,8 @@ fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize)> {
                 content: "\n".to_string(),
                 old_lineno: Some(old_lineno),
                 new_lineno: Some(new_lineno),
+                raw: None,
+                no_newline: false,
             });
             old_lineno += 1;
             new_lineno += 1;
@@ -228,7 +357,7 @@ fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize)> {
     Ok((
         Hunk {
             header,
-            lines: diff_lines,
+            lines: diff_lines.into(),
             status: HunkStatus::Pending,
             old_start,
             old_lines,
@@ -241,7 +370,7 @@ fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize)> {
 
 /// Parse a hunk header like "@@ -10,5 +10,7 @@ fn foo()".
 /// Returns (old_start, old_lines, new_start, new_lines, full_header_string).
-fn parse_hunk_header(line: &str) -> Result<(u32, u32, u32, u32, String)> {
+pub(crate) fn parse_hunk_header(line: &str) -> Result<(u32, u32, u32, u32, String)> {
     let header = line.trim_end().to_string();
 
     // Extract the range part between @@ markers
@@ -475,7 +604,62 @@ Binary files a/image.png and b/image.png differ
         let files = parse_unified_diff(diff).unwrap();
         assert_eq!(files.len(), 1);
         assert!(files[0].is_binary);
-        assert!(files[0].hunks.is_empty());
+        assert_eq!(files[0].hunks.len(), 1, "placeholder for file-level review");
+        assert!(files[0].hunks[0].lines.is_empty());
+    }
+
+    #[test]
+    fn test_mode_change() {
+        let diff = "\
+diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+diff --git a/build.sh b/build.sh
+old mode 100755
+new mode 100644
+--- a/build.sh
++++ b/build.sh
+@@ -1 +1 @@
+-make
++make all
+";
+        let files = parse_unified_diff(diff).unwrap();
+        assert_eq!(files[0].mode_change, Some((0o100644, 0o100755)));
+        assert_eq!(files[0].hunks.len(), 1);
+        assert_eq!(files[0].hunks[0].header, "Mode change 100644 → 100755");
+        assert_eq!(files[0].placeholder_noun(0), Some("mode change"));
+
+        assert_eq!(files[1].mode_change, Some((0o100755, 0o100644)));
+        assert_eq!(files[1].hunks.len(), 2);
+        assert!(crate::diff::is_mode_hunk(&files[1].hunks[0]));
+        assert_eq!(files[1].placeholder_noun(1), None);
+    }
+
+    #[test]
+    fn test_submodule() {
+        let diff = "\
+diff --git a/vendor/lib b/vendor/lib
+index 46b8e26..50a47a1 160000
+--- a/vendor/lib
++++ b/vendor/lib
+@@ -1 +1 @@
+-Subproject commit 46b8e26a2a5f1e35c6760b97410c1d755067a454
++Subproject commit 50a47a1d29e5e41532d29b0dfb66f9110b1a8f58
+";
+        let files = parse_unified_diff(diff).unwrap();
+        assert!(files[0].is_submodule);
+        assert!(files[0].is_whole_file());
+        let hunk = &files[0].hunks[0];
+        assert_eq!(hunk.header, "Submodule commit 46b8e26 → 50a47a1");
+        let (old, new) = crate::diff::submodule_commits(hunk);
+        assert_eq!(
+            old.unwrap().to_string(),
+            "46b8e26a2a5f1e35c6760b97410c1d755067a454"
+        );
+        assert_eq!(
+            new.unwrap().to_string(),
+            "50a47a1d29e5e41532d29b0dfb66f9110b1a8f58"
+        );
     }
 
     #[test]
@@ -490,7 +674,37 @@ diff --git a/foo.rs b/foo.rs
 \\ No newline at end of file
 ";
         let files = parse_unified_diff(diff).unwrap();
-        assert_eq!(files[0].hunks[0].lines.len(), 2);
+        let lines = &files[0].hunks[0].lines;
+        assert_eq!(lines.len(), 2);
+        assert!(!lines[0].no_newline);
+        assert!(lines[1].no_newline);
+        assert_eq!(lines[1].content, "new");
+    }
+
+    #[test]
+    fn test_text_after_a_complete_hunk_ends_it() {
+        let diff = "\
+diff --git a/foo.rs b/foo.rs
+--- a/foo.rs
++++ b/foo.rs
+@@ -1,2 +1,2 @@
+
+-old
++new
+
+# a note between hunks
+@@ -9 +9 @@
+-a
++b
+";
+        let files = parse_unified_diff(diff).unwrap();
+        let hunks = &files[0].hunks;
+        assert_eq!(hunks.len(), 2);
+        // A blank line within the counts is an empty context line; after
+        // them, it is not part of the hunk
+        assert_eq!(hunks[0].lines.len(), 3);
+        assert_eq!(hunks[0].lines[0].content, "\n");
+        assert_eq!(hunks[1].lines.len(), 2);
     }
 
     #[test]
@@ -576,6 +790,48 @@ diff --git a/foo.rs b/foo.rs
         );
     }
 
+    #[test]
+    fn test_noprefix_paths() {
+        // diff.noprefix, including a path that starts with "a/"
+        let diff = "\
+diff --git src/lib.rs src/lib.rs
+--- src/lib.rs
++++ src/lib.rs
+@@ -1 +1 @@
+-old
++new
+diff --git a/b/c.rs a/b/c.rs
+--- a/b/c.rs
++++ a/b/c.rs
+@@ -1 +1 @@
+-old
++new
+diff --git gone.rs gone.rs
+deleted file mode 100644
+--- gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-old
+diff --git old name.rs new name.rs
+similarity index 90%
+rename from old name.rs
+rename to new name.rs
+";
+        let files = parse_unified_diff(diff).unwrap();
+        let paths: Vec<_> = files.iter().map(|f| f.path.to_str().unwrap()).collect();
+        assert_eq!(
+            paths,
+            vec!["src/lib.rs", "a/b/c.rs", "gone.rs", "new name.rs"]
+        );
+        assert_eq!(files[2].status, DeltaStatus::Deleted);
+
+        // With prefixes, a directory named "a" keeps its name
+        let files =
+            parse_unified_diff("diff --git a/a/x.rs b/a/x.rs\n--- a/a/x.rs\n+++ b/a/x.rs\n")
+                .unwrap();
+        assert_eq!(files[0].path.to_str(), Some("a/x.rs"));
+    }
+
     #[test]
     fn test_strip_ab_prefix() {
         assert_eq!(strip_ab_prefix("a/foo.rs"), "foo.rs");
@@ -597,7 +853,8 @@ diff --git a/foo.rs b/foo.rs
 ";
         let files = parse_unified_diff(diff).unwrap();
         let hunk = &files[0].hunks[0];
-        // All content lines should end with \n
+        // All content lines should end with \n when there is no
+        // "\ No newline at end of file" marker
         for line in &hunk.lines {
             assert!(
                 line.content.ends_with('\n'),
@@ -0,0 +1,193 @@
+//! `stagent print`: render the diff to stdout without the TUI.
+//!
+//! Lines are built by the same code as the interactive diff view (syntax
+//! highlighting, theme, hunk statuses) and serialized as ANSI escape
+//! sequences, so stagent can double as a non-interactive diff renderer.
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+
+use crate::app::App;
+use crate::highlight::Highlighter;
+use crate::types::DeltaStatus;
+use crate::ui::{diff_view, theme};
+
+/// Width used when stdout is not a terminal.
+pub const DEFAULT_WIDTH: u16 = 100;
+
+/// When to emit ANSI colors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorMode {
+    /// Color when stdout is a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Render every file of `app` as text, `width` columns wide. Hunk statuses
+/// are shown as in the TUI; nothing is selected.
+pub fn render(app: &mut App, highlighter: &Highlighter, width: u16, color: bool) -> String {
+    let mut out = String::new();
+    // No hunk is selected, so no header gets the selection background
+    app.selected_hunk = usize::MAX;
+
+    for file_idx in 0..app.files.len() {
+        // Per-file state in the diff view (expanded context, marks) is keyed
+        // by the selected file
+        app.selected_file = file_idx;
+        let file = &app.files[file_idx];
+        let highlighted =
+            highlighter.highlight_file_lines(&file.path.to_string_lossy(), &file.hunks);
+
+        if file_idx > 0 {
+            out.push('\n');
+        }
+        let header = Line::styled(
+            format!("{} ({})", file.path.display(), status_label(file.status)),
+            theme::file_header_style(),
+        );
+        out.push_str(&line_to_ansi(&header, color));
+        out.push('\n');
+
+        // The diff view subtracts its borders from the width
+        let lines = diff_view::file_lines(
+            app,
+            file,
+            width.saturating_add(2),
+            Some(&highlighted),
+            0..usize::MAX,
+        );
+        for line in lines {
+            out.push_str(&line_to_ansi(&line, color));
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+fn status_label(status: DeltaStatus) -> &'static str {
+    match status {
+        DeltaStatus::Modified => "modified",
+        DeltaStatus::Added => "added",
+        DeltaStatus::Deleted => "deleted",
+        DeltaStatus::Renamed => "renamed",
+        DeltaStatus::Untracked => "untracked",
+    }
+}
+
+/// Serialize a styled line as text with ANSI SGR sequences (or plain text
+/// without `color`). Newlines inside spans are dropped.
+pub fn line_to_ansi(line: &Line, color: bool) -> String {
+    let mut out = String::new();
+    for span in &line.spans {
+        let text: String = span.content.chars().filter(|c| *c != '\n').collect();
+        if text.is_empty() {
+            continue;
+        }
+        let sgr = if color {
+            sgr_codes(line.style.patch(span.style))
+        } else {
+            Vec::new()
+        };
+        if sgr.is_empty() {
+            out.push_str(&text);
+        } else {
+            out.push_str(&format!("\x1b[{}m{}\x1b[0m", sgr.join(";"), text));
+        }
+    }
+    out
+}
+
+/// SGR parameters for a style.
+fn sgr_codes(style: Style) -> Vec<String> {
+    let mut codes = Vec::new();
+    let modifiers = [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::REVERSED, "7"),
+        (Modifier::CROSSED_OUT, "9"),
+    ];
+    for (modifier, code) in modifiers {
+        if style.add_modifier.contains(modifier) {
+            codes.push(code.to_string());
+        }
+    }
+    if let Some(fg) = style.fg.and_then(|c| color_code(c, false)) {
+        codes.push(fg);
+    }
+    if let Some(bg) = style.bg.and_then(|c| color_code(c, true)) {
+        codes.push(bg);
+    }
+    codes
+}
+
+/// SGR parameter for a foreground or background color. `None` for the
+/// terminal default.
+fn color_code(color: Color, background: bool) -> Option<String> {
+    let base = if background { 40 } else { 30 };
+    let named = |n: u8, bright: bool| {
+        let offset = if bright { 60 } else { 0 };
+        Some((base + offset + n).to_string())
+    };
+    match color {
+        Color::Reset => None,
+        Color::Black => named(0, false),
+        Color::Red => named(1, false),
+        Color::Green => named(2, false),
+        Color::Yellow => named(3, false),
+        Color::Blue => named(4, false),
+        Color::Magenta => named(5, false),
+        Color::Cyan => named(6, false),
+        Color::Gray => named(7, false),
+        Color::DarkGray => named(0, true),
+        Color::LightRed => named(1, true),
+        Color::LightGreen => named(2, true),
+        Color::LightYellow => named(3, true),
+        Color::LightBlue => named(4, true),
+        Color::LightMagenta => named(5, true),
+        Color::LightCyan => named(6, true),
+        Color::White => named(7, true),
+        Color::Rgb(r, g, b) => Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+        Color::Indexed(i) => Some(format!("{};5;{}", base + 8, i)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::text::Span;
+
+    #[test]
+    fn test_line_to_ansi() {
+        let line = Line::from(vec![
+            Span::styled(
+                "+",
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled("x\n", Style::default().bg(Color::Rgb(1, 2, 3))),
+            Span::raw(" plain"),
+        ]);
+        assert_eq!(
+            line_to_ansi(&line, true),
+            "\x1b[1;32m+\x1b[0m\x1b[48;2;1;2;3mx\x1b[0m plain"
+        );
+        assert_eq!(line_to_ansi(&line, false), "+x plain");
+    }
+
+    #[test]
+    fn test_color_codes() {
+        assert_eq!(color_code(Color::DarkGray, false).as_deref(), Some("90"));
+        assert_eq!(color_code(Color::White, true).as_deref(), Some("107"));
+        assert_eq!(
+            color_code(Color::Indexed(208), false).as_deref(),
+            Some("38;5;208")
+        );
+        assert_eq!(color_code(Color::Reset, false), None);
+    }
+}
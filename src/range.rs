@@ -0,0 +1,86 @@
+//! Reviewing a range of commits (`--range`): the diff from one revision to
+//! another, and the commits in between, which can be commented on as a
+//! whole.
+
+use anyhow::{Context, Result, bail};
+use git2::{Commit, Oid, Repository, RevparseMode, Sort};
+
+use crate::git::{self, DiffSettings};
+use crate::types::FileDiff;
+
+/// A commit of the range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeCommit {
+    pub id: Oid,
+    /// First line of the commit message.
+    pub summary: String,
+}
+
+impl RangeCommit {
+    /// Short SHA and subject, as feedback on the commit is labeled:
+    /// `1a2b3c4 Fix the parser`.
+    pub fn label(&self) -> String {
+        let id = self.id.to_string();
+        format!("{} {}", &id[..7], self.summary)
+    }
+}
+
+/// The two ends of a range, resolved from `A..B`, `A...B` (from the merge
+/// base of `A` and `B`) or `A` (`A..HEAD`), as `git log` reads them.
+pub struct RevRange<'r> {
+    pub from: Commit<'r>,
+    pub to: Commit<'r>,
+}
+
+impl<'r> RevRange<'r> {
+    pub fn resolve(repo: &'r Repository, spec: &str) -> Result<Self> {
+        let revspec = repo
+            .revparse(spec)
+            .with_context(|| format!("Invalid range '{}'", spec))?;
+        let commit = |object: Option<&git2::Object<'r>>| -> Result<Commit<'r>> {
+            object
+                .with_context(|| format!("Invalid range '{}'", spec))?
+                .peel_to_commit()
+                .with_context(|| format!("'{}' does not name commits", spec))
+        };
+        let (from, to) = if revspec.mode().contains(RevparseMode::SINGLE) {
+            let head = repo.head().context("Failed to read HEAD")?;
+            (commit(revspec.from())?, head.peel_to_commit()?)
+        } else {
+            (commit(revspec.from())?, commit(revspec.to())?)
+        };
+        let from = if revspec.mode().contains(RevparseMode::MERGE_BASE) {
+            let base = repo
+                .merge_base(from.id(), to.id())
+                .with_context(|| format!("No merge base for '{}'", spec))?;
+            repo.find_commit(base)?
+        } else {
+            from
+        };
+        if from.id() == to.id() {
+            bail!("The range '{}' is empty", spec);
+        }
+        Ok(Self { from, to })
+    }
+
+    /// The commits reachable from `to` but not from `from`, oldest first.
+    pub fn commits(&self, repo: &Repository) -> Result<Vec<RangeCommit>> {
+        let mut walk = repo.revwalk().context("Failed to walk the range")?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        walk.push(self.to.id())?;
+        walk.hide(self.from.id())?;
+        walk.map(|id| {
+            let commit = repo.find_commit(id?)?;
+            Ok(RangeCommit {
+                id: commit.id(),
+                summary: commit.summary().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+    }
+
+    /// The changes from `from` to `to`, computed as `settings` asks.
+    pub fn diff(&self, repo: &Repository, settings: &DiffSettings) -> Result<Vec<FileDiff>> {
+        git::get_tree_diff_with(repo, &self.from.tree()?, &self.to.tree()?, settings)
+    }
+}
@@ -0,0 +1,198 @@
+//! Recovery of editor tempfiles left behind when stagent dies while an
+//! editor pane is open.
+//!
+//! Every edit/comment tempfile gets a JSON sidecar (`<tempfile>.stagent.json`)
+//! recording the hunk it was prepared for. The sidecar is removed once the
+//! editor result has been read. On startup, sidecars whose stagent process
+//! is gone are orphans: their tempfiles still hold whatever was typed, and
+//! can be turned into feedback or discarded.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::editor;
+use crate::types::{DiffLine, HunkFeedback};
+
+/// Appended to a tempfile's name to form its sidecar's.
+pub const SIDECAR_SUFFIX: &str = ".stagent.json";
+
+/// An editor tempfile and the hunk it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditorSession {
+    pub tempfile: PathBuf,
+    /// Working directory of the repository under review; `None` in patch
+    /// mode.
+    pub workdir: Option<PathBuf>,
+    /// Process id of the stagent that opened the editor.
+    pub pid: u32,
+    pub is_comment: bool,
+    pub file_path: String,
+    pub hunk_header: String,
+    pub hunk_lines: Vec<DiffLine>,
+    /// Tempfile content before editing.
+    pub original_content: String,
+}
+
+impl EditorSession {
+    pub fn sidecar_path(&self) -> PathBuf {
+        let mut name = self.tempfile.clone().into_os_string();
+        name.push(SIDECAR_SUFFIX);
+        PathBuf::from(name)
+    }
+
+    /// Write the sidecar next to the tempfile.
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        std::fs::write(self.sidecar_path(), json).context("Failed to write editor sidecar")
+    }
+
+    /// Remove the sidecar once the editor result has been read.
+    pub fn clear(&self) {
+        let _ = std::fs::remove_file(self.sidecar_path());
+    }
+
+    /// Remove both the tempfile and the sidecar.
+    pub fn discard(&self) {
+        let _ = std::fs::remove_file(&self.tempfile);
+        self.clear();
+    }
+
+    /// Feedback from whatever was saved in the tempfile, or `None` if it is
+    /// gone or unchanged.
+    pub fn recover(&self) -> Option<HunkFeedback> {
+        let edited = std::fs::read_to_string(&self.tempfile).ok()?;
+        editor::parse_editor_result(
+            &self.original_content,
+            &edited,
+            self.is_comment,
+            &self.file_path,
+            &self.hunk_header,
+            &self.hunk_lines,
+        )
+    }
+}
+
+/// Editor sessions in `dir` (the temp directory) for `workdir` whose
+/// stagent process is no longer running. Unreadable sidecars are skipped.
+pub fn find_orphans(dir: &Path, workdir: Option<&Path>) -> Vec<EditorSession> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut orphans: Vec<EditorSession> = entries
+        .flatten()
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with("stagent-") && name.ends_with(SIDECAR_SUFFIX)
+        })
+        .filter_map(|e| std::fs::read_to_string(e.path()).ok())
+        .filter_map(|json| serde_json::from_str::<EditorSession>(&json).ok())
+        .filter(|s| s.workdir.as_deref() == workdir && !process_alive(s.pid))
+        .collect();
+    orphans.sort_by(|a, b| a.tempfile.cmp(&b.tempfile));
+    orphans
+}
+
+/// Whether a process with this id exists (`kill -0`).
+pub fn process_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FeedbackKind, LineKind};
+
+    /// A pid no process has: above the kernel's maximum.
+    const DEAD_PID: u32 = 999_999_999;
+
+    fn session(dir: &Path, name: &str, pid: u32, workdir: Option<&Path>) -> EditorSession {
+        let original = "# Add your comments anywhere in this file.\n\n-old\n+new\n";
+        let tempfile = dir.join(name);
+        std::fs::write(&tempfile, original).unwrap();
+        EditorSession {
+            tempfile,
+            workdir: workdir.map(Path::to_path_buf),
+            pid,
+            is_comment: true,
+            file_path: "src/a.rs".to_string(),
+            hunk_header: "@@ -1 +1 @@".to_string(),
+            hunk_lines: vec![
+                DiffLine {
+                    kind: LineKind::Removed,
+                    content: "old\n".to_string(),
+                    old_lineno: Some(1),
+                    new_lineno: None,
+                    raw: None,
+                    no_newline: false,
+                },
+                DiffLine {
+                    kind: LineKind::Added,
+                    content: "new\n".to_string(),
+                    old_lineno: None,
+                    new_lineno: Some(1),
+                    raw: None,
+                    no_newline: false,
+                },
+            ],
+            original_content: original.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_orphans_are_sessions_of_dead_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Path::new("/work/repo");
+        let orphan = session(dir.path(), "stagent-comment-1.tmp", DEAD_PID, Some(repo));
+        orphan.save().unwrap();
+        session(
+            dir.path(),
+            "stagent-comment-2.tmp",
+            std::process::id(),
+            Some(repo),
+        )
+        .save()
+        .unwrap();
+        session(dir.path(), "stagent-comment-3.tmp", DEAD_PID, None)
+            .save()
+            .unwrap();
+
+        let orphans = find_orphans(dir.path(), Some(repo));
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].tempfile, orphan.tempfile);
+        assert_eq!(find_orphans(dir.path(), None).len(), 1);
+    }
+
+    #[test]
+    fn test_recover_and_discard() {
+        let dir = tempfile::tempdir().unwrap();
+        let s = session(dir.path(), "stagent-comment-1.tmp", DEAD_PID, None);
+        s.save().unwrap();
+        assert!(s.recover().is_none(), "nothing typed yet");
+
+        std::fs::write(
+            &s.tempfile,
+            "# Add your comments anywhere in this file.\n\n-old\n+new\nwhy?\n",
+        )
+        .unwrap();
+        let fb = s.recover().unwrap();
+        assert_eq!(fb.kind, FeedbackKind::Comment);
+        assert_eq!(fb.content, "why?");
+        assert_eq!(fb.file_path, "src/a.rs");
+
+        s.discard();
+        assert!(!s.tempfile.exists());
+        assert!(!s.sidecar_path().exists());
+    }
+}
@@ -0,0 +1,506 @@
+//! Reminders for comments from an earlier review round that haven't been
+//! addressed yet.
+//!
+//! Comments are read from the previous feedback file (the `--output` file,
+//! or the latest archived feedback) and from git notes. A comment counts as
+//! unaddressed while the diff lines it was placed after are still present,
+//! unchanged, in a hunk of the same file.
+//!
+//! Feedback files may be in any output format: diff output, strict-diff
+//! output (`--output-style strict-diff`) or JSON.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use git2::Repository;
+use serde::Deserialize;
+
+use crate::archive;
+use crate::patch::parse_hunk_header;
+use crate::types::{FeedbackKind, Hunk, HunkFeedback};
+
+/// Number of diff lines before a comment that must still match.
+pub const ANCHOR_LINES: usize = 3;
+
+const COMMENT_PREFIX: &str = "# REVIEW COMMENT:";
+
+/// Title of a strict-diff comment block, before the path.
+const BLOCK_PREFIX: &str = "#=== REVIEW COMMENT ";
+
+/// A comment left in an earlier review round.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviousComment {
+    pub path: PathBuf,
+    /// Hunk header the comment was attached to.
+    pub header: Option<String>,
+    /// Diff lines (with their `+`/`-`/` ` prefix, no newline) the comment
+    /// followed, at most [`ANCHOR_LINES`]. Empty when the position is
+    /// unknown; the header is matched instead.
+    pub anchor: Vec<String>,
+    pub text: String,
+}
+
+impl PreviousComment {
+    /// Whether the comment's lines are still in `hunk` unchanged.
+    pub fn is_unaddressed(&self, hunk: &Hunk) -> bool {
+        if self.anchor.is_empty() {
+            return self.header.as_deref() == Some(hunk.header.as_str());
+        }
+        let lines: Vec<String> = hunk
+            .lines
+            .iter()
+            .map(|l| format!("{}{}", l.kind.prefix(), l.content.trim_end_matches('\n')))
+            .collect();
+        lines
+            .windows(self.anchor.len())
+            .any(|window| window == self.anchor.as_slice())
+    }
+}
+
+/// JSON output with a review verdict; only the feedback matters here.
+#[derive(Deserialize)]
+struct JsonReview {
+    feedback: Vec<HunkFeedback>,
+}
+
+/// Comments from feedback output, in diff, strict-diff or JSON format.
+pub fn parse_feedback(text: &str) -> Vec<PreviousComment> {
+    if text.lines().any(|l| l.starts_with("#=== ")) {
+        return parse_strict_feedback(text);
+    }
+    if text.trim_start().starts_with('[') {
+        return serde_json::from_str::<Vec<HunkFeedback>>(text)
+            .map(|fbs| from_hunk_feedback(&fbs))
+            .unwrap_or_default();
+    }
+    if text.trim_start().starts_with('{') {
+        return serde_json::from_str::<JsonReview>(text)
+            .map(|review| from_hunk_feedback(&review.feedback))
+            .unwrap_or_default();
+    }
+
+    let mut comments = Vec::new();
+    let mut path: Option<PathBuf> = None;
+    let mut header: Option<String> = None;
+    let mut recent: Vec<String> = Vec::new();
+    // Consecutive comment lines form one comment
+    let mut current: Option<PreviousComment> = None;
+
+    for line in text.lines() {
+        if let Some(text) = line.strip_prefix(COMMENT_PREFIX) {
+            let text = text.strip_prefix(' ').unwrap_or(text);
+            match current.as_mut() {
+                Some(comment) => {
+                    comment.text.push('\n');
+                    comment.text.push_str(text);
+                }
+                None => {
+                    let Some(ref path) = path else {
+                        continue;
+                    };
+                    current = Some(PreviousComment {
+                        path: path.clone(),
+                        header: header.clone(),
+                        anchor: recent[recent.len().saturating_sub(ANCHOR_LINES)..].to_vec(),
+                        text: text.to_string(),
+                    });
+                }
+            }
+            continue;
+        }
+        comments.extend(current.take());
+
+        if let Some(p) = line.strip_prefix("+++ b/") {
+            path = Some(PathBuf::from(p));
+            header = None;
+            recent.clear();
+        } else if line.starts_with("--- a/") {
+            path = None;
+        } else if line.starts_with("@@") {
+            header = Some(line.to_string());
+            recent.clear();
+        } else if line == "  ..." {
+            recent.clear();
+        } else if line.starts_with([' ', '+', '-']) {
+            recent.push(line.to_string());
+        }
+    }
+    comments.extend(current);
+    comments
+}
+
+/// A diff line of strict-diff output, with the hunk it is in and its line
+/// numbers.
+struct NumberedLine {
+    header: String,
+    /// The line with its `+`/`-`/` ` prefix, no newline.
+    text: String,
+    old_lineno: Option<u32>,
+    new_lineno: Option<u32>,
+}
+
+/// Comments from strict-diff output. A `#=== REVIEW COMMENT <path> line
+/// N ===` block is anchored on the diff lines up to line `N` (`old line N`
+/// after a removed line) in the hunks of its file, which come before the
+/// blocks. A block on a whole hunk, titled with its `@@` header, is matched
+/// by the header.
+fn parse_strict_feedback(text: &str) -> Vec<PreviousComment> {
+    let mut comments = Vec::new();
+    // Diff lines of the current file's hunks
+    let mut lines: Vec<NumberedLine> = Vec::new();
+    let mut header = String::new();
+    let (mut old_lineno, mut new_lineno) = (0, 0);
+
+    let mut rest = text.lines();
+    while let Some(line) = rest.next() {
+        if line.starts_with("#=== ") {
+            let body: Vec<&str> = rest
+                .by_ref()
+                .take_while(|l| *l != "#=== END ===")
+                .filter(|l| !l.starts_with("# SEVERITY: "))
+                .map(|l| l.strip_prefix("# ").unwrap_or(l.trim_start_matches('#')))
+                .collect();
+            let title = line
+                .strip_prefix(BLOCK_PREFIX)
+                .and_then(|t| t.strip_suffix(" ==="));
+            if let Some(comment) = title.and_then(|t| strict_comment(t, &lines, &body)) {
+                comments.push(comment);
+            }
+        } else if line.starts_with("diff --git ") {
+            lines.clear();
+        } else if ["--- a/", "+++ b/", "--- /dev/null", "+++ /dev/null"]
+            .iter()
+            .any(|p| line.starts_with(p))
+        {
+            continue;
+        } else if line.starts_with("@@") {
+            if let Ok((old_start, _, new_start, _, _)) = parse_hunk_header(line) {
+                (old_lineno, new_lineno) = (old_start, new_start);
+                header = line.to_string();
+            }
+        } else if let Some(kind) = line.chars().next().filter(|c| matches!(c, ' ' | '+' | '-')) {
+            let old = (kind != '+').then_some(old_lineno);
+            let new = (kind != '-').then_some(new_lineno);
+            old_lineno += u32::from(old.is_some());
+            new_lineno += u32::from(new.is_some());
+            lines.push(NumberedLine {
+                header: header.clone(),
+                text: line.to_string(),
+                old_lineno: old,
+                new_lineno: new,
+            });
+        }
+    }
+    comments
+}
+
+/// The comment of a strict-diff block titled `<path> line N`, `<path> old
+/// line N` or `<path> <hunk header>`, placed in `lines`. `None` if its line
+/// is not in them.
+fn strict_comment(title: &str, lines: &[NumberedLine], body: &[&str]) -> Option<PreviousComment> {
+    let text = body.join("\n").trim_end().to_string();
+    if let Some(at) = title.find(" @@") {
+        return Some(PreviousComment {
+            path: PathBuf::from(&title[..at]),
+            header: Some(title[at + 1..].to_string()),
+            anchor: Vec::new(),
+            text,
+        });
+    }
+
+    let (path, number) = title.rsplit_once(" line ")?;
+    let number: u32 = number.parse().ok()?;
+    let (path, old_side) = match path.strip_suffix(" old") {
+        Some(path) => (path, true),
+        None => (path, false),
+    };
+    let end = 1 + lines.iter().position(|l| {
+        if old_side {
+            l.text.starts_with('-') && l.old_lineno == Some(number)
+        } else {
+            !l.text.starts_with('-') && l.new_lineno == Some(number)
+        }
+    })?;
+    let header = &lines[end - 1].header;
+    // Only lines of the same hunk anchor the comment
+    let start = lines[..end]
+        .iter()
+        .rposition(|l| l.header != *header)
+        .map_or(0, |i| i + 1)
+        .max(end.saturating_sub(ANCHOR_LINES));
+    Some(PreviousComment {
+        path: PathBuf::from(path),
+        header: Some(header.clone()),
+        anchor: lines[start..end].iter().map(|l| l.text.clone()).collect(),
+        text,
+    })
+}
+
+/// Comments from structured feedback (the JSON output format).
+fn from_hunk_feedback(feedback: &[HunkFeedback]) -> Vec<PreviousComment> {
+    let mut comments = Vec::new();
+    for fb in feedback
+        .iter()
+        .filter(|fb| fb.kind == FeedbackKind::Comment)
+    {
+        let comment = |anchor: Vec<String>, text: &str| PreviousComment {
+            path: PathBuf::from(&fb.file_path),
+            header: Some(fb.hunk_header.clone()),
+            anchor,
+            text: text.to_string(),
+        };
+        if fb.comment_positions.is_empty() {
+            comments.push(comment(Vec::new(), &fb.content));
+        }
+        for (pos, text) in &fb.comment_positions {
+            let end = (*pos).min(fb.context_lines.len());
+            let anchor = fb.context_lines[end.saturating_sub(ANCHOR_LINES)..end]
+                .iter()
+                .map(|l| format!("{}{}", l.kind.prefix(), l.content.trim_end_matches('\n')))
+                .collect();
+            comments.push(comment(anchor, text));
+        }
+    }
+    comments
+}
+
+/// Comments recorded in review notes (see [`crate::notes::build_note`]).
+/// Notes only keep the hunk header and the first line of each comment.
+pub fn from_notes(notes: &HashMap<PathBuf, String>) -> Vec<PreviousComment> {
+    let mut comments = Vec::new();
+    for (path, note) in notes {
+        for line in note.lines() {
+            let Some((header, text)) = line
+                .strip_prefix("- ")
+                .and_then(|l| l.split_once(" comment: "))
+            else {
+                continue;
+            };
+            comments.push(PreviousComment {
+                path: path.clone(),
+                header: Some(header.to_string()),
+                anchor: Vec::new(),
+                text: text.to_string(),
+            });
+        }
+    }
+    comments
+}
+
+/// Load previous comments for a review: from `feedback_file` if given,
+/// otherwise the latest archived feedback, plus the review notes. Missing
+/// or unreadable sources are skipped.
+pub fn load(
+    repo: &Repository,
+    feedback_file: Option<&Path>,
+    notes: &HashMap<PathBuf, String>,
+) -> Vec<PreviousComment> {
+    let path = match feedback_file {
+        Some(path) => Some(path.to_path_buf()),
+        None => archive::find_archived(&archive::archive_dir(repo), "latest")
+            .ok()
+            .map(|a| a.path),
+    };
+    let mut comments = path
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .map(|text| parse_feedback(&text))
+        .unwrap_or_default();
+
+    for comment in from_notes(notes) {
+        if !comments.contains(&comment) {
+            comments.push(comment);
+        }
+    }
+    comments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DiffLine, HunkStatus, LineKind, ReviewVerdict, Verdict};
+
+    fn line(kind: LineKind, content: &str) -> DiffLine {
+        DiffLine {
+            kind,
+            content: format!("{}\n", content),
+            old_lineno: None,
+            new_lineno: None,
+            raw: None,
+            no_newline: false,
+        }
+    }
+
+    fn hunk(lines: Vec<DiffLine>) -> Hunk {
+        Hunk {
+            header: "@@ -1,3 +1,3 @@".to_string(),
+            lines: lines.into(),
+            status: HunkStatus::Pending,
+            old_start: 1,
+            old_lines: 3,
+            new_start: 1,
+            new_lines: 3,
+        }
+    }
+
+    const FEEDBACK: &str = "\
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    old();
++    new();
+# REVIEW COMMENT: why new?
+# REVIEW COMMENT: explain
+ }
+--- a/src/b.rs
++++ b/src/b.rs
+@@ -5 +5 @@
+-x
++y
+";
+
+    #[test]
+    fn test_parse_diff_feedback() {
+        let comments = parse_feedback(FEEDBACK);
+        assert_eq!(comments.len(), 1);
+        let comment = &comments[0];
+        assert_eq!(comment.path, PathBuf::from("src/a.rs"));
+        assert_eq!(comment.header.as_deref(), Some("@@ -1,3 +1,3 @@"));
+        assert_eq!(
+            comment.anchor,
+            vec![" fn main() {", "-    old();", "+    new();"]
+        );
+        assert_eq!(comment.text, "why new?\nexplain");
+    }
+
+    #[test]
+    fn test_unaddressed_until_lines_change() {
+        let comment = &parse_feedback(FEEDBACK)[0];
+        let unchanged = hunk(vec![
+            line(LineKind::Context, "fn main() {"),
+            line(LineKind::Removed, "    old();"),
+            line(LineKind::Added, "    new();"),
+            line(LineKind::Context, "}"),
+        ]);
+        assert!(comment.is_unaddressed(&unchanged));
+
+        let addressed = hunk(vec![
+            line(LineKind::Context, "fn main() {"),
+            line(LineKind::Removed, "    old();"),
+            line(LineKind::Added, "    new(); // needed for X"),
+            line(LineKind::Context, "}"),
+        ]);
+        assert!(!comment.is_unaddressed(&addressed));
+    }
+
+    #[test]
+    fn test_parse_strict_diff_feedback() {
+        let numbered = |kind, content: &str, old, new| DiffLine {
+            old_lineno: old,
+            new_lineno: new,
+            ..line(kind, content)
+        };
+        let comment = |content: &str, positions: Vec<(usize, String)>| HunkFeedback {
+            file_path: "src/a.rs".to_string(),
+            hunk_header: "@@ -1,3 +1,3 @@".to_string(),
+            kind: FeedbackKind::Comment,
+            content: content.to_string(),
+            context_lines: vec![
+                numbered(LineKind::Context, "fn main() {", Some(1), Some(1)),
+                numbered(LineKind::Removed, "    old();", Some(2), None),
+                numbered(LineKind::Added, "    new();", None, Some(2)),
+                numbered(LineKind::Context, "}", Some(3), Some(3)),
+            ],
+            comment_positions: positions,
+            suggestion: None,
+            severity: Some(crate::types::Severity::Nit),
+        };
+        let feedback = [
+            comment("why new?", vec![(3, "why new?\n\nexplain".to_string())]),
+            comment("", vec![(2, "gone?".to_string())]),
+            comment("looks odd", Vec::new()),
+        ];
+        let strict = crate::feedback::format_feedback_as(
+            &feedback,
+            None,
+            None,
+            5,
+            crate::types::FeedbackFormat::Diff,
+            crate::types::OutputStyle::StrictDiff,
+        )
+        .unwrap();
+
+        let comments = parse_feedback(&strict);
+        assert_eq!(comments.len(), 3, "{}", strict);
+        assert!(
+            comments
+                .iter()
+                .all(|c| c.path == Path::new("src/a.rs") && c.header.is_some())
+        );
+        assert_eq!(
+            comments[0].anchor,
+            vec![" fn main() {", "-    old();", "+    new();"]
+        );
+        assert_eq!(comments[0].text, "why new?\n\nexplain");
+        assert_eq!(comments[1].anchor, vec![" fn main() {", "-    old();"]);
+        assert_eq!(comments[1].text, "gone?");
+        assert!(comments[2].anchor.is_empty());
+        assert_eq!(comments[2].header.as_deref(), Some("@@ -1,3 +1,3 @@"));
+        assert_eq!(comments[2].text, "looks odd");
+
+        let unchanged = hunk(vec![
+            line(LineKind::Context, "fn main() {"),
+            line(LineKind::Removed, "    old();"),
+            line(LineKind::Added, "    new();"),
+            line(LineKind::Context, "}"),
+        ]);
+        assert!(comments.iter().all(|c| c.is_unaddressed(&unchanged)));
+    }
+
+    #[test]
+    fn test_parse_json_feedback() {
+        let fb = HunkFeedback {
+            file_path: "src/a.rs".to_string(),
+            hunk_header: "@@ -1 +1 @@".to_string(),
+            kind: FeedbackKind::Comment,
+            content: "hmm".to_string(),
+            context_lines: vec![line(LineKind::Removed, "a"), line(LineKind::Added, "b")],
+            comment_positions: vec![(2, "hmm".to_string())],
+            suggestion: None,
+            severity: None,
+        };
+        let json = serde_json::to_string(&vec![&fb]).unwrap();
+        let comments = parse_feedback(&json);
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].anchor, vec!["-a", "+b"]);
+
+        // With a verdict the feedback is wrapped in an object
+        let verdict = ReviewVerdict {
+            verdict: Verdict::Comment,
+            summary: None,
+        };
+        let json = crate::feedback::format_feedback_as(
+            &[fb],
+            Some(&verdict),
+            None,
+            5,
+            crate::types::FeedbackFormat::Json,
+            crate::types::OutputStyle::Annotated,
+        )
+        .unwrap();
+        assert_eq!(parse_feedback(&json).len(), 1);
+    }
+
+    #[test]
+    fn test_notes_match_by_header() {
+        let notes = HashMap::from([(
+            PathBuf::from("src/a.rs"),
+            "stagent review: src/a.rs\nFeedback: 1\n- @@ -1,3 +1,3 @@ comment: looks odd\n"
+                .to_string(),
+        )]);
+        let comments = from_notes(&notes);
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].text, "looks odd");
+        assert!(comments[0].is_unaddressed(&hunk(Vec::new())));
+    }
+}
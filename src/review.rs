@@ -0,0 +1,349 @@
+//! The review engine without the TUI: a diff's hunks, their statuses and
+//! the feedback collected on them, with the actions that change them.
+//!
+//! [`ReviewSession`] is what another tool drives to run a stagent review
+//! behind its own interface. The TUI's [`crate::app::App`] shares the
+//! offset and comment bookkeeping below with it, so both stage the same
+//! bytes and produce the same feedback.
+//!
+//! ```no_run
+//! use stagent::review::ReviewSession;
+//! use stagent::types::{FeedbackFormat, OutputStyle};
+//!
+//! let repo = stagent::git::open_repo(".")?;
+//! let mut review = ReviewSession::open(&repo, &Default::default())?;
+//! review.stage(0, 0)?;
+//! review.comment(0, 1, "!nit This name is unclear")?;
+//! let output = review.format_feedback(FeedbackFormat::Diff, OutputStyle::Annotated, 3)?;
+//! print!("{}", output);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{Result, bail};
+use git2::Repository;
+
+use crate::feedback;
+use crate::git::{self, DiffSettings, IntentToAdd};
+use crate::staging::{self, WorktreeSnapshot};
+use crate::stats::{self, ReviewStats};
+use crate::types::{
+    FeedbackFormat, FeedbackKind, FileDiff, HunkFeedback, HunkStatus, OutputStyle, Severity,
+};
+
+/// A review of a diff: hunk statuses and feedback, changed through
+/// [`stage`](Self::stage), [`skip`](Self::skip) and
+/// [`comment`](Self::comment). Files and hunks are addressed by their index
+/// in [`files`](Self::files).
+pub struct ReviewSession<'r> {
+    files: Vec<FileDiff>,
+    feedback: Vec<HunkFeedback>,
+    /// Where staged hunks go; `None` only marks them staged, as `y` does
+    /// in patch mode.
+    repo: Option<&'r Repository>,
+    /// Working tree content the diff was computed from, to refuse staging
+    /// a hunk whose file has changed since.
+    snapshot: WorktreeSnapshot,
+    /// Untracked files [`open`](Self::open) added with intent-to-add,
+    /// untracked again when the session is dropped unless something of
+    /// them was staged.
+    intent_added: Option<IntentToAdd<'r>>,
+}
+
+impl<'r> ReviewSession<'r> {
+    /// Review the unstaged changes of `repo`, as `stagent` does: untracked
+    /// files are added with intent-to-add unless `settings` leaves them
+    /// out, and staging writes to its index. Files nothing was staged from
+    /// are untracked again once the session is dropped.
+    pub fn open(repo: &'r Repository, settings: &DiffSettings) -> Result<Self> {
+        let intent_added = if settings.exclude_untracked {
+            IntentToAdd::none(repo)
+        } else {
+            IntentToAdd::add_untracked(repo)?
+        };
+        let files = git::get_unstaged_diff_with(repo, settings)?;
+        Ok(Self {
+            intent_added: Some(intent_added),
+            ..Self::with_repo(files, repo)
+        })
+    }
+
+    /// Review `files`, staging into the index of `repo`. The hunks must
+    /// apply to it, as with `stagent --patch --apply`.
+    pub fn with_repo(files: Vec<FileDiff>, repo: &'r Repository) -> Self {
+        let snapshot = staging::snapshot_worktree(repo, &files);
+        Self {
+            files,
+            feedback: Vec::new(),
+            repo: Some(repo),
+            snapshot,
+            intent_added: None,
+        }
+    }
+
+    /// Review `files` without a repository, e.g. from
+    /// [`crate::patch::parse_unified_diff`]. Staging only marks hunks as
+    /// accepted.
+    pub fn new(files: Vec<FileDiff>) -> Self {
+        Self {
+            files,
+            feedback: Vec::new(),
+            repo: None,
+            snapshot: WorktreeSnapshot::new(),
+            intent_added: None,
+        }
+    }
+
+    pub fn files(&self) -> &[FileDiff] {
+        &self.files
+    }
+
+    /// The feedback collected so far, in the order it was given.
+    pub fn feedback(&self) -> &[HunkFeedback] {
+        &self.feedback
+    }
+
+    pub fn status(&self, file: usize, hunk: usize) -> Option<HunkStatus> {
+        self.files.get(file)?.hunks.get(hunk).map(|h| h.status)
+    }
+
+    pub fn stats(&self) -> ReviewStats {
+        stats::compute(&self.files)
+    }
+
+    /// Stage a pending hunk into the index (or accept it without a
+    /// repository). Fails if the file changed in the working tree since the
+    /// diff was computed.
+    pub fn stage(&mut self, file: usize, hunk: usize) -> Result<()> {
+        self.check_pending(file, hunk)?;
+        if let Some(repo) = self.repo {
+            let diff = &self.files[file];
+            staging::check_unchanged(repo, &self.snapshot, &diff.path)?;
+            staging::stage_hunk(repo, diff, &diff.hunks[hunk], line_offset(diff, hunk))?;
+        }
+        self.files[file].hunks[hunk].status = HunkStatus::Staged;
+        Ok(())
+    }
+
+    /// Mark a pending hunk as skipped.
+    pub fn skip(&mut self, file: usize, hunk: usize) -> Result<()> {
+        self.check_pending(file, hunk)?;
+        self.files[file].hunks[hunk].status = HunkStatus::Skipped;
+        Ok(())
+    }
+
+    /// Comment on a hunk, after its last line.
+    pub fn comment(&mut self, file: usize, hunk: usize, text: &str) -> Result<()> {
+        let last = self.check_hunk(file, hunk)?.hunks[hunk].lines.len();
+        self.comment_on_line(file, hunk, last.saturating_sub(1), text)
+    }
+
+    /// Comment on a hunk after its line `line` (an index into its
+    /// `lines`). A `!nit`, `!suggestion`, `!issue` or `!blocker` prefix
+    /// rates the comment; comments on the same hunk are merged.
+    pub fn comment_on_line(
+        &mut self,
+        file: usize,
+        hunk: usize,
+        line: usize,
+        text: &str,
+    ) -> Result<()> {
+        self.check_hunk(file, hunk)?;
+        let diff = &self.files[file];
+        if add_comment(&mut self.feedback, diff, hunk, line, text.trim()).is_none() {
+            bail!("Empty comment");
+        }
+        self.files[file].hunks[hunk].status = HunkStatus::Commented;
+        Ok(())
+    }
+
+    /// The feedback rendered as stagent writes it on quitting, in `format`
+    /// and, for diff output, `style`.
+    pub fn format_feedback(
+        &self,
+        format: FeedbackFormat,
+        style: OutputStyle,
+        context_lines: usize,
+    ) -> Result<String> {
+        feedback::format_feedback_as(&self.feedback, None, None, context_lines, format, style)
+    }
+
+    /// The files, with their final statuses, and the feedback. Untracked
+    /// files nothing was staged from are untracked again.
+    pub fn into_parts(self) -> Result<(Vec<FileDiff>, Vec<HunkFeedback>)> {
+        if let Some(added) = self.intent_added {
+            added.finish()?;
+        }
+        Ok((self.files, self.feedback))
+    }
+
+    fn check_hunk(&self, file: usize, hunk: usize) -> Result<&FileDiff> {
+        match self.files.get(file) {
+            Some(diff) if hunk < diff.hunks.len() => Ok(diff),
+            Some(diff) => bail!("{} has no hunk {}", diff.path.display(), hunk),
+            None => bail!("No file {} in the review", file),
+        }
+    }
+
+    fn check_pending(&self, file: usize, hunk: usize) -> Result<()> {
+        let diff = self.check_hunk(file, hunk)?;
+        match diff.hunks[hunk].status {
+            HunkStatus::Pending => Ok(()),
+            status => bail!(
+                "Hunk {} of {} is already reviewed ({:?})",
+                hunk,
+                diff.path.display(),
+                status
+            ),
+        }
+    }
+}
+
+/// The line offset for staging hunk `hunk_idx` of `file` after the hunks
+/// already staged before it: each shifts line numbers by
+/// (new_lines - old_lines).
+pub fn line_offset(file: &FileDiff, hunk_idx: usize) -> i32 {
+    file.hunks
+        .iter()
+        .take(hunk_idx)
+        .filter(|h| h.status == HunkStatus::Staged)
+        .map(|h| h.new_lines as i32 - h.old_lines as i32)
+        .sum()
+}
+
+/// Attach `text` as a comment after line `anchor` of hunk `hunk_idx` of
+/// `file`. A `!<severity>` prefix (see [`Severity::strip_prefix`]) rates
+/// it.
+///
+/// Comments on a hunk that already has comment feedback are merged into
+/// it, ordered by position, so the output matches editor comments. Returns
+/// the index of the feedback and the comment's severity, or `None` if
+/// there is no text besides the prefix.
+pub fn add_comment(
+    feedback: &mut Vec<HunkFeedback>,
+    file: &FileDiff,
+    hunk_idx: usize,
+    anchor: usize,
+    text: &str,
+) -> Option<(usize, Option<Severity>)> {
+    let hunk = &file.hunks[hunk_idx];
+    let (severity, text) = match Severity::strip_prefix(text) {
+        Some((severity, rest)) => (Some(severity), rest),
+        None => (None, text),
+    };
+    if text.is_empty() {
+        return None;
+    }
+    let file_path = file.path.to_string_lossy().to_string();
+    let position = (anchor + 1).min(hunk.lines.len());
+
+    let existing = feedback.iter().position(|fb| {
+        fb.kind == FeedbackKind::Comment
+            && fb.file_path == file_path
+            && fb.hunk_header == hunk.header
+    });
+    let index = match existing {
+        Some(index) => {
+            let fb = &mut feedback[index];
+            let idx = fb
+                .comment_positions
+                .partition_point(|(p, _)| *p <= position);
+            fb.comment_positions
+                .insert(idx, (position, text.to_string()));
+            fb.severity = fb.severity.max(severity);
+            fb.content = fb
+                .comment_positions
+                .iter()
+                .map(|(_, t)| t.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            index
+        }
+        None => {
+            feedback.push(HunkFeedback {
+                file_path,
+                hunk_header: hunk.header.clone(),
+                kind: FeedbackKind::Comment,
+                content: text.to_string(),
+                context_lines: hunk.lines.to_vec(),
+                comment_positions: vec![(position, text.to_string())],
+                suggestion: None,
+                severity,
+            });
+            feedback.len() - 1
+        }
+    };
+    Some((index, severity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::parse_unified_diff;
+
+    fn files() -> Vec<FileDiff> {
+        parse_unified_diff(
+            "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,3 @@
+ one
++two
+ three
+@@ -10 +11 @@
+-ten
++TEN
+",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_actions_change_statuses() {
+        let mut review = ReviewSession::new(files());
+        review.stage(0, 0).unwrap();
+        assert_eq!(review.status(0, 0), Some(HunkStatus::Staged));
+        assert!(review.stage(0, 0).is_err());
+        review.skip(0, 1).unwrap();
+        assert_eq!(review.status(0, 1), Some(HunkStatus::Skipped));
+        assert!(review.skip(0, 2).is_err());
+        assert!(review.skip(1, 0).is_err());
+        assert_eq!(review.stats().pending, 0);
+    }
+
+    #[test]
+    fn test_comments_merge_per_hunk() {
+        let mut review = ReviewSession::new(files());
+        review.comment(0, 0, "At the end").unwrap();
+        review.comment_on_line(0, 0, 0, "!issue After one").unwrap();
+        assert!(review.comment(0, 1, "!nit").is_err());
+
+        assert_eq!(review.status(0, 0), Some(HunkStatus::Commented));
+        assert_eq!(review.status(0, 1), Some(HunkStatus::Pending));
+        let feedback = review.feedback();
+        assert_eq!(feedback.len(), 1);
+        assert_eq!(feedback[0].content, "After one\nAt the end");
+        assert_eq!(feedback[0].severity, Some(Severity::Issue));
+        assert_eq!(
+            feedback[0].comment_positions,
+            [(1, "After one".to_string()), (3, "At the end".to_string())]
+        );
+        let output = review
+            .format_feedback(FeedbackFormat::Diff, OutputStyle::Annotated, 3)
+            .unwrap();
+        assert!(output.contains("After one"), "got: {}", output);
+        let strict = review
+            .format_feedback(FeedbackFormat::Diff, OutputStyle::StrictDiff, 3)
+            .unwrap();
+        assert!(strict.contains("#=== REVIEW COMMENT"), "got: {}", strict);
+    }
+
+    #[test]
+    fn test_line_offset_counts_staged_hunks_before() {
+        let mut files = files();
+        assert_eq!(line_offset(&files[0], 1), 0);
+        files[0].hunks[0].status = HunkStatus::Staged;
+        assert_eq!(line_offset(&files[0], 1), 1);
+        assert_eq!(line_offset(&files[0], 0), 0);
+    }
+}
@@ -0,0 +1,264 @@
+//! Per-hunk risk scores from git history, for `--order risk` and the hunk
+//! header badge.
+//!
+//! Two signals feed the score, each worth up to half of it:
+//! - churn: how many of the last [`CHURN_COMMITS`] commits touched the file
+//! - fix density: the share of the hunk's old lines that `git blame`
+//!   attributes to bug-fix commits (see [`is_fix_message`])
+//!
+//! Files without history (untracked, or an unborn `HEAD`) score 0.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use git2::{BlameOptions, DiffOptions, Oid, Repository};
+
+use crate::session::hunk_key;
+use crate::types::{FileDiff, Hunk, LineKind};
+
+/// Number of commits from `HEAD` examined for churn.
+pub const CHURN_COMMITS: usize = 300;
+
+/// Commits touching a file at which the churn half of the score maxes out.
+pub const CHURN_CAP: usize = 20;
+
+/// Words in a commit message that mark a bug fix.
+const FIX_WORDS: &[&str] = &[
+    "fix",
+    "fixes",
+    "fixed",
+    "bug",
+    "bugfix",
+    "hotfix",
+    "regression",
+    "revert",
+];
+
+/// Coarse risk bucket shown in the badge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+}
+
+/// Risk score of a hunk and the signals behind it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HunkRisk {
+    /// 0–100.
+    pub score: u8,
+    /// Commits among the last [`CHURN_COMMITS`] that touched the file.
+    pub churn: usize,
+    /// Old lines of the hunk last changed by a bug-fix commit.
+    pub fix_lines: usize,
+    /// Old lines of the hunk that could be blamed.
+    pub blamed_lines: usize,
+}
+
+impl HunkRisk {
+    /// Combine the signals into a score.
+    pub fn new(churn: usize, fix_lines: usize, blamed_lines: usize) -> Self {
+        let churn_part = churn.min(CHURN_CAP) * 50 / CHURN_CAP;
+        let fix_part = (fix_lines * 50).checked_div(blamed_lines).unwrap_or(0);
+        Self {
+            score: (churn_part + fix_part).min(100) as u8,
+            churn,
+            fix_lines,
+            blamed_lines,
+        }
+    }
+
+    pub fn level(&self) -> RiskLevel {
+        match self.score {
+            60.. => RiskLevel::High,
+            30.. => RiskLevel::Medium,
+            _ => RiskLevel::Low,
+        }
+    }
+}
+
+/// Risk scores keyed by file path and [`hunk_key`].
+#[derive(Debug, Clone, Default)]
+pub struct RiskScores(HashMap<(PathBuf, String), HunkRisk>);
+
+impl RiskScores {
+    pub fn get(&self, path: &Path, hunk: &Hunk) -> Option<&HunkRisk> {
+        self.0.get(&(path.to_path_buf(), hunk_key(hunk)))
+    }
+
+    /// Record `risk` for `hunk`, e.g. for the parts of a split hunk.
+    pub fn insert(&mut self, path: &Path, hunk: &Hunk, risk: HunkRisk) {
+        self.0.insert((path.to_path_buf(), hunk_key(hunk)), risk);
+    }
+
+    /// Highest score of any hunk in `file`.
+    pub fn file_score(&self, file: &FileDiff) -> u8 {
+        file.hunks
+            .iter()
+            .filter_map(|h| self.get(&file.path, h))
+            .map(|r| r.score)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Whether a commit message describes a bug fix.
+pub fn is_fix_message(message: &str) -> bool {
+    message
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| FIX_WORDS.contains(&word.to_lowercase().as_str()))
+}
+
+/// Number of commits among the last [`CHURN_COMMITS`] reachable from
+/// `HEAD` that touched each of `paths`. Merge commits are not counted.
+pub fn file_churn(repo: &Repository, paths: &[PathBuf]) -> HashMap<PathBuf, usize> {
+    let mut churn = HashMap::new();
+    let Ok(mut walk) = repo.revwalk() else {
+        return churn;
+    };
+    if paths.is_empty() || walk.push_head().is_err() {
+        return churn;
+    }
+
+    let mut opts = DiffOptions::new();
+    for path in paths {
+        opts.pathspec(path);
+    }
+    for oid in walk.flatten().take(CHURN_COMMITS) {
+        let Ok(commit) = repo.find_commit(oid) else {
+            continue;
+        };
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+        let Ok(tree) = commit.tree() else {
+            continue;
+        };
+        let Ok(diff) = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut opts))
+        else {
+            continue;
+        };
+        for delta in diff.deltas() {
+            if let Some(path) = delta.new_file().path().or(delta.old_file().path()) {
+                *churn.entry(path.to_path_buf()).or_insert(0) += 1;
+            }
+        }
+    }
+    churn
+}
+
+/// Blame the old lines of `hunk` and count how many of them were last
+/// changed by a bug-fix commit. Returns `(fix_lines, blamed_lines)`.
+/// `fix_commits` caches [`is_fix_message`] per commit across calls.
+fn blame_fix_lines(
+    repo: &Repository,
+    path: &Path,
+    hunk: &Hunk,
+    fix_commits: &mut HashMap<Oid, bool>,
+) -> (usize, usize) {
+    let old_lines = hunk
+        .lines
+        .iter()
+        .filter(|l| l.kind != LineKind::Added)
+        .filter_map(|l| l.old_lineno);
+    let (Some(first), Some(last)) = (old_lines.clone().min(), old_lines.max()) else {
+        return (0, 0);
+    };
+
+    let mut opts = BlameOptions::new();
+    opts.min_line(first as usize).max_line(last as usize);
+    let Ok(blame) = repo.blame_file(path, Some(&mut opts)) else {
+        return (0, 0);
+    };
+
+    let mut fix_lines = 0;
+    let mut blamed_lines = 0;
+    for blame_hunk in blame.iter() {
+        let lines = blame_hunk.lines_in_hunk();
+        blamed_lines += lines;
+        let oid = blame_hunk.final_commit_id();
+        let is_fix = *fix_commits.entry(oid).or_insert_with(|| {
+            repo.find_commit(oid)
+                .ok()
+                .and_then(|c| c.message().map(is_fix_message))
+                .unwrap_or(false)
+        });
+        if is_fix {
+            fix_lines += lines;
+        }
+    }
+    (fix_lines, blamed_lines)
+}
+
+/// Score every hunk of `files`.
+pub fn score_files(repo: &Repository, files: &[FileDiff]) -> RiskScores {
+    let paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+    let churn = file_churn(repo, &paths);
+    let mut fix_commits = HashMap::new();
+
+    let mut scores = RiskScores::default();
+    for file in files.iter().filter(|f| !f.is_whole_file()) {
+        let file_churn = churn.get(&file.path).copied().unwrap_or(0);
+        for hunk in &file.hunks {
+            let (fix_lines, blamed_lines) =
+                blame_fix_lines(repo, &file.path, hunk, &mut fix_commits);
+            scores.insert(
+                &file.path,
+                hunk,
+                HunkRisk::new(file_churn, fix_lines, blamed_lines),
+            );
+        }
+    }
+    scores
+}
+
+/// Order files by their riskiest hunk, highest first; ties keep path order.
+/// Hunks within a file keep their order, which staging relies on.
+pub fn order_by_risk(files: &mut [FileDiff], scores: &RiskScores) {
+    files.sort_by(|a, b| compare_by_risk(a, b, scores));
+}
+
+/// The comparison behind [`order_by_risk`].
+pub fn compare_by_risk(a: &FileDiff, b: &FileDiff, scores: &RiskScores) -> Ordering {
+    scores
+        .file_score(b)
+        .cmp(&scores.file_score(a))
+        .then_with(|| a.path.cmp(&b.path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_fix_message() {
+        assert!(is_fix_message("Fix off-by-one in parser"));
+        assert!(is_fix_message("parser: fixes #12"));
+        assert!(is_fix_message("Revert \"Add cache\""));
+        assert!(is_fix_message("fix(ui): wrap long lines"));
+        assert!(!is_fix_message("Add prefix option"));
+        assert!(!is_fix_message("Debugging output for suffixes"));
+    }
+
+    #[test]
+    fn test_score_combines_churn_and_fixes() {
+        assert_eq!(HunkRisk::new(0, 0, 0).score, 0);
+        assert_eq!(HunkRisk::new(CHURN_CAP, 0, 10).score, 50);
+        assert_eq!(HunkRisk::new(CHURN_CAP * 3, 0, 10).score, 50);
+        assert_eq!(HunkRisk::new(0, 5, 10).score, 25);
+        assert_eq!(HunkRisk::new(CHURN_CAP, 10, 10).score, 100);
+    }
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(HunkRisk::new(2, 0, 10).level(), RiskLevel::Low);
+        assert_eq!(HunkRisk::new(CHURN_CAP, 0, 10).level(), RiskLevel::Medium);
+        assert_eq!(HunkRisk::new(CHURN_CAP, 4, 10).level(), RiskLevel::High);
+    }
+}
@@ -0,0 +1,268 @@
+//! Scripted reviews (`--script`): drive a review from a list of actions
+//! instead of the keyboard, with no terminal, for CI and end-to-end tests.
+//!
+//! A script has one action per line; blank lines and lines starting with
+//! `#` are ignored:
+//!
+//! ```text
+//! goto src/main.rs:12   # a file, the hunk at a line, or the N-th hunk (path#N)
+//! stage                 # stage the hunk (accept it with --no-stage)
+//! skip
+//! next                  # next / prev hunk, next-pending for the next pending one
+//! comment "Needs a test"
+//! quit                  # stop here; the end of the script quits too
+//! ```
+//!
+//! Actions go through the same [`App`] methods as the keys, so the review
+//! ends with the same feedback output and session state as an interactive
+//! one.
+
+use anyhow::{Context, Result, bail};
+use git2::Repository;
+use std::path::PathBuf;
+
+use crate::app::{self, App, RunOptions};
+use crate::git;
+use crate::types::{AppMode, FileDiff, HunkStatus, StartPosition};
+
+/// One step of a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Next,
+    Prev,
+    NextPending,
+    /// Select a file (`path`), the hunk holding a line (`path:line`), or
+    /// the N-th hunk (`path#N`).
+    Goto(StartPosition),
+    Stage,
+    Skip,
+    /// Comment on the current hunk; `!nit` and friends rate it as in the
+    /// inline comment input.
+    Comment(String),
+    Quit,
+}
+
+/// Parse a script into its actions, each with its 1-based line number.
+///
+/// ```
+/// use stagent::script::{Action, parse};
+///
+/// let actions = parse("# review\nstage\ncomment \"say \\\"why\\\"\"\n")?;
+/// assert_eq!(actions[0], (2, Action::Stage));
+/// assert_eq!(actions[1], (3, Action::Comment("say \"why\"".to_string())));
+/// assert!(parse("dance").is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn parse(script: &str) -> Result<Vec<(usize, Action)>> {
+    let mut actions = Vec::new();
+    for (idx, line) in script.lines().enumerate() {
+        let number = idx + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let action = parse_action(line).with_context(|| format!("Script line {}", number))?;
+        actions.push((number, action));
+    }
+    Ok(actions)
+}
+
+fn parse_action(line: &str) -> Result<Action> {
+    let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    // Trailing ` # ...` comments, except inside a comment's text
+    let arg = match word {
+        "comment" => rest.trim(),
+        _ if rest.trim_start().starts_with('#') => "",
+        _ => rest.split(" #").next().unwrap_or("").trim(),
+    };
+    let no_arg = |action: Action| {
+        if arg.is_empty() {
+            Ok(action)
+        } else {
+            bail!("'{}' takes no argument", word)
+        }
+    };
+    match word {
+        "next" => no_arg(Action::Next),
+        "prev" => no_arg(Action::Prev),
+        "next-pending" => no_arg(Action::NextPending),
+        "stage" => no_arg(Action::Stage),
+        "skip" => no_arg(Action::Skip),
+        "quit" => no_arg(Action::Quit),
+        "goto" if arg.is_empty() => bail!("'goto' needs a path"),
+        "goto" => Ok(Action::Goto(match arg.rsplit_once('#') {
+            Some((path, n)) => {
+                let hunk = n
+                    .parse()
+                    .with_context(|| format!("Invalid hunk number '{}'", n))?;
+                StartPosition::from_args(Some(path), Some(hunk))
+            }
+            None => StartPosition::from_args(Some(arg), None),
+        })),
+        "comment" => {
+            let text = unquote(arg)?;
+            if text.trim().is_empty() {
+                bail!("'comment' needs a text");
+            }
+            Ok(Action::Comment(text))
+        }
+        _ => bail!(
+            "Unknown action '{}' (next, prev, next-pending, goto, stage, skip, comment, quit)",
+            word
+        ),
+    }
+}
+
+/// The text of a comment argument: a double-quoted string with `\"`, `\\`
+/// and `\n` escapes, or the bare rest of the line.
+fn unquote(arg: &str) -> Result<String> {
+    let Some(inner) = arg.strip_prefix('"') else {
+        return Ok(arg.to_string());
+    };
+    let mut text = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if chars.as_str().trim().is_empty() => return Ok(text),
+            '"' => bail!("Unexpected text after the closing quote"),
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some(c @ ('"' | '\\')) => text.push(c),
+                Some(c) => bail!("Unknown escape '\\{}'", c),
+                None => break,
+            },
+            c => text.push(c),
+        }
+    }
+    bail!("Missing closing quote")
+}
+
+/// Run a scripted review: set the app up like the TUI does, then apply
+/// `actions` in order. Returns the final app state, as [`app::run`] does.
+pub fn run(
+    files: Vec<FileDiff>,
+    repo: Option<&Repository>,
+    opts: RunOptions,
+    actions: &[(usize, Action)],
+) -> Result<App> {
+    let mut app = app::prepare(files, repo, opts);
+    if app.mode == AppMode::ResumePrompt {
+        app.accept_resume();
+    }
+    app.mode = AppMode::Browsing;
+
+    for (number, action) in actions {
+        app.message = None;
+        apply(&mut app, repo, action).with_context(|| format!("Script line {}", number))?;
+        if *action == Action::Quit {
+            break;
+        }
+    }
+    Ok(app)
+}
+
+fn apply(app: &mut App, repo: Option<&Repository>, action: &Action) -> Result<()> {
+    match action {
+        Action::Next => app.select_next_hunk(),
+        Action::Prev => app.select_prev_hunk(),
+        Action::NextPending => app.select_next_pending_hunk(),
+        Action::Goto(pos) => {
+            let pos = StartPosition {
+                path: pos.path.as_ref().map(|p| {
+                    PathBuf::from(git::to_repo_path(&app.cwd_prefix, &p.to_string_lossy()))
+                }),
+                ..pos.clone()
+            };
+            if !app.jump_to_position(&pos) {
+                bail!("{}", app.message.as_deref().unwrap_or("Position not found"));
+            }
+        }
+        Action::Stage => {
+            expect_pending(app)?;
+            match repo {
+                Some(repo) => app.stage_current_hunk(repo)?,
+                None => app.accept_current_hunk(),
+            }
+        }
+        Action::Skip => {
+            expect_pending(app)?;
+            app.skip_current_hunk();
+        }
+        Action::Comment(text) => {
+            app.start_inline_comment();
+            if app.mode != AppMode::InlineComment {
+                bail!(
+                    "{}",
+                    app.message.as_deref().unwrap_or("Cannot comment here")
+                );
+            }
+            app.comment_input = text.clone();
+            app.submit_inline_comment();
+        }
+        Action::Quit => {}
+    }
+    Ok(())
+}
+
+/// Fail unless the current hunk is still pending, instead of silently
+/// doing nothing like the keys do.
+fn expect_pending(app: &App) -> Result<()> {
+    match app.current_hunk() {
+        Some(hunk) if hunk.status == HunkStatus::Pending => Ok(()),
+        Some(hunk) => bail!("The hunk is already reviewed ({:?})", hunk.status),
+        None => bail!("No hunk selected"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_actions() {
+        let script = "\
+# comment line
+next # then
+goto src/a.rs:12  # the hunk at line 12
+goto b.rs#2
+comment !nit tidy # this stays
+  skip
+";
+        let actions = parse(script).unwrap();
+        assert_eq!(
+            actions,
+            [
+                (2, Action::Next),
+                (
+                    3,
+                    Action::Goto(StartPosition::from_args(Some("src/a.rs:12"), None))
+                ),
+                (
+                    4,
+                    Action::Goto(StartPosition::from_args(Some("b.rs"), Some(2)))
+                ),
+                (5, Action::Comment("!nit tidy # this stays".to_string())),
+                (6, Action::Skip),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        let err = parse("next\nstage now\n").unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "Script line 2: 'stage' takes no argument"
+        );
+        assert!(parse("comment \"open").is_err());
+        assert!(parse("comment").is_err());
+        assert!(parse("goto a.rs#x").is_err());
+    }
+
+    #[test]
+    fn test_comment_quoting() {
+        assert_eq!(unquote(r#""a \"b\"\nc""#).unwrap(), "a \"b\"\nc");
+        assert_eq!(unquote("bare words").unwrap(), "bare words");
+        assert!(unquote(r#""a" b"#).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+//! Persist review progress so a later stagent run can continue where the
+//! previous one stopped.
+//!
+//! A session records the status of every reviewed hunk together with a
+//! fingerprint of the diff it was taken from. Hunks are matched back by file
+//! path and content rather than by index, so hunks staged in the previous run
+//! (and therefore gone from the new diff) don't shift the others.
+//!
+//! Two files use this format: the spawn handoff (`.git/stagent/handoff.json`)
+//! and the session saved on every quit (`.git/stagent-session.json`), which
+//! `--resume` loads.
+
+use anyhow::{Context, Result};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "tui")]
+use crate::app::App;
+use crate::types::{FileDiff, Hunk, HunkFeedback, HunkStatus};
+
+/// Review status of a single hunk, keyed by its file and content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedHunk {
+    pub path: PathBuf,
+    pub key: String,
+    pub status: HunkStatus,
+}
+
+/// Snapshot of a review session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// Fingerprint of the diff the session applies to (see [`fingerprint`]).
+    pub fingerprint: String,
+    /// Path of the file that was selected on quit.
+    pub selected_path: Option<PathBuf>,
+    /// Index of the hunk that was selected on quit.
+    pub selected_hunk: usize,
+    /// Every hunk that was no longer pending.
+    pub hunks: Vec<SavedHunk>,
+    /// Feedback collected so far.
+    #[serde(default)]
+    pub feedback: Vec<HunkFeedback>,
+}
+
+#[cfg(feature = "tui")]
+impl Session {
+    /// Capture the review state of `app`. `fingerprint` should describe the
+    /// diff the next run will see.
+    pub fn capture(app: &App, fingerprint: String) -> Self {
+        let hunks = app
+            .files
+            .iter()
+            .flat_map(|f| {
+                f.hunks
+                    .iter()
+                    .filter(|h| h.status != HunkStatus::Pending)
+                    .map(|h| SavedHunk {
+                        path: f.path.clone(),
+                        key: hunk_key(h),
+                        status: h.status,
+                    })
+            })
+            .collect();
+
+        Self {
+            fingerprint,
+            selected_path: app.current_file().map(|f| f.path.clone()),
+            selected_hunk: app.selected_hunk,
+            hunks,
+            feedback: app.feedback.clone(),
+        }
+    }
+
+    /// Restore hunk statuses, feedback and the selected position into `app`.
+    /// Returns the number of hunks whose status was restored.
+    pub fn apply(&self, app: &mut App) -> usize {
+        let mut restored = 0;
+        for file in &mut app.files {
+            for hunk in &mut file.hunks {
+                if hunk.status != HunkStatus::Pending {
+                    continue;
+                }
+                let key = hunk_key(hunk);
+                if let Some(saved) = self
+                    .hunks
+                    .iter()
+                    .find(|s| s.path == file.path && s.key == key)
+                {
+                    hunk.status = saved.status;
+                    restored += 1;
+                }
+            }
+        }
+
+        for fb in &self.feedback {
+            let duplicate = app.feedback.iter().any(|existing| {
+                existing.file_path == fb.file_path
+                    && existing.hunk_header == fb.hunk_header
+                    && existing.content == fb.content
+            });
+            if !duplicate {
+                app.feedback.push(fb.clone());
+            }
+        }
+
+        if let Some(ref path) = self.selected_path
+            && let Some(idx) = app.files.iter().position(|f| &f.path == path)
+        {
+            app.selected_file = idx;
+            app.selected_hunk = self
+                .selected_hunk
+                .min(app.files[idx].hunks.len().saturating_sub(1));
+        }
+        app.highlight_cache.clear();
+        app.dirty = true;
+        restored
+    }
+}
+
+/// Whether any hunk in the review is still pending.
+pub fn has_pending(files: &[FileDiff]) -> bool {
+    files
+        .iter()
+        .flat_map(|f| &f.hunks)
+        .any(|h| h.status == HunkStatus::Pending)
+}
+
+/// Stable 64-bit FNV-1a hash. `DefaultHasher` is not guaranteed to be
+/// stable across Rust releases, which matters for on-disk keys.
+fn fnv1a(hash: &mut u64, bytes: &[u8]) {
+    for b in bytes {
+        *hash ^= *b as u64;
+        *hash = hash.wrapping_mul(0x100000001b3);
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// Content key for a hunk: a hash of its diff lines, ignoring line numbers
+/// so the key survives earlier hunks being staged.
+pub fn hunk_key(hunk: &Hunk) -> String {
+    let mut hash = FNV_OFFSET;
+    for line in &hunk.lines {
+        fnv1a(&mut hash, line.kind.prefix().as_bytes());
+        fnv1a(&mut hash, line.bytes());
+    }
+    format!("{:016x}", hash)
+}
+
+/// Fingerprint of an entire diff: file paths plus every hunk's content key.
+pub fn fingerprint(files: &[FileDiff]) -> String {
+    let mut hash = FNV_OFFSET;
+    for file in files {
+        fnv1a(&mut hash, file.path.to_string_lossy().as_bytes());
+        fnv1a(&mut hash, b"\0");
+        for hunk in &file.hunks {
+            fnv1a(&mut hash, hunk_key(hunk).as_bytes());
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Path of the spawn handoff file (`.git/stagent/handoff.json`).
+pub fn handoff_path(repo: &Repository) -> PathBuf {
+    repo.path().join("stagent").join("handoff.json")
+}
+
+/// Path of the session saved on quit (`.git/stagent-session.json`).
+pub fn session_path(repo: &Repository) -> PathBuf {
+    repo.path().join("stagent-session.json")
+}
+
+/// Write a session to `path`, creating parent directories as needed.
+pub fn save(path: &Path, session: &Session) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let json = serde_json::to_string_pretty(session).context("Failed to serialize session")?;
+    std::fs::write(path, json)
+        .with_context(|| format!("Failed to write session file: {}", path.display()))
+}
+
+/// Load a session from `path`. Missing or unreadable files yield `None`.
+pub fn load(path: &Path) -> Option<Session> {
+    let json = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Load a session only if it was recorded for a diff with this fingerprint.
+pub fn load_matching(path: &Path, fingerprint: &str) -> Option<Session> {
+    load(path).filter(|s| s.fingerprint == fingerprint)
+}
+
+/// Remove a session file if it exists.
+pub fn clear(path: &Path) {
+    let _ = std::fs::remove_file(path);
+}
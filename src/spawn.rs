@@ -1,50 +1,120 @@
 //! Spawn stagent in a tmux split pane and wait for completion.
 //!
 //! This module provides the `--spawn` functionality that allows Claude (or other
-//! tools) to launch stagent in a new tmux split, wait for the user to complete
-//! their review, and then read the feedback output.
+//! tools) to launch stagent in a new tmux split (or zellij pane, see
+//! [`crate::multiplexer`]), wait for the user to complete their review, and
+//! then read the feedback output.
 
-use anyhow::{Context, Result, bail};
-use std::path::PathBuf;
-use std::process::Command;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::editor::pane_exists;
+use crate::editor::{PANE_POLL_INTERVAL, SplitLayout, build_tmux_pane_command};
+use crate::git::DiffAlgorithm;
+use crate::multiplexer::{self, Multiplexer, MultiplexerKind};
+use crate::tmux::{Clock, SystemClock};
+use crate::types::{FeedbackFormat, OutputStyle, ReviewOrder, ViewMode};
 
 /// Options for spawning stagent in a split pane.
 #[derive(Debug, Clone)]
 pub struct SpawnOptions {
     /// Output file for feedback (--output)
     pub output: Option<PathBuf>,
-    /// Glob pattern for filtering files (--files)
-    pub files: Option<String>,
+    /// Output file for the edits as a patch (--edits-patch)
+    pub edits_patch: Option<PathBuf>,
+    /// Glob patterns for filtering files (--files)
+    pub files: Vec<String>,
+    /// Glob patterns for hiding files (--exclude)
+    pub exclude: Vec<String>,
     /// Theme name (--theme)
     pub theme: String,
     /// Context lines for feedback (--context-lines)
     pub context_lines: usize,
+    /// Feedback output format (--format)
+    pub format: FeedbackFormat,
     /// No-stage mode (--no-stage)
     pub no_stage: bool,
+    /// Initial file selection (--start-file)
+    pub start_file: Option<String>,
+    /// Initial hunk selection (--start-hunk)
+    pub start_hunk: Option<usize>,
+    /// Diff layout (--view)
+    pub view: ViewMode,
+    /// Draw tabs and spaces (--show-whitespace)
+    pub show_whitespace: bool,
+    /// Columns between tab stops, if not the default (--tab-width)
+    pub tab_width: Option<usize>,
+    /// Ignore whitespace changes (--ignore-whitespace)
+    pub ignore_whitespace: bool,
+    /// Ignore blank-line changes (--ignore-blank-lines)
+    pub ignore_blank_lines: bool,
+    /// Leave out untracked files (--no-untracked)
+    pub no_untracked: bool,
+    /// Show untracked files without intent-to-add (--no-ita)
+    pub no_ita: bool,
+    /// Line matching algorithm (--diff-algorithm)
+    pub diff_algorithm: Option<DiffAlgorithm>,
+    /// Start with the file overview (--overview)
+    pub overview: bool,
+    /// Diff context lines (--unified)
+    pub unified: Option<u32>,
+    /// Ask for a review verdict when quitting (--verdict)
+    pub verdict: bool,
+    /// Time the review per hunk (--track-time)
+    pub track_time: bool,
+    /// How comments sit in diff feedback (--output-style)
+    pub output_style: OutputStyle,
+    /// Record review notes (--notes)
+    pub notes: bool,
+    /// Resume the saved session (--resume)
+    pub resume: bool,
+    /// Refresh the diff when files change (--watch)
+    pub watch: bool,
+    /// File order (--order)
+    pub order: ReviewOrder,
+    /// Hunk size threshold (--auto-split)
+    pub auto_split: Option<usize>,
+    /// File the child writes its `--summary` line to, for the parent to
+    /// print once the pane closes
+    pub summary_file: Option<PathBuf>,
+    /// File the child writes its exit status to, for the parent to report
+    /// once the pane closes (see [`report_line`])
+    pub status_file: Option<PathBuf>,
+    /// Diff to review instead of the working tree (--patch-file)
+    pub patch_file: Option<PathBuf>,
+    /// Stage hunks of the patch into the repository (--apply)
+    pub apply: bool,
+    /// Pull request to publish the feedback to (--publish)
+    pub publish: Option<String>,
+    /// Range of commits to review instead of the working tree (--range)
+    pub range: Option<String>,
+    /// Where the review and its editors open (--multiplexer)
+    pub multiplexer: MultiplexerKind,
+    /// How tmux lays out the review's pane and its editors' (--split,
+    /// --split-size)
+    pub split: SplitLayout,
+    /// How long to wait for the review before closing its pane
+    /// (--spawn-timeout); `None` waits for as long as it takes
+    pub timeout: Option<Duration>,
 }
 
 /// Build the tmux split-window command for spawning stagent.
 ///
 /// Constructs a command that:
-/// - Opens a horizontal split at 50% width
+/// - Opens a split (or window) laid out as `opts.split` says, by default
+///   horizontal at 50% width
 /// - Returns the pane ID via -P -F '#{pane_id}'
-/// - Runs stagent with forwarded CLI args (but NOT --spawn)
+/// - Runs stagent with forwarded CLI args (but NOT --spawn), marked `--spawned`
 pub fn build_spawn_command(opts: &SpawnOptions) -> Vec<String> {
-    let mut cmd = vec![
-        "tmux".to_string(),
-        "split-window".to_string(),
-        "-h".to_string(),
-        "-p".to_string(),
-        "50".to_string(),
-        "-P".to_string(),
-        "-F".to_string(),
-        "#{pane_id}".to_string(),
-        "--".to_string(),
-    ];
+    build_tmux_pane_command(&opts.split, build_spawn_args(opts))
+}
+
+/// The stagent invocation run in the spawned pane: this executable with
+/// the forwarded CLI args (but NOT --spawn), marked `--spawned`.
+pub fn build_spawn_args(opts: &SpawnOptions) -> Vec<String> {
+    let mut cmd = Vec::new();
 
     // Get the current executable path
     let stagent_exe = std::env::current_exe()
@@ -53,17 +123,31 @@ pub fn build_spawn_command(opts: &SpawnOptions) -> Vec<String> {
 
     cmd.push(stagent_exe);
 
+    // Mark the child as spawned so unfinished reviews are handed off to the
+    // next --spawn invocation
+    cmd.push("--spawned".to_string());
+
     // Forward CLI args (but NOT --spawn to avoid infinite recursion)
     if let Some(ref output) = opts.output {
         cmd.push("--output".to_string());
         cmd.push(output.to_string_lossy().to_string());
     }
 
-    if let Some(ref files) = opts.files {
+    if let Some(ref edits_patch) = opts.edits_patch {
+        cmd.push("--edits-patch".to_string());
+        cmd.push(edits_patch.to_string_lossy().to_string());
+    }
+
+    for files in &opts.files {
         cmd.push("--files".to_string());
         cmd.push(files.clone());
     }
 
+    for exclude in &opts.exclude {
+        cmd.push("--exclude".to_string());
+        cmd.push(exclude.clone());
+    }
+
     if opts.theme != "default" {
         cmd.push("--theme".to_string());
         cmd.push(opts.theme.clone());
@@ -74,68 +158,322 @@ pub fn build_spawn_command(opts: &SpawnOptions) -> Vec<String> {
         cmd.push(opts.context_lines.to_string());
     }
 
+    let format = match opts.format {
+        FeedbackFormat::Diff => None,
+        FeedbackFormat::Json => Some("json"),
+        FeedbackFormat::Gerrit => Some("gerrit"),
+    };
+    if let Some(format) = format {
+        cmd.push("--format".to_string());
+        cmd.push(format.to_string());
+    }
+
     if opts.no_stage {
         cmd.push("--no-stage".to_string());
     }
 
-    cmd
-}
+    if let Some(ref start_file) = opts.start_file {
+        cmd.push("--start-file".to_string());
+        cmd.push(start_file.clone());
+    }
 
-/// Maximum number of poll iterations before giving up.
-/// At 500ms per poll, this is ~30 minutes.
-const MAX_SPAWN_POLL_ITERATIONS: u32 = 3600;
+    if let Some(start_hunk) = opts.start_hunk {
+        cmd.push("--start-hunk".to_string());
+        cmd.push(start_hunk.to_string());
+    }
 
-/// Spawn stagent in a tmux split pane and wait for it to complete.
-///
-/// Returns Ok(()) when the spawned stagent completes, or an error if
-/// the spawn fails.
-pub fn spawn_in_split(opts: &SpawnOptions) -> Result<()> {
-    let cmd = build_spawn_command(opts);
+    if opts.view == ViewMode::SideBySide {
+        cmd.push("--view".to_string());
+        cmd.push("side-by-side".to_string());
+    }
 
-    let output = Command::new(&cmd[0])
-        .args(&cmd[1..])
-        .output()
-        .context("Failed to run tmux split-window")?;
+    if opts.show_whitespace {
+        cmd.push("--show-whitespace".to_string());
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("tmux split-window failed: {}", stderr);
+    if let Some(width) = opts.tab_width {
+        cmd.push("--tab-width".to_string());
+        cmd.push(width.to_string());
     }
 
-    let pane_id = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if pane_id.is_empty() {
-        bail!("tmux split-window did not return a pane ID");
+    if opts.ignore_whitespace {
+        cmd.push("--ignore-whitespace".to_string());
     }
 
-    // Poll until the pane closes
-    wait_for_pane(&pane_id)?;
+    if opts.ignore_blank_lines {
+        cmd.push("--ignore-blank-lines".to_string());
+    }
 
-    Ok(())
+    if opts.no_untracked {
+        cmd.push("--no-untracked".to_string());
+    }
+
+    if opts.no_ita {
+        cmd.push("--no-ita".to_string());
+    }
+
+    if let Some(algorithm) = opts.diff_algorithm {
+        cmd.push("--diff-algorithm".to_string());
+        cmd.push(algorithm.name().to_string());
+    }
+
+    if opts.overview {
+        cmd.push("--overview".to_string());
+    }
+
+    if let Some(lines) = opts.unified {
+        cmd.push("--unified".to_string());
+        cmd.push(lines.to_string());
+    }
+
+    if opts.verdict {
+        cmd.push("--verdict".to_string());
+    }
+
+    if opts.track_time {
+        cmd.push("--track-time".to_string());
+    }
+
+    if opts.output_style == OutputStyle::StrictDiff {
+        cmd.push("--output-style".to_string());
+        cmd.push("strict-diff".to_string());
+    }
+
+    if opts.notes {
+        cmd.push("--notes".to_string());
+    }
+
+    if opts.resume {
+        cmd.push("--resume".to_string());
+    }
+
+    if opts.watch {
+        cmd.push("--watch".to_string());
+    }
+
+    let order = match opts.order {
+        ReviewOrder::Path => None,
+        ReviewOrder::Risk => Some("risk"),
+        ReviewOrder::Pending => Some("pending"),
+        ReviewOrder::Lines => Some("lines"),
+    };
+    if let Some(order) = order {
+        cmd.push("--order".to_string());
+        cmd.push(order.to_string());
+    }
+
+    if let Some(max_lines) = opts.auto_split {
+        cmd.push(format!("--auto-split={}", max_lines));
+    }
+
+    if let Some(ref patch_file) = opts.patch_file {
+        cmd.push("--patch-file".to_string());
+        cmd.push(patch_file.to_string_lossy().to_string());
+    }
+
+    if opts.apply {
+        cmd.push("--apply".to_string());
+    }
+
+    if let Some(ref publish) = opts.publish {
+        cmd.push("--publish".to_string());
+        cmd.push(publish.clone());
+    }
+
+    if let Some(ref range) = opts.range {
+        cmd.push("--range".to_string());
+        cmd.push(range.clone());
+    }
+
+    if let Some(ref summary_file) = opts.summary_file {
+        cmd.push("--summary-file".to_string());
+        cmd.push(summary_file.to_string_lossy().to_string());
+    }
+
+    if let Some(ref status_file) = opts.status_file {
+        cmd.push("--status-file".to_string());
+        cmd.push(status_file.to_string_lossy().to_string());
+    }
+
+    if opts.multiplexer != MultiplexerKind::Auto {
+        cmd.push("--multiplexer".to_string());
+        cmd.push(opts.multiplexer.name().to_string());
+    }
+
+    if opts.split != SplitLayout::default() {
+        cmd.push("--split".to_string());
+        cmd.push(opts.split.direction.name().to_string());
+        cmd.push("--split-size".to_string());
+        cmd.push(opts.split.size.to_string());
+    }
+
+    cmd
 }
 
-/// Block until the given tmux pane closes.
-fn wait_for_pane(pane_id: &str) -> Result<()> {
-    for _ in 0..MAX_SPAWN_POLL_ITERATIONS {
-        if !pane_exists(pane_id) {
-            return Ok(());
+/// How long `--spawn` waits for the review by default (`--spawn-timeout`).
+pub const DEFAULT_SPAWN_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// How often the waiting process checks for Ctrl+C and its deadline.
+const SUPERVISE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Why a spawned review ended without its pane closing by itself. The
+/// pane has been closed; callers can tell the cases apart by downcasting
+/// the error from [`spawn_in_split`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    /// The review was still open after the timeout.
+    TimedOut(Duration),
+    /// The spawning process was interrupted (Ctrl+C).
+    Interrupted,
+}
+
+impl std::fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::TimedOut(timeout) => write!(
+                f,
+                "Timed out after {}s waiting for stagent pane to close; closed it",
+                timeout.as_secs()
+            ),
+            Self::Interrupted => write!(f, "Interrupted; closed the stagent pane"),
         }
-        thread::sleep(Duration::from_millis(500));
     }
+}
+
+impl std::error::Error for SpawnError {}
+
+/// Spawn stagent in a tmux split pane (or zellij pane) and wait for it to
+/// complete.
+///
+/// Returns Ok(()) when the spawned stagent completes. Setting `interrupted`
+/// (e.g. from a SIGINT handler) closes the pane and fails with
+/// [`SpawnError::Interrupted`]; so does `opts.timeout` running out, with
+/// [`SpawnError::TimedOut`].
+pub fn spawn_in_split(opts: &SpawnOptions, interrupted: &AtomicBool) -> Result<()> {
+    let mux = multiplexer::system(opts.multiplexer.resolve(), opts.split);
+    spawn_in_split_with(mux.as_ref(), &SystemClock, opts, interrupted)
+}
+
+/// [`spawn_in_split`] with an explicit multiplexer and clock.
+pub fn spawn_in_split_with(
+    mux: &dyn Multiplexer,
+    clock: &dyn Clock,
+    opts: &SpawnOptions,
+    interrupted: &AtomicBool,
+) -> Result<()> {
+    let pane = mux.open_pane(&build_spawn_args(opts))?;
+
+    // Polling gives up after the timeout's worth of polls; waiting on a
+    // tmux channel is cut short by closing the pane at the deadline
+    let max_polls = match opts.timeout {
+        Some(timeout) => (timeout.as_millis() / PANE_POLL_INTERVAL.as_millis()).max(1) as u32,
+        None => u32::MAX,
+    };
+    let deadline = opts.timeout.map(|timeout| Instant::now() + timeout);
+    let done = AtomicBool::new(false);
+    let mut ended = None;
+    let closed = thread::scope(|scope| {
+        let supervisor = scope.spawn(|| {
+            while !done.load(Ordering::SeqCst) {
+                let reason = if interrupted.load(Ordering::SeqCst) {
+                    SpawnError::Interrupted
+                } else if deadline.is_some_and(|d| Instant::now() >= d) {
+                    SpawnError::TimedOut(opts.timeout.unwrap_or_default())
+                } else {
+                    thread::sleep(SUPERVISE_INTERVAL);
+                    continue;
+                };
+                let _ = mux.close_pane(&pane);
+                return Some(reason);
+            }
+            None
+        });
+        let closed = multiplexer::wait_until_closed(mux, clock, &pane, max_polls);
+        done.store(true, Ordering::SeqCst);
+        ended = supervisor.join().unwrap_or(None);
+        closed
+    });
+
+    if let Some(reason) = ended {
+        return Err(reason.into());
+    }
+    if !closed {
+        let _ = mux.close_pane(&pane);
+        return Err(SpawnError::TimedOut(opts.timeout.unwrap_or_default()).into());
+    }
+    Ok(())
+}
+
+/// Start of the line the spawning process prints once the pane closes,
+/// for scripts to find it among other output.
+pub const REPORT_PREFIX: &str = "STAGENT_SPAWN: ";
+
+/// The line reporting where a spawned review left its feedback:
+/// [`REPORT_PREFIX`] followed by a JSON object with the `output` file and
+/// the child's `exit_status` (`null` if it never got to write one, e.g.
+/// when its pane was killed), e.g.
+/// `STAGENT_SPAWN: {"exit_status":0,"output":"/tmp/stagent-feedback-x.diff"}`.
+pub fn report_line(output: &Path, exit_status: Option<i32>) -> String {
+    let report = serde_json::json!({
+        "output": output.to_string_lossy(),
+        "exit_status": exit_status,
+    });
+    format!("{}{}", REPORT_PREFIX, report)
+}
 
-    bail!("Timed out waiting for stagent pane to close");
+/// The exit status the spawned stagent left in its `--status-file`.
+pub fn read_exit_status(status_file: &Path) -> Option<i32> {
+    std::fs::read_to_string(status_file)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::editor::{SplitDirection, SplitSize};
 
     fn default_opts() -> SpawnOptions {
         SpawnOptions {
             output: None,
-            files: None,
+            edits_patch: None,
+            files: Vec::new(),
+            exclude: Vec::new(),
             theme: "default".to_string(),
             context_lines: crate::feedback::DEFAULT_CONTEXT_LINES,
+            format: FeedbackFormat::Diff,
             no_stage: false,
+            start_file: None,
+            start_hunk: None,
+            view: ViewMode::Unified,
+            show_whitespace: false,
+            tab_width: None,
+            ignore_whitespace: false,
+            ignore_blank_lines: false,
+            no_untracked: false,
+            no_ita: false,
+            diff_algorithm: None,
+            overview: false,
+            unified: None,
+            verdict: false,
+            track_time: false,
+            output_style: OutputStyle::Annotated,
+            notes: false,
+            resume: false,
+            watch: false,
+            order: ReviewOrder::Path,
+            auto_split: None,
+            summary_file: None,
+            status_file: None,
+            patch_file: None,
+            apply: false,
+            publish: None,
+            range: None,
+            multiplexer: MultiplexerKind::Auto,
+            split: SplitLayout::default(),
+            timeout: Some(DEFAULT_SPAWN_TIMEOUT),
         }
     }
 
@@ -158,6 +496,8 @@ mod tests {
             !cmd.contains(&"--spawn".to_string()),
             "Command should not contain --spawn"
         );
+        // Child is marked so it can hand off unfinished reviews
+        assert!(cmd.contains(&"--spawned".to_string()));
     }
 
     #[test]
@@ -172,10 +512,23 @@ mod tests {
         assert!(cmd.contains(&"/tmp/feedback.diff".to_string()));
     }
 
+    #[test]
+    fn test_build_spawn_command_with_edits_patch() {
+        let opts = SpawnOptions {
+            edits_patch: Some(PathBuf::from("/tmp/edits.patch")),
+            ..default_opts()
+        };
+        let cmd = build_spawn_command(&opts);
+
+        let flag = cmd.iter().position(|a| a == "--edits-patch").unwrap();
+        assert_eq!(cmd[flag + 1], "/tmp/edits.patch");
+        assert!(!build_spawn_command(&default_opts()).contains(&"--edits-patch".to_string()));
+    }
+
     #[test]
     fn test_build_spawn_command_with_files() {
         let opts = SpawnOptions {
-            files: Some("*.rs".to_string()),
+            files: vec!["*.rs".to_string()],
             ..default_opts()
         };
         let cmd = build_spawn_command(&opts);
@@ -241,10 +594,45 @@ mod tests {
     fn test_build_spawn_command_all_options() {
         let opts = SpawnOptions {
             output: Some(PathBuf::from("/tmp/out.diff")),
-            files: Some("src/*.rs".to_string()),
+            edits_patch: None,
+            files: vec!["src/*.rs".to_string()],
+            exclude: vec!["src/gen.rs".to_string()],
             theme: "monokai".to_string(),
             context_lines: 10,
+            format: FeedbackFormat::Gerrit,
             no_stage: true,
+            start_file: Some("src/lib.rs:42".to_string()),
+            start_hunk: None,
+            view: ViewMode::SideBySide,
+            show_whitespace: true,
+            tab_width: Some(8),
+            ignore_whitespace: true,
+            ignore_blank_lines: true,
+            no_untracked: false,
+            no_ita: true,
+            diff_algorithm: Some(DiffAlgorithm::Patience),
+            overview: true,
+            unified: Some(1),
+            verdict: true,
+            track_time: true,
+            output_style: OutputStyle::StrictDiff,
+            notes: true,
+            resume: true,
+            watch: true,
+            order: ReviewOrder::Risk,
+            auto_split: Some(12),
+            summary_file: Some(PathBuf::from("/tmp/summary")),
+            status_file: Some(PathBuf::from("/tmp/status")),
+            patch_file: Some(PathBuf::from("/tmp/change.patch")),
+            apply: true,
+            publish: Some("github:o/r#1".to_string()),
+            range: Some("main..feature".to_string()),
+            multiplexer: MultiplexerKind::Zellij,
+            split: SplitLayout {
+                direction: SplitDirection::Vertical,
+                size: SplitSize::Percent(30),
+            },
+            timeout: None,
         };
         let cmd = build_spawn_command(&opts);
 
@@ -252,11 +640,246 @@ mod tests {
         assert!(cmd.contains(&"/tmp/out.diff".to_string()));
         assert!(cmd.contains(&"--files".to_string()));
         assert!(cmd.contains(&"src/*.rs".to_string()));
+        assert!(cmd.contains(&"--exclude".to_string()));
+        assert!(cmd.contains(&"src/gen.rs".to_string()));
         assert!(cmd.contains(&"--theme".to_string()));
         assert!(cmd.contains(&"monokai".to_string()));
         assert!(cmd.contains(&"--context-lines".to_string()));
         assert!(cmd.contains(&"10".to_string()));
+        assert!(cmd.contains(&"gerrit".to_string()));
+        let tab_width = cmd.iter().position(|a| a == "--tab-width").unwrap();
+        assert_eq!(cmd[tab_width + 1], "8");
         assert!(cmd.contains(&"--no-stage".to_string()));
+        assert!(cmd.contains(&"--start-file".to_string()));
+        assert!(cmd.contains(&"src/lib.rs:42".to_string()));
+        assert!(cmd.contains(&"side-by-side".to_string()));
+        assert!(cmd.contains(&"--show-whitespace".to_string()));
+        assert!(cmd.contains(&"--ignore-whitespace".to_string()));
+        assert!(cmd.contains(&"--ignore-blank-lines".to_string()));
+        assert!(cmd.contains(&"--no-ita".to_string()));
+        assert!(!cmd.contains(&"--no-untracked".to_string()));
+        assert!(cmd.contains(&"--diff-algorithm".to_string()));
+        assert!(cmd.contains(&"patience".to_string()));
+        assert!(cmd.contains(&"--overview".to_string()));
+        assert!(cmd.contains(&"--unified".to_string()));
+        assert!(cmd.contains(&"--verdict".to_string()));
+        assert!(cmd.contains(&"--track-time".to_string()));
+        let style = cmd.iter().position(|a| a == "--output-style").unwrap();
+        assert_eq!(cmd[style + 1], "strict-diff");
+        assert!(cmd.contains(&"--notes".to_string()));
+        assert!(cmd.contains(&"--resume".to_string()));
+        assert!(cmd.contains(&"--watch".to_string()));
+        assert!(cmd.contains(&"risk".to_string()));
+        assert!(cmd.contains(&"--auto-split=12".to_string()));
+        assert!(cmd.contains(&"--summary-file".to_string()));
+        assert!(cmd.contains(&"/tmp/summary".to_string()));
+        assert!(cmd.contains(&"--status-file".to_string()));
+        assert!(cmd.contains(&"/tmp/status".to_string()));
+        assert!(cmd.contains(&"--patch-file".to_string()));
+        assert!(cmd.contains(&"/tmp/change.patch".to_string()));
+        assert!(cmd.contains(&"--apply".to_string()));
+        assert!(cmd.contains(&"github:o/r#1".to_string()));
+        let range = cmd.iter().position(|a| a == "--range").unwrap();
+        assert_eq!(cmd[range + 1], "main..feature");
+        assert!(cmd.contains(&"zellij".to_string()));
+        assert!(cmd.contains(&"--split".to_string()));
+        assert!(cmd.contains(&"vertical".to_string()));
+        assert!(cmd.contains(&"30%".to_string()));
         assert!(!cmd.contains(&"--spawn".to_string()));
+        // The spawned pane itself opens with the same layout
+        assert_eq!(cmd[1..4], ["split-window", "-v", "-p"]);
+    }
+
+    #[test]
+    fn test_report_line_and_exit_status() {
+        let line = report_line(Path::new("/tmp/feedback.diff"), Some(1));
+        let report: serde_json::Value =
+            serde_json::from_str(line.strip_prefix(REPORT_PREFIX).unwrap()).unwrap();
+        assert_eq!(report["output"], "/tmp/feedback.diff");
+        assert_eq!(report["exit_status"], 1);
+        assert!(report_line(Path::new("/tmp/f"), None).contains("\"exit_status\":null"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let status_file = dir.path().join("status");
+        assert_eq!(read_exit_status(&status_file), None);
+        std::fs::write(&status_file, "0\n").unwrap();
+        assert_eq!(read_exit_status(&status_file), Some(0));
+    }
+
+    #[test]
+    fn test_build_spawn_command_with_start_hunk() {
+        let opts = SpawnOptions {
+            start_file: Some("src/main.rs".to_string()),
+            start_hunk: Some(3),
+            ..default_opts()
+        };
+        let cmd = build_spawn_command(&opts);
+
+        let pos = cmd.iter().position(|s| s == "--start-hunk").unwrap();
+        assert_eq!(cmd[pos + 1], "3");
+        assert!(cmd.contains(&"--start-file".to_string()));
+    }
+
+    #[test]
+    fn test_spawn_waits_for_pane_to_close() {
+        use crate::multiplexer::Tmux;
+        use crate::tmux::{FakeClock, FakeTmux};
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let polls = AtomicUsize::new(0);
+        let tmux = Arc::new(FakeTmux::new(move |args| match args[0].as_str() {
+            "split-window" => Ok("%5\n".to_string()),
+            // An old tmux: completion is polled for
+            "wait-for" => anyhow::bail!("unknown command: wait-for"),
+            _ if polls.fetch_add(1, Ordering::SeqCst) < 2 => Ok("%0\n%5\n".to_string()),
+            _ => Ok("%0\n".to_string()),
+        }));
+        let clock = FakeClock::new();
+
+        spawn_in_split_with(
+            &Tmux::new(tmux.clone()),
+            &clock,
+            &default_opts(),
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        let calls = tmux.calls();
+        assert_eq!(calls[0][0], "split-window");
+        assert_eq!(calls[1][0], "wait-for");
+        assert_eq!(calls.len(), 5);
+        assert_eq!(clock.elapsed(), crate::editor::PANE_POLL_INTERVAL * 2);
+    }
+
+    #[test]
+    fn test_spawn_times_out() {
+        use crate::multiplexer::Tmux;
+        use crate::tmux::{FakeClock, FakeTmux};
+        use std::sync::Arc;
+
+        let tmux = Arc::new(FakeTmux::new(|args| match args[0].as_str() {
+            "wait-for" => anyhow::bail!("unknown command: wait-for"),
+            _ => Ok("%5\n".to_string()),
+        }));
+        let clock = FakeClock::new();
+
+        let err = spawn_in_split_with(
+            &Tmux::new(tmux),
+            &clock,
+            &default_opts(),
+            &AtomicBool::new(false),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Timed out"));
+        assert_eq!(clock.elapsed(), DEFAULT_SPAWN_TIMEOUT);
+    }
+
+    #[test]
+    fn test_spawn_interrupted_closes_the_pane() {
+        use crate::multiplexer::Tmux;
+        use crate::tmux::{FakeClock, FakeTmux};
+        use std::sync::Arc;
+
+        let killed = Arc::new(AtomicBool::new(false));
+        let tmux = Arc::new(FakeTmux::new({
+            let killed = killed.clone();
+            move |args| match args[0].as_str() {
+                "split-window" => Ok("%5\n".to_string()),
+                "wait-for" => anyhow::bail!("unknown command: wait-for"),
+                "kill-pane" => {
+                    killed.store(true, Ordering::SeqCst);
+                    Ok(String::new())
+                }
+                _ if killed.load(Ordering::SeqCst) => Ok("%0\n".to_string()),
+                _ => Ok("%0\n%5\n".to_string()),
+            }
+        }));
+        let opts = SpawnOptions {
+            timeout: None,
+            ..default_opts()
+        };
+
+        let err = spawn_in_split_with(
+            &Tmux::new(tmux.clone()),
+            &FakeClock::new(),
+            &opts,
+            &AtomicBool::new(true),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SpawnError>(),
+            Some(&SpawnError::Interrupted)
+        );
+        assert!(tmux.calls().contains(&vec![
+            "kill-pane".to_string(),
+            "-t".to_string(),
+            "%5".to_string()
+        ]));
+    }
+
+    #[test]
+    fn test_spawn_timeout_ends_a_channel_wait() {
+        use crate::multiplexer::Tmux;
+        use crate::tmux::{FakeClock, FakeTmux};
+        use std::sync::Arc;
+
+        // wait-for returns once the pane is killed, as the wrapper signals
+        let killed = Arc::new(AtomicBool::new(false));
+        let tmux = Arc::new(FakeTmux::new({
+            let killed = killed.clone();
+            move |args| match args[0].as_str() {
+                "split-window" => Ok("%5\n".to_string()),
+                "wait-for" => {
+                    while !killed.load(Ordering::SeqCst) {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                    Ok(String::new())
+                }
+                "kill-pane" => {
+                    killed.store(true, Ordering::SeqCst);
+                    Ok(String::new())
+                }
+                _ => Ok("%0\n".to_string()),
+            }
+        }));
+        let timeout = Duration::from_millis(300);
+        let opts = SpawnOptions {
+            timeout: Some(timeout),
+            ..default_opts()
+        };
+
+        let err = spawn_in_split_with(
+            &Tmux::new(tmux),
+            &FakeClock::new(),
+            &opts,
+            &AtomicBool::new(false),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SpawnError>(),
+            Some(&SpawnError::TimedOut(timeout))
+        );
+    }
+
+    #[test]
+    fn test_spawn_fails_without_pane_id() {
+        use crate::multiplexer::Tmux;
+        use crate::tmux::{FakeClock, FakeTmux};
+        use std::sync::Arc;
+
+        let tmux = Arc::new(FakeTmux::new(|_| Ok(String::new())));
+        let clock = FakeClock::new();
+
+        assert!(
+            spawn_in_split_with(
+                &Tmux::new(tmux.clone()),
+                &clock,
+                &default_opts(),
+                &AtomicBool::new(false)
+            )
+            .is_err()
+        );
+        assert_eq!(tmux.calls().len(), 1, "no polling after a failed split");
+        assert_eq!(clock.sleeps(), 0);
     }
 }
@@ -1,8 +1,309 @@
 use anyhow::{Context, Result, bail};
-use git2::Repository;
-use std::path::Path;
+use git2::{ErrorCode, Index, ObjectType, Oid, Repository};
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::types::{FileDiff, Hunk, LineKind};
+use crate::diff;
+use crate::types::{DeltaStatus, FileDiff, Hunk, LineKind};
+
+/// Hashes of the working tree files of a diff, taken when it was computed.
+/// `None` records a file that didn't exist (deleted).
+pub type WorktreeSnapshot = HashMap<PathBuf, Option<Oid>>;
+
+/// Blob hash of the working tree file at `path`, or `None` if there is none.
+pub fn worktree_oid(repo: &Repository, path: &Path) -> Option<Oid> {
+    let full_path = repo.workdir()?.join(path);
+    if !full_path.is_file() {
+        return None;
+    }
+    Oid::hash_file(ObjectType::Blob, &full_path).ok()
+}
+
+/// Snapshot the working tree content of every file in `files`.
+pub fn snapshot_worktree(repo: &Repository, files: &[FileDiff]) -> WorktreeSnapshot {
+    files
+        .iter()
+        .map(|f| (f.path.clone(), worktree_oid(repo, &f.path)))
+        .collect()
+}
+
+/// Add the current hashes of `paths` to `snapshot`, e.g. changed files a
+/// `--files` filter left out of the review.
+pub fn extend_snapshot(repo: &Repository, snapshot: &mut WorktreeSnapshot, paths: &[PathBuf]) {
+    for path in paths {
+        if !snapshot.contains_key(path) {
+            snapshot.insert(path.clone(), worktree_oid(repo, path));
+        }
+    }
+}
+
+/// Whether the working tree differs from `snapshot`: a snapshotted file
+/// changed, or one of `changed_paths` (see [`crate::git::worktree_changes`])
+/// is not in the snapshot at all.
+pub fn worktree_changed(
+    repo: &Repository,
+    snapshot: &WorktreeSnapshot,
+    changed_paths: &[PathBuf],
+) -> bool {
+    changed_paths.iter().any(|p| !snapshot.contains_key(p))
+        || snapshot
+            .iter()
+            .any(|(path, oid)| *oid != worktree_oid(repo, path))
+}
+
+/// Fail if the working tree file at `path` no longer matches `snapshot`,
+/// i.e. the diff on screen is stale. Paths missing from the snapshot are
+/// not checked.
+pub fn check_unchanged(repo: &Repository, snapshot: &WorktreeSnapshot, path: &Path) -> Result<()> {
+    match snapshot.get(path) {
+        Some(expected) if *expected != worktree_oid(repo, path) => bail!(
+            "{} changed on disk since the diff was loaded; press r to refresh the review",
+            path.display()
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Replace the new side of `hunk` in the working tree file at `path` with
+/// `new_side`, e.g. to apply an edit made in the editor. Fails if the file
+/// no longer holds the hunk's new side where the diff put it.
+pub fn apply_to_worktree(
+    repo: &Repository,
+    path: &Path,
+    hunk: &Hunk,
+    new_side: &str,
+) -> Result<()> {
+    let workdir = repo.workdir().context("Bare repository not supported")?;
+    let full_path = workdir.join(path);
+    // Bytes, so that files that aren't UTF-8 keep their other lines as is
+    let content = std::fs::read(&full_path)
+        .with_context(|| format!("Failed to read {}", full_path.display()))?;
+
+    let lines: Vec<&[u8]> = split_lines(&content).collect();
+    let region: Vec<&[u8]> = hunk
+        .lines
+        .iter()
+        .filter(|l| l.kind != LineKind::Removed)
+        .map(|l| l.bytes())
+        .collect();
+    // A hunk with an empty new side names the line before it
+    let start = if hunk.new_lines == 0 {
+        hunk.new_start as usize
+    } else {
+        (hunk.new_start as usize).saturating_sub(1)
+    };
+    if lines.get(start..start + region.len()) != Some(&region[..]) {
+        bail!(
+            "{} no longer matches the diff; press r to refresh the review",
+            path.display()
+        );
+    }
+
+    let mut updated = lines[..start].concat();
+    updated.extend_from_slice(new_side.as_bytes());
+    updated.extend_from_slice(&lines[start + region.len()..].concat());
+    std::fs::write(&full_path, updated)
+        .with_context(|| format!("Failed to write {}", full_path.display()))
+}
+
+/// How long to wait before each retry of writing the index while another
+/// git process (an editor's git integration, say) holds `index.lock`:
+/// about 1.5s in all before giving up.
+pub const INDEX_LOCK_RETRY_DELAYS: [Duration; 5] = [
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(400),
+    Duration::from_millis(800),
+];
+
+/// Write `index` to disk, retrying with backoff while it is locked. If
+/// that fails, the changes are dropped from the repository's cached index
+/// too, so that staging the hunk again starts from what is on disk.
+pub(crate) fn write_index(index: &mut Index) -> Result<()> {
+    let mut delays = INDEX_LOCK_RETRY_DELAYS.iter();
+    let written = loop {
+        match index.write() {
+            Err(e) if e.code() == ErrorCode::Locked => match delays.next() {
+                Some(delay) => std::thread::sleep(*delay),
+                None => {
+                    break Err(anyhow::Error::new(e).context(format!(
+                        "The index is locked by another git process (index.lock still \
+                         exists after {} retries)",
+                        INDEX_LOCK_RETRY_DELAYS.len()
+                    )));
+                }
+            },
+            written => break written.context("Failed to write index"),
+        }
+    };
+    if written.is_err() {
+        let _ = index.read(true);
+    }
+    written
+}
+
+/// Stage a whole file as it is in the working tree (`git add <path>`), or
+/// remove it from the index if it was deleted (see [`stage_deletion`]).
+/// Used for binary files, which can't be reconstructed hunk by hunk.
+pub fn stage_file(repo: &Repository, file_diff: &FileDiff) -> Result<()> {
+    let file_path = &file_diff.path;
+    let mut index = repo.index().context("Failed to get repository index")?;
+
+    if file_diff.status == DeltaStatus::Deleted {
+        return stage_deletion(repo, file_path);
+    }
+
+    let file_path_str = file_path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("File path is not valid UTF-8: {:?}", file_path))?;
+    let workdir = repo.workdir().context("Bare repository not supported")?;
+    let full_path = workdir.join(file_path);
+    let metadata = std::fs::metadata(&full_path)
+        .with_context(|| format!("Failed to read metadata for {}", full_path.display()))?;
+    let blob_oid = repo
+        .blob_path(&full_path)
+        .with_context(|| format!("Failed to write blob for {}", full_path.display()))?;
+
+    let mut entry = index
+        .get_path(Path::new(file_path_str), 0)
+        .unwrap_or_else(|| git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id: blob_oid,
+            flags: 0,
+            flags_extended: 0,
+            path: file_path_str.as_bytes().to_vec(),
+        });
+    // Follow the exec bit both ways; symlinks and gitlinks keep their mode
+    if matches!(entry.mode, 0o100644 | 0o100755) {
+        entry.mode = if metadata.permissions().mode() & 0o111 != 0 {
+            0o100755
+        } else {
+            0o100644
+        };
+    }
+    entry.id = blob_oid;
+    entry.file_size = metadata.len() as u32;
+    // Staging the whole file resolves any intent-to-add entry
+    const GIT_IDXENTRY_INTENT_TO_ADD: u16 = 1 << 13;
+    entry.flags_extended &= !GIT_IDXENTRY_INTENT_TO_ADD;
+
+    index.add(&entry).context("Failed to update index entry")?;
+    write_index(&mut index)?;
+    Ok(())
+}
+
+/// Stage the deletion of `path` (`git rm --cached`): remove it from the
+/// index, leaving the working tree alone.
+pub fn stage_deletion(repo: &Repository, path: &Path) -> Result<()> {
+    let mut index = repo.index().context("Failed to get repository index")?;
+    index
+        .remove_path(path)
+        .with_context(|| format!("Failed to remove {} from index", path.display()))?;
+    write_index(&mut index)
+}
+
+/// Stage a submodule's new commit (the placeholder hunk from
+/// [`diff::submodule_hunk`]), or remove a deleted submodule from the index.
+/// Fails if the index no longer records the old commit.
+pub fn stage_submodule(repo: &Repository, file_diff: &FileDiff, hunk: &Hunk) -> Result<()> {
+    let path = &file_diff.path;
+    let (old, new) = diff::submodule_commits(hunk);
+    if let Some(m) = submodule_mismatch(repo, path, old)? {
+        bail!(
+            "Cannot stage submodule {}: the index {}; it was changed by another process. \
+             Refresh the diff and try again",
+            path.display(),
+            m
+        );
+    }
+
+    let mut index = repo.index().context("Failed to get repository index")?;
+    match new {
+        None => index
+            .remove_path(path)
+            .with_context(|| format!("Failed to remove {} from index", path.display()))?,
+        Some(id) => {
+            let path_str = path
+                .to_str()
+                .ok_or_else(|| anyhow::anyhow!("File path is not valid UTF-8: {:?}", path))?;
+            let mut entry = index.get_path(path, 0).unwrap_or_else(|| git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: 0,
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id,
+                flags: 0,
+                flags_extended: 0,
+                path: path_str.as_bytes().to_vec(),
+            });
+            entry.mode = 0o160000;
+            entry.id = id;
+            entry.file_size = 0;
+            index.add(&entry).context("Failed to update index entry")?;
+        }
+    }
+    write_index(&mut index)?;
+    Ok(())
+}
+
+/// Stage just the mode change of a file (its [`diff::mode_hunk`]), leaving
+/// the staged content alone. Fails if the index no longer has the old mode.
+pub fn stage_mode(repo: &Repository, file_diff: &FileDiff) -> Result<()> {
+    let path = &file_diff.path;
+    let Some((old, new)) = file_diff.mode_change else {
+        bail!("{} has no mode change", path.display());
+    };
+    let mut index = repo.index().context("Failed to get repository index")?;
+    let mut entry = index
+        .get_path(path, 0)
+        .with_context(|| format!("{} is not in the index", path.display()))?;
+    if entry.mode != old {
+        bail!(
+            "Cannot stage the mode of {}: the index has mode {:o} instead of {:o}; \
+             it was changed by another process. Refresh the diff and try again",
+            path.display(),
+            entry.mode,
+            old
+        );
+    }
+    entry.mode = new;
+    index.add(&entry).context("Failed to update index entry")?;
+    write_index(&mut index)?;
+    Ok(())
+}
+
+/// How the index entry for submodule `path` differs from the `expected`
+/// commit, if it does.
+fn submodule_mismatch(
+    repo: &Repository,
+    path: &Path,
+    expected: Option<Oid>,
+) -> Result<Option<String>> {
+    let index = repo.index().context("Failed to get index")?;
+    let current = index.get_path(path, 0).map(|e| e.id);
+    Ok((current != expected).then(|| {
+        let describe = |id: Option<Oid>| id.map_or("no commit".to_string(), |id| id.to_string());
+        format!(
+            "has {} instead of {}",
+            describe(current),
+            describe(expected)
+        )
+    }))
+}
 
 /// Stage a single hunk by reconstructing the blob content in the index.
 ///
@@ -24,19 +325,39 @@ pub fn stage_hunk(
     hunk: &Hunk,
     line_offset: i32,
 ) -> Result<()> {
+    // Binary files have a single placeholder hunk and are staged whole
+    if file_diff.is_binary {
+        return stage_file(repo, file_diff);
+    }
+    if file_diff.is_submodule {
+        return stage_submodule(repo, file_diff, hunk);
+    }
+    if diff::is_mode_hunk(hunk) {
+        return stage_mode(repo, file_diff);
+    }
+
     let file_path = &file_diff.path;
     let mut index = repo.index().context("Failed to get repository index")?;
 
     // Read current index content (what's already staged or HEAD content)
     let old_content = get_index_content(repo, file_path)?;
 
+    // Another process (or an earlier stage) may have changed the index
+    // since the diff was computed
+    verify_old_lines(&old_content, hunk, line_offset)
+        .with_context(|| format!("Cannot stage hunk in {}", file_path.display()))?;
+
     // Reconstruct content with this hunk applied (adjusting for offset)
     let new_content = reconstruct_blob(&old_content, hunk, line_offset)?;
 
+    // The last of a deleted file's lines: stage the deletion itself, not
+    // an empty file
+    if file_diff.status == DeltaStatus::Deleted && new_content.is_empty() {
+        return stage_deletion(repo, file_path);
+    }
+
     // Write the new blob
-    let blob_oid = repo
-        .blob(new_content.as_bytes())
-        .context("Failed to write blob")?;
+    let blob_oid = repo.blob(&new_content).context("Failed to write blob")?;
 
     // Create/update the index entry
     let file_path_str = file_path
@@ -79,14 +400,14 @@ pub fn stage_hunk(
     entry.flags_extended &= !GIT_IDXENTRY_INTENT_TO_ADD;
 
     index.add(&entry).context("Failed to update index entry")?;
-    index.write().context("Failed to write index")?;
+    write_index(&mut index)?;
 
     Ok(())
 }
 
 /// Read the current content of a file from the index/HEAD.
 /// Returns empty string for untracked/new files.
-fn get_index_content(repo: &Repository, path: &Path) -> Result<String> {
+fn get_index_content(repo: &Repository, path: &Path) -> Result<Vec<u8>> {
     let index = repo.index().context("Failed to get index")?;
     let path_str = path
         .to_str()
@@ -102,9 +423,7 @@ fn get_index_content(repo: &Repository, path: &Path) -> Result<String> {
                 path
             );
         }
-        let content = String::from_utf8(blob.content().to_vec())
-            .with_context(|| format!("File is not valid UTF-8: {:?}", path))?;
-        Ok(content)
+        Ok(blob.content().to_vec())
     } else {
         // Try HEAD tree
         if let Ok(head) = repo.head()
@@ -121,35 +440,157 @@ fn get_index_content(repo: &Repository, path: &Path) -> Result<String> {
                         path
                     );
                 }
-                return String::from_utf8(blob.content().to_vec())
-                    .with_context(|| format!("File is not valid UTF-8: {:?}", path));
+                return Ok(blob.content().to_vec());
             }
         }
         // New file - return empty
-        Ok(String::new())
+        Ok(Vec::new())
     }
 }
 
+/// Check that the context and removed lines of `hunk` are present in
+/// `original` where the hunk applies (`old_start` shifted by
+/// `line_offset`), i.e. that the hunk was computed from this content.
+/// Staging a hunk against content it wasn't computed from would silently
+/// write the wrong blob.
+pub fn verify_old_lines(original: &[u8], hunk: &Hunk, line_offset: i32) -> Result<()> {
+    if let Some(m) = old_line_mismatch(original, hunk, line_offset) {
+        bail!(
+            "the index no longer matches the diff at {}; \
+             it was changed by another process. Refresh the diff and try again",
+            m
+        );
+    }
+    Ok(())
+}
+
+/// Check that every hunk of a patch (`--patch --apply`) applies to the
+/// index, before any of them is staged.
+pub fn verify_patch_applies(repo: &Repository, files: &[FileDiff]) -> Result<()> {
+    for (file, hunk) in files
+        .iter()
+        .filter(|f| f.is_submodule)
+        .flat_map(|f| f.hunks.iter().map(move |h| (f, h)))
+    {
+        let (old, _) = diff::submodule_commits(hunk);
+        if let Some(m) = submodule_mismatch(repo, &file.path, old)? {
+            bail!(
+                "submodule {} does not apply to the index: the index {}",
+                file.path.display(),
+                m
+            );
+        }
+    }
+    for file in files.iter().filter(|f| f.mode_change.is_some()) {
+        let index = repo.index().context("Failed to get index")?;
+        let mode = index.get_path(&file.path, 0).map(|e| e.mode);
+        if mode != file.mode_change.map(|(old, _)| old) {
+            bail!(
+                "{} mode change does not apply to the index: it has mode {}",
+                file.path.display(),
+                mode.map_or("none".to_string(), |m| format!("{:o}", m))
+            );
+        }
+    }
+    for file in files.iter().filter(|f| !f.is_whole_file()) {
+        let content = get_index_content(repo, &file.path)?;
+        for hunk in file.hunks.iter().filter(|h| !diff::is_mode_hunk(h)) {
+            if let Some(m) = old_line_mismatch(&content, hunk, 0) {
+                bail!(
+                    "{} {} does not apply to the index: {}",
+                    file.path.display(),
+                    hunk.header,
+                    m
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Where `original` differs from the old side of `hunk`, described as
+/// `line N (expected .., found ..)`.
+///
+/// Context lines are compared ignoring whitespace: a diff made with
+/// `--ignore-whitespace` shows the worktree's version of them.
+fn old_line_mismatch(original: &[u8], hunk: &Hunk, line_offset: i32) -> Option<String> {
+    let orig_lines: Vec<&[u8]> = split_lines(original).map(trim_eol).collect();
+    let start = (hunk.old_start as i32 + line_offset).max(1) as usize - 1;
+    let old_lines = hunk.lines.iter().filter(|l| l.kind != LineKind::Added);
+    for (i, line) in old_lines.enumerate() {
+        let expected = trim_eol(line.bytes());
+        let found = orig_lines.get(start + i).copied();
+        let matches = match found {
+            Some(found) if line.kind == LineKind::Context => {
+                without_whitespace(found).eq(without_whitespace(expected))
+            }
+            found => found == Some(expected),
+        };
+        if !matches {
+            return Some(format!(
+                "line {} (expected {:?}, found {:?})",
+                start + i + 1,
+                String::from_utf8_lossy(expected),
+                found.map_or("end of file".into(), String::from_utf8_lossy)
+            ));
+        }
+    }
+    None
+}
+
+/// Lines of `content`, each with its terminator.
+fn split_lines(content: &[u8]) -> impl Iterator<Item = &[u8]> {
+    content.split_inclusive(|&b| b == b'\n')
+}
+
+/// The bytes of `line` that aren't ASCII whitespace.
+fn without_whitespace(line: &[u8]) -> impl Iterator<Item = &u8> {
+    line.iter().filter(|b| !b.is_ascii_whitespace())
+}
+
+/// `line` without its `\n` or `\r\n`.
+fn trim_eol(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
 /// Reconstruct file content with a single hunk applied.
 ///
 /// `line_offset` adjusts `old_start` to account for line count changes
 /// from previously staged hunks in the same file.
 ///
-/// This walks the original file line-by-line. When we reach the hunk's
+/// This walks the original file line-by-line, as bytes so files that
+/// aren't UTF-8 are staged unchanged. When we reach the hunk's
 /// target range, we apply the changes (keep context, add '+' lines, skip '-' lines).
+/// Context lines are taken from `original` rather than the hunk, so a
+/// diff that ignored whitespace doesn't stage the worktree's whitespace.
 /// Outside the hunk range, we keep original content unchanged.
-pub fn reconstruct_blob(original: &str, hunk: &Hunk, line_offset: i32) -> Result<String> {
-    let orig_lines: Vec<&str> = if original.is_empty() {
-        Vec::new()
-    } else {
-        original.lines().collect()
-    };
+///
+/// ```
+/// let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -2,1 +2,2 @@\n b\n+c\n";
+/// let hunk = &stagent::patch::parse_unified_diff(diff)?[0].hunks[0];
+/// assert_eq!(stagent::staging::reconstruct_blob(b"a\nb\n", hunk, 0)?, b"a\nb\nc\n");
+/// // One line inserted above the hunk by an earlier staged hunk
+/// assert_eq!(
+///     stagent::staging::reconstruct_blob(b"x\na\nb\n", hunk, 1)?,
+///     b"x\na\nb\nc\n"
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn reconstruct_blob(original: &[u8], hunk: &Hunk, line_offset: i32) -> Result<Vec<u8>> {
+    // Lines keep their own terminators so CRLF files stay CRLF
+    let orig_lines: Vec<&[u8]> = split_lines(original).collect();
+    let eol = dominant_eol(original);
 
-    let mut result = Vec::new();
+    // Each line and whether it may end the file without a terminator: lines
+    // kept from the original as they are, or marked so by the diff
+    let mut result: Vec<(&[u8], bool)> = Vec::new();
     let adjusted_start = (hunk.old_start as i32 + line_offset).max(0) as usize;
-    // old_start is 1-based, convert to 0-based index
-    let hunk_start_idx = if adjusted_start == 0 {
-        0
+    // old_start is 1-based, convert to 0-based index. A hunk without old
+    // lines (an insertion with no context, `-U0`) names the line it
+    // follows instead.
+    let hunk_start_idx = if adjusted_start == 0 || hunk.old_lines == 0 {
+        adjusted_start
     } else {
         adjusted_start - 1
     };
@@ -159,19 +600,26 @@ pub fn reconstruct_blob(original: &str, hunk: &Hunk, line_offset: i32) -> Result
 
     // Copy lines before the hunk
     for line in orig_lines.iter().take(hunk_start_idx) {
-        result.push(line.to_string());
+        result.push((line, true));
     }
 
     // Apply hunk lines
+    let mut old_idx = hunk_start_idx;
     for diff_line in &hunk.lines {
         match diff_line.kind {
-            LineKind::Context | LineKind::Added => {
-                // Trim trailing newline if present (we re-add with join)
-                let content = diff_line.content.trim_end_matches('\n');
-                result.push(content.to_string());
+            LineKind::Context => {
+                result.push(match orig_lines.get(old_idx) {
+                    Some(line) => (line, true),
+                    None => (diff_line.bytes(), diff_line.no_newline),
+                });
+                old_idx += 1;
+            }
+            LineKind::Added => {
+                result.push((diff_line.bytes(), diff_line.no_newline));
             }
             LineKind::Removed => {
                 // Skip removed lines - they are consumed from original
+                old_idx += 1;
             }
         }
     }
@@ -179,14 +627,88 @@ pub fn reconstruct_blob(original: &str, hunk: &Hunk, line_offset: i32) -> Result
     // Copy lines after the hunk
     let after_hunk_idx = hunk_start_idx + hunk_old_line_count;
     for line in orig_lines.iter().skip(after_hunk_idx) {
-        result.push(line.to_string());
+        result.push((line, true));
     }
 
-    // Preserve trailing newline if original had one
-    let mut output = result.join("\n");
-    if original.ends_with('\n') || original.is_empty() {
-        output.push('\n');
+    // Lines missing a terminator get the file's usual one, unless they may
+    // end the file and do
+    let mut output = Vec::new();
+    let last = result.len().saturating_sub(1);
+    for (i, (line, may_end_file)) in result.into_iter().enumerate() {
+        output.extend_from_slice(line);
+        if !(line.ends_with(b"\n") || may_end_file && i == last) {
+            output.extend_from_slice(eol);
+        }
     }
 
     Ok(output)
 }
+
+/// `"\r\n"` if most lines of `content` end that way, otherwise `"\n"`.
+fn dominant_eol(content: &[u8]) -> &'static [u8] {
+    let crlf = split_lines(content)
+        .filter(|l| l.ends_with(b"\r\n"))
+        .count();
+    let lf = split_lines(content).filter(|l| l.ends_with(b"\n")).count();
+    if crlf > lf - crlf { b"\r\n" } else { b"\n" }
+}
+
+/// A hunk that could not be staged: the error with all its causes and
+/// what to do about it, for the error panel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageFailure {
+    pub path: PathBuf,
+    pub hunk_header: String,
+    /// The error and its causes, outermost first.
+    pub causes: Vec<String>,
+    /// Suggested ways past the error (see [`remedies`]).
+    pub remedies: Vec<&'static str>,
+}
+
+impl StageFailure {
+    pub fn new(path: &Path, hunk_header: &str, err: &anyhow::Error) -> Self {
+        let causes: Vec<String> = err.chain().map(|cause| cause.to_string()).collect();
+        Self {
+            path: path.to_path_buf(),
+            hunk_header: hunk_header.to_string(),
+            remedies: remedies(&causes),
+            causes,
+        }
+    }
+}
+
+/// Suggestions for getting past a staging error, from the text of its
+/// causes. Errors stagent doesn't recognize get none.
+pub fn remedies(causes: &[String]) -> Vec<&'static str> {
+    let text = causes.join("\n").to_lowercase();
+    let mut remedies = Vec::new();
+    if text.contains("index.lock") || text.contains("locked") {
+        remedies.push(
+            "Another git process holds the index lock. Wait for it to finish; if no git \
+             command is running, delete .git/index.lock",
+        );
+    }
+    if text.contains("changed on disk")
+        || text.contains("no longer matches")
+        || text.contains("changed by another process")
+        || text.contains("does not apply to the index")
+    {
+        remedies.push(
+            "The file or the index changed since the diff was loaded: press r to refresh \
+             the review, then stage the hunk again",
+        );
+    }
+    if text.contains("binary") {
+        remedies.push(
+            "Binary content can't be staged hunk by hunk: stage the whole file with \
+             `git add <path>`",
+        );
+    }
+    if text.contains("not valid utf-8") {
+        remedies.push("stagent can only stage UTF-8 paths: use `git add -p` for this file");
+    }
+    if text.contains("permission denied") {
+        remedies.push("Check that you can write to the repository's .git directory");
+    }
+    remedies
+}
@@ -0,0 +1,187 @@
+//! Review progress statistics for the status bar, the `i` panel and the
+//! `--summary` line.
+
+use crate::types::{FileDiff, HunkStatus, LineKind};
+
+/// Counts over every file in a review.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReviewStats {
+    pub files: usize,
+    pub hunks: usize,
+    /// Added lines over all hunks.
+    pub added: usize,
+    /// Removed lines over all hunks.
+    pub removed: usize,
+    pub pending: usize,
+    pub staged: usize,
+    pub skipped: usize,
+    pub edited: usize,
+    pub commented: usize,
+}
+
+impl ReviewStats {
+    /// Number of hunks that are no longer pending.
+    pub fn reviewed(&self) -> usize {
+        self.hunks - self.pending
+    }
+
+    /// Reviewed hunks as a whole percentage; 100 for an empty review.
+    pub fn percent_reviewed(&self) -> usize {
+        (self.reviewed() * 100)
+            .checked_div(self.hunks)
+            .unwrap_or(100)
+    }
+}
+
+/// Start of the `--summary` line, for scripts to find it among other output.
+pub const SUMMARY_PREFIX: &str = "STAGENT_RESULT: ";
+
+/// The `--summary` line for a finished review: [`SUMMARY_PREFIX`] followed
+/// by a JSON object with the hunk counts per status and the number of
+/// feedback items, e.g.
+/// `STAGENT_RESULT: {"files":2,"hunks":5,"staged":3,"skipped":1,...}`.
+pub fn summary_line(stats: &ReviewStats, feedback: usize) -> String {
+    let summary = serde_json::json!({
+        "files": stats.files,
+        "hunks": stats.hunks,
+        "staged": stats.staged,
+        "skipped": stats.skipped,
+        "edited": stats.edited,
+        "commented": stats.commented,
+        "pending": stats.pending,
+        "feedback": feedback,
+    });
+    format!("{}{}", SUMMARY_PREFIX, summary)
+}
+
+/// Compute statistics for `files`.
+pub fn compute(files: &[FileDiff]) -> ReviewStats {
+    let mut stats = ReviewStats {
+        files: files.len(),
+        ..ReviewStats::default()
+    };
+    for hunk in files.iter().flat_map(|f| &f.hunks) {
+        stats.hunks += 1;
+        match hunk.status {
+            HunkStatus::Pending => stats.pending += 1,
+            HunkStatus::Staged => stats.staged += 1,
+            HunkStatus::Skipped => stats.skipped += 1,
+            HunkStatus::Edited => stats.edited += 1,
+            HunkStatus::Commented => stats.commented += 1,
+        }
+        // Without unpacking the lines of large files
+        for (kind, _) in hunk.lines.contents() {
+            match kind {
+                LineKind::Added => stats.added += 1,
+                LineKind::Removed => stats.removed += 1,
+                LineKind::Context => {}
+            }
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DeltaStatus, DiffLine, Hunk};
+
+    fn hunk(status: HunkStatus, kinds: &[LineKind]) -> Hunk {
+        Hunk {
+            header: "@@ -1 +1 @@".to_string(),
+            lines: kinds
+                .iter()
+                .map(|&kind| DiffLine {
+                    kind,
+                    content: "x\n".to_string(),
+                    old_lineno: None,
+                    new_lineno: None,
+                    raw: None,
+                    no_newline: false,
+                })
+                .collect(),
+            status,
+            old_start: 1,
+            old_lines: 1,
+            new_start: 1,
+            new_lines: 1,
+        }
+    }
+
+    fn file(path: &str, hunks: Vec<Hunk>) -> FileDiff {
+        FileDiff {
+            path: path.into(),
+            hunks,
+            status: DeltaStatus::Modified,
+            is_binary: false,
+            is_submodule: false,
+            mode_change: None,
+        }
+    }
+
+    #[test]
+    fn test_compute_counts_hunks_and_lines() {
+        use LineKind::*;
+        let files = vec![
+            file(
+                "a.rs",
+                vec![
+                    hunk(HunkStatus::Staged, &[Context, Removed, Added, Added]),
+                    hunk(HunkStatus::Pending, &[Added]),
+                ],
+            ),
+            file(
+                "b.rs",
+                vec![
+                    hunk(HunkStatus::Skipped, &[Removed]),
+                    hunk(HunkStatus::Commented, &[Context, Added]),
+                    hunk(HunkStatus::Edited, &[Removed, Added]),
+                ],
+            ),
+        ];
+        let stats = compute(&files);
+        assert_eq!(
+            stats,
+            ReviewStats {
+                files: 2,
+                hunks: 5,
+                added: 5,
+                removed: 3,
+                pending: 1,
+                staged: 1,
+                skipped: 1,
+                edited: 1,
+                commented: 1,
+            }
+        );
+        assert_eq!(stats.reviewed(), 4);
+        assert_eq!(stats.percent_reviewed(), 80);
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let stats = ReviewStats {
+            files: 2,
+            hunks: 4,
+            staged: 2,
+            skipped: 1,
+            pending: 1,
+            ..ReviewStats::default()
+        };
+        let line = summary_line(&stats, 3);
+        let json: serde_json::Value =
+            serde_json::from_str(line.strip_prefix(SUMMARY_PREFIX).unwrap()).unwrap();
+        assert_eq!(json["staged"], 2);
+        assert_eq!(json["skipped"], 1);
+        assert_eq!(json["pending"], 1);
+        assert_eq!(json["commented"], 0);
+        assert_eq!(json["feedback"], 3);
+    }
+
+    #[test]
+    fn test_empty_review_is_complete() {
+        let stats = compute(&[]);
+        assert_eq!(stats.hunks, 0);
+        assert_eq!(stats.percent_reviewed(), 100);
+    }
+}
@@ -0,0 +1,216 @@
+//! Review time tracking (`--track-time`): the clock runs for whichever hunk
+//! is selected, and the time per hunk and file is summarized in the
+//! feedback output.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// A hunk by file path and `@@` header, which stay the same while the
+/// hunk is staged or skipped.
+type HunkKey = (String, String);
+
+/// Time spent on each hunk, charged to the selected hunk until the
+/// selection changes.
+#[derive(Debug, Default)]
+pub struct ReviewTimer {
+    /// The hunk the clock is running for, and since when.
+    current: Option<(HunkKey, Instant)>,
+    /// Time spent per hunk, in the order they were first selected.
+    spent: Vec<(HunkKey, Duration)>,
+}
+
+impl ReviewTimer {
+    /// Run the clock for `hunk` (file path, hunk header) from `now`, or
+    /// pause it with `None`. Selecting the same hunk again keeps it running.
+    pub fn track(&mut self, hunk: Option<(&str, &str)>, now: Instant) {
+        let same = match (&self.current, hunk) {
+            (Some(((path, header), _)), Some((p, h))) => path == p && header == h,
+            (None, None) => true,
+            _ => false,
+        };
+        if same {
+            return;
+        }
+        if let Some((key, since)) = self.current.take() {
+            self.charge(key, now.saturating_duration_since(since));
+        }
+        self.current = hunk.map(|(path, header)| ((path.to_string(), header.to_string()), now));
+    }
+
+    fn charge(&mut self, key: HunkKey, elapsed: Duration) {
+        match self.spent.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, spent)) => *spent += elapsed,
+            None => self.spent.push((key, elapsed)),
+        }
+    }
+
+    /// The time spent so far, counting the running hunk up to `now`.
+    pub fn timing(&self, now: Instant) -> ReviewTiming {
+        let mut spent = self.spent.clone();
+        if let Some((key, since)) = &self.current {
+            let elapsed = now.saturating_duration_since(*since);
+            match spent.iter_mut().find(|(k, _)| k == key) {
+                Some((_, d)) => *d += elapsed,
+                None => spent.push((key.clone(), elapsed)),
+            }
+        }
+
+        let mut by_file: BTreeMap<String, Vec<(String, Duration)>> = BTreeMap::new();
+        for ((path, header), elapsed) in spent {
+            by_file.entry(path).or_default().push((header, elapsed));
+        }
+        let files: Vec<FileTiming> = by_file
+            .into_iter()
+            .map(|(file_path, hunks)| FileTiming {
+                seconds: seconds(hunks.iter().map(|(_, d)| *d).sum()),
+                file_path,
+                hunks: hunks
+                    .into_iter()
+                    .map(|(hunk_header, elapsed)| HunkTiming {
+                        hunk_header,
+                        seconds: seconds(elapsed),
+                    })
+                    .collect(),
+            })
+            .collect();
+        ReviewTiming {
+            total_seconds: seconds(self.total(now)),
+            files,
+        }
+    }
+
+    fn total(&self, now: Instant) -> Duration {
+        let running = self.current.as_ref().map_or(Duration::ZERO, |(_, since)| {
+            now.saturating_duration_since(*since)
+        });
+        self.spent.iter().map(|(_, d)| *d).sum::<Duration>() + running
+    }
+}
+
+/// Whole seconds, rounded to the nearest.
+fn seconds(duration: Duration) -> u64 {
+    duration.as_secs_f64().round() as u64
+}
+
+/// The time a review took, per file (by path) and hunk.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReviewTiming {
+    pub total_seconds: u64,
+    pub files: Vec<FileTiming>,
+}
+
+/// The time spent on a file's hunks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileTiming {
+    pub file_path: String,
+    pub seconds: u64,
+    pub hunks: Vec<HunkTiming>,
+}
+
+/// The time a hunk was selected for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HunkTiming {
+    pub hunk_header: String,
+    pub seconds: u64,
+}
+
+/// A duration for people: `45s`, `3m 05s`, `1h 02m 03s`.
+pub fn format_seconds(seconds: u64) -> String {
+    let (hours, minutes, secs) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, secs)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, secs)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// The timing header of diff output:
+///
+/// ```text
+/// # REVIEW TIME: 3m 12s total
+/// # REVIEW TIME: 2m 01s src/lib.rs
+/// # REVIEW TIME: 45s src/lib.rs @@ -1,3 +1,4 @@
+/// ```
+pub fn format_timing(timing: &ReviewTiming) -> String {
+    let mut output = format!(
+        "# REVIEW TIME: {} total\n",
+        format_seconds(timing.total_seconds)
+    );
+    for file in &timing.files {
+        output.push_str(&format!(
+            "# REVIEW TIME: {} {}\n",
+            format_seconds(file.seconds),
+            file.file_path
+        ));
+        for hunk in &file.hunks {
+            output.push_str(&format!(
+                "# REVIEW TIME: {} {} {}\n",
+                format_seconds(hunk.seconds),
+                file.file_path,
+                hunk.hunk_header
+            ));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_charges_the_selected_hunk() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut timer = ReviewTimer::default();
+
+        timer.track(Some(("b.rs", "@@ -1 +1 @@")), at(0));
+        timer.track(Some(("b.rs", "@@ -1 +1 @@")), at(5));
+        timer.track(Some(("a.rs", "@@ -3 +3 @@")), at(10));
+        // Paused, e.g. in the help
+        timer.track(None, at(20));
+        timer.track(Some(("b.rs", "@@ -1 +1 @@")), at(100));
+        timer.track(Some(("b.rs", "@@ -9 +9 @@")), at(102));
+
+        let timing = timer.timing(at(105));
+        assert_eq!(timing.total_seconds, 25);
+        assert_eq!(timing.files.len(), 2);
+        assert_eq!(timing.files[0].file_path, "a.rs");
+        assert_eq!(timing.files[0].seconds, 10);
+        assert_eq!(timing.files[1].seconds, 15);
+        let hunks: Vec<_> = timing.files[1]
+            .hunks
+            .iter()
+            .map(|h| (h.hunk_header.as_str(), h.seconds))
+            .collect();
+        assert_eq!(hunks, vec![("@@ -1 +1 @@", 12), ("@@ -9 +9 @@", 3)]);
+    }
+
+    #[test]
+    fn test_format_timing() {
+        assert_eq!(format_seconds(45), "45s");
+        assert_eq!(format_seconds(185), "3m 05s");
+        assert_eq!(format_seconds(3723), "1h 02m 03s");
+
+        let timing = ReviewTiming {
+            total_seconds: 70,
+            files: vec![FileTiming {
+                file_path: "src/lib.rs".to_string(),
+                seconds: 70,
+                hunks: vec![HunkTiming {
+                    hunk_header: "@@ -1 +1 @@".to_string(),
+                    seconds: 70,
+                }],
+            }],
+        };
+        assert_eq!(
+            format_timing(&timing),
+            "# REVIEW TIME: 1m 10s total\n\
+             # REVIEW TIME: 1m 10s src/lib.rs\n\
+             # REVIEW TIME: 1m 10s src/lib.rs @@ -1 +1 @@\n"
+        );
+    }
+}
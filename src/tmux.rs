@@ -0,0 +1,125 @@
+//! Abstractions over tmux invocation and time, so the pane polling in
+//! `editor.rs` and `spawn.rs` can be tested without a tmux server or real
+//! sleeps.
+//!
+//! Production code uses [`SystemTmux`] and [`SystemClock`]. Tests use
+//! [`FakeTmux`], which answers commands from a closure and records them, and
+//! [`FakeClock`], which only advances virtual time when slept on.
+
+use anyhow::{Context, Result, bail};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Runs tmux commands.
+pub trait TmuxRunner: Send + Sync {
+    /// Run `tmux <args>` and return its stdout. Fails if tmux cannot be run
+    /// or exits unsuccessfully.
+    fn run(&self, args: &[String]) -> Result<String>;
+}
+
+/// Source of time for poll loops.
+pub trait Clock: Send + Sync {
+    /// Block for `duration`.
+    fn sleep(&self, duration: Duration);
+}
+
+/// Runs the real `tmux` binary.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemTmux;
+
+impl TmuxRunner for SystemTmux {
+    fn run(&self, args: &[String]) -> Result<String> {
+        let output = std::process::Command::new("tmux")
+            .args(args)
+            .output()
+            .with_context(|| {
+                format!(
+                    "Failed to run tmux {}",
+                    args.first().map(String::as_str).unwrap_or("")
+                )
+            })?;
+        if !output.status.success() {
+            bail!(
+                "tmux {} failed: {}",
+                args.first().map(String::as_str).unwrap_or(""),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+/// Real wall-clock sleeps.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// The default (real) tmux runner and clock.
+pub fn system() -> (Arc<dyn TmuxRunner>, Arc<dyn Clock>) {
+    (Arc::new(SystemTmux), Arc::new(SystemClock))
+}
+
+type Responder = dyn Fn(&[String]) -> Result<String> + Send + Sync;
+
+/// Scripted tmux for tests: every call is recorded and answered by a closure.
+pub struct FakeTmux {
+    responder: Box<Responder>,
+    calls: Mutex<Vec<Vec<String>>>,
+}
+
+impl FakeTmux {
+    pub fn new(responder: impl Fn(&[String]) -> Result<String> + Send + Sync + 'static) -> Self {
+        Self {
+            responder: Box::new(responder),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Every command run so far, in order.
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl TmuxRunner for FakeTmux {
+    fn run(&self, args: &[String]) -> Result<String> {
+        self.calls.lock().unwrap().push(args.to_vec());
+        (self.responder)(args)
+    }
+}
+
+/// Virtual clock for tests: `sleep` returns immediately and adds to the
+/// elapsed time.
+#[derive(Debug, Default)]
+pub struct FakeClock {
+    elapsed: Mutex<Duration>,
+    sleeps: Mutex<usize>,
+}
+
+impl FakeClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total virtual time slept.
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+
+    /// Number of `sleep` calls.
+    pub fn sleeps(&self) -> usize {
+        *self.sleeps.lock().unwrap()
+    }
+}
+
+impl Clock for FakeClock {
+    fn sleep(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+        *self.sleeps.lock().unwrap() += 1;
+    }
+}
@@ -1,16 +1,70 @@
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
 /// Represents a file with unstaged changes and its collection of diff hunks.
-#[derive(Debug, Clone)]
+///
+/// Serializes to the JSON printed by `stagent --dump-json`.
+#[derive(Debug, Clone, Serialize)]
 pub struct FileDiff {
     pub path: PathBuf,
     pub hunks: Vec<Hunk>,
     pub status: DeltaStatus,
     pub is_binary: bool,
+    /// A submodule whose commit changed; its single hunk shows the old and
+    /// new commits (see [`crate::diff::submodule_hunk`]).
+    pub is_submodule: bool,
+    /// Old and new mode (`0o100644`/`0o100755`) when the executable bit
+    /// flipped. The change is the file's first hunk (see
+    /// [`crate::diff::mode_hunk`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode_change: Option<(u32, u32)>,
+}
+
+impl FileDiff {
+    /// Binary files and submodules have a single placeholder hunk and are
+    /// staged or skipped as a whole.
+    pub fn is_whole_file(&self) -> bool {
+        self.is_binary || self.is_submodule
+    }
+
+    /// A text file with no previous version (added or untracked), shown
+    /// as a preview of its content rather than as a diff.
+    pub fn is_new_file(&self) -> bool {
+        matches!(self.status, DeltaStatus::Added | DeltaStatus::Untracked)
+            && !self.is_whole_file()
+            && self.mode_change.is_none()
+    }
+
+    /// A text file removed from the working tree, shown as a preview of
+    /// the content that goes.
+    pub fn is_deleted_file(&self) -> bool {
+        self.status == DeltaStatus::Deleted && !self.is_whole_file() && self.mode_change.is_none()
+    }
+
+    /// What hunk `hunk_idx` stands for if it is a placeholder without
+    /// reviewable lines (a binary file, submodule or mode change), for
+    /// messages about what can't be done with it.
+    pub fn placeholder_noun(&self, hunk_idx: usize) -> Option<&'static str> {
+        if self.is_submodule {
+            Some("submodule")
+        } else if self.is_binary {
+            Some("binary file")
+        } else if self
+            .hunks
+            .get(hunk_idx)
+            .is_some_and(crate::diff::is_mode_hunk)
+        {
+            Some("mode change")
+        } else {
+            None
+        }
+    }
 }
 
 /// Maps to git2 Delta variants we care about.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DeltaStatus {
     Modified,
     Added,
@@ -20,11 +74,11 @@ pub enum DeltaStatus {
 }
 
 /// A single diff hunk with header, lines, and review status.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Hunk {
     /// The @@ header line, e.g. "@@ -10,5 +10,7 @@ fn foo()"
     pub header: String,
-    pub lines: Vec<DiffLine>,
+    pub lines: HunkLines,
     pub status: HunkStatus,
     /// Old file start line (from the @@ header)
     pub old_start: u32,
@@ -38,16 +92,262 @@ pub struct Hunk {
 }
 
 /// A single line within a diff hunk.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiffLine {
     pub kind: LineKind,
     pub content: String,
     pub old_lineno: Option<u32>,
     pub new_lineno: Option<u32>,
+    /// The line's bytes when they aren't valid UTF-8. `content` then holds
+    /// a lossy copy for display, and staging uses these.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<Vec<u8>>,
+    /// The line is the last of its file and has no newline
+    /// (`\ No newline at end of file`): of the old file for a removed
+    /// line, the new one for an added line, both for context. Its content
+    /// then has no `\n` either.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_newline: bool,
+}
+
+impl DiffLine {
+    /// The line as it is in the file.
+    pub fn bytes(&self) -> &[u8] {
+        self.raw.as_deref().unwrap_or(self.content.as_bytes())
+    }
+
+    /// Record that the line has no newline at the end of its file, for a
+    /// `\ No newline at end of file` marker after it.
+    pub fn mark_no_newline(&mut self) {
+        self.no_newline = true;
+        if self.content.ends_with('\n') {
+            self.content.pop();
+        }
+        if let Some(raw) = &mut self.raw
+            && raw.ends_with(b"\n")
+        {
+            raw.pop();
+        }
+    }
+}
+
+/// The lines of a [`Hunk`], read and changed like a `Vec<DiffLine>`.
+///
+/// The lines of a large file off screen can be packed into one buffer
+/// with [`pack`](Self::pack), which costs a fraction of the memory of
+/// separate lines. They are unpacked again when they are next read, and
+/// stay packed as well until they are changed, so packing them again is
+/// free.
+#[derive(Clone)]
+pub enum HunkLines {
+    Unpacked(Vec<DiffLine>),
+    Packed {
+        packed: PackedLines,
+        /// The lines, once read since packing.
+        unpacked: OnceLock<Vec<DiffLine>>,
+    },
+}
+
+impl HunkLines {
+    /// Pack the lines into one buffer, dropping the separate ones.
+    pub fn pack(&mut self) {
+        match self {
+            HunkLines::Unpacked(lines) => {
+                *self = HunkLines::Packed {
+                    packed: PackedLines::new(lines),
+                    unpacked: OnceLock::new(),
+                };
+            }
+            HunkLines::Packed { unpacked, .. } => {
+                unpacked.take();
+            }
+        }
+    }
+
+    /// Whether the lines are packed and haven't been read since.
+    pub fn is_packed(&self) -> bool {
+        matches!(self, HunkLines::Packed { unpacked, .. } if unpacked.get().is_none())
+    }
+
+    /// Number of lines, without unpacking them.
+    pub fn len(&self) -> usize {
+        match self {
+            HunkLines::Unpacked(lines) => lines.len(),
+            HunkLines::Packed { packed, .. } => packed.lines.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The kind and content of each line, without unpacking them.
+    pub fn contents(&self) -> impl Iterator<Item = (LineKind, &str)> {
+        let (lines, packed) = match self {
+            HunkLines::Unpacked(lines) => (lines.as_slice(), None),
+            HunkLines::Packed { packed, .. } => (&[][..], Some(packed)),
+        };
+        lines
+            .iter()
+            .map(|l| (l.kind, l.content.as_str()))
+            .chain(packed.into_iter().flat_map(PackedLines::contents))
+    }
+}
+
+impl Default for HunkLines {
+    fn default() -> Self {
+        HunkLines::Unpacked(Vec::new())
+    }
+}
+
+impl std::ops::Deref for HunkLines {
+    type Target = Vec<DiffLine>;
+
+    fn deref(&self) -> &Vec<DiffLine> {
+        match self {
+            HunkLines::Unpacked(lines) => lines,
+            HunkLines::Packed { packed, unpacked } => unpacked.get_or_init(|| packed.unpack()),
+        }
+    }
+}
+
+impl std::ops::DerefMut for HunkLines {
+    fn deref_mut(&mut self) -> &mut Vec<DiffLine> {
+        // Changed lines no longer match the packed ones
+        if let HunkLines::Packed { packed, unpacked } = self {
+            let lines = unpacked.take().unwrap_or_else(|| packed.unpack());
+            *self = HunkLines::Unpacked(lines);
+        }
+        match self {
+            HunkLines::Unpacked(lines) => lines,
+            HunkLines::Packed { .. } => unreachable!("unpacked above"),
+        }
+    }
+}
+
+impl From<Vec<DiffLine>> for HunkLines {
+    fn from(lines: Vec<DiffLine>) -> Self {
+        HunkLines::Unpacked(lines)
+    }
+}
+
+impl From<HunkLines> for Vec<DiffLine> {
+    fn from(lines: HunkLines) -> Self {
+        match lines {
+            HunkLines::Unpacked(lines) => lines,
+            HunkLines::Packed {
+                packed,
+                mut unpacked,
+            } => unpacked.take().unwrap_or_else(|| packed.unpack()),
+        }
+    }
+}
+
+impl FromIterator<DiffLine> for HunkLines {
+    fn from_iter<I: IntoIterator<Item = DiffLine>>(iter: I) -> Self {
+        HunkLines::Unpacked(iter.into_iter().collect())
+    }
+}
+
+impl<'a> IntoIterator for &'a HunkLines {
+    type Item = &'a DiffLine;
+    type IntoIter = std::slice::Iter<'a, DiffLine>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut HunkLines {
+    type Item = &'a mut DiffLine;
+    type IntoIter = std::slice::IterMut<'a, DiffLine>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl std::fmt::Debug for HunkLines {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Serialize for HunkLines {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_slice().serialize(serializer)
+    }
+}
+
+/// Diff lines packed by [`HunkLines::pack`]: their contents back to back
+/// in one buffer, and the rest of each line alongside.
+#[derive(Clone)]
+pub struct PackedLines {
+    text: Box<str>,
+    lines: Box<[PackedLine]>,
+}
+
+#[derive(Clone)]
+struct PackedLine {
+    kind: LineKind,
+    old_lineno: Option<u32>,
+    new_lineno: Option<u32>,
+    /// End of the line's content in [`PackedLines::text`].
+    end: usize,
+    raw: Option<Box<[u8]>>,
+    no_newline: bool,
+}
+
+impl PackedLines {
+    fn new(lines: &[DiffLine]) -> Self {
+        let mut text = String::with_capacity(lines.iter().map(|l| l.content.len()).sum());
+        let lines = lines
+            .iter()
+            .map(|line| {
+                text.push_str(&line.content);
+                PackedLine {
+                    kind: line.kind,
+                    old_lineno: line.old_lineno,
+                    new_lineno: line.new_lineno,
+                    end: text.len(),
+                    raw: line.raw.as_deref().map(Box::from),
+                    no_newline: line.no_newline,
+                }
+            })
+            .collect();
+        PackedLines {
+            text: text.into_boxed_str(),
+            lines,
+        }
+    }
+
+    fn contents(&self) -> impl Iterator<Item = (LineKind, &str)> {
+        self.lines.iter().scan(0, |start, line| {
+            let content = &self.text[*start..line.end];
+            *start = line.end;
+            Some((line.kind, content))
+        })
+    }
+
+    fn unpack(&self) -> Vec<DiffLine> {
+        self.lines
+            .iter()
+            .zip(self.contents())
+            .map(|(line, (_, content))| DiffLine {
+                kind: line.kind,
+                content: content.to_string(),
+                old_lineno: line.old_lineno,
+                new_lineno: line.new_lineno,
+                raw: line.raw.as_deref().map(<[u8]>::to_vec),
+                no_newline: line.no_newline,
+            })
+            .collect()
+    }
 }
 
 /// The type of a diff line.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LineKind {
     Context,
     Added,
@@ -72,7 +372,7 @@ impl std::fmt::Display for Hunk {
 }
 
 /// Review status for a hunk during the interactive session.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HunkStatus {
     Pending,
     Staged,
@@ -87,6 +387,95 @@ pub enum AppMode {
     Browsing,
     WaitingForEditor,
     Help,
+    /// Asking whether to continue a previous spawn session.
+    ResumePrompt,
+    /// Typing a `:` command.
+    Command,
+    /// Typing a quick comment on a hunk line (without the editor).
+    InlineComment,
+    /// Asking whether to recover editor tempfiles left by a stagent that
+    /// exited while an editor was open.
+    RecoveryPrompt,
+    /// Typing the file list filter after `f`.
+    FileFilter,
+    /// Asking whether to quit with hunks still pending or feedback not
+    /// yet written.
+    ConfirmQuit,
+    /// The file overview shown before the review (`--overview`), where
+    /// files can be left out.
+    Overview,
+    /// Asking whether to apply and stage a hunk edit or keep it as
+    /// feedback.
+    StageEditPrompt,
+    /// Asking for the overall review verdict before quitting
+    /// (`--verdict`).
+    VerdictPrompt,
+    /// Asking before staging the deletion of a file.
+    DeletionPrompt,
+    /// Listing the captured feedback (`F`) to prune it before quitting.
+    FeedbackPanel,
+    /// Asking whether to stage the hunks `:stage-matching` found.
+    BulkStagePrompt,
+    /// Showing the messages of the review so far (`M`).
+    MessageHistory,
+    /// Showing why a hunk could not be staged.
+    StageError,
+    /// Showing the commits that last changed the current hunk's old lines
+    /// (`D`).
+    HunkDetails,
+    /// Listing the commits of a `--range` review (`O`) to comment on them.
+    CommitsPanel,
+}
+
+/// How hunks are laid out in the diff view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum ViewMode {
+    /// Single column with `-`/`+` lines interleaved.
+    #[default]
+    Unified,
+    /// Old content on the left, new content on the right.
+    SideBySide,
+}
+
+/// Order in which files are listed for review.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum ReviewOrder {
+    /// Alphabetical by path, as `git diff` lists them.
+    #[default]
+    Path,
+    /// Riskiest first, by churn and bug-fix history (see [`crate::risk`]).
+    Risk,
+    /// Most pending hunks first.
+    Pending,
+    /// Most added plus removed lines first.
+    Lines,
+}
+
+/// Output format for collected feedback.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum FeedbackFormat {
+    /// Unified diff with `# REVIEW COMMENT:` lines.
+    #[default]
+    Diff,
+    /// JSON array of feedback entries.
+    Json,
+    /// Gerrit review input JSON: comments by file and new-side line.
+    Gerrit,
+}
+
+/// How comments sit in diff-format feedback.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum OutputStyle {
+    /// `# REVIEW COMMENT:` lines among the lines they are about.
+    #[default]
+    Annotated,
+    /// Whole hunks that parse and apply as a diff, with the comments in
+    /// `#=== ... ===` blocks between files.
+    StrictDiff,
 }
 
 /// Which panel is focused in the TUI.
@@ -96,8 +485,42 @@ pub enum FocusPanel {
     DiffView,
 }
 
+/// Initial cursor location requested on the command line
+/// (`--start-file <path[:line]>` / `--start-hunk <n>`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StartPosition {
+    /// File to select. `None` keeps the first file selected.
+    pub path: Option<PathBuf>,
+    /// 1-based line in the new version of the file; selects the hunk containing it.
+    pub line: Option<u32>,
+    /// 1-based hunk index within the file. Ignored when `line` is set.
+    pub hunk: Option<usize>,
+}
+
+impl StartPosition {
+    /// Build a start position from the raw `--start-file` and `--start-hunk` values.
+    /// A trailing `:<line>` on the path is split off as the line number.
+    pub fn from_args(start_file: Option<&str>, start_hunk: Option<usize>) -> Self {
+        let (path, line) = match start_file {
+            Some(spec) => match spec.rsplit_once(':') {
+                Some((path, line)) if !path.is_empty() => match line.parse::<u32>() {
+                    Ok(n) => (Some(PathBuf::from(path)), Some(n)),
+                    Err(_) => (Some(PathBuf::from(spec)), None),
+                },
+                _ => (Some(PathBuf::from(spec)), None),
+            },
+            None => (None, None),
+        };
+        Self {
+            path,
+            line,
+            hunk: start_hunk,
+        }
+    }
+}
+
 /// Feedback collected from user edits or comments on a hunk.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HunkFeedback {
     pub file_path: String,
     pub hunk_header: String,
@@ -109,11 +532,122 @@ pub struct HunkFeedback {
     /// which it appears) and text. Allows rendering comments inline at the
     /// correct location within the diff.
     pub comment_positions: Vec<(usize, String)>,
+    /// For comments: replacement code from a `suggestion` fence, rendered as
+    /// a GitHub suggestion block.
+    #[serde(default)]
+    pub suggestion: Option<String>,
+    /// For comments: how much the comment matters, set by starting a
+    /// comment with `!nit`, `!suggestion`, `!issue` or `!blocker`. The
+    /// highest severity among a hunk's comments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
 }
 
-/// The type of feedback: an edit (unified diff) or a comment.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Severity of a review comment, from least to most important.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Nit,
+    Suggestion,
+    Issue,
+    Blocker,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 4] = [
+        Severity::Nit,
+        Severity::Suggestion,
+        Severity::Issue,
+        Severity::Blocker,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Nit => "nit",
+            Severity::Suggestion => "suggestion",
+            Severity::Issue => "issue",
+            Severity::Blocker => "blocker",
+        }
+    }
+
+    /// Split a `!<severity>` prefix (any case) off a comment, returning the
+    /// severity and the rest of the text. `None` if the comment has no
+    /// such prefix.
+    ///
+    /// ```
+    /// use stagent::types::Severity;
+    ///
+    /// assert_eq!(
+    ///     Severity::strip_prefix("!Blocker leaks the handle"),
+    ///     Some((Severity::Blocker, "leaks the handle"))
+    /// );
+    /// assert_eq!(Severity::strip_prefix("!nitpicky"), None);
+    /// ```
+    pub fn strip_prefix(text: &str) -> Option<(Severity, &str)> {
+        let rest = text.strip_prefix('!')?;
+        let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (word, rest) = rest.split_at(word_end);
+        let severity = Self::ALL
+            .into_iter()
+            .find(|s| s.label().eq_ignore_ascii_case(word))?;
+        Some((severity, rest.trim_start()))
+    }
+}
+
+/// Overall verdict on a review, asked for when quitting with `--verdict`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Approve,
+    RequestChanges,
+    Comment,
+}
+
+impl Verdict {
+    pub fn label(self) -> &'static str {
+        match self {
+            Verdict::Approve => "approve",
+            Verdict::RequestChanges => "request changes",
+            Verdict::Comment => "comment",
+        }
+    }
+}
+
+/// The verdict given at the end of a review, with an optional summary
+/// message. Output as a header block before the feedback.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReviewVerdict {
+    pub verdict: Verdict,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
+/// The type of feedback: an edit (unified diff), a comment on a hunk, a
+/// comment on a whole file, on the review as a whole, or on a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum FeedbackKind {
     Edit,
     Comment,
+    /// Not tied to a hunk: `hunk_header` and `context_lines` are empty.
+    #[serde(rename = "file_comment")]
+    FileComment,
+    /// Not tied to a file either: `file_path` is empty too.
+    #[serde(rename = "review_comment")]
+    ReviewComment,
+    /// On a commit of a `--range` review: `file_path` is empty and
+    /// `hunk_header` is the commit's short SHA and subject.
+    #[serde(rename = "commit_comment")]
+    CommitComment,
+}
+
+impl FeedbackKind {
+    /// Whether the feedback is on a file, rather than on the review as a
+    /// whole or a commit.
+    pub fn has_file(self) -> bool {
+        !matches!(
+            self,
+            FeedbackKind::ReviewComment | FeedbackKind::CommitComment
+        )
+    }
 }
@@ -0,0 +1,82 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::blame::{self, FileBlame};
+use crate::types::Hunk;
+use crate::ui::theme;
+
+/// Most rows the panel takes, including borders.
+const MAX_HEIGHT: u16 = 12;
+
+/// Render the blame of `hunk`'s old lines along the bottom of `area` (the
+/// diff view). `blame` is `None` when the hunk has no old lines. `now` is
+/// in seconds since the epoch, for the commit ages.
+pub fn render(
+    frame: &mut Frame,
+    area: Rect,
+    blame: Option<&Result<FileBlame, String>>,
+    hunk: &Hunk,
+    now: i64,
+) {
+    let dim = Style::default()
+        .fg(theme::context_fg())
+        .add_modifier(Modifier::DIM);
+    let lines: Vec<Line> = match blame {
+        None => vec![Line::styled(" No old lines to blame", dim)],
+        Some(Err(e)) => vec![Line::styled(format!(" {}", e), dim)],
+        Some(Ok(blame)) => {
+            let ranges = blame.hunk_ranges(hunk);
+            if ranges.is_empty() {
+                vec![Line::styled(" No old lines to blame", dim)]
+            } else {
+                ranges
+                    .into_iter()
+                    .map(|(range, commit)| {
+                        let lines = blame::format_ranges(&[range]);
+                        let age = if commit.is_committed() {
+                            blame::format_age(commit.time, now)
+                        } else {
+                            String::new()
+                        };
+                        Line::from(vec![
+                            Span::styled(format!(" {:>9}  ", lines), dim),
+                            Span::styled(
+                                format!("{:<7}  ", commit.id),
+                                Style::default().fg(theme::hunk_header_fg()),
+                            ),
+                            Span::styled(
+                                format!(
+                                    "{:<16.16}  {:<14}  {}",
+                                    commit.author, age, commit.summary
+                                ),
+                                dim,
+                            ),
+                        ])
+                    })
+                    .collect()
+            }
+        }
+    };
+
+    let height = (lines.len() as u16 + 2)
+        .min(MAX_HEIGHT)
+        .min(area.height / 2)
+        .max(3)
+        .min(area.height);
+    let panel = Rect {
+        x: area.x,
+        y: area.y + area.height - height,
+        width: area.width,
+        height,
+    };
+    let block = Block::default()
+        .title(" Blame (b) ")
+        .borders(Borders::ALL)
+        .border_style(theme::border_unfocused_style());
+
+    frame.render_widget(Clear, panel);
+    frame.render_widget(Paragraph::new(lines).block(block), panel);
+}
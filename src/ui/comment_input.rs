@@ -0,0 +1,43 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::types::{DiffLine, LineKind};
+use crate::ui::theme;
+
+/// Height of the inline comment box, including borders.
+pub const HEIGHT: u16 = 3;
+
+/// Render the inline comment input box. `anchor` is the diff line the
+/// comment will follow.
+pub fn render(frame: &mut Frame, area: Rect, input: &str, anchor: Option<&DiffLine>) {
+    let title = match anchor {
+        Some(line) => {
+            let location = match (line.kind, line.new_lineno, line.old_lineno) {
+                (LineKind::Removed, _, Some(old)) => format!("old line {}", old),
+                (_, Some(new), _) => format!("line {}", new),
+                _ => "line".to_string(),
+            };
+            format!(" Comment after {} — Enter: save, Esc: cancel ", location)
+        }
+        None => " Comment — Enter: save, Esc: cancel ".to_string(),
+    };
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(theme::border_focused_style());
+
+    // Keep the end of long input (and the cursor) visible
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let text = format!("{}█", input);
+    let skip = text.chars().count().saturating_sub(inner_width);
+    let visible: String = text.chars().skip(skip).collect();
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(Line::from(Span::raw(visible))).block(block),
+        area,
+    );
+}
@@ -0,0 +1,72 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState};
+
+use crate::range::RangeCommit;
+use crate::types::{FeedbackKind, HunkFeedback};
+use crate::ui::help_overlay::centered_rect;
+use crate::ui::theme;
+
+/// Render the commits panel (`O`) of a `--range` review: one row per
+/// commit, oldest first, with the first line of its comment if it has one.
+pub fn render(
+    frame: &mut Frame,
+    area: Rect,
+    commits: &[RangeCommit],
+    feedback: &[HunkFeedback],
+    cursor: usize,
+) {
+    let width = 100u16.min(area.width.saturating_sub(4));
+    let height = (commits.len() as u16 + 2).clamp(3, area.height.saturating_sub(4).max(3));
+    let panel = centered_rect(width, height.min(area.height), area);
+    frame.render_widget(Clear, panel);
+
+    let dim = Style::default().fg(theme::border_unfocused());
+    let items: Vec<ListItem> = commits
+        .iter()
+        .map(|commit| {
+            let label = commit.label();
+            let comment = feedback
+                .iter()
+                .find(|fb| fb.kind == FeedbackKind::CommitComment && fb.hunk_header == label);
+            let (sha, summary) = label.split_at(7);
+            let mut spans = vec![
+                Span::styled(
+                    format!(" {}", sha),
+                    Style::default().fg(theme::hunk_header_fg()),
+                ),
+                Span::styled(
+                    summary.to_string(),
+                    Style::default().fg(theme::selected_fg()),
+                ),
+            ];
+            if let Some(fb) = comment {
+                let severity = fb
+                    .severity
+                    .map(|s| format!("[{}] ", s.label()))
+                    .unwrap_or_default();
+                let text = fb.content.lines().next().unwrap_or("");
+                spans.push(Span::styled(format!("  # {}{}", severity, text), dim));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme::border_focused()))
+        .title(format!(" Commits ({}) ", commits.len()))
+        .title_style(
+            Style::default()
+                .fg(theme::hunk_header_fg())
+                .add_modifier(Modifier::BOLD),
+        );
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(theme::selected_style());
+    let mut state = ListState::default();
+    state.select(Some(cursor));
+    frame.render_stateful_widget(list, panel, &mut state);
+}
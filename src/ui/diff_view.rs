@@ -1,22 +1,28 @@
+use std::ops::{Range, RangeInclusive};
+
 use ratatui::Frame;
-use ratatui::layout::Rect;
+use ratatui::layout::{Margin, Rect};
 use ratatui::style::{Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::widgets::{
+    Block, Borders, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
+};
+use unicode_width::UnicodeWidthChar;
 
-use crate::types::{FileDiff, Hunk, HunkStatus, LineKind};
+use crate::app::App;
+use crate::context::ExpandedContext;
+use crate::diff;
+use crate::notes;
+use crate::risk::RiskLevel;
+use crate::types::{DiffLine, FileDiff, FocusPanel, Hunk, HunkStatus, LineKind, ViewMode};
 use crate::ui::theme;
 
 /// Render the diff view panel showing hunks for the selected file.
-pub fn render(
-    frame: &mut Frame,
-    area: Rect,
-    file: Option<&FileDiff>,
-    selected_hunk: usize,
-    scroll_offset: u32,
-    focused: bool,
-    highlighted_lines: Option<&Vec<Vec<Line<'static>>>>,
-) {
+pub fn render(frame: &mut Frame, area: Rect, app: &App) {
+    let file = app.current_file();
+    let focused = app.focus == FocusPanel::DiffView;
+    let highlighted_lines = app.highlight_cache.get(app.selected_file).map(|h| &h.hunks);
+
     let border_style = if focused {
         theme::border_focused_style()
     } else {
@@ -24,14 +30,44 @@ pub fn render(
     };
 
     let title = match file {
+        Some(f) if f.is_new_file() => {
+            let lines = f
+                .hunks
+                .iter()
+                .map(|h| (h.new_start + h.new_lines).saturating_sub(1))
+                .max()
+                .unwrap_or(0);
+            format!(" {} · new file, {} lines ", f.path.display(), lines)
+        }
+        Some(f) if f.is_deleted_file() => {
+            let lines = f
+                .hunks
+                .iter()
+                .map(|h| (h.old_start + h.old_lines).saturating_sub(1))
+                .max()
+                .unwrap_or(0);
+            format!(" {} · deleted file, {} lines ", f.path.display(), lines)
+        }
         Some(f) => format!(" {} ", f.path.display()),
         None => " No file selected ".to_string(),
     };
+    let title = if app.h_scroll > 0 && !app.wrap_lines {
+        format!("{}· from column {} ", title, app.h_scroll + 1)
+    } else {
+        title
+    };
 
-    let block = Block::default()
+    let mut block = Block::default()
         .title(title)
         .borders(Borders::ALL)
         .border_style(border_style);
+    // Earlier review of the same content, from refs/notes/stagent
+    if let Some(note) = file.and_then(|f| app.review_notes.get(&f.path)) {
+        block = block.title_bottom(Line::from(Span::styled(
+            format!(" {} ", notes::summarize(note)),
+            theme::context_style().add_modifier(Modifier::DIM),
+        )));
+    }
 
     let file = match file {
         Some(f) => f,
@@ -42,87 +78,813 @@ pub fn render(
         }
     };
 
-    let mut lines: Vec<Line> = Vec::new();
+    // Only the rows on screen are built
+    let top = app.scroll_offset as usize;
+    let rows = top..top + area.height.saturating_sub(2) as usize;
+    let lines = file_lines(app, file, area.width, highlighted_lines, rows);
 
-    for (hunk_idx, hunk) in file.hunks.iter().enumerate() {
-        let is_selected = hunk_idx == selected_hunk;
+    let paragraph = Paragraph::new(lines).block(block);
+
+    frame.render_widget(paragraph, area);
+
+    // Scrollbar over the right border when the file doesn't fit
+    let total = app.total_content_lines() as usize;
+    let visible = area.height.saturating_sub(2) as usize;
+    if total > visible {
+        let mut state = ScrollbarState::new(total.saturating_sub(visible))
+            .position(top)
+            .viewport_content_length(visible);
+        frame.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .style(border_style),
+            area.inner(Margin {
+                vertical: 1,
+                horizontal: 0,
+            }),
+            &mut state,
+        );
+    }
+}
+
+/// Build the rendered lines for `file` in the current view mode, keeping
+/// only those in `rows`. `width` is the width of the bordered diff view
+/// block. Also used by `stagent print`.
+pub fn file_lines<'a>(
+    app: &'a App,
+    file: &'a FileDiff,
+    width: u16,
+    highlighted_lines: Option<&Vec<Vec<Line<'static>>>>,
+    rows: Range<usize>,
+) -> Vec<Line<'a>> {
+    let mut window = Window::new(rows);
+    match app.view_mode {
+        ViewMode::Unified => unified_lines(&mut window, app, file, width, highlighted_lines),
+        ViewMode::SideBySide => {
+            side_by_side_lines(&mut window, app, file, width, highlighted_lines)
+        }
+    }
+    window.lines
+}
+
+/// Rows of the diff view being built. Rows outside `rows` are counted but
+/// not built, so a long file costs no more to draw than the part on screen.
+struct Window<'a> {
+    rows: Range<usize>,
+    next: usize,
+    lines: Vec<Line<'a>>,
+}
+
+impl<'a> Window<'a> {
+    fn new(rows: Range<usize>) -> Self {
+        Self {
+            rows,
+            next: 0,
+            lines: Vec::new(),
+        }
+    }
+
+    /// Add the next row, building it only if it is in the window.
+    fn push(&mut self, row: impl FnOnce() -> Line<'a>) {
+        if self.rows.contains(&self.next) {
+            self.lines.push(row());
+        }
+        self.next += 1;
+    }
+
+    /// Add the next `count` rows, the rows of one long line, building them
+    /// only if any is in the window.
+    fn push_rows(&mut self, count: usize, rows: impl FnOnce() -> Vec<Line<'a>>) {
+        let range = self.next..self.next + count;
+        if range.start < self.rows.end && self.rows.start < range.end {
+            let mut rows = rows();
+            rows.resize(count, Line::default());
+            for (row, line) in range.clone().zip(rows) {
+                if self.rows.contains(&row) {
+                    self.lines.push(line);
+                }
+            }
+        }
+        self.next = range.end;
+    }
+
+    /// Skip the next `count` rows if they all come before the window.
+    fn skip_before(&mut self, count: usize) -> bool {
+        let skip = self.next + count <= self.rows.start;
+        if skip {
+            self.next += count;
+        }
+        skip
+    }
+
+    fn is_full(&self) -> bool {
+        self.next >= self.rows.end
+    }
+}
+
+/// Rows taken by hunk `hunk_idx` of the selected file, separator included.
+fn hunk_rows(app: &App, file: &FileDiff, hunk_idx: usize, hunk: &Hunk) -> usize {
+    let separator = usize::from(hunk_idx + 1 < file.hunks.len());
+    1 + app.hunk_body_rows(app.selected_file, hunk_idx, hunk) as usize + separator
+}
+
+/// Columns taken by the line numbers and the `+`/`-` column in front of a
+/// line's content in the unified layout.
+fn unified_gutter_width(file: &FileDiff) -> usize {
+    if file.is_new_file() || file.is_deleted_file() {
+        7
+    } else {
+        11
+    }
+}
+
+/// Columns taken by the line number and the `+`/`-` column of a
+/// side-by-side cell.
+const SIDE_GUTTER_WIDTH: usize = 6;
 
-        // Hunk header line
-        let header_style = if is_selected {
-            theme::hunk_header_style().bg(theme::selected_bg())
+/// Columns of line content that fit on a row of the diff view block,
+/// `width` wide, when long lines wrap (`w`); `None` when they don't.
+pub fn wrap_width(app: &App, file: &FileDiff, width: u16) -> Option<usize> {
+    if !app.wrap_lines {
+        return None;
+    }
+    let inner = width.saturating_sub(2) as usize;
+    let available = match app.view_mode {
+        ViewMode::Unified => inner.saturating_sub(unified_gutter_width(file)),
+        ViewMode::SideBySide => (inner.saturating_sub(1) / 2).saturating_sub(SIDE_GUTTER_WIDTH),
+    };
+    (available > 0).then_some(available)
+}
+
+/// Columns between tab stops unless `--tab-width` says otherwise.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// Columns `c` takes on screen: two for wide characters such as CJK and
+/// most emoji, none for control characters and newlines.
+fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// The column of the tab stop after `column`, stops `tab_width` apart.
+fn next_tab_stop(column: usize, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    (column / tab_width + 1) * tab_width
+}
+
+/// Columns `content` takes on screen, with tabs expanded to stops
+/// `tab_width` apart.
+pub fn display_width(content: &str, tab_width: usize) -> usize {
+    content.chars().fold(0, |column, c| match c {
+        '\t' => next_tab_stop(column, tab_width),
+        c => column + char_width(c),
+    })
+}
+
+/// `content` with tabs expanded to spaces, up to stops `tab_width` apart.
+fn expand_tabs(content: &str, tab_width: usize) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut column = 0;
+    for c in content.chars() {
+        if c == '\t' {
+            let stop = next_tab_stop(column, tab_width);
+            out.extend(std::iter::repeat_n(' ', stop - column));
+            column = stop;
         } else {
-            theme::hunk_header_style()
-        };
+            out.push(c);
+            column += char_width(c);
+        }
+    }
+    out
+}
 
-        let status_indicator = hunk_status_indicator(hunk);
-        lines.push(Line::from(vec![
-            Span::styled(status_indicator, hunk_status_style(hunk)),
-            Span::raw(" "),
-            Span::styled(&hunk.header, header_style),
-        ]));
+/// Rows a line of `content` takes when wrapped at `wrap` columns.
+fn line_height(content: &str, wrap: Option<usize>, tab_width: usize) -> usize {
+    match wrap {
+        Some(wrap) => display_width(content, tab_width).div_ceil(wrap).max(1),
+        None => 1,
+    }
+}
+
+/// Rows taken by each line of `hunk` (each [`side_by_side_rows`] pair side
+/// by side) in a diff view block `width` wide: more than one for long
+/// lines when they wrap.
+pub fn body_row_heights(app: &App, file: &FileDiff, hunk: &Hunk, width: u16) -> Vec<usize> {
+    let wrap = wrap_width(app, file, width);
+    let height = |i: usize| line_height(&hunk.lines[i].content, wrap, app.tab_width);
+    match app.view_mode {
+        ViewMode::Unified => (0..hunk.lines.len()).map(height).collect(),
+        ViewMode::SideBySide => side_by_side_rows(hunk)
+            .into_iter()
+            .map(|(old, new)| old.map_or(1, height).max(new.map_or(1, height)))
+            .collect(),
+    }
+}
+
+/// Build the rendered lines for the unified (single column) layout.
+fn unified_lines<'a>(
+    window: &mut Window<'a>,
+    app: &'a App,
+    file: &'a FileDiff,
+    width: u16,
+    highlighted_lines: Option<&Vec<Vec<Line<'static>>>>,
+) {
+    // A new or deleted file is previewed as plain content: one column of
+    // line numbers, and no `+` or `-` on every line
+    let preview = if file.is_new_file() {
+        Some(LineKind::Added)
+    } else if file.is_deleted_file() {
+        Some(LineKind::Removed)
+    } else {
+        None
+    };
+    let wrap = wrap_width(app, file, width);
+    for (hunk_idx, hunk) in file.hunks.iter().enumerate() {
+        if window.is_full() {
+            break;
+        }
+        if window.skip_before(hunk_rows(app, file, hunk_idx, hunk)) {
+            continue;
+        }
+        window.push(|| hunk_header_line(app, hunk_idx, hunk));
+
+        let expanded = expanded_context(app, hunk_idx);
+        if let Some(ctx) = expanded {
+            for diff_line in &ctx.before {
+                window.push(|| expanded_row(diff_line, app.h_scroll, app.tab_width));
+            }
+        }
 
         // Hunk lines
+        let cursor = cursor_in_hunk(app, hunk_idx);
         for (line_idx, diff_line) in hunk.lines.iter().enumerate() {
-            let prefix = diff_line.kind.prefix();
-
-            // Build line number gutter
-            let old_no = diff_line
-                .old_lineno
-                .map(|n| format!("{:>4}", n))
-                .unwrap_or_else(|| "    ".to_string());
-            let new_no = diff_line
-                .new_lineno
-                .map(|n| format!("{:>4}", n))
-                .unwrap_or_else(|| "    ".to_string());
-
-            let gutter_style = Style::default()
-                .fg(theme::context_fg())
-                .add_modifier(Modifier::DIM);
-
-            // Use cached syntax highlighting
-            let highlighted = highlighted_lines
-                .and_then(|h| h.get(hunk_idx))
-                .and_then(|h| h.get(line_idx))
-                .cloned()
-                .unwrap_or_else(|| Line::from(diff_line.content.clone()));
-
-            let mut spans = vec![
-                Span::styled(old_no, gutter_style),
-                Span::styled(" ", gutter_style),
-                Span::styled(new_no, gutter_style),
-                Span::styled(" ", gutter_style),
-                Span::styled(
-                    prefix,
-                    match diff_line.kind {
-                        LineKind::Added => Style::default()
-                            .fg(theme::added_fg())
-                            .add_modifier(Modifier::BOLD),
-                        LineKind::Removed => Style::default()
-                            .fg(theme::removed_fg())
-                            .add_modifier(Modifier::BOLD),
-                        LineKind::Context => Style::default().fg(theme::context_fg()),
-                    },
-                ),
-            ];
-            spans.extend(highlighted.spans);
-
-            lines.push(Line::from(spans));
+            if window.is_full() {
+                break;
+            }
+            let height = line_height(&diff_line.content, wrap, app.tab_width);
+            window.push_rows(height, || {
+                let prefix = diff_line.kind.prefix();
+
+                // Build line number gutter
+                let old_no = diff_line
+                    .old_lineno
+                    .map(|n| format!("{:>4}", n))
+                    .unwrap_or_else(|| "    ".to_string());
+                let new_no = diff_line
+                    .new_lineno
+                    .map(|n| format!("{:>4}", n))
+                    .unwrap_or_else(|| "    ".to_string());
+
+                let gutter_style = if cursor.as_ref().is_some_and(|c| c.contains(&line_idx)) {
+                    theme::selected_style()
+                } else {
+                    gutter_style()
+                };
+
+                // Use cached syntax highlighting
+                let highlighted = highlighted_lines
+                    .and_then(|h| h.get(hunk_idx))
+                    .and_then(|h| h.get(line_idx))
+                    .cloned()
+                    .unwrap_or_else(|| Line::from(diff_line.content.clone()));
+
+                let mut spans = match preview {
+                    Some(kind) => vec![
+                        Span::styled(
+                            if kind == LineKind::Added {
+                                new_no
+                            } else {
+                                old_no
+                            },
+                            gutter_style,
+                        ),
+                        Span::styled(" │", gutter_style),
+                    ],
+                    None => vec![
+                        Span::styled(old_no, gutter_style),
+                        Span::styled(" ", gutter_style),
+                        Span::styled(new_no, gutter_style),
+                        Span::styled(" ", gutter_style),
+                    ],
+                };
+                // Context only comes from staging part of a previewed file
+                if preview == Some(diff_line.kind) {
+                    spans.push(Span::raw(" "));
+                } else {
+                    spans.push(Span::styled(prefix, prefix_style(diff_line)));
+                }
+                let content = mark_whitespace(
+                    highlighted.spans,
+                    diff_line.kind,
+                    app.show_whitespace,
+                    app.tab_width,
+                );
+
+                // The gutter stays put: wrapped rows continue under the
+                // content, and scrolling sideways moves the content only
+                let Some(wrap) = wrap else {
+                    spans.extend(skip_columns(content, app.h_scroll));
+                    return vec![Line::from(spans)];
+                };
+                let mut pieces = split_spans(content, wrap).into_iter();
+                spans.extend(pieces.next().unwrap_or_default());
+                let mut rows = vec![Line::from(spans)];
+                for piece in pieces {
+                    let mut row = match preview {
+                        Some(_) => vec![
+                            Span::styled("    ", gutter_style),
+                            Span::styled(" │", gutter_style),
+                            Span::raw(" "),
+                        ],
+                        None => vec![Span::styled(" ".repeat(10), gutter_style), Span::raw(" ")],
+                    };
+                    row.extend(piece);
+                    rows.push(Line::from(row));
+                }
+                rows
+            });
+        }
+
+        if let Some(ctx) = expanded {
+            for diff_line in &ctx.after {
+                window.push(|| expanded_row(diff_line, app.h_scroll, app.tab_width));
+            }
         }
 
         // Separator between hunks
         if hunk_idx < file.hunks.len() - 1 {
-            lines.push(Line::from(Span::styled(
-                "─".repeat(area.width.saturating_sub(2) as usize),
-                Style::default().fg(theme::border_unfocused()),
-            )));
+            window.push(|| {
+                Line::from(Span::styled(
+                    "─".repeat(width.saturating_sub(2) as usize),
+                    Style::default().fg(theme::border_unfocused()),
+                ))
+            });
         }
     }
+}
 
-    let paragraph = Paragraph::new(lines)
-        .block(block)
-        .scroll((scroll_offset as u16, 0));
+/// Build the rendered lines for the side-by-side layout: old content on the
+/// left, new content on the right, one row per [`side_by_side_rows`] entry.
+fn side_by_side_lines<'a>(
+    window: &mut Window<'a>,
+    app: &'a App,
+    file: &'a FileDiff,
+    width: u16,
+    highlighted_lines: Option<&Vec<Vec<Line<'static>>>>,
+) {
+    let inner = width.saturating_sub(2) as usize;
+    // One column for the divider between the two halves
+    let half = inner.saturating_sub(1) / 2;
+    let divider_style = Style::default().fg(theme::border_unfocused());
+    let layout = CellLayout {
+        width: half,
+        wrap: wrap_width(app, file, width),
+        h_scroll: app.h_scroll,
+        show_whitespace: app.show_whitespace,
+        tab_width: app.tab_width,
+    };
+    // The halves of a row side by side, the shorter one padded
+    let join = |old: Vec<Vec<Span<'static>>>, new: Vec<Vec<Span<'static>>>| {
+        let height = old.len().max(new.len());
+        let blank = || vec![Span::raw(" ".repeat(half))];
+        let mut old = old.into_iter();
+        let mut new = new.into_iter();
+        (0..height)
+            .map(|_| {
+                let mut spans = old.next().unwrap_or_else(blank);
+                spans.push(Span::styled("│", divider_style));
+                spans.extend(new.next().unwrap_or_else(blank));
+                Line::from(spans)
+            })
+            .collect::<Vec<_>>()
+    };
 
-    frame.render_widget(paragraph, area);
+    // Expanded context doesn't wrap
+    let context_layout = CellLayout {
+        wrap: None,
+        ..layout
+    };
+    let context_row = |dl: &DiffLine| {
+        let old = side_cell(Some(dl), None, context_layout, true, false);
+        let new = side_cell(Some(dl), None, context_layout, false, false);
+        join(old, new).remove(0)
+    };
+
+    for (hunk_idx, hunk) in file.hunks.iter().enumerate() {
+        if window.is_full() {
+            break;
+        }
+        if window.skip_before(hunk_rows(app, file, hunk_idx, hunk)) {
+            continue;
+        }
+        window.push(|| hunk_header_line(app, hunk_idx, hunk));
+
+        let expanded = expanded_context(app, hunk_idx);
+        if let Some(ctx) = expanded {
+            for dl in &ctx.before {
+                window.push(|| context_row(dl));
+            }
+        }
+
+        let cached = highlighted_lines.and_then(|h| h.get(hunk_idx));
+        let cursor = cursor_in_hunk(app, hunk_idx);
+        let cell = |idx: Option<usize>, old_side: bool| {
+            side_cell(
+                idx.map(|i| &hunk.lines[i]),
+                idx.and_then(|i| cached.and_then(|c| c.get(i))),
+                layout,
+                old_side,
+                idx.is_some_and(|i| cursor.as_ref().is_some_and(|c| c.contains(&i))),
+            )
+        };
+        let height = |idx: Option<usize>| {
+            idx.map_or(1, |i| {
+                line_height(&hunk.lines[i].content, layout.wrap, layout.tab_width)
+            })
+        };
+        for (old_idx, new_idx) in side_by_side_rows(hunk) {
+            if window.is_full() {
+                break;
+            }
+            window.push_rows(height(old_idx).max(height(new_idx)), || {
+                join(cell(old_idx, true), cell(new_idx, false))
+            });
+        }
+
+        if let Some(ctx) = expanded {
+            for dl in &ctx.after {
+                window.push(|| context_row(dl));
+            }
+        }
+
+        if hunk_idx < file.hunks.len() - 1 {
+            window.push(|| {
+                Line::from(Span::styled(
+                    "─".repeat(inner),
+                    Style::default().fg(theme::border_unfocused()),
+                ))
+            });
+        }
+    }
+}
+
+/// Pair a hunk's lines into side-by-side rows of `(old, new)` line indices.
+///
+/// Context lines occupy both sides of a row. A run of removed lines is paired
+/// row-by-row with the run of added lines that follows it; the shorter run is
+/// padded with empty cells.
+pub fn side_by_side_rows(hunk: &Hunk) -> Vec<(Option<usize>, Option<usize>)> {
+    fn flush(
+        rows: &mut Vec<(Option<usize>, Option<usize>)>,
+        removed: &mut Vec<usize>,
+        added: &mut Vec<usize>,
+    ) {
+        for k in 0..removed.len().max(added.len()) {
+            rows.push((removed.get(k).copied(), added.get(k).copied()));
+        }
+        removed.clear();
+        added.clear();
+    }
+
+    let mut rows = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+
+    for (i, line) in hunk.lines.iter().enumerate() {
+        match line.kind {
+            LineKind::Context => {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push((Some(i), Some(i)));
+            }
+            LineKind::Removed => {
+                if !added.is_empty() {
+                    flush(&mut rows, &mut removed, &mut added);
+                }
+                removed.push(i);
+            }
+            LineKind::Added => added.push(i),
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+
+    rows
+}
+
+/// How the halves of side-by-side rows are laid out.
+#[derive(Debug, Clone, Copy)]
+struct CellLayout {
+    /// Columns of a half.
+    width: usize,
+    /// Columns of content before a long line wraps, if lines wrap.
+    wrap: Option<usize>,
+    /// Columns of content scrolled off to the left, if lines don't wrap.
+    h_scroll: usize,
+    show_whitespace: bool,
+    tab_width: usize,
+}
+
+/// Render one half of a side-by-side row, padded or truncated to the
+/// layout's width: one row, or more for a long line that wraps.
+/// `highlighted` is the cached syntax-highlighted content, if any; `cursor`
+/// marks the highlighted line's gutter.
+fn side_cell(
+    diff_line: Option<&DiffLine>,
+    highlighted: Option<&Line<'static>>,
+    layout: CellLayout,
+    old_side: bool,
+    cursor: bool,
+) -> Vec<Vec<Span<'static>>> {
+    let width = layout.width;
+    let Some(diff_line) = diff_line else {
+        return vec![vec![Span::raw(" ".repeat(width))]];
+    };
+    let lineno = if old_side {
+        diff_line.old_lineno
+    } else {
+        diff_line.new_lineno
+    };
+
+    let mut spans = vec![
+        Span::styled(
+            lineno
+                .map(|n| format!("{:>4} ", n))
+                .unwrap_or_else(|| "     ".to_string()),
+            if cursor {
+                theme::selected_style()
+            } else {
+                gutter_style()
+            },
+        ),
+        Span::styled(diff_line.kind.prefix(), prefix_style(diff_line)),
+    ];
+    let content = highlighted.cloned().unwrap_or_else(|| {
+        Line::from(Span::styled(
+            diff_line.content.clone(),
+            theme::context_style(),
+        ))
+    });
+    let content = mark_whitespace(
+        content.spans,
+        diff_line.kind,
+        layout.show_whitespace,
+        layout.tab_width,
+    );
+
+    let Some(wrap) = layout.wrap else {
+        spans.extend(skip_columns(content, layout.h_scroll));
+        return vec![fit_spans(spans, width)];
+    };
+    let mut pieces = split_spans(content, wrap).into_iter();
+    spans.extend(pieces.next().unwrap_or_default());
+    let mut rows = vec![fit_spans(spans, width)];
+    for piece in pieces {
+        let mut row = vec![Span::raw(" ".repeat(SIDE_GUTTER_WIDTH))];
+        row.extend(piece);
+        rows.push(fit_spans(row, width));
+    }
+    rows
+}
+
+/// Mark whitespace in a line's content: trailing whitespace on added lines
+/// gets a warning background, and with `show` (`--show-whitespace`) tabs
+/// and spaces are drawn as `→` and `·`. Tabs are expanded to stops
+/// `tab_width` apart, so what follows them lines up.
+fn mark_whitespace(
+    spans: Vec<Span<'static>>,
+    kind: LineKind,
+    show: bool,
+    tab_width: usize,
+) -> Vec<Span<'static>> {
+    let text: String = spans.iter().map(|s| s.content.as_ref()).collect();
+    let body = text.trim_end_matches(['\n', '\r']);
+    let end = body.chars().count();
+    let trailing = if kind == LineKind::Added {
+        body.trim_end().chars().count()
+    } else {
+        end
+    };
+    if trailing == end && !body.contains('\t') && !(show && body.contains(' ')) {
+        return spans;
+    }
+
+    let mut out: Vec<Span<'static>> = Vec::with_capacity(spans.len() + 1);
+    let mut idx = 0;
+    let mut column = 0;
+    for span in spans {
+        for c in span.content.chars() {
+            let mut style = span.style;
+            if (trailing..end).contains(&idx) {
+                style = style.bg(theme::removed_bg());
+            }
+            let (shown, padding) = match c {
+                ' ' if show => ('·', 0),
+                '\t' => {
+                    let stop = next_tab_stop(column, tab_width);
+                    (if show { '→' } else { ' ' }, stop - column - 1)
+                }
+                c => (c, 0),
+            };
+            column += char_width(shown) + padding;
+            let shown = std::iter::once(shown).chain(std::iter::repeat_n(' ', padding));
+            match out.last_mut() {
+                Some(last) if last.style == style => last.content.to_mut().extend(shown),
+                _ => out.push(Span::styled(shown.collect::<String>(), style)),
+            }
+            idx += 1;
+        }
+    }
+    out
+}
+
+/// Truncate spans to `width` columns (dropping trailing newlines) and pad
+/// with spaces so the row's divider lines up. A wide character that would
+/// straddle the edge is left out.
+fn fit_spans(spans: Vec<Span<'static>>, width: usize) -> Vec<Span<'static>> {
+    let mut out = Vec::with_capacity(spans.len() + 1);
+    let mut used = 0;
+    'spans: for span in spans {
+        let mut text = String::new();
+        for c in span.content.chars().filter(|c| *c != '\n') {
+            let c_width = char_width(c);
+            if used + c_width > width {
+                out.push(Span::styled(text, span.style));
+                break 'spans;
+            }
+            text.push(c);
+            used += c_width;
+        }
+        out.push(Span::styled(text, span.style));
+    }
+    if used < width {
+        out.push(Span::raw(" ".repeat(width - used)));
+    }
+    out
+}
+
+/// Drop the first `columns` columns of spans, for scrolling long lines
+/// sideways. What shows of a wide character cut in half is blank.
+fn skip_columns(spans: Vec<Span<'static>>, columns: usize) -> Vec<Span<'static>> {
+    if columns == 0 {
+        return spans;
+    }
+    let mut column = 0;
+    let mut out = Vec::with_capacity(spans.len());
+    for span in spans {
+        let mut text = String::new();
+        for c in span.content.chars() {
+            let c_width = char_width(c);
+            if column >= columns {
+                text.push(c);
+            } else if column + c_width > columns {
+                text.extend(std::iter::repeat_n(' ', column + c_width - columns));
+            }
+            column += c_width;
+        }
+        if !text.is_empty() {
+            out.push(Span::styled(text, span.style));
+        }
+    }
+    out
+}
+
+/// Split spans into rows of at most `width` columns (dropping newlines),
+/// for wrapping a long line. A wide character that doesn't fit at the end
+/// of a row starts the next. There is always at least one row.
+fn split_spans(spans: Vec<Span<'static>>, width: usize) -> Vec<Vec<Span<'static>>> {
+    let mut rows = Vec::new();
+    let mut row: Vec<Span<'static>> = Vec::new();
+    let mut used = 0;
+    for span in spans {
+        for c in span.content.chars().filter(|c| *c != '\n') {
+            let c_width = char_width(c);
+            if used > 0 && used + c_width > width {
+                rows.push(std::mem::take(&mut row));
+                used = 0;
+            }
+            match row.last_mut() {
+                Some(last) if last.style == span.style => last.content.to_mut().push(c),
+                _ => row.push(Span::styled(c.to_string(), span.style)),
+            }
+            used += c_width;
+        }
+    }
+    rows.push(row);
+    rows
+}
+
+/// The highlighted lines if they are in hunk `hunk_idx` of the selected
+/// file.
+fn cursor_in_hunk(app: &App, hunk_idx: usize) -> Option<RangeInclusive<usize>> {
+    if hunk_idx == app.selected_hunk {
+        app.highlighted_lines()
+    } else {
+        None
+    }
+}
+
+/// Extra file context requested for a hunk of the selected file.
+fn expanded_context(app: &App, hunk_idx: usize) -> Option<&ExpandedContext> {
+    app.expanded_context
+        .get(&(app.selected_file, hunk_idx))
+        .map(|(_, ctx)| ctx)
+}
+
+/// Unified row for an expanded context line (not syntax highlighted), its
+/// content scrolled `h_scroll` columns sideways.
+fn expanded_row(diff_line: &DiffLine, h_scroll: usize, tab_width: usize) -> Line<'static> {
+    let gutter = format!(
+        "{} {} ",
+        diff_line
+            .old_lineno
+            .map(|n| format!("{:>4}", n))
+            .unwrap_or_else(|| "    ".to_string()),
+        diff_line
+            .new_lineno
+            .map(|n| format!("{:>4}", n))
+            .unwrap_or_else(|| "    ".to_string()),
+    );
+    let content = Span::styled(
+        expand_tabs(diff_line.content.trim_end_matches('\n'), tab_width),
+        theme::context_style().add_modifier(Modifier::DIM),
+    );
+    let mut spans = vec![
+        Span::styled(gutter, gutter_style()),
+        Span::styled(" ", prefix_style(diff_line)),
+    ];
+    spans.extend(skip_columns(vec![content], h_scroll));
+    Line::from(spans)
+}
+
+/// Hunk header row: status indicator followed by the `@@` header, and a
+/// badge when a previous round's comment on it is still unaddressed.
+fn hunk_header_line<'a>(app: &App, hunk_idx: usize, hunk: &'a Hunk) -> Line<'a> {
+    let header_style = if hunk_idx == app.selected_hunk {
+        theme::hunk_header_style().bg(theme::selected_bg())
+    } else {
+        theme::hunk_header_style()
+    };
+
+    // Marker column: set with space, batch-staged with Y
+    let marker = if app.is_marked(app.selected_file, hunk_idx) {
+        Span::styled("▶ ", theme::file_header_style())
+    } else {
+        Span::raw("  ")
+    };
+
+    let mut spans = vec![
+        marker,
+        Span::styled(hunk_status_indicator(hunk), hunk_status_style(hunk)),
+        Span::raw(" "),
+        Span::styled(&hunk.header, header_style),
+    ];
+    if let Some(file) = app.current_file()
+        && let Some(risk) = app.risk.get(&file.path, hunk)
+    {
+        let style = match risk.level() {
+            RiskLevel::High => Style::default()
+                .fg(theme::removed_fg())
+                .add_modifier(Modifier::BOLD),
+            RiskLevel::Medium => Style::default().fg(theme::status_commented_fg()),
+            RiskLevel::Low => gutter_style(),
+        };
+        spans.push(Span::styled(format!("  [risk {}]", risk.score), style));
+    }
+    if diff::is_whitespace_only(hunk) {
+        spans.push(Span::styled(
+            "  [whitespace only]",
+            Style::default().fg(theme::status_skipped_fg()),
+        ));
+    }
+    if !app
+        .unaddressed_comments(app.selected_file, hunk_idx)
+        .is_empty()
+    {
+        spans.push(Span::styled(
+            "  [previous comment unaddressed]",
+            Style::default()
+                .fg(theme::status_commented_fg())
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    Line::from(spans)
+}
+
+fn gutter_style() -> Style {
+    Style::default()
+        .fg(theme::context_fg())
+        .add_modifier(Modifier::DIM)
+}
+
+fn prefix_style(diff_line: &DiffLine) -> Style {
+    match diff_line.kind {
+        LineKind::Added => Style::default()
+            .fg(theme::added_fg())
+            .add_modifier(Modifier::BOLD),
+        LineKind::Removed => Style::default()
+            .fg(theme::removed_fg())
+            .add_modifier(Modifier::BOLD),
+        LineKind::Context => Style::default().fg(theme::context_fg()),
+    }
 }
 
 fn hunk_status_indicator(hunk: &Hunk) -> &'static str {
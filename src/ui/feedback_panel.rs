@@ -0,0 +1,73 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState};
+
+use crate::types::{FeedbackKind, HunkFeedback};
+use crate::ui::help_overlay::centered_rect;
+use crate::ui::theme;
+
+/// Render the feedback panel (`F`): one row per captured feedback item
+/// with its kind, where it applies and the first line of its text.
+pub fn render(frame: &mut Frame, area: Rect, feedback: &[HunkFeedback], cursor: usize) {
+    let width = 100u16.min(area.width.saturating_sub(4));
+    let height = (feedback.len() as u16 + 2).clamp(3, area.height.saturating_sub(4).max(3));
+    let panel = centered_rect(width, height.min(area.height), area);
+    frame.render_widget(Clear, panel);
+
+    let dim = Style::default().fg(theme::border_unfocused());
+    let items: Vec<ListItem> = feedback
+        .iter()
+        .map(|fb| {
+            let kind = match fb.kind {
+                FeedbackKind::Edit => "edit",
+                FeedbackKind::Comment => "comment",
+                FeedbackKind::FileComment => "file",
+                FeedbackKind::ReviewComment => "review",
+                FeedbackKind::CommitComment => "commit",
+            };
+            let target = match fb.kind {
+                FeedbackKind::ReviewComment => "whole review".to_string(),
+                FeedbackKind::CommitComment => fb.hunk_header.clone(),
+                FeedbackKind::FileComment => fb.file_path.clone(),
+                _ => format!("{} {}", fb.file_path, fb.hunk_header),
+            };
+            let severity = fb
+                .severity
+                .map(|s| format!("[{}] ", s.label()))
+                .unwrap_or_default();
+            let text = match fb.kind {
+                FeedbackKind::Edit => String::new(),
+                _ => fb.content.lines().next().unwrap_or("").to_string(),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!(" {:<8}", kind),
+                    Style::default().fg(theme::hunk_header_fg()),
+                ),
+                Span::styled(target, dim),
+                Span::styled(
+                    format!("  {}{}", severity, text),
+                    Style::default().fg(theme::selected_fg()),
+                ),
+            ]))
+        })
+        .collect();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme::border_focused()))
+        .title(format!(" Feedback ({}) ", feedback.len()))
+        .title_style(
+            Style::default()
+                .fg(theme::hunk_header_fg())
+                .add_modifier(Modifier::BOLD),
+        );
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(theme::selected_style());
+    let mut state = ListState::default();
+    state.select(Some(cursor));
+    frame.render_stateful_widget(list, panel, &mut state);
+}
@@ -4,50 +4,127 @@ use ratatui::style::{Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, List, ListItem, ListState};
 
-use crate::types::{DeltaStatus, FileDiff, HunkStatus};
+use crate::file_tree::FileTreeRow;
+use crate::stats;
+use crate::types::{DeltaStatus, FileDiff, Hunk, HunkStatus};
 use crate::ui::theme;
 
-/// Render the file list panel.
-pub fn render(frame: &mut Frame, area: Rect, files: &[FileDiff], selected: usize, focused: bool) {
-    let border_style = if focused {
-        theme::border_focused_style()
+/// Title of the file list panel, with the `f` filter and match count when
+/// filtering.
+pub fn title(filter: &str, shown: usize, total: usize) -> String {
+    if filter.is_empty() {
+        " Files ".to_string()
     } else {
-        theme::border_unfocused_style()
-    };
+        format!(" Files ~{} ({}/{}) ", filter, shown, total)
+    }
+}
 
-    let block = Block::default()
-        .title(" Files ")
-        .borders(Borders::ALL)
-        .border_style(border_style);
+/// The file list panel's frame: its title, the first row on screen and
+/// whether it has focus.
+pub struct Panel<'a> {
+    pub title: &'a str,
+    pub offset: usize,
+    pub focused: bool,
+}
 
-    let items: Vec<ListItem> = files
+/// Render the file list panel with the `shown` files (indices into
+/// `files`). `selected` is a file index; it is not highlighted if filtered
+/// out.
+pub fn render(
+    frame: &mut Frame,
+    area: Rect,
+    panel: &Panel,
+    files: &[FileDiff],
+    shown: &[usize],
+    selected: usize,
+) {
+    let items: Vec<ListItem> = shown
+        .iter()
+        .map(|&i| {
+            let file = &files[i];
+            file_item(
+                file,
+                file.path.to_string_lossy().to_string(),
+                0,
+                i == selected,
+            )
+        })
+        .collect();
+
+    let cursor = shown.iter().position(|&i| i == selected);
+    render_list(frame, area, panel, items, cursor);
+}
+
+/// Render the file list panel as a directory tree.
+pub fn render_tree(
+    frame: &mut Frame,
+    area: Rect,
+    panel: &Panel,
+    files: &[FileDiff],
+    rows: &[FileTreeRow],
+    cursor: usize,
+) {
+    let items: Vec<ListItem> = rows
         .iter()
         .enumerate()
-        .map(|(i, file)| {
-            let (status_icon, status_style) = file_review_status(file);
-            let delta_icon = delta_status_icon(file.status);
-            let path_str = file.path.to_string_lossy();
-
-            let style = if i == selected {
-                theme::selected_style().add_modifier(Modifier::BOLD)
-            } else {
-                Style::default()
-            };
-
-            let line = Line::from(vec![
-                Span::styled(status_icon, status_style),
-                Span::raw(" "),
-                Span::styled(delta_icon, delta_color(file.status)),
-                Span::raw(" "),
-                Span::styled(path_str.to_string(), style),
-            ]);
-
-            ListItem::new(line)
+        .map(|(i, row)| match row {
+            FileTreeRow::File { index, depth } => {
+                let file = &files[*index];
+                let name = file.path.file_name().map_or_else(
+                    || file.path.to_string_lossy(),
+                    |name| name.to_string_lossy(),
+                );
+                file_item(file, name.to_string(), *depth, i == cursor)
+            }
+            FileTreeRow::Dir {
+                label,
+                depth,
+                files: indices,
+                collapsed,
+                ..
+            } => dir_item(files, label, indices, *depth, *collapsed, i == cursor),
         })
         .collect();
 
-    let mut state = ListState::default();
-    state.select(Some(selected));
+    render_list(frame, area, panel, items, Some(cursor));
+}
+
+/// Position indicator for a list with more rows than fit: the cursor row
+/// out of the total, e.g. `12/87`.
+pub fn position(cursor: Option<usize>, rows: usize, visible: usize) -> Option<String> {
+    if rows <= visible {
+        return None;
+    }
+    Some(match cursor {
+        Some(cursor) => format!(" {}/{} ", cursor + 1, rows),
+        None => format!(" -/{} ", rows),
+    })
+}
+
+fn render_list(
+    frame: &mut Frame,
+    area: Rect,
+    panel: &Panel,
+    items: Vec<ListItem>,
+    selected: Option<usize>,
+) {
+    let border_style = if panel.focused {
+        theme::border_focused_style()
+    } else {
+        theme::border_unfocused_style()
+    };
+
+    let mut block = Block::default()
+        .title(panel.title.to_string())
+        .borders(Borders::ALL)
+        .border_style(border_style);
+    let visible = area.height.saturating_sub(2) as usize;
+    if let Some(position) = position(selected, items.len(), visible) {
+        block = block.title_bottom(Line::from(position).right_aligned());
+    }
+
+    let mut state = ListState::default().with_offset(panel.offset);
+    state.select(selected);
 
     let list = List::new(items)
         .block(block)
@@ -57,17 +134,105 @@ pub fn render(frame: &mut Frame, area: Rect, files: &[FileDiff], selected: usize
     frame.render_stateful_widget(list, area, &mut state);
 }
 
-/// Compute the file's review status icon and style in a single pass over hunks.
-fn file_review_status(file: &FileDiff) -> (&'static str, Style) {
-    if file.hunks.is_empty() {
-        return (" ", Style::default());
+fn file_item(file: &FileDiff, label: String, depth: usize, selected: bool) -> ListItem<'static> {
+    let (status_icon, status_style) = review_status(&file.hunks);
+    let delta_icon = delta_status_icon(file.status);
+
+    let style = if selected {
+        theme::selected_style().add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+
+    let mut spans = vec![
+        Span::raw("  ".repeat(depth)),
+        Span::styled(status_icon, status_style),
+        Span::raw(" "),
+        Span::styled(delta_icon, delta_color(file.status)),
+        Span::raw(" "),
+        Span::styled(label, style),
+    ];
+    if file.is_binary {
+        spans.push(Span::styled(
+            " [bin]",
+            Style::default().fg(theme::context_fg()),
+        ));
+    } else if file.is_submodule {
+        spans.push(Span::styled(
+            " [sub]",
+            Style::default().fg(theme::context_fg()),
+        ));
+    } else {
+        spans.extend(diff_stats(file));
     }
+    ListItem::new(Line::from(spans))
+}
+
+/// Added and removed line counts of a file, and how many of its hunks are
+/// reviewed: ` +12 -3 2/5`.
+fn diff_stats(file: &FileDiff) -> [Span<'static>; 3] {
+    let stats = stats::compute(std::slice::from_ref(file));
+    let progress_style = if stats.pending == 0 {
+        Style::default().fg(theme::status_staged_fg())
+    } else {
+        Style::default().fg(theme::context_fg())
+    };
+    [
+        Span::styled(
+            format!(" +{}", stats.added),
+            Style::default().fg(theme::added_fg()),
+        ),
+        Span::styled(
+            format!(" -{}", stats.removed),
+            Style::default().fg(theme::removed_fg()),
+        ),
+        Span::styled(
+            format!(" {}/{}", stats.reviewed(), stats.hunks),
+            progress_style,
+        ),
+    ]
+}
+
+/// A directory row: fold marker, review status of all its hunks, and the
+/// number of changed files under it.
+fn dir_item(
+    files: &[FileDiff],
+    label: &str,
+    indices: &[usize],
+    depth: usize,
+    collapsed: bool,
+    selected: bool,
+) -> ListItem<'static> {
+    let (status_icon, status_style) = review_status(indices.iter().flat_map(|&i| &files[i].hunks));
+
+    let style = if selected {
+        theme::selected_style().add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(theme::file_header_fg())
+    };
+
+    ListItem::new(Line::from(vec![
+        Span::raw("  ".repeat(depth)),
+        Span::styled(status_icon, status_style),
+        Span::raw(if collapsed { " ▸ " } else { " ▾ " }),
+        Span::styled(format!("{}/", label), style),
+        Span::styled(
+            format!(" ({})", indices.len()),
+            Style::default().fg(theme::context_fg()),
+        ),
+    ]))
+}
 
+/// Compute the review status icon and style of a file or directory in a
+/// single pass over its hunks.
+fn review_status<'a>(hunks: impl IntoIterator<Item = &'a Hunk>) -> (&'static str, Style) {
+    let mut any = false;
     let mut all_staged = true;
     let mut all_done = true;
     let mut any_staged = false;
 
-    for h in &file.hunks {
+    for h in hunks {
+        any = true;
         match h.status {
             HunkStatus::Staged => {
                 any_staged = true;
@@ -82,7 +247,9 @@ fn file_review_status(file: &FileDiff) -> (&'static str, Style) {
         }
     }
 
-    if all_staged {
+    if !any {
+        (" ", Style::default())
+    } else if all_staged {
         ("✓", Style::default().fg(theme::status_staged_fg()))
     } else if all_done {
         ("●", Style::default().fg(theme::status_edited_fg()))
@@ -1,96 +1,273 @@
 use ratatui::Frame;
 use ratatui::layout::{Constraint, Layout, Rect};
-use ratatui::style::{Color, Modifier, Style};
+use ratatui::style::{Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Clear, Paragraph};
 
-/// Render a centered help overlay listing all keybindings.
-pub fn render(frame: &mut Frame, area: Rect) {
-    // Size: 60 wide, 22 tall, centered
+use crate::app::App;
+use crate::ui::theme;
+
+/// When an action in the help can be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Availability {
+    Always,
+    /// Needs staging: not in `--no-stage` or patch mode without `--apply`.
+    Staging,
+    /// Needs the working tree: not in patch mode.
+    Worktree,
+    /// Needs the commits of a `--range` review.
+    Range,
+}
+
+impl Availability {
+    /// Whether the action can be used in `app`'s current mode.
+    pub fn is_available(self, app: &App) -> bool {
+        match self {
+            Availability::Always => true,
+            Availability::Staging => !app.no_stage,
+            Availability::Worktree => !app.reviewing_patch,
+            Availability::Range => !app.commits.is_empty(),
+        }
+    }
+}
+
+/// One entry of the help: the keys and what they do.
+#[derive(Debug, Clone, Copy)]
+pub struct Binding {
+    pub keys: &'static str,
+    pub action: &'static str,
+    pub availability: Availability,
+}
+
+const fn bind(keys: &'static str, action: &'static str) -> Binding {
+    Binding {
+        keys,
+        action,
+        availability: Availability::Always,
+    }
+}
+
+const fn bind_if(keys: &'static str, action: &'static str, availability: Availability) -> Binding {
+    Binding {
+        keys,
+        action,
+        availability,
+    }
+}
+
+/// Every keybinding, by section, as listed in the help overlay.
+pub const SECTIONS: &[(&str, &[Binding])] = &[
+    (
+        "Navigation",
+        &[
+            bind(
+                "j / k",
+                "Scroll diff (DiffView) / Navigate files (FileList)",
+            ),
+            bind("J / K  { / }", "Next / previous hunk"),
+            bind("] / [", "Next / previous pending hunk"),
+            bind("H / L", "Previous / next file"),
+            bind(
+                "Ctrl+o / Ctrl+i",
+                "Back / forward through jumps (or Ctrl+n)",
+            ),
+            bind("h / l", "Focus file list / diff view"),
+            bind("gg / G", "Scroll to top / bottom (also Home / End)"),
+            bind("Ctrl+d / Ctrl+u", "Half-page down / up"),
+            bind("Ctrl+f / Ctrl+b", "Full-page down / up (also PgDn / PgUp)"),
+            bind("Shift+← / Shift+→", "Scroll long lines left / right"),
+            bind("Tab", "Toggle panel focus"),
+            bind("t", "Toggle directory tree in file list"),
+            bind("Enter / ← / →", "Toggle / collapse / expand directory"),
+            bind("f", "Filter the file list"),
+            bind("i", "Toggle statistics panel"),
+            bind("↑ / ↓", "Navigate hunks/files"),
+            bind("v", "Line mode: j/k move a line cursor"),
+            bind("V", "Line mode: select lines from the cursor"),
+        ],
+    ),
+    (
+        "Actions",
+        &[
+            bind_if("y", "Stage hunk (line in line mode)", Availability::Staging),
+            bind("n", "Skip hunk"),
+            bind("W", "Skip all whitespace-only hunks"),
+            bind("space", "Mark/unmark hunk"),
+            bind_if("Y", "Stage all marked hunks", Availability::Staging),
+            bind("s", "Split hunk (at the cursor in line mode)"),
+            bind("S", "Toggle side-by-side view"),
+            bind("w", "Wrap long lines"),
+            bind("+/-", "Expand/collapse context"),
+            bind("e", "Edit hunk (then a: apply and stage, f: feedback)"),
+            bind("c", "Comment on hunk (on the file in the file list)"),
+            bind("C", "Quick comment on a line"),
+            bind("R", "Comment on the whole review"),
+            bind("F", "List feedback (d: delete, Enter: go to)"),
+            bind("M", "Message history"),
+            bind_if(
+                "O",
+                "List commits (c: comment on a commit)",
+                Availability::Range,
+            ),
+            bind_if("o", "Open file at hunk in editor", Availability::Worktree),
+            bind_if(
+                "r",
+                "Refresh diff, keeping review state",
+                Availability::Worktree,
+            ),
+            bind("p", "Show unaddressed previous comment"),
+            bind_if(
+                "b",
+                "Toggle blame of the hunk's old lines",
+                Availability::Worktree,
+            ),
+            bind_if(
+                "D",
+                "Hunk details: commits behind its old lines",
+                Availability::Worktree,
+            ),
+            bind(
+                ":",
+                "Command line, Tab completes (:w :files :stage-file ...)",
+            ),
+            bind(
+                "q",
+                "Quit (asks while hunks are pending; --verdict asks for a verdict)",
+            ),
+        ],
+    ),
+    (
+        "Help",
+        &[
+            bind("j / k  ↓ / ↑", "Scroll this help"),
+            bind("/", "Search this help (Enter keeps, Esc clears)"),
+        ],
+    ),
+];
+
+/// The help's sections with only the bindings matching `query` (case
+/// insensitive, on keys or action); sections left empty are dropped.
+pub fn matching_sections(query: &str) -> Vec<(&'static str, Vec<Binding>)> {
+    let query = query.to_lowercase();
+    SECTIONS
+        .iter()
+        .map(|(title, bindings)| {
+            let matching: Vec<Binding> = bindings
+                .iter()
+                .filter(|b| {
+                    b.keys.to_lowercase().contains(&query)
+                        || b.action.to_lowercase().contains(&query)
+                })
+                .copied()
+                .collect();
+            (*title, matching)
+        })
+        .filter(|(_, bindings)| !bindings.is_empty())
+        .collect()
+}
+
+/// Render a centered, scrollable help overlay listing all keybindings.
+/// Actions unavailable in the current mode are grayed out. Clamps
+/// `app.help_scroll` to the content.
+pub fn render(frame: &mut Frame, area: Rect, app: &mut App) {
+    // Size: 60 wide, 40 tall, centered
     let width = 60u16.min(area.width.saturating_sub(4));
-    let height = 22u16.min(area.height.saturating_sub(2));
+    let height = 40u16.min(area.height.saturating_sub(2));
     let overlay = centered_rect(width, height, area);
 
     // Clear the area behind the overlay
     frame.render_widget(Clear, overlay);
 
     let title_style = Style::default()
-        .fg(Color::Cyan)
+        .fg(theme::hunk_header_fg())
         .add_modifier(Modifier::BOLD);
     let key_style = Style::default()
-        .fg(Color::Yellow)
+        .fg(theme::file_header_fg())
         .add_modifier(Modifier::BOLD);
-    let desc_style = Style::default().fg(Color::White);
+    let desc_style = Style::default().fg(theme::selected_fg());
+    let unavailable_style = Style::default().fg(theme::border_unfocused());
     let section_style = Style::default()
-        .fg(Color::Green)
+        .fg(theme::added_fg())
         .add_modifier(Modifier::BOLD);
     let footer_style = Style::default()
-        .fg(Color::DarkGray)
+        .fg(theme::border_unfocused())
         .add_modifier(Modifier::ITALIC);
 
     // Inner width is overlay width minus 2 for borders
     let inner_width = width.saturating_sub(2) as usize;
 
-    let lines = vec![
+    let mut lines = vec![
         centered_line("Keyboard Shortcuts", title_style, inner_width),
         Line::from(""),
-        centered_line("── Navigation ──", section_style, inner_width),
-        key_line(
-            "j / k",
-            "Scroll diff (DiffView) / Navigate files (FileList)",
-            key_style,
-            desc_style,
-        ),
-        key_line(
-            "J / K  { / }",
-            "Next / previous hunk",
-            key_style,
-            desc_style,
-        ),
-        key_line("H / L", "Previous / next file", key_style, desc_style),
-        key_line(
-            "h / l",
-            "Focus file list / diff view",
-            key_style,
-            desc_style,
-        ),
-        key_line("gg", "Scroll to top", key_style, desc_style),
-        key_line("G", "Scroll to bottom", key_style, desc_style),
-        key_line(
-            "Ctrl+d / Ctrl+u",
-            "Half-page down / up",
-            key_style,
-            desc_style,
-        ),
-        key_line(
-            "Ctrl+f / Ctrl+b",
-            "Full-page down / up",
-            key_style,
-            desc_style,
-        ),
-        key_line("Tab", "Toggle panel focus", key_style, desc_style),
-        key_line("↑ / ↓", "Navigate hunks/files", key_style, desc_style),
-        Line::from(""),
-        centered_line("── Actions ──", section_style, inner_width),
-        key_line("y", "Stage hunk", key_style, desc_style),
-        key_line("n", "Skip hunk", key_style, desc_style),
-        key_line("s", "Split hunk", key_style, desc_style),
-        key_line("e", "Edit hunk", key_style, desc_style),
-        key_line("c", "Comment on hunk", key_style, desc_style),
-        key_line("q", "Quit", key_style, desc_style),
-        Line::from(""),
-        centered_line("Press any key to start", footer_style, inner_width),
     ];
+    let sections = matching_sections(&app.help_query);
+    let all_available = SECTIONS
+        .iter()
+        .flat_map(|(_, bindings)| bindings.iter())
+        .all(|b| b.availability.is_available(app));
+    if !all_available {
+        lines.insert(
+            1,
+            centered_line(
+                "Grayed out: not available in this mode",
+                footer_style,
+                inner_width,
+            ),
+        );
+    }
+    if sections.is_empty() {
+        lines.push(centered_line("No matching keys", footer_style, inner_width));
+    }
+    for (i, (title, bindings)) in sections.iter().enumerate() {
+        if i > 0 {
+            lines.push(Line::from(""));
+        }
+        let heading = format!("── {} ──", title);
+        lines.push(centered_line(&heading, section_style, inner_width));
+        for binding in bindings {
+            lines.push(if binding.availability.is_available(app) {
+                key_line(binding.keys, binding.action, key_style, desc_style)
+            } else {
+                key_line(
+                    binding.keys,
+                    binding.action,
+                    unavailable_style,
+                    unavailable_style,
+                )
+            });
+        }
+    }
 
+    let title = if app.help_searching || !app.help_query.is_empty() {
+        format!(" Help /{} ", app.help_query)
+    } else {
+        " Help ".to_string()
+    };
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan))
-        .title(" Help ")
+        .border_style(Style::default().fg(theme::border_focused()))
+        .title(title)
         .title_style(title_style);
+    let inner = block.inner(overlay);
+    frame.render_widget(block, overlay);
+
+    // The footer stays put below the scrolling list
+    let [body, footer] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+    let max_scroll = lines.len().saturating_sub(body.height as usize);
+    app.help_scroll = app.help_scroll.min(max_scroll);
 
-    let paragraph = Paragraph::new(lines).block(block);
+    let paragraph = Paragraph::new(lines).scroll((app.help_scroll as u16, 0));
+    frame.render_widget(paragraph, body);
 
-    frame.render_widget(paragraph, overlay);
+    let hint = if app.help_searching {
+        "Type to search · Enter keep · Esc clear"
+    } else {
+        "j/k scroll · / search · any other key closes"
+    };
+    frame.render_widget(
+        Paragraph::new(centered_line(hint, footer_style, inner_width)),
+        footer,
+    );
 }
 
 fn key_line<'a>(key: &'a str, desc: &'a str, key_style: Style, desc_style: Style) -> Line<'a> {
@@ -108,7 +285,7 @@ fn centered_line(text: &str, style: Style, width: usize) -> Line<'static> {
 }
 
 /// Create a centered rect of given width and height within `area`.
-fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+pub fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
     let vertical = Layout::default()
         .direction(ratatui::layout::Direction::Vertical)
         .constraints([
@@ -0,0 +1,102 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+
+use crate::blame::{self, FileBlame};
+use crate::types::{FileDiff, Hunk};
+use crate::ui::help_overlay::centered_rect;
+use crate::ui::theme;
+
+/// Render the details of `hunk`: the commits that last changed its old
+/// lines, newest first, with those from the last two weeks highlighted.
+/// `blame` is `None` when the hunk has no old lines; `now` is in seconds
+/// since the epoch.
+pub fn render(
+    frame: &mut Frame,
+    area: Rect,
+    file: &FileDiff,
+    hunk: &Hunk,
+    blame: Option<&Result<FileBlame, String>>,
+    now: i64,
+) {
+    let heading = Style::default()
+        .fg(theme::hunk_header_fg())
+        .add_modifier(Modifier::BOLD);
+    let text = Style::default().fg(theme::selected_fg());
+    let dim = Style::default().fg(theme::border_unfocused());
+    let recent = Style::default()
+        .fg(theme::status_pending_fg())
+        .add_modifier(Modifier::BOLD);
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(" File  ", dim),
+            Span::styled(file.path.display().to_string(), text),
+        ]),
+        Line::from(vec![
+            Span::styled(" Hunk  ", dim),
+            Span::styled(hunk.header.clone(), text),
+        ]),
+        Line::default(),
+        Line::from(Span::styled(" Last changed by", heading)),
+    ];
+    let commits = match blame {
+        Some(Ok(blame)) => blame.hunk_commits(hunk),
+        Some(Err(e)) => {
+            lines.push(Line::from(Span::styled(
+                format!("   {}", e),
+                Style::default().fg(theme::removed_fg()),
+            )));
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+    if commits.is_empty() && !matches!(blame, Some(Err(_))) {
+        lines.push(Line::from(Span::styled(
+            "   No old lines: the hunk only adds code",
+            dim,
+        )));
+    }
+    for c in &commits {
+        let commit = c.commit;
+        let mut row = vec![Span::raw("   ")];
+        if commit.is_committed() {
+            let is_recent = now - commit.time < blame::RECENT_SECS;
+            row.push(Span::styled(
+                format!("{}  ", commit.id),
+                Style::default().fg(theme::hunk_header_fg()),
+            ));
+            row.push(Span::styled(
+                blame::format_age(commit.time, now),
+                if is_recent { recent } else { dim },
+            ));
+            row.push(Span::styled(format!("  {}  ", commit.author), dim));
+        }
+        row.push(Span::styled(commit.summary.clone(), text));
+        lines.push(Line::from(row));
+        lines.push(Line::from(Span::styled(
+            format!("     lines {}", blame::format_ranges(&c.lines)),
+            dim,
+        )));
+    }
+
+    let width = 90u16.min(area.width.saturating_sub(4));
+    // Long summaries wrap, so leave room for a few extra rows
+    let height = (lines.len() as u16 + 4).min(area.height.saturating_sub(2));
+    let panel = centered_rect(width, height, area);
+    frame.render_widget(Clear, panel);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme::border_focused_style())
+        .title(" Hunk details ")
+        .title_style(heading);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        panel,
+    );
+}
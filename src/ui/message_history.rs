@@ -0,0 +1,67 @@
+use std::time::Instant;
+
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem};
+
+use crate::messages::{MessageHistory, MessageLevel};
+use crate::ui::help_overlay::centered_rect;
+use crate::ui::theme;
+
+/// Render the message history (`M`): the messages of the review, newest
+/// first, each with its level and how long ago it was shown.
+pub fn render(frame: &mut Frame, area: Rect, history: &MessageHistory, now: Instant) {
+    let width = 100u16.min(area.width.saturating_sub(4));
+    let height = (history.len() as u16 + 2).clamp(3, area.height.saturating_sub(4).max(3));
+    let panel = centered_rect(width, height.min(area.height), area);
+    frame.render_widget(Clear, panel);
+
+    let dim = Style::default().fg(theme::border_unfocused());
+    let items: Vec<ListItem> = history
+        .newest_first()
+        .map(|message| {
+            let age = now.saturating_duration_since(message.shown_at).as_secs();
+            let age = match age {
+                0..60 => format!("{}s", age),
+                60..3600 => format!("{}m", age / 60),
+                _ => format!("{}h", age / 3600),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!(" {:>4} ", age), dim),
+                Span::styled(
+                    format!("{:<6}", message.level.label()),
+                    level_style(message.level),
+                ),
+                Span::styled(
+                    message.text.replace('\n', " "),
+                    Style::default().fg(theme::selected_fg()),
+                ),
+            ]))
+        })
+        .collect();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme::border_focused()))
+        .title(format!(" Messages ({}) ", history.len()))
+        .title_style(
+            Style::default()
+                .fg(theme::hunk_header_fg())
+                .add_modifier(Modifier::BOLD),
+        );
+    frame.render_widget(List::new(items).block(block), panel);
+}
+
+/// Color of a message of `level`: warnings like pending hunks, errors like
+/// removed lines.
+pub fn level_style(level: MessageLevel) -> Style {
+    match level {
+        MessageLevel::Info => theme::status_bar_style(),
+        MessageLevel::Warn => theme::status_bar_style().fg(theme::status_pending_fg()),
+        MessageLevel::Error => theme::status_bar_style()
+            .fg(theme::removed_fg())
+            .add_modifier(Modifier::BOLD),
+    }
+}
@@ -1,6 +1,16 @@
+pub mod blame_panel;
+pub mod comment_input;
+pub mod commits_panel;
 pub mod diff_view;
+pub mod feedback_panel;
 pub mod file_list;
 pub mod help_overlay;
+pub mod hunk_details;
+pub mod message_history;
+pub mod overview;
+pub mod quit_dialog;
+pub mod stage_error;
+pub mod stats_panel;
 pub mod status_bar;
 pub mod theme;
 
@@ -8,7 +18,8 @@ use ratatui::Frame;
 use ratatui::layout::{Constraint, Direction, Layout};
 
 use crate::app::App;
-use crate::highlight::Highlighter;
+use crate::highlight::{self, Highlighter};
+use crate::stats;
 use crate::types::AppMode;
 
 /// Render the full TUI layout.
@@ -36,42 +47,116 @@ pub fn render(frame: &mut Frame, app: &mut App, highlighter: &Highlighter) {
     let file_list_area = main_chunks[0];
     let diff_view_area = main_chunks[1];
 
+    // The overview takes the whole screen until the review starts
+    if app.mode == AppMode::Overview {
+        overview::render(frame, main_area, app);
+        status_bar::render(
+            frame,
+            status_area,
+            &app.files,
+            app.mode,
+            app.message.as_ref(),
+            app.no_stage,
+            "",
+        );
+        return;
+    }
+
     // Store areas for mouse click mapping and page scroll calculations
     app.file_list_area = file_list_area;
     app.diff_view_area = diff_view_area;
+    // The content may have shrunk (staging, a smaller terminal)
+    app.clamp_scroll();
+    app.sync_file_list_offset();
 
     // Render file list
-    file_list::render(
-        frame,
-        file_list_area,
-        &app.files,
-        app.selected_file,
-        app.focus == crate::types::FocusPanel::FileList,
-    );
-
-    // Rebuild highlight cache if needed
-    let needs_rebuild = match &app.highlight_cache {
-        Some((idx, _)) => *idx != app.selected_file,
-        None => true,
+    let title = file_list::title(&app.file_filter, app.shown_files().len(), app.files.len());
+    let panel = file_list::Panel {
+        title: &title,
+        offset: app.file_list_offset,
+        focused: app.focus == crate::types::FocusPanel::FileList,
     };
-    if needs_rebuild && let Some(file) = app.current_file() {
-        let path_str = file.path.to_string_lossy().to_string();
-        let lines = highlighter.highlight_file_lines(&path_str, &file.hunks);
-        app.highlight_cache = Some((app.selected_file, lines));
+    if app.tree_view {
+        let rows = app.file_tree_rows();
+        file_list::render_tree(
+            frame,
+            file_list_area,
+            &panel,
+            &app.files,
+            &rows,
+            app.file_tree_cursor(&rows),
+        );
+    } else {
+        file_list::render(
+            frame,
+            file_list_area,
+            &panel,
+            &app.files,
+            &app.shown_files(),
+            app.selected_file,
+        );
     }
-    let cached = app.highlight_cache.as_ref().map(|(_, lines)| lines);
 
-    // Render diff view
-    let current_file = app.current_file();
-    diff_view::render(
-        frame,
-        diff_view_area,
-        current_file,
-        app.selected_hunk,
-        app.scroll_offset,
-        app.focus == crate::types::FocusPanel::DiffView,
-        cached,
-    );
+    // Highlight the selected file as far as the diff view reaches. A line
+    // on screen row `r` is at most line `2r` of the file (side-by-side rows
+    // hold two lines), so that bounds what has to be highlighted. Large
+    // files only have the hunks on screen highlighted.
+    let top = app.scroll_offset as usize;
+    let bottom = top + diff_view_area.height as usize;
+    let visible: Vec<(usize, usize)> = app
+        .visible_hunks(top..bottom)
+        .into_iter()
+        .map(|(hunk, rows)| (hunk, 2 * rows))
+        .collect();
+    if let Some(file) = app.files.get(app.selected_file) {
+        let cache = app.highlight_cache.viewed(app.selected_file);
+        let path_str = file.path.to_string_lossy();
+        if highlight::is_large(&file.hunks) {
+            highlighter.highlight_hunks(&path_str, &file.hunks, cache, &visible);
+        } else {
+            highlighter.highlight_more(&path_str, &file.hunks, cache, 2 * bottom);
+        }
+        app.highlight_cache.trim(&visible);
+    }
+    // Now the cache holds the selected file, pack the others not viewed
+    app.pack_unviewed_files();
+
+    // Render diff view, with the inline comment box below it when typing
+    if app.mode == AppMode::InlineComment {
+        let parts = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(3),
+                Constraint::Length(comment_input::HEIGHT),
+            ])
+            .split(diff_view_area);
+        diff_view::render(frame, parts[0], app);
+        comment_input::render(
+            frame,
+            parts[1],
+            &app.comment_input,
+            app.comment_anchor_line(),
+        );
+    } else {
+        diff_view::render(frame, diff_view_area, app);
+    }
+
+    if app.show_blame
+        && let Some(file) = app.current_file()
+        && let Some(hunk) = file.hunks.get(app.selected_hunk)
+    {
+        blame_panel::render(
+            frame,
+            diff_view_area,
+            app.blame.get(&file.path),
+            hunk,
+            unix_now(),
+        );
+    }
+
+    if app.show_stats {
+        stats_panel::render(frame, diff_view_area, &stats::compute(&app.files));
+    }
 
     // Render status bar
     status_bar::render(
@@ -79,12 +164,70 @@ pub fn render(frame: &mut Frame, app: &mut App, highlighter: &Highlighter) {
         status_area,
         &app.files,
         app.mode,
-        app.message.as_deref(),
+        app.message.as_ref(),
         app.no_stage,
+        if app.mode == AppMode::FileFilter {
+            &app.file_filter
+        } else {
+            &app.command_input
+        },
     );
 
     // Render help overlay on top of everything
     if app.mode == AppMode::Help {
-        help_overlay::render(frame, frame.area());
+        help_overlay::render(frame, frame.area(), app);
+    }
+    if app.mode == AppMode::MessageHistory {
+        message_history::render(
+            frame,
+            frame.area(),
+            &app.message_history,
+            std::time::Instant::now(),
+        );
+    }
+    if app.mode == AppMode::StageError
+        && let Some((_, failure)) = &app.stage_failure
+    {
+        stage_error::render(frame, frame.area(), failure);
+    }
+    if app.mode == AppMode::HunkDetails
+        && let Some(file) = app.current_file()
+        && let Some(hunk) = file.hunks.get(app.selected_hunk)
+    {
+        hunk_details::render(
+            frame,
+            frame.area(),
+            file,
+            hunk,
+            app.blame.get(&file.path),
+            unix_now(),
+        );
     }
+    if app.mode == AppMode::FeedbackPanel {
+        feedback_panel::render(frame, frame.area(), &app.feedback, app.feedback_cursor);
+    }
+    if app.mode == AppMode::CommitsPanel {
+        commits_panel::render(
+            frame,
+            frame.area(),
+            &app.commits,
+            &app.feedback,
+            app.commit_cursor,
+        );
+    }
+    if app.mode == AppMode::ConfirmQuit {
+        quit_dialog::render(
+            frame,
+            frame.area(),
+            app.pending_hunk_count(),
+            app.feedback.len(),
+        );
+    }
+}
+
+/// The current time in seconds since the epoch, for commit ages.
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
 }
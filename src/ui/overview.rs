@@ -0,0 +1,78 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState};
+
+use crate::app::App;
+use crate::stats;
+use crate::ui::theme;
+
+/// Render the file overview (`--overview`): every changed file with its
+/// added/removed line counts, and whether it will be reviewed.
+pub fn render(frame: &mut Frame, area: Rect, app: &App) {
+    let total = stats::compute(&app.files);
+    let included = app.files.len() - app.overview_excluded.len();
+    let title = format!(
+        " Overview: {} of {} files, +{} -{} ",
+        included,
+        app.files.len(),
+        total.added,
+        total.removed
+    );
+
+    // Right-align the counts after the longest path
+    let width = app
+        .files
+        .iter()
+        .map(|f| f.path.to_string_lossy().chars().count())
+        .max()
+        .unwrap_or(0);
+    let dim = Style::default().fg(theme::border_unfocused());
+    let items: Vec<ListItem> = app
+        .files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| {
+            let counts = stats::compute(std::slice::from_ref(file));
+            let excluded = app.overview_excluded.contains(&i);
+            let (mark, path_style) = if excluded {
+                ("[ ]", dim.add_modifier(Modifier::CROSSED_OUT))
+            } else {
+                ("[x]", Style::default().fg(theme::selected_fg()))
+            };
+            let path_style = if i == app.overview_cursor {
+                path_style.add_modifier(Modifier::BOLD)
+            } else {
+                path_style
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!(" {} ", mark), path_style),
+                Span::styled(
+                    format!("{:<width$}", file.path.to_string_lossy(), width = width),
+                    path_style,
+                ),
+                Span::styled(
+                    format!("  +{:<5}", counts.added),
+                    Style::default().fg(theme::added_fg()),
+                ),
+                Span::styled(
+                    format!("-{:<5}", counts.removed),
+                    Style::default().fg(theme::removed_fg()),
+                ),
+                Span::styled(format!("{} hunk(s)", counts.hunks), dim),
+            ]))
+        })
+        .collect();
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(theme::border_focused_style());
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(theme::selected_style());
+    let mut state = ListState::default();
+    state.select(Some(app.overview_cursor));
+    frame.render_stateful_widget(list, area, &mut state);
+}
@@ -0,0 +1,55 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::ui::help_overlay::centered_rect;
+use crate::ui::theme;
+
+/// Render the quit confirmation: what would be left behind and the keys
+/// to quit or keep reviewing.
+pub fn render(frame: &mut Frame, area: Rect, pending: usize, feedback: usize) {
+    let width = 50u16.min(area.width.saturating_sub(4));
+    let height = if feedback > 0 { 8 } else { 7 };
+    let dialog = centered_rect(width, height.min(area.height), area);
+    frame.render_widget(Clear, dialog);
+
+    let key_style = Style::default()
+        .fg(theme::file_header_fg())
+        .add_modifier(Modifier::BOLD);
+    let desc_style = Style::default().fg(theme::selected_fg());
+    let key_line = |key: &'static str, desc: &'static str| {
+        Line::from(vec![
+            Span::styled(format!("  {:<8}", key), key_style),
+            Span::styled(desc, desc_style),
+        ])
+    };
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!(
+                " {} pending hunk(s), {} feedback item(s)",
+                pending, feedback
+            ),
+            desc_style,
+        )),
+        Line::from(""),
+        key_line("y / q", "Quit and write the feedback"),
+    ];
+    if feedback > 0 {
+        lines.push(key_line("d", "Quit and discard the feedback"));
+    }
+    lines.push(key_line("n / Esc", "Keep reviewing"));
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme::border_focused()))
+        .title(" Quit? ")
+        .title_style(
+            Style::default()
+                .fg(theme::hunk_header_fg())
+                .add_modifier(Modifier::BOLD),
+        );
+    frame.render_widget(Paragraph::new(lines).block(block), dialog);
+}
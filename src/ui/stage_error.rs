@@ -0,0 +1,72 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+
+use crate::staging::StageFailure;
+use crate::ui::help_overlay::centered_rect;
+use crate::ui::theme;
+
+/// Render the details of a staging failure: the hunk, the full error
+/// chain, and what might get past it.
+pub fn render(frame: &mut Frame, area: Rect, failure: &StageFailure) {
+    let heading = Style::default()
+        .fg(theme::hunk_header_fg())
+        .add_modifier(Modifier::BOLD);
+    let text = Style::default().fg(theme::selected_fg());
+    let dim = Style::default().fg(theme::border_unfocused());
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(" File  ", dim),
+            Span::styled(failure.path.display().to_string(), text),
+        ]),
+        Line::from(vec![
+            Span::styled(" Hunk  ", dim),
+            Span::styled(failure.hunk_header.clone(), text),
+        ]),
+        Line::default(),
+        Line::from(Span::styled(" Error", heading)),
+    ];
+    for (i, cause) in failure.causes.iter().enumerate() {
+        let prefix = if i == 0 { "   " } else { "   caused by: " };
+        lines.push(Line::from(vec![
+            Span::styled(prefix, dim),
+            Span::styled(cause.clone(), Style::default().fg(theme::removed_fg())),
+        ]));
+    }
+    if !failure.remedies.is_empty() {
+        lines.push(Line::default());
+        lines.push(Line::from(Span::styled(" What to do", heading)));
+        for remedy in &failure.remedies {
+            lines.push(Line::from(vec![
+                Span::styled("   • ", dim),
+                Span::styled(*remedy, text),
+            ]));
+        }
+    }
+    lines.push(Line::default());
+    lines.push(Line::from(Span::styled(
+        " The hunk is still pending: y stages it again",
+        dim,
+    )));
+
+    let width = 90u16.min(area.width.saturating_sub(4));
+    // Long causes wrap, so leave room for a few extra rows
+    let height = (lines.len() as u16 + 6).min(area.height.saturating_sub(2));
+    let panel = centered_rect(width, height, area);
+    frame.render_widget(Clear, panel);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme::removed_fg()))
+        .title(" Stage error ")
+        .title_style(heading);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        panel,
+    );
+}
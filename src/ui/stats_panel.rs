@@ -0,0 +1,89 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::stats::ReviewStats;
+use crate::ui::theme;
+
+/// Panel size, including borders.
+const WIDTH: u16 = 30;
+const HEIGHT: u16 = 14;
+
+/// Render the statistics panel in the top-right corner of `area` (the diff
+/// view).
+pub fn render(frame: &mut Frame, area: Rect, stats: &ReviewStats) {
+    let width = WIDTH.min(area.width);
+    let height = HEIGHT.min(area.height);
+    let panel = Rect {
+        x: area.x + area.width - width,
+        y: area.y,
+        width,
+        height,
+    };
+
+    let label_style = Style::default().fg(theme::context_fg());
+    let row = |label: &'static str, value: String, style: Style| {
+        Line::from(vec![
+            Span::styled(format!(" {:<12}", label), label_style),
+            Span::styled(value, style),
+        ])
+    };
+    let count_style = |color| Style::default().fg(color).add_modifier(Modifier::BOLD);
+
+    let lines = vec![
+        row(
+            "Reviewed",
+            format!(
+                "{}/{} ({}%)",
+                stats.reviewed(),
+                stats.hunks,
+                stats.percent_reviewed()
+            ),
+            count_style(theme::file_header_fg()),
+        ),
+        Line::from(""),
+        row("Files", stats.files.to_string(), label_style),
+        row("Hunks", stats.hunks.to_string(), label_style),
+        row(
+            "Lines",
+            format!("+{} -{}", stats.added, stats.removed),
+            label_style,
+        ),
+        Line::from(""),
+        row(
+            "Pending",
+            stats.pending.to_string(),
+            count_style(theme::status_pending_fg()),
+        ),
+        row(
+            "Staged",
+            stats.staged.to_string(),
+            count_style(theme::status_staged_fg()),
+        ),
+        row(
+            "Skipped",
+            stats.skipped.to_string(),
+            count_style(theme::status_skipped_fg()),
+        ),
+        row(
+            "Commented",
+            stats.commented.to_string(),
+            count_style(theme::status_commented_fg()),
+        ),
+        row(
+            "Edited",
+            stats.edited.to_string(),
+            count_style(theme::status_edited_fg()),
+        ),
+    ];
+
+    let block = Block::default()
+        .title(" Statistics (i) ")
+        .borders(Borders::ALL)
+        .border_style(theme::border_focused_style());
+
+    frame.render_widget(Clear, panel);
+    frame.render_widget(Paragraph::new(lines).block(block), panel);
+}
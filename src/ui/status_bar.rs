@@ -3,37 +3,125 @@ use ratatui::layout::Rect;
 use ratatui::text::{Line, Span};
 use ratatui::widgets::Paragraph;
 
+use crate::diff;
+use crate::messages::Message;
+use crate::stats;
 use crate::types::{AppMode, FileDiff, HunkStatus};
+use crate::ui::message_history::level_style;
 use crate::ui::theme;
 
-/// Render the status bar at the bottom of the screen.
+/// Render the status bar at the bottom of the screen. `input` is the text
+/// typed in `Command` or `FileFilter` mode. While browsing, `message` is
+/// colored by its level.
 pub fn render(
     frame: &mut Frame,
     area: Rect,
     files: &[FileDiff],
     mode: AppMode,
-    message: Option<&str>,
+    message: Option<&Message>,
     no_stage: bool,
+    input: &str,
 ) {
+    let level = message.map(|m| m.level);
+    let message = message.map(|m| m.text.as_str());
     let line = match mode {
         AppMode::WaitingForEditor => Line::from(vec![
             Span::styled(" Editing in split pane... ", theme::status_bar_style()),
             Span::styled("(waiting for editor to close)", theme::status_bar_style()),
         ]),
         AppMode::Help => Line::from(Span::styled(
-            " Press any key to dismiss help ",
+            " j/k scroll · / search · any other key dismisses help ",
+            theme::status_bar_style(),
+        )),
+        AppMode::ResumePrompt => Line::from(Span::styled(
+            " Unfinished review from a previous session found. Resume? (y/n) ",
+            theme::status_bar_style(),
+        )),
+        AppMode::RecoveryPrompt => Line::from(Span::styled(
+            format!(
+                " {} ",
+                message.unwrap_or("Recover unsaved editor content? (y/n)")
+            ),
+            theme::status_bar_style(),
+        )),
+        AppMode::Overview => Line::from(Span::styled(
+            format!(
+                " {}space: include/exclude file · a: include all · Enter: start review · q: quit ",
+                message.map(|m| format!("{} · ", m)).unwrap_or_default()
+            ),
+            theme::status_bar_style(),
+        )),
+        AppMode::BulkStagePrompt => Line::from(Span::styled(
+            format!(" {} (y/n) ", message.unwrap_or("Stage the matching hunks?")),
+            theme::status_bar_style(),
+        )),
+        AppMode::MessageHistory => Line::from(Span::styled(
+            " Newest first · M/Esc: close ",
+            theme::status_bar_style(),
+        )),
+        AppMode::StageError => Line::from(Span::styled(
+            " Enter: go to hunk · Esc: close ",
+            theme::status_bar_style(),
+        )),
+        AppMode::HunkDetails => Line::from(Span::styled(
+            " Newest first · D/Esc: close ",
+            theme::status_bar_style(),
+        )),
+        AppMode::FeedbackPanel => Line::from(Span::styled(
+            " j/k: move · d: delete · Enter: go to hunk · Esc: close ",
+            theme::status_bar_style(),
+        )),
+        AppMode::CommitsPanel => Line::from(Span::styled(
+            " j/k: move · c/Enter: comment on commit · Esc: close ",
+            theme::status_bar_style(),
+        )),
+        AppMode::ConfirmQuit => Line::from(Span::styled(
+            " Quit? (y: quit, n: keep reviewing) ",
+            theme::status_bar_style(),
+        )),
+        AppMode::StageEditPrompt => Line::from(Span::styled(
+            " Edit captured. a: apply to the working tree and stage · f: keep as feedback ",
+            theme::status_bar_style(),
+        )),
+        AppMode::VerdictPrompt => Line::from(Span::styled(
+            " Verdict? a: approve · r: request changes · c: comment · s: skip · Esc: keep reviewing ",
+            theme::status_bar_style(),
+        )),
+        AppMode::DeletionPrompt => Line::from(Span::styled(
+            format!(
+                " Stage the deletion of {}? (y: remove it from the index, n: keep reviewing) ",
+                message.unwrap_or("this file")
+            ),
+            theme::status_bar_style(),
+        )),
+        // Completions or the argument of the command, after the cursor
+        AppMode::Command => Line::from(Span::styled(
+            match message {
+                Some(hint) => format!(":{}█  {}", input, hint),
+                None => format!(":{}█", input),
+            },
+            theme::status_bar_style(),
+        )),
+        AppMode::FileFilter => Line::from(Span::styled(
+            format!(" Filter files: {}█  (Enter: keep, Esc: clear) ", input),
+            theme::status_bar_style(),
+        )),
+        AppMode::InlineComment => Line::from(Span::styled(
+            " Type a comment for the highlighted line (Enter: save, Esc: cancel) ",
             theme::status_bar_style(),
         )),
         AppMode::Browsing => {
-            if let Some(msg) = message {
-                Line::from(Span::styled(
-                    format!(" {} ", msg),
-                    theme::status_bar_style(),
-                ))
+            if let Some((msg, level)) = message.zip(level) {
+                Line::from(Span::styled(format!(" {} ", msg), level_style(level)))
             } else {
-                let progress = compute_progress(files);
+                let stats = stats::compute(files);
                 let y_label = if no_stage { "y:accept" } else { "y:stage" };
-                Line::from(vec![
+                let whitespace_only = files
+                    .iter()
+                    .flat_map(|f| &f.hunks)
+                    .filter(|h| h.status == HunkStatus::Pending && diff::is_whitespace_only(h))
+                    .count();
+                let mut spans = vec![
                     Span::styled(
                         format!(
                             " {}  n:skip  s:split  e:edit  c:comment  q:quit  ?:help ",
@@ -42,10 +130,30 @@ pub fn render(
                         theme::status_bar_style(),
                     ),
                     Span::styled(
-                        format!(" [{}/{}] ", progress.0, progress.1),
+                        format!(
+                            " [{}/{} {}%  +{} -{}]  i:stats ",
+                            stats.reviewed(),
+                            stats.hunks,
+                            stats.percent_reviewed(),
+                            stats.added,
+                            stats.removed
+                        ),
                         theme::status_bar_style(),
                     ),
-                ])
+                ];
+                if stats.pending > 0 {
+                    spans.push(Span::styled(
+                        format!(" {} pending  ]:next ", stats.pending),
+                        theme::status_bar_style().fg(theme::status_pending_fg()),
+                    ));
+                }
+                if whitespace_only > 0 {
+                    spans.push(Span::styled(
+                        format!(" W:skip {} whitespace-only ", whitespace_only),
+                        theme::status_bar_style().fg(theme::status_pending_fg()),
+                    ));
+                }
+                Line::from(spans)
             }
         }
     };
@@ -53,14 +161,3 @@ pub fn render(
     let paragraph = Paragraph::new(line);
     frame.render_widget(paragraph, area);
 }
-
-/// Compute (reviewed_hunks, total_hunks) for progress display.
-fn compute_progress(files: &[FileDiff]) -> (usize, usize) {
-    let total: usize = files.iter().map(|f| f.hunks.len()).sum();
-    let reviewed: usize = files
-        .iter()
-        .flat_map(|f| &f.hunks)
-        .filter(|h| h.status != HunkStatus::Pending)
-        .count();
-    (reviewed, total)
-}
@@ -1,5 +1,5 @@
 use ratatui::style::{Color, Modifier, Style};
-use std::sync::OnceLock;
+use std::sync::{LazyLock, RwLock};
 
 // --- Theme infrastructure ---
 
@@ -126,39 +126,81 @@ impl ThemeColors {
     }
 }
 
-/// Global active theme, initialised once at startup.
-static THEME: OnceLock<ThemeColors> = OnceLock::new();
+static DARK: LazyLock<ThemeColors> = LazyLock::new(ThemeColors::dark);
+static LIGHT: LazyLock<ThemeColors> = LazyLock::new(ThemeColors::light);
 
-/// Initialise the global theme. Call once from main before the TUI starts.
-/// Accepts the `--theme` CLI value: "dark", "light", or "auto"/"default".
+/// Global active theme variant. Set at startup and switchable at runtime
+/// (`:theme`); `None` until `init()` is called.
+static ACTIVE: RwLock<Option<ThemeVariant>> = RwLock::new(None);
+
+/// Resolve a `--theme` value: "dark", "light", or "auto"/"default".
+/// Returns `None` for unknown names.
+pub fn resolve(name: &str) -> Option<ThemeVariant> {
+    match name {
+        "light" => Some(ThemeVariant::Light),
+        "dark" => Some(ThemeVariant::Dark),
+        "auto" | "default" => Some(detect_variant()),
+        _ => None,
+    }
+}
+
+/// Auto-detect via COLORFGBG (set by many terminals).
+/// Format: "fg;bg" — bg >= 8 usually means light background.
+fn detect_variant() -> ThemeVariant {
+    let bg = std::env::var("COLORFGBG")
+        .ok()
+        .and_then(|val| val.rsplit(';').next().and_then(|s| s.parse::<u8>().ok()));
+    match bg {
+        Some(bg) if bg >= 8 => ThemeVariant::Light,
+        _ => ThemeVariant::Dark,
+    }
+}
+
+/// Syntect theme picked by name (`--theme`, `:theme`), used instead of the
+/// palette's own.
+static SYNTAX_THEME: RwLock<Option<String>> = RwLock::new(None);
+
+/// Initialise the global theme from main before the TUI starts.
+/// Accepts the `--theme` CLI value; unknown names fall back to auto-detection.
 pub fn init(name: &str) {
-    let colors = match name {
-        "light" => ThemeColors::light(),
-        "dark" => ThemeColors::dark(),
-        _ => {
-            // Auto-detect via COLORFGBG (set by many terminals).
-            // Format: "fg;bg" — bg >= 8 usually means light background.
-            if let Ok(val) = std::env::var("COLORFGBG") {
-                if let Some(bg) = val.rsplit(';').next().and_then(|s| s.parse::<u8>().ok()) {
-                    if bg >= 8 {
-                        ThemeColors::light()
-                    } else {
-                        ThemeColors::dark()
-                    }
-                } else {
-                    ThemeColors::dark()
-                }
-            } else {
-                ThemeColors::dark()
-            }
-        }
+    if select(name).is_none() {
+        set_variant(detect_variant());
+    }
+}
+
+/// Switch to the theme `name`: a palette (see [`resolve`]) or a syntect
+/// theme, built in or from `~/.config/stagent/themes`. A syntect theme
+/// comes with the palette matching its background. Returns the palette, or
+/// `None` for unknown names.
+pub fn select(name: &str) -> Option<ThemeVariant> {
+    let (variant, syntax_theme) = match resolve(name) {
+        Some(variant) => (variant, None),
+        None => (
+            crate::highlight::theme_variant(name)?,
+            Some(name.to_string()),
+        ),
     };
-    let _ = THEME.set(colors);
+    set_variant(variant);
+    if let Ok(mut active) = SYNTAX_THEME.write() {
+        *active = syntax_theme;
+    }
+    Some(variant)
+}
+
+/// Switch the active theme.
+pub fn set_variant(variant: ThemeVariant) {
+    if let Ok(mut active) = ACTIVE.write() {
+        *active = Some(variant);
+    }
 }
 
 /// Return the active theme. Falls back to dark if `init()` was not called.
 pub fn current() -> &'static ThemeColors {
-    THEME.get_or_init(ThemeColors::dark)
+    let variant = ACTIVE.read().ok().and_then(|v| *v);
+    match variant {
+        Some(ThemeVariant::Light) => &LIGHT,
+        _ => &DARK,
+    }
 }
 
 // --- Accessor functions (replace the old constants) ---
@@ -225,8 +267,12 @@ pub fn border_unfocused() -> Color {
 }
 
 /// Name of the syntect theme to use for syntax highlighting.
-pub fn syntect_theme() -> &'static str {
-    current().syntect_theme
+pub fn syntect_theme() -> String {
+    SYNTAX_THEME
+        .read()
+        .ok()
+        .and_then(|name| name.clone())
+        .unwrap_or_else(|| current().syntect_theme.to_string())
 }
 
 // --- Style helpers ---
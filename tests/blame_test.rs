@@ -0,0 +1,103 @@
+mod helpers;
+
+use git2::{Repository, Signature};
+use stagent::blame::blame_file;
+use stagent::git::get_unstaged_diff;
+use std::path::Path;
+
+/// Commit `content` to `path` with a custom message and author.
+fn commit_as(repo: &Repository, author: &str, path: &str, content: &str, message: &str) {
+    std::fs::write(repo.workdir().unwrap().join(path), content).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(path)).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = Signature::now(author, "test@test.com").unwrap();
+    let parent = repo.head().unwrap().peel_to_commit().unwrap();
+    repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])
+        .unwrap();
+}
+
+#[test]
+fn test_blame_groups_old_lines_by_commit() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\ntwo\nthree\nfour\n");
+    commit_as(
+        &repo,
+        "Other",
+        "a.txt",
+        "one\nTWO\nthree\nfour\n",
+        "Shout two\n\nBody",
+    );
+
+    // Staged but not committed: the old side of the unstaged diff
+    std::fs::write(
+        repo.workdir().unwrap().join("a.txt"),
+        "one\nTWO\nthree\nFOUR\n",
+    )
+    .unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("a.txt")).unwrap();
+    index.write().unwrap();
+    helpers::modify_file(&repo, "a.txt", "1\n2\n3\n4\n");
+
+    let files = get_unstaged_diff(&repo).unwrap();
+    let blame = blame_file(&repo, &files[0].path).unwrap();
+    let ranges: Vec<_> = blame
+        .hunk_ranges(&files[0].hunks[0])
+        .into_iter()
+        .map(|(range, commit)| (range, commit.summary.as_str(), commit.author.as_str()))
+        .collect();
+    assert_eq!(
+        ranges,
+        vec![
+            (1..=1, "Add a.txt", "Test"),
+            (2..=2, "Shout two", "Other"),
+            (3..=3, "Add a.txt", "Test"),
+            (4..=4, "Not committed yet", ""),
+        ]
+    );
+    assert!(!blame.line(4).unwrap().is_committed());
+    assert_eq!(blame.line(1).unwrap().id.len(), 7);
+}
+
+#[test]
+fn test_hunk_commits_newest_first() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\ntwo\nthree\nfour\n");
+    commit_as(
+        &repo,
+        "Other",
+        "a.txt",
+        "one\nTWO\nthree\nfour\n",
+        "Shout two",
+    );
+    helpers::modify_file(&repo, "a.txt", "1\n2\n3\n4\n");
+
+    let files = get_unstaged_diff(&repo).unwrap();
+    let blame = blame_file(&repo, &files[0].path).unwrap();
+    let commits = blame.hunk_commits(&files[0].hunks[0]);
+    assert_eq!(commits.len(), 2);
+    let summaries: Vec<_> = commits.iter().map(|c| c.commit.summary.as_str()).collect();
+    let (newest, oldest) = if commits[0].commit.time == commits[1].commit.time {
+        // Same second: either order is newest first
+        let shout = summaries.iter().position(|s| *s == "Shout two").unwrap();
+        (shout, 1 - shout)
+    } else {
+        (0, 1)
+    };
+    assert_eq!(commits[newest].commit.summary, "Shout two");
+    assert_eq!(commits[newest].lines, vec![2..=2]);
+    assert_eq!(commits[oldest].lines, vec![1..=1, 3..=4]);
+    assert_eq!(
+        stagent::blame::format_ranges(&commits[oldest].lines),
+        "1, 3-4"
+    );
+}
+
+#[test]
+fn test_blame_untracked_file_fails() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::create_untracked_file(&repo, "new.txt", "hello\n");
+    assert!(blame_file(&repo, Path::new("new.txt")).is_err());
+}
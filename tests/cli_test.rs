@@ -16,11 +16,13 @@ fn binary_path() -> std::path::PathBuf {
     path
 }
 
-/// Run the binary with given args, removing TMUX from env by default.
+/// Run the binary with given args, removing TMUX (and ZELLIJ) from env by
+/// default.
 fn run_binary(args: &[&str]) -> std::process::Output {
     Command::new(binary_path())
         .args(args)
         .env_remove("TMUX")
+        .env_remove("ZELLIJ")
         .output()
         .expect("Failed to execute binary")
 }
@@ -37,6 +39,8 @@ fn run_binary_in_dir(dir: &std::path::Path, args: &[&str]) -> std::process::Outp
 
 #[test]
 fn test_no_tmux_error() {
+    // Without tmux or zellij the review needs this terminal, and there is
+    // none here
     let output = run_binary(&[]);
     let stderr = String::from_utf8_lossy(&output.stderr);
     assert!(!output.status.success(), "Should fail without tmux");
@@ -45,6 +49,7 @@ fn test_no_tmux_error() {
         "Error should mention tmux, got: {}",
         stderr
     );
+    assert!(stderr.contains("interactive terminal"), "got: {}", stderr);
 }
 
 #[test]
@@ -81,6 +86,48 @@ fn test_no_changes_message() {
     );
 }
 
+#[test]
+fn test_summary_line_on_stderr() {
+    let (dir, _repo) = helpers::create_temp_repo();
+    let output = run_binary_in_dir(dir.path(), &["--summary"]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    let line = stderr
+        .lines()
+        .find_map(|l| l.strip_prefix("STAGENT_RESULT: "))
+        .unwrap_or_else(|| panic!("no summary line in: {}", stderr));
+    let json: serde_json::Value = serde_json::from_str(line).unwrap();
+    assert_eq!(json["hunks"], 0);
+    assert_eq!(json["staged"], 0);
+    assert_eq!(json["feedback"], 0);
+
+    // The hidden file variant used by --spawn
+    let summary = dir.path().join("summary.txt");
+    let output = run_binary_in_dir(dir.path(), &["--summary-file", summary.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("STAGENT_RESULT"));
+    let written = std::fs::read_to_string(&summary).unwrap();
+    assert!(written.starts_with("STAGENT_RESULT: {"));
+}
+
+#[test]
+fn test_status_file_records_the_exit_status() {
+    // The hidden flag --spawn passes to its child
+    let (dir, _repo) = helpers::create_temp_repo();
+    let status = dir.path().join("status.txt");
+    let output = run_binary_in_dir(dir.path(), &["--status-file", status.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(&status).unwrap(), "0\n");
+
+    let output = run_binary_in_dir(
+        dir.path(),
+        &["--status-file", status.to_str().unwrap(), "--apply"],
+    );
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read_to_string(&status).unwrap(), "1\n");
+}
+
 #[test]
 fn test_output_flag_parsed() {
     // The --output flag should be accepted (we won't actually write to a file
@@ -153,6 +200,33 @@ fn test_binary_file_skipped_via_glob() {
     );
 }
 
+#[test]
+fn test_exclude_filters_out_files() {
+    let (dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "src/gen/api.rs", "fn api() {}");
+    helpers::modify_file(&repo, "src/gen/api.rs", "fn api() { todo!() }");
+
+    let output = run_binary_in_dir(
+        dir.path(),
+        &["--files", "src/**/*.rs", "--exclude", "**/gen/**"],
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.to_lowercase().contains("no unstaged changes"),
+        "Excluded file should not be reviewed, got: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_invalid_files_glob_is_an_error() {
+    let (dir, _repo) = helpers::create_temp_repo();
+    let output = run_binary_in_dir(dir.path(), &["print", "--files", "src/["]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Invalid glob pattern"), "got: {}", stderr);
+}
+
 #[test]
 fn test_theme_flag_parsed() {
     let output = run_binary(&["--theme", "monokai"]);
@@ -165,6 +239,20 @@ fn test_theme_flag_parsed() {
     );
 }
 
+#[test]
+fn test_auto_split_flag_parsed() {
+    // With and without the optional threshold
+    for args in [&["--auto-split"][..], &["--auto-split", "8"]] {
+        let output = run_binary(args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.to_lowercase().contains("tmux"),
+            "Should fail due to tmux, not bad arg parse, got: {}",
+            stderr
+        );
+    }
+}
+
 #[test]
 fn test_help_flag() {
     let output = run_binary(&["--help"]);
@@ -193,6 +281,18 @@ fn test_help_flag() {
     );
 }
 
+#[test]
+fn test_start_position_flags_parsed() {
+    let output = run_binary(&["--start-file", "src/main.rs:42", "--start-hunk", "2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // Should fail due to tmux, not a parse error
+    assert!(
+        stderr.to_lowercase().contains("tmux"),
+        "Should fail due to tmux, not bad arg parse, got: {}",
+        stderr
+    );
+}
+
 #[test]
 fn test_patch_flag_parsed() {
     let output = run_binary(&["-p"]);
@@ -205,6 +305,24 @@ fn test_patch_flag_parsed() {
     );
 }
 
+#[test]
+fn test_spawn_timeout_without_a_split() {
+    // Only --spawn waits for a pane
+    let output = run_binary(&["--spawn-timeout", "5"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--spawn"), "got: {}", stderr);
+
+    // Nothing to spawn into: the review would run here, without a timeout
+    let output = run_binary(&["--spawn", "--spawn-timeout", "5"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--spawn-timeout is ignored"),
+        "got: {}",
+        stderr
+    );
+}
+
 #[test]
 fn test_patch_and_spawn_rejected() {
     // Must set TMUX so we get past the tmux check and actually hit the
@@ -227,6 +345,109 @@ fn test_patch_and_spawn_rejected() {
     );
 }
 
+#[test]
+fn test_patch_and_resume_rejected() {
+    let output = Command::new(binary_path())
+        .args(["-p", "--resume"])
+        .env("TMUX", "/tmp/tmux-fake/default,12345,0")
+        .output()
+        .expect("Failed to execute binary");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("--resume is not supported with --patch"),
+        "Should report the flag conflict, got: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_patch_file_is_read() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let patch = tmp.path().join("change.patch");
+    std::fs::write(
+        &patch,
+        "diff --git a/greeting.txt b/greeting.txt\n--- a/greeting.txt\n+++ b/greeting.txt\n@@ -1 +1 @@\n-hello\n+there\n",
+    )
+    .unwrap();
+
+    // `print` needs no tmux, so it shows the patch was parsed
+    let output = run_binary(&[
+        "print",
+        "--color",
+        "never",
+        "--patch-file",
+        patch.to_str().unwrap(),
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stderr: {:?}", output.stderr);
+    assert!(stdout.contains("greeting.txt"), "got: {}", stdout);
+    assert!(stdout.contains("there"), "got: {}", stdout);
+
+    std::fs::write(&patch, "").unwrap();
+    let output = run_binary_in_dir(tmp.path(), &["--patch-file", patch.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No changes to review"));
+}
+
+#[test]
+fn test_patch_file_errors() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let output = run_binary_in_dir(tmp.path(), &["--patch-file", "missing.patch"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("missing.patch"), "got: {}", stderr);
+
+    let output = run_binary_in_dir(tmp.path(), &["-p", "--patch-file", "x.patch"]);
+    assert!(
+        !output.status.success(),
+        "--patch and --patch-file conflict"
+    );
+
+    let output = run_binary_in_dir(tmp.path(), &["--apply"]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("--apply requires --patch"),
+        "got: {:?}",
+        output.stderr
+    );
+
+    let output = run_binary_in_dir(tmp.path(), &["--patch-file", "x.patch", "--watch"]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("--watch is not supported"),
+        "got: {:?}",
+        output.stderr
+    );
+}
+
+#[test]
+fn test_publish_target_is_checked_up_front() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let output = run_binary_in_dir(tmp.path(), &["--publish", "kimpers/stagent#1"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("expected github:<owner>/<repo>#<pr>"),
+        "got: {}",
+        stderr
+    );
+
+    let output = run_binary_in_dir(tmp.path(), &["--dry-run"]);
+    assert!(!output.status.success(), "--dry-run requires --publish");
+
+    // Without a token the review would be lost to a failed publish
+    let output = Command::new(binary_path())
+        .args(["--publish", "github:kimpers/stagent#1"])
+        .env("TMUX", "/tmp/tmux-fake/default,12345,0")
+        .env_remove("GITHUB_TOKEN")
+        .env_remove("GH_TOKEN")
+        .current_dir(tmp.path())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("needs a token"), "got: {}", stderr);
+}
+
 #[test]
 fn test_patch_empty_diff_from_stdin() {
     // Pipe an empty string to stagent -p. Should exit cleanly with "No changes to review."
@@ -331,3 +552,324 @@ fn test_unknown_flag_rejected() {
         stderr
     );
 }
+
+#[test]
+fn test_feedback_list_and_show() {
+    let (dir, repo) = helpers::create_temp_repo();
+    let archive = stagent::archive::archive_dir(&repo);
+    stagent::archive::archive_feedback_at(&archive, "--- a/x\n+++ b/x\n@@ -1 +1 @@\n", 100)
+        .unwrap();
+
+    // Subcommands work outside tmux
+    let list = Command::new(binary_path())
+        .args(["feedback", "list"])
+        .env_remove("TMUX")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(list.status.success());
+    let stdout = String::from_utf8_lossy(&list.stdout);
+    assert!(stdout.contains("19700101T000140Z"), "got: {}", stdout);
+    assert!(stdout.contains("1 file(s), 1 hunk(s)"), "got: {}", stdout);
+
+    let show = Command::new(binary_path())
+        .args(["feedback", "show"])
+        .env_remove("TMUX")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(show.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&show.stdout),
+        "--- a/x\n+++ b/x\n@@ -1 +1 @@\n"
+    );
+}
+
+#[test]
+fn test_feedback_show_unknown_id() {
+    let (dir, _repo) = helpers::create_temp_repo();
+    let output = Command::new(binary_path())
+        .args(["feedback", "show", "2099"])
+        .env_remove("TMUX")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No archived feedback"));
+}
+
+#[test]
+fn test_print_renders_diff_without_tui() {
+    let (dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "src/a.rs", "fn a() {}\n");
+    helpers::commit_file(&repo, "b.txt", "old\n");
+    helpers::modify_file(&repo, "src/a.rs", "fn a() { 1 }\n");
+    helpers::modify_file(&repo, "b.txt", "new\n");
+
+    let output = Command::new(binary_path())
+        .args(["print", "--color", "never", "--files", "src/*"])
+        .env_remove("TMUX")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("src/a.rs (modified)\n"),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout.contains("-fn a() {}"), "got: {}", stdout);
+    assert!(stdout.contains("+fn a() { 1 }"), "got: {}", stdout);
+    assert!(!stdout.contains("b.txt"), "--files applies: {}", stdout);
+    assert!(!stdout.contains('\x1b'), "no colors with --color never");
+
+    // Read-only: nothing was added to the index
+    assert!(
+        repo.index()
+            .unwrap()
+            .get_path(std::path::Path::new("src/a.rs"), 0)
+            .is_some()
+    );
+    assert!(
+        repo.statuses(None)
+            .unwrap()
+            .iter()
+            .all(|s| !s.status().is_index_modified())
+    );
+}
+
+#[test]
+fn test_print_patch_from_stdin_with_color() {
+    use std::io::Write;
+
+    let mut child = Command::new(binary_path())
+        .args(["print", "-p", "--color", "always"])
+        .env_remove("TMUX")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"diff --git a/x.txt b/x.txt\n--- a/x.txt\n+++ b/x.txt\n@@ -1 +1 @@\n-a\n+b\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("x.txt"), "got: {}", stdout);
+    assert!(stdout.contains("\x1b["), "colored output: {:?}", stdout);
+}
+
+#[test]
+fn test_range_reviews_committed_changes_without_staging() {
+    let (dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\n");
+    helpers::commit_file(&repo, "a.txt", "two\n");
+    helpers::modify_file(&repo, "a.txt", "three\n");
+
+    let tmp = tempfile::TempDir::new().unwrap();
+    let script = tmp.path().join("review.txt");
+    std::fs::write(&script, "comment \"Why two?\"\n").unwrap();
+    let output = Command::new(binary_path())
+        .args(["--range", "HEAD~1", "--script", script.to_str().unwrap()])
+        .env_remove("TMUX")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("+two"), "the committed change: {}", stdout);
+    assert!(stdout.contains("Why two?"), "got: {}", stdout);
+    // The working tree's change is neither reviewed nor staged
+    assert!(!stdout.contains("three"), "got: {}", stdout);
+    let mut index = repo.index().unwrap();
+    index.read(true).unwrap();
+    let staged = index.get_path(std::path::Path::new("a.txt"), 0).unwrap();
+    assert_eq!(repo.find_blob(staged.id).unwrap().content(), b"two\n");
+
+    let output = Command::new(binary_path())
+        .args([
+            "--range",
+            "HEAD..HEAD",
+            "--script",
+            script.to_str().unwrap(),
+        ])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is empty"));
+}
+
+#[test]
+fn test_script_runs_review_without_tmux() {
+    let (dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\n");
+    helpers::commit_file(&repo, "b.txt", "two\n");
+    helpers::modify_file(&repo, "a.txt", "one\nmore\n");
+    helpers::modify_file(&repo, "b.txt", "two\nmore\n");
+
+    // Outside the repo, or they would be reviewed too
+    let tmp = tempfile::TempDir::new().unwrap();
+    let script = tmp.path().join("review.txt");
+    std::fs::write(
+        &script,
+        "# stage a, comment on b\ngoto a.txt\nstage\ngoto b.txt\ncomment \"Needs a test\"\n",
+    )
+    .unwrap();
+    let feedback = tmp.path().join("feedback.md");
+    let output = Command::new(binary_path())
+        .args(["--script", script.to_str().unwrap()])
+        .args(["--output", feedback.to_str().unwrap()])
+        .env_remove("TMUX")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut index = repo.index().unwrap();
+    index.read(true).unwrap();
+    let staged = index.get_path(std::path::Path::new("a.txt"), 0).unwrap();
+    let blob = repo.find_blob(staged.id).unwrap();
+    assert_eq!(blob.content(), b"one\nmore\n");
+    let staged = index.get_path(std::path::Path::new("b.txt"), 0).unwrap();
+    assert_eq!(repo.find_blob(staged.id).unwrap().content(), b"two\n");
+
+    let written = std::fs::read_to_string(&feedback).unwrap();
+    assert!(written.contains("b.txt"), "got: {}", written);
+    assert!(written.contains("Needs a test"), "got: {}", written);
+}
+
+/// Run a scripted `--hook pre-commit` review in `dir`.
+fn run_hook_review(dir: &std::path::Path, script: &str) -> std::process::Output {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let path = tmp.path().join("review.txt");
+    std::fs::write(&path, script).unwrap();
+    Command::new(binary_path())
+        .args(["--hook", "pre-commit", "--script", path.to_str().unwrap()])
+        .env_remove("TMUX")
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_pre_commit_hook_reviews_staged_changes() {
+    let (dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\n");
+    helpers::modify_file(&repo, "a.txt", "two\n");
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new("a.txt")).unwrap();
+    index.write().unwrap();
+
+    let output = run_hook_review(dir.path(), "stage\n");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = run_hook_review(dir.path(), "comment \"!blocker Breaks the build\"\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("Commit blocked: 1 blocker comment"),
+        "got: {}",
+        stderr
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("+two"), "the staged change: {}", stdout);
+
+    // Nothing staged: nothing to block
+    helpers::commit_file(&repo, "a.txt", "two\n");
+    let output = run_hook_review(dir.path(), "stage\n");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No staged changes"));
+}
+
+#[test]
+fn test_script_errors_name_the_line() {
+    let (dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\n");
+    helpers::modify_file(&repo, "a.txt", "two\n");
+
+    let tmp = tempfile::TempDir::new().unwrap();
+    let script = tmp.path().join("review.txt");
+    std::fs::write(&script, "stage\nstage\n").unwrap();
+    let output = Command::new(binary_path())
+        .args(["--script", script.to_str().unwrap()])
+        .env_remove("TMUX")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Script line 2"), "got: {}", stderr);
+}
+
+#[test]
+fn test_dump_json_prints_the_diff() {
+    let (dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\ntwo\n");
+    helpers::modify_file(&repo, "a.txt", "one\n2\n");
+    helpers::create_untracked_file(&repo, "new.txt", "hello\n");
+
+    // No tmux needed, and nothing is added with intent-to-add
+    let output = Command::new(binary_path())
+        .arg("--dump-json")
+        .env_remove("TMUX")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let files = json.as_array().unwrap();
+    assert_eq!(files.len(), 2);
+
+    assert_eq!(files[0]["path"], "a.txt");
+    assert_eq!(files[0]["status"], "modified");
+    assert!(files[0].get("mode_change").is_none());
+    let hunk = &files[0]["hunks"][0];
+    assert_eq!(hunk["header"], "@@ -1,2 +1,2 @@");
+    assert_eq!(hunk["old_start"], 1);
+    let lines = hunk["lines"].as_array().unwrap();
+    assert_eq!(lines[1]["kind"], "removed");
+    assert_eq!(lines[1]["content"], "two\n");
+    assert_eq!(lines[1]["old_lineno"], 2);
+    assert_eq!(lines[2]["kind"], "added");
+    assert_eq!(lines[2]["new_lineno"], 2);
+
+    assert_eq!(files[1]["path"], "new.txt");
+    assert_eq!(files[1]["status"], "untracked");
+    assert!(
+        repo.index()
+            .unwrap()
+            .get_path(std::path::Path::new("new.txt"), 0)
+            .is_none()
+    );
+
+    // --files filters the dump as it does the review
+    let output = Command::new(binary_path())
+        .args(["--dump-json", "--files", "new.txt"])
+        .env_remove("TMUX")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+}
@@ -24,20 +24,27 @@ fn test_comment_content_round_trip() {
                 content: "line1\n".to_string(),
                 old_lineno: Some(1),
                 new_lineno: Some(1),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Removed,
                 content: "old\n".to_string(),
                 old_lineno: Some(2),
                 new_lineno: None,
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Added,
                 content: "new\n".to_string(),
                 old_lineno: None,
                 new_lineno: Some(2),
+                raw: None,
+                no_newline: false,
             },
-        ],
+        ]
+        .into(),
         status: HunkStatus::Pending,
         old_start: 1,
         old_lines: 3,
@@ -91,20 +98,27 @@ fn test_flush_pending_comment_captures_feedback() {
                     content: "ctx\n".to_string(),
                     old_lineno: Some(1),
                     new_lineno: Some(1),
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Removed,
                     content: "old\n".to_string(),
                     old_lineno: Some(2),
                     new_lineno: None,
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Added,
                     content: "new\n".to_string(),
                     old_lineno: None,
                     new_lineno: Some(2),
+                    raw: None,
+                    no_newline: false,
                 },
-            ],
+            ]
+            .into(),
             status: HunkStatus::Pending,
             old_start: 1,
             old_lines: 3,
@@ -113,6 +127,8 @@ fn test_flush_pending_comment_captures_feedback() {
         }],
         status: DeltaStatus::Modified,
         is_binary: false,
+        is_submodule: false,
+        mode_change: None,
     }];
 
     let mut app = App::new(files, true);
@@ -141,6 +157,84 @@ fn test_flush_pending_comment_captures_feedback() {
     );
 }
 
+/// A commented hunk's comment is pre-filled when it is commented on again,
+/// and the revised comment replaces it rather than adding another.
+#[test]
+fn test_revised_comment_replaces_previous() {
+    use stagent::app::App;
+
+    let files = vec![FileDiff {
+        path: "src/main.rs".into(),
+        hunks: vec![Hunk {
+            header: "@@ -1,3 +1,4 @@".to_string(),
+            lines: vec![
+                DiffLine {
+                    kind: LineKind::Context,
+                    content: "ctx\n".to_string(),
+                    old_lineno: Some(1),
+                    new_lineno: Some(1),
+                    raw: None,
+                    no_newline: false,
+                },
+                DiffLine {
+                    kind: LineKind::Removed,
+                    content: "old\n".to_string(),
+                    old_lineno: Some(2),
+                    new_lineno: None,
+                    raw: None,
+                    no_newline: false,
+                },
+                DiffLine {
+                    kind: LineKind::Added,
+                    content: "new\n".to_string(),
+                    old_lineno: None,
+                    new_lineno: Some(2),
+                    raw: None,
+                    no_newline: false,
+                },
+            ]
+            .into(),
+            status: HunkStatus::Pending,
+            old_start: 1,
+            old_lines: 3,
+            new_start: 1,
+            new_lines: 4,
+        }],
+        status: DeltaStatus::Modified,
+        is_binary: false,
+        is_submodule: false,
+        mode_change: None,
+    }];
+
+    let mut app = App::new(files, true);
+
+    let tmpfile = editor::prepare_comment_tempfile(app.current_hunk().unwrap()).unwrap();
+    let original = std::fs::read_to_string(tmpfile.path()).unwrap();
+    let edited = original.replace("-old\n", "!nit why not keep it?\n-old\n");
+    std::fs::write(tmpfile.path(), &edited).unwrap();
+    assert!(app.flush_pending_editor_state(tmpfile.path(), true, &original));
+    assert_eq!(app.current_hunk().unwrap().status, HunkStatus::Commented);
+
+    // The draft reads back as the same comment
+    let draft = editor::comment_draft(&original, &app.feedback[0]);
+    assert_eq!(draft, edited);
+
+    std::fs::write(
+        tmpfile.path(),
+        draft.replace("why not keep it?", "keep it, it is used elsewhere"),
+    )
+    .unwrap();
+    assert!(app.flush_pending_editor_state(tmpfile.path(), true, &original));
+
+    assert_eq!(app.feedback.len(), 1);
+    assert_eq!(app.feedback[0].content, "keep it, it is used elsewhere");
+    assert_eq!(app.feedback[0].severity, Some(Severity::Nit));
+    assert_eq!(
+        app.feedback[0].comment_positions,
+        vec![(1, "keep it, it is used elsewhere".to_string())]
+    );
+}
+
 /// BUG REPRO: User writes a plain text comment (no `# COMMENT:` prefix)
 /// in the comment tempfile. `parse_comment_result` returns None because
 /// it only looks for `# COMMENT:` prefixed lines. The TUI says "Comment
@@ -156,20 +250,27 @@ fn test_plain_text_comment_is_captured() {
                 content: "line1\n".to_string(),
                 old_lineno: Some(1),
                 new_lineno: Some(1),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Removed,
                 content: "old\n".to_string(),
                 old_lineno: Some(2),
                 new_lineno: None,
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Added,
                 content: "new\n".to_string(),
                 old_lineno: None,
                 new_lineno: Some(2),
+                raw: None,
+                no_newline: false,
             },
-        ],
+        ]
+        .into(),
         status: HunkStatus::Pending,
         old_start: 1,
         old_lines: 3,
@@ -210,7 +311,10 @@ fn test_prefixed_comment_still_works() {
             content: "line1\n".to_string(),
             old_lineno: Some(1),
             new_lineno: Some(1),
-        }],
+            raw: None,
+            no_newline: false,
+        }]
+        .into(),
         status: HunkStatus::Pending,
         old_start: 1,
         old_lines: 3,
@@ -254,7 +358,10 @@ fn test_no_changes_produces_no_feedback() {
             content: "line1\n".to_string(),
             old_lineno: Some(1),
             new_lineno: Some(1),
-        }],
+            raw: None,
+            no_newline: false,
+        }]
+        .into(),
         status: HunkStatus::Pending,
         old_start: 1,
         old_lines: 3,
@@ -283,32 +390,43 @@ fn test_positioned_comments_in_hunk() {
                 content: "line1\n".to_string(),
                 old_lineno: Some(1),
                 new_lineno: Some(1),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Removed,
                 content: "old_a\n".to_string(),
                 old_lineno: Some(2),
                 new_lineno: None,
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Added,
                 content: "new_a\n".to_string(),
                 old_lineno: None,
                 new_lineno: Some(2),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Context,
                 content: "line3\n".to_string(),
                 old_lineno: Some(3),
                 new_lineno: Some(3),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Context,
                 content: "line4\n".to_string(),
                 old_lineno: Some(4),
                 new_lineno: Some(4),
+                raw: None,
+                no_newline: false,
             },
-        ],
+        ]
+        .into(),
         status: HunkStatus::Pending,
         old_start: 1,
         old_lines: 5,
@@ -403,6 +521,8 @@ fn test_editor_strips_trailing_whitespace() {
                 content: "first\n".to_string(),
                 old_lineno: Some(1),
                 new_lineno: Some(1),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Context,
@@ -410,20 +530,27 @@ fn test_editor_strips_trailing_whitespace() {
                 content: "\n".to_string(),
                 old_lineno: Some(2),
                 new_lineno: Some(2),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Added,
                 content: "async fn ensure_request_id(\n".to_string(),
                 old_lineno: None,
                 new_lineno: Some(3),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Context,
                 content: "last\n".to_string(),
                 old_lineno: Some(3),
                 new_lineno: Some(4),
+                raw: None,
+                no_newline: false,
             },
-        ],
+        ]
+        .into(),
         status: HunkStatus::Pending,
         old_start: 1,
         old_lines: 4,
@@ -513,24 +640,32 @@ fn test_comment_replaces_empty_context_line() {
                 content: "\n".to_string(), // empty source line
                 old_lineno: Some(6),
                 new_lineno: Some(6),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Context,
                 content: "[dependencies]\n".to_string(),
                 old_lineno: Some(7),
                 new_lineno: Some(7),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Context,
                 content: "ratatui = \"0.29\"\n".to_string(),
                 old_lineno: Some(8),
                 new_lineno: Some(8),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Removed,
                 content: "crossterm = \"0.28\"\n".to_string(),
                 old_lineno: Some(9),
                 new_lineno: None,
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Added,
@@ -538,26 +673,35 @@ fn test_comment_replaces_empty_context_line() {
                     .to_string(),
                 old_lineno: None,
                 new_lineno: Some(9),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Context,
                 content: "git2 = \"0.19\"\n".to_string(),
                 old_lineno: Some(10),
                 new_lineno: Some(10),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Context,
                 content: "syntect = \"5\"\n".to_string(),
                 old_lineno: Some(11),
                 new_lineno: Some(11),
+                raw: None,
+                no_newline: false,
             },
             DiffLine {
                 kind: LineKind::Context,
                 content: "clap = { version = \"4\", features = [\"derive\"] }\n".to_string(),
                 old_lineno: Some(12),
                 new_lineno: Some(12),
+                raw: None,
+                no_newline: false,
             },
-        ],
+        ]
+        .into(),
         status: HunkStatus::Pending,
         old_start: 6,
         old_lines: 7,
@@ -613,20 +757,27 @@ fn test_flush_pending_edit_captures_feedback() {
                     content: "ctx\n".to_string(),
                     old_lineno: Some(1),
                     new_lineno: Some(1),
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Removed,
                     content: "old\n".to_string(),
                     old_lineno: Some(2),
                     new_lineno: None,
+                    raw: None,
+                    no_newline: false,
                 },
                 DiffLine {
                     kind: LineKind::Added,
                     content: "new\n".to_string(),
                     old_lineno: None,
                     new_lineno: Some(2),
+                    raw: None,
+                    no_newline: false,
                 },
-            ],
+            ]
+            .into(),
             status: HunkStatus::Pending,
             old_start: 1,
             old_lines: 3,
@@ -635,6 +786,8 @@ fn test_flush_pending_edit_captures_feedback() {
         }],
         status: DeltaStatus::Modified,
         is_binary: false,
+        is_submodule: false,
+        mode_change: None,
     }];
 
     let mut app = App::new(files, true);
@@ -0,0 +1,61 @@
+mod helpers;
+
+use helpers::render::render_to_string;
+use helpers::*;
+use stagent::app::App;
+use stagent::git::get_unstaged_diff;
+use stagent::types::AppMode;
+
+/// 30-line file with line 15 changed so the hunk has room to expand.
+fn one_hunk_repo() -> (tempfile::TempDir, git2::Repository) {
+    let (dir, repo) = create_temp_repo();
+    let original: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+    commit_file(&repo, "big.txt", &original);
+    let modified = original.replace("line 15\n", "line 15 CHANGED\n");
+    modify_file(&repo, "big.txt", &modified);
+    (dir, repo)
+}
+
+#[test]
+fn test_expand_and_collapse_context() {
+    let (_dir, repo) = one_hunk_repo();
+    let files = get_unstaged_diff(&repo).unwrap();
+    let mut app = App::new_with_help(files, true, false);
+    app.mode = AppMode::Browsing;
+    let base = app.total_content_lines();
+
+    // Default diff context is 3 lines, so line 7 is not visible yet
+    assert!(!render_to_string(100, 50, &mut app).contains("line 7 "));
+
+    app.expand_current_context(Some(&repo), true);
+    assert_eq!(app.total_content_lines(), base + 10);
+    let output = render_to_string(100, 50, &mut app);
+    assert!(
+        output.contains("line 7 "),
+        "expanded line missing:\n{}",
+        output
+    );
+    assert!(
+        output.contains("line 23"),
+        "expanded line missing:\n{}",
+        output
+    );
+
+    app.expand_current_context(Some(&repo), false);
+    assert_eq!(app.total_content_lines(), base);
+    assert!(app.expanded_context.is_empty());
+}
+
+#[test]
+fn test_expand_context_stops_at_file_start() {
+    let (_dir, repo) = create_temp_repo();
+    commit_file(&repo, "short.txt", "a\nb\nc\nd\n");
+    modify_file(&repo, "short.txt", "a\nB\nc\nd\n");
+    let files = get_unstaged_diff(&repo).unwrap();
+    let mut app = App::new_with_help(files, true, false);
+    let base = app.total_content_lines();
+
+    // The hunk already covers the whole file; nothing to add
+    app.expand_current_context(Some(&repo), true);
+    assert_eq!(app.total_content_lines(), base);
+}
@@ -1,10 +1,18 @@
 use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 use stagent::editor::{
-    build_pane_exists_check_command, build_tmux_split_command, parse_comment_result,
-    parse_edit_result, prepare_comment_tempfile, prepare_edit_tempfile,
+    EditorLaunch, MAX_PANE_POLL_ITERATIONS, PANE_POLL_INTERVAL, SplitDirection, SplitLayout,
+    SplitSize, build_editor_args_at_line, build_pane_exists_check_command, build_tmux_pane_command,
+    build_tmux_split_command, build_tmux_split_command_for, pane_exists_with, parse_comment_result,
+    parse_edit_result, parse_file_comment, parse_review_comment, plan_editor_launch,
+    prepare_comment_tempfile, prepare_edit_tempfile, prepare_file_comment_tempfile,
+    prepare_review_comment_tempfile, run_tmux_split, wait_for_pane_close_with,
 };
-use stagent::types::{DiffLine, FeedbackKind, Hunk, HunkStatus, LineKind};
+use stagent::tmux::{FakeClock, FakeTmux};
+use stagent::types::{DiffLine, FeedbackKind, Hunk, HunkStatus, LineKind, Severity};
 
 /// Helper: build a Hunk with the given lines for testing.
 fn make_hunk(header: &str, lines: Vec<(LineKind, &str)>) -> Hunk {
@@ -17,6 +25,8 @@ fn make_hunk(header: &str, lines: Vec<(LineKind, &str)>) -> Hunk {
                 content: content.to_string(),
                 old_lineno: None,
                 new_lineno: None,
+                raw: None,
+                no_newline: false,
             })
             .collect(),
         status: HunkStatus::Pending,
@@ -96,6 +106,182 @@ fn test_build_tmux_split_falls_back_to_vi() {
     );
 }
 
+// ---------------------------------------------------------------------------
+// build_editor_args_at_line
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_editor_args_at_line_plus_syntax() {
+    assert_eq!(
+        build_editor_args_at_line("vim", "/repo/src/lib.rs", 42),
+        vec!["vim", "+42", "/repo/src/lib.rs"]
+    );
+    // Detected by executable name, even with a full path
+    assert_eq!(
+        build_editor_args_at_line("/usr/bin/nvim", "a.rs", 7),
+        vec!["/usr/bin/nvim", "+7", "a.rs"]
+    );
+    assert_eq!(
+        build_editor_args_at_line("nano", "a.rs", 3),
+        vec!["nano", "+3", "a.rs"]
+    );
+}
+
+#[test]
+fn test_editor_args_at_line_colon_syntax() {
+    assert_eq!(
+        build_editor_args_at_line("hx", "a.rs", 10),
+        vec!["hx", "a.rs:10"]
+    );
+    assert_eq!(
+        build_editor_args_at_line("code", "a.rs", 10),
+        vec!["code", "--goto", "a.rs:10"]
+    );
+}
+
+#[test]
+fn test_editor_args_at_line_unknown_editor() {
+    assert_eq!(
+        build_editor_args_at_line("ed", "a.rs", 10),
+        vec!["ed", "a.rs"]
+    );
+}
+
+#[test]
+fn test_build_tmux_split_command_for_appends_args() {
+    let cmd = build_tmux_split_command_for(build_editor_args_at_line("vim", "a.rs", 5));
+    let sep = cmd.iter().position(|s| s == "--").unwrap();
+    assert_eq!(&cmd[sep + 1..], ["vim", "+5", "a.rs"]);
+}
+
+#[test]
+fn test_editor_args_keep_editor_arguments() {
+    assert_eq!(
+        build_editor_args_at_line("nvim -u NONE", "a.rs", 3),
+        vec!["nvim", "-u", "NONE", "+3", "a.rs"]
+    );
+}
+
+#[test]
+fn test_terminal_editors_open_in_a_pane() {
+    assert_eq!(
+        plan_editor_launch("vim", None, "a.rs", Some(2)),
+        EditorLaunch::Pane(vec!["vim".into(), "+2".into(), "a.rs".into()])
+    );
+}
+
+#[test]
+fn test_gui_editors_are_told_to_wait() {
+    assert_eq!(
+        plan_editor_launch("code", None, "a.rs", Some(2)),
+        EditorLaunch::Wait(vec![
+            "code".into(),
+            "--wait".into(),
+            "--goto".into(),
+            "a.rs:2".into()
+        ])
+    );
+    // An editor that already waits keeps its own flag
+    assert_eq!(
+        plan_editor_launch("/usr/bin/code -w", None, "a.rs", None),
+        EditorLaunch::Wait(vec!["/usr/bin/code".into(), "-w".into(), "a.rs".into()])
+    );
+    assert_eq!(
+        plan_editor_launch("gvim", None, "a.rs", None),
+        EditorLaunch::Wait(vec!["gvim".into(), "--nofork".into(), "a.rs".into()])
+    );
+}
+
+#[test]
+fn test_editor_wait_command_replaces_the_editor() {
+    assert_eq!(
+        plan_editor_launch("vim", Some("my-edit --block"), "a.rs", None),
+        EditorLaunch::Wait(vec!["my-edit".into(), "--block".into(), "a.rs".into()])
+    );
+    assert!(matches!(
+        plan_editor_launch("vim", Some(" "), "a.rs", None),
+        EditorLaunch::Pane(_)
+    ));
+}
+
+#[test]
+fn test_waiting_editor_signals_when_it_exits() {
+    let rx = stagent::editor::spawn_waiting_editor(&["true".to_string()]).unwrap();
+    assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    assert!(stagent::editor::spawn_waiting_editor(&["no-such-editor-xyz".to_string()]).is_err());
+}
+
+#[test]
+fn test_build_tmux_pane_command_layouts() {
+    let args = || vec!["vim".to_string()];
+    let vertical = SplitLayout {
+        direction: SplitDirection::Vertical,
+        size: SplitSize::Cells(15),
+    };
+    assert_eq!(
+        build_tmux_pane_command(&vertical, args()),
+        [
+            "tmux",
+            "split-window",
+            "-v",
+            "-l",
+            "15",
+            "-P",
+            "-F",
+            "#{pane_id}",
+            "--",
+            "vim"
+        ]
+    );
+    let window = SplitLayout {
+        direction: SplitDirection::Window,
+        ..Default::default()
+    };
+    assert_eq!(
+        build_tmux_pane_command(&window, args()),
+        ["tmux", "new-window", "-P", "-F", "#{pane_id}", "--", "vim"]
+    );
+    assert_eq!(
+        build_tmux_pane_command(&SplitLayout::default(), args()),
+        build_tmux_split_command_for(args())
+    );
+}
+
+#[test]
+fn test_parse_split_size() {
+    assert_eq!("40%".parse(), Ok(SplitSize::Percent(40)));
+    assert_eq!("80".parse(), Ok(SplitSize::Cells(80)));
+    assert!("0%".parse::<SplitSize>().is_err());
+    assert!("100%".parse::<SplitSize>().is_err());
+    assert!("0".parse::<SplitSize>().is_err());
+    assert!("half".parse::<SplitSize>().is_err());
+    assert_eq!(SplitSize::Percent(40).to_string(), "40%");
+}
+
+#[test]
+fn test_split_layout_from_git_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = git2::Config::open(&dir.path().join("config")).unwrap();
+    assert_eq!(
+        SplitLayout::from_git_config(&config).unwrap(),
+        SplitLayout::default()
+    );
+
+    config.set_str("stagent.split", "vertical").unwrap();
+    config.set_str("stagent.splitSize", "30%").unwrap();
+    assert_eq!(
+        SplitLayout::from_git_config(&config).unwrap(),
+        SplitLayout {
+            direction: SplitDirection::Vertical,
+            size: SplitSize::Percent(30),
+        }
+    );
+
+    config.set_str("stagent.split", "diagonal").unwrap();
+    let err = SplitLayout::from_git_config(&config).unwrap_err();
+    assert!(err.to_string().contains("stagent.split"), "got: {}", err);
+}
+
 #[test]
 fn test_editor_env_precedence() {
     // get_editor() checks VISUAL first, then EDITOR, then falls back to vi.
@@ -146,6 +332,80 @@ fn test_pane_exists_check_command() {
     assert!(cmd.contains(&"#{pane_id}".to_string()));
 }
 
+// ---------------------------------------------------------------------------
+// pane polling with fake tmux/clock
+// ---------------------------------------------------------------------------
+
+/// Fake tmux whose `list-panes` reports `%1` for the first `polls` calls.
+fn pane_open_for(polls: usize) -> FakeTmux {
+    let count = AtomicUsize::new(0);
+    FakeTmux::new(move |args| {
+        assert_eq!(args[0], "list-panes");
+        if count.fetch_add(1, Ordering::SeqCst) < polls {
+            Ok("%0\n%1\n".to_string())
+        } else {
+            Ok("%0\n".to_string())
+        }
+    })
+}
+
+#[test]
+fn test_pane_exists_with_fake_tmux() {
+    let tmux = FakeTmux::new(|_| Ok("%0\n%12\n".to_string()));
+    assert!(pane_exists_with(&tmux, "%12"));
+    assert!(!pane_exists_with(&tmux, "%1"));
+    assert_eq!(tmux.calls()[0], &build_pane_exists_check_command()[1..]);
+}
+
+#[test]
+fn test_pane_exists_treats_tmux_failure_as_closed() {
+    let tmux = FakeTmux::new(|_| anyhow::bail!("no server running"));
+    assert!(!pane_exists_with(&tmux, "%1"));
+}
+
+#[test]
+fn test_wait_for_pane_close_polls_until_gone() {
+    let tmux = Arc::new(pane_open_for(3));
+    let clock = Arc::new(FakeClock::new());
+    let rx = wait_for_pane_close_with(tmux.clone(), clock.clone(), "%1".to_string());
+
+    rx.recv_timeout(Duration::from_secs(5))
+        .expect("close should be signalled");
+    assert_eq!(tmux.calls().len(), 4);
+    assert_eq!(clock.sleeps(), 3);
+    assert_eq!(clock.elapsed(), PANE_POLL_INTERVAL * 3);
+}
+
+#[test]
+fn test_wait_for_pane_close_times_out() {
+    let tmux = Arc::new(FakeTmux::new(|_| Ok("%1\n".to_string())));
+    let clock = Arc::new(FakeClock::new());
+    let rx = wait_for_pane_close_with(tmux.clone(), clock.clone(), "%1".to_string());
+
+    // Signalled anyway so the UI never hangs, without real waiting
+    rx.recv_timeout(Duration::from_secs(5))
+        .expect("timeout should still signal");
+    assert_eq!(clock.sleeps(), MAX_PANE_POLL_ITERATIONS as usize);
+    assert_eq!(tmux.calls().len(), MAX_PANE_POLL_ITERATIONS as usize);
+}
+
+#[test]
+fn test_run_tmux_split_returns_pane_id() {
+    let tmux = FakeTmux::new(|_| Ok("%7\n".to_string()));
+    let cmd = build_tmux_split_command("vim", "/tmp/x");
+    assert_eq!(run_tmux_split(&tmux, &cmd).unwrap(), "%7");
+    assert_eq!(tmux.calls()[0], &cmd[1..]);
+}
+
+#[test]
+fn test_run_tmux_split_errors() {
+    let cmd = build_tmux_split_command("vim", "/tmp/x");
+    let failing = FakeTmux::new(|_| anyhow::bail!("tmux split-window failed: no space"));
+    assert!(run_tmux_split(&failing, &cmd).is_err());
+    let empty = FakeTmux::new(|_| Ok("\n".to_string()));
+    assert!(run_tmux_split(&empty, &cmd).is_err());
+}
+
 // ---------------------------------------------------------------------------
 // prepare_edit_tempfile
 // ---------------------------------------------------------------------------
@@ -380,6 +640,105 @@ fn test_parse_comments_whitespace_handling() {
     assert!(feedback.content.contains("no leading space"));
 }
 
+#[test]
+fn test_parse_comments_with_severity() {
+    let original = "# @@ -1,2 +1,2 @@\n\n a\n-b\n+c\n";
+    let edited = "# @@ -1,2 +1,2 @@\n\n a\n!suggestion name it d\n-b\n+c\n# COMMENT: !Blocker breaks the build\n";
+
+    let feedback = parse_comment_result(original, edited, "f.txt", "@@ -1,2 +1,2 @@", &[])
+        .expect("should capture the comments");
+    assert_eq!(feedback.content, "name it d\nbreaks the build");
+    assert_eq!(feedback.severity, Some(Severity::Blocker));
+
+    // An unknown word is part of the comment
+    let edited = "# @@ -1,2 +1,2 @@\n\n a\n!important\n-b\n+c\n";
+    let feedback = parse_comment_result(original, edited, "f.txt", "@@ -1,2 +1,2 @@", &[]).unwrap();
+    assert_eq!(feedback.content, "!important");
+    assert_eq!(feedback.severity, None);
+}
+
+#[test]
+fn test_file_comment_round_trip() {
+    let tmpfile = prepare_file_comment_tempfile("src/lib.rs", None).unwrap();
+    let template = std::fs::read_to_string(tmpfile.path()).unwrap();
+    assert!(template.starts_with("# Comment on src/lib.rs\n"));
+    assert!(parse_file_comment(&template, "src/lib.rs").is_none());
+
+    let edited = format!("{}!issue Split this module\nit does too much\n", template);
+    let feedback = parse_file_comment(&edited, "src/lib.rs").unwrap();
+    assert_eq!(feedback.kind, FeedbackKind::FileComment);
+    assert_eq!(feedback.file_path, "src/lib.rs");
+    assert!(feedback.hunk_header.is_empty());
+    assert_eq!(feedback.content, "Split this module\nit does too much");
+    assert_eq!(feedback.severity, Some(Severity::Issue));
+
+    // Revising a comment starts from its text and severity
+    let tmpfile = prepare_file_comment_tempfile("src/lib.rs", Some(&feedback)).unwrap();
+    let prefilled = std::fs::read_to_string(tmpfile.path()).unwrap();
+    let revised = parse_file_comment(&prefilled, "src/lib.rs").unwrap();
+    assert_eq!(revised.content, feedback.content);
+    assert_eq!(revised.severity, feedback.severity);
+}
+
+#[test]
+fn test_review_comment_lists_files() {
+    let files = vec![
+        "src/a.rs: 2 staged".to_string(),
+        "src/b.rs: 1 pending".to_string(),
+    ];
+    let tmpfile = prepare_review_comment_tempfile(&files, None).unwrap();
+    let template = std::fs::read_to_string(tmpfile.path()).unwrap();
+    assert!(template.contains("#   src/a.rs: 2 staged\n#   src/b.rs: 1 pending\n"));
+    assert!(parse_review_comment(&template).is_none());
+
+    let feedback = parse_review_comment(&format!("{}Ship it\n", template)).unwrap();
+    assert_eq!(feedback.kind, FeedbackKind::ReviewComment);
+    assert!(feedback.file_path.is_empty());
+    assert_eq!(feedback.content, "Ship it");
+}
+
+#[test]
+fn test_parse_comments_with_suggestion() {
+    let original = "\
+# Add your comments anywhere in this file.
+# @@ -1,3 +1,3 @@
+
+ fn main() {
+-    old();
++    code();
+ }
+";
+
+    let edited = "\
+# Add your comments anywhere in this file.
+# @@ -1,3 +1,3 @@
+
+ fn main() {
+-    old();
++    code();
+Handle the error:
+```suggestion
+    code()?;
+```
+ }
+";
+
+    let feedback = parse_comment_result(original, edited, "src/main.rs", "@@ -1,3 +1,3 @@", &[])
+        .expect("should capture the comment");
+    assert_eq!(feedback.content, "Handle the error:");
+    assert_eq!(feedback.suggestion.as_deref(), Some("    code()?;"));
+
+    // A suggestion alone is feedback too
+    let edited = original.replace(
+        "+    code();\n",
+        "+    code();\n```suggestion\n    code()?;\n```\n",
+    );
+    let feedback = parse_comment_result(original, &edited, "src/main.rs", "@@ -1,3 +1,3 @@", &[])
+        .expect("should capture the suggestion");
+    assert!(feedback.comment_positions.is_empty());
+    assert_eq!(feedback.suggestion.as_deref(), Some("    code()?;"));
+}
+
 // ---------------------------------------------------------------------------
 // Integration tests (require tmux, marked #[ignore])
 // ---------------------------------------------------------------------------
@@ -1,7 +1,11 @@
 mod helpers;
 
 use helpers::*;
-use stagent::git::{get_unstaged_diff, open_repo};
+use stagent::git::{
+    DiffAlgorithm, DiffSettings, cwd_prefix, get_staged_diff_with, get_unstaged_diff,
+    get_unstaged_diff_with, open_repo, to_repo_path,
+};
+use stagent::range::RevRange;
 use stagent::types::{DeltaStatus, LineKind};
 
 #[test]
@@ -255,14 +259,247 @@ fn test_binary_file_detected() {
     let file_diff = &diffs[0];
     assert_eq!(file_diff.path.display().to_string(), "image.bin");
 
-    // Binary files should have no text hunks parsed from the patch
-    // (git2's Patch API produces no hunks for binary content).
-    // Note: the BINARY flag in git2 is set lazily during content examination,
-    // so is_binary may or may not be set depending on when deltas are inspected.
-    // The key invariant is that binary diffs produce no parseable hunks.
+    // Binary files are reviewed as a whole: one placeholder hunk with no
+    // lines, showing the old and new sizes
+    assert!(file_diff.is_binary);
+    assert_eq!(file_diff.hunks.len(), 1);
+    assert!(file_diff.hunks[0].lines.is_empty());
+    assert_eq!(file_diff.hunks[0].header, "Binary file (256 → 256 bytes)");
+}
+
+#[test]
+fn test_open_repo_from_subdirectory() {
+    let (dir, repo) = create_temp_repo();
+    commit_file(&repo, "src/ui/view.rs", "a\n");
+    modify_file(&repo, "src/ui/view.rs", "b\n");
+
+    let sub = dir.path().join("src/ui");
+    let opened = open_repo(&sub).expect("discover from subdirectory");
+    let diffs = get_unstaged_diff(&opened).unwrap();
+    assert_eq!(diffs[0].path.to_string_lossy(), "src/ui/view.rs");
+    assert_eq!(cwd_prefix(&opened, &sub).to_string_lossy(), "src/ui");
+    assert_eq!(cwd_prefix(&opened, dir.path()).to_string_lossy(), "");
+}
+
+#[test]
+fn test_to_repo_path() {
+    let prefix = std::path::Path::new("src/ui");
+    assert_eq!(to_repo_path(prefix, "view.rs"), "src/ui/view.rs");
+    assert_eq!(to_repo_path(prefix, "./*.rs"), "src/ui/*.rs");
+    assert_eq!(to_repo_path(prefix, "../lib.rs"), "src/lib.rs");
+    assert_eq!(to_repo_path(prefix, "../../../x"), "../x");
+    assert_eq!(
+        to_repo_path(std::path::Path::new(""), "src/*.rs"),
+        "src/*.rs"
+    );
+}
+
+#[test]
+fn test_linked_worktree_diff_and_stage() {
+    let (dir, repo) = create_temp_repo();
+    commit_file(&repo, "src/a.txt", "one\n");
+
+    let parent = tempfile::TempDir::new().unwrap();
+    let wt_path = parent.path().join("wt");
+    repo.worktree("wt", &wt_path, None).unwrap();
+    std::fs::write(wt_path.join("src/a.txt"), "two\n").unwrap();
+
+    let wt = open_repo(wt_path.join("src")).expect("discover linked worktree");
+    assert!(wt.is_worktree());
+    let files = get_unstaged_diff(&wt).unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path.to_string_lossy(), "src/a.txt");
+
+    stagent::staging::stage_hunk(&wt, &files[0], &files[0].hunks[0], 0).unwrap();
+    assert!(get_unstaged_diff(&wt).unwrap().is_empty());
+    // The main checkout's index is untouched
+    let main = open_repo(dir.path()).unwrap();
+    assert!(get_unstaged_diff(&main).unwrap().is_empty());
+    let entry = main
+        .index()
+        .unwrap()
+        .get_path(std::path::Path::new("src/a.txt"), 0)
+        .unwrap();
+    assert_eq!(main.find_blob(entry.id).unwrap().content(), b"one\n");
+}
+
+#[test]
+fn test_ignore_whitespace_hides_reformatting() {
+    let (_dir, repo) = create_temp_repo();
+    commit_file(&repo, "a.txt", "fn f() {\n    x\n}\n");
+    commit_file(&repo, "b.txt", "one\ntwo\n");
+    modify_file(&repo, "a.txt", "fn f() {\n\tx  \n}\n");
+    modify_file(&repo, "b.txt", "one\ntwo!\n");
+
+    assert_eq!(get_unstaged_diff(&repo).unwrap().len(), 2);
+    let settings = DiffSettings {
+        ignore_whitespace: true,
+        ..Default::default()
+    };
+    let diffs = get_unstaged_diff_with(&repo, &settings).unwrap();
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].path.to_str(), Some("b.txt"));
+}
+
+#[test]
+fn test_ignore_blank_lines_hides_blank_line_changes() {
+    let (_dir, repo) = create_temp_repo();
+    commit_file(&repo, "a.txt", "one\ntwo\nthree\n");
+    modify_file(&repo, "a.txt", "one\n\n\ntwo\nthree\n");
+
+    let settings = DiffSettings {
+        ignore_blank_lines: true,
+        ..Default::default()
+    };
+    assert!(get_unstaged_diff_with(&repo, &settings).unwrap().is_empty());
+    assert_eq!(get_unstaged_diff(&repo).unwrap().len(), 1);
+}
+
+#[test]
+fn test_context_lines_control_hunk_merging() {
+    let (_dir, repo) = create_temp_repo();
+    let original: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+    commit_file(&repo, "a.txt", &original);
+    // Changes 4 lines apart: one hunk with 3 lines of context, two with 1
+    let modified = original
+        .replace("line 5\n", "five\n")
+        .replace("line 10\n", "ten\n");
+    modify_file(&repo, "a.txt", &modified);
+
+    assert_eq!(get_unstaged_diff(&repo).unwrap()[0].hunks.len(), 1);
+    let settings = DiffSettings {
+        context_lines: Some(1),
+        ..Default::default()
+    };
+    let diffs = get_unstaged_diff_with(&repo, &settings).unwrap();
+    assert_eq!(diffs[0].hunks.len(), 2);
+    assert_eq!(diffs[0].hunks[0].lines.len(), 4);
+}
+
+/// The removed lines of the first file's hunks, without newlines.
+fn removed_lines(repo: &git2::Repository, settings: &DiffSettings) -> Vec<String> {
+    let diffs = get_unstaged_diff_with(repo, settings).unwrap();
+    diffs[0]
+        .hunks
+        .iter()
+        .flat_map(|h| &h.lines)
+        .filter(|l| l.kind == LineKind::Removed)
+        .map(|l| l.content.trim_end().to_string())
+        .collect()
+}
+
+#[test]
+fn test_diff_algorithm_from_git_config() {
+    let (_dir, repo) = create_temp_repo();
+    commit_file(&repo, "f.txt", "b\na\n}\na\n{\n{\n");
+    modify_file(&repo, "f.txt", "{\n{\nb\na\n{\na\n");
+
+    assert_eq!(
+        removed_lines(&repo, &DiffSettings::default()),
+        vec!["}", "a", "{"]
+    );
+
+    let mut config = repo.config().unwrap();
+    config.set_str("diff.algorithm", "patience").unwrap();
+    assert_eq!(
+        removed_lines(&repo, &DiffSettings::default()),
+        vec!["}", "{", "{"]
+    );
+
+    // --diff-algorithm takes precedence over the config
+    let settings = DiffSettings {
+        algorithm: Some(DiffAlgorithm::Myers),
+        ..DiffSettings::default()
+    };
+    assert_eq!(removed_lines(&repo, &settings), vec!["}", "a", "{"]);
+
+    config.set_str("diff.algorithm", "quadratic").unwrap();
+    let err = get_unstaged_diff(&repo).unwrap_err();
     assert!(
-        file_diff.hunks.is_empty(),
-        "binary file should have no parsed hunks, got {}",
-        file_diff.hunks.len()
+        format!("{:#}", err).contains("diff.algorithm: unknown diff algorithm 'quadratic'"),
+        "{:#}",
+        err
     );
 }
+
+#[test]
+fn test_core_excludes_file_hides_untracked_files() {
+    let (dir, repo) = create_temp_repo();
+    commit_file(&repo, "tracked.txt", "content\n");
+    create_untracked_file(&repo, "notes.scratch", "local notes\n");
+    create_untracked_file(&repo, "new.txt", "new\n");
+
+    let excludes = dir.path().join(".git").join("global-excludes");
+    std::fs::write(&excludes, "*.scratch\n").unwrap();
+    repo.config()
+        .unwrap()
+        .set_str("core.excludesFile", excludes.to_str().unwrap())
+        .unwrap();
+
+    // The excludes file is read when the repository is opened
+    let repo = open_repo(dir.path()).unwrap();
+    let diffs = get_unstaged_diff(&repo).unwrap();
+    let paths: Vec<_> = diffs.iter().map(|f| f.path.display().to_string()).collect();
+    assert_eq!(paths, vec!["new.txt"]);
+}
+
+#[test]
+fn test_staged_diff_is_head_to_index() {
+    let (_dir, repo) = create_temp_repo();
+    commit_file(&repo, "a.txt", "one\n");
+    modify_file(&repo, "a.txt", "two\n");
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new("a.txt")).unwrap();
+    index.write().unwrap();
+    // Unstaged on top of the staged change: not part of the staged diff
+    modify_file(&repo, "a.txt", "three\n");
+    create_untracked_file(&repo, "new.txt", "hello\n");
+
+    let files = get_staged_diff_with(&repo, &DiffSettings::default()).unwrap();
+    assert_eq!(files.len(), 1);
+    let lines: Vec<_> = files[0].hunks[0]
+        .lines
+        .iter()
+        .map(|l| (l.kind, l.content.as_str()))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![(LineKind::Removed, "one\n"), (LineKind::Added, "two\n")]
+    );
+}
+
+#[test]
+fn test_range_diff_and_commits() {
+    let (_dir, repo) = create_temp_repo();
+    commit_file(&repo, "a.txt", "one\n");
+    commit_file(&repo, "a.txt", "two\n");
+    commit_file(&repo, "b.txt", "three\n");
+    // Not part of the range
+    modify_file(&repo, "a.txt", "four\n");
+
+    for spec in ["HEAD~2..HEAD", "HEAD~2"] {
+        let range = RevRange::resolve(&repo, spec).unwrap();
+        let commits = range.commits(&repo).unwrap();
+        let summaries: Vec<_> = commits.iter().map(|c| c.summary.as_str()).collect();
+        assert_eq!(summaries, ["Add a.txt", "Add b.txt"], "{}", spec);
+        assert!(commits[0].label().ends_with(" Add a.txt"));
+
+        let files = range.diff(&repo, &DiffSettings::default()).unwrap();
+        let paths: Vec<_> = files.iter().map(|f| f.path.to_str().unwrap()).collect();
+        assert_eq!(paths, ["a.txt", "b.txt"]);
+        let added: Vec<_> = files[0].hunks[0]
+            .lines
+            .iter()
+            .filter(|l| l.kind == LineKind::Added)
+            .map(|l| l.content.as_str())
+            .collect();
+        assert_eq!(
+            added,
+            ["two\n"],
+            "the committed change, not the working tree's"
+        );
+    }
+
+    assert!(RevRange::resolve(&repo, "HEAD..HEAD").is_err());
+    assert!(RevRange::resolve(&repo, "nope..HEAD").is_err());
+}
@@ -5,6 +5,11 @@ use std::fs;
 use std::path::Path;
 use tempfile::TempDir;
 
+#[cfg(feature = "tui")]
+pub mod render;
+#[cfg(feature = "tui")]
+pub mod tmux;
+
 /// Create a temporary git repository with an initial commit.
 pub fn create_temp_repo() -> (TempDir, Repository) {
     let dir = TempDir::new().expect("Failed to create temp dir");
@@ -25,6 +30,11 @@ pub fn create_temp_repo() -> (TempDir, Repository) {
 
 /// Add and commit a file to the repository.
 pub fn commit_file(repo: &Repository, path: &str, content: &str) {
+    commit_bytes(repo, path, content.as_bytes());
+}
+
+/// Add and commit a file with raw (possibly non-UTF8) content.
+pub fn commit_bytes(repo: &Repository, path: &str, content: &[u8]) {
     let workdir = repo.workdir().expect("Not a bare repo");
     let full_path = workdir.join(path);
 
@@ -59,6 +69,11 @@ pub fn commit_file(repo: &Repository, path: &str, content: &str) {
 
 /// Modify a file in the working directory (without staging).
 pub fn modify_file(repo: &Repository, path: &str, content: &str) {
+    modify_bytes(repo, path, content.as_bytes());
+}
+
+/// Modify a file in the working directory with raw content.
+pub fn modify_bytes(repo: &Repository, path: &str, content: &[u8]) {
     let workdir = repo.workdir().expect("Not a bare repo");
     let full_path = workdir.join(path);
 
@@ -102,3 +117,65 @@ pub fn create_binary_file(repo: &Repository, path: &str) {
     let binary_content: Vec<u8> = (0..256).map(|i| i as u8).collect();
     fs::write(&full_path, &binary_content).unwrap();
 }
+
+/// Create a repository at `path` inside `repo`'s working directory with one
+/// commit, and commit it to `repo` as a submodule. Returns the submodule.
+pub fn add_submodule(repo: &Repository, path: &str) -> Repository {
+    let workdir = repo.workdir().expect("Not a bare repo");
+    let sub = Repository::init(workdir.join(path)).unwrap();
+    let commit = commit_in_submodule(&sub, "one\n");
+
+    fs::write(
+        workdir.join(".gitmodules"),
+        format!("[submodule \"{0}\"]\n\tpath = {0}\n\turl = ./{0}\n", path),
+    )
+    .unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(".gitmodules")).unwrap();
+    index
+        .add(&git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o160000,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id: commit,
+            flags: 0,
+            flags_extended: 0,
+            path: path.as_bytes().to_vec(),
+        })
+        .unwrap();
+    index.write().unwrap();
+
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = Signature::now("Test", "test@test.com").unwrap();
+    let parent = repo.head().unwrap().peel_to_commit().unwrap();
+    repo.commit(
+        Some("HEAD"),
+        &sig,
+        &sig,
+        &format!("Add submodule {}", path),
+        &tree,
+        &[&parent],
+    )
+    .unwrap();
+    sub
+}
+
+/// Commit `content` as `file.txt` in a submodule, moving its HEAD.
+pub fn commit_in_submodule(sub: &Repository, content: &str) -> git2::Oid {
+    let workdir = sub.workdir().expect("Not a bare repo");
+    fs::write(workdir.join("file.txt"), content).unwrap();
+    let mut index = sub.index().unwrap();
+    index.add_path(Path::new("file.txt")).unwrap();
+    index.write().unwrap();
+    let tree = sub.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = Signature::now("Test", "test@test.com").unwrap();
+    let parent = sub.head().ok().and_then(|h| h.peel_to_commit().ok());
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    sub.commit(Some("HEAD"), &sig, &sig, "Update", &tree, &parents)
+        .unwrap()
+}
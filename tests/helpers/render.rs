@@ -0,0 +1,36 @@
+//! Rendering the TUI into a [`TestBackend`] for assertions on its output.
+
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+use ratatui::buffer::Buffer;
+use stagent::app::App;
+use stagent::highlight::Highlighter;
+use stagent::ui;
+
+/// Render the UI into a `width` x `height` buffer.
+pub fn render_to_buffer(width: u16, height: u16, app: &mut App) -> Buffer {
+    let backend = TestBackend::new(width, height);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let highlighter = Highlighter::new();
+
+    terminal
+        .draw(|frame| {
+            ui::render(frame, app, &highlighter);
+        })
+        .unwrap();
+
+    terminal.backend().buffer().clone()
+}
+
+/// Render the UI and return the buffer content as a string, one line per row.
+pub fn render_to_string(width: u16, height: u16, app: &mut App) -> String {
+    let buffer = render_to_buffer(width, height, app);
+    let mut output = String::new();
+    for y in 0..buffer.area.height {
+        for x in 0..buffer.area.width {
+            output.push_str(buffer[(x, y)].symbol());
+        }
+        output.push('\n');
+    }
+    output
+}
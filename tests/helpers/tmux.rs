@@ -0,0 +1,218 @@
+//! Test support for driving stagent inside a real, isolated tmux server.
+//!
+//! Every [`TmuxServer`] runs on its own socket (`tmux -L stagent-test-…`) with
+//! no user config, so tests neither touch the developer's tmux session nor
+//! each other. Processes started in the server see a `TMUX` variable pointing
+//! at that socket, so the `tmux split-window` / `list-panes` calls stagent
+//! makes land in the same isolated server.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+static NEXT_SERVER: AtomicUsize = AtomicUsize::new(0);
+
+/// Default timeout for [`TmuxServer::wait_for`].
+pub const WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An isolated tmux server, killed on drop.
+pub struct TmuxServer {
+    socket: String,
+    /// HOME for processes in the server, so stagent's help marker and
+    /// config never leak between tests or into the real home directory.
+    home: tempfile::TempDir,
+}
+
+impl TmuxServer {
+    /// Whether a usable tmux binary is installed.
+    pub fn available() -> bool {
+        Command::new("tmux")
+            .arg("-V")
+            .output()
+            .is_ok_and(|o| o.status.success())
+    }
+
+    /// Start a server whose first pane runs `command` in `cwd`.
+    ///
+    /// The stagent help overlay is pre-dismissed. Returns `None` when tmux
+    /// is unavailable, so callers can skip.
+    pub fn start(cwd: &Path, command: &str, env: &[(&str, &str)]) -> Option<Self> {
+        if !Self::available() {
+            eprintln!("Skipping: tmux is not installed");
+            return None;
+        }
+
+        let socket = format!(
+            "stagent-test-{}-{}",
+            std::process::id(),
+            NEXT_SERVER.fetch_add(1, Ordering::SeqCst)
+        );
+        let home = tempfile::TempDir::new().expect("create tmux test home");
+        let marker = home.path().join(".config/stagent/help_shown");
+        std::fs::create_dir_all(marker.parent().unwrap()).unwrap();
+        std::fs::write(&marker, "").unwrap();
+
+        let server = Self { socket, home };
+        let mut args: Vec<String> = [
+            "-f",
+            "/dev/null",
+            "new-session",
+            "-d",
+            "-s",
+            "test",
+            "-x",
+            "200",
+            "-y",
+            "50",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        args.push("-c".to_string());
+        args.push(cwd.to_string_lossy().to_string());
+        let home_var = format!("HOME={}", server.home.path().display());
+        for assignment in
+            std::iter::once(home_var).chain(env.iter().map(|(k, v)| format!("{}={}", k, v)))
+        {
+            args.push("-e".to_string());
+            args.push(assignment);
+        }
+        args.push(command.to_string());
+
+        let output = server.tmux(&args.iter().map(String::as_str).collect::<Vec<_>>());
+        assert!(
+            output.status.success(),
+            "failed to start tmux server: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Some(server)
+    }
+
+    /// HOME used by processes in the server.
+    pub fn home(&self) -> PathBuf {
+        self.home.path().to_path_buf()
+    }
+
+    /// Run a tmux command against this server.
+    pub fn tmux(&self, args: &[&str]) -> Output {
+        Command::new("tmux")
+            .arg("-L")
+            .arg(&self.socket)
+            .args(args)
+            .output()
+            .expect("failed to run tmux")
+    }
+
+    /// Pane ids in the session, in creation order.
+    pub fn panes(&self) -> Vec<String> {
+        let output = self.tmux(&["list-panes", "-s", "-F", "#{pane_id}"]);
+        let mut panes: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect();
+        panes.sort_by_key(|p| p.trim_start_matches('%').parse::<u32>().unwrap_or(0));
+        panes
+    }
+
+    /// The first pane of the session.
+    pub fn first_pane(&self) -> String {
+        self.panes()
+            .into_iter()
+            .next()
+            .expect("session has no panes")
+    }
+
+    /// Send keys (tmux key names, e.g. `"y"`, `"Enter"`, `"Escape"`) to a pane.
+    pub fn send_keys(&self, pane: &str, keys: &[&str]) {
+        let mut args = vec!["send-keys", "-t", pane];
+        args.extend_from_slice(keys);
+        let output = self.tmux(&args);
+        assert!(
+            output.status.success(),
+            "send-keys failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    /// Send literal text to a pane (no key-name lookup).
+    pub fn send_text(&self, pane: &str, text: &str) {
+        self.tmux(&["send-keys", "-t", pane, "-l", text]);
+    }
+
+    /// Current visible content of a pane.
+    pub fn capture(&self, pane: &str) -> String {
+        let output = self.tmux(&["capture-pane", "-p", "-t", pane]);
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    /// Whether the process in `pane` has exited. Panes are destroyed when
+    /// their process exits (stagent's own pane-close detection relies on
+    /// that), so this checks that the pane is gone.
+    pub fn pane_closed(&self, pane: &str) -> bool {
+        !self.panes().iter().any(|p| p == pane)
+    }
+
+    /// Poll `pane` until its content contains `needle`. Returns the captured
+    /// screen, or panics with the last capture on timeout.
+    pub fn wait_for(&self, pane: &str, needle: &str) -> String {
+        let mut last = String::new();
+        let ok = poll(WAIT_TIMEOUT, || {
+            last = self.capture(pane);
+            last.contains(needle)
+        });
+        assert!(ok, "timed out waiting for {:?} in pane:\n{}", needle, last);
+        last
+    }
+
+    /// Poll until `pane`'s process has exited.
+    pub fn wait_for_exit(&self, pane: &str) {
+        let ok = poll(WAIT_TIMEOUT, || self.pane_closed(pane));
+        assert!(ok, "pane did not exit:\n{}", self.capture(pane));
+    }
+
+    /// Poll until the session has `count` panes.
+    pub fn wait_for_pane_count(&self, count: usize) -> Vec<String> {
+        let mut panes = Vec::new();
+        let ok = poll(WAIT_TIMEOUT, || {
+            panes = self.panes();
+            panes.len() == count
+        });
+        assert!(ok, "expected {} panes, found {:?}", count, panes);
+        panes
+    }
+}
+
+impl Drop for TmuxServer {
+    fn drop(&mut self) {
+        let _ = self.tmux(&["kill-server"]);
+    }
+}
+
+/// Call `check` every 100ms until it returns true or `timeout` elapses.
+pub fn poll(timeout: Duration, mut check: impl FnMut() -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if check() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    check()
+}
+
+/// Path to the stagent binary built for the integration tests.
+pub fn stagent_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_stagent"))
+}
+
+/// Write an executable shell script to `dir/name` and return its path.
+/// Used as a fake `$VISUAL` that edits the file non-interactively.
+pub fn write_script(dir: &Path, name: &str, body: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
@@ -0,0 +1,99 @@
+mod helpers;
+
+use helpers::*;
+use stagent::git::get_unstaged_diff;
+use stagent::notes;
+use stagent::types::{FeedbackKind, HunkFeedback, HunkStatus};
+
+#[test]
+fn test_write_and_load_review_notes() {
+    let (_dir, repo) = create_temp_repo();
+    commit_file(&repo, "a.txt", "one\ntwo\n");
+    commit_file(&repo, "b.txt", "alpha\n");
+    modify_file(&repo, "a.txt", "one\nTWO\n");
+    modify_file(&repo, "b.txt", "beta\n");
+
+    let mut files = get_unstaged_diff(&repo).unwrap();
+    files[0].hunks[0].status = HunkStatus::Skipped;
+    let feedback = vec![HunkFeedback {
+        file_path: "a.txt".to_string(),
+        hunk_header: files[0].hunks[0].header.clone(),
+        kind: FeedbackKind::Comment,
+        content: "why uppercase?\nsecond line".to_string(),
+        context_lines: vec![],
+        comment_positions: vec![],
+        suggestion: None,
+        severity: None,
+    }];
+
+    // b.txt was not reviewed, so only a.txt gets a note
+    let written = notes::write_notes(&repo, &files, &feedback).unwrap();
+    assert_eq!(written, 1);
+
+    let loaded = notes::load_notes(&repo, &files);
+    assert_eq!(loaded.len(), 1);
+    let note = &loaded[&files[0].path];
+    assert!(note.contains("stagent review: a.txt"));
+    assert!(note.contains("Hunks: 0 staged, 1 skipped, 0 pending"));
+    assert!(note.contains("comment: why uppercase?"));
+    assert!(!note.contains("second line"));
+    assert!(notes::summarize(note).contains("1 skipped"));
+}
+
+#[test]
+fn test_note_not_shown_for_changed_content() {
+    let (_dir, repo) = create_temp_repo();
+    commit_file(&repo, "a.txt", "one\n");
+    modify_file(&repo, "a.txt", "two\n");
+
+    let mut files = get_unstaged_diff(&repo).unwrap();
+    files[0].hunks[0].status = HunkStatus::Staged;
+    notes::write_notes(&repo, &files, &[]).unwrap();
+
+    modify_file(&repo, "a.txt", "three\n");
+    let files = get_unstaged_diff(&repo).unwrap();
+    assert!(notes::load_notes(&repo, &files).is_empty());
+}
+
+#[test]
+fn test_format_timestamp() {
+    assert_eq!(notes::format_timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(notes::format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+    assert_eq!(
+        notes::format_timestamp(1_760_000_000),
+        "2025-10-09T08:53:20Z"
+    );
+}
+
+#[test]
+fn test_reminders_from_archived_feedback() {
+    use stagent::app::App;
+
+    let (_dir, repo) = create_temp_repo();
+    commit_file(&repo, "a.txt", "one\ntwo\nthree\n");
+    commit_file(&repo, "b.txt", "alpha\n");
+    modify_file(&repo, "a.txt", "one\nTWO\nthree\n");
+    modify_file(&repo, "b.txt", "beta\n");
+
+    // First round: comment on both files and archive the feedback
+    let mut first = App::new_with_help(get_unstaged_diff(&repo).unwrap(), true, false);
+    first.add_line_comment(2, "why uppercase?");
+    first.selected_file = 1;
+    first.add_line_comment(1, "rename");
+    let output = stagent::feedback::format_feedback(&first.feedback, 5);
+    stagent::archive::archive_feedback(&stagent::archive::archive_dir(&repo), &output).unwrap();
+
+    // Only b.txt is addressed before the next round
+    modify_file(&repo, "b.txt", "gamma\n");
+    let mut app = App::new_with_help(get_unstaged_diff(&repo).unwrap(), true, false);
+    app.previous_comments = stagent::reminders::load(&repo, None, &Default::default());
+    assert_eq!(app.previous_comments.len(), 2);
+    assert_eq!(app.unaddressed_comments(0, 0).len(), 1);
+    assert!(app.unaddressed_comments(1, 0).is_empty());
+
+    app.show_previous_comments();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("Previous comment: why uppercase?")
+    );
+}
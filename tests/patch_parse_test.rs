@@ -174,3 +174,124 @@ fn test_patch_parse_added_lines_only() {
     assert_eq!(added[0].content, "line 3\n");
     assert_eq!(added[1].content, "line 4\n");
 }
+
+#[test]
+fn test_patch_apply_diff_settings_drops_ignored_hunks() {
+    let input = "diff --git a/f b/f\n--- a/f\n+++ b/f\n\
+                 @@ -1,3 +1,4 @@\n a\n+\n b\n c\n\
+                 @@ -10,2 +11,2 @@\n x\n-if a  {\n+if a {\n\
+                 @@ -20,2 +21,2 @@\n y\n-old\n+new\n";
+    let settings = |ignore_whitespace, ignore_blank_lines| stagent::git::DiffSettings {
+        ignore_whitespace,
+        ignore_blank_lines,
+        ..Default::default()
+    };
+    let hunks = |ignore_whitespace, ignore_blank_lines| {
+        let mut files = parse_unified_diff(input).unwrap();
+        stagent::patch::apply_diff_settings(
+            &mut files,
+            &settings(ignore_whitespace, ignore_blank_lines),
+        );
+        files[0]
+            .hunks
+            .iter()
+            .map(|h| h.old_start)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(hunks(false, false), [1, 10, 20]);
+    assert_eq!(hunks(true, false), [1, 20]);
+    assert_eq!(hunks(false, true), [10, 20]);
+    assert_eq!(hunks(true, true), [20]);
+}
+
+/// Strict-diff feedback parses back into the hunks it was given on, and
+/// applies as a patch, whatever the comments in between say.
+#[test]
+fn test_strict_diff_feedback_round_trips() {
+    use stagent::feedback::format_feedback_as;
+    use stagent::review::ReviewSession;
+    use stagent::types::{
+        FeedbackFormat, FeedbackKind, HunkFeedback, OutputStyle, ReviewVerdict, Verdict,
+    };
+
+    let (dir, repo) = create_temp_repo();
+    let original: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+    commit_file(&repo, "big.txt", &original);
+    commit_file(&repo, "tail.txt", "a\nb");
+    modify_file(
+        &repo,
+        "big.txt",
+        &original
+            .replace("line 2\n", "line 2 CHANGED\n")
+            .replace("line 28\n", "line 28 CHANGED\n+ not an added line\n"),
+    );
+    modify_file(&repo, "tail.txt", "a\nc");
+
+    let files = parse_unified_diff(&git_diff_output(&repo)).unwrap();
+    let mut review = ReviewSession::new(files.clone());
+    review
+        .comment_on_line(0, 0, 1, "!blocker Why?\n\n- a list")
+        .unwrap();
+    review.comment(0, 1, "Also here").unwrap();
+    review.comment(0, 1, "@@ -1 +1 @@ and not a hunk").unwrap();
+    review.comment(1, 0, "diff --git a/x b/x").unwrap();
+    let (_, mut feedback) = review.into_parts().unwrap();
+    feedback.push(HunkFeedback {
+        file_path: "big.txt".to_string(),
+        hunk_header: String::new(),
+        kind: FeedbackKind::FileComment,
+        content: "Split this file".to_string(),
+        context_lines: vec![],
+        comment_positions: vec![],
+        suggestion: None,
+        severity: None,
+    });
+    let verdict = ReviewVerdict {
+        verdict: Verdict::RequestChanges,
+        summary: Some("Nearly".to_string()),
+    };
+
+    let output = format_feedback_as(
+        &feedback,
+        Some(&verdict),
+        None,
+        3,
+        FeedbackFormat::Diff,
+        OutputStyle::StrictDiff,
+    )
+    .unwrap();
+    // After the removed line: on the old side
+    assert!(output.contains("#=== REVIEW COMMENT big.txt old line 2 ===\n# SEVERITY: blocker\n"));
+    assert!(output.contains("#=== FILE COMMENT big.txt ===\n# Split this file\n#=== END ===\n"));
+
+    let parsed = parse_unified_diff(&output).unwrap();
+    assert_eq!(parsed.len(), files.len());
+    for (parsed, file) in parsed.iter().zip(&files) {
+        assert_eq!(parsed.path, file.path);
+        assert_eq!(parsed.hunks.len(), file.hunks.len());
+        for (parsed, hunk) in parsed.hunks.iter().zip(&file.hunks) {
+            assert_eq!(parsed.header, hunk.header);
+            let lines = |h: &stagent::types::Hunk| -> Vec<_> {
+                h.lines
+                    .iter()
+                    .map(|l| {
+                        let numbers = (l.old_lineno, l.new_lineno);
+                        (l.kind, l.content.clone(), numbers, l.no_newline)
+                    })
+                    .collect()
+            };
+            assert_eq!(lines(parsed), lines(hunk), "{}", output);
+        }
+    }
+
+    // The index still has the committed files: the output applies to it
+    let patch = dir.path().join("feedback.diff");
+    std::fs::write(&patch, &output).unwrap();
+    let status = Command::new("git")
+        .args(["apply", "--cached", "--check"])
+        .arg(&patch)
+        .current_dir(dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success(), "{}", output);
+}
@@ -0,0 +1,131 @@
+mod helpers;
+
+use helpers::*;
+use stagent::app::App;
+use stagent::editor::{
+    EDIT_REGION_END, EDIT_REGION_START, MAX_FRAGMENT_EDIT_LINES, exceeds_fragment_limit,
+    extract_edit_region, is_region_edit, parse_region_edit_result, prepare_region_edit_tempfile,
+};
+use stagent::git::get_unstaged_diff;
+use stagent::types::{AppMode, FeedbackKind, HunkStatus};
+
+/// `count` numbered lines, `line 1\n` … `line N\n`.
+fn numbered(count: usize) -> String {
+    (1..=count).map(|i| format!("line {}\n", i)).collect()
+}
+
+/// Repo where `big.rs` gets a hunk longer than the fragment limit, starting
+/// well into the file. Returns the temp dir, repo, and file content.
+fn repo_with_large_hunk() -> (tempfile::TempDir, git2::Repository, String) {
+    let (dir, repo) = create_temp_repo();
+    let head = numbered(20);
+    commit_file(&repo, "big.rs", &head);
+
+    let mut content = head.clone();
+    for i in 0..MAX_FRAGMENT_EDIT_LINES + 5 {
+        content.push_str(&format!("added {}\n", i));
+    }
+    content.push_str(&numbered(3));
+    modify_file(&repo, "big.rs", &content);
+    (dir, repo, content)
+}
+
+#[test]
+fn test_region_tempfile_wraps_hunk_in_markers() {
+    let (_dir, repo, content) = repo_with_large_hunk();
+    let files = get_unstaged_diff(&repo).unwrap();
+    let hunk = &files[0].hunks[0];
+    assert!(exceeds_fragment_limit(hunk));
+
+    let (tmpfile, line) = prepare_region_edit_tempfile(&content, hunk, Some("rs"))
+        .unwrap()
+        .expect("file matches the hunk");
+    assert!(tmpfile.path().to_string_lossy().ends_with(".rs"));
+    assert_eq!(line, hunk.new_start);
+
+    let written = std::fs::read_to_string(tmpfile.path()).unwrap();
+    assert!(is_region_edit(&written));
+    // The whole file is present, plus the two marker lines
+    assert_eq!(written.lines().count(), content.lines().count() + 2);
+    let (start, region) = extract_edit_region(&written).unwrap();
+    assert_eq!(start, hunk.new_start);
+    assert_eq!(
+        region,
+        stagent::editor::extract_new_side_content(&hunk.lines)
+    );
+}
+
+#[test]
+fn test_region_tempfile_declines_when_file_changed() {
+    let (_dir, repo, _content) = repo_with_large_hunk();
+    let files = get_unstaged_diff(&repo).unwrap();
+
+    let changed = numbered(5);
+    let prepared = prepare_region_edit_tempfile(&changed, &files[0].hunks[0], None).unwrap();
+    assert!(prepared.is_none());
+}
+
+#[test]
+fn test_region_edit_diff_uses_file_line_numbers() {
+    let original = format!(
+        "a\nb\n{}\nx\ny\nz\n{}\nc\n",
+        EDIT_REGION_START, EDIT_REGION_END
+    );
+    let edited = format!(
+        "a\nEDITED OUTSIDE\nb\n{}\nx\nY\nz\n{}\nc\n",
+        EDIT_REGION_START, EDIT_REGION_END
+    );
+
+    let fb = parse_region_edit_result(&original, &edited, "f.rs", "@@ -1 +1 @@", &[]).unwrap();
+    assert_eq!(fb.kind, FeedbackKind::Edit);
+    // Region starts at file line 3; edits outside the markers are ignored
+    assert!(fb.content.starts_with("@@ -3,3 +3,3 @@"), "{}", fb.content);
+    assert!(fb.content.contains("+Y"));
+    assert!(!fb.content.contains("OUTSIDE"));
+}
+
+#[test]
+fn test_region_edit_with_removed_marker_is_ignored() {
+    let original = format!("{}\nx\n{}\n", EDIT_REGION_START, EDIT_REGION_END);
+    let edited = "x\nchanged\n";
+    assert!(parse_region_edit_result(&original, edited, "f.rs", "@@", &[]).is_none());
+}
+
+#[test]
+fn test_app_edits_large_hunk_in_file_region() {
+    let (_dir, repo, _content) = repo_with_large_hunk();
+    let files = get_unstaged_diff(&repo).unwrap();
+    let mut app = App::new(files, true);
+
+    let (tmpfile, _) = app
+        .prepare_region_edit(Some(&repo))
+        .unwrap()
+        .expect("large hunk uses region edit");
+    let original = std::fs::read_to_string(tmpfile.path()).unwrap();
+    std::fs::write(
+        tmpfile.path(),
+        original.replace("added 3\n", "added three\n"),
+    )
+    .unwrap();
+
+    app.mode = AppMode::WaitingForEditor;
+    assert!(app.flush_pending_editor_state(tmpfile.path(), false, &original));
+    let fb = &app.feedback[0];
+    // "added 3" is line 24 of big.rs; the diff context starts 3 lines earlier
+    assert!(
+        fb.content.starts_with("@@ -21,7 +21,7 @@"),
+        "{}",
+        fb.content
+    );
+    assert!(fb.content.contains("+added three"));
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Edited);
+}
+
+#[test]
+fn test_small_hunk_keeps_fragment_edit() {
+    let (_dir, repo) = create_temp_repo();
+    commit_file(&repo, "a.txt", "one\n");
+    modify_file(&repo, "a.txt", "two\n");
+    let app = App::new(get_unstaged_diff(&repo).unwrap(), true);
+    assert!(app.prepare_region_edit(Some(&repo)).unwrap().is_none());
+}
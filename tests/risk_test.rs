@@ -0,0 +1,63 @@
+mod helpers;
+
+use git2::{Repository, Signature};
+use stagent::git::get_unstaged_diff;
+use stagent::risk::{order_by_risk, score_files};
+use std::path::Path;
+
+/// Commit `content` to `path` with a custom message.
+fn commit_with_message(repo: &Repository, path: &str, content: &str, message: &str) {
+    std::fs::write(repo.workdir().unwrap().join(path), content).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(path)).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = Signature::now("Test", "test@test.com").unwrap();
+    let parent = repo.head().unwrap().peel_to_commit().unwrap();
+    repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])
+        .unwrap();
+}
+
+#[test]
+fn test_fix_history_raises_risk() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a_cold.txt", "one\ntwo\nthree\n");
+    helpers::commit_file(&repo, "b_hot.txt", "one\ntwo\nthree\n");
+    for i in 0..4 {
+        commit_with_message(
+            &repo,
+            "b_hot.txt",
+            &format!("one\ntwo {}\nthree\n", i),
+            "Fix crash on empty input",
+        );
+    }
+    helpers::modify_file(&repo, "a_cold.txt", "one\nTWO\nthree\n");
+    helpers::modify_file(&repo, "b_hot.txt", "one\nTWO\nthree\n");
+
+    let mut files = get_unstaged_diff(&repo).unwrap();
+    let scores = score_files(&repo, &files);
+    let cold = *scores.get(&files[0].path, &files[0].hunks[0]).unwrap();
+    let hot = *scores.get(&files[1].path, &files[1].hunks[0]).unwrap();
+
+    assert_eq!(cold.churn, 1);
+    assert_eq!(cold.fix_lines, 0);
+    assert_eq!(hot.churn, 5);
+    assert_eq!(hot.fix_lines, 1, "only the changed line came from a fix");
+    assert_eq!(hot.blamed_lines, 3);
+    assert!(hot.score > cold.score, "{:?} vs {:?}", hot, cold);
+
+    order_by_risk(&mut files, &scores);
+    assert_eq!(files[0].path, Path::new("b_hot.txt"));
+}
+
+#[test]
+fn test_untracked_files_score_zero() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::create_untracked_file(&repo, "new.txt", "hello\n");
+    stagent::git::intent_to_add_untracked(&repo).unwrap();
+
+    let files = get_unstaged_diff(&repo).unwrap();
+    let scores = score_files(&repo, &files);
+    let risk = scores.get(&files[0].path, &files[0].hunks[0]).unwrap();
+    assert_eq!((risk.score, risk.churn, risk.blamed_lines), (0, 0, 0));
+}
@@ -0,0 +1,175 @@
+mod helpers;
+
+use helpers::*;
+use stagent::app::App;
+use stagent::git::get_unstaged_diff;
+use stagent::session::{self, Session};
+use stagent::staging::stage_hunk;
+use stagent::types::{AppMode, FeedbackKind, HunkFeedback, HunkStatus};
+
+/// 30-line file with changes on lines 2 and 28 so git produces two hunks.
+fn two_hunk_repo() -> (tempfile::TempDir, git2::Repository) {
+    let (dir, repo) = create_temp_repo();
+    let original: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+    commit_file(&repo, "big.txt", &original);
+    let modified: String = (1..=30)
+        .map(|i| match i {
+            2 => "line 2 CHANGED\n".to_string(),
+            28 => "line 28 CHANGED\n".to_string(),
+            _ => format!("line {}\n", i),
+        })
+        .collect();
+    modify_file(&repo, "big.txt", &modified);
+    (dir, repo)
+}
+
+#[test]
+fn test_fingerprint_stable_and_content_sensitive() {
+    let (_dir, repo) = two_hunk_repo();
+    let a = get_unstaged_diff(&repo).unwrap();
+    let b = get_unstaged_diff(&repo).unwrap();
+    assert_eq!(session::fingerprint(&a), session::fingerprint(&b));
+
+    modify_file(&repo, "big.txt", "something else entirely\n");
+    let c = get_unstaged_diff(&repo).unwrap();
+    assert_ne!(session::fingerprint(&a), session::fingerprint(&c));
+}
+
+#[test]
+fn test_capture_and_apply_restores_statuses() {
+    let (_dir, repo) = two_hunk_repo();
+    let files = get_unstaged_diff(&repo).unwrap();
+
+    let mut app = App::new_with_help(files.clone(), true, false);
+    app.files[0].hunks[0].status = HunkStatus::Skipped;
+    app.selected_hunk = 1;
+    let saved = Session::capture(&app, session::fingerprint(&files));
+    assert_eq!(saved.hunks.len(), 1);
+
+    let mut fresh = App::new_with_help(files, true, false);
+    fresh.offer_resume(saved);
+    assert_eq!(fresh.mode, AppMode::ResumePrompt);
+    fresh.accept_resume();
+    assert_eq!(fresh.mode, AppMode::Browsing);
+    assert_eq!(fresh.files[0].hunks[0].status, HunkStatus::Skipped);
+    assert_eq!(fresh.files[0].hunks[1].status, HunkStatus::Pending);
+    assert_eq!(fresh.selected_hunk, 1);
+}
+
+#[test]
+fn test_decline_resume_keeps_fresh_state() {
+    let (_dir, repo) = two_hunk_repo();
+    let files = get_unstaged_diff(&repo).unwrap();
+
+    let mut app = App::new_with_help(files.clone(), true, false);
+    app.files[0].hunks[0].status = HunkStatus::Skipped;
+    let saved = Session::capture(&app, session::fingerprint(&files));
+
+    let mut fresh = App::new_with_help(files, true, false);
+    fresh.offer_resume(saved);
+    fresh.decline_resume();
+    assert_eq!(fresh.mode, AppMode::Browsing);
+    assert!(fresh.resume_offer.is_none());
+    assert_eq!(fresh.files[0].hunks[0].status, HunkStatus::Pending);
+}
+
+#[test]
+fn test_handoff_survives_staged_hunks() {
+    let (_dir, repo) = two_hunk_repo();
+    let files = get_unstaged_diff(&repo).unwrap();
+
+    // First session: stage hunk 1, leave hunk 2 pending with the cursor on it
+    let mut app = App::new_with_help(files, false, false);
+    stage_hunk(&repo, &app.files[0], &app.files[0].hunks[0], 0).unwrap();
+    app.files[0].hunks[0].status = HunkStatus::Staged;
+    app.selected_hunk = 1;
+    assert!(session::has_pending(&app.files));
+
+    let remaining = get_unstaged_diff(&repo).unwrap();
+    let path = session::handoff_path(&repo);
+    session::save(
+        &path,
+        &Session::capture(&app, session::fingerprint(&remaining)),
+    )
+    .unwrap();
+
+    // Next session sees only the second hunk; the handoff still matches
+    let next = get_unstaged_diff(&repo).unwrap();
+    assert_eq!(next[0].hunks.len(), 1);
+    let loaded = session::load_matching(&path, &session::fingerprint(&next));
+    assert!(loaded.is_some(), "handoff should match the remaining diff");
+
+    let mut app = App::new_with_help(next, false, false);
+    app.offer_resume(loaded.unwrap());
+    app.accept_resume();
+    assert_eq!(app.selected_hunk, 0, "selection clamped to remaining hunks");
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
+}
+
+#[test]
+fn test_load_matching_rejects_stale_session() {
+    let (_dir, repo) = two_hunk_repo();
+    let files = get_unstaged_diff(&repo).unwrap();
+    let app = App::new_with_help(files.clone(), true, false);
+    let path = session::handoff_path(&repo);
+    session::save(&path, &Session::capture(&app, session::fingerprint(&files))).unwrap();
+
+    assert!(session::load_matching(&path, "0000000000000000").is_none());
+    assert!(session::load_matching(&path, &session::fingerprint(&files)).is_some());
+
+    session::clear(&path);
+    assert!(session::load(&path).is_none());
+}
+
+#[test]
+fn test_session_restores_feedback() {
+    let (_dir, repo) = two_hunk_repo();
+    let files = get_unstaged_diff(&repo).unwrap();
+
+    let mut app = App::new_with_help(files.clone(), true, false);
+    app.files[0].hunks[0].status = HunkStatus::Skipped;
+    app.feedback.push(HunkFeedback {
+        file_path: "big.txt".to_string(),
+        hunk_header: app.files[0].hunks[0].header.clone(),
+        kind: FeedbackKind::Comment,
+        content: "why?".to_string(),
+        context_lines: app.files[0].hunks[0].lines.to_vec(),
+        comment_positions: vec![(1, "why?".to_string())],
+        suggestion: None,
+        severity: None,
+    });
+
+    // Round-trip through the session file
+    let path = session::session_path(&repo);
+    assert!(path.ends_with("stagent-session.json"));
+    session::save(&path, &Session::capture(&app, session::fingerprint(&files))).unwrap();
+    let loaded = session::load_matching(&path, &session::fingerprint(&files)).unwrap();
+
+    let mut fresh = App::new_with_help(files, true, false);
+    fresh.offer_resume(loaded.clone());
+    fresh.accept_resume();
+    assert_eq!(fresh.feedback.len(), 1);
+    assert_eq!(
+        fresh.feedback[0].comment_positions,
+        vec![(1, "why?".to_string())]
+    );
+    assert_eq!(fresh.files[0].hunks[0].status, HunkStatus::Skipped);
+
+    // Applying twice does not duplicate feedback
+    loaded.apply(&mut fresh);
+    assert_eq!(fresh.feedback.len(), 1);
+}
+
+#[test]
+fn test_session_without_feedback_field_loads() {
+    // Handoff files written before feedback was stored still parse
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("old.json");
+    std::fs::write(
+        &path,
+        r#"{"fingerprint":"abc","selected_path":null,"selected_hunk":0,"hunks":[]}"#,
+    )
+    .unwrap();
+    let loaded = session::load(&path).unwrap();
+    assert!(loaded.feedback.is_empty());
+}
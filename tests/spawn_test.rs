@@ -1,15 +1,50 @@
 //! Tests for the spawn module (--spawn flag functionality).
 
+use stagent::editor::SplitLayout;
+use stagent::multiplexer::MultiplexerKind;
 use stagent::spawn::{SpawnOptions, build_spawn_command};
+use stagent::types::{FeedbackFormat, OutputStyle, ReviewOrder, ViewMode};
 use std::path::PathBuf;
 
 fn default_opts() -> SpawnOptions {
     SpawnOptions {
         output: None,
-        files: None,
+        edits_patch: None,
+        files: Vec::new(),
+        exclude: Vec::new(),
         theme: "default".to_string(),
         context_lines: stagent::feedback::DEFAULT_CONTEXT_LINES,
+        format: FeedbackFormat::Diff,
         no_stage: false,
+        start_file: None,
+        start_hunk: None,
+        view: ViewMode::Unified,
+        show_whitespace: false,
+        tab_width: None,
+        ignore_whitespace: false,
+        ignore_blank_lines: false,
+        no_untracked: false,
+        no_ita: false,
+        diff_algorithm: None,
+        overview: false,
+        unified: None,
+        verdict: false,
+        track_time: false,
+        output_style: OutputStyle::Annotated,
+        notes: false,
+        resume: false,
+        watch: false,
+        order: ReviewOrder::Path,
+        auto_split: None,
+        summary_file: None,
+        status_file: None,
+        patch_file: None,
+        apply: false,
+        publish: None,
+        range: None,
+        multiplexer: MultiplexerKind::Auto,
+        split: SplitLayout::default(),
+        timeout: None,
     }
 }
 
@@ -54,10 +89,42 @@ fn test_spawn_command_format() {
 fn test_spawn_command_no_spawn_flag() {
     let opts = SpawnOptions {
         output: Some(PathBuf::from("/tmp/test.diff")),
-        files: Some("*.rs".to_string()),
+        edits_patch: None,
+        files: vec!["*.rs".to_string()],
+        exclude: Vec::new(),
         theme: "dark".to_string(),
         context_lines: 5,
+        format: FeedbackFormat::Diff,
         no_stage: true,
+        start_file: None,
+        start_hunk: None,
+        view: ViewMode::Unified,
+        show_whitespace: false,
+        tab_width: None,
+        ignore_whitespace: false,
+        ignore_blank_lines: false,
+        no_untracked: false,
+        no_ita: false,
+        diff_algorithm: None,
+        overview: false,
+        unified: None,
+        verdict: false,
+        track_time: false,
+        output_style: OutputStyle::Annotated,
+        notes: false,
+        resume: false,
+        watch: false,
+        order: ReviewOrder::Path,
+        auto_split: None,
+        summary_file: None,
+        status_file: None,
+        patch_file: None,
+        apply: false,
+        publish: None,
+        range: None,
+        multiplexer: MultiplexerKind::Auto,
+        split: SplitLayout::default(),
+        timeout: None,
     };
     let cmd = build_spawn_command(&opts);
 
@@ -88,7 +155,8 @@ fn test_spawn_command_forwards_output() {
 #[test]
 fn test_spawn_command_forwards_files_filter() {
     let opts = SpawnOptions {
-        files: Some("src/**/*.rs".to_string()),
+        files: vec!["src/**/*.rs".to_string(), "*.toml".to_string()],
+        exclude: vec!["**/generated/**".to_string()],
         ..default_opts()
     };
     let cmd = build_spawn_command(&opts);
@@ -100,6 +168,9 @@ fn test_spawn_command_forwards_files_filter() {
         "src/**/*.rs",
         "glob pattern should follow --files"
     );
+    assert_eq!(cmd.iter().filter(|s| *s == "--files").count(), 2);
+    let exclude_pos = cmd.iter().position(|s| s == "--exclude").unwrap();
+    assert_eq!(cmd[exclude_pos + 1], "**/generated/**");
 }
 
 #[test]
@@ -3,8 +3,9 @@ mod helpers;
 use git2::{DiffOptions, Repository};
 use stagent::diff::{parse_diff, split_hunk};
 use stagent::git::intent_to_add_untracked;
+use stagent::messages::MessageLevel;
 use stagent::staging::{reconstruct_blob, stage_hunk};
-use stagent::types::{DiffLine, FileDiff, Hunk, HunkStatus, LineKind};
+use stagent::types::{DeltaStatus, DiffLine, FileDiff, Hunk, HunkStatus, LineKind};
 
 /// Helper: get the staged (cached) diff for assertion checks.
 fn get_staged_diff(repo: &Repository) -> Vec<FileDiff> {
@@ -171,6 +172,123 @@ fn test_stage_hunk_new_file() {
     drop(dir);
 }
 
+#[test]
+fn test_intent_to_add_is_undone_for_files_not_staged() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "tracked.txt", "old\n");
+    helpers::create_untracked_file(&repo, "kept.txt", "staged content\n");
+    helpers::create_untracked_file(&repo, "left.txt", "never staged\n");
+
+    let added = intent_to_add_untracked(&repo).unwrap();
+    assert_eq!(added, vec!["kept.txt", "left.txt"]);
+
+    let files = get_unstaged_diff(&repo);
+    let kept = files
+        .iter()
+        .find(|f| f.path.to_str() == Some("kept.txt"))
+        .unwrap();
+    stage_hunk(&repo, kept, &kept.hunks[0], 0).unwrap();
+
+    let removed = stagent::git::remove_intent_to_add(&repo, &added).unwrap();
+    assert_eq!(removed, vec!["left.txt"]);
+
+    let index = repo.index().unwrap();
+    assert!(
+        index
+            .get_path(std::path::Path::new("kept.txt"), 0)
+            .is_some()
+    );
+    assert!(
+        index
+            .get_path(std::path::Path::new("left.txt"), 0)
+            .is_none()
+    );
+    assert!(
+        index
+            .get_path(std::path::Path::new("tracked.txt"), 0)
+            .is_some()
+    );
+    let status = repo.status_file(std::path::Path::new("left.txt")).unwrap();
+    assert!(status.contains(git2::Status::WT_NEW), "{:?}", status);
+
+    // Nothing more to undo
+    assert!(
+        stagent::git::remove_intent_to_add(&repo, &added)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_intent_to_add_is_undone_when_dropped() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "tracked.txt", "old\n");
+    helpers::create_untracked_file(&repo, "left.txt", "never staged\n");
+
+    {
+        let added = stagent::git::IntentToAdd::add_untracked(&repo).unwrap();
+        let index = repo.index().unwrap();
+        assert!(
+            index
+                .get_path(std::path::Path::new("left.txt"), 0)
+                .is_some()
+        );
+        // A review ending in an error drops the guard without finishing it
+        drop(added);
+    }
+
+    let index = repo.index().unwrap();
+    assert!(
+        index
+            .get_path(std::path::Path::new("left.txt"), 0)
+            .is_none()
+    );
+    let status = repo.status_file(std::path::Path::new("left.txt")).unwrap();
+    assert!(status.contains(git2::Status::WT_NEW), "{:?}", status);
+}
+
+#[test]
+fn test_untracked_files_without_intent_to_add() {
+    use stagent::git::{DiffSettings, get_unstaged_diff_with};
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "tracked.txt", "old\n");
+    std::fs::write(repo.workdir().unwrap().join("tracked.txt"), "new\n").unwrap();
+    helpers::create_untracked_file(&repo, "new.txt", "first\nsecond\n");
+
+    // --no-untracked: only the tracked change
+    let settings = DiffSettings {
+        exclude_untracked: true,
+        ..DiffSettings::default()
+    };
+    let files = get_unstaged_diff_with(&repo, &settings).unwrap();
+    let paths: Vec<_> = files.iter().map(|f| f.path.to_str().unwrap()).collect();
+    assert_eq!(paths, vec!["tracked.txt"]);
+
+    // --no-ita: shown as untracked, and staging adds the file
+    let files = get_unstaged_diff_with(&repo, &DiffSettings::default()).unwrap();
+    let new = files
+        .iter()
+        .find(|f| f.path.to_str() == Some("new.txt"))
+        .unwrap();
+    assert_eq!(new.status, DeltaStatus::Untracked);
+    assert!(
+        repo.index()
+            .unwrap()
+            .get_path(std::path::Path::new("new.txt"), 0)
+            .is_none(),
+        "showing the file leaves the index alone"
+    );
+
+    stage_hunk(&repo, new, &new.hunks[0], 0).unwrap();
+    let staged = get_staged_diff(&repo);
+    let staged_new = staged
+        .iter()
+        .find(|f| f.path.to_str() == Some("new.txt"))
+        .unwrap();
+    assert_eq!(staged_new.status, DeltaStatus::Added);
+}
+
 #[test]
 fn test_stage_new_file_via_intent_to_add_clears_ita_flag() {
     let (dir, repo) = helpers::create_temp_repo();
@@ -341,6 +459,85 @@ fn test_stage_hunk_deleted_lines() {
     drop(dir);
 }
 
+#[test]
+fn test_stage_deleted_file_removes_it_from_index() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "gone.txt", "a\nb\n");
+    helpers::delete_file(&repo, "gone.txt");
+
+    let files = get_unstaged_diff(&repo);
+    assert_eq!(files[0].status, DeltaStatus::Deleted);
+    stage_hunk(&repo, &files[0], &files[0].hunks[0], 0).unwrap();
+
+    // Removed, rather than left behind as an empty file
+    let index = repo.index().unwrap();
+    assert!(
+        index
+            .get_path(std::path::Path::new("gone.txt"), 0)
+            .is_none()
+    );
+    let staged = get_staged_diff(&repo);
+    assert_eq!(staged[0].status, DeltaStatus::Deleted);
+}
+
+#[test]
+fn test_stage_deleted_file_in_parts() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "gone.txt", "a\nb\nc\n");
+    helpers::delete_file(&repo, "gone.txt");
+
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), false, false);
+    app.toggle_line_mode();
+    app.stage_current_line(Some(&repo)).unwrap();
+    assert_eq!(index_content(&repo, "gone.txt"), "b\nc\n");
+
+    app.line_mode = false;
+    app.stage_current_hunk(&repo).unwrap();
+    let index = repo.index().unwrap();
+    assert!(
+        index
+            .get_path(std::path::Path::new("gone.txt"), 0)
+            .is_none()
+    );
+}
+
+#[test]
+fn test_staging_a_deletion_asks_first() {
+    use crossterm::event::KeyCode;
+    use stagent::app::App;
+    use stagent::types::AppMode;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "gone.txt", "a\nb\n");
+    helpers::delete_file(&repo, "gone.txt");
+
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), false, false);
+    assert!(app.stages_deletion());
+    app.confirm_deletion();
+    assert_eq!(app.mode, AppMode::DeletionPrompt);
+    app.handle_deletion_key(KeyCode::Esc, Some(&repo));
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
+    assert_eq!(app.message.as_deref(), Some("Deletion not staged"));
+
+    // Staging only some of its lines doesn't delete it
+    app.toggle_line_mode();
+    assert!(!app.stages_deletion());
+    app.line_mode = false;
+
+    app.confirm_deletion();
+    app.handle_deletion_key(KeyCode::Char('y'), Some(&repo));
+    assert_eq!(app.mode, AppMode::Browsing);
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Staged);
+    let index = repo.index().unwrap();
+    assert!(
+        index
+            .get_path(std::path::Path::new("gone.txt"), 0)
+            .is_none()
+    );
+}
+
 #[test]
 fn test_stage_hunk_added_lines() {
     let (dir, repo) = helpers::create_temp_repo();
@@ -454,6 +651,43 @@ fn test_stage_preserves_other_files() {
     drop(dir);
 }
 
+#[test]
+fn test_stage_hunk_non_utf8_file() {
+    let (dir, repo) = helpers::create_temp_repo();
+
+    // Latin-1: "café" and "naïve" aren't valid UTF-8
+    let mut original = b"caf\xe9\n".to_vec();
+    for i in 2..=20 {
+        original.extend_from_slice(format!("line{}\n", i).as_bytes());
+    }
+    original.extend_from_slice(b"na\xefve\n");
+    helpers::commit_bytes(&repo, "latin1.txt", &original);
+
+    let mut modified = original.clone();
+    modified.splice(0..0, b"\xe0 la carte\n".iter().copied());
+    modified.extend_from_slice(b"r\xe9sum\xe9\n");
+    helpers::modify_bytes(&repo, "latin1.txt", &modified);
+
+    let files = get_unstaged_diff(&repo);
+    assert_eq!(files[0].hunks.len(), 2);
+    let added = &files[0].hunks[0].lines[0];
+    assert_eq!(added.content, "\u{fffd} la carte\n", "displayed lossily");
+    assert_eq!(added.bytes(), b"\xe0 la carte\n");
+
+    stage_hunk(&repo, &files[0], &files[0].hunks[0], 0).unwrap();
+
+    let index = repo.index().unwrap();
+    let entry = index
+        .get_path(std::path::Path::new("latin1.txt"), 0)
+        .unwrap();
+    let blob = repo.find_blob(entry.id).unwrap();
+    let mut expected = b"\xe0 la carte\n".to_vec();
+    expected.extend_from_slice(&original);
+    assert_eq!(blob.content(), expected.as_slice());
+
+    drop(dir);
+}
+
 // ============================================================
 // Unit tests: reconstruct_blob
 // ============================================================
@@ -477,6 +711,8 @@ fn make_hunk(
                 content: content.to_string(),
                 old_lineno: None,
                 new_lineno: None,
+                raw: None,
+                no_newline: false,
             })
             .collect(),
         status: HunkStatus::Pending,
@@ -490,7 +726,7 @@ fn make_hunk(
 #[test]
 fn test_reconstruct_blob_content() {
     // Original: 5 lines, hunk modifies line 3
-    let original = "line1\nline2\nline3\nline4\nline5\n";
+    let original = b"line1\nline2\nline3\nline4\nline5\n";
 
     let hunk = make_hunk(
         2,
@@ -506,12 +742,12 @@ fn test_reconstruct_blob_content() {
     );
 
     let result = reconstruct_blob(original, &hunk, 0).unwrap();
-    assert_eq!(result, "line1\nline2\nLINE3_MODIFIED\nline4\nline5\n");
+    assert_eq!(result, b"line1\nline2\nLINE3_MODIFIED\nline4\nline5\n");
 }
 
 #[test]
 fn test_reconstruct_blob_hunk_at_start() {
-    let original = "first\nsecond\nthird\n";
+    let original = b"first\nsecond\nthird\n";
 
     let hunk = make_hunk(
         1,
@@ -526,12 +762,12 @@ fn test_reconstruct_blob_hunk_at_start() {
     );
 
     let result = reconstruct_blob(original, &hunk, 0).unwrap();
-    assert_eq!(result, "FIRST\nsecond\nthird\n");
+    assert_eq!(result, b"FIRST\nsecond\nthird\n");
 }
 
 #[test]
 fn test_reconstruct_blob_hunk_at_end() {
-    let original = "first\nsecond\nthird\n";
+    let original = b"first\nsecond\nthird\n";
 
     let hunk = make_hunk(
         2,
@@ -546,13 +782,13 @@ fn test_reconstruct_blob_hunk_at_end() {
     );
 
     let result = reconstruct_blob(original, &hunk, 0).unwrap();
-    assert_eq!(result, "first\nsecond\nTHIRD\n");
+    assert_eq!(result, b"first\nsecond\nTHIRD\n");
 }
 
 #[test]
 fn test_reconstruct_blob_add_lines() {
     // Adding a line between line2 and line3
-    let original = "line1\nline2\nline3\n";
+    let original = b"line1\nline2\nline3\n";
 
     let hunk = make_hunk(
         2,
@@ -567,12 +803,12 @@ fn test_reconstruct_blob_add_lines() {
     );
 
     let result = reconstruct_blob(original, &hunk, 0).unwrap();
-    assert_eq!(result, "line1\nline2\ninserted\nline3\n");
+    assert_eq!(result, b"line1\nline2\ninserted\nline3\n");
 }
 
 #[test]
 fn test_reconstruct_blob_remove_lines() {
-    let original = "line1\nline2\nline3\nline4\n";
+    let original = b"line1\nline2\nline3\nline4\n";
 
     let hunk = make_hunk(
         2,
@@ -587,13 +823,13 @@ fn test_reconstruct_blob_remove_lines() {
     );
 
     let result = reconstruct_blob(original, &hunk, 0).unwrap();
-    assert_eq!(result, "line1\nline2\nline4\n");
+    assert_eq!(result, b"line1\nline2\nline4\n");
 }
 
 #[test]
 fn test_reconstruct_blob_empty_original() {
     // New file: original is empty, hunk adds all lines
-    let original = "";
+    let original = b"";
 
     let hunk = make_hunk(
         0,
@@ -607,13 +843,13 @@ fn test_reconstruct_blob_empty_original() {
     );
 
     let result = reconstruct_blob(original, &hunk, 0).unwrap();
-    assert_eq!(result, "new_line1\nnew_line2\n");
+    assert_eq!(result, b"new_line1\nnew_line2\n");
 }
 
 #[test]
 fn test_reconstruct_blob_multiple_sequential() {
     // Stage hunk 0, then stage hunk 1 on the result
-    let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    let original = b"a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
 
     // First hunk: modify line 2 (b -> B)
     let hunk0 = make_hunk(
@@ -630,7 +866,7 @@ fn test_reconstruct_blob_multiple_sequential() {
     );
 
     let after_hunk0 = reconstruct_blob(original, &hunk0, 0).unwrap();
-    assert_eq!(after_hunk0, "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\n");
+    assert_eq!(after_hunk0, b"a\nB\nc\nd\ne\nf\ng\nh\ni\nj\n");
 
     // Second hunk: modify line 9 (i -> I) — operating on the *new* content
     let hunk1 = make_hunk(
@@ -647,7 +883,80 @@ fn test_reconstruct_blob_multiple_sequential() {
     );
 
     let after_hunk1 = reconstruct_blob(&after_hunk0, &hunk1, 0).unwrap();
-    assert_eq!(after_hunk1, "a\nB\nc\nd\ne\nf\ng\nh\nI\nj\n");
+    assert_eq!(after_hunk1, b"a\nB\nc\nd\ne\nf\ng\nh\nI\nj\n");
+}
+
+#[test]
+fn test_reconstruct_blob_preserves_line_endings() {
+    // Mixed endings: untouched lines keep theirs, hunk lines bring their own
+    let original = b"a\r\nb\r\nc\nd\r\n";
+    let hunk = make_hunk(
+        2,
+        2,
+        2,
+        2,
+        vec![
+            (LineKind::Removed, "b\r\n"),
+            (LineKind::Added, "B\r\n"),
+            (LineKind::Context, "c\n"),
+        ],
+    );
+    let result = reconstruct_blob(original, &hunk, 0).unwrap();
+    assert_eq!(result, b"a\r\nB\r\nc\nd\r\n");
+
+    // Lines without a terminator get the file's dominant one, unless the
+    // diff says the file ends without one
+    let original = b"a\r\nb\r\nc";
+    let mut hunk = make_hunk(
+        3,
+        1,
+        3,
+        2,
+        vec![(LineKind::Context, "c"), (LineKind::Added, "d")],
+    );
+    let result = reconstruct_blob(original, &hunk, 0).unwrap();
+    assert_eq!(result, b"a\r\nb\r\nc\r\nd\r\n");
+    hunk.lines[1].no_newline = true;
+    let result = reconstruct_blob(original, &hunk, 0).unwrap();
+    assert_eq!(result, b"a\r\nb\r\nc\r\nd");
+}
+
+#[test]
+fn test_stage_hunk_keeps_crlf() {
+    let (dir, repo) = helpers::create_temp_repo();
+
+    let original = (1..=20)
+        .map(|i| format!("line{}\r\n", i))
+        .collect::<String>();
+    helpers::commit_file(&repo, "crlf.txt", &original);
+    let modified = original
+        .replace("line2\r\n", "line2 CHANGED\r\n")
+        .replace("line19\r\n", "line19 CHANGED\r\n");
+    helpers::modify_file(&repo, "crlf.txt", &modified);
+
+    let files = get_unstaged_diff(&repo);
+    assert_eq!(files[0].hunks.len(), 2);
+    stage_hunk(&repo, &files[0], &files[0].hunks[0], 0).unwrap();
+
+    let index = repo.index().unwrap();
+    let entry = index.get_path(std::path::Path::new("crlf.txt"), 0).unwrap();
+    let blob = repo.find_blob(entry.id).unwrap();
+    assert_eq!(
+        std::str::from_utf8(blob.content()).unwrap(),
+        original.replace("line2\r\n", "line2 CHANGED\r\n")
+    );
+
+    // Only the second hunk is left, with no line-ending noise
+    let unstaged = get_unstaged_diff(&repo);
+    assert_eq!(unstaged[0].hunks.len(), 1);
+    let added = unstaged[0].hunks[0]
+        .lines
+        .iter()
+        .filter(|l| l.kind == LineKind::Added)
+        .count();
+    assert_eq!(added, 1);
+
+    drop(dir);
 }
 
 // ============================================================
@@ -842,7 +1151,7 @@ fn test_reconstruct_blob_with_offset() {
     // if the first hunk's net change is +N lines, the second hunk's
     // old_start is adjusted by that offset.
 
-    let original = "a\nb\nc\nd\ne\n";
+    let original = b"a\nb\nc\nd\ne\n";
 
     // Simple test: hunk that removes "b" and adds "B"
     // old_start=2, old_lines=2 means we start at line 2 and consume 2 lines
@@ -860,16 +1169,1137 @@ fn test_reconstruct_blob_with_offset() {
 
     let after_modify = reconstruct_blob(original, &hunk_modify_b, 0).unwrap();
     // Should replace b with B, keep everything else
-    assert_eq!(after_modify, "a\nB\nc\nd\ne\n");
+    assert_eq!(after_modify, b"a\nB\nc\nd\ne\n");
 
     // Test applying with offset: if we apply the same hunk but with offset=1
     // (as if a previous hunk added 1 line), old_start would become 3
-    let original_with_insert = "a\nINSERTED\nb\nc\nd\ne\n";
+    let original_with_insert = b"a\nINSERTED\nb\nc\nd\ne\n";
 
     // This hunk still targets the same content (remove b, add B)
     // but the offset tells us the hunk's old_start is shifted by +1
     let after_with_offset = reconstruct_blob(original_with_insert, &hunk_modify_b, 1).unwrap();
     // With offset=1, old_start=2 becomes 3, so we start at line 3 which is "b"
     // We should still get the modification
-    assert_eq!(after_with_offset, "a\nINSERTED\nB\nc\nd\ne\n");
+    assert_eq!(after_with_offset, b"a\nINSERTED\nB\nc\nd\ne\n");
+}
+
+// ============================================================
+// Integration tests: batch staging of marked hunks
+// ============================================================
+
+#[test]
+fn test_stage_marked_hunks_across_files_with_offsets() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    let original: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+    helpers::commit_file(&repo, "big.txt", &original);
+    helpers::commit_file(&repo, "other.txt", "a\nb\n");
+
+    // First hunk adds two lines, so the third hunk needs an offset of +2
+    let modified: String = (1..=30)
+        .map(|i| match i {
+            2 => "line 2\nextra a\nextra b\n".to_string(),
+            15 => "line 15 CHANGED\n".to_string(),
+            28 => "line 28 CHANGED\n".to_string(),
+            _ => format!("line {}\n", i),
+        })
+        .collect();
+    helpers::modify_file(&repo, "big.txt", &modified);
+    helpers::modify_file(&repo, "other.txt", "a\nB\n");
+
+    let files = get_unstaged_diff(&repo);
+    assert_eq!(files[0].hunks.len(), 3);
+    let mut app = App::new_with_help(files, false, false);
+
+    // Mark big.txt hunks 1 and 3, and the other.txt hunk; leave hunk 2
+    app.toggle_mark_current_hunk();
+    app.selected_hunk = 2;
+    app.toggle_mark_current_hunk();
+    app.selected_file = 1;
+    app.selected_hunk = 0;
+    app.toggle_mark_current_hunk();
+    assert_eq!(app.marked.len(), 3);
+
+    let staged = app.stage_marked_hunks(Some(&repo)).unwrap();
+    assert_eq!(staged, 3);
+    assert!(app.marked.is_empty());
+    assert_eq!(app.files[0].hunks[1].status, HunkStatus::Pending);
+
+    // Only the unmarked hunk remains unstaged
+    let remaining = get_unstaged_diff(&repo);
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].path.to_str().unwrap(), "big.txt");
+    assert_eq!(remaining[0].hunks.len(), 1);
+    assert!(
+        remaining[0].hunks[0]
+            .lines
+            .iter()
+            .any(|l| l.content.contains("line 15 CHANGED"))
+    );
+}
+
+// ============================================================
+// Binary files: staged whole
+// ============================================================
+
+#[test]
+fn test_stage_untracked_binary_file() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "readme.txt", "hi\n");
+    helpers::create_binary_file(&repo, "img/logo.bin");
+    intent_to_add_untracked(&repo).unwrap();
+
+    let files = stagent::git::get_unstaged_diff(&repo).unwrap();
+    assert_eq!(files.len(), 1);
+    assert!(files[0].is_binary);
+    assert_eq!(files[0].hunks[0].header, "Binary file (0 → 256 bytes)");
+
+    let mut app = App::new_with_help(files, false, false);
+    app.stage_current_hunk(&repo).unwrap();
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Staged);
+
+    assert!(stagent::git::get_unstaged_diff(&repo).unwrap().is_empty());
+    let index = repo.index().unwrap();
+    let entry = index
+        .get_path(std::path::Path::new("img/logo.bin"), 0)
+        .unwrap();
+    assert_eq!(repo.find_blob(entry.id).unwrap().size(), 256);
+}
+
+#[test]
+fn test_stage_binary_file_follows_exec_bit() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "readme.txt", "hi\n");
+    helpers::create_binary_file(&repo, "tool.bin");
+    let path = dir.path().join("tool.bin");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    intent_to_add_untracked(&repo).unwrap();
+
+    let files = stagent::git::get_unstaged_diff(&repo).unwrap();
+    stage_hunk(&repo, &files[0], &files[0].hunks[0], 0).unwrap();
+    let mode = |repo: &Repository| {
+        repo.index()
+            .unwrap()
+            .get_path(std::path::Path::new("tool.bin"), 0)
+            .unwrap()
+            .mode
+    };
+    assert_eq!(mode(&repo), 0o100755);
+
+    // chmod -x on the tracked executable, with new content
+    let mut content = std::fs::read(&path).unwrap();
+    content.push(0);
+    std::fs::write(&path, &content).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+    let files = stagent::git::get_unstaged_diff(&repo).unwrap();
+    assert!(files[0].is_binary);
+    stage_hunk(&repo, &files[0], &files[0].hunks[0], 0).unwrap();
+    assert_eq!(mode(&repo), 0o100644);
+    assert!(stagent::git::get_unstaged_diff(&repo).unwrap().is_empty());
+}
+
+#[test]
+fn test_binary_file_cannot_be_edited_or_split() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "readme.txt", "hi\n");
+    helpers::create_binary_file(&repo, "data.bin");
+    intent_to_add_untracked(&repo).unwrap();
+
+    let mut app = App::new_with_help(
+        stagent::git::get_unstaged_diff(&repo).unwrap(),
+        false,
+        false,
+    );
+    assert!(app.start_edit(Some(&repo)).is_err());
+    assert!(app.start_comment().is_err());
+    app.split_current_hunk();
+    assert_eq!(app.files[0].hunks.len(), 1);
+
+    app.skip_current_hunk();
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Skipped);
+}
+
+// ============================================================
+// Line staging
+// ============================================================
+
+/// Content of `path` in the index.
+fn index_content(repo: &Repository, path: &str) -> String {
+    let index = repo.index().unwrap();
+    let entry = index.get_path(std::path::Path::new(path), 0).unwrap();
+    String::from_utf8(repo.find_blob(entry.id).unwrap().content().to_vec()).unwrap()
+}
+
+#[test]
+fn test_stage_lines_one_at_a_time() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    let original: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+    helpers::commit_file(&repo, "f.txt", &original);
+    let modified: String = (1..=30)
+        .map(|i| match i {
+            5 => "line five\n".to_string(),
+            6 => "line 6\nnew\n".to_string(),
+            25 => "line 25 CHANGED\n".to_string(),
+            _ => format!("line {}\n", i),
+        })
+        .collect();
+    helpers::modify_file(&repo, "f.txt", &modified);
+
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), false, false);
+    assert_eq!(app.files[0].hunks.len(), 2);
+    app.toggle_line_mode();
+    assert_eq!(app.line_cursor(), Some(3));
+
+    // Stage only the removal of "line 5"
+    app.stage_current_line(Some(&repo)).unwrap();
+    assert_eq!(app.files[0].hunks.len(), 3);
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Staged);
+    assert!(!index_content(&repo, "f.txt").contains("line 5\n"));
+    assert!(!index_content(&repo, "f.txt").contains("line five"));
+
+    // The cursor moved on to "+line five" in the pending rest
+    assert_eq!(app.selected_hunk, 1);
+    let cursor = app.line_cursor().unwrap();
+    assert_eq!(
+        app.current_hunk().unwrap().lines[cursor].content,
+        "line five\n"
+    );
+    app.stage_current_line(Some(&repo)).unwrap();
+    assert!(index_content(&repo, "f.txt").contains("line 4\nline five\nline 6\nline 7\n"));
+
+    // "new" is now the only change left in the hunk: staged as a whole
+    let cursor = app.line_cursor().unwrap();
+    assert_eq!(app.current_hunk().unwrap().lines[cursor].content, "new\n");
+    app.stage_current_line(Some(&repo)).unwrap();
+    assert_eq!(app.files[0].hunks[2].status, HunkStatus::Staged);
+
+    // The later hunk still stages at the right place
+    assert_eq!(app.current_hunk().unwrap().status, HunkStatus::Pending);
+    app.stage_current_hunk(&repo).unwrap();
+    assert_eq!(index_content(&repo, "f.txt"), modified);
+    assert!(get_unstaged_diff(&repo).is_empty());
+}
+
+#[test]
+fn test_stage_line_rejects_context() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "f.txt", "a\nb\nc\n");
+    helpers::modify_file(&repo, "f.txt", "a\nB\nc\nd\n");
+
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), false, false);
+    app.toggle_line_mode();
+    app.cursor_line = 0;
+    app.stage_current_line(Some(&repo)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Not a changed line"));
+    assert_eq!(app.files[0].hunks.len(), 1);
+    assert_eq!(index_content(&repo, "f.txt"), "a\nb\nc\n");
+}
+
+// ============================================================
+// Working tree changes during review
+// ============================================================
+
+#[test]
+fn test_stage_refused_when_file_changed_since_diff() {
+    use stagent::app::App;
+    use stagent::staging::snapshot_worktree;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\ntwo\n");
+    helpers::commit_file(&repo, "b.txt", "alpha\n");
+    helpers::modify_file(&repo, "a.txt", "one\nTWO\n");
+    helpers::modify_file(&repo, "b.txt", "beta\n");
+
+    let files = get_unstaged_diff(&repo);
+    let mut app = App::new_with_help(files, false, false);
+    app.worktree_snapshot = snapshot_worktree(&repo, &app.files);
+
+    // An editor autosaves a.txt after the diff was shown
+    helpers::modify_file(&repo, "a.txt", "one\nTWO\nthree\n");
+    let err = app.stage_current_hunk(&repo).unwrap_err();
+    assert!(err.to_string().contains("a.txt changed on disk"), "{}", err);
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
+    assert!(get_staged_diff(&repo).is_empty(), "nothing was staged");
+
+    // Unchanged files still stage
+    app.selected_file = 1;
+    app.stage_current_hunk(&repo).unwrap();
+    assert_eq!(app.files[1].hunks[0].status, HunkStatus::Staged);
+    assert_eq!(get_staged_diff(&repo).len(), 1);
+}
+
+#[test]
+fn test_marked_staging_stops_at_changed_file() {
+    use stagent::app::App;
+    use stagent::staging::snapshot_worktree;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\n");
+    helpers::commit_file(&repo, "b.txt", "alpha\n");
+    helpers::modify_file(&repo, "a.txt", "ONE\n");
+    helpers::modify_file(&repo, "b.txt", "beta\n");
+
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), false, false);
+    app.worktree_snapshot = snapshot_worktree(&repo, &app.files);
+    app.toggle_mark_current_hunk();
+    app.toggle_mark_current_hunk();
+
+    helpers::modify_file(&repo, "b.txt", "gamma\n");
+    assert!(app.stage_marked_hunks(Some(&repo)).is_err());
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Staged);
+    assert_eq!(app.files[1].hunks[0].status, HunkStatus::Pending);
+    assert!(app.is_marked(1, 0), "unstaged hunk stays marked");
+}
+
+#[test]
+fn test_watch_refresh_keeps_review_state() {
+    use stagent::app::App;
+    use std::path::{Path, PathBuf};
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\n");
+    helpers::commit_file(&repo, "b.txt", "alpha\n");
+    helpers::commit_file(&repo, "c.txt", "red\n");
+    helpers::modify_file(&repo, "a.txt", "ONE\n");
+    helpers::modify_file(&repo, "b.txt", "beta\n");
+    helpers::modify_file(&repo, "c.txt", "green\n");
+
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), false, false);
+    app.snapshot_worktree(&repo);
+    app.skip_current_hunk();
+    app.selected_file = 1;
+    app.selected_hunk = 0;
+    app.stage_current_hunk(&repo).unwrap();
+    app.selected_file = 2;
+    app.selected_hunk = 0;
+
+    // Staging only touches the index
+    assert!(!app.refresh_if_changed(&repo).unwrap());
+
+    helpers::modify_file(&repo, "c.txt", "blue\n");
+    assert!(app.refresh_if_changed(&repo).unwrap());
+    assert_eq!(app.message.as_deref(), Some("Diff refreshed"));
+    let paths: Vec<_> = app.files.iter().map(|f| f.path.clone()).collect();
+    assert_eq!(paths, vec![PathBuf::from("a.txt"), PathBuf::from("c.txt")]);
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Skipped);
+    assert_eq!(app.files[1].hunks[0].status, HunkStatus::Pending);
+    assert_eq!(app.selected_file, 1, "selection follows c.txt");
+    assert!(!app.refresh_if_changed(&repo).unwrap());
+
+    // New untracked files show up too
+    helpers::create_untracked_file(&repo, "d.txt", "new\n");
+    assert!(app.refresh_if_changed(&repo).unwrap());
+    assert!(app.files.iter().any(|f| f.path == Path::new("d.txt")));
+}
+
+#[test]
+fn test_watch_ignores_files_outside_filter() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.rs", "one\n");
+    helpers::commit_file(&repo, "b.txt", "alpha\n");
+    helpers::modify_file(&repo, "a.rs", "ONE\n");
+    helpers::modify_file(&repo, "b.txt", "beta\n");
+
+    let filter =
+        stagent::filter::PathFilter::new(std::path::Path::new(""), &["*.rs".to_string()], &[])
+            .unwrap();
+    let mut files = get_unstaged_diff(&repo);
+    files.retain(|f| filter.matches(&f.path));
+    let mut app = App::new_with_help(files, false, false);
+    app.path_filter = filter;
+    app.snapshot_worktree(&repo);
+    assert!(!app.refresh_if_changed(&repo).unwrap());
+
+    // An edit outside the filter refreshes once but stays hidden
+    helpers::modify_file(&repo, "b.txt", "gamma\n");
+    assert!(app.refresh_if_changed(&repo).unwrap());
+    assert_eq!(app.files.len(), 1);
+    assert!(!app.refresh_if_changed(&repo).unwrap());
+}
+
+#[test]
+fn test_refresh_keeps_status_of_unchanged_hunks() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    let original: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+    helpers::commit_file(&repo, "a.txt", &original);
+    let edited = original
+        .replace("line 2\n", "line two\n")
+        .replace("line 18\n", "line eighteen\n");
+    helpers::modify_file(&repo, "a.txt", &edited);
+
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), false, false);
+    assert_eq!(app.files[0].hunks.len(), 2);
+    app.skip_current_hunk();
+    app.selected_hunk = 1;
+    app.skip_current_hunk();
+
+    // Rework the second change only
+    helpers::modify_file(
+        &repo,
+        "a.txt",
+        &edited.replace("line eighteen\n", "line 18 (eighteen)\n"),
+    );
+    app.refresh(&repo).unwrap();
+    let hunks = &app.files[0].hunks;
+    assert_eq!(hunks.len(), 2);
+    assert_eq!(hunks[0].status, HunkStatus::Skipped);
+    assert_eq!(hunks[1].status, HunkStatus::Pending);
+    assert!(
+        hunks[1]
+            .lines
+            .iter()
+            .any(|l| l.content.contains("(eighteen)"))
+    );
+}
+
+#[test]
+fn test_apply_patch_hunks_to_index() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "hello.txt", "one\ntwo\nthree\n");
+    helpers::modify_file(&repo, "hello.txt", "one\n2\nthree\n");
+
+    // As piped from `git diff` into `stagent -p --apply`
+    let patch = "diff --git a/hello.txt b/hello.txt\n\
+                 --- a/hello.txt\n\
+                 +++ b/hello.txt\n\
+                 @@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n";
+    let files = stagent::patch::parse_unified_diff(patch).unwrap();
+    stagent::staging::verify_patch_applies(&repo, &files).unwrap();
+    stage_hunk(&repo, &files[0], &files[0].hunks[0], 0).unwrap();
+    assert_eq!(get_staged_diff(&repo).len(), 1);
+    assert!(get_unstaged_diff(&repo).is_empty());
+
+    // Now the index has the change: the same patch no longer applies
+    let err = stagent::staging::verify_patch_applies(&repo, &files).unwrap_err();
+    let msg = err.to_string();
+    assert!(
+        msg.contains("hello.txt @@ -1,3 +1,3 @@ does not apply"),
+        "{}",
+        msg
+    );
+    assert!(msg.contains("line 2"), "{}", msg);
+}
+
+#[test]
+fn test_stage_hunk_refuses_when_index_changed() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\ntwo\nthree\n");
+    helpers::modify_file(&repo, "a.txt", "one\nTWO\nthree\n");
+    let files = get_unstaged_diff(&repo);
+
+    // Another process stages a different version of the file
+    helpers::modify_file(&repo, "a.txt", "one\n2\nthree\n");
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new("a.txt")).unwrap();
+    index.write().unwrap();
+    helpers::modify_file(&repo, "a.txt", "one\nTWO\nthree\n");
+
+    let err = stage_hunk(&repo, &files[0], &files[0].hunks[0], 0).unwrap_err();
+    let message = format!("{:#}", err);
+    assert!(message.contains("index no longer matches"), "{}", message);
+    assert!(message.contains("\"two\""), "{}", message);
+
+    // The index was left alone
+    let staged = get_staged_diff(&repo);
+    let added: Vec<_> = staged[0].hunks[0]
+        .lines
+        .iter()
+        .filter(|l| l.kind == LineKind::Added)
+        .map(|l| l.content.as_str())
+        .collect();
+    assert_eq!(added, vec!["2\n"]);
+}
+
+#[test]
+fn test_verify_old_lines() {
+    use stagent::staging::verify_old_lines;
+
+    let hunk = make_hunk(
+        2,
+        2,
+        2,
+        2,
+        vec![
+            (LineKind::Context, "b\n"),
+            (LineKind::Removed, "c\n"),
+            (LineKind::Added, "C\n"),
+        ],
+    );
+    assert!(verify_old_lines(b"a\nb\nc\n", &hunk, 0).is_ok());
+    assert!(verify_old_lines(b"x\na\nb\nc\n", &hunk, 1).is_ok());
+    assert!(verify_old_lines(b"a\r\nb\r\nc\r\n", &hunk, 0).is_ok());
+    assert!(verify_old_lines(b"a\nb\nz\n", &hunk, 0).is_err());
+    assert!(
+        verify_old_lines(b"a\nb\n", &hunk, 0).is_err(),
+        "past end of file"
+    );
+}
+
+#[test]
+fn test_stage_submodule_pointer() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    let sub = helpers::add_submodule(&repo, "vendor/lib");
+    let old = repo
+        .index()
+        .unwrap()
+        .get_path(std::path::Path::new("vendor/lib"), 0)
+        .unwrap()
+        .id;
+
+    // Uncommitted changes inside the submodule can't be staged here
+    std::fs::write(sub.workdir().unwrap().join("file.txt"), "dirty\n").unwrap();
+    assert!(stagent::git::get_unstaged_diff(&repo).unwrap().is_empty());
+
+    let new = helpers::commit_in_submodule(&sub, "two\n");
+    let files = stagent::git::get_unstaged_diff(&repo).unwrap();
+    assert_eq!(files.len(), 1);
+    let file = &files[0];
+    assert!(file.is_submodule);
+    assert_eq!(
+        file.hunks[0].header,
+        format!(
+            "Submodule commit {} → {}",
+            &old.to_string()[..7],
+            &new.to_string()[..7]
+        )
+    );
+    assert_eq!(
+        stagent::diff::submodule_commits(&file.hunks[0]),
+        (Some(old), Some(new))
+    );
+
+    stage_hunk(&repo, file, &file.hunks[0], 0).unwrap();
+    let entry = repo
+        .index()
+        .unwrap()
+        .get_path(std::path::Path::new("vendor/lib"), 0)
+        .unwrap();
+    assert_eq!(entry.id, new);
+    assert_eq!(entry.mode, 0o160000);
+    assert!(stagent::git::get_unstaged_diff(&repo).unwrap().is_empty());
+
+    // Staging the same change again finds the index already moved on
+    let err = stage_hunk(&repo, file, &file.hunks[0], 0).unwrap_err();
+    assert!(
+        err.to_string().contains("changed by another process"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_stage_mode_change_separately() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "run.sh", "echo one\n");
+    helpers::modify_file(&repo, "run.sh", "echo two\n");
+    let path = dir.path().join("run.sh");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let files = stagent::git::get_unstaged_diff(&repo).unwrap();
+    let file = &files[0];
+    assert_eq!(file.mode_change, Some((0o100644, 0o100755)));
+    assert_eq!(file.hunks.len(), 2);
+    assert_eq!(file.hunks[0].header, "Mode change 100644 → 100755");
+    assert_eq!(file.placeholder_noun(0), Some("mode change"));
+
+    // Only the mode is staged; the content change stays unstaged
+    stage_hunk(&repo, file, &file.hunks[0], 0).unwrap();
+    let entry = repo
+        .index()
+        .unwrap()
+        .get_path(std::path::Path::new("run.sh"), 0)
+        .unwrap();
+    assert_eq!(entry.mode, 0o100755);
+    let remaining = stagent::git::get_unstaged_diff(&repo).unwrap();
+    assert_eq!(remaining[0].mode_change, None);
+    assert_eq!(remaining[0].hunks.len(), 1);
+
+    // The content hunk stages without touching the mode
+    stage_hunk(&repo, &remaining[0], &remaining[0].hunks[0], 0).unwrap();
+    assert!(stagent::git::get_unstaged_diff(&repo).unwrap().is_empty());
+
+    // Staging the mode again finds the index already changed
+    let err = stage_hunk(&repo, file, &file.hunks[0], 0).unwrap_err();
+    assert!(err.to_string().contains("instead of 100644"), "{}", err);
+}
+
+#[test]
+fn test_stage_hunk_from_whitespace_ignoring_diff() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "ws.txt", "a\nb\nc\nd\n");
+    // Reindented context around a real change
+    helpers::modify_file(&repo, "ws.txt", "a\n  b\nC\n  d\n");
+
+    let settings = stagent::git::DiffSettings {
+        ignore_whitespace: true,
+        ..Default::default()
+    };
+    let files = stagent::git::get_unstaged_diff_with(&repo, &settings).unwrap();
+    assert_eq!(files[0].hunks.len(), 1);
+    stage_hunk(&repo, &files[0], &files[0].hunks[0], 0).unwrap();
+
+    // The change is staged, the whitespace-only edits are not
+    let index = repo.index().unwrap();
+    let entry = index.get_path(std::path::Path::new("ws.txt"), 0).unwrap();
+    let blob = repo.find_blob(entry.id).unwrap();
+    assert_eq!(blob.content(), b"a\nb\nC\nd\n");
+}
+
+#[test]
+fn test_stage_hunks_without_context() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "1\n2\n3\n4\n5\n6\n");
+    helpers::modify_file(&repo, "a.txt", "0\n1\n2\nthree\n4\n5\nadded\n6\n");
+
+    let settings = stagent::git::DiffSettings {
+        context_lines: Some(0),
+        ..Default::default()
+    };
+    let files = stagent::git::get_unstaged_diff_with(&repo, &settings).unwrap();
+    assert_eq!(files[0].hunks.len(), 3);
+    let mut offset = 0;
+    for hunk in &files[0].hunks {
+        stage_hunk(&repo, &files[0], hunk, offset).unwrap();
+        offset += hunk.new_lines as i32 - hunk.old_lines as i32;
+    }
+
+    let index = repo.index().unwrap();
+    let entry = index.get_path(std::path::Path::new("a.txt"), 0).unwrap();
+    let blob = repo.find_blob(entry.id).unwrap();
+    assert_eq!(blob.content(), b"0\n1\n2\nthree\n4\n5\nadded\n6\n");
+}
+
+#[test]
+fn test_auto_split_hunks_stage_in_any_order() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    let original: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+    helpers::commit_file(&repo, "f.txt", &original);
+    // Changes three unchanged lines apart: the middle insertion's split
+    // piece gets no context of its own
+    let modified: String = (1..=20)
+        .map(|i| match i {
+            4 => "line four\n".to_string(),
+            7 => "line 7\ninserted\n".to_string(),
+            11 => "line eleven\n".to_string(),
+            _ => format!("line {}\n", i),
+        })
+        .collect();
+    helpers::modify_file(&repo, "f.txt", &modified);
+
+    for order in [[0, 1, 2], [2, 1, 0], [1, 2, 0]] {
+        // Reset the index to the committed content
+        let mut index = repo.index().unwrap();
+        let entry = index.get_path(std::path::Path::new("f.txt"), 0).unwrap();
+        index.add_frombuffer(&entry, original.as_bytes()).unwrap();
+        index.write().unwrap();
+
+        let mut files = get_unstaged_diff(&repo);
+        assert_eq!(stagent::diff::auto_split(&mut files, 5), 1);
+        assert_eq!(files[0].hunks.len(), 3);
+        let mut app = App::new_with_help(files, false, false);
+        for hunk in order {
+            app.selected_hunk = hunk;
+            app.stage_current_hunk(&repo).unwrap();
+            assert_eq!(
+                app.files[0].hunks[hunk].status,
+                HunkStatus::Staged,
+                "order {:?}, hunk {}: {:?}",
+                order,
+                hunk,
+                app.message
+            );
+        }
+        assert_eq!(index_content(&repo, "f.txt"), modified, "order {:?}", order);
+    }
+}
+
+#[test]
+fn test_split_at_cursor_parts_stage_separately() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    let original: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+    helpers::commit_file(&repo, "f.txt", &original);
+    // One run of changes that split_hunk cannot divide
+    let modified = original.replace("line 4\nline 5\n", "four\n");
+    helpers::modify_file(&repo, "f.txt", &modified);
+
+    let files = get_unstaged_diff(&repo);
+    let mut app = App::new_with_help(files, false, false);
+    app.split_current_hunk();
+    assert_eq!(app.files[0].hunks.len(), 1);
+
+    // Split after "-line 4", then split "-line 5" from "+four"
+    app.toggle_line_mode();
+    app.cursor_line = app.line_cursor().unwrap() + 1;
+    app.split_current_hunk();
+    app.cursor_line = 1;
+    app.split_current_hunk();
+    assert_eq!(app.files[0].hunks.len(), 3);
+
+    // Stage the last part, then the first: the middle one stays unstaged
+    app.line_mode = false;
+    app.selected_hunk = 2;
+    app.stage_current_hunk(&repo).unwrap();
+    app.selected_hunk = 0;
+    app.stage_current_hunk(&repo).unwrap();
+    assert_eq!(
+        app.files[0]
+            .hunks
+            .iter()
+            .map(|h| h.status)
+            .collect::<Vec<_>>(),
+        [HunkStatus::Staged, HunkStatus::Pending, HunkStatus::Staged]
+    );
+    assert_eq!(
+        index_content(&repo, "f.txt"),
+        original.replace("line 4\nline 5\n", "line 5\nfour\n")
+    );
+
+    app.selected_hunk = 1;
+    app.stage_current_hunk(&repo).unwrap();
+    assert_eq!(index_content(&repo, "f.txt"), modified);
+}
+
+// ============================================================
+// Staging edited hunks
+// ============================================================
+
+/// Write `content` to a tempfile standing in for the closed editor.
+fn edited_tempfile(content: &str) -> tempfile::NamedTempFile {
+    use std::io::Write;
+    let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+    write!(tmpfile, "{}", content).unwrap();
+    tmpfile
+}
+
+#[test]
+fn test_stage_edited_hunk() {
+    use crossterm::event::KeyCode;
+    use stagent::app::App;
+    use stagent::types::AppMode;
+
+    let (dir, repo) = helpers::create_temp_repo();
+    let original: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+    helpers::commit_file(&repo, "f.txt", &original);
+    let modified = original
+        .replace("line 3\n", "line three\n")
+        .replace("line 17\n", "line seventeen\n");
+    helpers::modify_file(&repo, "f.txt", &modified);
+
+    let files = get_unstaged_diff(&repo);
+    assert_eq!(files[0].hunks.len(), 2);
+    let mut app = App::new_with_help(files, false, false);
+    app.snapshot_worktree(&repo);
+
+    // The edit adds a line: the second hunk moves down by one
+    let original_content = stagent::editor::extract_new_side_content(&app.files[0].hunks[0].lines);
+    let edited = original_content.replace("line three\n", "line 3, edited\nplus one\n");
+    let tmpfile = edited_tempfile(&edited);
+    assert!(app.capture_edit(Some(&repo), tmpfile.path(), &original_content));
+    assert_eq!(app.mode, AppMode::StageEditPrompt);
+    assert!(app.feedback.is_empty());
+
+    app.handle_stage_edit_key(KeyCode::Char('a'), Some(&repo));
+    assert_eq!(app.mode, AppMode::Browsing);
+    assert_eq!(app.message.as_deref(), Some("Edit applied and staged"));
+    assert!(app.feedback.is_empty());
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Staged);
+    let worktree = std::fs::read_to_string(dir.path().join("f.txt")).unwrap();
+    let expected = modified.replace("line three\n", "line 3, edited\nplus one\n");
+    assert_eq!(worktree, expected);
+    assert_eq!(
+        index_content(&repo, "f.txt"),
+        original.replace("line 3\n", "line 3, edited\nplus one\n")
+    );
+
+    app.selected_hunk = 1;
+    assert_eq!(app.files[0].hunks[1].new_start, 15);
+    app.stage_current_hunk(&repo).unwrap();
+    assert_eq!(app.files[0].hunks[1].status, HunkStatus::Staged);
+    assert_eq!(index_content(&repo, "f.txt"), expected);
+}
+
+#[test]
+fn test_stage_edited_hunk_of_non_utf8_file() {
+    use stagent::app::App;
+
+    let (dir, repo) = helpers::create_temp_repo();
+    // Latin-1 first line, outside the hunk
+    let rest: String = (2..=20).map(|i| format!("line {}\n", i)).collect();
+    let latin1 = |rest: &str| [b"caf\xe9\n", rest.as_bytes()].concat();
+    helpers::commit_bytes(&repo, "latin1.txt", &latin1(&rest));
+    let modified = rest.replace("line 17\n", "line seventeen\n");
+    helpers::modify_bytes(&repo, "latin1.txt", &latin1(&modified));
+
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), false, false);
+    app.snapshot_worktree(&repo);
+    let new_side = stagent::editor::extract_new_side_content(&app.files[0].hunks[0].lines);
+    let edited = new_side.replace("line seventeen\n", "line 17, edited\n");
+    app.stage_edit(&repo, &edited).unwrap();
+
+    let expected = latin1(&modified.replace("line seventeen\n", "line 17, edited\n"));
+    let worktree = std::fs::read(dir.path().join("latin1.txt")).unwrap();
+    assert_eq!(worktree, expected);
+    let index = repo.index().unwrap();
+    let entry = index
+        .get_path(std::path::Path::new("latin1.txt"), 0)
+        .unwrap();
+    assert_eq!(
+        repo.find_blob(entry.id).unwrap().content(),
+        expected.as_slice()
+    );
+}
+
+#[test]
+fn test_edit_kept_as_feedback() {
+    use crossterm::event::KeyCode;
+    use stagent::app::App;
+    use stagent::types::AppMode;
+
+    let (dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "f.txt", "a\nb\nc\n");
+    helpers::modify_file(&repo, "f.txt", "a\nB\nc\n");
+
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), false, false);
+    let tmpfile = edited_tempfile("a\nB2\nc\n");
+    assert!(app.capture_edit(Some(&repo), tmpfile.path(), "a\nB\nc\n"));
+    assert_eq!(app.mode, AppMode::StageEditPrompt);
+
+    app.handle_stage_edit_key(KeyCode::Char('f'), Some(&repo));
+    assert_eq!(app.feedback.len(), 1);
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Edited);
+    // Neither the working tree nor the index changed
+    let worktree = std::fs::read_to_string(dir.path().join("f.txt")).unwrap();
+    assert_eq!(worktree, "a\nB\nc\n");
+    assert_eq!(index_content(&repo, "f.txt"), "a\nb\nc\n");
+
+    // Without staging there is nothing to ask
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), true, false);
+    assert!(app.capture_edit(Some(&repo), tmpfile.path(), "a\nB\nc\n"));
+    assert_eq!(app.mode, AppMode::Browsing);
+    assert_eq!(app.feedback.len(), 1);
+}
+
+#[test]
+fn test_stage_selected_range_of_untracked_file() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "README", "readme\n");
+    let content: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+    helpers::create_untracked_file(&repo, "new.txt", &content);
+    intent_to_add_untracked(&repo).unwrap();
+
+    let files = get_unstaged_diff(&repo);
+    assert!(files[0].is_new_file(), "{:?}", files[0].status);
+    let mut app = App::new_with_help(files, false, false);
+
+    // Select lines 4-6 with V and stage only them
+    app.toggle_line_mode();
+    app.cursor_line = 3;
+    app.toggle_line_selection();
+    app.cursor_down();
+    app.cursor_down();
+    assert_eq!(app.selected_lines(), Some(3..=5));
+    app.stage_current_line(Some(&repo)).unwrap();
+    assert_eq!(index_content(&repo, "new.txt"), "line 4\nline 5\nline 6\n");
+
+    // The lines above and below them stage on top, in any order
+    let statuses: Vec<_> = app.files[0].hunks.iter().map(|h| h.status).collect();
+    assert_eq!(
+        statuses,
+        [HunkStatus::Pending, HunkStatus::Staged, HunkStatus::Pending]
+    );
+    app.line_mode = false;
+    app.selected_hunk = 2;
+    app.stage_current_hunk(&repo).unwrap();
+    assert_eq!(
+        index_content(&repo, "new.txt"),
+        (4..=10)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>()
+    );
+    app.selected_hunk = 0;
+    app.stage_current_hunk(&repo).unwrap();
+    assert_eq!(index_content(&repo, "new.txt"), content);
+}
+
+#[test]
+fn test_stage_top_lines_of_untracked_file() {
+    use stagent::app::App;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "README", "readme\n");
+    let content: String = (1..=6).map(|i| format!("line {}\n", i)).collect();
+    helpers::create_untracked_file(&repo, "new.txt", &content);
+    intent_to_add_untracked(&repo).unwrap();
+
+    let mut app = App::new_with_help(get_unstaged_diff(&repo), false, false);
+    app.toggle_line_mode();
+    app.stage_current_line(Some(&repo)).unwrap();
+    app.stage_current_line(Some(&repo)).unwrap();
+    assert_eq!(index_content(&repo, "new.txt"), "line 1\nline 2\n");
+
+    app.line_mode = false;
+    app.stage_current_hunk(&repo).unwrap();
+    assert_eq!(index_content(&repo, "new.txt"), content);
+}
+
+// ============================================================
+// ReviewSession
+// ============================================================
+
+#[test]
+fn test_review_session_stages_out_of_order() {
+    use stagent::review::ReviewSession;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    let original: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+    helpers::commit_file(&repo, "f.txt", &original);
+    let modified = original
+        .replace("line 2\n", "line 2\nadded a\nadded b\n")
+        .replace("line 25\n", "line 25 CHANGED\n");
+    helpers::modify_file(&repo, "f.txt", &modified);
+    helpers::create_untracked_file(&repo, "new.txt", "hello\n");
+
+    let mut review = ReviewSession::open(&repo, &Default::default()).unwrap();
+    assert_eq!(review.files().len(), 2);
+    assert_eq!(review.files()[0].hunks.len(), 2);
+
+    // The second hunk first, then the first: offsets stay right
+    review.stage(0, 1).unwrap();
+    review.stage(0, 0).unwrap();
+    assert_eq!(index_content(&repo, "f.txt"), modified);
+    assert!(review.stage(0, 0).is_err());
+
+    review.comment(1, 0, "Why is this here?").unwrap();
+    assert_eq!(review.status(1, 0), Some(HunkStatus::Commented));
+    assert_eq!(index_content(&repo, "new.txt"), "");
+
+    let (files, feedback) = review.into_parts().unwrap();
+    assert_eq!(stagent::stats::compute(&files).pending, 0);
+    assert_eq!(feedback.len(), 1);
+    assert_eq!(feedback[0].file_path, "new.txt");
+    // Only commented on, so untracked again
+    let index = repo.index().unwrap();
+    assert!(index.get_path(std::path::Path::new("new.txt"), 0).is_none());
+}
+
+#[test]
+fn test_review_session_refuses_stale_hunks() {
+    use stagent::review::ReviewSession;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "f.txt", "one\n");
+    helpers::modify_file(&repo, "f.txt", "two\n");
+
+    let mut review = ReviewSession::open(&repo, &Default::default()).unwrap();
+    helpers::modify_file(&repo, "f.txt", "three\n");
+    assert!(review.stage(0, 0).is_err());
+    assert_eq!(review.status(0, 0), Some(HunkStatus::Pending));
+    assert_eq!(index_content(&repo, "f.txt"), "one\n");
+}
+
+#[test]
+fn test_stage_failure_keeps_hunk_pending_and_explains() {
+    use crossterm::event::KeyCode;
+    use stagent::app::App;
+    use stagent::types::AppMode;
+
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "a.txt", "one\ntwo\n");
+    helpers::commit_file(&repo, "b.txt", "alpha\n");
+    helpers::modify_file(&repo, "a.txt", "one\nTWO\n");
+    helpers::modify_file(&repo, "b.txt", "beta\n");
+
+    let files = get_unstaged_diff(&repo);
+    let mut app = App::new_with_help(files, false, false);
+
+    // Another git process holds the index lock
+    let lock = repo.path().join("index.lock");
+    std::fs::write(&lock, "").unwrap();
+    app.selected_file = 1;
+    let err = app.stage_current_hunk(&repo).unwrap_err();
+    app.report_stage_error(&err);
+    assert_eq!(app.mode, AppMode::StageError);
+    assert_eq!(app.files[1].hunks[0].status, HunkStatus::Pending);
+    assert_eq!(app.message.as_ref().unwrap().level, MessageLevel::Error);
+
+    let (at, failure) = app.stage_failure.as_ref().unwrap();
+    assert_eq!(*at, (1, 0));
+    assert_eq!(failure.path.to_str(), Some("b.txt"));
+    assert!(
+        failure.hunk_header.starts_with("@@ -1"),
+        "{}",
+        failure.hunk_header
+    );
+    assert!(failure.causes.len() > 1, "{:?}", failure.causes);
+    assert!(
+        failure.remedies.iter().any(|r| r.contains("index.lock")),
+        "{:?}",
+        failure.remedies
+    );
+
+    // Enter goes back to the hunk, which stages once the lock is gone
+    app.selected_file = 0;
+    app.handle_stage_error_key(KeyCode::Enter);
+    assert_eq!(app.mode, AppMode::Browsing);
+    assert!(app.stage_failure.is_none());
+    assert_eq!(app.selected_file, 1);
+    std::fs::remove_file(&lock).unwrap();
+    app.stage_current_hunk(&repo).unwrap();
+    assert_eq!(app.files[1].hunks[0].status, HunkStatus::Staged);
+}
+
+#[test]
+fn test_stage_failure_remedies() {
+    use stagent::staging::remedies;
+
+    let remedy = |cause: &str| remedies(&[cause.to_string()]);
+    assert!(remedy("a.txt changed on disk since the diff was loaded")[0].contains("press r"));
+    assert!(remedy("cannot stage binary content")[0].contains("git add"));
+    assert!(remedy("something else").is_empty());
+}
+
+#[test]
+fn test_stage_waits_for_index_lock_to_be_released() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "f.txt", "one\n");
+    helpers::modify_file(&repo, "f.txt", "two\n");
+    let files = get_unstaged_diff(&repo);
+
+    // Another git process holds the lock for a moment
+    let lock = repo.path().join("index.lock");
+    std::fs::write(&lock, "").unwrap();
+    let releaser = {
+        let lock = lock.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(120));
+            std::fs::remove_file(lock).unwrap();
+        })
+    };
+    stage_hunk(&repo, &files[0], &files[0].hunks[0], 0).unwrap();
+    releaser.join().unwrap();
+    assert_eq!(get_staged_diff(&repo).len(), 1);
+
+    // A lock that stays is reported after the retries
+    std::fs::write(&lock, "").unwrap();
+    helpers::modify_file(&repo, "f.txt", "three\n");
+    let files = get_unstaged_diff(&repo);
+    let err = stage_hunk(&repo, &files[0], &files[0].hunks[0], 0).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("index.lock still exists"),
+        "{:#}",
+        err
+    );
+}
+
+// ============================================================
+// No newline at end of file
+// ============================================================
+
+/// Stage every hunk of `path` in the unstaged diff, then return the
+/// staged content and the hunks that were staged.
+fn stage_all_hunks(repo: &Repository, path: &str) -> (String, Vec<Hunk>) {
+    let files = get_unstaged_diff(repo);
+    let file = files
+        .iter()
+        .find(|f| f.path.to_str() == Some(path))
+        .unwrap();
+    for hunk in &file.hunks {
+        stage_hunk(repo, file, hunk, 0).unwrap();
+    }
+    (index_content(repo, path), file.hunks.clone())
+}
+
+#[test]
+fn test_stage_adding_trailing_newline() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "f.txt", "one\ntwo");
+    helpers::modify_file(&repo, "f.txt", "one\ntwo\n");
+
+    let (staged, hunks) = stage_all_hunks(&repo, "f.txt");
+    assert_eq!(staged, "one\ntwo\n");
+    let removed = hunks[0].lines.iter().find(|l| l.kind == LineKind::Removed);
+    assert!(removed.unwrap().no_newline, "the old side had no newline");
+}
+
+#[test]
+fn test_stage_removing_trailing_newline() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "f.txt", "one\ntwo\n");
+    helpers::modify_file(&repo, "f.txt", "one\ntwo");
+
+    let (staged, hunks) = stage_all_hunks(&repo, "f.txt");
+    assert_eq!(staged, "one\ntwo");
+    let added = hunks[0].lines.iter().find(|l| l.kind == LineKind::Added);
+    assert!(added.unwrap().no_newline, "the new side has no newline");
+    assert_eq!(added.unwrap().content, "two");
+}
+
+#[test]
+fn test_stage_change_before_missing_trailing_newline() {
+    let (_dir, repo) = helpers::create_temp_repo();
+    helpers::commit_file(&repo, "f.txt", "one\ntwo\nthree");
+    helpers::modify_file(&repo, "f.txt", "ONE\ntwo\nthree");
+    assert_eq!(stage_all_hunks(&repo, "f.txt").0, "ONE\ntwo\nthree");
+
+    // Lines appended after a last line without newline
+    helpers::modify_file(&repo, "f.txt", "ONE\ntwo\nthree\nfour");
+    assert_eq!(stage_all_hunks(&repo, "f.txt").0, "ONE\ntwo\nthree\nfour");
+}
+
+#[test]
+fn test_reconstruct_blob_from_patch_with_no_newline_markers() {
+    let diff = "\
+diff --git a/f b/f
+--- a/f
++++ b/f
+@@ -1,2 +1,2 @@
+ a
+-b
+\\ No newline at end of file
++b
+";
+    let files = stagent::patch::parse_unified_diff(diff).unwrap();
+    let hunk = &files[0].hunks[0];
+    assert!(hunk.lines[1].no_newline && !hunk.lines[2].no_newline);
+    assert_eq!(reconstruct_blob(b"a\nb", hunk, 0).unwrap(), b"a\nb\n");
+
+    let diff = "\
+diff --git a/f b/f
+--- a/f
++++ b/f
+@@ -1,2 +1,2 @@
+ a
+-b
++b
+\\ No newline at end of file
+";
+    let files = stagent::patch::parse_unified_diff(diff).unwrap();
+    assert_eq!(
+        reconstruct_blob(b"a\nb\n", &files[0].hunks[0], 0).unwrap(),
+        b"a\nb"
+    );
 }
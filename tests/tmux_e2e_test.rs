@@ -0,0 +1,110 @@
+//! End-to-end tests that run stagent inside an isolated tmux server.
+//! Skipped when tmux is not installed.
+
+mod helpers;
+
+use helpers::tmux::{TmuxServer, poll, stagent_bin, write_script};
+use helpers::*;
+use std::time::Duration;
+
+/// Repo with one modified file, ready for review.
+fn repo_with_change() -> (tempfile::TempDir, git2::Repository) {
+    let (dir, repo) = create_temp_repo();
+    commit_file(&repo, "hello.txt", "hello\nworld\n");
+    modify_file(&repo, "hello.txt", "hello\nthere\n");
+    (dir, repo)
+}
+
+fn staged_content(repo: &git2::Repository, path: &str) -> String {
+    let mut index = repo.index().unwrap();
+    // stagent wrote the index from another process
+    index.read(true).unwrap();
+    let entry = index.get_path(std::path::Path::new(path), 0).unwrap();
+    let blob = repo.find_blob(entry.id).unwrap();
+    String::from_utf8_lossy(blob.content()).to_string()
+}
+
+#[test]
+fn test_e2e_stage_hunk_and_quit() {
+    let (dir, repo) = repo_with_change();
+    let cmd = stagent_bin().display().to_string();
+    let Some(tmux) = TmuxServer::start(dir.path(), &cmd, &[]) else {
+        return;
+    };
+    let pane = tmux.first_pane();
+
+    tmux.wait_for(&pane, "hello.txt");
+    tmux.send_keys(&pane, &["y"]);
+    tmux.wait_for(&pane, "Hunk staged");
+    tmux.send_keys(&pane, &["q"]);
+    tmux.wait_for_exit(&pane);
+
+    assert_eq!(staged_content(&repo, "hello.txt"), "hello\nthere\n");
+}
+
+#[test]
+fn test_e2e_comment_in_editor_split() {
+    let (dir, _repo) = repo_with_change();
+    let scripts = tempfile::TempDir::new().unwrap();
+    // Fake editor: append a comment line and exit, closing the split
+    let editor = write_script(
+        scripts.path(),
+        "comment.sh",
+        "echo 'please explain' >> \"$1\"",
+    );
+    let output = dir.path().join("feedback.diff");
+    let cmd = format!(
+        "{} --no-stage --output {}",
+        stagent_bin().display(),
+        output.display()
+    );
+    let Some(tmux) = TmuxServer::start(dir.path(), &cmd, &[("VISUAL", editor.to_str().unwrap())])
+    else {
+        return;
+    };
+    let pane = tmux.first_pane();
+
+    tmux.wait_for(&pane, "hello.txt");
+    tmux.send_keys(&pane, &["c"]);
+    tmux.wait_for(&pane, "Comment captured");
+    tmux.send_keys(&pane, &["q"]);
+    // The hunk is still pending, so quitting asks first
+    tmux.wait_for(&pane, "Quit and write the feedback");
+    tmux.send_keys(&pane, &["y"]);
+    tmux.wait_for_exit(&pane);
+
+    let feedback = std::fs::read_to_string(&output).expect("feedback written");
+    assert!(
+        feedback.contains("# REVIEW COMMENT: please explain"),
+        "got: {}",
+        feedback
+    );
+}
+
+#[test]
+fn test_e2e_spawn_waits_for_child_pane() {
+    let (dir, _repo) = repo_with_change();
+    let done = dir.path().join("spawn-done");
+    let cmd = format!(
+        "{} --spawn --no-stage; touch {}",
+        stagent_bin().display(),
+        done.display()
+    );
+    let Some(tmux) = TmuxServer::start(dir.path(), &cmd, &[]) else {
+        return;
+    };
+
+    // --spawn opens the review in a second pane and blocks on it
+    let panes = tmux.wait_for_pane_count(2);
+    let child = &panes[1];
+    tmux.wait_for(child, "hello.txt");
+    assert!(!done.exists(), "parent must wait for the review");
+
+    tmux.send_keys(child, &["q"]);
+    tmux.wait_for(child, "Keep reviewing");
+    tmux.send_keys(child, &["y"]);
+    assert!(
+        poll(Duration::from_secs(10), || done.exists()),
+        "parent should return once the review pane closes"
+    );
+}
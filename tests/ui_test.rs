@@ -1,5 +1,10 @@
+mod helpers;
+
+use crossterm::event::KeyCode;
+use helpers::render::{render_to_buffer, render_to_string};
 use ratatui::Terminal;
 use ratatui::backend::TestBackend;
+use ratatui::buffer::Buffer;
 
 use stagent::app::App;
 use stagent::highlight::Highlighter;
@@ -23,26 +28,35 @@ fn make_test_files() -> Vec<FileDiff> {
                         content: "use std::io;\n".to_string(),
                         old_lineno: Some(1),
                         new_lineno: Some(1),
+                        raw: None,
+                        no_newline: false,
                     },
                     DiffLine {
                         kind: LineKind::Removed,
                         content: "let x = 1;\n".to_string(),
                         old_lineno: Some(2),
                         new_lineno: None,
+                        raw: None,
+                        no_newline: false,
                     },
                     DiffLine {
                         kind: LineKind::Added,
                         content: "let x = 42;\n".to_string(),
                         old_lineno: None,
                         new_lineno: Some(2),
+                        raw: None,
+                        no_newline: false,
                     },
                     DiffLine {
                         kind: LineKind::Context,
                         content: "println!(\"hello\");\n".to_string(),
                         old_lineno: Some(3),
                         new_lineno: Some(3),
+                        raw: None,
+                        no_newline: false,
                     },
-                ],
+                ]
+                .into(),
                 status: HunkStatus::Pending,
                 old_start: 1,
                 old_lines: 3,
@@ -51,6 +65,8 @@ fn make_test_files() -> Vec<FileDiff> {
             }],
             status: DeltaStatus::Modified,
             is_binary: false,
+            is_submodule: false,
+            mode_change: None,
         },
         FileDiff {
             path: "src/lib.rs".into(),
@@ -62,14 +78,19 @@ fn make_test_files() -> Vec<FileDiff> {
                         content: "old_fn()\n".to_string(),
                         old_lineno: Some(6),
                         new_lineno: None,
+                        raw: None,
+                        no_newline: false,
                     },
                     DiffLine {
                         kind: LineKind::Added,
                         content: "new_fn()\n".to_string(),
                         old_lineno: None,
                         new_lineno: Some(6),
+                        raw: None,
+                        no_newline: false,
                     },
-                ],
+                ]
+                .into(),
                 status: HunkStatus::Pending,
                 old_start: 5,
                 old_lines: 3,
@@ -78,34 +99,12 @@ fn make_test_files() -> Vec<FileDiff> {
             }],
             status: DeltaStatus::Modified,
             is_binary: false,
+            is_submodule: false,
+            mode_change: None,
         },
     ]
 }
 
-/// Helper: render the UI into a TestBackend buffer and return the buffer content as a string.
-fn render_to_string(width: u16, height: u16, app: &mut App) -> String {
-    let backend = TestBackend::new(width, height);
-    let mut terminal = Terminal::new(backend).unwrap();
-    let highlighter = Highlighter::new();
-
-    terminal
-        .draw(|frame| {
-            ui::render(frame, app, &highlighter);
-        })
-        .unwrap();
-
-    let buffer = terminal.backend().buffer().clone();
-    let mut output = String::new();
-    for y in 0..buffer.area.height {
-        for x in 0..buffer.area.width {
-            let cell = &buffer[(x, y)];
-            output.push_str(cell.symbol());
-        }
-        output.push('\n');
-    }
-    output
-}
-
 #[test]
 fn test_file_list_render() {
     let mut app = App::new(make_test_files(), false);
@@ -125,6 +124,153 @@ fn test_file_list_render() {
     );
 }
 
+#[test]
+fn test_file_list_shows_line_counts_and_progress() {
+    let mut app = App::new(make_test_files(), false);
+    set_browsing(&mut app);
+    let output = render_to_string(160, 24, &mut app);
+    assert!(output.contains("src/main.rs +1 -1 0/1"), "{}", output);
+
+    app.files[0].hunks[0].status = HunkStatus::Skipped;
+    let output = render_to_string(160, 24, &mut app);
+    assert!(output.contains("src/main.rs +1 -1 1/1"), "{}", output);
+    assert!(output.contains("src/lib.rs +1 -1 0/1"), "{}", output);
+}
+
+#[test]
+fn test_file_list_scrolls_to_the_selected_file() {
+    let template = make_test_files().remove(0);
+    let files: Vec<FileDiff> = (0..40)
+        .map(|i| FileDiff {
+            path: format!("f{:02}.rs", i).into(),
+            ..template.clone()
+        })
+        .collect();
+    let mut app = App::new(files, false);
+    set_browsing(&mut app);
+
+    let output = render_to_string(100, 12, &mut app);
+    assert!(output.contains("f00.rs"), "{}", output);
+    assert!(output.contains("1/40"), "{}", output);
+
+    app.selected_file = 30;
+    let output = render_to_string(100, 12, &mut app);
+    assert!(output.contains("f30.rs"), "{}", output);
+    assert!(!output.contains("f00.rs"), "{}", output);
+    assert!(output.contains("31/40"), "{}", output);
+}
+
+#[test]
+fn test_blame_panel_render() {
+    let mut app = App::new(make_test_files(), false);
+    set_browsing(&mut app);
+    app.show_blame = true;
+    app.blame.insert(
+        "src/main.rs".into(),
+        Err("Failed to blame src/main.rs".to_string()),
+    );
+    let output = render_to_string(100, 24, &mut app);
+    assert!(output.contains("Blame (b)"), "{}", output);
+    assert!(output.contains("Failed to blame src/main.rs"), "{}", output);
+
+    app.show_blame = false;
+    let output = render_to_string(100, 24, &mut app);
+    assert!(!output.contains("Blame (b)"), "{}", output);
+}
+
+#[test]
+fn test_hunk_details_render() {
+    let mut app = App::new(make_test_files(), false);
+    app.mode = AppMode::HunkDetails;
+    let output = render_to_string(100, 24, &mut app);
+    assert!(output.contains("Hunk details"), "{}", output);
+    assert!(output.contains("only adds code"), "{}", output);
+
+    app.blame.insert(
+        "src/main.rs".into(),
+        Err("Failed to blame src/main.rs".to_string()),
+    );
+    let output = render_to_string(100, 24, &mut app);
+    assert!(output.contains("Failed to blame src/main.rs"), "{}", output);
+    assert!(output.contains("D/Esc: close"), "{}", output);
+}
+
+#[test]
+fn test_new_file_renders_as_preview() {
+    let lines = ["fn main() {\n", "}\n"];
+    let file = FileDiff {
+        path: "src/new.rs".into(),
+        hunks: vec![Hunk {
+            header: "@@ -0,0 +1,2 @@".to_string(),
+            lines: lines
+                .iter()
+                .enumerate()
+                .map(|(i, content)| DiffLine {
+                    kind: LineKind::Added,
+                    content: content.to_string(),
+                    old_lineno: None,
+                    new_lineno: Some(i as u32 + 1),
+                    raw: None,
+                    no_newline: false,
+                })
+                .collect(),
+            status: HunkStatus::Pending,
+            old_start: 0,
+            old_lines: 0,
+            new_start: 1,
+            new_lines: 2,
+        }],
+        status: DeltaStatus::Untracked,
+        is_binary: false,
+        is_submodule: false,
+        mode_change: None,
+    };
+    let mut app = App::new(vec![file], false);
+    set_browsing(&mut app);
+    let output = render_to_string(120, 24, &mut app);
+    assert!(
+        output.contains("src/new.rs · new file, 2 lines"),
+        "{}",
+        output
+    );
+    assert!(output.contains("   1 │ fn main() {"), "{}", output);
+    assert!(!output.contains("+fn main"), "{}", output);
+}
+
+#[test]
+fn test_binary_file_render() {
+    let files = vec![FileDiff {
+        path: "logo.png".into(),
+        hunks: vec![stagent::diff::binary_hunk(Some((120, 4096)))],
+        status: DeltaStatus::Modified,
+        is_binary: true,
+        is_submodule: false,
+        mode_change: None,
+    }];
+    let mut app = App::new(files, false);
+    set_browsing(&mut app);
+    let output = render_to_string(100, 24, &mut app);
+
+    assert!(output.contains("logo.png [bin]"), "{}", output);
+    assert!(
+        output.contains("Binary file (120 → 4096 bytes)"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_inline_comment_box_render() {
+    let mut app = App::new(make_test_files(), false);
+    set_browsing(&mut app);
+    app.start_inline_comment();
+    app.comment_input.push_str("needs a test");
+    let output = render_to_string(120, 30, &mut app);
+
+    assert!(output.contains("Comment after line"), "{}", output);
+    assert!(output.contains("needs a test█"), "{}", output);
+}
+
 #[test]
 fn test_diff_view_render() {
     let mut app = App::new(make_test_files(), false);
@@ -151,6 +297,171 @@ fn test_diff_view_render() {
     );
 }
 
+#[test]
+fn test_diff_view_render_scrolled_long_file() {
+    let lines = (1..=20_000)
+        .map(|n| DiffLine {
+            kind: LineKind::Added,
+            content: format!("generated line {}\n", n),
+            old_lineno: None,
+            new_lineno: Some(n),
+            raw: None,
+            no_newline: false,
+        })
+        .collect();
+    let files = vec![FileDiff {
+        path: "gen.txt".into(),
+        hunks: vec![Hunk {
+            header: "@@ -0,0 +1,20000 @@".to_string(),
+            lines,
+            status: HunkStatus::Pending,
+            old_start: 0,
+            old_lines: 0,
+            new_start: 1,
+            new_lines: 20_000,
+        }],
+        status: DeltaStatus::Added,
+        is_binary: false,
+        is_submodule: false,
+        mode_change: None,
+    }];
+    let mut app = App::new(files, false);
+    set_browsing(&mut app);
+    // Row 0 is the hunk header, so row 5000 is line 5000
+    app.scroll_offset = 5_000;
+    let output = render_to_string(100, 30, &mut app);
+
+    let first_row = output.lines().nth(1).unwrap();
+    assert!(first_row.contains("generated line 5000"), "{}", output);
+    assert!(!output.contains("@@ -0,0"), "{}", output);
+
+    // Only what was on screen has been highlighted
+    let cache = app.highlight_cache.get(0).unwrap();
+    let highlighted = cache.hunks[0].len();
+    assert!((5_000..20_000).contains(&highlighted), "{}", highlighted);
+
+    // Scrolling past the end is clamped to the last page, and the
+    // scrollbar thumb sits at the bottom of the right border
+    app.scroll_offset = 1_000_000;
+    let buffer = render_to_buffer(100, 30, &mut app);
+    // 20001 rows, 27 of them visible
+    assert_eq!(app.scroll_offset, 20_001 - 27);
+    let (_, y) = find_text(&buffer, "generated line 20000");
+    assert_eq!(y, 27);
+    assert_eq!(buffer[(99, 27)].symbol(), "█");
+    assert_ne!(buffer[(99, 1)].symbol(), "█");
+}
+
+#[test]
+fn test_large_files_off_screen_stay_packed() {
+    let file = |path: &str| FileDiff {
+        path: path.into(),
+        hunks: vec![Hunk {
+            header: "@@ -0,0 +1,6000 @@".to_string(),
+            lines: (1..=6_000)
+                .map(|n| DiffLine {
+                    kind: LineKind::Added,
+                    content: format!("generated line {}\n", n),
+                    old_lineno: None,
+                    new_lineno: Some(n),
+                    raw: None,
+                    no_newline: false,
+                })
+                .collect(),
+            status: HunkStatus::Pending,
+            old_start: 0,
+            old_lines: 0,
+            new_start: 1,
+            new_lines: 6_000,
+        }],
+        status: DeltaStatus::Added,
+        is_binary: false,
+        is_submodule: false,
+        mode_change: None,
+    };
+    let mut app = App::new(vec![file("a.txt"), file("b.txt")], false);
+    set_browsing(&mut app);
+
+    // The file list's counts and the status bar read them packed
+    let output = render_to_string(100, 30, &mut app);
+    assert!(output.contains("+6000"), "{}", output);
+    assert!(app.files[1].hunks[0].lines.is_packed());
+    render_to_string(100, 30, &mut app);
+    assert!(app.files[1].hunks[0].lines.is_packed());
+
+    // Shown, and the file viewed before is kept unpacked with its highlighting
+    app.select_next_file();
+    let output = render_to_string(100, 30, &mut app);
+    assert!(output.contains("generated line 1"), "{}", output);
+    assert!(!app.files[0].hunks[0].lines.is_packed());
+    assert!(!app.files[1].hunks[0].lines.is_packed());
+}
+
+/// Position of the first cell of `text` in `buffer`.
+fn find_text(buffer: &Buffer, text: &str) -> (u16, u16) {
+    for y in 0..buffer.area.height {
+        let row: Vec<&str> = (0..buffer.area.width)
+            .map(|x| buffer[(x, y)].symbol())
+            .collect();
+        if let Some(x) = (0..row.len()).find(|&x| row[x..].concat().starts_with(text)) {
+            return (x as u16, y);
+        }
+    }
+    panic!("{:?} not rendered", text);
+}
+
+#[test]
+fn test_light_palette_in_diff_view() {
+    use stagent::ui::theme::{self, ThemeColors, ThemeVariant};
+
+    theme::set_variant(ThemeVariant::Light);
+    let mut app = App::new(make_test_files(), false);
+    set_browsing(&mut app);
+    let buffer = render_to_buffer(100, 30, &mut app);
+    theme::set_variant(ThemeVariant::Dark);
+
+    let light = ThemeColors::light();
+    let added = &buffer[find_text(&buffer, "42;")];
+    assert_eq!(added.bg, light.added_bg);
+    // Syntax colors come from the light syntect theme, not the dark one
+    let dark_fg = Highlighter::new()
+        .highlight_line("src/main.rs", "let x = 42;", LineKind::Context)
+        .spans
+        .iter()
+        .find(|s| s.content.contains("42"))
+        .and_then(|s| s.style.fg);
+    assert_ne!(Some(added.fg), dark_fg);
+
+    let removed = &buffer[find_text(&buffer, "let x = 1;")];
+    assert_eq!(removed.fg, light.removed_dim_fg);
+    assert_eq!(removed.bg, light.removed_dim_bg);
+
+    let status_bar = &buffer[(0, 29)];
+    assert_eq!(status_bar.bg, light.status_bar_bg);
+}
+
+#[test]
+fn test_whitespace_marks() {
+    let mut files = make_test_files();
+    // Reindent the added line and leave trailing spaces on it
+    files[0].hunks[0].lines[1].content = "let x = 42;\n".to_string();
+    files[0].hunks[0].lines[2].content = "\tlet x = 42;  \n".to_string();
+    let mut app = App::new(files, false);
+    set_browsing(&mut app);
+
+    let buffer = render_to_buffer(140, 30, &mut app);
+    let output = render_to_string(140, 30, &mut app);
+    assert!(output.contains("[whitespace only]"), "{}", output);
+    assert!(output.contains("W:skip 1 whitespace-only"), "{}", output);
+    let (x, y) = find_text(&buffer, "    let x = 42;");
+    assert_eq!(buffer[(x + 15, y)].bg, stagent::ui::theme::removed_bg());
+    assert_ne!(buffer[(x + 14, y)].bg, stagent::ui::theme::removed_bg());
+
+    app.show_whitespace = true;
+    let output = render_to_string(100, 30, &mut app);
+    assert!(output.contains("→   let·x·=·42;··"), "{}", output);
+}
+
 #[test]
 fn test_status_bar_render() {
     let mut app = App::new(make_test_files(), false);
@@ -209,7 +520,7 @@ fn test_layout_proportions() {
     // The remaining 75% should be for diff view
     let diff_width = 100 - fl_area.width;
     assert!(
-        diff_width >= 70 && diff_width <= 80,
+        (70..=80).contains(&diff_width),
         "Diff view width {} should be ~75% of 100",
         diff_width
     );
@@ -236,6 +547,82 @@ fn test_help_overlay_render() {
     );
 }
 
+#[test]
+fn test_help_overlay_scrolls_to_the_end() {
+    let mut app = App::new_with_help(make_test_files(), false, true);
+    let output = render_to_string(100, 20, &mut app);
+    assert!(!output.contains("Search this help"));
+
+    app.help_scroll = 1000;
+    let output = render_to_string(100, 20, &mut app);
+    assert!(output.contains("Search this help"), "{}", output);
+    assert!(!output.contains("Keyboard Shortcuts"));
+    // Clamped so the last line sits at the bottom
+    assert!(app.help_scroll < 1000);
+    assert!(output.contains("any other key closes"));
+}
+
+#[test]
+fn test_help_overlay_search_filters_bindings() {
+    let mut app = App::new_with_help(make_test_files(), false, true);
+    app.help_query = "STAGE".to_string();
+    let output = render_to_string(100, 30, &mut app);
+    assert!(output.contains("Stage all marked hunks"), "{}", output);
+    assert!(output.contains("Help /STAGE"));
+    assert!(!output.contains("Skip hunk"));
+    assert!(!output.contains("Navigation"));
+
+    app.help_query = "nothing like this".to_string();
+    let output = render_to_string(100, 30, &mut app);
+    assert!(output.contains("No matching keys"));
+}
+
+#[test]
+fn test_help_overlay_grays_out_staging_in_no_stage_mode() {
+    let color_of = |no_stage, text| {
+        let mut app = App::new_with_help(make_test_files(), no_stage, true);
+        let buffer = render_to_buffer(100, 40, &mut app);
+        let (x, y) = find_text(&buffer, text);
+        buffer[(x, y)].fg
+    };
+    assert_eq!(color_of(false, "Stage hunk"), color_of(false, "Skip hunk"));
+    assert_ne!(color_of(true, "Stage hunk"), color_of(true, "Skip hunk"));
+}
+
+#[test]
+fn test_overview_render() {
+    let mut app = App::new_with_help(make_test_files(), false, false);
+    app.start_overview();
+    app.handle_overview_key(KeyCode::Char(' '));
+    let output = render_to_string(100, 30, &mut app);
+    let total = app.files.len();
+    assert!(
+        output.contains(&format!("Overview: {} of {} files", total - 1, total)),
+        "{}",
+        output
+    );
+    assert!(output.contains("[ ] src/main.rs"), "{}", output);
+    assert!(output.contains("Enter: start review"));
+    // No diff view yet
+    assert!(!output.contains("@@"));
+}
+
+#[test]
+fn test_quit_dialog_render() {
+    let mut app = App::new_with_help(make_test_files(), false, false);
+    assert!(!app.request_quit());
+    let output = render_to_string(100, 30, &mut app);
+    assert!(output.contains("Quit?"), "{}", output);
+    let pending = format!(
+        "{} pending hunk(s), 0 feedback item(s)",
+        app.pending_hunk_count()
+    );
+    assert!(output.contains(&pending), "{}", output);
+    assert!(output.contains("Keep reviewing"));
+    // Nothing to discard
+    assert!(!output.contains("discard"));
+}
+
 #[test]
 fn test_help_overlay_dismissed_shows_normal_ui() {
     let mut app = App::new_with_help(make_test_files(), false, true);
@@ -251,7 +638,7 @@ fn test_help_overlay_dismissed_shows_normal_ui() {
     );
     // Help overlay text should not be present
     assert!(
-        !output.contains("Press any key to start"),
+        !output.contains("any other key closes"),
         "Help overlay should not be visible after dismissing:\n{}",
         output
     );
@@ -269,3 +656,197 @@ fn test_status_bar_shows_help_hint() {
         output
     );
 }
+
+// --- Side-by-side view tests ---
+
+#[test]
+fn test_side_by_side_render() {
+    let mut app = App::new(make_test_files(), false);
+    set_browsing(&mut app);
+    app.view_mode = ViewMode::SideBySide;
+    let output = render_to_string(140, 30, &mut app);
+
+    // Removed and added lines share a row, separated by the divider
+    let row = output
+        .lines()
+        .find(|l| l.contains("let x = 1"))
+        .unwrap_or_else(|| panic!("Expected removed line in output:\n{}", output));
+    let rest = &row[row.find("let x = 1").unwrap()..];
+    let divider = rest.find('│').expect("row should contain the divider");
+    let new_pos = rest.find("let x = 42").expect("added line on same row");
+    assert!(divider < new_pos);
+
+    // Context lines appear on both sides
+    let ctx = output.lines().find(|l| l.contains("use std::io")).unwrap();
+    assert_eq!(ctx.matches("use std::io").count(), 2);
+}
+
+#[test]
+fn test_side_by_side_rows_pairing() {
+    let files = make_test_files();
+    let rows = stagent::ui::diff_view::side_by_side_rows(&files[0].hunks[0]);
+    assert_eq!(
+        rows,
+        vec![(Some(0), Some(0)), (Some(1), Some(2)), (Some(3), Some(3)),]
+    );
+}
+
+#[test]
+fn test_stats_in_status_bar_and_panel() {
+    let mut app = App::new(make_test_files(), false);
+    set_browsing(&mut app);
+    app.skip_current_hunk();
+    app.message = None;
+
+    let output = render_to_string(120, 30, &mut app);
+    assert!(output.contains("[1/2 50%  +2 -2]"), "{}", output);
+    assert!(!output.contains("Statistics"));
+
+    app.toggle_stats();
+    let output = render_to_string(120, 30, &mut app);
+    assert!(output.contains("Statistics (i)"), "{}", output);
+    assert!(output.contains("Skipped     1"), "{}", output);
+    assert!(output.contains("Lines       +2 -2"), "{}", output);
+}
+
+#[test]
+fn test_feedback_panel_lists_and_deletes_feedback() {
+    let mut app = App::new(make_test_files(), false);
+    set_browsing(&mut app);
+    app.open_feedback_panel();
+    assert_eq!(app.mode, AppMode::Browsing, "nothing to list yet");
+
+    app.add_line_comment(1, "!nit why 42?");
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Commented);
+    app.open_feedback_panel();
+    assert_eq!(app.mode, AppMode::FeedbackPanel);
+    let output = render_to_string(120, 30, &mut app);
+    assert!(output.contains("Feedback (1)"), "{}", output);
+    assert!(output.contains("[nit] why 42?"), "{}", output);
+
+    app.handle_feedback_panel_key(KeyCode::Char('d'));
+    assert!(app.feedback.is_empty());
+    assert_eq!(app.mode, AppMode::Browsing);
+    assert_eq!(app.files[0].hunks[0].status, HunkStatus::Pending);
+}
+
+#[test]
+fn test_long_lines_scroll_sideways_or_wrap() {
+    let mut files = make_test_files();
+    files[0].hunks[0].lines[2].content = format!("let x = \"{}END\";\n", "x".repeat(150));
+    let mut app = App::new(files, false);
+    set_browsing(&mut app);
+
+    let output = render_to_string(100, 30, &mut app);
+    assert!(!output.contains("END"), "cut off at the edge:\n{}", output);
+
+    // Scrolling sideways moves the content but not the gutter
+    app.scroll_horizontally(140);
+    let output = render_to_string(100, 30, &mut app);
+    let row = output.lines().find(|l| l.contains("END")).unwrap();
+    assert!(row.contains("   2 +xxx"), "{}", row);
+    assert!(output.contains("from column 141"), "{}", output);
+
+    // Wrapping shows the whole line, continued under the content
+    app.toggle_wrap();
+    assert_eq!(app.h_scroll, 0);
+    let buffer = render_to_buffer(100, 30, &mut app);
+    let output = render_to_string(100, 30, &mut app);
+    let rows: Vec<&str> = output.lines().collect();
+    let first = rows
+        .iter()
+        .position(|l| l.contains("+let x = \"xxx"))
+        .unwrap();
+    let (x, end) = find_text(&buffer, "END");
+    assert!(end as usize > first, "{}", output);
+    let (content_x, _) = find_text(&buffer, "let x = \"xxx");
+    assert!(x >= content_x, "wrapped rows start at the content column");
+    let gutter: String = rows[first + 1].chars().take(content_x as usize).collect();
+    assert!(!gutter.contains('2'), "{}", gutter);
+    assert_eq!(
+        app.total_content_lines() as usize,
+        5 + (end as usize - first),
+        "rows are counted with the wrapped ones"
+    );
+
+    // A click below the wrapped line lands on the line after it
+    let (px, py) = find_text(&buffer, "println!");
+    app.handle_mouse_click(px, py);
+    assert_eq!(app.cursor_line, 3);
+
+    // Side by side wraps each half
+    app.toggle_view_mode();
+    let output = render_to_string(100, 30, &mut app);
+    assert!(output.contains("END"), "{}", output);
+    assert!(output.contains("println!"), "{}", output);
+}
+
+#[test]
+fn test_wide_characters_keep_columns_aligned() {
+    let mut files = make_test_files();
+    files[0].hunks[0].lines[1].content = "let 名前 = \"😀\";\n".to_string();
+    files[0].hunks[0].lines[2].content = "let name = \"ok\";\n".to_string();
+    let mut app = App::new(files, false);
+    set_browsing(&mut app);
+    app.view_mode = ViewMode::SideBySide;
+
+    // The divider between the halves is in the same column on every row
+    let buffer = render_to_buffer(100, 30, &mut app);
+    let (_, wide_row) = find_text(&buffer, "let 名");
+    let (_, plain_row) = find_text(&buffer, "use std::io;");
+    let divider = |y: u16| {
+        (buffer.area.width / 3..buffer.area.width - 1)
+            .find(|&x| buffer[(x, y)].symbol() == "│")
+            .unwrap()
+    };
+    assert_eq!(divider(wide_row), divider(plain_row));
+
+    // Cut off at the edge of a narrow half, a wide character is left out
+    // rather than pushing the divider over
+    files_with_long_wide_line(&mut app);
+    let buffer = render_to_buffer(60, 30, &mut app);
+    let (_, wide_row) = find_text(&buffer, "let 名");
+    assert_eq!(divider(wide_row), divider(plain_row));
+
+    // Wrapped, wide lines take the rows their width needs
+    app.view_mode = ViewMode::Unified;
+    app.toggle_wrap();
+    let buffer = render_to_buffer(60, 30, &mut app);
+    let output = render_to_string(60, 30, &mut app);
+    let (_, first) = find_text(&buffer, "let 名");
+    let (_, next) = find_text(&buffer, "let name");
+    let rows = app.total_content_lines() as u16 - 4;
+    assert_eq!(next - first, rows, "{}", output);
+    assert!(rows > 1, "{}", output);
+}
+
+/// Make the removed line of the first file long and full of CJK.
+fn files_with_long_wide_line(app: &mut App) {
+    app.files[0].hunks[0].lines[1].content = format!("let 名前 = \"{}\";\n", "名".repeat(40));
+    app.highlight_cache.clear();
+}
+
+#[test]
+fn test_tabs_expand_to_tab_stops() {
+    let mut files = make_test_files();
+    files[0].hunks[0].lines[2].content = "\tlet x = 42;\n".to_string();
+    files[0].hunks[0].lines[3].content = "ab\tprintln!();\n".to_string();
+    let mut app = App::new(files, false);
+    set_browsing(&mut app);
+
+    let buffer = render_to_buffer(100, 30, &mut app);
+    let (context_x, _) = find_text(&buffer, "use std::io;");
+    assert_eq!(find_text(&buffer, "let x = 42").0, context_x + 4);
+    assert_eq!(find_text(&buffer, "println!").0, context_x + 4);
+
+    app.tab_width = 8;
+    let buffer = render_to_buffer(100, 30, &mut app);
+    assert_eq!(find_text(&buffer, "let x = 42").0, context_x + 8);
+    assert_eq!(find_text(&buffer, "println!").0, context_x + 8);
+
+    // Drawn, a tab is an arrow padded to the stop
+    app.show_whitespace = true;
+    let output = render_to_string(100, 30, &mut app);
+    assert!(output.contains("+→       let·x·=·42;"), "{}", output);
+    assert!(output.contains("ab→     println!();"), "{}", output);
+}